@@ -0,0 +1,59 @@
+//! Compares setting a property for a large part of the population with
+//! `Context::update_people_by_id()` against a loop of
+//! `Context::set_person_property()`, as in a vaccination campaign.
+//!
+//! To compare against another revision, run
+//! `cargo bench --bench mass_update -- --save-baseline before` on it and
+//! then `cargo bench --bench mass_update -- --baseline before` on this one.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use ixa::{
+    define_person_property, define_person_property_with_default, Context, ContextPeopleExt,
+    PersonId, PersonPropertyChangeEvent,
+};
+
+const POPULATION: usize = 500_000;
+
+define_person_property!(Age, u8);
+define_person_property_with_default!(Vaccinated, bool, false);
+
+fn setup() -> (Context, Vec<PersonId>) {
+    let mut context = Context::new();
+    for i in 0..POPULATION {
+        context
+            .add_person((Age, u8::try_from(i % 100).unwrap()))
+            .unwrap();
+    }
+    context.index_property(Vaccinated);
+    context.subscribe_to_event(|_, _: PersonPropertyChangeEvent<Vaccinated>| {});
+    // Build the index before timing the updates.
+    let people = context.query_people((Vaccinated, false));
+    (context, people)
+}
+
+fn vaccinate_everyone(c: &mut Criterion) {
+    c.bench_function("vaccinate 500k people with set_person_property", |b| {
+        b.iter_batched(
+            setup,
+            |(mut context, people)| {
+                for person_id in people {
+                    context.set_person_property(person_id, Vaccinated, true);
+                }
+                context.execute();
+            },
+            BatchSize::LargeInput,
+        );
+    });
+    c.bench_function("vaccinate 500k people with update_people_by_id", |b| {
+        b.iter_batched(
+            setup,
+            |(mut context, people)| {
+                context.update_people_by_id(&people, Vaccinated, true);
+                context.execute();
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group!(benches, vaccinate_everyone);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+//! Benchmarks for the plan queue.
+//!
+//! To compare against another revision, run
+//! `cargo bench --bench plan_queue -- --save-baseline before` on it and then
+//! `cargo bench --bench plan_queue -- --baseline before` on this one.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use ixa::plan::Queue;
+use ixa::ExecutionPhase;
+
+const PLAN_COUNT: usize = 1_000_000;
+
+fn same_time_plans(c: &mut Criterion) {
+    c.bench_function("add and drain 1M plans at the same time", |b| {
+        b.iter_batched(
+            Queue::<usize, ExecutionPhase>::new,
+            |mut queue| {
+                for i in 0..PLAN_COUNT {
+                    queue.add_plan(1.0, i, ExecutionPhase::Normal);
+                }
+                while let Some(plan) = queue.get_next_plan() {
+                    black_box(plan.data);
+                }
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn distinct_time_plans(c: &mut Criterion) {
+    c.bench_function("add and drain 1M plans at distinct times", |b| {
+        b.iter_batched(
+            Queue::<usize, ExecutionPhase>::new,
+            |mut queue| {
+                for i in 0..PLAN_COUNT {
+                    // Interleave times so that plans aren't added in order
+                    let time = ((i * 7919) % PLAN_COUNT) as f64;
+                    queue.add_plan(time, i, ExecutionPhase::Normal);
+                }
+                while let Some(plan) = queue.get_next_plan() {
+                    black_box(plan.data);
+                }
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn interleaved_time_plans(c: &mut Criterion) {
+    c.bench_function("add and drain 1M plans interleaved over 10 times", |b| {
+        b.iter_batched(
+            Queue::<usize, ExecutionPhase>::new,
+            |mut queue| {
+                for i in 0..PLAN_COUNT {
+                    // Consecutive plans are never at the same time
+                    let time = (i % 10) as f64;
+                    queue.add_plan(time, i, ExecutionPhase::Normal);
+                }
+                while let Some(plan) = queue.get_next_plan() {
+                    black_box(plan.data);
+                }
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group!(
+    benches,
+    same_time_plans,
+    distinct_time_plans,
+    interleaved_time_plans
+);
+criterion_main!(benches);
@@ -0,0 +1,105 @@
+//! Benchmarks for a query-heavy workload.
+//!
+//! Besides the timings, this prints the number of heap allocations made per
+//! query, which is what the scratch arena (see `Context::with_scratch()`)
+//! is meant to reduce.
+//!
+//! To compare against another revision, run
+//! `cargo bench --bench queries -- --save-baseline before` on it and then
+//! `cargo bench --bench queries -- --baseline before` on this one.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ixa::{define_edge_type, define_person_property, define_rng};
+use ixa::{Context, ContextNetworkExt, ContextPeopleExt, ContextRandomExt, PersonId};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const POPULATION: usize = 10_000;
+const QUERIES: usize = 1_000;
+
+define_person_property!(Age, u8);
+define_person_property!(Infected, bool);
+define_edge_type!(Contact, ());
+define_rng!(BenchRng);
+
+fn setup() -> (Context, Vec<PersonId>) {
+    let mut context = Context::new();
+    context.init_random(42);
+    let people = (0..POPULATION)
+        .map(|i| {
+            let age = u8::try_from(i % 100).unwrap();
+            context
+                .add_person(((Age, age), (Infected, i % 7 == 0)))
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    context.index_property(Age);
+    for (i, person) in people.iter().enumerate() {
+        for j in 1..=10 {
+            let neighbor = people[(i + j * 97) % POPULATION];
+            let weight = f32::from(u8::try_from(j).unwrap());
+            context
+                .add_edge::<Contact>(*person, neighbor, weight, ())
+                .unwrap();
+        }
+    }
+    (context, people)
+}
+
+fn run_queries(context: &Context, people: &[PersonId]) {
+    for i in 0..QUERIES {
+        let age = u8::try_from(i % 100).unwrap();
+        let person = people[i % POPULATION];
+        black_box(context.query_people(((Age, age), (Infected, true))));
+        black_box(
+            context.get_matching_edges::<Contact>(person, |context, edge| {
+                context.get_person_property(edge.neighbor, Infected)
+            }),
+        );
+        black_box(
+            context
+                .select_random_edge::<Contact, _>(BenchRng, person)
+                .unwrap(),
+        );
+    }
+}
+
+fn report_allocations(context: &Context, people: &[PersonId]) {
+    // Warm up, so that one-time allocations aren't counted
+    run_queries(context, people);
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    run_queries(context, people);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "query workload: {allocations} allocations for {QUERIES} iterations ({} per iteration)",
+        allocations / QUERIES
+    );
+}
+
+fn query_workload(c: &mut Criterion) {
+    let (context, people) = setup();
+    report_allocations(&context, &people);
+    c.bench_function("1000 queries, edge filters and edge samples", |b| {
+        b.iter(|| run_queries(&context, &people));
+    });
+}
+
+criterion_group!(benches, query_workload);
+criterion_main!(benches);
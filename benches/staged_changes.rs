@@ -0,0 +1,105 @@
+//! Compares staging property changes with
+//! `Context::stage_property_change()` against adding a plan per person.
+//!
+//! Besides the timings, this prints the memory held by the pending changes
+//! in each case.
+//!
+//! To compare against another revision, run
+//! `cargo bench --bench staged_changes -- --save-baseline before` on it and
+//! then `cargo bench --bench staged_changes -- --baseline before` on this one.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use ixa::{define_person_property_with_default, Context, ContextPeopleExt, PersonId};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const POPULATION: usize = 100_000;
+// Recovery times are whole days, as they often are in models.
+const DAYS: usize = 30;
+
+define_person_property_with_default!(Recovered, bool, false);
+
+fn setup() -> (Context, Vec<PersonId>) {
+    let mut context = Context::new();
+    let people = (0..POPULATION)
+        .map(|_| context.add_person(()).unwrap())
+        .collect();
+    (context, people)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn recovery_time(i: usize) -> f64 {
+    ((i * 7919) % DAYS + 1) as f64
+}
+
+fn add_plans(context: &mut Context, people: &[PersonId]) {
+    for (i, &person_id) in people.iter().enumerate() {
+        context.add_plan(recovery_time(i), move |context| {
+            context.set_person_property(person_id, Recovered, true);
+        });
+    }
+}
+
+fn stage_changes(context: &mut Context, people: &[PersonId]) {
+    for (i, &person_id) in people.iter().enumerate() {
+        context.stage_property_change(person_id, Recovered, true, recovery_time(i));
+    }
+}
+
+fn report_memory(name: &str, schedule: fn(&mut Context, &[PersonId])) {
+    let (mut context, people) = setup();
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    schedule(&mut context, &people);
+    let bytes = ALLOCATED.load(Ordering::Relaxed).saturating_sub(before);
+    println!(
+        "{name}: {bytes} bytes pending for {POPULATION} people ({} per person)",
+        bytes / POPULATION
+    );
+}
+
+fn recoveries(c: &mut Criterion) {
+    report_memory("per-person plans", add_plans);
+    report_memory("staged changes", stage_changes);
+
+    c.bench_function("100k recoveries with per-person plans", |b| {
+        b.iter_batched(
+            setup,
+            |(mut context, people)| {
+                add_plans(&mut context, &people);
+                context.execute();
+            },
+            BatchSize::LargeInput,
+        );
+    });
+    c.bench_function("100k recoveries with staged changes", |b| {
+        b.iter_batched(
+            setup,
+            |(mut context, people)| {
+                stage_changes(&mut context, &people);
+                context.execute();
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group!(benches, recoveries);
+criterion_main!(benches);
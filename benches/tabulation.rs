@@ -0,0 +1,82 @@
+//! Compares a day of a model with a daily report of a property, in which
+//! some people's values change and then the property is tabulated, with a
+//! full index of the property against with just counts from
+//! `Context::index_property_counts()`.
+//!
+//! To compare against another revision, run
+//! `cargo bench --bench tabulation -- --save-baseline before` on it and
+//! then `cargo bench --bench tabulation -- --baseline before` on this one.
+use criterion::{criterion_group, criterion_main, Criterion};
+use ixa::{define_person_property, Context, ContextPeopleExt, PersonId};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::hint::black_box;
+
+const POPULATION: usize = 5_000_000;
+const CHANGES_PER_DAY: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DiseaseStatusValue {
+    S,
+    I,
+    R,
+}
+define_person_property!(DiseaseStatus, DiseaseStatusValue);
+
+fn setup(counts_only: bool) -> (Context, Vec<PersonId>) {
+    let mut context = Context::new();
+    if counts_only {
+        context.index_property_counts(DiseaseStatus);
+    } else {
+        context.index_property(DiseaseStatus);
+    }
+    let mut people = Vec::with_capacity(POPULATION);
+    for i in 0..POPULATION {
+        let status = match i % 3 {
+            0 => DiseaseStatusValue::S,
+            1 => DiseaseStatusValue::I,
+            _ => DiseaseStatusValue::R,
+        };
+        people.push(context.add_person((DiseaseStatus, status)).unwrap());
+    }
+    (context, people)
+}
+
+fn tabulate(context: &Context) -> usize {
+    let total = Cell::new(0);
+    context.tabulate_person_properties(&(DiseaseStatus,), |_, values, count| {
+        black_box(values);
+        total.set(total.get() + count);
+    });
+    total.get()
+}
+
+// Moves the next `CHANGES_PER_DAY` people on to the next status and
+// tabulates the statuses.
+fn run_day(context: &mut Context, people: &[PersonId], day: &mut usize) -> usize {
+    let start = (*day * CHANGES_PER_DAY) % POPULATION;
+    for &person_id in &people[start..start + CHANGES_PER_DAY] {
+        let status = match context.get_person_property(person_id, DiseaseStatus) {
+            DiseaseStatusValue::S => DiseaseStatusValue::I,
+            DiseaseStatusValue::I => DiseaseStatusValue::R,
+            DiseaseStatusValue::R => DiseaseStatusValue::S,
+        };
+        context.set_person_property(person_id, DiseaseStatus, status);
+    }
+    *day += 1;
+    tabulate(context)
+}
+
+fn daily_report(c: &mut Criterion) {
+    for (counts_only, name) in [(false, "full index"), (true, "counts")] {
+        let (mut context, people) = setup(counts_only);
+        let mut day = 0;
+        assert_eq!(run_day(&mut context, &people, &mut day), POPULATION);
+        c.bench_function(&format!("daily report of 5M people with {name}"), |b| {
+            b.iter(|| run_day(&mut context, &people, &mut day));
+        });
+    }
+}
+
+criterion_group!(benches, daily_report);
+criterion_main!(benches);
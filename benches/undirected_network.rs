@@ -0,0 +1,100 @@
+//! Compares a household network stored with an undirected edge type
+//! against the same network stored as pairs of directed edges with
+//! `Context::add_edge_bidi()`.
+//!
+//! Before timing, it prints the heap memory each takes for the 1M edges,
+//! counted with a global allocator that keeps track of the bytes in use.
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use ixa::{define_edge_type, Context, ContextNetworkExt, ContextPeopleExt, EdgeType, PersonId};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Households of four, with an edge between each pair of members, make
+// 1M edges.
+const HOUSEHOLD_SIZE: usize = 4;
+const HOUSEHOLDS: usize = 1_000_000 / 6;
+
+define_edge_type!(DirectedHousehold, ());
+define_edge_type!(UndirectedHousehold, (), undirected);
+
+fn setup() -> (Context, Vec<PersonId>) {
+    let mut context = Context::new();
+    let people = (0..HOUSEHOLDS * HOUSEHOLD_SIZE)
+        .map(|_| context.add_person(()).unwrap())
+        .collect();
+    (context, people)
+}
+
+// Adds an edge between each pair of members of each household with
+// `add_edge_bidi()`, which adds one edge for an undirected edge type and
+// two for a directed one.
+fn add_households<T: EdgeType + 'static>(context: &mut Context, people: &[PersonId]) {
+    for household in people.chunks(HOUSEHOLD_SIZE) {
+        for (i, &person) in household.iter().enumerate() {
+            for &other in &household[i + 1..] {
+                context
+                    .add_edge_bidi::<T>(person, other, 1.0, T::Value::default())
+                    .unwrap();
+            }
+        }
+    }
+}
+
+fn network_bytes<T: EdgeType + 'static>() -> usize {
+    let (mut context, people) = setup();
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    add_households::<T>(&mut context, &people);
+    ALLOCATED.load(Ordering::Relaxed) - before
+}
+
+fn household_network(c: &mut Criterion) {
+    let directed = network_bytes::<DirectedHousehold>();
+    let undirected = network_bytes::<UndirectedHousehold>();
+    println!(
+        "1M household edges: {} MB as directed pairs, {} MB undirected ({}%)",
+        directed / 1_000_000,
+        undirected / 1_000_000,
+        100 * undirected / directed
+    );
+
+    c.bench_function("add 1M household edges as directed pairs", |b| {
+        b.iter_batched(
+            setup,
+            |(mut context, people)| add_households::<DirectedHousehold>(&mut context, &people),
+            BatchSize::LargeInput,
+        );
+    });
+    c.bench_function("add 1M undirected household edges", |b| {
+        b.iter_batched(
+            setup,
+            |(mut context, people)| add_households::<UndirectedHousehold>(&mut context, &people),
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = household_network
+}
+criterion_main!(benches);
@@ -6,7 +6,7 @@ use ixa::report::ContextReportExt;
 use ixa::report::Report;
 use ixa::{create_report_trait, trace, PersonId};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::Path;
 
 #[derive(Serialize, Deserialize, Clone)]
 struct IncidenceReportItem {
@@ -31,7 +31,7 @@ fn handle_infection_status_change(context: &mut Context, event: InfectionStatusE
     });
 }
 
-pub fn init(context: &mut Context) -> Result<(), IxaError> {
+pub fn init(context: &mut Context, output_dir: &Path) -> Result<(), IxaError> {
     trace!("Initializing incidence_report");
 
     // In the configuration of report options below, we set `overwrite(true)`, which is not
@@ -40,7 +40,7 @@ pub fn init(context: &mut Context) -> Result<(), IxaError> {
     // examples.
     context
         .report_options()
-        .directory(PathBuf::from("./examples/basic-infection/"))
+        .directory(output_dir.to_path_buf())
         .overwrite(true);
     context.add_report::<IncidenceReportItem>("incidence")?;
     context.subscribe_to_event::<InfectionStatusEvent>(handle_infection_status_change);
@@ -4,8 +4,6 @@ use log::trace;
 use ixa::random::ContextRandomExt;
 use ixa::{define_rng, ContextPeopleExt, PersonId, PersonPropertyChangeEvent};
 
-use rand_distr::Exp;
-
 use crate::people::{InfectionStatus, InfectionStatusValue};
 use crate::INFECTION_DURATION;
 
@@ -18,7 +16,9 @@ define_rng!(InfectionRng);
 fn schedule_recovery(context: &mut Context, person_id: PersonId) {
     trace!("Scheduling recovery");
     let recovery_time = context.get_current_time()
-        + context.sample_distr(InfectionRng, Exp::new(1.0 / INFECTION_DURATION).unwrap());
+        + context
+            .sample_exponential(InfectionRng, 1.0 / INFECTION_DURATION)
+            .unwrap();
     context.add_plan(recovery_time, move |context| {
         context.set_person_property::<InfectionStatus>(
             person_id,
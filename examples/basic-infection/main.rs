@@ -2,6 +2,7 @@ use ixa::context::Context;
 use ixa::error::IxaError;
 use ixa::random::ContextRandomExt;
 use ixa::run_with_args;
+use std::path::Path;
 
 mod incidence_report;
 mod infection_manager;
@@ -14,13 +15,13 @@ static MAX_TIME: f64 = 303.0;
 static FOI: f64 = 0.1;
 static INFECTION_DURATION: f64 = 5.0;
 
-fn initialize(context: &mut Context) -> Result<(), IxaError> {
+fn initialize(context: &mut Context, output_dir: &Path) -> Result<(), IxaError> {
     context.init_random(SEED);
 
     people::init(context);
     transmission_manager::init(context);
     infection_manager::init(context);
-    incidence_report::init(context)?;
+    incidence_report::init(context, output_dir)?;
 
     context.add_plan(MAX_TIME, |context| {
         context.shutdown();
@@ -29,5 +30,24 @@ fn initialize(context: &mut Context) -> Result<(), IxaError> {
 }
 
 fn main() {
-    run_with_args(|ctx, _, _| initialize(ctx)).expect("failed to run the model");
+    run_with_args(|ctx, _, _| initialize(ctx, Path::new("./examples/basic-infection/")))
+        .expect("failed to run the model");
+}
+
+#[cfg(test)]
+#[path = "../../tests/golden/mod.rs"]
+mod golden;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn golden_output() {
+        golden::check_goldens(&golden::golden_dir(file!()), |output_dir| {
+            let mut context = Context::new();
+            initialize(&mut context, output_dir).unwrap();
+            context.execute();
+        });
+    }
 }
@@ -14,7 +14,7 @@ define_rng!(TransmissionRng);
 fn attempt_infection(context: &mut Context) {
     trace!("Attempting infection");
     let population_size: usize = context.get_current_population();
-    let person_to_infect: PersonId = context.sample_person(TransmissionRng, ()).unwrap(); //.sample_range(TransmissionRng, 0..population_size);
+    let person_to_infect: PersonId = context.sample_person(TransmissionRng, ()).unwrap().unwrap(); //.sample_range(TransmissionRng, 0..population_size);
 
     let person_status: InfectionStatusValue =
         context.get_person_property(person_to_infect, InfectionStatus);
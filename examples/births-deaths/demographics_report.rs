@@ -1,16 +1,19 @@
-use crate::population_manager::{Age, AgeGroupFoi, AgeGroupRisk, Alive};
+use crate::population_manager::{Age, AgeGroupFoi, AgeGroupRisk};
 use crate::Parameters;
 use ixa::error::IxaError;
 use ixa::{
     context::Context,
-    create_report_trait,
+    create_report_trait, define_data_plugin,
     global_properties::ContextGlobalPropertiesExt,
-    people::{ContextPeopleExt, PersonCreatedEvent, PersonPropertyChangeEvent},
+    people::{
+        ContextPeopleExt, PersonCreatedEvent, PersonId, PersonPropertyChangeEvent,
+        PersonRemovedEvent,
+    },
     report::{ContextReportExt, Report},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
-use std::path::PathBuf;
 
 #[derive(Serialize, Deserialize, Clone)]
 struct PersonReportItem {
@@ -24,9 +27,20 @@ struct PersonReportItem {
 
 create_report_trait!(PersonReportItem);
 
+// People's properties are gone by the time their removal is reported, so
+// keep track of everyone's age group.
+define_data_plugin!(
+    AgeGroupsPlugin,
+    HashMap<PersonId, AgeGroupRisk>,
+    HashMap::new()
+);
+
 fn handle_person_created(context: &mut Context, event: PersonCreatedEvent) {
     let person = event.person_id;
     let age_group_person = context.get_person_property(person, AgeGroupFoi);
+    context
+        .get_data_container_mut(AgeGroupsPlugin)
+        .insert(person, age_group_person);
     context.send_report(PersonReportItem {
         time: context.get_current_time(),
         person_id: format!("{person}"),
@@ -40,6 +54,9 @@ fn handle_person_created(context: &mut Context, event: PersonCreatedEvent) {
 fn handle_person_aging(context: &mut Context, event: PersonPropertyChangeEvent<Age>) {
     let person = event.person_id;
     let age_group_person = context.get_person_property(person, AgeGroupFoi);
+    context
+        .get_data_container_mut(AgeGroupsPlugin)
+        .insert(person, age_group_person);
     context.send_report(PersonReportItem {
         time: context.get_current_time(),
         person_id: format!("{person}"),
@@ -50,38 +67,38 @@ fn handle_person_aging(context: &mut Context, event: PersonPropertyChangeEvent<A
     });
 }
 
-fn handle_death_events(context: &mut Context, event: PersonPropertyChangeEvent<Alive>) {
-    if !event.current {
-        let person = event.person_id;
-        let age_group_person = context.get_person_property(person, AgeGroupFoi);
-        context.send_report(PersonReportItem {
-            time: context.get_current_time(),
-            person_id: format!("{person}"),
-            age_group: age_group_person,
-            property: "Alive".to_string(),
-            property_prev: format!("{:?}", event.previous),
-            property_current: format!("{:?}", event.current),
-        });
-    }
+fn handle_death_events(context: &mut Context, event: PersonRemovedEvent) {
+    let person = event.person_id;
+    let age_group_person = context
+        .get_data_container_mut(AgeGroupsPlugin)
+        .remove(&person)
+        .unwrap();
+    context.send_report(PersonReportItem {
+        time: context.get_current_time(),
+        person_id: format!("{person}"),
+        age_group: age_group_person,
+        property: "Removed".to_string(),
+        property_prev: String::new(),
+        property_current: String::new(),
+    });
 }
 
-pub fn init(context: &mut Context) -> Result<(), IxaError> {
+pub fn init(context: &mut Context, output_dir: &Path) -> Result<(), IxaError> {
     let parameters = context
         .get_global_property_value(Parameters)
         .unwrap()
         .clone();
 
-    let current_dir = Path::new(file!()).parent().unwrap();
     context
         .report_options()
-        .directory(PathBuf::from(current_dir))
+        .directory(output_dir.to_path_buf())
         .overwrite(true); // Not recommended for production. See `basic-infection/incidence-report`.
 
     context.add_report::<PersonReportItem>(&parameters.demographic_output_file)?;
     context.subscribe_to_event(|context, event: PersonCreatedEvent| {
         handle_person_created(context, event);
     });
-    context.subscribe_to_event(|context, event: PersonPropertyChangeEvent<Alive>| {
+    context.subscribe_to_event(|context, event: PersonRemovedEvent| {
         handle_death_events(context, event);
     });
 
@@ -5,7 +5,6 @@ use ixa::people::{ContextPeopleExt, PersonPropertyChangeEvent};
 use ixa::report::ContextReportExt;
 use ixa::{create_report_trait, report::Report};
 use std::path::Path;
-use std::path::PathBuf;
 
 use crate::population_manager::{
     Age, AgeGroupFoi, AgeGroupRisk, InfectionStatus, InfectionStatusValue,
@@ -41,15 +40,14 @@ fn handle_infection_status_change(
     });
 }
 
-pub fn init(context: &mut Context) -> Result<(), IxaError> {
+pub fn init(context: &mut Context, output_dir: &Path) -> Result<(), IxaError> {
     let parameters = context
         .get_global_property_value(Parameters)
         .unwrap()
         .clone();
-    let current_dir = Path::new(file!()).parent().unwrap();
     context
         .report_options()
-        .directory(PathBuf::from(current_dir))
+        .directory(output_dir.to_path_buf())
         .overwrite(true); // Not recommended for production. See `basic-infection/incidence-report`.;
 
     context.add_report::<IncidenceReportItem>(&parameters.output_file)?;
@@ -1,29 +1,18 @@
-use crate::population_manager::Alive;
 use crate::population_manager::InfectionStatus;
 use crate::population_manager::InfectionStatusValue;
 use crate::Parameters;
 use ixa::context::Context;
-use ixa::define_data_plugin;
 use ixa::define_rng;
 use ixa::global_properties::ContextGlobalPropertiesExt;
-use ixa::people::{ContextPeopleExt, PersonId, PersonPropertyChangeEvent};
-use ixa::plan::PlanId;
+use ixa::people::{ContextPeopleExt, PersonId, PersonPropertyChangeEvent, PersonRemovedEvent};
 use ixa::random::ContextRandomExt;
 use rand_distr::Exp;
-use std::collections::{HashMap, HashSet};
 
 define_rng!(InfectionRng);
-define_data_plugin!(
-    InfectionPlansPlugin,
-    InfectionPlansData,
-    InfectionPlansData {
-        plans_map: HashMap::<PersonId, HashSet::<PlanId>>::new(),
-    }
-);
 
-#[derive(Debug)]
-struct InfectionPlansData {
-    plans_map: HashMap<PersonId, HashSet<PlanId>>,
+// The key of a person's recovery plan, so it can be cancelled if they die
+fn recovery_key(person_id: PersonId) -> (PersonId, &'static str) {
+    (person_id, "recovery")
 }
 
 fn schedule_recovery(context: &mut Context, person_id: PersonId) {
@@ -35,37 +24,15 @@ fn schedule_recovery(context: &mut Context, person_id: PersonId) {
     let recovery_time = context.get_current_time()
         + context.sample_distr(InfectionRng, Exp::new(1.0 / infection_duration).unwrap());
 
-    if context.get_person_property(person_id, Alive) {
-        let plan_id = context.add_plan(recovery_time, move |context| {
+    if context.person_exists(person_id) {
+        context.add_plan_keyed(recovery_time, recovery_key(person_id), move |context| {
             context.set_person_property(person_id, InfectionStatus, InfectionStatusValue::R);
         });
-        let plans_data_container = context.get_data_container_mut(InfectionPlansPlugin);
-        plans_data_container
-            .plans_map
-            .entry(person_id)
-            .or_default()
-            .insert(plan_id);
     }
 }
 
-fn remove_recovery_plan_data(context: &mut Context, person_id: PersonId) {
-    let plans_data_container = context.get_data_container_mut(InfectionPlansPlugin);
-    plans_data_container.plans_map.remove(&person_id);
-}
-
 fn cancel_recovery_plans(context: &mut Context, person_id: PersonId) {
-    let plans_data_container = context.get_data_container_mut(InfectionPlansPlugin);
-    let plans_set = plans_data_container
-        .plans_map
-        .get(&person_id)
-        .unwrap_or(&HashSet::<PlanId>::new())
-        .clone();
-
-    for plan_id in plans_set {
-        context.cancel_plan(&plan_id);
-    }
-
-    remove_recovery_plan_data(context, person_id);
+    context.cancel_plans_matching(&recovery_key(person_id));
 }
 
 fn handle_infection_status_change(
@@ -75,16 +42,12 @@ fn handle_infection_status_change(
     if matches!(event.current, InfectionStatusValue::I) {
         schedule_recovery(context, event.person_id);
     }
-    if matches!(event.current, InfectionStatusValue::R) {
-        remove_recovery_plan_data(context, event.person_id);
-    }
 }
 
-fn handle_person_removal(context: &mut Context, event: PersonPropertyChangeEvent<Alive>) {
-    if !event.current {
-        cancel_recovery_plans(context, event.person_id);
-    }
+fn handle_person_removal(context: &mut Context, event: PersonRemovedEvent) {
+    cancel_recovery_plans(context, event.person_id);
 }
+
 pub fn init(context: &mut Context) {
     context.subscribe_to_event(
         move |context, event: PersonPropertyChangeEvent<InfectionStatus>| {
@@ -92,7 +55,7 @@ pub fn init(context: &mut Context) {
         },
     );
 
-    context.subscribe_to_event(move |context, event: PersonPropertyChangeEvent<Alive>| {
+    context.subscribe_to_event(move |context, event: PersonRemovedEvent| {
         handle_person_removal(context, event);
     });
 }
@@ -152,18 +115,51 @@ mod test {
 
             if index == 0 {
                 context.add_plan(1.1, move |context| {
-                    context.set_person_property(person, Alive, false);
+                    context.remove_person(person).unwrap();
                 });
             }
         }
 
         context.execute();
-        assert_eq!(population_size, context.get_current_population());
+        assert_eq!(population_size - 1, context.get_current_population());
         let recovered_size: usize = *context.get_data_container(RecoveryPlugin).unwrap();
 
         assert_eq!(recovered_size, population_size - 1);
     }
 
+    #[test]
+    fn test_recovery_cancelled_on_death() {
+        let p_values = ParametersValues {
+            population: 1,
+            max_time: 10.0,
+            seed: 42,
+            birth_rate: 0.0,
+            death_rate: 0.0,
+            foi_groups: Vec::<FoiAgeGroups>::new(),
+            infection_duration: 5.0,
+            output_file: ".".to_string(),
+            demographic_output_file: ".".to_string(),
+        };
+        let mut context = Context::new();
+        context
+            .set_global_property_value(Parameters, p_values)
+            .unwrap();
+        context.init_random(42);
+        init(&mut context);
+
+        let person = context.add_person((Age, 0)).unwrap();
+        context.add_plan(0.0, move |context| {
+            context.set_person_property(person, InfectionStatus, InfectionStatusValue::I);
+        });
+        context.add_plan(0.0, move |context| {
+            assert!(context.has_plan(&recovery_key(person)));
+            context.remove_person(person).unwrap();
+        });
+        context.execute();
+        assert!(!context.has_plan(&recovery_key(person)));
+        assert_eq!(context.get_current_time(), 0.0);
+    }
+
     #[test]
     fn test_cancel_null_plan() {
         let mut context = Context::new();
@@ -12,12 +12,12 @@ mod transmission_manager;
 
 use crate::parameters_loader::Parameters;
 
-fn initialize() -> Result<Context, IxaError> {
+/// Sets up the model with the parameters in `file_path`, writing reports
+/// to `output_dir`.
+fn initialize(file_path: &Path, output_dir: &Path) -> Result<Context, IxaError> {
     let mut context = Context::new();
-    let current_dir = Path::new(file!()).parent().unwrap();
-    let file_path = current_dir.join("input.json");
 
-    parameters_loader::init_parameters(&mut context, &file_path)?;
+    parameters_loader::init_parameters(&mut context, file_path)?;
 
     let parameters = context
         .get_global_property_value(Parameters)
@@ -25,8 +25,8 @@ fn initialize() -> Result<Context, IxaError> {
         .clone();
     context.init_random(parameters.seed);
 
-    demographics_report::init(&mut context)?;
-    incidence_report::init(&mut context)?;
+    demographics_report::init(&mut context, output_dir)?;
+    incidence_report::init(&mut context, output_dir)?;
 
     population_manager::init(&mut context);
     transmission_manager::init(&mut context);
@@ -40,7 +40,27 @@ fn initialize() -> Result<Context, IxaError> {
 }
 
 fn main() {
-    let mut context = initialize().expect("Could not initialize context.");
+    let current_dir = Path::new(file!()).parent().unwrap();
+    let mut context = initialize(&current_dir.join("input.json"), current_dir)
+        .expect("Could not initialize context.");
 
     context.execute();
 }
+
+#[cfg(test)]
+#[path = "../../tests/golden/mod.rs"]
+mod golden;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn golden_output() {
+        let golden_dir = golden::golden_dir(file!());
+        golden::check_goldens(&golden_dir, |output_dir| {
+            let mut context = initialize(&golden_dir.join("input.json"), output_dir).unwrap();
+            context.execute();
+        });
+    }
+}
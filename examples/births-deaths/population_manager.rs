@@ -40,7 +40,6 @@ define_person_property_with_default!(
     InfectionStatusValue::S
 );
 define_person_property!(Age, u8);
-define_person_property_with_default!(Alive, bool, true);
 define_derived_property!(AgeGroupFoi, AgeGroupRisk, [Age], |age| {
     if age <= 1 {
         AgeGroupRisk::NewBorn
@@ -52,7 +51,8 @@ define_derived_property!(AgeGroupFoi, AgeGroupRisk, [Age], |age| {
 });
 
 fn schedule_aging(context: &mut Context, person_id: PersonId) {
-    if context.get_person_property(person_id, Alive) {
+    // The person may have died since this plan was scheduled
+    if context.person_exists(person_id) {
         let prev_age = context.get_person_property(person_id, Age);
         context.set_person_property(person_id, Age, prev_age + 1);
         let next_age_event = context.get_current_time() + 365.0;
@@ -85,8 +85,8 @@ fn schedule_death(context: &mut Context) {
         .unwrap()
         .clone();
 
-    if let Ok(person) = context.sample_person(PeopleRng, (Alive, true)) {
-        context.set_person_property(person, Alive, false);
+    if let Ok(Some(person)) = context.sample_person(PeopleRng, ()) {
+        context.remove_person(person).unwrap();
 
         let next_death_event = context.get_current_time()
             + context.sample_distr(PeopleRng, Exp::new(parameters.death_rate).unwrap());
@@ -145,27 +145,36 @@ mod test {
             *person2_clone.borrow_mut() = Some(context.add_person((Age, 0)).unwrap());
         });
         context.add_plan(400.0, move |context| {
-            context.set_person_property(person1, Alive, false);
+            context.remove_person(person1).unwrap();
         });
         context.add_plan(390.0, |context| {
-            let pop = context.query_people_count((Alive, true));
-            assert_eq!(pop, 2);
+            assert_eq!(context.get_current_population(), 2);
         });
         context.add_plan(401.0, |context| {
-            let pop = context.query_people_count((Alive, true));
-            assert_eq!(pop, 1);
+            assert_eq!(context.get_current_population(), 1);
         });
         context.execute();
-        let population = context.get_current_population();
 
-        // Even if these people have died during simulation, we can still get their properties
-        let age_0 = context.get_person_property(person1, Age);
+        // People who have died no longer have properties
+        assert!(context.try_get_person_property(person1, Age).is_err());
         let age_1 = context.get_person_property((*person2).borrow().unwrap(), Age);
-        assert_eq!(age_0, 10);
         assert_eq!(age_1, 0);
+        assert_eq!(context.get_current_population(), 1);
+    }
 
-        // Ixa population contains all individuals ever created
-        assert_eq!(population, 2);
+    #[test]
+    fn test_aging_stops_after_death() {
+        let mut context = Context::new();
+        let person = context.add_person((Age, 10)).unwrap();
+        context.add_plan(365.0, move |context| {
+            schedule_aging(context, person);
+        });
+        context.add_plan(400.0, move |context| {
+            context.remove_person(person).unwrap();
+        });
+        // The aging plan scheduled for t=730 must skip the removed person
+        context.execute();
+        assert!(!context.person_exists(person));
     }
 
     #[test]
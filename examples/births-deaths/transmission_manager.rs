@@ -7,7 +7,7 @@ use ixa::random::ContextRandomExt;
 use crate::parameters_loader::Foi;
 use crate::population_manager::InfectionStatus;
 use crate::population_manager::InfectionStatusValue;
-use crate::population_manager::{AgeGroupFoi, AgeGroupRisk, Alive};
+use crate::population_manager::{AgeGroupFoi, AgeGroupRisk};
 use crate::Parameters;
 use rand_distr::Exp;
 
@@ -15,8 +15,7 @@ define_rng!(TransmissionRng);
 
 //Attempt infection for specific age group risk (meaning different forces of infection)
 fn attempt_infection(context: &mut Context, age_group: AgeGroupRisk) {
-    let population_size: usize =
-        context.query_people_count(((Alive, true), (AgeGroupFoi, age_group)));
+    let population_size: usize = context.query_people_count((AgeGroupFoi, age_group));
     let parameters = context
         .get_global_property_value(Parameters)
         .unwrap()
@@ -27,15 +26,21 @@ fn attempt_infection(context: &mut Context, age_group: AgeGroupRisk) {
         .get(&age_group)
         .unwrap();
     if population_size > 0 {
-        let person_to_infect = context
-            .sample_person(TransmissionRng, ((Alive, true), (AgeGroupFoi, age_group)))
-            .unwrap();
+        // There may be no one left in the age group.
+        if let Some(person_to_infect) = context
+            .sample_person(TransmissionRng, (AgeGroupFoi, age_group))
+            .unwrap()
+        {
+            let person_status: InfectionStatusValue =
+                context.get_person_property(person_to_infect, InfectionStatus);
 
-        let person_status: InfectionStatusValue =
-            context.get_person_property(person_to_infect, InfectionStatus);
-
-        if person_status == InfectionStatusValue::S {
-            context.set_person_property(person_to_infect, InfectionStatus, InfectionStatusValue::I);
+            if person_status == InfectionStatusValue::S {
+                context.set_person_property(
+                    person_to_infect,
+                    InfectionStatus,
+                    InfectionStatusValue::I,
+                );
+            }
         }
         #[allow(clippy::cast_precision_loss)]
         let next_attempt_time = context.get_current_time()
@@ -51,8 +56,13 @@ fn attempt_infection(context: &mut Context, age_group: AgeGroupRisk) {
 }
 
 pub fn init(context: &mut Context) {
-    let foi_age_groups = context.get_global_property_value(Foi).unwrap().clone();
-    for (age_group, _) in foi_age_groups {
+    // Schedule the groups in the order of the parameters file, not the
+    // order of the Foi map, so that runs are reproducible.
+    let parameters = context
+        .get_global_property_value(Parameters)
+        .unwrap()
+        .clone();
+    for age_group in parameters.foi_groups.iter().map(|group| group.group_name) {
         context.add_plan(0.0, move |context| {
             attempt_infection(context, age_group);
         });
@@ -1,9 +1,10 @@
 use std::path::Path;
 
-use crate::vaccine::{ContextVaccineExt, VaccineEfficacy, VaccineType};
+use crate::vaccine::{ContextVaccineExt, VaccineEfficacy, VaccineType, VaccineTypeValue};
 use ixa::context::Context;
 use ixa::define_person_property;
-use ixa::{ContextPeopleExt, PersonId};
+use ixa::ContextPeopleExt;
+use ordered_float::OrderedFloat;
 use serde::Deserialize;
 
 #[derive(Deserialize, Copy, Clone, PartialEq, Eq, Debug, Hash)]
@@ -12,7 +13,7 @@ pub enum RiskCategoryValue {
     Low,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Copy, Clone, Debug)]
 struct PeopleRecord {
     age: u8,
     risk_category: RiskCategoryValue,
@@ -21,27 +22,28 @@ struct PeopleRecord {
 define_person_property!(Age, u8);
 define_person_property!(RiskCategory, RiskCategoryValue);
 
-fn create_person_from_record(context: &mut Context, record: &PeopleRecord) -> PersonId {
+type PersonProperties = (
+    (Age, u8),
+    (RiskCategory, RiskCategoryValue),
+    (VaccineType, VaccineTypeValue),
+    (VaccineEfficacy, OrderedFloat<f64>),
+);
+
+fn person_from_record(context: &Context, record: PeopleRecord) -> PersonProperties {
     let (t, e) = context.get_vaccine_props(record.risk_category);
-    context
-        .add_person((
-            (Age, record.age),
-            (RiskCategory, record.risk_category),
-            (VaccineType, t),
-            (VaccineEfficacy, e),
-        ))
-        .unwrap()
+    (
+        (Age, record.age),
+        (RiskCategory, record.risk_category),
+        (VaccineType, t),
+        (VaccineEfficacy, e),
+    )
 }
 
 pub fn init(context: &mut Context) {
-    // Load csv and deserialize records
     let current_dir = Path::new(file!()).parent().unwrap();
-    let mut reader = csv::Reader::from_path(current_dir.join("people.csv")).unwrap();
-
-    for result in reader.deserialize() {
-        let record: PeopleRecord = result.expect("Failed to parse record");
-        create_person_from_record(context, &record);
-    }
+    context
+        .load_people_from_csv(&current_dir.join("people.csv"), person_from_record)
+        .expect("Failed to load people");
 }
 
 #[cfg(test)]
@@ -76,7 +78,7 @@ mod tests {
         // Define expected computed values for each person
         let expected_computed = vec![
             (20, RiskCategoryValue::Low, VaccineTypeValue::B, 0.8, 1),
-            (80, RiskCategoryValue::High, VaccineTypeValue::A, 0.9, 2),
+            (80, RiskCategoryValue::High, VaccineTypeValue::A, 0.9, 4),
         ];
 
         let mut context = Context::new();
@@ -124,7 +126,8 @@ mod tests {
 
         // Create people from records based on expected values
         for &(age, risk_category, _, _, _) in expected_computed.iter() {
-            create_person_from_record(&mut context, &PeopleRecord { age, risk_category });
+            let properties = person_from_record(&context, PeopleRecord { age, risk_category });
+            context.add_person(properties).unwrap();
         }
 
         // Execute the context
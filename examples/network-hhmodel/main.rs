@@ -7,6 +7,8 @@ mod network;
 mod parameters;
 mod seir;
 
+use parameters::Parameters;
+
 define_rng!(MainRng);
 
 fn main() {
@@ -24,7 +26,13 @@ fn main() {
     // Load network
     network::init(&mut context, &people);
 
-    let to_infect = vec![context.sample_person(MainRng, ()).unwrap()];
+    let initial_infections = context
+        .get_global_property_value(Parameters)
+        .unwrap()
+        .initial_infections;
+    let to_infect = context
+        .sample_people(MainRng, (), initial_infections)
+        .unwrap();
     seir::init(&mut context, &to_infect);
 
     context.execute();
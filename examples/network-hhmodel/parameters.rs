@@ -12,5 +12,6 @@ pub struct ParametersValues {
     pub shape: f64,
     pub infection_duration: f64,
     pub between_hh_transmission_reduction: f64,
+    pub initial_infections: usize,
 }
 define_global_property!(Parameters, ParametersValues);
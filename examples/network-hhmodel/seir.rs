@@ -135,12 +135,10 @@ pub fn init(context: &mut Context, initial_infections: &Vec<PersonId>) {
     );
 
     context.subscribe_to_event(
-        move |context, event: PersonPropertyChangeEvent<DiseaseStatus>| {
-            match event.current {
-                DiseaseStatusValue::E => schedule_infection(context, event.person_id),
-                DiseaseStatusValue::I => schedule_recovery(context, event.person_id),
-                _ => (),
-            };
+        move |context, event: PersonPropertyChangeEvent<DiseaseStatus>| match event.current {
+            DiseaseStatusValue::E => schedule_infection(context, event.person_id),
+            DiseaseStatusValue::I => schedule_recovery(context, event.person_id),
+            _ => (),
         },
     );
 
@@ -175,6 +173,7 @@ mod tests {
             shape: 15.0,
             infection_duration: 5.0,
             between_hh_transmission_reduction: 1.0,
+            initial_infections: 1,
         };
         context
             .set_global_property_value(Parameters, parameters)
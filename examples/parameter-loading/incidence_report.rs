@@ -32,14 +32,14 @@ fn handle_infection_status_change(
     });
 }
 
-pub fn init(context: &mut Context) -> Result<(), IxaError> {
+pub fn init(context: &mut Context, output_dir: PathBuf) -> Result<(), IxaError> {
     let parameters = context
         .get_global_property_value(Parameters)
         .unwrap()
         .clone();
     context
         .report_options()
-        .directory(PathBuf::from(parameters.output_dir))
+        .directory(output_dir)
         .overwrite(true); // Not recommended for production. See `basic-infection/incidence-report`.;
     context.add_report::<IncidenceReportItem>(&parameters.output_file)?;
     context.subscribe_to_event(
@@ -5,7 +5,7 @@ use ixa::{
     context::Context, define_person_property_with_default,
     global_properties::ContextGlobalPropertiesExt,
 };
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 mod incidence_report;
 mod infection_manager;
@@ -28,13 +28,12 @@ define_person_property_with_default!(
     InfectionStatusValue::S
 );
 
-fn initialize() -> Result<Context, IxaError> {
+/// Sets up the model with the parameters in `file_path`. Reports go to
+/// `output_dir` if it's given and to the `output_dir` parameter otherwise.
+fn initialize(file_path: &Path, output_dir: Option<&Path>) -> Result<Context, IxaError> {
     let mut context = Context::new();
-    let file_path = Path::new("examples")
-        .join("parameter-loading")
-        .join("input.json");
 
-    parameters_loader::init_parameters(&mut context, &file_path)?;
+    parameters_loader::init_parameters(&mut context, file_path)?;
 
     let parameters = context
         .get_global_property_value(Parameters)
@@ -48,7 +47,9 @@ fn initialize() -> Result<Context, IxaError> {
 
     transmission_manager::init(&mut context);
     infection_manager::init(&mut context);
-    incidence_report::init(&mut context)?;
+    let output_dir =
+        output_dir.map_or_else(|| PathBuf::from(&parameters.output_dir), Path::to_path_buf);
+    incidence_report::init(&mut context, output_dir)?;
 
     context.add_plan(parameters.max_time, |context| {
         context.shutdown();
@@ -59,7 +60,28 @@ fn initialize() -> Result<Context, IxaError> {
 }
 
 fn main() {
-    let mut context = initialize().expect("Could not initialize context.");
+    let file_path = Path::new("examples")
+        .join("parameter-loading")
+        .join("input.json");
+    let mut context = initialize(&file_path, None).expect("Could not initialize context.");
 
     context.execute();
 }
+
+#[cfg(test)]
+#[path = "../../tests/golden/mod.rs"]
+mod golden;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn golden_output() {
+        let golden_dir = golden::golden_dir(file!());
+        golden::check_goldens(&golden_dir, |output_dir| {
+            let mut context = initialize(&golden_dir.join("input.json"), Some(output_dir)).unwrap();
+            context.execute();
+        });
+    }
+}
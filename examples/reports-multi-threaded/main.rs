@@ -1,9 +1,9 @@
 use ixa::context::Context;
 use ixa::report::ContextReportExt;
-use ixa::{create_report_trait, report::Report};
+use ixa::runner::{run_scenarios, ScenarioOptions};
+use ixa::{create_report_trait, report::Report, IxaError};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::thread;
 
 #[allow(dead_code)]
 #[derive(Serialize, Deserialize, Clone)]
@@ -15,53 +15,37 @@ struct Incidence {
 
 create_report_trait!(Incidence);
 
-#[allow(unexpected_cfgs)]
-fn main() {
-    let scenarios = vec!["Illinois", "Wisconsin", "Arizona", "California"];
-    let mut handles = vec![];
+fn setup(context: &mut Context, scenario: &&str) -> Result<(), IxaError> {
+    context.add_report::<Incidence>("incidence")?;
+    println!("Scenario: {scenario}");
 
-    for scenario in scenarios {
+    for person in ["1", "2", "3"] {
         let scenario = scenario.to_string();
-        let handle = thread::spawn(move || {
-            let mut context = Context::new();
-
-            context
-                .report_options()
-                .directory(PathBuf::from("./examples/reports-multi-threaded"))
-                .file_prefix(format!("{scenario}_"))
-                .overwrite(true); // Not recommended for production. See `basic-infection/incidence-report`.;
-            context
-                .add_report::<Incidence>("incidence")
-                .expect("Error adding report");
-            println!("Scenario: {scenario}");
-
-            let people = vec!["1", "2", "3"];
-            for person in people {
-                let person = person.to_string();
-                let scenario = scenario.clone();
-                context.add_plan(1.0, {
-                    move |context| {
-                        context.send_report(Incidence {
-                            scenario: scenario.to_string(),
-                            person_id: person.clone(),
-                            t: context.get_current_time(),
-                        });
-                        println!(
-                            "Scenario: {}, Person {} was infected at time {}.",
-                            scenario,
-                            person,
-                            context.get_current_time()
-                        );
-                    }
-                });
-            }
-
-            context.execute();
+        context.add_plan(1.0, move |context| {
+            context.send_report(Incidence {
+                scenario: scenario.clone(),
+                person_id: person.to_string(),
+                t: context.get_current_time(),
+            });
+            println!(
+                "Scenario: {}, Person {} was infected at time {}.",
+                scenario,
+                person,
+                context.get_current_time()
+            );
         });
-        handles.push(handle);
     }
+    Ok(())
+}
 
-    for handle in handles {
-        handle.join().unwrap();
+fn main() {
+    let options = ScenarioOptions {
+        output_dir: PathBuf::from("./examples/reports-multi-threaded"),
+        force_overwrite: true, // Not recommended for production. See `basic-infection/incidence-report`.
+        ..Default::default()
+    };
+    let scenarios = ["Illinois", "Wisconsin", "Arizona", "California"];
+    for result in run_scenarios(&scenarios, setup, &options) {
+        result.expect("Error running scenario");
     }
 }
@@ -1,6 +1,6 @@
 use ixa::context::Context;
 use ixa::error::IxaError;
-use ixa::report::ContextReportExt;
+use ixa::report::{ContextReportExt, ReportFormat};
 use ixa::{create_report_trait, report::Report};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -28,7 +28,8 @@ fn initialize() -> Result<Context, IxaError> {
         .report_options()
         .file_prefix("Reports_".to_string())
         .directory(PathBuf::from("./"))
-        .overwrite(true); // Not recommended for production. See `basic-infection/incidence-report`.;
+        .overwrite(true) // Not recommended for production. See `basic-infection/incidence-report`.;
+        .format(ReportFormat::Csv); // Or `ReportFormat::JsonLines` for `.jsonl` files
     context.add_report::<Incidence>("incidence")?;
     context.add_report::<Death>("death")?;
     Ok(context)
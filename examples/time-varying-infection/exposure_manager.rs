@@ -137,19 +137,23 @@ mod test {
             .unwrap()
             .clone();
         context.init_random(parameters.seed);
-        // empirical mean
-        let mut sum = 0.0;
+        // empirical mean and its standard error
         let n = 1000;
-        for _ in 0..n {
-            sum += inverse_sampling_infection(&mut context);
-        }
-        let mean = sum / n as f64;
+        let samples: Vec<f64> = (0..n)
+            .map(|_| inverse_sampling_infection(&mut context))
+            .collect();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        let standard_error = (variance / n as f64).sqrt();
 
         // Now calculate theoretical mean.
         // Use the fact that integral from 0 to infinity of survival fcn is the mean.
         let hazard_fcn = func!(move |t| foi_t(t, parameters.foi, parameters.foi_sin_shift));
         let survival_fcn = func!(move |t| f64::exp(-integrate(&hazard_fcn, 0.0, t)));
         let theoretical_mean = integrate(&survival_fcn, 0.0, 10000.0); // large enough upper bound
-        assert!((mean - theoretical_mean).abs() < 0.1);
+
+        // The difference is more than three standard errors in fewer than
+        // 0.3% of seeds.
+        assert!((mean - theoretical_mean).abs() < 3.0 * standard_error);
     }
 }
@@ -0,0 +1,396 @@
+//! Timing the benchmarks and comparing the results against baselines.
+//!
+//! Each benchmark does a fixed number of operations per sample, so every
+//! run does the same work. A benchmark is timed `samples` times after one
+//! sample to warm up, and the median time per operation is used, which
+//! isn't thrown off by a sample or two that were interrupted.
+//!
+//! Baselines are kept along with the time of a fixed calibration workload
+//! on the machine that recorded them. When checking, the baselines are
+//! scaled by how much faster or slower the calibration workload runs on
+//! the current machine, so that a CI runner that is a little slower than
+//! the one the baselines came from doesn't fail every benchmark.
+use ixa::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::time::Instant;
+
+/// The threshold given to new benchmarks when blessing, as a fraction of
+/// the baseline time.
+pub const DEFAULT_THRESHOLD: f64 = 0.2;
+
+/// Samples whose spread, the difference between the slowest and fastest
+/// over the median, is larger than this get a warning.
+const NOISY_SPREAD: f64 = 0.1;
+
+const CALIBRATION_ITERATIONS: usize = 10_000_000;
+
+/// A benchmark of one operation of the simulation kernel.
+pub struct Benchmark {
+    pub name: &'static str,
+    /// The number of operations in each sample
+    pub iterations: usize,
+    /// Sets up a context and returns the operations to time, which return
+    /// the context so that dropping it isn't timed.
+    pub setup: fn(usize) -> Box<dyn FnOnce() -> Context>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    pub name: String,
+    pub iterations: usize,
+    /// The median over the samples of the time per operation
+    pub median_ns: f64,
+    pub min_ns: f64,
+    pub max_ns: f64,
+}
+
+impl Measurement {
+    #[must_use]
+    pub fn spread(&self) -> f64 {
+        (self.max_ns - self.min_ns) / self.median_ns
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Environment {
+    pub os: String,
+    pub arch: String,
+    pub cpus: usize,
+    pub optimized: bool,
+    /// Conditions that make the measurements less reliable
+    pub warnings: Vec<String>,
+}
+
+/// The output of a run, written as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Results {
+    pub environment: Environment,
+    /// The time per operation of the calibration workload
+    pub calibration_ns: f64,
+    pub measurements: Vec<Measurement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub median_ns: f64,
+    /// How much slower than `median_ns` a measurement can be before it's
+    /// a regression, as a fraction, e.g., 0.2 for 20%.
+    pub threshold: f64,
+}
+
+/// The committed baselines file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Baselines {
+    pub calibration_ns: f64,
+    pub benchmarks: BTreeMap<String, Baseline>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub name: String,
+    /// The baseline time, scaled to the speed of this machine
+    pub expected_ns: f64,
+    pub measured_ns: f64,
+    pub threshold: f64,
+}
+
+impl Comparison {
+    /// The change from the baseline as a fraction, positive when slower
+    #[must_use]
+    pub fn change(&self) -> f64 {
+        self.measured_ns / self.expected_ns - 1.0
+    }
+
+    #[must_use]
+    pub fn regressed(&self) -> bool {
+        self.change() > self.threshold
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn time_sample(benchmark: &Benchmark) -> f64 {
+    let operations = (benchmark.setup)(benchmark.iterations);
+    let start = Instant::now();
+    let context = operations();
+    let elapsed = start.elapsed();
+    drop(context);
+    elapsed.as_nanos() as f64 / benchmark.iterations as f64
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        f64::midpoint(values[middle - 1], values[middle])
+    } else {
+        values[middle]
+    }
+}
+
+/// Time `benchmark` over `samples` samples, after one to warm up.
+///
+/// # Panics
+/// Panics if `samples` is zero.
+#[must_use]
+pub fn measure(benchmark: &Benchmark, samples: usize) -> Measurement {
+    assert!(samples > 0, "Need at least one sample");
+    time_sample(benchmark);
+    let mut times: Vec<f64> = (0..samples).map(|_| time_sample(benchmark)).collect();
+    let median_ns = median(&mut times);
+    Measurement {
+        name: benchmark.name.to_string(),
+        iterations: benchmark.iterations,
+        median_ns,
+        min_ns: times[0],
+        max_ns: times[times.len() - 1],
+    }
+}
+
+// A fixed workload that doesn't depend on ixa, for comparing the speed of
+// machines.
+#[allow(clippy::cast_precision_loss)]
+fn calibrate(samples: usize) -> f64 {
+    let mut times: Vec<f64> = (0..=samples)
+        .map(|_| {
+            let start = Instant::now();
+            let mut state = black_box(0x2545_f491_4f6c_dd1d_u64);
+            let mut table = [0_u64; 1024];
+            for i in 0..CALIBRATION_ITERATIONS {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                table[(state % 1024) as usize] += i as u64;
+            }
+            black_box(table);
+            start.elapsed().as_nanos() as f64 / CALIBRATION_ITERATIONS as f64
+        })
+        .collect();
+    // Leave out the warm-up sample.
+    median(&mut times[1..])
+}
+
+fn environment() -> Environment {
+    let mut warnings = Vec::new();
+    let optimized = !cfg!(debug_assertions);
+    if !optimized {
+        warnings.push(String::from(
+            "built without optimizations; run with `cargo run --release`",
+        ));
+    }
+    if let Ok(governor) =
+        std::fs::read_to_string("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor")
+    {
+        let governor = governor.trim();
+        if governor != "performance" {
+            warnings.push(format!(
+                "CPU frequency governor is `{governor}` rather than `performance`"
+            ));
+        }
+    }
+    let cpus = std::thread::available_parallelism().map_or(1, std::num::NonZero::get);
+    if let Some(load) = std::fs::read_to_string("/proc/loadavg")
+        .ok()
+        .and_then(|loadavg| loadavg.split_whitespace().next()?.parse::<f64>().ok())
+    {
+        if load > 1.0 {
+            warnings.push(format!(
+                "load average is {load:.2}; other processes may slow the benchmarks"
+            ));
+        }
+    }
+    Environment {
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        cpus,
+        optimized,
+        warnings,
+    }
+}
+
+/// Measure the calibration workload and each of `benchmarks`, adding a
+/// warning for each noisy benchmark.
+#[must_use]
+pub fn run(benchmarks: &[Benchmark], samples: usize) -> Results {
+    let mut environment = environment();
+    let calibration_ns = calibrate(samples);
+    let measurements: Vec<Measurement> = benchmarks
+        .iter()
+        .map(|benchmark| measure(benchmark, samples))
+        .collect();
+    for measurement in &measurements {
+        if measurement.spread() > NOISY_SPREAD {
+            environment.warnings.push(format!(
+                "{} is noisy: samples spread over {:.0}% of the median",
+                measurement.name,
+                100.0 * measurement.spread()
+            ));
+        }
+    }
+    Results {
+        environment,
+        calibration_ns,
+        measurements,
+    }
+}
+
+/// Compare `results` against `baselines`, returning the comparisons and
+/// the names of the benchmarks that have no baseline.
+#[must_use]
+pub fn compare(baselines: &Baselines, results: &Results) -> (Vec<Comparison>, Vec<String>) {
+    let speed = results.calibration_ns / baselines.calibration_ns;
+    let mut comparisons = Vec::new();
+    let mut missing = Vec::new();
+    for measurement in &results.measurements {
+        match baselines.benchmarks.get(&measurement.name) {
+            Some(baseline) => comparisons.push(Comparison {
+                name: measurement.name.clone(),
+                expected_ns: baseline.median_ns * speed,
+                measured_ns: measurement.median_ns,
+                threshold: baseline.threshold,
+            }),
+            None => missing.push(measurement.name.clone()),
+        }
+    }
+    (comparisons, missing)
+}
+
+/// New baselines from `results`, keeping the thresholds of the benchmarks
+/// in `previous`. Benchmarks that weren't measured are dropped.
+#[must_use]
+pub fn bless(previous: Option<&Baselines>, results: &Results) -> Baselines {
+    let benchmarks = results
+        .measurements
+        .iter()
+        .map(|measurement| {
+            let threshold = previous
+                .and_then(|previous| previous.benchmarks.get(&measurement.name))
+                .map_or(DEFAULT_THRESHOLD, |baseline| baseline.threshold);
+            (
+                measurement.name.clone(),
+                Baseline {
+                    median_ns: measurement.median_ns,
+                    threshold,
+                },
+            )
+        })
+        .collect();
+    Baselines {
+        calibration_ns: results.calibration_ns,
+        benchmarks,
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::{bless, compare, measure, median, Baseline, Baselines, Benchmark, Results};
+    use ixa::Context;
+
+    fn results(calibration_ns: f64, measurements: &[(&str, f64)]) -> Results {
+        let json = serde_json::json!({
+            "environment": {
+                "os": "linux", "arch": "x86_64", "cpus": 4, "optimized": true, "warnings": []
+            },
+            "calibration_ns": calibration_ns,
+            "measurements": measurements.iter().map(|(name, ns)| serde_json::json!({
+                "name": name, "iterations": 10, "median_ns": ns, "min_ns": ns, "max_ns": ns
+            })).collect::<Vec<_>>(),
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn baselines(calibration_ns: f64, benchmarks: &[(&str, f64, f64)]) -> Baselines {
+        Baselines {
+            calibration_ns,
+            benchmarks: benchmarks
+                .iter()
+                .map(|&(name, median_ns, threshold)| {
+                    (
+                        name.to_string(),
+                        Baseline {
+                            median_ns,
+                            threshold,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn median_of_samples() {
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&mut [4.0, 1.0, 3.0, 2.0]), 2.5);
+        assert_eq!(median(&mut [100.0, 1.0, 1.1, 0.9, 1.0]), 1.0);
+    }
+
+    #[test]
+    fn measures_fixed_iterations() {
+        let benchmark = Benchmark {
+            name: "test/plans",
+            iterations: 100,
+            setup: |iterations| {
+                Box::new(move || {
+                    let mut context = Context::new();
+                    for i in 0..iterations {
+                        context.add_plan(f64::from(u32::try_from(i).unwrap()), |_| {});
+                    }
+                    context.execute();
+                    context
+                })
+            },
+        };
+        let measurement = measure(&benchmark, 3);
+        assert_eq!(measurement.name, "test/plans");
+        assert_eq!(measurement.iterations, 100);
+        assert!(measurement.min_ns <= measurement.median_ns);
+        assert!(measurement.median_ns <= measurement.max_ns);
+    }
+
+    #[test]
+    fn finds_regressions() {
+        let baselines = baselines(
+            1.0,
+            &[("a", 100.0, 0.2), ("b", 100.0, 0.2), ("c", 10.0, 0.5)],
+        );
+        let results = results(1.0, &[("a", 110.0), ("b", 130.0), ("c", 14.0), ("d", 1.0)]);
+        let (comparisons, missing) = compare(&baselines, &results);
+        let regressed: Vec<&str> = comparisons
+            .iter()
+            .filter(|comparison| comparison.regressed())
+            .map(|comparison| comparison.name.as_str())
+            .collect();
+        assert_eq!(regressed, ["b"]);
+        assert_eq!(missing, ["d"]);
+        assert!((comparisons[1].change() - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn scales_by_machine_speed() {
+        // A machine half as fast takes twice as long without a regression.
+        let baselines = baselines(1.0, &[("a", 100.0, 0.2)]);
+        let (comparisons, _) = compare(&baselines, &results(2.0, &[("a", 210.0)]));
+        assert_eq!(comparisons[0].expected_ns, 200.0);
+        assert!(!comparisons[0].regressed());
+        let (comparisons, _) = compare(&baselines, &results(2.0, &[("a", 250.0)]));
+        assert!(comparisons[0].regressed());
+    }
+
+    #[test]
+    fn bless_keeps_thresholds() {
+        let previous = baselines(1.0, &[("a", 100.0, 0.5), ("old", 1.0, 0.2)]);
+        let blessed = bless(Some(&previous), &results(1.5, &[("a", 90.0), ("new", 5.0)]));
+        assert_eq!(
+            blessed,
+            baselines(
+                1.5,
+                &[("a", 90.0, 0.5), ("new", 5.0, super::DEFAULT_THRESHOLD)]
+            )
+        );
+        // The baselines survive a round trip through JSON.
+        let json = serde_json::to_string(&blessed).unwrap();
+        assert_eq!(serde_json::from_str::<Baselines>(&json).unwrap(), blessed);
+    }
+}
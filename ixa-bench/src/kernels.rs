@@ -0,0 +1,255 @@
+//! The benchmarks, each of one operation that models spend most of their
+//! time in. Populations are built before timing starts, so only the
+//! operation itself is timed.
+use crate::harness::Benchmark;
+use ixa::{
+    define_edge_type, define_person_property, define_person_property_with_default, define_rng,
+    Context, ContextNetworkExt, ContextPeopleExt, ContextRandomExt, IxaEvent, PersonId,
+};
+use std::hint::black_box;
+
+const POPULATION: usize = 10_000;
+
+define_person_property!(Age, u8);
+define_person_property!(Region, u8);
+define_person_property_with_default!(Vaccinated, bool, false);
+define_person_property_with_default!(Contacts, u32, 0);
+define_edge_type!(Contact, ());
+define_rng!(BenchRng);
+
+#[derive(Copy, Clone)]
+struct Tick;
+impl IxaEvent for Tick {}
+
+/// All the benchmarks, in the order they're run.
+#[must_use]
+pub fn benchmarks() -> Vec<Benchmark> {
+    vec![
+        Benchmark {
+            name: "plans/schedule_and_execute",
+            iterations: 200_000,
+            setup: schedule_and_execute,
+        },
+        Benchmark {
+            name: "people/add_person",
+            iterations: 100_000,
+            setup: add_person,
+        },
+        Benchmark {
+            name: "properties/get",
+            iterations: 1_000_000,
+            setup: get_property,
+        },
+        Benchmark {
+            name: "properties/set",
+            iterations: 200_000,
+            setup: |iterations| set_property(iterations, false),
+        },
+        Benchmark {
+            name: "properties/set_indexed",
+            iterations: 200_000,
+            setup: |iterations| set_property(iterations, true),
+        },
+        Benchmark {
+            name: "queries/one_indexed",
+            iterations: 20_000,
+            setup: |iterations| query(iterations, 1),
+        },
+        Benchmark {
+            name: "queries/two_indexed",
+            iterations: 20_000,
+            setup: |iterations| query(iterations, 2),
+        },
+        Benchmark {
+            name: "queries/three_indexed",
+            iterations: 20_000,
+            setup: |iterations| query(iterations, 3),
+        },
+        Benchmark {
+            name: "events/emit_0_subscribers",
+            iterations: 500_000,
+            setup: |iterations| emit_event(iterations, 0),
+        },
+        Benchmark {
+            name: "events/emit_1_subscriber",
+            iterations: 500_000,
+            setup: |iterations| emit_event(iterations, 1),
+        },
+        Benchmark {
+            name: "events/emit_5_subscribers",
+            iterations: 200_000,
+            setup: |iterations| emit_event(iterations, 5),
+        },
+        Benchmark {
+            name: "network/add_edge",
+            iterations: 200_000,
+            setup: add_edge,
+        },
+        Benchmark {
+            name: "network/select_random_edge",
+            iterations: 500_000,
+            setup: select_random_edge,
+        },
+    ]
+}
+
+fn population() -> (Context, Vec<PersonId>) {
+    let mut context = Context::new();
+    context.init_random(42);
+    let people = (0..POPULATION)
+        .map(|i| {
+            let age = u8::try_from(i % 100).unwrap();
+            let region = u8::try_from(i % 10).unwrap();
+            context.add_person(((Age, age), (Region, region))).unwrap()
+        })
+        .collect();
+    (context, people)
+}
+
+fn schedule_and_execute(iterations: usize) -> Box<dyn FnOnce() -> Context> {
+    let mut context = Context::new();
+    Box::new(move || {
+        for i in 0..iterations {
+            let time = f64::from(u32::try_from(i).unwrap());
+            context.add_plan(time, |context| {
+                black_box(context.get_current_time());
+            });
+        }
+        context.execute();
+        context
+    })
+}
+
+fn add_person(iterations: usize) -> Box<dyn FnOnce() -> Context> {
+    let mut context = Context::new();
+    Box::new(move || {
+        for i in 0..iterations {
+            let age = u8::try_from(i % 100).unwrap();
+            black_box(context.add_person((Age, age)).unwrap());
+        }
+        context
+    })
+}
+
+fn get_property(iterations: usize) -> Box<dyn FnOnce() -> Context> {
+    let (context, people) = population();
+    Box::new(move || {
+        for i in 0..iterations {
+            black_box(context.get_person_property(people[i % POPULATION], Age));
+        }
+        context
+    })
+}
+
+fn set_property(iterations: usize, indexed: bool) -> Box<dyn FnOnce() -> Context> {
+    let (mut context, people) = population();
+    if indexed {
+        context.index_property(Contacts);
+        // Build the index before timing.
+        black_box(context.query_people_count((Contacts, 0)));
+    }
+    Box::new(move || {
+        for i in 0..iterations {
+            let value = u32::try_from(i).unwrap();
+            context.set_person_property(people[i % POPULATION], Contacts, value);
+        }
+        context
+    })
+}
+
+fn query(iterations: usize, properties: usize) -> Box<dyn FnOnce() -> Context> {
+    let (mut context, people) = population();
+    context.index_property(Age);
+    context.index_property(Region);
+    context.index_property(Vaccinated);
+    for person in people.iter().step_by(3) {
+        context.set_person_property(*person, Vaccinated, true);
+    }
+    // Build the indexes before timing.
+    black_box(context.query_people_count(((Age, 0), (Region, 0), (Vaccinated, true))));
+    Box::new(move || {
+        for i in 0..iterations {
+            let age = u8::try_from(i % 100).unwrap();
+            let region = u8::try_from(i % 10).unwrap();
+            let count = match properties {
+                1 => context.query_people_count((Age, age)),
+                2 => context.query_people_count(((Age, age), (Region, region))),
+                _ => context.query_people_count(((Age, age), (Region, region), (Vaccinated, true))),
+            };
+            black_box(count);
+        }
+        context
+    })
+}
+
+fn emit_event(iterations: usize, subscribers: usize) -> Box<dyn FnOnce() -> Context> {
+    let mut context = Context::new();
+    for _ in 0..subscribers {
+        context.subscribe_to_event(|context, event: Tick| {
+            black_box((context.get_current_time(), event));
+        });
+    }
+    Box::new(move || {
+        for _ in 0..iterations {
+            context.emit_event(Tick);
+        }
+        context.execute();
+        context
+    })
+}
+
+fn add_edge(iterations: usize) -> Box<dyn FnOnce() -> Context> {
+    let (mut context, people) = population();
+    Box::new(move || {
+        // Each person gets edges to the people after them, a different
+        // neighbor for each edge.
+        for i in 0..iterations {
+            let person = people[i % POPULATION];
+            let neighbor = people[(i % POPULATION + 1 + i / POPULATION) % POPULATION];
+            context
+                .add_edge::<Contact>(person, neighbor, 1.0, ())
+                .unwrap();
+        }
+        context
+    })
+}
+
+fn select_random_edge(iterations: usize) -> Box<dyn FnOnce() -> Context> {
+    let (mut context, people) = population();
+    for (i, person) in people.iter().enumerate() {
+        for offset in 1..=10 {
+            let neighbor = people[(i + offset) % POPULATION];
+            let weight = f32::from(u8::try_from(offset).unwrap());
+            context
+                .add_edge::<Contact>(*person, neighbor, weight, ())
+                .unwrap();
+        }
+    }
+    Box::new(move || {
+        for i in 0..iterations {
+            black_box(
+                context
+                    .select_random_edge::<Contact, _>(BenchRng, people[i % POPULATION])
+                    .unwrap(),
+            );
+        }
+        context
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::benchmarks;
+    use std::collections::HashSet;
+
+    #[test]
+    fn benchmarks_run() {
+        let benchmarks = benchmarks();
+        let names: HashSet<&str> = benchmarks.iter().map(|benchmark| benchmark.name).collect();
+        assert_eq!(names.len(), benchmarks.len());
+        // A few operations of each, to check that they work
+        for benchmark in &benchmarks {
+            (benchmark.setup)(100)();
+        }
+    }
+}
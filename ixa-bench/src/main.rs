@@ -0,0 +1,267 @@
+//! Kernel micro-benchmarks for ixa, and a check of them against committed
+//! baselines for catching performance regressions in CI.
+//!
+//! ```text
+//! cargo run --release -p ixa-bench -- run [--output results.json]
+//! cargo run --release -p ixa-bench -- check --baseline ixa-bench/baselines.json
+//! cargo run --release -p ixa-bench -- bless --baseline ixa-bench/baselines.json
+//! cargo run --release -p ixa-bench -- soak --config ixa-bench/soak/laptop.yaml
+//! ```
+//!
+//! `check` exits with status 1 and lists the benchmarks that are slower
+//! than their baselines by more than their thresholds. Updating the
+//! baselines with `bless` rewrites the baselines file, so it shows up in
+//! review like any other change. See the README for the methodology.
+//!
+//! `soak` runs a synthetic model of the size given in a config instead
+//! (see [`soak`]) and prints its timings as JSON.
+mod harness;
+mod kernels;
+mod soak;
+
+use clap::{Parser, Subcommand};
+use harness::{Baselines, Benchmark, Results};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser, Debug)]
+#[command(about = "Kernel micro-benchmarks for ixa")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the benchmarks and print the results as JSON
+    Run {
+        #[command(flatten)]
+        options: RunOptions,
+        /// Write the results to this file instead of printing them
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Run the benchmarks and fail if any is slower than its baseline by
+    /// more than its threshold
+    Check {
+        #[command(flatten)]
+        options: RunOptions,
+        /// The baselines file
+        #[arg(long)]
+        baseline: PathBuf,
+        /// Also write the results to this file as JSON
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// The number of times to measure a benchmark again when it looks
+        /// like a regression, keeping the fastest median
+        #[arg(long, default_value_t = 2)]
+        retries: usize,
+    },
+    /// Run all the benchmarks and replace the baselines with the results,
+    /// keeping the thresholds already set
+    Bless {
+        /// The number of samples of each benchmark
+        #[arg(long, default_value_t = 7)]
+        samples: usize,
+        /// The baselines file
+        #[arg(long)]
+        baseline: PathBuf,
+    },
+    /// Run a synthetic model at the scale given in a config and print its
+    /// timings as JSON
+    Soak {
+        /// The config file, in YAML
+        #[arg(long)]
+        config: PathBuf,
+        /// Write the result to this file instead of printing it
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Write the model's report to this directory instead of a
+        /// temporary one
+        #[arg(long)]
+        report_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct RunOptions {
+    /// The number of samples of each benchmark
+    #[arg(long, default_value_t = 7)]
+    samples: usize,
+    /// Only run the benchmarks whose names contain this
+    #[arg(long)]
+    filter: Option<String>,
+}
+
+fn selected(filter: Option<&str>) -> Vec<Benchmark> {
+    kernels::benchmarks()
+        .into_iter()
+        .filter(|benchmark| filter.is_none_or(|filter| benchmark.name.contains(filter)))
+        .collect()
+}
+
+fn run(options: &RunOptions) -> Results {
+    let results = harness::run(&selected(options.filter.as_deref()), options.samples);
+    for warning in &results.environment.warnings {
+        eprintln!("warning: {warning}");
+    }
+    results
+}
+
+fn write_json<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|error| error.to_string())?;
+    std::fs::write(path, json + "\n")
+        .map_err(|error| format!("Can't write {}: {error}", path.display()))
+}
+
+fn print_or_write_json<T: serde::Serialize>(
+    output: Option<&Path>,
+    value: &T,
+) -> Result<(), String> {
+    if let Some(path) = output {
+        return write_json(path, value);
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|error| error.to_string())?;
+    println!("{json}");
+    Ok(())
+}
+
+fn read_baselines(path: &Path) -> Result<Baselines, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|error| format!("Can't read {}: {error}", path.display()))?;
+    serde_json::from_str(&json).map_err(|error| format!("Invalid {}: {error}", path.display()))
+}
+
+// Measure the benchmarks that look like they've regressed again, up to
+// `retries` times, keeping the fastest median, so that a regression has to
+// show up in every measurement to fail the check.
+fn confirm(baselines: &Baselines, results: &mut Results, samples: usize, retries: usize) {
+    let benchmarks = kernels::benchmarks();
+    for _ in 0..retries {
+        let (comparisons, _) = harness::compare(baselines, results);
+        let regressed: Vec<&str> = comparisons
+            .iter()
+            .filter(|comparison| comparison.regressed())
+            .map(|comparison| comparison.name.as_str())
+            .collect();
+        if regressed.is_empty() {
+            return;
+        }
+        for benchmark in benchmarks
+            .iter()
+            .filter(|benchmark| regressed.contains(&benchmark.name))
+        {
+            let again = harness::measure(benchmark, samples);
+            let measurement = results
+                .measurements
+                .iter_mut()
+                .find(|measurement| measurement.name == benchmark.name)
+                .unwrap();
+            if again.median_ns < measurement.median_ns {
+                *measurement = again;
+            }
+        }
+    }
+}
+
+fn check(baselines: &Baselines, results: &Results) -> bool {
+    let (comparisons, missing) = harness::compare(baselines, results);
+    for comparison in &comparisons {
+        println!(
+            "{:<32} {:>12.1} ns {:>12.1} ns {:>+7.1}% (threshold {:.0}%){}",
+            comparison.name,
+            comparison.expected_ns,
+            comparison.measured_ns,
+            100.0 * comparison.change(),
+            100.0 * comparison.threshold,
+            if comparison.regressed() {
+                "  REGRESSED"
+            } else {
+                ""
+            }
+        );
+    }
+    for name in &missing {
+        eprintln!("warning: {name} has no baseline; add one with `bless`");
+    }
+    let regressed: Vec<&str> = comparisons
+        .iter()
+        .filter(|comparison| comparison.regressed())
+        .map(|comparison| comparison.name.as_str())
+        .collect();
+    if regressed.is_empty() {
+        println!("No regressions in {} benchmarks", comparisons.len());
+        true
+    } else {
+        println!("Regressions: {}", regressed.join(", "));
+        false
+    }
+}
+
+fn main_with_args(args: Args) -> Result<bool, String> {
+    match args.command {
+        Command::Run { options, output } => {
+            print_or_write_json(output.as_deref(), &run(&options))?;
+            Ok(true)
+        }
+        Command::Check {
+            options,
+            baseline,
+            output,
+            retries,
+        } => {
+            let baselines = read_baselines(&baseline)?;
+            let mut results = run(&options);
+            confirm(&baselines, &mut results, options.samples, retries);
+            if let Some(path) = output {
+                write_json(&path, &results)?;
+            }
+            Ok(check(&baselines, &results))
+        }
+        Command::Bless { samples, baseline } => {
+            let previous = if baseline.exists() {
+                Some(read_baselines(&baseline)?)
+            } else {
+                None
+            };
+            let options = RunOptions {
+                samples,
+                filter: None,
+            };
+            let blessed = harness::bless(previous.as_ref(), &run(&options));
+            write_json(&baseline, &blessed)?;
+            println!(
+                "Wrote {} baselines to {}",
+                blessed.benchmarks.len(),
+                baseline.display()
+            );
+            Ok(true)
+        }
+        Command::Soak {
+            config,
+            output,
+            report_dir,
+        } => {
+            let yaml = std::fs::read_to_string(&config)
+                .map_err(|error| format!("Can't read {}: {error}", config.display()))?;
+            let config = soak::SoakConfig::from_yaml(&yaml)?;
+            // The temporary directory is removed when the run is done
+            let temp_dir = tempfile::tempdir().map_err(|error| error.to_string())?;
+            let report_dir = report_dir.unwrap_or_else(|| temp_dir.path().to_path_buf());
+            let result = soak::run(&config, &report_dir)?;
+            print_or_write_json(output.as_deref(), &result)?;
+            Ok(true)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match main_with_args(Args::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::from(2)
+        }
+    }
+}
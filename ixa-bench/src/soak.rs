@@ -0,0 +1,576 @@
+//! A synthetic model for stress testing ixa at the scale of a real one,
+//! for capacity planning and for checking performance-affecting changes
+//! end to end rather than one kernel at a time.
+//!
+//! A [`SoakConfig`] sets the size of each part of the workload. The model
+//! adds `people` people with categorical and numeric properties drawn
+//! from the base seed, indexes a fraction of the properties, and builds a
+//! random contact network. Then on each simulated day it
+//!
+//! * schedules `plans_per_sim_day` plans spread over the day, each of
+//!   which sets a random property of a random person,
+//! * emits `events_per_sim_day` events, each handled with a query of an
+//!   indexed property and a draw of a random contact, and
+//! * writes `report_rows_per_day` rows to a report.
+//!
+//! All of the choices come from the config's seed, so the same config
+//! always does the same work. The [`SoakResult`] is written as JSON. Its
+//! field names are a stable schema in the same way as
+//! [`ExecutionStatistics`]: fields are only removed, renamed, or changed
+//! in meaning together with an increase of [`SOAK_RESULT_SCHEMA_VERSION`].
+use ixa::{
+    create_report_trait, define_edge_type, define_person_property, define_rng, Context,
+    ContextNetworkExt, ContextPeopleExt, ContextRandomExt, ContextReportExt, ExecutionStatistics,
+    IxaEvent, PersonId, Report,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// The version of the serialized form of [`SoakResult`]. This is increased
+/// whenever a change to the schema would break existing readers.
+pub const SOAK_RESULT_SCHEMA_VERSION: u32 = 1;
+
+/// The most categorical properties, and the most numeric ones, that a
+/// config can ask for.
+pub const MAX_PROPERTIES: usize = 8;
+
+const CATEGORIES: u32 = 10;
+const NUMERIC_VALUES: u32 = 1000;
+
+/// The size of each part of the workload, read from YAML.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SoakConfig {
+    pub people: usize,
+    /// The number of properties with 10 values
+    pub categorical_properties: usize,
+    /// The number of properties with 1000 values
+    pub numeric_properties: usize,
+    /// The fraction of the properties that are indexed, categorical ones
+    /// first
+    pub indexed_fraction: f64,
+    pub events_per_sim_day: usize,
+    pub plans_per_sim_day: usize,
+    /// The mean number of contacts each person has in the network, or 0
+    /// for no network
+    pub network_mean_degree: usize,
+    pub report_rows_per_day: usize,
+    pub sim_days: usize,
+    /// The base seed for all of the random choices
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl SoakConfig {
+    /// Reads and validates a config from YAML.
+    ///
+    /// # Errors
+    /// If the YAML doesn't describe a config, or the config is invalid.
+    pub fn from_yaml(yaml: &str) -> Result<SoakConfig, String> {
+        let config: SoakConfig =
+            serde_yaml::from_str(yaml).map_err(|error| format!("Invalid config: {error}"))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// # Errors
+    /// If the config asks for more properties than there are, a fraction
+    /// outside 0 to 1, or more contacts than there are people.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.categorical_properties > MAX_PROPERTIES || self.numeric_properties > MAX_PROPERTIES
+        {
+            return Err(format!(
+                "At most {MAX_PROPERTIES} categorical and {MAX_PROPERTIES} numeric properties are supported"
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.indexed_fraction) {
+            return Err(format!(
+                "indexed_fraction must be between 0 and 1, not {}",
+                self.indexed_fraction
+            ));
+        }
+        if self.network_mean_degree > 0 && self.network_mean_degree >= self.people {
+            return Err(format!(
+                "network_mean_degree must be less than the number of people, {}",
+                self.people
+            ));
+        }
+        Ok(())
+    }
+
+    fn properties(&self) -> Vec<PropertyOps> {
+        categorical_properties()
+            .into_iter()
+            .take(self.categorical_properties)
+            .chain(
+                numeric_properties()
+                    .into_iter()
+                    .take(self.numeric_properties),
+            )
+            .collect()
+    }
+
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn indexed_properties(&self) -> usize {
+        let properties = self.categorical_properties + self.numeric_properties;
+        (self.indexed_fraction * properties as f64).round() as usize
+    }
+}
+
+/// The output of a soak run, written as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoakResult {
+    /// The version of this schema; see [`SOAK_RESULT_SCHEMA_VERSION`]
+    pub schema_version: u32,
+    pub config: SoakConfig,
+    /// The wall clock time of the whole run, including setup, in seconds
+    pub wall_time_seconds: f64,
+    /// The wall clock time spent building the population, indexes, and
+    /// network, in seconds
+    pub setup_seconds: f64,
+    /// The peak resident set size of the process, where the operating
+    /// system reports it
+    pub peak_rss_bytes: Option<u64>,
+    /// The wall clock time spent in each part of the workload, in seconds.
+    /// Every run has the same keys; see [`SPANS`].
+    pub spans: BTreeMap<String, f64>,
+    /// The largest number of plans that were pending at once
+    pub plan_queue_high_water: usize,
+    /// The statistics of the simulation itself
+    pub execution: ExecutionStatistics,
+}
+
+/// The parts of the workload that are timed separately in
+/// [`SoakResult::spans`].
+pub const SPANS: [&str; 7] = [
+    "people",
+    "indexing",
+    "network_setup",
+    "property_updates",
+    "queries",
+    "network_sampling",
+    "reports",
+];
+
+macro_rules! define_soak_properties {
+    ($value:ty, $values:expr, [$($property:ident),*]) => {
+        $(
+            define_person_property!($property, $value, init_distribution = |rng| {
+                <$value>::try_from(rng.gen_range(0..$values)).unwrap()
+            });
+        )*
+    };
+}
+
+define_soak_properties!(
+    u8,
+    CATEGORIES,
+    [
+        Categorical0,
+        Categorical1,
+        Categorical2,
+        Categorical3,
+        Categorical4,
+        Categorical5,
+        Categorical6,
+        Categorical7
+    ]
+);
+define_soak_properties!(
+    u16,
+    NUMERIC_VALUES,
+    [Numeric0, Numeric1, Numeric2, Numeric3, Numeric4, Numeric5, Numeric6, Numeric7]
+);
+define_edge_type!(SoakContact, ());
+define_rng!(SoakRng);
+
+// The operations of the workload on one property, so that the property
+// can be chosen at random.
+#[derive(Clone, Copy)]
+struct PropertyOps {
+    values: u32,
+    index: fn(&mut Context),
+    set: fn(&mut Context, PersonId, u32),
+    count: fn(&Context, u32) -> usize,
+}
+
+macro_rules! property_ops {
+    ($value:ty, $values:expr, [$($property:ident),*]) => {
+        vec![$(
+            PropertyOps {
+                values: $values,
+                index: |context| context.index_property($property),
+                set: |context, person_id, value| {
+                    context.set_person_property(
+                        person_id,
+                        $property,
+                        <$value>::try_from(value).unwrap(),
+                    );
+                },
+                count: |context, value| {
+                    context.query_people_count(($property, <$value>::try_from(value).unwrap()))
+                },
+            },
+        )*]
+    };
+}
+
+fn categorical_properties() -> Vec<PropertyOps> {
+    property_ops!(
+        u8,
+        CATEGORIES,
+        [
+            Categorical0,
+            Categorical1,
+            Categorical2,
+            Categorical3,
+            Categorical4,
+            Categorical5,
+            Categorical6,
+            Categorical7
+        ]
+    )
+}
+
+fn numeric_properties() -> Vec<PropertyOps> {
+    property_ops!(
+        u16,
+        NUMERIC_VALUES,
+        [Numeric0, Numeric1, Numeric2, Numeric3, Numeric4, Numeric5, Numeric6, Numeric7]
+    )
+}
+
+#[derive(Copy, Clone)]
+struct SoakEvent;
+impl IxaEvent for SoakEvent {}
+
+#[derive(Serialize)]
+struct SoakRow {
+    day: usize,
+    person: PersonId,
+    value: u32,
+}
+create_report_trait!(SoakRow);
+
+// The state shared by the plans and event handlers of the workload.
+struct Workload {
+    config: SoakConfig,
+    properties: Vec<PropertyOps>,
+    indexed: usize,
+    people: Vec<PersonId>,
+    spans: RefCell<BTreeMap<&'static str, Duration>>,
+    plan_queue_high_water: Cell<usize>,
+}
+
+impl Workload {
+    fn time<T>(&self, span: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        *self.spans.borrow_mut().get_mut(span).unwrap() += start.elapsed();
+        result
+    }
+
+    fn random_person(&self, context: &Context) -> PersonId {
+        self.people[context.sample_range(SoakRng, 0..self.people.len())]
+    }
+}
+
+/// Builds and runs the model for `config`, writing its report to
+/// `report_dir`.
+///
+/// # Errors
+/// If the config is invalid or the report can't be created.
+pub fn run(config: &SoakConfig, report_dir: &Path) -> Result<SoakResult, String> {
+    config.validate()?;
+    let start = Instant::now();
+    let mut context = Context::new();
+    context.init_random(config.seed);
+    context
+        .report_options()
+        .directory(report_dir.to_path_buf())
+        .file_prefix("soak_".to_string())
+        .overwrite(true);
+    context
+        .add_report::<SoakRow>("rows")
+        .map_err(|error| error.to_string())?;
+
+    let mut workload = Workload {
+        config: config.clone(),
+        properties: config.properties(),
+        indexed: config.indexed_properties(),
+        people: Vec::with_capacity(config.people),
+        spans: RefCell::new(SPANS.iter().map(|span| (*span, Duration::ZERO)).collect()),
+        plan_queue_high_water: Cell::new(0),
+    };
+    let people = workload.time("people", || {
+        (0..config.people)
+            .map(|_| context.add_person(()).unwrap())
+            .collect()
+    });
+    workload.people = people;
+    workload.time("indexing", || {
+        for property in &workload.properties[..workload.indexed] {
+            (property.index)(&mut context);
+            // Build the index now rather than on the first query
+            black_box((property.count)(&context, 0));
+        }
+    });
+    workload.time("network_setup", || build_network(&mut context, &workload));
+    let setup = start.elapsed();
+
+    let workload = Rc::new(workload);
+    let handler_workload = Rc::clone(&workload);
+    context.subscribe_to_event(move |context, _: SoakEvent| {
+        handle_event(context, &handler_workload);
+    });
+    for day in 0..config.sim_days {
+        let workload = Rc::clone(&workload);
+        context.add_plan(day_start(day), move |context| {
+            run_day(context, &workload, day);
+        });
+    }
+    context.execute();
+
+    let execution = context.execution_statistics();
+    let spans = workload
+        .spans
+        .borrow()
+        .iter()
+        .map(|(span, duration)| ((*span).to_string(), duration.as_secs_f64()))
+        .collect();
+    Ok(SoakResult {
+        schema_version: SOAK_RESULT_SCHEMA_VERSION,
+        config: config.clone(),
+        wall_time_seconds: start.elapsed().as_secs_f64(),
+        setup_seconds: setup.as_secs_f64(),
+        peak_rss_bytes: peak_rss_bytes(),
+        spans,
+        plan_queue_high_water: workload.plan_queue_high_water.get(),
+        execution,
+    })
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn day_start(day: usize) -> f64 {
+    day as f64
+}
+
+// Adds edges between random pairs of people until the mean degree is
+// about `network_mean_degree`. Pairs that are already connected are
+// skipped rather than drawn again.
+fn build_network(context: &mut Context, workload: &Workload) {
+    let edges = workload.people.len() * workload.config.network_mean_degree / 2;
+    for _ in 0..edges {
+        let person = workload.random_person(context);
+        let contact = workload.random_person(context);
+        if person != contact {
+            let _ = context.add_edge_bidi::<SoakContact>(person, contact, 1.0, ());
+        }
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn run_day(context: &mut Context, workload: &Rc<Workload>, day: usize) {
+    let plans = workload.config.plans_per_sim_day;
+    for i in 0..plans {
+        let time = day_start(day) + (i as f64 + 0.5) / plans as f64;
+        let workload = Rc::clone(workload);
+        context.add_plan(time, move |context| update_property(context, &workload));
+    }
+    let pending = context.get_pending_plan_count();
+    if pending > workload.plan_queue_high_water.get() {
+        workload.plan_queue_high_water.set(pending);
+    }
+
+    for _ in 0..workload.config.events_per_sim_day {
+        context.emit_event(SoakEvent);
+    }
+
+    workload.time("reports", || {
+        for _ in 0..workload.config.report_rows_per_day {
+            context.send_report(SoakRow {
+                day,
+                person: workload.random_person(context),
+                value: context.sample_range(SoakRng, 0..CATEGORIES),
+            });
+        }
+    });
+}
+
+fn update_property(context: &mut Context, workload: &Workload) {
+    if workload.properties.is_empty() || workload.people.is_empty() {
+        return;
+    }
+    let person_id = workload.random_person(context);
+    let property = workload.properties[context.sample_range(SoakRng, 0..workload.properties.len())];
+    let value = context.sample_range(SoakRng, 0..property.values);
+    workload.time("property_updates", || {
+        (property.set)(context, person_id, value);
+    });
+}
+
+fn handle_event(context: &mut Context, workload: &Workload) {
+    if workload.indexed > 0 {
+        let property = workload.properties[context.sample_range(SoakRng, 0..workload.indexed)];
+        let value = context.sample_range(SoakRng, 0..property.values);
+        workload.time("queries", || black_box((property.count)(context, value)));
+    }
+    if workload.config.network_mean_degree > 0 {
+        let person_id = workload.random_person(context);
+        workload.time("network_sampling", || {
+            // People without contacts are an error, which is ignored
+            black_box(
+                context
+                    .select_random_edge::<SoakContact, _>(SoakRng, person_id)
+                    .ok(),
+            )
+        });
+    }
+}
+
+// The peak resident set size from /proc, on Linux.
+fn peak_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{run, SoakConfig, SoakResult, SOAK_RESULT_SCHEMA_VERSION, SPANS};
+    use ixa::execution_stats::{ReportFileStatistics, ReportStatistics};
+    use ixa::ExecutionStatistics;
+    use std::collections::BTreeMap;
+
+    fn small_config() -> SoakConfig {
+        SoakConfig {
+            people: 1000,
+            categorical_properties: 3,
+            numeric_properties: 2,
+            indexed_fraction: 0.4,
+            events_per_sim_day: 50,
+            plans_per_sim_day: 200,
+            network_mean_degree: 4,
+            report_rows_per_day: 10,
+            sim_days: 5,
+            seed: 7,
+        }
+    }
+
+    #[test]
+    fn small_config_runs() {
+        let report_dir = tempfile::tempdir().unwrap();
+        let result = run(&small_config(), report_dir.path()).unwrap();
+
+        // The plans of each day and the plan that starts it
+        assert_eq!(result.execution.plans_executed, 5 * 201);
+        assert_eq!(result.execution.population, 1000);
+        assert!(result.execution.events_emitted >= 5 * 50);
+        assert!(result.plan_queue_high_water >= 200);
+        assert!(result.setup_seconds <= result.wall_time_seconds);
+        let spans: Vec<&str> = result.spans.keys().map(String::as_str).collect();
+        let mut expected = SPANS.to_vec();
+        expected.sort_unstable();
+        assert_eq!(spans, expected);
+
+        let rows = csv::Reader::from_path(report_dir.path().join("soak_rows.csv"))
+            .unwrap()
+            .records()
+            .count();
+        assert_eq!(rows, 5 * 10);
+    }
+
+    #[test]
+    fn runs_are_deterministic() {
+        let report_dir = tempfile::tempdir().unwrap();
+        let config = small_config();
+        let first = run(&config, report_dir.path()).unwrap();
+        let second = run(&config, report_dir.path()).unwrap();
+        assert_eq!(
+            first.execution.events_emitted,
+            second.execution.events_emitted
+        );
+        assert_eq!(first.plan_queue_high_water, second.plan_queue_high_water);
+    }
+
+    #[test]
+    fn reference_configs_are_valid() {
+        for yaml in [
+            include_str!("../soak/laptop.yaml"),
+            include_str!("../soak/cluster.yaml"),
+        ] {
+            SoakConfig::from_yaml(yaml).unwrap();
+        }
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let yaml = serde_yaml::to_string(&small_config()).unwrap();
+        assert!(SoakConfig::from_yaml(&format!("{yaml}extra: 1\n")).is_err());
+
+        let mut config = small_config();
+        config.categorical_properties = 9;
+        assert!(config.validate().is_err());
+        let mut config = small_config();
+        config.indexed_fraction = 1.5;
+        assert!(config.validate().is_err());
+        let mut config = small_config();
+        config.network_mean_degree = 1000;
+        assert!(config.validate().is_err());
+    }
+
+    // Changing this file means the schema changed. If the change breaks
+    // existing readers, increase SOAK_RESULT_SCHEMA_VERSION too.
+    const GOLDEN_SCHEMA: &str = include_str!("../soak/result_schema.json");
+
+    #[test]
+    fn serialized_schema_matches_golden_file() {
+        let result = SoakResult {
+            schema_version: SOAK_RESULT_SCHEMA_VERSION,
+            config: small_config(),
+            wall_time_seconds: 2.5,
+            setup_seconds: 0.5,
+            peak_rss_bytes: Some(1_048_576),
+            spans: SPANS
+                .iter()
+                .map(|span| ((*span).to_string(), 0.25))
+                .collect(),
+            plan_queue_high_water: 201,
+            execution: ExecutionStatistics {
+                schema_version: 1,
+                plans_executed: 1005,
+                callbacks_executed: 250,
+                events_emitted: 1250,
+                plans_pending: 0,
+                wall_time_seconds: 2.0,
+                simulation_time: 4.9975,
+                population: 1000,
+                running: false,
+                modes: BTreeMap::new(),
+                reports: Some(ReportStatistics {
+                    files: vec![ReportFileStatistics {
+                        name: "rows".to_string(),
+                        path: "soak_rows.csv".to_string(),
+                        rows: 50,
+                    }],
+                }),
+            },
+        };
+        let serialized = serde_json::to_value(&result).unwrap();
+        let golden: serde_json::Value = serde_json::from_str(GOLDEN_SCHEMA).unwrap();
+        assert_eq!(serialized, golden);
+        let deserialized: SoakResult = serde_json::from_value(golden).unwrap();
+        assert_eq!(deserialized, result);
+    }
+}
@@ -0,0 +1,664 @@
+//! Alarms that act on trends in a count rather than its level.
+//!
+//! An alarm evaluates an [`AlarmObservable`], such as the number of people
+//! matching a query, every `eval_interval` time units and keeps as many
+//! recent values as its [`AlarmCondition`] needs. When the condition
+//! starts to hold, the handler is called with an [`AlarmFiring`]
+//! describing the evidence. It isn't called again until the condition has
+//! cleared and then holds again, and not at all after the first firing if
+//! `rearm` is false. For instance, to activate a response when infections
+//! grow by more than 30% per day for 3 days in a row:
+//!
+//! ```
+//! use ixa::alarm::{AlarmCondition, AlarmObservable, AlarmSpec, ContextAlarmExt};
+//! use ixa::{define_person_property_with_default, Context};
+//!
+//! define_person_property_with_default!(Infected, bool, false);
+//!
+//! let mut context = Context::new();
+//! context
+//!     .add_alarm(
+//!         AlarmSpec {
+//!             name: "infections growing".to_string(),
+//!             observable: AlarmObservable::query_count((Infected, true)),
+//!             eval_interval: 1.0,
+//!             condition: AlarmCondition::GrowthRate {
+//!                 window: 3,
+//!                 threshold: 0.3,
+//!             },
+//!             rearm: true,
+//!         },
+//!         |context, firing| {
+//!             println!("{} at {}: {:?}", firing.name, firing.time, firing.rate);
+//!         },
+//!     )
+//!     .unwrap();
+//! ```
+//!
+//! Alarms are evaluated in the [`ExecutionPhase::Last`] phase, after the
+//! other plans at the same time, and stop being evaluated once nothing
+//! else is scheduled. Call [`ContextAlarmExt::record_alarm_firings()`] to
+//! record every firing in the `alarm_firings` report.
+use crate::context::{Context, ExecutionPhase};
+use crate::error::IxaError;
+use crate::people::{ContextPeopleExt, Query};
+use crate::plan::PlanId;
+use crate::report::{ContextReportExt, Report};
+use crate::tabulator::TabulationKey;
+use crate::{create_report_trait, define_data_plugin, trace};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::rc::Rc;
+
+/// The value an alarm watches, computed each time the alarm is evaluated
+pub struct AlarmObservable(Box<dyn Fn(&Context) -> f64>);
+
+impl AlarmObservable {
+    /// The number of people matching `query`
+    #[allow(clippy::cast_precision_loss)]
+    pub fn query_count<Q: Query + Clone + 'static>(query: Q) -> Self {
+        AlarmObservable(Box::new(move |context| {
+            context.query_people_count(query.clone()) as f64
+        }))
+    }
+
+    /// The number of people with the values `values` of the properties in
+    /// `T`, which is a tuple of properties such as `(Age, RiskCategory)`
+    #[allow(clippy::cast_precision_loss)]
+    pub fn tabulated_count<T: TabulationKey>(values: T::Values) -> Self
+    where
+        T::Values: Eq,
+    {
+        AlarmObservable(Box::new(move |context| {
+            context
+                .tabulate_person_count::<T>()
+                .get(&values)
+                .copied()
+                .unwrap_or(0) as f64
+        }))
+    }
+
+    /// Any other value, such as a count kept by the model
+    pub fn from_fn(observable: impl Fn(&Context) -> f64 + 'static) -> Self {
+        AlarmObservable(Box::new(observable))
+    }
+}
+
+/// Which side of the threshold of [`AlarmCondition::ThresholdCross`]
+/// violates the condition
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrossDirection {
+    Above,
+    Below,
+}
+
+/// A custom alarm condition; see [`AlarmCondition::Custom`]
+pub type AlarmCheck = Box<dyn Fn(&[f64]) -> bool>;
+
+/// When an alarm fires
+pub enum AlarmCondition {
+    /// The value grew by more than `threshold` (e.g., 0.3 for 30%) from
+    /// each evaluation to the next, `window` times in a row. Growth from
+    /// zero isn't counted.
+    GrowthRate { window: usize, threshold: f64 },
+    /// The value is above or below `value`
+    ThresholdCross {
+        value: f64,
+        direction: CrossDirection,
+    },
+    /// `check` returns true when passed the last `history` values, oldest
+    /// first
+    Custom { history: usize, check: AlarmCheck },
+}
+
+struct Violation {
+    // For growth rate conditions, the average growth per evaluation over
+    // the window
+    rate: Option<f64>,
+}
+
+impl AlarmCondition {
+    // The number of values needed to check the condition
+    fn history(&self) -> usize {
+        match self {
+            AlarmCondition::GrowthRate { window, .. } => window + 1,
+            AlarmCondition::ThresholdCross { .. } => 1,
+            AlarmCondition::Custom { history, .. } => *history,
+        }
+    }
+
+    fn validate(&self) -> Result<(), IxaError> {
+        match self {
+            AlarmCondition::GrowthRate { window, threshold } => {
+                if *window == 0 || !threshold.is_finite() {
+                    return Err(IxaError::from(format!(
+                        "Invalid growth rate condition: window {window}, threshold {threshold}"
+                    )));
+                }
+            }
+            AlarmCondition::ThresholdCross { value, .. } => {
+                if value.is_nan() {
+                    return Err(IxaError::from("Invalid threshold NaN"));
+                }
+            }
+            AlarmCondition::Custom { history, .. } => {
+                if *history == 0 {
+                    return Err(IxaError::from("Custom conditions need at least one value"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Returns `None` if the condition doesn't hold for `recent`, which has
+    // `history()` values.
+    fn check(&self, recent: &[f64]) -> Option<Violation> {
+        match self {
+            AlarmCondition::GrowthRate { window, threshold } => {
+                let growing = recent
+                    .windows(2)
+                    .all(|pair| pair[0] > 0.0 && pair[1] / pair[0] - 1.0 > *threshold);
+                if !growing {
+                    return None;
+                }
+                #[allow(clippy::cast_precision_loss)]
+                let rate = (recent[*window] / recent[0]).powf(1.0 / *window as f64) - 1.0;
+                Some(Violation { rate: Some(rate) })
+            }
+            AlarmCondition::ThresholdCross { value, direction } => {
+                let current = recent[0];
+                let violated = match direction {
+                    CrossDirection::Above => current > *value,
+                    CrossDirection::Below => current < *value,
+                };
+                violated.then_some(Violation { rate: None })
+            }
+            AlarmCondition::Custom { check, .. } => {
+                check(recent).then_some(Violation { rate: None })
+            }
+        }
+    }
+}
+
+/// An alarm to add with [`ContextAlarmExt::add_alarm()`]
+pub struct AlarmSpec {
+    /// A name for the alarm, used in listings and the firings report
+    pub name: String,
+    /// The value to watch
+    pub observable: AlarmObservable,
+    /// The time between evaluations, starting when the alarm is added
+    pub eval_interval: f64,
+    /// When to fire
+    pub condition: AlarmCondition,
+    /// Whether to fire again once the condition has cleared and then
+    /// holds again
+    pub rearm: bool,
+}
+
+/// Identifies an alarm added with [`ContextAlarmExt::add_alarm()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AlarmId(usize);
+
+/// The evidence passed to an alarm's handler when it fires
+#[derive(Clone, Debug)]
+pub struct AlarmFiring {
+    pub alarm_id: AlarmId,
+    pub name: String,
+    pub time: f64,
+    /// The values the condition was checked against, oldest first
+    pub values: Vec<f64>,
+    /// For growth rate conditions, the average growth per evaluation over
+    /// the window
+    pub rate: Option<f64>,
+}
+
+type AlarmHandler = Rc<dyn Fn(&mut Context, &AlarmFiring)>;
+
+struct Alarm {
+    spec: AlarmSpec,
+    handler: AlarmHandler,
+    recent: VecDeque<f64>,
+    // Whether the condition held at the last evaluation
+    violated: bool,
+    fired: bool,
+    next_plan: Option<PlanId>,
+}
+
+#[derive(Default)]
+struct AlarmData {
+    alarms: BTreeMap<AlarmId, Alarm>,
+    next_id: usize,
+    record_firings: bool,
+}
+
+define_data_plugin!(AlarmPlugin, AlarmData, AlarmData::default());
+
+#[derive(Serialize)]
+struct AlarmFiringReport {
+    time: f64,
+    alarm: String,
+    value: f64,
+    rate: Option<f64>,
+    values: String,
+}
+
+create_report_trait!(AlarmFiringReport);
+
+// Evaluates an alarm and schedules the next evaluation if anything else
+// is scheduled.
+fn evaluate_alarm(context: &mut Context, alarm_id: AlarmId) {
+    let Some(alarm) = context
+        .get_data_container(AlarmPlugin)
+        .and_then(|data| data.alarms.get(&alarm_id))
+    else {
+        return;
+    };
+    let value = (alarm.spec.observable.0)(context);
+    let time = context.get_current_time();
+
+    let data = context.get_data_container_mut(AlarmPlugin);
+    let alarm = data.alarms.get_mut(&alarm_id).unwrap();
+    alarm.recent.push_back(value);
+    let history = alarm.spec.condition.history();
+    if alarm.recent.len() > history {
+        alarm.recent.pop_front();
+    }
+    let check = if alarm.recent.len() == history {
+        alarm.spec.condition.check(alarm.recent.make_contiguous())
+    } else {
+        None
+    };
+
+    let mut firing = None;
+    if let Some(violation) = check {
+        if !alarm.violated && (alarm.spec.rearm || !alarm.fired) {
+            alarm.fired = true;
+            firing = Some((
+                Rc::clone(&alarm.handler),
+                AlarmFiring {
+                    alarm_id,
+                    name: alarm.spec.name.clone(),
+                    time,
+                    values: alarm.recent.iter().copied().collect(),
+                    rate: violation.rate,
+                },
+            ));
+        }
+        alarm.violated = true;
+    } else {
+        alarm.violated = false;
+    }
+    let record_firings = data.record_firings;
+
+    if let Some((handler, firing)) = firing {
+        trace!("Alarm {} fired at {}", firing.name, firing.time);
+        if record_firings {
+            context.send_report(AlarmFiringReport {
+                time: firing.time,
+                alarm: firing.name.clone(),
+                value,
+                rate: firing.rate,
+                values: firing
+                    .values
+                    .iter()
+                    .map(f64::to_string)
+                    .collect::<Vec<_>>()
+                    .join(";"),
+            });
+        }
+        handler(context, &firing);
+    }
+
+    // The handler may have cancelled the alarm.
+    if context.remaining_plan_count() == 0 {
+        return;
+    }
+    let Some(alarm) = context
+        .get_data_container(AlarmPlugin)
+        .and_then(|data| data.alarms.get(&alarm_id))
+    else {
+        return;
+    };
+    let next_time = context.get_current_time() + alarm.spec.eval_interval;
+    let plan_id = context.add_plan_with_phase(
+        next_time,
+        move |context| evaluate_alarm(context, alarm_id),
+        ExecutionPhase::Last,
+    );
+    let data = context.get_data_container_mut(AlarmPlugin);
+    data.alarms.get_mut(&alarm_id).unwrap().next_plan = Some(plan_id);
+}
+
+pub trait ContextAlarmExt {
+    /// Add an alarm that calls `handler` when its condition starts to hold.
+    /// It's first evaluated at the current time.
+    ///
+    /// # Errors
+    /// Returns an `IxaError` if `eval_interval` isn't positive and finite
+    /// or the condition's parameters are invalid.
+    fn add_alarm(
+        &mut self,
+        spec: AlarmSpec,
+        handler: impl Fn(&mut Context, &AlarmFiring) + 'static,
+    ) -> Result<AlarmId, IxaError>;
+
+    /// Returns the alarms that haven't been cancelled and their names, in
+    /// the order they were added.
+    fn list_alarms(&self) -> Vec<(AlarmId, String)>;
+
+    /// Stop evaluating an alarm. Returns false if it doesn't exist or was
+    /// already cancelled.
+    fn cancel_alarm(&mut self, alarm_id: AlarmId) -> bool;
+
+    /// Record every subsequent alarm firing in the `alarm_firings` report,
+    /// with the time, the alarm's name, the latest value, the growth rate
+    /// if any, and the values the condition was checked against separated
+    /// by semicolons. The report options must be configured first.
+    ///
+    /// # Errors
+    /// Returns an `IxaError` if the report can't be created.
+    fn record_alarm_firings(&mut self) -> Result<(), IxaError>;
+}
+
+impl ContextAlarmExt for Context {
+    fn add_alarm(
+        &mut self,
+        spec: AlarmSpec,
+        handler: impl Fn(&mut Context, &AlarmFiring) + 'static,
+    ) -> Result<AlarmId, IxaError> {
+        if !(spec.eval_interval > 0.0 && spec.eval_interval.is_finite()) {
+            return Err(IxaError::from(format!(
+                "Invalid evaluation interval {} for alarm {}",
+                spec.eval_interval, spec.name
+            )));
+        }
+        spec.condition.validate()?;
+
+        let data = self.get_data_container_mut(AlarmPlugin);
+        let alarm_id = AlarmId(data.next_id);
+        data.next_id += 1;
+        data.alarms.insert(
+            alarm_id,
+            Alarm {
+                spec,
+                handler: Rc::new(handler),
+                recent: VecDeque::new(),
+                violated: false,
+                fired: false,
+                next_plan: None,
+            },
+        );
+
+        let plan_id = self.add_plan_with_phase(
+            self.get_current_time(),
+            move |context| evaluate_alarm(context, alarm_id),
+            ExecutionPhase::Last,
+        );
+        let data = self.get_data_container_mut(AlarmPlugin);
+        data.alarms.get_mut(&alarm_id).unwrap().next_plan = Some(plan_id);
+        Ok(alarm_id)
+    }
+
+    fn list_alarms(&self) -> Vec<(AlarmId, String)> {
+        self.get_data_container(AlarmPlugin)
+            .map(|data| {
+                data.alarms
+                    .iter()
+                    .map(|(alarm_id, alarm)| (*alarm_id, alarm.spec.name.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn cancel_alarm(&mut self, alarm_id: AlarmId) -> bool {
+        let Some(alarm) = self
+            .get_data_container_mut(AlarmPlugin)
+            .alarms
+            .remove(&alarm_id)
+        else {
+            return false;
+        };
+        if let Some(plan_id) = alarm.next_plan {
+            self.try_cancel_plan(&plan_id);
+        }
+        true
+    }
+
+    fn record_alarm_firings(&mut self) -> Result<(), IxaError> {
+        self.add_report::<AlarmFiringReport>("alarm_firings")?;
+        self.get_data_container_mut(AlarmPlugin).record_firings = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::{
+        AlarmCondition, AlarmFiring, AlarmObservable, AlarmSpec, ContextAlarmExt, CrossDirection,
+    };
+    use crate::{define_person_property_with_default, Context, ContextPeopleExt, ContextReportExt};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use tempfile::tempdir;
+
+    fn growth(window: usize, threshold: f64) -> AlarmCondition {
+        AlarmCondition::GrowthRate { window, threshold }
+    }
+
+    #[test]
+    fn growth_rate_math() {
+        let condition = growth(3, 0.3);
+        assert_eq!(condition.history(), 4);
+        // 50% growth each step
+        let rate = condition
+            .check(&[8.0, 12.0, 18.0, 27.0])
+            .unwrap()
+            .rate
+            .unwrap();
+        assert!((rate - 0.5).abs() < 1e-12);
+        // One step of only 25% growth
+        assert!(condition.check(&[8.0, 12.0, 15.0, 27.0]).is_none());
+        // Exactly the threshold isn't more than it
+        assert!(growth(1, 0.5).check(&[2.0, 3.0]).is_none());
+        // Growth from zero isn't counted
+        assert!(growth(1, 0.3).check(&[0.0, 5.0]).is_none());
+        // Shrinking series don't grow
+        assert!(growth(2, -0.6).check(&[10.0, 5.0, 2.5]).is_some());
+
+        assert!(growth(0, 0.3).validate().is_err());
+        assert!(growth(1, f64::NAN).validate().is_err());
+    }
+
+    // Runs the simulation until time `steps - 1` with an alarm over
+    // `series`, returning the firings.
+    fn run_series(series: &[f64], condition: AlarmCondition, rearm: bool) -> Vec<AlarmFiring> {
+        let mut context = Context::new();
+        let series = series.to_vec();
+        let steps = series.len();
+        let firings = Rc::new(RefCell::new(Vec::new()));
+        let firings_clone = Rc::clone(&firings);
+        context
+            .add_alarm(
+                AlarmSpec {
+                    name: "test".to_string(),
+                    observable: AlarmObservable::from_fn(move |context| {
+                        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                        series[context.get_current_time() as usize]
+                    }),
+                    eval_interval: 1.0,
+                    condition,
+                    rearm,
+                },
+                move |_, firing| firings_clone.borrow_mut().push(firing.clone()),
+            )
+            .unwrap();
+        #[allow(clippy::cast_precision_loss)]
+        context.add_plan((steps - 1) as f64, |_| {});
+        context.execute();
+        firings.take()
+    }
+
+    #[test]
+    fn fires_once_per_episode() {
+        // Violated at times 2-3 and 6-7
+        let series = [1.0, 2.0, 4.0, 8.0, 8.0, 8.0, 16.0, 32.0, 32.0];
+        let firings = run_series(&series, growth(2, 0.5), true);
+        let times: Vec<f64> = firings.iter().map(|firing| firing.time).collect();
+        assert_eq!(times, vec![2.0, 7.0]);
+        assert_eq!(firings[0].values, vec![1.0, 2.0, 4.0]);
+        assert_eq!(firings[0].rate, Some(1.0));
+        assert_eq!(firings[1].values, vec![8.0, 16.0, 32.0]);
+
+        // Without re-arming, only the first episode fires
+        let firings = run_series(&series, growth(2, 0.5), false);
+        assert_eq!(firings.len(), 1);
+        assert_eq!(firings[0].time, 2.0);
+    }
+
+    #[test]
+    fn threshold_cross() {
+        let series = [1.0, 5.0, 6.0, 2.0, 7.0];
+        let above = AlarmCondition::ThresholdCross {
+            value: 4.0,
+            direction: CrossDirection::Above,
+        };
+        let times: Vec<f64> = run_series(&series, above, true)
+            .iter()
+            .map(|firing| firing.time)
+            .collect();
+        assert_eq!(times, vec![1.0, 4.0]);
+
+        let below = AlarmCondition::ThresholdCross {
+            value: 4.0,
+            direction: CrossDirection::Below,
+        };
+        let firings = run_series(&series, below, true);
+        assert_eq!(firings.len(), 2);
+        assert_eq!(firings[1].values, vec![2.0]);
+        assert_eq!(firings[1].rate, None);
+    }
+
+    #[test]
+    fn custom_condition() {
+        // Fires when the last three values sum to more than 10
+        let condition = AlarmCondition::Custom {
+            history: 3,
+            check: Box::new(|recent| recent.iter().sum::<f64>() > 10.0),
+        };
+        let firings = run_series(&[1.0, 2.0, 3.0, 6.0, 1.0, 1.0, 1.0, 9.0], condition, true);
+        let times: Vec<f64> = firings.iter().map(|firing| firing.time).collect();
+        assert_eq!(times, vec![3.0, 7.0]);
+        assert_eq!(firings[0].values, vec![2.0, 3.0, 6.0]);
+    }
+
+    define_person_property_with_default!(AlarmInfected, bool, false);
+
+    #[test]
+    fn query_count_list_and_cancel() {
+        let mut context = Context::new();
+        for _ in 0..10 {
+            context.add_person(()).unwrap();
+        }
+        // Infect one more person every day
+        for day in 0..10 {
+            context.add_plan(f64::from(day), move |context| {
+                let person = context.query_people((AlarmInfected, false))[0];
+                context.set_person_property(person, AlarmInfected, true);
+            });
+        }
+        let fired = Rc::new(RefCell::new(Vec::new()));
+        let fired_clone = Rc::clone(&fired);
+        let half = context
+            .add_alarm(
+                AlarmSpec {
+                    name: "half infected".to_string(),
+                    observable: AlarmObservable::query_count((AlarmInfected, true)),
+                    eval_interval: 1.0,
+                    condition: AlarmCondition::ThresholdCross {
+                        value: 4.5,
+                        direction: CrossDirection::Above,
+                    },
+                    rearm: true,
+                },
+                move |_, firing| fired_clone.borrow_mut().push(firing.time),
+            )
+            .unwrap();
+        let cancelled = context
+            .add_alarm(
+                AlarmSpec {
+                    name: "cancelled".to_string(),
+                    observable: AlarmObservable::tabulated_count::<(AlarmInfected,)>((true,)),
+                    eval_interval: 1.0,
+                    condition: AlarmCondition::ThresholdCross {
+                        value: 0.0,
+                        direction: CrossDirection::Above,
+                    },
+                    rearm: true,
+                },
+                |_, _| panic!("Cancelled alarm fired"),
+            )
+            .unwrap();
+        assert_eq!(
+            context.list_alarms(),
+            vec![
+                (half, "half infected".to_string()),
+                (cancelled, "cancelled".to_string())
+            ]
+        );
+        assert!(context.cancel_alarm(cancelled));
+        assert!(!context.cancel_alarm(cancelled));
+        assert_eq!(context.list_alarms().len(), 1);
+
+        context.execute();
+        // Five people are infected by the end of day 4
+        assert_eq!(*fired.borrow(), vec![4.0]);
+    }
+
+    #[test]
+    fn invalid_alarms() {
+        let mut context = Context::new();
+        let spec = |eval_interval| AlarmSpec {
+            name: "invalid".to_string(),
+            observable: AlarmObservable::from_fn(|_| 0.0),
+            eval_interval,
+            condition: growth(1, 0.1),
+            rearm: true,
+        };
+        assert!(context.add_alarm(spec(0.0), |_, _| {}).is_err());
+        assert!(context.add_alarm(spec(f64::INFINITY), |_, _| {}).is_err());
+        assert!(context.list_alarms().is_empty());
+    }
+
+    #[test]
+    fn firing_report() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        {
+            let mut context = Context::new();
+            context.report_options().directory(path.clone());
+            context.record_alarm_firings().unwrap();
+            context
+                .add_alarm(
+                    AlarmSpec {
+                        name: "doubling".to_string(),
+                        observable: AlarmObservable::from_fn(|context| {
+                            2.0_f64.powf(context.get_current_time())
+                        }),
+                        eval_interval: 1.0,
+                        condition: growth(1, 0.5),
+                        rearm: true,
+                    },
+                    |_, _| {},
+                )
+                .unwrap();
+            context.add_plan(2.0, |_| {});
+            context.execute();
+        }
+        let contents = std::fs::read_to_string(path.join("alarm_firings.csv")).unwrap();
+        assert_eq!(
+            contents,
+            "time,alarm,value,rate,values\n1.0,doubling,2.0,1.0,1;2\n"
+        );
+    }
+}
@@ -0,0 +1,479 @@
+//! Per-person comparison of an outcome between two paired simulations.
+//!
+//! When a baseline and an intervention scenario are run with the same
+//! population and common random numbers, the same `PersonId` refers to the
+//! same person in both, so their outcomes can be linked person by person:
+//! who was infected in both, in only one, and how much later or earlier.
+//! This gives the number of infections averted exactly, rather than as the
+//! difference of two noisy totals.
+//!
+//! An outcome is a person property changing to a value, e.g., `InfectionStatus`
+//! becoming `Infected`. Call [`ContextAnalysisExt::track_outcome()`] on
+//! both contexts before running them to record when it first happens to each
+//! person, then compare them with [`paired_outcomes()`]:
+//!
+//! ```
+//! use ixa::analysis::{paired_outcomes, ContextAnalysisExt, OutcomeSpec};
+//! use ixa::{define_person_property_with_default, Context, ContextPeopleExt};
+//!
+//! define_person_property_with_default!(Infected, bool, false);
+//!
+//! let spec = OutcomeSpec { property: Infected, event_value: true };
+//! let mut contexts = [Context::new(), Context::new()];
+//! for (context, infection_time) in contexts.iter_mut().zip([1.0, 3.0]) {
+//!     let person = context.add_person(()).unwrap();
+//!     context.track_outcome(spec);
+//!     context.add_plan(infection_time, move |context| {
+//!         context.set_person_property(person, Infected, true);
+//!     });
+//!     context.add_plan(5.0, |_| {});
+//!     context.execute();
+//! }
+//!
+//! let paired = paired_outcomes(&contexts[0], &contexts[1], spec).unwrap();
+//! assert_eq!(paired.summary.delayed, 1);
+//! assert_eq!(paired.summary.exposure_time_gained, 2.0);
+//! ```
+use crate::context::Context;
+use crate::error::IxaError;
+use crate::people::{ContextPeopleExt, PersonPropertyChangeEvent};
+use crate::report::{ContextReportExt, Report};
+use crate::{create_report_trait, define_checkpointable_data_plugin, define_data_plugin};
+use crate::{PersonId, PersonProperty};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// An outcome to compare: `property` changing to `event_value`
+#[derive(Clone, Copy, Debug)]
+pub struct OutcomeSpec<T: PersonProperty> {
+    pub property: T,
+    pub event_value: T::Value,
+}
+
+impl<T: PersonProperty + 'static> OutcomeSpec<T> {
+    // Identifies the outcome in the recorded times
+    fn key(&self) -> String {
+        format!("{}={:?}", T::name(), self.event_value)
+    }
+}
+
+// The time each outcome first happened to each person, keyed by outcome
+define_data_plugin!(
+    OutcomePlugin,
+    HashMap<String, HashMap<PersonId, f64>>,
+    HashMap::new()
+);
+define_checkpointable_data_plugin!(OutcomePlugin);
+
+pub trait ContextAnalysisExt {
+    /// Record the first time each person's `spec.property` changes to
+    /// `spec.event_value`, for [`paired_outcomes()`]. People who have the
+    /// value when they're added, or when tracking starts, aren't counted
+    /// unless it changes to the value again.
+    fn track_outcome<T: PersonProperty + 'static>(&mut self, spec: OutcomeSpec<T>);
+}
+
+impl ContextAnalysisExt for Context {
+    fn track_outcome<T: PersonProperty + 'static>(&mut self, spec: OutcomeSpec<T>) {
+        let key = spec.key();
+        if self
+            .get_data_container(OutcomePlugin)
+            .is_some_and(|data| data.contains_key(&key))
+        {
+            return;
+        }
+        self.get_data_container_mut(OutcomePlugin)
+            .insert(key.clone(), HashMap::new());
+        self.subscribe_to_event(move |context, event: PersonPropertyChangeEvent<T>| {
+            if event.current != spec.event_value {
+                return;
+            }
+            let time = context.get_current_time();
+            context
+                .get_data_container_mut(OutcomePlugin)
+                .get_mut(&key)
+                .unwrap()
+                .entry(event.person_id)
+                .or_insert(time);
+        });
+    }
+}
+
+/// How a person's outcome in scenario B compares with scenario A
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairedClassification {
+    /// The outcome happened in neither scenario
+    Neither,
+    /// The outcome happened in A but not in B
+    Averted,
+    /// The outcome happened in B but not in A
+    Added,
+    /// The outcome happened later in B than in A
+    Delayed,
+    /// The outcome happened earlier in B than in A
+    Advanced,
+    /// The outcome happened at the same time in both
+    Unchanged,
+}
+
+/// A person's outcome in each of the paired scenarios
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PairedOutcome {
+    pub person_id: PersonId,
+    pub outcome_a: bool,
+    pub outcome_b: bool,
+    /// When the outcome happened in A, if it did
+    pub time_a: Option<f64>,
+    /// When the outcome happened in B, if it did
+    pub time_b: Option<f64>,
+    /// `time_b - time_a`, if the outcome happened in both
+    pub delta: Option<f64>,
+}
+
+create_report_trait!(PairedOutcome);
+
+impl PairedOutcome {
+    #[must_use]
+    pub fn classification(&self) -> PairedClassification {
+        match (self.time_a, self.time_b) {
+            (None, None) => PairedClassification::Neither,
+            (Some(_), None) => PairedClassification::Averted,
+            (None, Some(_)) => PairedClassification::Added,
+            (Some(a), Some(b)) if b > a => PairedClassification::Delayed,
+            (Some(a), Some(b)) if b < a => PairedClassification::Advanced,
+            (Some(_), Some(_)) => PairedClassification::Unchanged,
+        }
+    }
+}
+
+/// Summary statistics of a [`PairedOutcomes`]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PairedSummary {
+    pub population: usize,
+    pub outcomes_a: usize,
+    pub outcomes_b: usize,
+    pub averted: usize,
+    pub added: usize,
+    pub delayed: usize,
+    pub advanced: usize,
+    /// The time the comparison runs to: the earlier of the two contexts'
+    /// current times
+    pub horizon: f64,
+    /// The total outcome-free time gained in B up to the horizon. A person
+    /// is outcome-free until their outcome, or until the horizon if it's
+    /// later or never happens, so this counts delays and, for averted
+    /// outcomes, the time from the outcome in A to the horizon. Time lost
+    /// to added and advanced outcomes is subtracted.
+    pub exposure_time_gained: f64,
+}
+
+create_report_trait!(PairedSummary);
+
+/// The per-person outcomes of two paired scenarios, from [`paired_outcomes()`]
+#[derive(Clone, Debug)]
+pub struct PairedOutcomes {
+    /// One row per person, in order of `PersonId`
+    pub rows: Vec<PairedOutcome>,
+    pub summary: PairedSummary,
+}
+
+impl PairedOutcomes {
+    /// Send the rows to the `paired_outcomes` report and the summary to the
+    /// `paired_outcomes_summary` report of `context`. The report options
+    /// must be configured first.
+    ///
+    /// # Errors
+    /// Returns an `IxaError` if the reports can't be created.
+    pub fn write_reports(&self, context: &mut Context) -> Result<(), IxaError> {
+        context.add_report::<PairedOutcome>("paired_outcomes")?;
+        context.add_report::<PairedSummary>("paired_outcomes_summary")?;
+        for row in &self.rows {
+            context.send_report(row.clone());
+        }
+        context.send_report(self.summary.clone());
+        Ok(())
+    }
+
+    /// Write the rows to a CSV file at `path`, replacing it if it exists
+    ///
+    /// # Errors
+    /// Returns an `IxaError` if the file can't be written.
+    pub fn write_csv(&self, path: &Path) -> Result<(), IxaError> {
+        let mut writer = csv::Writer::from_path(path)?;
+        for row in &self.rows {
+            writer.serialize(row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// The recorded outcome times of one context
+fn outcome_times<'a>(
+    context: &'a Context,
+    key: &str,
+    name: &str,
+) -> Result<&'a HashMap<PersonId, f64>, IxaError> {
+    context
+        .get_data_container(OutcomePlugin)
+        .and_then(|data| data.get(key))
+        .ok_or_else(|| {
+            IxaError::from(format!(
+                "Outcome {key} isn't tracked in context {name}; \
+                 call track_outcome() before running it"
+            ))
+        })
+}
+
+/// Compare when `spec`'s outcome happened to each person in scenario `a`,
+/// the baseline, and scenario `b`, the intervention. The outcome must have
+/// been tracked in both contexts with [`ContextAnalysisExt::track_outcome()`].
+///
+/// # Errors
+/// Returns an `IxaError` if the outcome wasn't tracked in either context
+/// or the contexts don't have the same people.
+pub fn paired_outcomes<T: PersonProperty + 'static>(
+    a: &Context,
+    b: &Context,
+    spec: OutcomeSpec<T>,
+) -> Result<PairedOutcomes, IxaError> {
+    let key = spec.key();
+    let recorded_a = outcome_times(a, &key, "A")?;
+    let recorded_b = outcome_times(b, &key, "B")?;
+
+    let people = a.query_people(());
+    let people_b = b.query_people(());
+    if people.len() != people_b.len() {
+        return Err(IxaError::from(format!(
+            "Can't pair populations of different sizes: {} people in A and {} in B",
+            people.len(),
+            people_b.len()
+        )));
+    }
+    if let Some((person_a, person_b)) = people.iter().zip(&people_b).find(|(x, y)| x != y) {
+        return Err(IxaError::from(format!(
+            "Can't pair populations with different people: {person_a:?} in A and {person_b:?} in B"
+        )));
+    }
+
+    let horizon = a.get_current_time().min(b.get_current_time());
+    let mut summary = PairedSummary {
+        population: people.len(),
+        horizon,
+        ..PairedSummary::default()
+    };
+    let rows: Vec<PairedOutcome> = people
+        .into_iter()
+        .map(|person_id| {
+            let time_a = recorded_a.get(&person_id).copied();
+            let time_b = recorded_b.get(&person_id).copied();
+            let row = PairedOutcome {
+                person_id,
+                outcome_a: time_a.is_some(),
+                outcome_b: time_b.is_some(),
+                time_a,
+                time_b,
+                delta: time_a.zip(time_b).map(|(a, b)| b - a),
+            };
+
+            summary.outcomes_a += usize::from(row.outcome_a);
+            summary.outcomes_b += usize::from(row.outcome_b);
+            match row.classification() {
+                PairedClassification::Averted => summary.averted += 1,
+                PairedClassification::Added => summary.added += 1,
+                PairedClassification::Delayed => summary.delayed += 1,
+                PairedClassification::Advanced => summary.advanced += 1,
+                PairedClassification::Neither | PairedClassification::Unchanged => {}
+            }
+            let outcome_free = |time: Option<f64>| time.map_or(horizon, |t| t.min(horizon));
+            summary.exposure_time_gained += outcome_free(time_b) - outcome_free(time_a);
+            row
+        })
+        .collect();
+
+    Ok(PairedOutcomes { rows, summary })
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::{
+        paired_outcomes, ContextAnalysisExt, OutcomeSpec, PairedClassification, PairedOutcome,
+        PairedSummary,
+    };
+    use crate::{
+        define_person_property_with_default, Context, ContextPeopleExt, ContextReportExt, IxaError,
+        PersonId,
+    };
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    define_person_property_with_default!(Infected, bool, false);
+
+    const SPEC: OutcomeSpec<Infected> = OutcomeSpec {
+        property: Infected,
+        event_value: true,
+    };
+
+    // Runs a context with `infection_times.len()` people until time 10,
+    // infecting each person at their time, if any.
+    fn run_scenario(infection_times: &[Option<f64>]) -> Context {
+        let mut context = Context::new();
+        context.track_outcome(SPEC);
+        for time in infection_times {
+            let person = context.add_person(()).unwrap();
+            if let Some(time) = *time {
+                context.add_plan(time, move |context| {
+                    context.set_person_property(person, Infected, true);
+                });
+            }
+        }
+        context.add_plan(10.0, |_| {});
+        context.execute();
+        context
+    }
+
+    #[test]
+    fn classifications_and_summary() {
+        let a = run_scenario(&[None, Some(2.0), Some(3.0), Some(4.0), Some(5.0), None]);
+        let b = run_scenario(&[None, None, Some(6.0), Some(1.0), Some(5.0), Some(8.0)]);
+        let paired = paired_outcomes(&a, &b, SPEC).unwrap();
+
+        assert_eq!(
+            paired
+                .rows
+                .iter()
+                .map(PairedOutcome::classification)
+                .collect::<Vec<_>>(),
+            vec![
+                PairedClassification::Neither,
+                PairedClassification::Averted,
+                PairedClassification::Delayed,
+                PairedClassification::Advanced,
+                PairedClassification::Unchanged,
+                PairedClassification::Added,
+            ]
+        );
+        assert_eq!(
+            paired.rows[2],
+            PairedOutcome {
+                person_id: PersonId(2),
+                outcome_a: true,
+                outcome_b: true,
+                time_a: Some(3.0),
+                time_b: Some(6.0),
+                delta: Some(3.0),
+            }
+        );
+        assert_eq!(paired.rows[1].delta, None);
+        assert_eq!(
+            paired.summary,
+            PairedSummary {
+                population: 6,
+                outcomes_a: 4,
+                outcomes_b: 4,
+                averted: 1,
+                added: 1,
+                delayed: 1,
+                advanced: 1,
+                horizon: 10.0,
+                // averted 10 - 2, delayed 6 - 3, advanced 1 - 4, added 8 - 10
+                exposure_time_gained: 8.0 + 3.0 - 3.0 - 2.0,
+            }
+        );
+    }
+
+    #[test]
+    fn only_first_outcome_counts() {
+        let mut context = run_scenario(&[Some(1.0)]);
+        context.add_plan(11.0, |context| {
+            context.set_person_property(PersonId(0), Infected, false);
+        });
+        context.add_plan(12.0, |context| {
+            context.set_person_property(PersonId(0), Infected, true);
+        });
+        context.execute();
+        // Tracking the same outcome again doesn't reset it.
+        context.track_outcome(SPEC);
+
+        let paired = paired_outcomes(&context, &context, SPEC).unwrap();
+        assert_eq!(paired.rows[0].time_a, Some(1.0));
+        assert_eq!(paired.summary.horizon, 12.0);
+        assert_eq!(paired.summary.exposure_time_gained, 0.0);
+    }
+
+    #[test]
+    fn horizon_is_earlier_end() {
+        let a = run_scenario(&[Some(2.0)]);
+        let mut b = run_scenario(&[None]);
+        b.add_plan(20.0, |_| {});
+        b.execute();
+        let paired = paired_outcomes(&a, &b, SPEC).unwrap();
+        assert_eq!(paired.summary.horizon, 10.0);
+        assert_eq!(paired.summary.exposure_time_gained, 8.0);
+    }
+
+    #[test]
+    fn mismatched_populations() {
+        let a = run_scenario(&[None, None]);
+        let b = run_scenario(&[None]);
+        let result = paired_outcomes(&a, &b, SPEC);
+        assert!(matches!(
+            result,
+            Err(IxaError::IxaError(message))
+                if message == "Can't pair populations of different sizes: 2 people in A and 1 in B"
+        ));
+
+        let mut b = run_scenario(&[None, None, None]);
+        b.remove_person(PersonId(0)).unwrap();
+        let result = paired_outcomes(&a, &b, SPEC);
+        assert!(matches!(
+            result,
+            Err(IxaError::IxaError(message))
+                if message == "Can't pair populations with different people: Person 0 in A and Person 1 in B"
+        ));
+    }
+
+    #[test]
+    fn untracked_outcome() {
+        let a = run_scenario(&[None]);
+        let b = Context::new();
+        let result = paired_outcomes(&a, &b, SPEC);
+        assert!(matches!(
+            result,
+            Err(IxaError::IxaError(message))
+                if message == "Outcome Infected=true isn't tracked in context B; \
+                               call track_outcome() before running it"
+        ));
+    }
+
+    #[test]
+    fn write_reports_and_csv() {
+        let a = run_scenario(&[Some(2.0), None]);
+        let b = run_scenario(&[Some(4.5), None]);
+        let paired = paired_outcomes(&a, &b, SPEC).unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        paired.write_csv(&path.join("standalone.csv")).unwrap();
+        {
+            let mut context = Context::new();
+            context.report_options().directory(path.clone());
+            paired.write_reports(&mut context).unwrap();
+        }
+
+        let expected = "person_id,outcome_a,outcome_b,time_a,time_b,delta\n\
+                        0,true,true,2.0,4.5,2.5\n\
+                        1,false,false,,,\n";
+        for file in ["standalone.csv", "paired_outcomes.csv"] {
+            assert_eq!(std::fs::read_to_string(path.join(file)).unwrap(), expected);
+        }
+        assert_eq!(
+            std::fs::read_to_string(path.join("paired_outcomes_summary.csv")).unwrap(),
+            "population,outcomes_a,outcomes_b,averted,added,delayed,advanced,\
+             horizon,exposure_time_gained\n\
+             2,1,1,0,0,1,0,10.0,2.5\n"
+        );
+    }
+}
@@ -0,0 +1,1187 @@
+//! Saving the state of a simulation to a file and resuming it later.
+//!
+//! A long simulation can write a checkpoint with
+//! [`Context::write_checkpoint()`], e.g., at the end of every simulated
+//! month, so that if the run is killed it can be restored from the last
+//! checkpoint with [`Context::from_checkpoint()`], and `execute()`
+//! continues from exactly where it stopped. A checkpoint is a `bincode`
+//! file containing:
+//!
+//! * The current time
+//! * The people who have been added and removed
+//! * The values of the person properties, global properties and data
+//!   plugins that implement [`Checkpointable`]
+//! * The edges of the network types that implement [`Checkpointable`]
+//! * The pending plans, which must have been added with
+//!   [`Context::add_checkpointable_plan()`]
+//! * The base seed and the state of every random number generator
+//!
+//! Nothing is saved unless it opts in. Person properties, global
+//! properties, data plugins and edge types whose values implement
+//! `Serialize` and `Deserialize` opt in with
+//! [`define_checkpointable_person_property!()`],
+//! [`define_checkpointable_global_property!()`],
+//! [`define_checkpointable_data_plugin!()`] and
+//! [`define_checkpointable_edge_type!()`]. Plans can't be closures,
+//! which can't be serialized, so they are values of a type that implements
+//! [`CheckpointablePlan`] and is registered with
+//! [`register_checkpointable_plan!()`]. Writing a checkpoint fails with an
+//! error naming anything that hasn't opted in, rather than leaving it out:
+//! e.g., plans added with [`Context::add_plan()`], the periodic plans of
+//! reports, and neighborhoods, which can't be checkpointed. Indexes of
+//! person properties are rebuilt when they're next used, but reverse edge
+//! indexes need [`Context::enable_reverse_edges()`](crate::ContextNetworkExt::enable_reverse_edges)
+//! to be called again.
+//!
+//! Event subscriptions, reports, boundary hooks, the debugger and the Web
+//! API are part of the program rather than the state of the simulation,
+//! so they aren't saved. Set them up again in the `init` hook of
+//! [`Context::from_checkpoint_with_init()`], which is called once the
+//! state has been restored, but don't call `init_random()` or add people,
+//! which would change the restored state. It's usually the part of a
+//! model's setup that comes after creating the population:
+//!
+//! ```ignore
+//! fn subscribe(context: &mut Context) -> Result<(), IxaError> {
+//!     context.subscribe_to_event(handle_infection);
+//!     context.add_report::<Incidence>("incidence")?;
+//!     Ok(())
+//! }
+//!
+//! let mut context = Context::from_checkpoint_with_init(&path, subscribe)?;
+//! context.execute();
+//! ```
+//!
+//! A checkpoint isn't changed by restoring it, so many scenarios can be
+//! run from the same state, e.g., the end of a burn-in period, with an
+//! `init` hook for each that also sets up its intervention.
+//!
+//! Saved state is identified by the type names of the properties, plugins
+//! and plans, so a checkpoint can only be restored by the same version of
+//! the model that wrote it.
+use crate::context::{Context, DataPlugin};
+use crate::error::IxaError;
+use crate::global_properties::{self, ContextGlobalPropertiesExt, GlobalProperty};
+use crate::network::EdgeType;
+use crate::people::checkpoint as people_checkpoint;
+use crate::people::PersonProperty;
+use crate::plan::PlanId;
+use crate::random::{self, SavedRngs};
+use crate::{debugger, live_overrides, log, modes, network, report, value_map, web_api};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+/// The bytes at the start of every checkpoint file, which change whenever
+/// the format does
+const CHECKPOINT_HEADER: &[u8; 8] = b"IXACKPT1";
+
+/// A person property, global property, data plugin or edge type whose
+/// state is saved in checkpoints
+///
+/// This is usually implemented with
+/// [`define_checkpointable_person_property!()`],
+/// [`define_checkpointable_global_property!()`],
+/// [`define_checkpointable_data_plugin!()`] or
+/// [`define_checkpointable_edge_type!()`]. A type that implements it by
+/// hand must also be registered with [`register_checkpointable!()`], so
+/// that its state can be restored. The state is keyed by the `TypeId` of
+/// the implementing type, so it must be the property, plugin or edge type
+/// itself.
+pub trait Checkpointable: Any {
+    /// The name that identifies the saved state in checkpoint files
+    #[must_use]
+    fn checkpoint_name() -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Serializes the state, or returns `None` if there isn't any, e.g.,
+    /// because a global property hasn't been set.
+    ///
+    /// # Errors
+    /// Returns an error if the state can't be serialized.
+    fn save(context: &Context) -> Result<Option<Vec<u8>>, IxaError>;
+
+    /// Restores the state serialized by [`Checkpointable::save()`]
+    ///
+    /// # Errors
+    /// Returns an error if the data can't be deserialized.
+    fn restore(context: &mut Context, data: &[u8]) -> Result<(), IxaError>;
+}
+
+/// The data of a plan that can be saved in checkpoints, which is executed
+/// in place of a closure. Add plans with
+/// [`Context::add_checkpointable_plan()`], and register the type with
+/// [`register_checkpointable_plan!()`] so that it can be restored.
+pub trait CheckpointablePlan: Serialize + DeserializeOwned + 'static {
+    /// The name that identifies plans of this type in checkpoint files
+    #[must_use]
+    fn checkpoint_name() -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Executes the plan
+    fn execute(self, context: &mut Context);
+}
+
+type SaveFn = fn(&Context) -> Result<Option<Vec<u8>>, IxaError>;
+type RestoreFn = fn(&mut Context, &[u8]) -> Result<(), IxaError>;
+type RestorePlanFn = fn(&mut Context, f64, Vec<u8>) -> Result<PlanId, IxaError>;
+
+#[derive(Clone, Copy)]
+struct CheckpointableEntry {
+    type_id: TypeId,
+    name: &'static str,
+    save: SaveFn,
+    restore: RestoreFn,
+}
+
+// The global lists of checkpointable types and plan types that are
+// compiled in, which are registered at startup like global properties.
+static CHECKPOINTABLES: LazyLock<Mutex<Vec<CheckpointableEntry>>> =
+    LazyLock::new(|| Mutex::new(Vec::new()));
+
+static CHECKPOINTABLE_PLANS: LazyLock<Mutex<HashMap<&'static str, RestorePlanFn>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[doc(hidden)]
+#[allow(clippy::missing_panics_doc)]
+pub fn add_checkpointable<T: Checkpointable>() {
+    let mut checkpointables = CHECKPOINTABLES.lock().unwrap();
+    assert!(
+        checkpointables
+            .iter()
+            .all(|entry| entry.name != T::checkpoint_name()),
+        "Duplicate checkpointable {}",
+        T::checkpoint_name()
+    );
+    checkpointables.push(CheckpointableEntry {
+        type_id: TypeId::of::<T>(),
+        name: T::checkpoint_name(),
+        save: T::save,
+        restore: T::restore,
+    });
+}
+
+#[doc(hidden)]
+#[allow(clippy::missing_panics_doc)]
+pub fn add_checkpointable_plan<P: CheckpointablePlan>() {
+    let previous = CHECKPOINTABLE_PLANS
+        .lock()
+        .unwrap()
+        .insert(P::checkpoint_name(), restore_plan::<P>);
+    assert!(
+        previous.is_none(),
+        "Duplicate checkpointable plan {}",
+        P::checkpoint_name()
+    );
+}
+
+fn checkpointables() -> Vec<CheckpointableEntry> {
+    let mut checkpointables = CHECKPOINTABLES.lock().unwrap().clone();
+    checkpointables.sort_by_key(|entry| entry.name);
+    checkpointables
+}
+
+/// Registers a type that implements [`Checkpointable`] so that its state
+/// can be restored from checkpoints
+#[macro_export]
+macro_rules! register_checkpointable {
+    ($checkpointable:ident) => {
+        paste::paste! {
+            #[ctor::ctor]
+            fn [<$checkpointable:snake _register_checkpointable>]() {
+                $crate::checkpoint::add_checkpointable::<$checkpointable>();
+            }
+        }
+    };
+}
+pub use register_checkpointable;
+
+/// Registers a type that implements [`CheckpointablePlan`] so that its
+/// plans can be restored from checkpoints
+#[macro_export]
+macro_rules! register_checkpointable_plan {
+    ($plan:ident) => {
+        paste::paste! {
+            #[ctor::ctor]
+            fn [<$plan:snake _register_checkpointable_plan>]() {
+                $crate::checkpoint::add_checkpointable_plan::<$plan>();
+            }
+        }
+    };
+}
+pub use register_checkpointable_plan;
+
+/// Saves the values of a person property in checkpoints. The property's
+/// value type must implement `Serialize` and `Deserialize`.
+#[macro_export]
+macro_rules! define_checkpointable_person_property {
+    ($person_property:ident) => {
+        impl $crate::checkpoint::Checkpointable for $person_property {
+            fn save(
+                context: &$crate::context::Context,
+            ) -> Result<Option<Vec<u8>>, $crate::error::IxaError> {
+                $crate::checkpoint::save_person_property::<$person_property>(context)
+            }
+
+            fn restore(
+                context: &mut $crate::context::Context,
+                data: &[u8],
+            ) -> Result<(), $crate::error::IxaError> {
+                $crate::checkpoint::restore_person_property::<$person_property>(context, data)
+            }
+        }
+
+        $crate::checkpoint::register_checkpointable!($person_property);
+    };
+}
+pub use define_checkpointable_person_property;
+
+/// Saves the value of a global property in checkpoints. The property's
+/// value type must implement `Serialize` and `Deserialize`.
+#[macro_export]
+macro_rules! define_checkpointable_global_property {
+    ($global_property:ident) => {
+        impl $crate::checkpoint::Checkpointable for $global_property {
+            fn save(
+                context: &$crate::context::Context,
+            ) -> Result<Option<Vec<u8>>, $crate::error::IxaError> {
+                $crate::checkpoint::save_global_property::<$global_property>(context)
+            }
+
+            fn restore(
+                context: &mut $crate::context::Context,
+                data: &[u8],
+            ) -> Result<(), $crate::error::IxaError> {
+                $crate::checkpoint::restore_global_property::<$global_property>(context, data)
+            }
+        }
+
+        $crate::checkpoint::register_checkpointable!($global_property);
+    };
+}
+pub use define_checkpointable_global_property;
+
+/// Saves the data container of a data plugin in checkpoints. The data
+/// container must implement `Serialize` and `Deserialize`.
+#[macro_export]
+macro_rules! define_checkpointable_data_plugin {
+    ($data_plugin:ident) => {
+        impl $crate::checkpoint::Checkpointable for $data_plugin {
+            fn save(
+                context: &$crate::context::Context,
+            ) -> Result<Option<Vec<u8>>, $crate::error::IxaError> {
+                $crate::checkpoint::save_data_plugin(context, $data_plugin)
+            }
+
+            fn restore(
+                context: &mut $crate::context::Context,
+                data: &[u8],
+            ) -> Result<(), $crate::error::IxaError> {
+                $crate::checkpoint::restore_data_plugin(context, $data_plugin, data)
+            }
+        }
+
+        $crate::checkpoint::register_checkpointable!($data_plugin);
+    };
+}
+pub use define_checkpointable_data_plugin;
+
+/// Saves the edges of an edge type in checkpoints. The edge type's value
+/// type must implement `Serialize` and `Deserialize`.
+#[macro_export]
+macro_rules! define_checkpointable_edge_type {
+    ($edge_type:ident) => {
+        impl $crate::checkpoint::Checkpointable for $edge_type {
+            fn save(
+                context: &$crate::context::Context,
+            ) -> Result<Option<Vec<u8>>, $crate::error::IxaError> {
+                $crate::checkpoint::save_edge_type::<$edge_type>(context)
+            }
+
+            fn restore(
+                context: &mut $crate::context::Context,
+                data: &[u8],
+            ) -> Result<(), $crate::error::IxaError> {
+                $crate::checkpoint::restore_edge_type::<$edge_type>(context, data)
+            }
+        }
+
+        $crate::checkpoint::register_checkpointable!($edge_type);
+    };
+}
+pub use define_checkpointable_edge_type;
+
+#[doc(hidden)]
+#[allow(clippy::missing_errors_doc)]
+pub fn save_person_property<T: PersonProperty + 'static>(
+    context: &Context,
+) -> Result<Option<Vec<u8>>, IxaError>
+where
+    T::Value: Serialize,
+{
+    people_checkpoint::save_property::<T>(context)
+}
+
+#[doc(hidden)]
+#[allow(clippy::missing_errors_doc)]
+pub fn restore_person_property<T: PersonProperty + 'static>(
+    context: &mut Context,
+    data: &[u8],
+) -> Result<(), IxaError>
+where
+    T::Value: DeserializeOwned,
+{
+    people_checkpoint::restore_property::<T>(context, data)
+}
+
+#[doc(hidden)]
+#[allow(clippy::missing_errors_doc)]
+pub fn save_global_property<T: GlobalProperty + 'static>(
+    context: &Context,
+) -> Result<Option<Vec<u8>>, IxaError>
+where
+    T::Value: Serialize,
+{
+    match context.get_global_property_value(T::new()) {
+        Some(value) => Ok(Some(bincode::serialize(value)?)),
+        None => Ok(None),
+    }
+}
+
+#[doc(hidden)]
+#[allow(clippy::missing_errors_doc)]
+pub fn restore_global_property<T: GlobalProperty + 'static>(
+    context: &mut Context,
+    data: &[u8],
+) -> Result<(), IxaError>
+where
+    T::Value: DeserializeOwned,
+{
+    let value: T::Value = bincode::deserialize(data)?;
+    context.set_global_property_value(T::new(), value)
+}
+
+#[doc(hidden)]
+#[allow(clippy::missing_errors_doc)]
+pub fn save_data_plugin<T: DataPlugin>(
+    context: &Context,
+    data_plugin: T,
+) -> Result<Option<Vec<u8>>, IxaError>
+where
+    T::DataContainer: Serialize,
+{
+    match context.get_data_container(data_plugin) {
+        Some(data_container) => Ok(Some(bincode::serialize(data_container)?)),
+        None => Ok(None),
+    }
+}
+
+#[doc(hidden)]
+#[allow(clippy::missing_errors_doc)]
+pub fn restore_data_plugin<T: DataPlugin>(
+    context: &mut Context,
+    data_plugin: T,
+    data: &[u8],
+) -> Result<(), IxaError>
+where
+    T::DataContainer: DeserializeOwned,
+{
+    *context.get_data_container_mut(data_plugin) = bincode::deserialize(data)?;
+    Ok(())
+}
+
+#[doc(hidden)]
+#[allow(clippy::missing_errors_doc)]
+pub fn save_edge_type<T: EdgeType + 'static>(context: &Context) -> Result<Option<Vec<u8>>, IxaError>
+where
+    T::Value: Serialize,
+{
+    network::save_edges::<T>(context)
+}
+
+#[doc(hidden)]
+#[allow(clippy::missing_errors_doc)]
+pub fn restore_edge_type<T: EdgeType + 'static>(
+    context: &mut Context,
+    data: &[u8],
+) -> Result<(), IxaError>
+where
+    T::Value: DeserializeOwned,
+{
+    network::restore_edges::<T>(context, data)
+}
+
+/// A pending plan added with `add_checkpointable_plan()`
+#[derive(Clone, Serialize, Deserialize)]
+struct SavedPlan {
+    time: f64,
+    name: String,
+    data: Vec<u8>,
+}
+
+struct CheckpointData {
+    // The checkpointable plans that haven't run yet, keyed by a counter
+    // that orders them by when they were added. Cancelled plans stay
+    // here, and are left out of checkpoints because they aren't pending.
+    plans: HashMap<u64, (PlanId, SavedPlan)>,
+    plan_counter: u64,
+}
+
+crate::context::define_data_plugin!(
+    CheckpointPlugin,
+    CheckpointData,
+    CheckpointData {
+        plans: HashMap::new(),
+        plan_counter: 0,
+    }
+);
+
+#[derive(Serialize, Deserialize)]
+struct CheckpointFile {
+    time: f64,
+    people: Option<people_checkpoint::SavedPeople>,
+    rngs: Option<SavedRngs>,
+    // The saved state of each `Checkpointable`, keyed by checkpoint name
+    state: Vec<(String, Vec<u8>)>,
+    // The pending plans, in the order they were added
+    plans: Vec<SavedPlan>,
+}
+
+fn add_saved_plan(
+    context: &mut Context,
+    plan: SavedPlan,
+    execute: impl FnOnce(&mut Context) + 'static,
+) -> PlanId {
+    let data_container = context.get_data_container_mut(CheckpointPlugin);
+    let key = data_container.plan_counter;
+    data_container.plan_counter += 1;
+    let time = plan.time;
+    let plan_id = context.add_plan(time, move |context| {
+        context
+            .get_data_container_mut(CheckpointPlugin)
+            .plans
+            .remove(&key);
+        execute(context);
+    });
+    context
+        .get_data_container_mut(CheckpointPlugin)
+        .plans
+        .insert(key, (plan_id, plan));
+    plan_id
+}
+
+fn restore_plan<P: CheckpointablePlan>(
+    context: &mut Context,
+    time: f64,
+    data: Vec<u8>,
+) -> Result<PlanId, IxaError> {
+    let plan: P = bincode::deserialize(&data)?;
+    let saved = SavedPlan {
+        time,
+        name: P::checkpoint_name().to_string(),
+        data,
+    };
+    Ok(add_saved_plan(context, saved, move |context| {
+        plan.execute(context);
+    }))
+}
+
+/// Returns an error if writing a checkpoint would leave out any state
+fn check_checkpointable(
+    context: &Context,
+    checkpointables: &[CheckpointableEntry],
+) -> Result<(), IxaError> {
+    let callbacks = context.pending_callback_count();
+    if callbacks > 0 {
+        return Err(IxaError::IxaError(format!(
+            "Can't write a checkpoint with {callbacks} callbacks queued, e.g., \
+             for events that haven't been handled yet"
+        )));
+    }
+
+    let registered: HashSet<TypeId> = checkpointables.iter().map(|entry| entry.type_id).collect();
+    // Data plugins that are saved by this module, or that belong to the
+    // current run
+    let handled = [
+        TypeId::of::<CheckpointPlugin>(),
+        people_checkpoint::plugin_type_id(),
+        global_properties::plugin_type_id(),
+        random::plugin_type_id(),
+        debugger::plugin_type_id(),
+        debugger::watch_plugin_type_id(),
+        live_overrides::plugin_type_id(),
+        log::plugin_type_id(),
+        modes::plugin_type_id(),
+        network::plugin_type_id(),
+        report::plugin_type_id(),
+        value_map::plugin_type_id(),
+        web_api::plugin_type_id(),
+    ];
+    let mut data_plugins = context.data_plugin_types();
+    data_plugins.sort_by_key(|(_, name)| *name);
+    for (type_id, name) in data_plugins {
+        if !handled.contains(&type_id) && !registered.contains(&type_id) {
+            return Err(IxaError::IxaError(format!(
+                "Data plugin {name} isn't checkpointable; \
+                 opt in with define_checkpointable_data_plugin!()"
+            )));
+        }
+    }
+
+    let mut person_properties = people_checkpoint::stored_property_types(context);
+    person_properties.sort_by_key(|(_, name)| *name);
+    for (type_id, name) in person_properties {
+        if !registered.contains(&type_id) {
+            return Err(IxaError::IxaError(format!(
+                "Person property {name} isn't checkpointable; \
+                 opt in with define_checkpointable_person_property!()"
+            )));
+        }
+    }
+
+    let mut edge_types = network::edge_types(context);
+    edge_types.sort_by_key(|(_, name)| *name);
+    for (type_id, name) in edge_types {
+        if !registered.contains(&type_id) {
+            return Err(IxaError::IxaError(format!(
+                "Edge type {name} isn't checkpointable; \
+                 opt in with define_checkpointable_edge_type!()"
+            )));
+        }
+    }
+
+    let mut global_properties = global_properties::set_global_property_types(context);
+    global_properties.sort_by(|a, b| a.1.cmp(&b.1));
+    for (type_id, name) in global_properties {
+        if !registered.contains(&type_id) {
+            return Err(IxaError::IxaError(format!(
+                "Global property {name} isn't checkpointable; \
+                 opt in with define_checkpointable_global_property!()"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the pending checkpointable plans in the order they were added,
+/// or an error if any other plans are pending
+fn pending_plans(context: &Context) -> Result<Vec<SavedPlan>, IxaError> {
+    let pending = context.pending_plan_ids();
+    let mut plans: Vec<(u64, &SavedPlan)> = Vec::new();
+    if let Some(data_container) = context.get_data_container(CheckpointPlugin) {
+        let pending: HashSet<PlanId> = pending.iter().copied().collect();
+        plans = data_container
+            .plans
+            .iter()
+            .filter(|(_, (plan_id, _))| pending.contains(plan_id))
+            .map(|(key, (_, plan))| (*key, plan))
+            .collect();
+    }
+    let other_plans = pending.len() - plans.len();
+    if other_plans > 0 {
+        return Err(IxaError::IxaError(format!(
+            "Can't write a checkpoint with {other_plans} pending plans that weren't added \
+             with add_checkpointable_plan(), e.g., periodic plans for reports"
+        )));
+    }
+
+    let registered = CHECKPOINTABLE_PLANS.lock().unwrap();
+    plans.sort_by_key(|(key, _)| *key);
+    plans
+        .into_iter()
+        .map(|(_, plan)| {
+            if registered.contains_key(plan.name.as_str()) {
+                Ok(plan.clone())
+            } else {
+                Err(IxaError::IxaError(format!(
+                    "Plan type {} isn't registered; \
+                     register it with register_checkpointable_plan!()",
+                    plan.name
+                )))
+            }
+        })
+        .collect()
+}
+
+pub trait ContextCheckpointExt {
+    /// Writes the state of the simulation to a checkpoint file at `path`,
+    /// which can be restored with [`Context::from_checkpoint()`]. The
+    /// file is written to a temporary file first, so that an existing
+    /// checkpoint isn't lost if the program is killed while writing.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be written, if any state can't
+    /// be serialized, or if writing a checkpoint would leave out some of
+    /// the state: a data plugin, person property or global property that
+    /// or edge type that isn't [`Checkpointable`], a pending plan that wasn't added with
+    /// [`Context::add_checkpointable_plan()`], or a queued callback.
+    fn write_checkpoint(&self, path: &Path) -> Result<(), IxaError>;
+
+    /// Creates a context with the state saved in a checkpoint file by
+    /// [`Context::write_checkpoint()`]. Event subscriptions and reports
+    /// aren't saved, so they need to be set up again before calling
+    /// `execute()`, e.g., with [`Context::from_checkpoint_with_init()`].
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or isn't a checkpoint,
+    /// or if it has the state of a type that isn't registered as
+    /// checkpointable in this program.
+    fn from_checkpoint(path: &Path) -> Result<Context, IxaError>;
+
+    /// Like [`Context::from_checkpoint()`], but calls `init` with the
+    /// restored context, to set up the event subscriptions, reports and
+    /// other parts of the model that aren't saved in checkpoints.
+    ///
+    /// # Errors
+    /// Returns an error if the checkpoint can't be restored, or the error
+    /// returned by `init`.
+    fn from_checkpoint_with_init(
+        path: &Path,
+        init: impl FnOnce(&mut Context) -> Result<(), IxaError>,
+    ) -> Result<Context, IxaError>;
+
+    /// Add a plan to execute `plan` at the specified time, which is saved
+    /// in checkpoints. Plans are ordered like those added with
+    /// [`Context::add_plan()`].
+    ///
+    /// Returns a `PlanId` for the newly-added plan that can be used to
+    /// cancel it if needed.
+    ///
+    /// # Panics
+    /// Panics if time is in the past, infinite, or NaN, or if the plan
+    /// can't be serialized.
+    fn add_checkpointable_plan<P: CheckpointablePlan>(&mut self, time: f64, plan: P) -> PlanId;
+}
+
+impl ContextCheckpointExt for Context {
+    fn write_checkpoint(&self, path: &Path) -> Result<(), IxaError> {
+        let checkpointables = checkpointables();
+        check_checkpointable(self, &checkpointables)?;
+        let plans = pending_plans(self)?;
+
+        let mut state = Vec::new();
+        for entry in &checkpointables {
+            if let Some(data) = (entry.save)(self)? {
+                state.push((entry.name.to_string(), data));
+            }
+        }
+        let checkpoint = CheckpointFile {
+            time: self.get_current_time(),
+            people: people_checkpoint::save_people(self),
+            rngs: random::save_rngs(self)?,
+            state,
+            plans,
+        };
+
+        let mut bytes = CHECKPOINT_HEADER.to_vec();
+        bincode::serialize_into(&mut bytes, &checkpoint)?;
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, bytes)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    fn from_checkpoint(path: &Path) -> Result<Context, IxaError> {
+        let bytes = fs::read(path)?;
+        let Some(data) = bytes.strip_prefix(CHECKPOINT_HEADER) else {
+            return Err(IxaError::IxaError(format!(
+                "{} isn't an ixa checkpoint file",
+                path.display()
+            )));
+        };
+        let checkpoint: CheckpointFile = bincode::deserialize(data)?;
+
+        let mut context = Context::new();
+        context.set_current_time(checkpoint.time);
+        if let Some(rngs) = checkpoint.rngs {
+            random::restore_rngs(&mut context, rngs);
+        }
+        if let Some(people) = checkpoint.people {
+            people_checkpoint::restore_people(&mut context, people);
+        }
+
+        let checkpointables = checkpointables();
+        for (name, data) in checkpoint.state {
+            let entry = checkpointables
+                .iter()
+                .find(|entry| entry.name == name)
+                .ok_or_else(|| {
+                    IxaError::IxaError(format!(
+                        "Checkpoint has the state of {name}, \
+                         which isn't registered as checkpointable"
+                    ))
+                })?;
+            (entry.restore)(&mut context, &data)?;
+        }
+
+        let restore_plans = CHECKPOINTABLE_PLANS.lock().unwrap().clone();
+        for plan in checkpoint.plans {
+            let restore = restore_plans.get(plan.name.as_str()).ok_or_else(|| {
+                IxaError::IxaError(format!(
+                    "Checkpoint has a plan of type {}, which isn't registered",
+                    plan.name
+                ))
+            })?;
+            restore(&mut context, plan.time, plan.data)?;
+        }
+        Ok(context)
+    }
+
+    fn from_checkpoint_with_init(
+        path: &Path,
+        init: impl FnOnce(&mut Context) -> Result<(), IxaError>,
+    ) -> Result<Context, IxaError> {
+        let mut context = Context::from_checkpoint(path)?;
+        init(&mut context)?;
+        Ok(context)
+    }
+
+    fn add_checkpointable_plan<P: CheckpointablePlan>(&mut self, time: f64, plan: P) -> PlanId {
+        let saved = SavedPlan {
+            time,
+            name: P::checkpoint_name().to_string(),
+            data: bincode::serialize(&plan).expect("Plan can't be serialized"),
+        };
+        add_saved_plan(self, saved, move |context| plan.execute(context))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::*;
+    use crate::{
+        define_data_plugin, define_global_property, define_person_property,
+        define_person_property_with_default, define_rng, ContextPeopleExt, ContextRandomExt,
+        PersonId,
+    };
+    use rand::RngCore;
+    use tempfile::tempdir;
+
+    define_person_property!(Age, u8);
+    define_person_property_with_default!(Infected, bool, false);
+    define_checkpointable_person_property!(Age);
+    define_checkpointable_person_property!(Infected);
+
+    define_global_property!(TransmissionRate, f64);
+    define_checkpointable_global_property!(TransmissionRate);
+
+    define_data_plugin!(InfectionLog, Vec<(f64, usize, u64)>, Vec::new());
+    define_checkpointable_data_plugin!(InfectionLog);
+
+    define_data_plugin!(UncheckpointedCounter, usize, 0);
+
+    define_rng!(CheckpointRng);
+
+    #[derive(Serialize, Deserialize)]
+    struct Infect {
+        person: PersonId,
+    }
+
+    impl CheckpointablePlan for Infect {
+        fn execute(self, context: &mut Context) {
+            context.set_person_property(self.person, Infected, true);
+            let draw = context.sample(CheckpointRng, RngCore::next_u64);
+            let time = context.get_current_time();
+            context
+                .get_data_container_mut(InfectionLog)
+                .push((time, self.person.0, draw));
+
+            let rate = *context.get_global_property_value(TransmissionRate).unwrap();
+            let next = PersonId(self.person.0 + 1);
+            if context.person_exists(next) {
+                context.add_checkpointable_plan(time + rate, Infect { person: next });
+            }
+        }
+    }
+    register_checkpointable_plan!(Infect);
+
+    fn setup() -> Context {
+        let mut context = Context::new();
+        context.init_random(42);
+        context
+            .set_global_property_value(TransmissionRate, 1.5)
+            .unwrap();
+        for age in 0..10 {
+            context.add_person((Age, age)).unwrap();
+        }
+        context.add_checkpointable_plan(
+            0.0,
+            Infect {
+                person: PersonId(0),
+            },
+        );
+        context
+    }
+
+    #[test]
+    fn resume_matches_uninterrupted_run() {
+        let mut expected = setup();
+        expected.execute();
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        let mut context = setup();
+        context.run_until(5.0);
+        context.write_checkpoint(&path).unwrap();
+        drop(context);
+
+        let mut context = Context::from_checkpoint(&path).unwrap();
+        assert_eq!(context.get_current_time(), 5.0);
+        assert_eq!(context.get_current_population(), 10);
+        context.execute();
+
+        assert_eq!(
+            context.get_data_container(InfectionLog),
+            expected.get_data_container(InfectionLog)
+        );
+        assert_eq!(context.get_current_time(), expected.get_current_time());
+        assert_eq!(
+            context.query_people((Infected, true)).len(),
+            expected.query_people((Infected, true)).len()
+        );
+        for person in context.query_people(()) {
+            assert_eq!(
+                context.get_person_property(person, Age),
+                expected.get_person_property(person, Age)
+            );
+        }
+    }
+
+    #[test]
+    fn resume_from_repeated_checkpoints() {
+        let mut expected = setup();
+        expected.execute();
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        let mut context = setup();
+        for time in [2.0, 4.0, 8.0] {
+            context.run_until(time);
+            context.write_checkpoint(&path).unwrap();
+            context = Context::from_checkpoint(&path).unwrap();
+        }
+        context.execute();
+        assert_eq!(
+            context.get_data_container(InfectionLog),
+            expected.get_data_container(InfectionLog)
+        );
+    }
+
+    #[test]
+    fn cancelled_plans_are_not_saved() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        let mut context = setup();
+        let plan_id = context.add_checkpointable_plan(
+            1.0,
+            Infect {
+                person: PersonId(5),
+            },
+        );
+        context.cancel_plan(&plan_id);
+        context.write_checkpoint(&path).unwrap();
+
+        let context = Context::from_checkpoint(&path).unwrap();
+        assert_eq!(context.pending_plan_ids().len(), 1);
+    }
+
+    #[test]
+    fn unregistered_data_plugin_is_an_error() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        let mut context = setup();
+        *context.get_data_container_mut(UncheckpointedCounter) += 1;
+        let result = context.write_checkpoint(&path);
+        match result {
+            Err(IxaError::IxaError(message)) => {
+                assert!(message.contains("UncheckpointedCounter"), "{message}");
+            }
+            _ => panic!("Expected an error for the data plugin"),
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn closure_plan_is_an_error() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        let mut context = setup();
+        context.add_plan(1.0, |_| {});
+        let result = context.write_checkpoint(&path);
+        match result {
+            Err(IxaError::IxaError(message)) => {
+                assert!(message.contains("1 pending plans"), "{message}");
+            }
+            _ => panic!("Expected an error for the plan"),
+        }
+    }
+
+    #[test]
+    fn unknown_state_is_an_error() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        let checkpoint = CheckpointFile {
+            time: 0.0,
+            people: None,
+            rngs: None,
+            state: vec![("model::Unknown".to_string(), Vec::new())],
+            plans: Vec::new(),
+        };
+        let mut bytes = CHECKPOINT_HEADER.to_vec();
+        bincode::serialize_into(&mut bytes, &checkpoint).unwrap();
+        fs::write(&path, bytes).unwrap();
+
+        match Context::from_checkpoint(&path) {
+            Err(IxaError::IxaError(message)) => {
+                assert!(message.contains("model::Unknown"), "{message}");
+            }
+            _ => panic!("Expected an error for the unknown state"),
+        }
+    }
+
+    #[test]
+    fn not_a_checkpoint_is_an_error() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        fs::write(&path, "t,count\n").unwrap();
+        assert!(matches!(
+            Context::from_checkpoint(&path),
+            Err(IxaError::IxaError(_))
+        ));
+    }
+
+    mod sir {
+        use super::*;
+        use crate::{define_edge_type, ContextNetworkExt, PersonPropertyChangeEvent};
+        use rand_distr::Exp;
+
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        pub enum SirStatus {
+            S,
+            I,
+            R,
+        }
+        define_person_property!(Status, SirStatus);
+        define_checkpointable_person_property!(Status);
+
+        define_global_property!(ContactRate, f64);
+        define_checkpointable_global_property!(ContactRate);
+
+        define_edge_type!(Contact, ());
+        define_checkpointable_edge_type!(Contact);
+
+        define_data_plugin!(SirLog, Vec<(f64, usize, SirStatus)>, Vec::new());
+        define_checkpointable_data_plugin!(SirLog);
+
+        define_rng!(SirRng);
+
+        const RECOVERY_RATE: f64 = 0.1;
+
+        #[derive(Serialize, Deserialize)]
+        struct Seed;
+
+        impl CheckpointablePlan for Seed {
+            // Start with several infections so that the epidemic doesn't
+            // die out early by chance.
+            fn execute(self, context: &mut Context) {
+                for person in [0, 100, 200] {
+                    context.set_person_property(PersonId(person), Status, SirStatus::I);
+                }
+            }
+        }
+        register_checkpointable_plan!(Seed);
+
+        #[derive(Serialize, Deserialize)]
+        struct Recover {
+            person: PersonId,
+        }
+
+        impl CheckpointablePlan for Recover {
+            fn execute(self, context: &mut Context) {
+                context.set_person_property(self.person, Status, SirStatus::R);
+            }
+        }
+        register_checkpointable_plan!(Recover);
+
+        #[derive(Serialize, Deserialize)]
+        struct TransmitFrom {
+            person: PersonId,
+        }
+
+        impl CheckpointablePlan for TransmitFrom {
+            fn execute(self, context: &mut Context) {
+                if context.get_person_property(self.person, Status) != SirStatus::I {
+                    return;
+                }
+                let edge = context
+                    .select_random_edge::<Contact, _>(SirRng, self.person)
+                    .unwrap();
+                if context.get_person_property(edge.neighbor, Status) == SirStatus::S {
+                    context.set_person_property(edge.neighbor, Status, SirStatus::I);
+                }
+                schedule_contact(context, self.person);
+            }
+        }
+        register_checkpointable_plan!(TransmitFrom);
+
+        fn schedule_contact(context: &mut Context, person: PersonId) {
+            let rate = *context.get_global_property_value(ContactRate).unwrap();
+            let delay = context.sample_distr(SirRng, Exp::new(rate).unwrap());
+            let time = context.get_current_time() + delay;
+            context.add_checkpointable_plan(time, TransmitFrom { person });
+        }
+
+        fn on_status_change(context: &mut Context, event: PersonPropertyChangeEvent<Status>) {
+            let time = context.get_current_time();
+            context
+                .get_data_container_mut(SirLog)
+                .push((time, event.person_id.0, event.current));
+            if event.current == SirStatus::I {
+                let delay = context.sample_distr(SirRng, Exp::new(RECOVERY_RATE).unwrap());
+                context.add_checkpointable_plan(
+                    time + delay,
+                    Recover {
+                        person: event.person_id,
+                    },
+                );
+                schedule_contact(context, event.person_id);
+            }
+        }
+
+        // The part of the model that isn't saved in checkpoints
+        #[allow(clippy::unnecessary_wraps)]
+        fn init(context: &mut Context) -> Result<(), IxaError> {
+            context.subscribe_to_event(on_status_change);
+            Ok(())
+        }
+
+        // A ring where everyone is in contact with the two people on either
+        // side, plus some random shortcuts.
+        fn setup() -> Context {
+            let n = 300;
+            let mut context = Context::new();
+            context.init_random(7);
+            context.set_global_property_value(ContactRate, 0.5).unwrap();
+            let people: Vec<PersonId> = (0..n)
+                .map(|_| context.add_person((Status, SirStatus::S)).unwrap())
+                .collect();
+            for i in 0..n {
+                for j in 1..=2 {
+                    context
+                        .add_edge_bidi::<Contact>(people[i], people[(i + j) % n], 1.0, ())
+                        .unwrap();
+                }
+            }
+            for _ in 0..30 {
+                let a = people[context.sample_range(SirRng, 0..n)];
+                let b = people[context.sample_range(SirRng, 0..n)];
+                if a != b && context.get_edge::<Contact>(a, b).is_none() {
+                    context.add_edge_bidi::<Contact>(a, b, 1.0, ()).unwrap();
+                }
+            }
+            init(&mut context).unwrap();
+            context.add_checkpointable_plan(0.0, Seed);
+            context
+        }
+
+        fn statuses(context: &Context) -> Vec<SirStatus> {
+            context
+                .query_people(())
+                .into_iter()
+                .map(|person| context.get_person_property(person, Status))
+                .collect()
+        }
+
+        #[test]
+        fn resume_at_50_matches_uninterrupted_run() {
+            let mut expected = setup();
+            expected.execute();
+            let expected_log = expected.get_data_container(SirLog).unwrap();
+            // The epidemic is still going at the time of the checkpoint.
+            assert!(expected_log.iter().any(|(time, _, _)| *time < 50.0));
+            assert!(expected_log.iter().any(|(time, _, _)| *time > 50.0));
+
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("burn_in.bin");
+            let mut context = setup();
+            context.run_until(50.0);
+            context.write_checkpoint(&path).unwrap();
+            drop(context);
+
+            // Run two scenarios from the same checkpoint.
+            for _ in 0..2 {
+                let mut context = Context::from_checkpoint_with_init(&path, init).unwrap();
+                assert_eq!(context.get_current_time(), 50.0);
+                assert_eq!(
+                    context.get_edges::<Contact>(PersonId(0)),
+                    expected.get_edges::<Contact>(PersonId(0))
+                );
+                context.execute();
+                assert_eq!(context.get_data_container(SirLog), Some(expected_log));
+                assert_eq!(statuses(&context), statuses(&expected));
+                assert_eq!(context.get_current_time(), expected.get_current_time());
+            }
+        }
+
+        #[test]
+        fn init_error_is_returned() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("burn_in.bin");
+            let mut context = setup();
+            context.run_until(10.0);
+            context.write_checkpoint(&path).unwrap();
+            let result = Context::from_checkpoint_with_init(&path, |_| {
+                Err(IxaError::IxaError(String::from("bad scenario")))
+            });
+            assert!(
+                matches!(result, Err(IxaError::IxaError(message)) if message == "bad scenario")
+            );
+        }
+
+        #[test]
+        fn restored_edges_are_removed_with_people() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("burn_in.bin");
+            setup().write_checkpoint(&path).unwrap();
+            let mut context = Context::from_checkpoint(&path).unwrap();
+            context.remove_person(PersonId(1)).unwrap();
+            context.execute();
+            assert!(context
+                .get_edge::<Contact>(PersonId(0), PersonId(1))
+                .is_none());
+            assert!(context
+                .get_edge::<Contact>(PersonId(0), PersonId(2))
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn unregistered_edge_type_is_an_error() {
+        crate::define_edge_type!(Uncheckpointed, ());
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("checkpoint.bin");
+        let mut context = setup();
+        crate::ContextNetworkExt::add_edge::<Uncheckpointed>(
+            &mut context,
+            PersonId(0),
+            PersonId(1),
+            1.0,
+            (),
+        )
+        .unwrap();
+        match context.write_checkpoint(&path) {
+            Err(IxaError::IxaError(message)) => {
+                assert!(message.contains("Uncheckpointed"), "{message}");
+            }
+            _ => panic!("Expected an error for the edge type"),
+        }
+    }
+}
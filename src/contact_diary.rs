@@ -0,0 +1,586 @@
+//! Simulated contact diaries, for comparing the contact structure of a
+//! model with contact surveys such as POLYMOD.
+//!
+//! On each of a list of days, a random sample of people each "fill in a
+//! diary": a row for each of their contacts at the end of that day, with
+//! where the contact came from, its weight, and any person properties of
+//! the two people. Contacts come from [`ContactSource`]s, either the edges
+//! of a network edge type or the people sharing the value of a property,
+//! such as a household or school id.
+//!
+//! ```
+//! use ixa::contact_diary::{ContactDiaryConfig, ContactSource, ContextContactDiaryExt};
+//! use ixa::{define_edge_type, define_person_property, define_rng, Context, ContextPeopleExt};
+//!
+//! define_edge_type!(Household, ());
+//! define_person_property!(Age, u8);
+//! define_person_property!(SchoolId, u32);
+//! define_rng!(DiaryRng);
+//!
+//! let mut context = Context::new();
+//! context.add_person(((Age, 8), (SchoolId, 1))).unwrap();
+//! context.index_property(SchoolId);
+//! let config = ContactDiaryConfig {
+//!     sample_fraction: 0.05,
+//!     days: vec![10.0, 20.0],
+//!     sources: vec![
+//!         ContactSource::edges::<Household>("household"),
+//!         ContactSource::shared_property(SchoolId, "school"),
+//!     ],
+//!     ego_properties: vec![String::from("Age")],
+//!     alter_properties: vec![String::from("Age")],
+//!     ..ContactDiaryConfig::default()
+//! };
+//! # let dir = tempfile::tempdir().unwrap();
+//! # context.report_options().directory(dir.path().to_path_buf());
+//! # use ixa::ContextReportExt;
+//! context
+//!     .add_contact_diary_report("contact_diary", DiaryRng, config)
+//!     .unwrap();
+//! ```
+//!
+//! The people who fill in diaries are chosen with the random number
+//! generator passed to the report. Give it one that the rest of the model
+//! doesn't use, so that adding a diary report doesn't change the numbers
+//! the model draws; the same seed then gives the same diaries.
+//!
+//! Each contact is reported once per diary, even if it comes from more
+//! than one source: it's attributed to the first source in
+//! [`ContactDiaryConfig::sources`] that has it, with that source's weight.
+//! List the most specific sources first, e.g., households before schools.
+use crate::context::{Context, ExecutionPhase};
+use crate::error::IxaError;
+use crate::network::{ContextNetworkExt, EdgeType};
+use crate::people::external_api::ContextPeopleExtCrate;
+use crate::people::{ContextPeopleExt, PersonId, PersonProperty};
+use crate::random::{ContextRandomExt, RngId};
+use crate::report::ContextReportExt;
+use crate::{define_data_plugin, warn};
+use rand::Rng;
+use std::any::TypeId;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+type ContactsFn = dyn Fn(&Context, PersonId) -> Vec<(PersonId, f32)>;
+type VolumeFn = dyn Fn(&Context) -> usize;
+
+/// Where the contacts in a diary come from.
+pub struct ContactSource {
+    name: String,
+    // The contacts of a person, with weights
+    contacts: Box<ContactsFn>,
+    // The total number of contacts of everyone, for estimating the size of
+    // the report
+    volume: Box<VolumeFn>,
+}
+
+impl ContactSource {
+    /// The people a person has an edge of type `T` to, with the weights of
+    /// the edges.
+    #[must_use]
+    pub fn edges<T: EdgeType + 'static>(name: &str) -> Self {
+        ContactSource {
+            name: name.to_string(),
+            contacts: Box::new(|context, person_id| {
+                context
+                    .get_edges::<T>(person_id)
+                    .iter()
+                    .map(|edge| (edge.neighbor, edge.weight))
+                    .collect()
+            }),
+            volume: Box::new(|context| {
+                let edges = context.count_edges_total::<T>();
+                if T::UNDIRECTED {
+                    2 * edges
+                } else {
+                    edges
+                }
+            }),
+        }
+    }
+
+    /// The other people with the same value of `property`, with a weight
+    /// of 1, e.g., the people in the same school for a school id. This
+    /// queries `property` for each diary, so index it (see
+    /// [`Context::index_property()`]) unless the population is small.
+    #[must_use]
+    pub fn shared_property<T: PersonProperty + 'static>(property: T, name: &str) -> Self {
+        ContactSource {
+            name: name.to_string(),
+            contacts: Box::new(move |context, person_id| {
+                let value = context.get_person_property(person_id, property);
+                context
+                    .query_people((property, value))
+                    .into_iter()
+                    .filter(|other| *other != person_id)
+                    .map(|other| (other, 1.0))
+                    .collect()
+            }),
+            volume: Box::new(move |context| {
+                context
+                    .distinct_property_values(property)
+                    .iter()
+                    .map(|(_, count)| count * (count - 1))
+                    .sum()
+            }),
+        }
+    }
+}
+
+/// What to put in a contact diary report (see
+/// [`ContextContactDiaryExt::add_contact_diary_report()`])
+pub struct ContactDiaryConfig {
+    /// The fraction of people who fill in a diary on each day, chosen
+    /// independently each day
+    pub sample_fraction: f64,
+    /// The times to take diaries at, at the end of each
+    pub days: Vec<f64>,
+    /// Where contacts come from, in order of precedence
+    pub sources: Vec<ContactSource>,
+    /// The names of the properties of the person filling in the diary to
+    /// include, as columns `ego_<name>`
+    pub ego_properties: Vec<String>,
+    /// The names of the properties of each contact to include, as columns
+    /// `alter_<name>`
+    pub alter_properties: Vec<String>,
+    /// Log a warning before the first diary if the report is expected to
+    /// have more rows than this
+    pub warn_above_rows: usize,
+}
+
+impl Default for ContactDiaryConfig {
+    fn default() -> Self {
+        ContactDiaryConfig {
+            sample_fraction: 0.01,
+            days: Vec::new(),
+            sources: Vec::new(),
+            ego_properties: Vec::new(),
+            alter_properties: Vec::new(),
+            warn_above_rows: 1_000_000,
+        }
+    }
+}
+
+struct ContactDiaryData {
+    // The expected number of rows, estimated before the first diary
+    estimated_rows: Option<f64>,
+    volume_warnings: usize,
+}
+
+define_data_plugin!(
+    ContactDiaryPlugin,
+    ContactDiaryData,
+    ContactDiaryData {
+        estimated_rows: None,
+        volume_warnings: 0,
+    }
+);
+
+// The type the report file is keyed by
+struct ContactDiaryReport;
+
+fn validate(context: &Context, config: &ContactDiaryConfig) -> Result<(), IxaError> {
+    if !(0.0..=1.0).contains(&config.sample_fraction) {
+        return Err(IxaError::IxaError(format!(
+            "Contact diary sample fraction {} is not between 0 and 1",
+            config.sample_fraction
+        )));
+    }
+    if config.sources.is_empty() {
+        return Err(IxaError::IxaError(String::from(
+            "Contact diary needs at least one contact source",
+        )));
+    }
+    let mut names = HashSet::new();
+    for source in &config.sources {
+        if !names.insert(&source.name) {
+            return Err(IxaError::IxaError(format!(
+                "Duplicate contact source '{}'",
+                source.name
+            )));
+        }
+    }
+    for day in &config.days {
+        if !day.is_finite() || *day < context.get_current_time() {
+            return Err(IxaError::IxaError(format!(
+                "Contact diary day {day} is in the past or not finite"
+            )));
+        }
+    }
+    let known = context.get_property_names();
+    for name in config.ego_properties.iter().chain(&config.alter_properties) {
+        if !known.contains(name) {
+            return Err(IxaError::IxaError(format!(
+                "No property '{name}'; properties must be in use before the report is added"
+            )));
+        }
+    }
+    Ok(())
+}
+
+// The expected number of rows over all the days, before removing
+// contacts that come from more than one source.
+#[allow(clippy::cast_precision_loss)]
+fn estimate_rows(context: &Context, config: &ContactDiaryConfig) -> f64 {
+    let contacts: usize = config
+        .sources
+        .iter()
+        .map(|source| (source.volume)(context))
+        .sum();
+    config.days.len() as f64 * config.sample_fraction * contacts as f64
+}
+
+fn write_diaries<R: RngId + 'static>(context: &mut Context, rng_id: R, config: &ContactDiaryConfig)
+where
+    R::RngType: Rng,
+{
+    if context
+        .get_data_container(ContactDiaryPlugin)
+        .is_none_or(|data| data.estimated_rows.is_none())
+    {
+        let estimated = estimate_rows(context, config);
+        let data = context.get_data_container_mut(ContactDiaryPlugin);
+        data.estimated_rows = Some(estimated);
+        #[allow(clippy::cast_precision_loss)]
+        if estimated > config.warn_above_rows as f64 {
+            warn!(
+                "Contact diary report is expected to have about {estimated:.0} rows, more than {}",
+                config.warn_above_rows
+            );
+            data.volume_warnings += 1;
+        }
+    }
+
+    let day = context.get_current_time().to_string();
+    let egos: Vec<PersonId> = context
+        .query_people(())
+        .into_iter()
+        .filter(|_| context.sample_bool(rng_id, config.sample_fraction))
+        .collect();
+    let type_id = TypeId::of::<ContactDiaryReport>();
+    let mut rows = 0;
+    {
+        let mut writer = context.get_writer(type_id);
+        for ego in egos {
+            let mut seen = HashSet::from([ego]);
+            for source in &config.sources {
+                for (alter, weight) in (source.contacts)(context, ego) {
+                    if !seen.insert(alter) {
+                        continue;
+                    }
+                    let mut row = vec![
+                        day.clone(),
+                        ego.to_string(),
+                        alter.to_string(),
+                        source.name.clone(),
+                        weight.to_string(),
+                    ];
+                    for (person, names) in [
+                        (ego, &config.ego_properties),
+                        (alter, &config.alter_properties),
+                    ] {
+                        for name in names {
+                            row.push(context.get_person_property_by_name(name, person).unwrap());
+                        }
+                    }
+                    writer.write_record(&row).expect("Failed to write row");
+                    rows += 1;
+                }
+            }
+        }
+    }
+    for _ in 0..rows {
+        context.count_report_row(type_id);
+    }
+}
+
+pub trait ContextContactDiaryExt {
+    /// Add a report of contact diaries as described by `config`, with one
+    /// row per contact per diary and columns `day`, `ego`, `alter`,
+    /// `source`, `weight` and then the requested properties. Diaries are
+    /// taken in the last phase of each day, so they see the contacts as
+    /// they are at the end of it. The people who fill in diaries are drawn
+    /// from `rng_id`. There can only be one contact diary report.
+    ///
+    /// Before the first diary, the number of rows is estimated from the
+    /// population and the number of contacts from each source, and a
+    /// warning is logged if it's more than `config.warn_above_rows`.
+    ///
+    /// # Errors
+    /// Returns [`IxaError`] if the sample fraction isn't between 0 and 1,
+    /// there are no sources or two with the same name, a day is in the
+    /// past, a property name isn't a property in use, or the report file
+    /// can't be created.
+    fn add_contact_diary_report<R: RngId + 'static>(
+        &mut self,
+        short_name: &str,
+        rng_id: R,
+        config: ContactDiaryConfig,
+    ) -> Result<(), IxaError>
+    where
+        R::RngType: Rng;
+}
+
+impl ContextContactDiaryExt for Context {
+    fn add_contact_diary_report<R: RngId + 'static>(
+        &mut self,
+        short_name: &str,
+        rng_id: R,
+        config: ContactDiaryConfig,
+    ) -> Result<(), IxaError>
+    where
+        R::RngType: Rng,
+    {
+        validate(self, &config)?;
+        let type_id = TypeId::of::<ContactDiaryReport>();
+        self.add_report_by_type_id(type_id, short_name)?;
+        {
+            let mut writer = self.get_writer(type_id);
+            let mut header: Vec<String> = ["day", "ego", "alter", "source", "weight"]
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            header.extend(
+                config
+                    .ego_properties
+                    .iter()
+                    .map(|name| format!("ego_{name}")),
+            );
+            header.extend(
+                config
+                    .alter_properties
+                    .iter()
+                    .map(|name| format!("alter_{name}")),
+            );
+            writer
+                .write_record(&header)
+                .expect("Failed to write header");
+        }
+
+        let config = Rc::new(config);
+        for &day in &config.days {
+            let config = Rc::clone(&config);
+            self.add_plan_with_phase(
+                day,
+                move |context| write_diaries(context, rng_id, &config),
+                ExecutionPhase::Last,
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ContactDiaryConfig, ContactDiaryPlugin, ContactSource, ContextContactDiaryExt};
+    use crate::{
+        define_edge_type, define_person_property, define_rng, Context, ContextNetworkExt,
+        ContextPeopleExt, ContextRandomExt, ContextReportExt, IxaError,
+    };
+    use std::path::Path;
+
+    define_edge_type!(Household, ());
+    define_edge_type!(Work, (), undirected);
+    define_person_property!(Age, u8);
+    define_person_property!(SchoolId, u8);
+    define_rng!(ModelRng);
+    define_rng!(DiaryRng);
+
+    // Four people: 0 and 1 share a household and a school, 0 and 2 work
+    // together, and 2 and 3 share a school.
+    fn setup(dir: &Path) -> Context {
+        let mut context = Context::new();
+        context.init_random(42);
+        context.report_options().directory(dir.to_path_buf());
+        let people: Vec<_> = [(30, 1), (8, 1), (40, 2), (9, 2)]
+            .iter()
+            .map(|&(age, school)| {
+                context
+                    .add_person(((Age, age), (SchoolId, school)))
+                    .unwrap()
+            })
+            .collect();
+        context
+            .add_edge_bidi::<Household>(people[0], people[1], 2.0, ())
+            .unwrap();
+        context
+            .add_edge::<Work>(people[0], people[2], 0.5, ())
+            .unwrap();
+        context
+    }
+
+    fn config(sample_fraction: f64) -> ContactDiaryConfig {
+        ContactDiaryConfig {
+            sample_fraction,
+            days: vec![1.0],
+            sources: vec![
+                ContactSource::edges::<Household>("household"),
+                ContactSource::edges::<Work>("work"),
+                ContactSource::shared_property(SchoolId, "school"),
+            ],
+            ego_properties: vec![String::from("Age")],
+            alter_properties: vec![String::from("Age"), String::from("SchoolId")],
+            ..ContactDiaryConfig::default()
+        }
+    }
+
+    fn read_rows(path: &Path) -> Vec<Vec<String>> {
+        csv::Reader::from_path(path)
+            .unwrap()
+            .records()
+            .map(|record| record.unwrap().iter().map(String::from).collect())
+            .collect()
+    }
+
+    #[test]
+    fn diaries_attribute_and_deduplicate_contacts() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut context = setup(dir.path());
+            context
+                .add_contact_diary_report("diary", DiaryRng, config(1.0))
+                .unwrap();
+            context.execute();
+        }
+        let path = dir.path().join("diary.csv");
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec![
+                "day",
+                "ego",
+                "alter",
+                "source",
+                "weight",
+                "ego_Age",
+                "alter_Age",
+                "alter_SchoolId"
+            ]
+        );
+        // 0 and 1 are in both a household and a school, which is reported
+        // as the household, the first source. The undirected work edge is
+        // in the diaries of both 0 and 2.
+        let expected = [
+            ["1", "0", "1", "household", "2", "30", "8", "1"],
+            ["1", "0", "2", "work", "0.5", "30", "40", "2"],
+            ["1", "1", "0", "household", "2", "8", "30", "1"],
+            ["1", "2", "0", "work", "0.5", "40", "30", "1"],
+            ["1", "2", "3", "school", "1", "40", "9", "2"],
+            ["1", "3", "2", "school", "1", "9", "40", "2"],
+        ];
+        assert_eq!(read_rows(&path), expected);
+    }
+
+    fn diary_with_seed(seed: u64) -> (Vec<Vec<String>>, Vec<usize>) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut draws = Vec::new();
+        {
+            let mut context = Context::new();
+            context.init_random(seed);
+            context.report_options().directory(dir.path().to_path_buf());
+            for i in 0..200 {
+                context
+                    .add_person(((Age, i % 90), (SchoolId, i % 20)))
+                    .unwrap();
+            }
+            context.index_property(SchoolId);
+            context
+                .add_contact_diary_report(
+                    "diary",
+                    DiaryRng,
+                    ContactDiaryConfig {
+                        sample_fraction: 0.1,
+                        days: vec![1.0, 2.0],
+                        sources: vec![ContactSource::shared_property(SchoolId, "school")],
+                        ..ContactDiaryConfig::default()
+                    },
+                )
+                .unwrap();
+            context.execute();
+            draws.extend((0..5).map(|_| context.sample_range(ModelRng, 0..1000)));
+        }
+        (read_rows(&dir.path().join("diary.csv")), draws)
+    }
+
+    #[test]
+    fn sampling_is_deterministic_and_separate() {
+        let (rows, draws) = diary_with_seed(7);
+        assert!(!rows.is_empty());
+        // Each diary has the 9 other people in the school.
+        assert_eq!(rows.len() % 9, 0);
+        assert_eq!(diary_with_seed(7), (rows.clone(), draws.clone()));
+        let (other_rows, other_draws) = diary_with_seed(8);
+        assert_ne!(other_rows, rows);
+
+        // The model draws the same numbers with or without the diaries.
+        let mut context = Context::new();
+        context.init_random(7);
+        let without: Vec<usize> = (0..5)
+            .map(|_| context.sample_range(ModelRng, 0..1000))
+            .collect();
+        assert_eq!(without, draws);
+        let mut context = Context::new();
+        context.init_random(8);
+        let without: Vec<usize> = (0..5)
+            .map(|_| context.sample_range(ModelRng, 0..1000))
+            .collect();
+        assert_eq!(without, other_draws);
+    }
+
+    #[test]
+    fn invalid_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut context = setup(dir.path());
+        let mut unknown = config(1.0);
+        unknown.alter_properties.push(String::from("Height"));
+        let result = context.add_contact_diary_report("diary", DiaryRng, unknown);
+        assert!(matches!(result, Err(IxaError::IxaError(message)) if message.contains("Height")));
+
+        let result = context.add_contact_diary_report("diary", DiaryRng, config(1.5));
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+
+        let mut duplicate = config(1.0);
+        duplicate
+            .sources
+            .push(ContactSource::edges::<Work>("household"));
+        let result = context.add_contact_diary_report("diary", DiaryRng, duplicate);
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+
+        let empty = ContactDiaryConfig {
+            days: vec![1.0],
+            ..ContactDiaryConfig::default()
+        };
+        let result = context.add_contact_diary_report("diary", DiaryRng, empty);
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+    }
+
+    #[test]
+    fn large_reports_are_warned_about() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut context = setup(dir.path());
+        // Over two days, everyone's contacts: 2 household edges, 2 ends of
+        // the work edge and 2 pairs in each school, both ways.
+        let mut large = config(0.5);
+        large.days = vec![1.0, 2.0];
+        large.warn_above_rows = 7;
+        context
+            .add_contact_diary_report("diary", DiaryRng, large)
+            .unwrap();
+        context.execute();
+        let data = context.get_data_container(ContactDiaryPlugin).unwrap();
+        assert_eq!(data.estimated_rows, Some(8.0));
+        assert_eq!(data.volume_warnings, 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut context = setup(dir.path());
+        let mut small = config(0.5);
+        small.warn_above_rows = 8;
+        context
+            .add_contact_diary_report("diary", DiaryRng, small)
+            .unwrap();
+        context.execute();
+        let data = context.get_data_container(ContactDiaryPlugin).unwrap();
+        assert_eq!(data.estimated_rows, Some(4.0));
+        assert_eq!(data.volume_warnings, 0);
+    }
+}
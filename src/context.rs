@@ -4,12 +4,22 @@
 //! for storing and manipulating the state of a given simulation.
 use std::{
     any::{Any, TypeId},
-    collections::{HashMap, VecDeque},
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap, VecDeque},
+    hash::Hash,
+    panic::{self, AssertUnwindSafe},
     rc::Rc,
+    time::{Duration, Instant},
 };
 
+use crate::error::IxaError;
+use crate::execution_stats::{self, ExecutionCounters, ExecutionStatistics, PeriodicStatistics};
+use crate::log::LogFileGuard;
+use crate::observer::ReadOnlyContext;
 use crate::plan::{PlanId, Queue};
-use crate::trace;
+use crate::scratch::{Scratch, ScratchArenas};
+use crate::{trace, warn};
+use ixa_derive::IxaEvent;
 
 /// The common callback used by multiple `Context` methods for future events
 type Callback = dyn FnOnce(&mut Context);
@@ -17,25 +27,116 @@ type Callback = dyn FnOnce(&mut Context);
 /// A handler for an event type `E`
 type EventHandler<E> = dyn Fn(&mut Context, E);
 
+/// The handlers subscribed to an event type `E`, keyed by the ids used to
+/// remove them. Ids increase with each subscription, so the handlers are in
+/// the order they subscribed.
+struct EventHandlerStore<E> {
+    handlers: BTreeMap<u64, Rc<EventHandler<E>>>,
+}
+
+/// An [`EventHandlerStore`] of any event type, so a handler can be removed
+/// knowing only its id
+trait AnyEventHandlerStore {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn remove(&mut self, id: u64) -> bool;
+    fn contains(&self, id: u64) -> bool;
+}
+
+impl<E: 'static> AnyEventHandlerStore for EventHandlerStore<E> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove(&mut self, id: u64) -> bool {
+        self.handlers.remove(&id).is_some()
+    }
+
+    fn contains(&self, id: u64) -> bool {
+        self.handlers.contains_key(&id)
+    }
+}
+
+/// A subscription to an event type, returned by
+/// [`Context::subscribe_to_event()`] and used to remove it with
+/// [`Context::unsubscribe()`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionHandle {
+    event_type: TypeId,
+    id: u64,
+}
+
+/// Another name for [`SubscriptionHandle`]
+pub type SubscriptionId = SubscriptionHandle;
+
+/// Decides what to do with an error from a fallible event handler
+type HandlerErrorPolicy = dyn Fn(&mut Context, IxaError) -> Result<(), IxaError>;
+
+/// A callback that runs at every boundary `offset + k * stride`
+struct BoundaryHook {
+    stride: f64,
+    offset: f64,
+    // The `k` of the next boundary this hook will fire at
+    index: f64,
+    callback: Rc<dyn Fn(&mut Context)>,
+}
+
+impl BoundaryHook {
+    fn next_boundary(&self) -> f64 {
+        self.offset + self.index * self.stride
+    }
+}
+
 pub trait IxaEvent {
     /// Called every time `context.subscribe_to_event` is called with this event
     fn on_subscribe(_context: &mut Context) {}
 }
 
+/// Emitted once, when the simulation first starts running, before the
+/// first plan executes
+#[derive(Copy, Clone, Debug, IxaEvent)]
+pub struct SimulationStarted;
+
+/// Emitted when the simulation is shut down with [`Context::shutdown()`],
+/// once whatever is currently executing has finished. The handlers, and
+/// the handlers of events they emit, run even though the other pending
+/// callbacks and plans are abandoned.
+#[derive(Copy, Clone, Debug, IxaEvent)]
+pub struct SimulationShutdown;
+
+/// Emitted each time the simulation clock advances, after all the plans
+/// at time `from` have been processed and before any at time `to`. The
+/// current time is already `to` when the handlers run.
+#[derive(Copy, Clone, Debug, IxaEvent)]
+pub struct TimeStepCompleted {
+    pub from: f64,
+    pub to: f64,
+}
+
 /// An enum to indicate the phase for plans at a given time.
 ///
 /// Most plans will occur as `Normal`. Plans with phase `First` are
 /// handled before all `Normal` plans, and those with phase `Last` are
-/// handled after all `Normal` plans. In all cases ties between plans at the
-/// same time and with the same phase are handled in the order of scheduling.
+/// handled after all `Normal` plans. Within a phase, plans added with
+/// [`Context::add_plan_with_priority()`] or
+/// [`Context::add_plan_with_phase_and_priority()`] are ordered by priority. In all
+/// cases ties between plans at the same time, phase and priority are handled
+/// in the order of scheduling.
 ///
-#[derive(PartialEq, Eq, Ord, Clone, Copy, PartialOrd)]
+#[derive(PartialEq, Eq, Ord, Clone, Copy, PartialOrd, Debug, Hash)]
 pub enum ExecutionPhase {
     First,
     Normal,
     Last,
 }
 
+// Plans are ordered by phase, and then by priority within the phase.
+type PlanPriority = (ExecutionPhase, i32);
+
 /// A manager for the state of a discrete-event simulation
 ///
 /// Provides core simulation services including
@@ -52,7 +153,9 @@ pub enum ExecutionPhase {
 /// The future event list of the simulation is a queue of `Callback` objects -
 /// called `plans` - that will assume control of the Context at a future point
 /// in time and execute the logic in the associated `FnOnce(&mut Context)`
-/// closure. Modules can add plans to this queue through the `Context`.
+/// closure. Modules can add plans to this queue through the `Context`,
+/// including plans that repeat at a fixed interval with
+/// [`Context::add_periodic_plan()`].
 ///
 /// The simulation also has a separate callback mechanism. Callbacks
 /// fire before the next timed event (even if it is scheduled for the
@@ -64,12 +167,28 @@ pub enum ExecutionPhase {
 /// occurred and have other modules take turns reacting to these occurrences.
 ///
 pub struct Context {
-    plan_queue: Queue<Box<Callback>, ExecutionPhase>,
+    plan_queue: Queue<Box<Callback>, PlanPriority>,
     callback_queue: VecDeque<Box<Callback>>,
-    event_handlers: HashMap<TypeId, Box<dyn Any>>,
+    boundary_hooks: Vec<BoundaryHook>,
+    event_handlers: HashMap<TypeId, Box<dyn AnyEventHandlerStore>>,
+    subscription_counter: u64,
     data_plugins: HashMap<TypeId, Box<dyn Any>>,
+    // The type names of the data plugins, for error messages
+    data_plugin_names: HashMap<TypeId, &'static str>,
     current_time: f64,
+    // The phase of the plan that is running, if any
+    current_phase: Option<ExecutionPhase>,
+    started: bool,
     shutdown_requested: bool,
+    shutdown_emitted: bool,
+    counters: ExecutionCounters,
+    emission_counter: u64,
+    running_emission: u64,
+    failed_emission: Option<u64>,
+    handler_error: Option<IxaError>,
+    handler_error_policy: Option<Rc<HandlerErrorPolicy>>,
+    wall_clock_timeout: Option<Duration>,
+    scratch: ScratchArenas,
 }
 
 impl Context {
@@ -79,10 +198,24 @@ impl Context {
         Context {
             plan_queue: Queue::new(),
             callback_queue: VecDeque::new(),
+            boundary_hooks: Vec::new(),
             event_handlers: HashMap::new(),
+            subscription_counter: 0,
             data_plugins: HashMap::new(),
+            data_plugin_names: HashMap::new(),
             current_time: 0.0,
+            current_phase: None,
+            started: false,
             shutdown_requested: false,
+            shutdown_emitted: false,
+            counters: ExecutionCounters::default(),
+            emission_counter: 0,
+            running_emission: 0,
+            failed_emission: None,
+            handler_error: None,
+            handler_error_policy: None,
+            wall_clock_timeout: None,
+            scratch: ScratchArenas::default(),
         }
     }
 
@@ -90,43 +223,267 @@ impl Context {
     ///
     /// Handlers will be called upon event emission in order of subscription as
     /// queued `Callback`s with the appropriate event.
-    #[allow(clippy::missing_panics_doc)]
-    pub fn subscribe_to_event<E: IxaEvent + Copy + 'static>(
+    ///
+    /// Returns a handle that can be passed to [`Context::unsubscribe()`] to
+    /// stop handling the events.
+    pub fn subscribe_to_event<E: IxaEvent + Clone + 'static>(
+        &mut self,
+        handler: impl Fn(&mut Context, E) + 'static,
+    ) -> SubscriptionHandle {
+        let id = self.add_event_handler(handler);
+        SubscriptionHandle {
+            event_type: TypeId::of::<E>(),
+            id,
+        }
+    }
+
+    /// Register to handle emission of events of type E, returning a handle
+    /// that can be passed to [`Context::unsubscribe()`]
+    #[deprecated(note = "use `subscribe_to_event()`, which returns the same handle")]
+    pub fn subscribe_to_event_with_handle<E: IxaEvent + Clone + 'static>(
         &mut self,
         handler: impl Fn(&mut Context, E) + 'static,
+    ) -> SubscriptionHandle {
+        self.subscribe_to_event(handler)
+    }
+
+    /// Register to handle only the next event of type E
+    ///
+    /// The handler is unsubscribed when it is called, so it's called at most
+    /// once. The returned handle can be passed to [`Context::unsubscribe()`]
+    /// to remove it before then.
+    pub fn subscribe_to_event_once<E: IxaEvent + Clone + 'static>(
+        &mut self,
+        handler: impl FnOnce(&mut Context, E) + 'static,
+    ) -> SubscriptionHandle {
+        let handler = RefCell::new(Some(handler));
+        let own_handle = Rc::new(Cell::new(None));
+        let own_handle_clone = Rc::clone(&own_handle);
+        let handle = self.subscribe_to_event(move |context, event: E| {
+            if let Some(handle) = own_handle_clone.get() {
+                context.unsubscribe(handle);
+            }
+            let handler = handler.borrow_mut().take();
+            if let Some(handler) = handler {
+                handler(context, event);
+            }
+        });
+        own_handle.set(Some(handle));
+        handle
+    }
+
+    /// Remove a handler subscribed with [`Context::subscribe_to_event()`]
+    ///
+    /// The handler isn't called again, even for events that were already
+    /// emitted and are waiting in the callback queue, so a handler can
+    /// unsubscribe itself or another handler of the same event.
+    /// Unsubscribing a handler that was already removed does nothing.
+    pub fn unsubscribe(&mut self, handle: SubscriptionHandle) {
+        if let Some(store) = self.event_handlers.get_mut(&handle.event_type) {
+            store.remove(handle.id);
+        }
+    }
+
+    /// Register a fallible handler for events of type E
+    ///
+    /// If `handler` returns an error, the error is wrapped in
+    /// [`IxaError::HandlerFailed`] together with the type of the event and
+    /// where the handler was subscribed, and passed to the policy set with
+    /// [`Context::set_handler_error_policy()`]. By default the simulation
+    /// stops, and [`Context::try_execute()`] returns the error.
+    ///
+    /// Once a handler fails, the handlers for the same emission of the
+    /// event that haven't run yet are skipped, even if the policy lets the
+    /// simulation continue. Changes that handlers made before the failure,
+    /// including in the failed handler itself, are not rolled back.
+    ///
+    /// Returns a handle that can be passed to [`Context::unsubscribe()`].
+    #[track_caller]
+    pub fn subscribe_to_event_fallible<E: IxaEvent + Clone + 'static>(
+        &mut self,
+        handler: impl Fn(&mut Context, E) -> Result<(), IxaError> + 'static,
+    ) -> SubscriptionHandle {
+        let location = panic::Location::caller().to_string();
+        self.subscribe_to_event(move |context, event: E| {
+            if let Err(error) = handler(context, event) {
+                context.handler_failed(IxaError::HandlerFailed {
+                    event_type: std::any::type_name::<E>(),
+                    location: location.clone(),
+                    time: context.get_current_time(),
+                    source: Box::new(error),
+                });
+            }
+        })
+    }
+
+    /// Set the policy for errors from handlers subscribed with
+    /// [`Context::subscribe_to_event_fallible()`]
+    ///
+    /// `policy` is called with each error. If it returns `Ok`, the error is
+    /// discarded and the simulation continues; if it returns an error, the
+    /// simulation stops and [`Context::try_execute()`] returns that error.
+    pub fn set_handler_error_policy(
+        &mut self,
+        policy: impl Fn(&mut Context, IxaError) -> Result<(), IxaError> + 'static,
     ) {
-        let handler_vec = self
+        self.handler_error_policy = Some(Rc::new(policy));
+    }
+
+    fn handler_failed(&mut self, error: IxaError) {
+        self.failed_emission = Some(self.running_emission);
+        let result = match self.handler_error_policy.clone() {
+            Some(policy) => policy(self, error),
+            None => Err(error),
+        };
+        if let Err(error) = result {
+            self.handler_error = Some(error);
+        }
+    }
+
+    // Subscribe `handler` and return an id that can be used to remove it.
+    fn add_event_handler<E: IxaEvent + Clone + 'static>(
+        &mut self,
+        handler: impl Fn(&mut Context, E) + 'static,
+    ) -> u64 {
+        let id = self.subscription_counter;
+        self.subscription_counter += 1;
+        let store = self
             .event_handlers
             .entry(TypeId::of::<E>())
-            .or_insert_with(|| Box::<Vec<Rc<EventHandler<E>>>>::default());
-        let handler_vec: &mut Vec<Rc<EventHandler<E>>> = handler_vec.downcast_mut().unwrap();
-        handler_vec.push(Rc::new(handler));
+            .or_insert_with(|| {
+                Box::new(EventHandlerStore::<E> {
+                    handlers: BTreeMap::new(),
+                })
+            });
+        let store: &mut EventHandlerStore<E> = store.as_any_mut().downcast_mut().unwrap();
+        store.handlers.insert(id, Rc::new(handler));
         E::on_subscribe(self);
+        id
+    }
+
+    // Remove the handler with the given id. Events that were already
+    // emitted aren't delivered to it either.
+    fn remove_event_handler<E: IxaEvent + Clone + 'static>(&mut self, id: u64) {
+        if let Some(store) = self.event_handlers.get_mut(&TypeId::of::<E>()) {
+            store.remove(id);
+        }
+    }
+
+    /// Subscribe `handler` to events of type E while running `f`, and
+    /// unsubscribe it afterwards
+    ///
+    /// The handler is removed when `f` returns or panics, so it doesn't
+    /// handle events that `f` emits but doesn't run the simulation for.
+    /// Subscriptions that `f` makes itself are kept. Returns the result of
+    /// `f`.
+    pub fn with_subscription<E: IxaEvent + Clone + 'static, R>(
+        &mut self,
+        handler: impl Fn(&mut Context, E) + 'static,
+        f: impl FnOnce(&mut Context) -> R,
+    ) -> R {
+        let id = self.add_event_handler(handler);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        self.remove_event_handler::<E>(id);
+        match result {
+            Ok(value) => value,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Run the simulation until `time` (see [`Context::run_until()`]) and
+    /// count the events of type E that are emitted along the way
+    ///
+    /// Returns the number of events.
+    pub fn count_events<E: IxaEvent + Clone + 'static>(&mut self, time: f64) -> usize {
+        let count = Rc::new(Cell::new(0));
+        let handler_count = Rc::clone(&count);
+        self.with_subscription::<E, _>(
+            move |_, _| handler_count.set(handler_count.get() + 1),
+            |context| context.run_until(time),
+        );
+        count.get()
+    }
+
+    /// Get a read-only view of the context, for code that only observes
+    /// the simulation. See [`crate::observer`].
+    #[must_use]
+    pub fn as_read_only(&self) -> ReadOnlyContext<'_> {
+        ReadOnlyContext::new(self)
+    }
+
+    /// Register an observer to handle emission of events of type E
+    ///
+    /// Like [`Context::subscribe_to_event()`], but `handler` gets a
+    /// [`ReadOnlyContext`], so it can't change the simulation. It can keep
+    /// its own state, e.g., to count events.
+    ///
+    /// Returns a handle that can be passed to [`Context::unsubscribe()`].
+    pub fn subscribe_observer<E: IxaEvent + Clone + 'static>(
+        &mut self,
+        handler: impl FnMut(&ReadOnlyContext, &E) + 'static,
+    ) -> SubscriptionHandle {
+        let handler = RefCell::new(handler);
+        self.subscribe_to_event(move |context: &mut Context, event: E| {
+            (handler.borrow_mut())(&context.as_read_only(), &event);
+        })
     }
 
     /// Emit and event of type E to be handled by registered receivers
     ///
     /// Receivers will handle events in the order that they have subscribed and
-    /// are queued as callbacks
-    #[allow(clippy::missing_panics_doc)]
-    pub fn emit_event<E: IxaEvent + Copy + 'static>(&mut self, event: E) {
+    /// are queued as callbacks. Each receiver gets its own clone of the event.
+    pub fn emit_event<E: IxaEvent + Clone + 'static>(&mut self, event: E) {
+        self.counters.events_emitted += 1;
+        self.queue_event_handlers(event);
+    }
+
+    // Emits one of the simulation lifecycle events, which aren't counted in
+    // the execution statistics since they aren't emitted by the model.
+    fn emit_lifecycle_event<E: IxaEvent + Clone + 'static>(&mut self, event: E) {
+        self.queue_event_handlers(event);
+    }
+
+    // Queues a callback for each handler of `event`.
+    #[allow(clippy::needless_pass_by_value)]
+    fn queue_event_handlers<E: IxaEvent + Clone + 'static>(&mut self, event: E) {
         // Destructure to obtain event handlers and plan queue
         let Context {
             event_handlers,
             callback_queue,
+            emission_counter,
             ..
         } = self;
-        if let Some(handler_vec) = event_handlers.get(&TypeId::of::<E>()) {
-            let handler_vec: &Vec<Rc<EventHandler<E>>> = handler_vec.downcast_ref().unwrap();
-            for handler in handler_vec {
+        if let Some(store) = event_handlers.get(&TypeId::of::<E>()) {
+            let store: &EventHandlerStore<E> = store.as_any().downcast_ref().unwrap();
+            let emission = *emission_counter;
+            *emission_counter += 1;
+            for (&id, handler) in &store.handlers {
                 let handler_clone = Rc::clone(handler);
-                callback_queue.push_back(Box::new(move |context| handler_clone(context, event)));
+                let event = event.clone();
+                callback_queue.push_back(Box::new(move |context| {
+                    // Skip the remaining handlers once one has failed
+                    if context.failed_emission == Some(emission) {
+                        return;
+                    }
+                    // Skip handlers that were unsubscribed since the event
+                    // was emitted
+                    if !context
+                        .event_handlers
+                        .get(&TypeId::of::<E>())
+                        .is_some_and(|store| store.contains(id))
+                    {
+                        return;
+                    }
+                    context.running_emission = emission;
+                    handler_clone(context, event);
+                }));
             }
         }
     }
 
     /// Add a plan to the future event list at the specified time in the normal
-    /// phase
+    /// phase. This is equivalent to [`Context::add_plan_with_priority()`]
+    /// with a priority of 0.
     ///
     /// Returns a `PlanId` for the newly-added plan that can be used to cancel it
     /// if needed.
@@ -137,6 +494,124 @@ impl Context {
         self.add_plan_with_phase(time, callback, ExecutionPhase::Normal)
     }
 
+    /// Add a plan that observes the simulation at the specified time in the
+    /// normal phase
+    ///
+    /// Like [`Context::add_plan()`], but `callback` gets a
+    /// [`ReadOnlyContext`], so it can't change the simulation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if time is in the past, infinite, or NaN.
+    pub fn add_observer_plan(
+        &mut self,
+        time: f64,
+        callback: impl FnOnce(&ReadOnlyContext) + 'static,
+    ) -> PlanId {
+        self.add_plan(time, move |context| callback(&context.as_read_only()))
+    }
+
+    /// Add a plan that runs `callback` at `start` and then every `interval`
+    /// time units, e.g., to record daily incidence, until the simulation is
+    /// shut down with [`Context::shutdown()`]. The plan always reschedules
+    /// itself, so [`Context::execute()`] won't return unless something
+    /// shuts the simulation down; use
+    /// [`Context::add_periodic_plan_until()`] to stop at a given time
+    /// instead.
+    ///
+    /// The plan runs at exactly `start + n * interval` for each `n`, so
+    /// rounding errors don't accumulate, and in the normal phase like
+    /// plans added with [`Context::add_plan()`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is in the past, infinite, or NaN, or if `interval`
+    /// isn't positive and finite.
+    pub fn add_periodic_plan(
+        &mut self,
+        start: f64,
+        interval: f64,
+        callback: impl Fn(&mut Context) + 'static,
+    ) {
+        self.add_periodic_plan_until(start, interval, f64::INFINITY, callback);
+    }
+
+    /// Add a plan that runs `callback` at `start` and then every `interval`
+    /// time units like [`Context::add_periodic_plan()`], stopping once
+    /// `start + n * interval` is after `end_time`. The plan runs at
+    /// `end_time` if it falls on a multiple of the interval.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is in the past, infinite, or NaN, or if `interval`
+    /// isn't positive and finite.
+    pub fn add_periodic_plan_until(
+        &mut self,
+        start: f64,
+        interval: f64,
+        end_time: f64,
+        callback: impl Fn(&mut Context) + 'static,
+    ) {
+        assert!(
+            interval > 0.0 && interval.is_finite(),
+            "Interval must be positive and finite"
+        );
+        assert!(
+            start.is_finite() && start >= self.current_time,
+            "Time is invalid"
+        );
+        self.schedule_periodic_plan(start, interval, 0.0, end_time, callback);
+    }
+
+    // Schedules run `n` of a plan added with `add_periodic_plan_until()`,
+    // unless it's after the end time. `n` is a float so that it can't
+    // overflow.
+    fn schedule_periodic_plan(
+        &mut self,
+        start: f64,
+        interval: f64,
+        n: f64,
+        end_time: f64,
+        callback: impl Fn(&mut Context) + 'static,
+    ) {
+        let time = start + n * interval;
+        if time > end_time {
+            return;
+        }
+        self.add_plan(time, move |context| {
+            callback(context);
+            if !context.shutdown_requested {
+                context.schedule_periodic_plan(start, interval, n + 1.0, end_time, callback);
+            }
+        });
+    }
+
+    /// Add a plan to the future event list at the specified time in the normal
+    /// phase, with a priority that orders it among the plans at the same time,
+    /// e.g., so that all exposures happen before any recoveries. Plans with
+    /// numerically lower priorities are executed first, and plans with the
+    /// same priority are executed in the order they were added. Plans added
+    /// with [`Context::add_plan()`] have a priority of 0.
+    ///
+    /// Priorities only order plans within a phase, so plans in the `First`
+    /// and `Last` phases (see [`ExecutionPhase`]) still run before and after
+    /// them. Use [`Context::add_plan_with_phase_and_priority()`] to order
+    /// plans within those phases.
+    ///
+    /// Returns a `PlanId` for the newly-added plan that can be used to cancel it
+    /// if needed.
+    /// # Panics
+    ///
+    /// Panics if time is in the past, infinite, or NaN.
+    pub fn add_plan_with_priority(
+        &mut self,
+        time: f64,
+        priority: i32,
+        callback: impl FnOnce(&mut Context) + 'static,
+    ) -> PlanId {
+        self.add_plan_with_phase_and_priority(time, callback, ExecutionPhase::Normal, priority)
+    }
+
     /// Add a plan to the future event list at the specified time and with the
     /// specified phase (first, normal, or last among plans at the
     /// specified time)
@@ -151,12 +626,36 @@ impl Context {
         time: f64,
         callback: impl FnOnce(&mut Context) + 'static,
         phase: ExecutionPhase,
+    ) -> PlanId {
+        self.add_plan_with_phase_and_priority(time, callback, phase, 0)
+    }
+
+    /// Add a plan to the future event list at the specified time and phase,
+    /// with a priority that orders it among the plans at the same time in
+    /// that phase. Plans are executed in order of time, then phase, then
+    /// numerically lower priority first, and then in the order they were
+    /// added, e.g., end-of-day accounting in the normal phase with a
+    /// priority of 10 runs after all the normal plans at the same time with
+    /// the default priority of 0, whichever was added first.
+    ///
+    /// Returns a `PlanId` for the newly-added plan that can be used to cancel it
+    /// if needed.
+    /// # Panics
+    ///
+    /// Panics if time is in the past, infinite, or NaN.
+    pub fn add_plan_with_phase_and_priority(
+        &mut self,
+        time: f64,
+        callback: impl FnOnce(&mut Context) + 'static,
+        phase: ExecutionPhase,
+        priority: i32,
     ) -> PlanId {
         assert!(
             !time.is_nan() && !time.is_infinite() && time >= self.current_time,
             "Time is invalid"
         );
-        self.plan_queue.add_plan(time, Box::new(callback), phase)
+        self.plan_queue
+            .add_plan(time, Box::new(callback), (phase, priority))
     }
 
     fn evaluate_periodic_and_schedule_next(
@@ -206,6 +705,92 @@ impl Context {
         );
     }
 
+    /// Add a hook that runs at every time boundary `offset + k * stride`
+    /// for integer `k`, e.g., at the end of every day with a stride of 1.0
+    /// and an offset of 0.0.
+    ///
+    /// Before the simulation advances time across (or up to) a boundary, the
+    /// current time is set to the boundary and the hooks for that boundary
+    /// are queued as callbacks in registration order. This means they run
+    /// after every plan scheduled strictly before the boundary and before
+    /// any plan scheduled at or after it, regardless of phase. Each boundary
+    /// fires exactly once, even when a single time step crosses several
+    /// boundaries.
+    ///
+    /// Only boundaries strictly after the current time are considered, and a
+    /// boundary only fires if there is still a plan scheduled at or after it,
+    /// so hooks never keep the simulation running on their own. If the
+    /// simulation is shut down, no further boundaries fire.
+    ///
+    /// # Panics
+    ///
+    /// Panics if stride is not positive and finite or offset is not finite.
+    pub fn add_boundary_hook(
+        &mut self,
+        stride: f64,
+        offset: f64,
+        callback: impl Fn(&mut Context) + 'static,
+    ) {
+        assert!(
+            stride > 0.0 && stride.is_finite(),
+            "Stride must be greater than 0"
+        );
+        assert!(offset.is_finite(), "Offset must be finite");
+
+        let mut hook = BoundaryHook {
+            stride,
+            offset,
+            index: ((self.current_time - offset) / stride).floor(),
+            callback: Rc::new(callback),
+        };
+        while hook.next_boundary() <= self.current_time {
+            hook.index += 1.0;
+        }
+        self.boundary_hooks.push(hook);
+    }
+
+    /// Queue the hooks for the earliest boundary at or before `next_time`,
+    /// if there is one. Returns whether any hooks were queued.
+    fn queue_boundary_hooks(&mut self, next_time: f64) -> bool {
+        let Some(boundary) = self
+            .boundary_hooks
+            .iter()
+            .map(BoundaryHook::next_boundary)
+            .min_by(f64::total_cmp)
+        else {
+            return false;
+        };
+        if boundary > next_time {
+            return false;
+        }
+
+        trace!("crossing boundary at {boundary}");
+        self.advance_time(boundary);
+        let Context {
+            boundary_hooks,
+            callback_queue,
+            ..
+        } = self;
+        for hook in boundary_hooks {
+            if hook.next_boundary() <= boundary {
+                hook.index += 1.0;
+                let callback = Rc::clone(&hook.callback);
+                callback_queue.push_back(Box::new(move |context| callback(context)));
+            }
+        }
+        true
+    }
+
+    // Moves the clock forward to `time`, if it's later than the current
+    // time, and emits `TimeStepCompleted`.
+    fn advance_time(&mut self, time: f64) {
+        if time > self.current_time {
+            let from = self.current_time;
+            self.current_time = time;
+            self.emit_lifecycle_event(TimeStepCompleted { from, to: time });
+        }
+    }
+
     /// Cancel a plan that has been added to the queue
     ///
     /// # Panics
@@ -213,16 +798,121 @@ impl Context {
     /// This function panics if you cancel a plan which has already been
     /// cancelled or executed.
     pub fn cancel_plan(&mut self, plan_id: &PlanId) {
-        trace!("canceling plan {:?}", plan_id);
+        trace!("canceling plan {plan_id:?}");
         self.plan_queue.cancel_plan(plan_id);
     }
 
+    /// Add a plan to the future event list at the specified time, with a
+    /// key that other code can use to check for it with
+    /// [`Context::has_plan()`] or cancel it with
+    /// [`Context::cancel_plans_matching()`] without knowing its `PlanId`.
+    /// Keys can be of any hashable type, e.g., `(PersonId, &'static str)`,
+    /// and any number of plans can share a key.
+    ///
+    /// Returns a `PlanId` for the newly-added plan.
+    /// # Panics
+    ///
+    /// Panics if time is in the past, infinite, or NaN.
+    pub fn add_plan_keyed<K: Hash + Eq + 'static>(
+        &mut self,
+        time: f64,
+        key: K,
+        callback: impl FnOnce(&mut Context) + 'static,
+    ) -> PlanId {
+        assert!(
+            !time.is_nan() && !time.is_infinite() && time >= self.current_time,
+            "Time is invalid"
+        );
+        self.plan_queue.add_plan_with_key(
+            time,
+            Box::new(callback),
+            (ExecutionPhase::Normal, 0),
+            key,
+        )
+    }
+
+    /// Cancel all plans added with [`Context::add_plan_keyed()`] and `key`
+    /// that haven't run yet. Returns the number of plans cancelled, which
+    /// is 0 if there are none.
+    pub fn cancel_plans_matching<K: Hash + Eq + Clone + 'static>(&mut self, key: &K) -> usize {
+        self.plan_queue.cancel_plans_with_key(key)
+    }
+
+    /// Returns whether there are any plans added with
+    /// [`Context::add_plan_keyed()`] and `key` that haven't run yet
+    #[must_use]
+    pub fn has_plan<K: Hash + Eq + Clone + 'static>(&self, key: &K) -> bool {
+        self.plan_queue.has_plan_with_key(key)
+    }
+
+    /// Cancel a plan that has been added to the queue if it hasn't run yet,
+    /// e.g., a plan for a person's immunity to wane that should not happen
+    /// if they are reinfected first
+    ///
+    /// Returns `true` if the plan was pending and has been cancelled, or
+    /// `false` if it has already run or been cancelled.
+    pub fn try_cancel_plan(&mut self, plan_id: &PlanId) -> bool {
+        trace!("trying to cancel plan {plan_id:?}");
+        self.plan_queue.try_cancel_plan(plan_id)
+    }
+
     #[doc(hidden)]
     #[allow(dead_code)]
     pub(crate) fn remaining_plan_count(&self) -> usize {
         self.plan_queue.remaining_plan_count()
     }
 
+    /// The ids of the plans that haven't run or been cancelled yet
+    pub(crate) fn pending_plan_ids(&self) -> Vec<PlanId> {
+        self.plan_queue.pending_plan_ids().collect()
+    }
+
+    /// The number of plans that haven't run or been cancelled yet,
+    /// including the next plan of each periodic plan
+    #[must_use]
+    pub fn get_pending_plan_count(&self) -> usize {
+        self.plan_queue.pending_plan_count()
+    }
+
+    /// Returns whether there are any plans that haven't run or been
+    /// cancelled yet
+    #[must_use]
+    pub fn has_pending_plans(&self) -> bool {
+        self.plan_queue.pending_plan_count() > 0
+    }
+
+    /// The time of the next plan that will run, or `None` if there are no
+    /// pending plans. Callbacks added with [`Context::queue_callback()`]
+    /// run before it.
+    #[must_use]
+    pub fn get_next_plan_time(&self) -> Option<f64> {
+        self.plan_queue.next_plan_time()
+    }
+
+    /// The number of pending plans at exactly `time`, in any phase. The
+    /// first call counts the plans at every time; later calls are *O*(1).
+    #[must_use]
+    pub fn count_plans_at_time(&self, time: f64) -> usize {
+        self.plan_queue.count_plans_at_time(time)
+    }
+
+    pub(crate) fn pending_callback_count(&self) -> usize {
+        self.callback_queue.len()
+    }
+
+    /// The type ids and names of the data plugins that have data containers
+    pub(crate) fn data_plugin_types(&self) -> Vec<(TypeId, &'static str)> {
+        self.data_plugins
+            .keys()
+            .map(|type_id| (*type_id, self.data_plugin_names[type_id]))
+            .collect()
+    }
+
+    // Only used when restoring a checkpoint into a new context
+    pub(crate) fn set_current_time(&mut self, time: f64) {
+        self.current_time = time;
+    }
+
     /// Add a `Callback` to the queue to be executed before the next plan
     pub fn queue_callback(&mut self, callback: impl FnOnce(&mut Context) + 'static) {
         trace!("queuing callback");
@@ -244,9 +934,13 @@ impl Context {
         &mut self,
         _data_plugin: T,
     ) -> &mut T::DataContainer {
+        let data_plugin_names = &mut self.data_plugin_names;
         self.data_plugins
             .entry(TypeId::of::<T>())
-            .or_insert_with(|| Box::new(T::create_data_container()))
+            .or_insert_with(|| {
+                data_plugin_names.insert(TypeId::of::<T>(), std::any::type_name::<T>());
+                Box::new(T::create_data_container())
+            })
             .downcast_mut::<T::DataContainer>()
             .unwrap() // Will never panic as data container has the matching type
     }
@@ -280,38 +974,285 @@ impl Context {
         self.current_time
     }
 
+    /// Get the phase of the plan being run, or of the plan that ran last
+    /// when a callback or event handler is running
+    ///
+    /// Returns `None` outside of [`Context::execute()`] and the other
+    /// functions that run the simulation.
+    #[must_use]
+    pub fn get_current_phase(&self) -> Option<ExecutionPhase> {
+        self.current_phase
+    }
+
     /// Execute the simulation until the plan and callback queues are empty
+    ///
+    /// If the wall clock timeout set with
+    /// [`Context::set_wall_clock_timeout()`] is exceeded, this logs a
+    /// warning and returns with the remaining plans still queued.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a fallible event handler fails and the error policy
+    /// doesn't handle the error; use [`Context::try_execute()`] to get the
+    /// error instead.
     pub fn execute(&mut self) {
-        trace!("entering event loop");
-        // Start plan loop
-        loop {
-            if self.shutdown_requested {
-                break;
-            }
-
-            // If there is a callback, run it.
-            if let Some(callback) = self.callback_queue.pop_front() {
-                trace!("calling callback");
-                callback(self);
-                continue;
-            }
-
-            // There aren't any callbacks, so look at the first plan.
-            if let Some(plan) = self.plan_queue.get_next_plan() {
-                trace!("calling plan at {}", plan.time);
-                self.current_time = plan.time;
-                (plan.data)(self);
-            } else {
-                trace!("No callbacks or plans; exiting event loop");
-                // OK, there aren't any plans, so we're done.
-                break;
-            }
+        match self.run_event_loop(None, usize::MAX) {
+            Ok(_) | Err(IxaError::WallClockTimeout { .. }) => {}
+            Err(error) => panic!("{}", error.display_chain()),
         }
     }
-}
 
-// TODO(cym4@cdc.gov): This is a temporary hack to let you
-// run a plan with mutable references to both the context
+    /// Execute up to `n` plans in time order, along with the callbacks and
+    /// event handlers that they queue, and return the number of plans
+    /// executed
+    ///
+    /// This is less than `n` if the plan queue runs out or the simulation
+    /// is shut down. The simulation can be continued with another call to
+    /// `execute_steps()` or [`Context::execute()`], which runs all the
+    /// remaining plans.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a fallible event handler fails like [`Context::execute()`].
+    pub fn execute_steps(&mut self, n: usize) -> usize {
+        match self.run_event_loop(None, n) {
+            Ok(executed) => executed,
+            Err(error) => panic!("{}", error.display_chain()),
+        }
+    }
+
+    /// Execute the simulation like [`Context::execute()`], stopping early
+    /// if a fallible event handler fails
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from a handler subscribed with
+    /// [`Context::subscribe_to_event_fallible()`], or from the error policy.
+    /// The simulation stops after the failed handler, and can be continued
+    /// by calling this again. Also returns [`IxaError::WallClockTimeout`]
+    /// if the simulation runs for longer than the limit set with
+    /// [`Context::set_wall_clock_timeout()`].
+    pub fn try_execute(&mut self) -> Result<(), IxaError> {
+        self.run_event_loop(None, usize::MAX).map(|_| ())
+    }
+
+    /// Execute the simulation until there are no callbacks left and no plans
+    /// at or before `time`, and then advance the current time to `time`
+    ///
+    /// Plans after `time` stay in the queue, so the simulation can be
+    /// continued with another call to `run_until()` or `execute()`. If the
+    /// simulation is shut down, it stops without advancing the time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if time is NaN, or if a fallible event handler fails like
+    /// [`Context::execute()`].
+    pub fn run_until(&mut self, time: f64) {
+        assert!(!time.is_nan(), "Time is invalid");
+        if let Err(error) = self.run_event_loop(Some(time), usize::MAX) {
+            panic!("{}", error.display_chain());
+        }
+    }
+
+    #[allow(clippy::missing_panics_doc)]
+    // Runs callbacks and plans until there are none left, none at or
+    // before `end_time`, or `max_plans` plans have been executed, and
+    // returns the number of plans executed
+    fn run_event_loop(
+        &mut self,
+        end_time: Option<f64>,
+        max_plans: usize,
+    ) -> Result<usize, IxaError> {
+        // Write log messages to this context's log file while it runs.
+        let _log_file = LogFileGuard::enter(self);
+        trace!("entering event loop");
+        self.counters.start_running();
+        if !self.started && !self.shutdown_requested {
+            self.started = true;
+            self.emit_lifecycle_event(SimulationStarted);
+        }
+        let mut plans_executed = 0;
+        // Start plan loop
+        loop {
+            if self.shutdown_requested || self.handler_error.is_some() {
+                break;
+            }
+            if !self.counters.periodic.is_empty() {
+                self.publish_due_statistics();
+            }
+            if let Some(timeout) = self.wall_clock_timeout {
+                if self.counters.wall_time_so_far() > timeout {
+                    warn!(
+                        "Stopping the simulation at time {} after running for longer than {timeout:?}",
+                        self.current_time
+                    );
+                    self.handler_error = Some(IxaError::WallClockTimeout {
+                        timeout,
+                        time: self.current_time,
+                    });
+                    break;
+                }
+            }
+
+            // If there is a callback, run it.
+            if let Some(callback) = self.callback_queue.pop_front() {
+                trace!("calling callback");
+                self.counters.callbacks_executed += 1;
+                callback(self);
+                continue;
+            }
+
+            // The callbacks of the last plan have run
+            if plans_executed == max_plans {
+                trace!("Executed {max_plans} plans; exiting event loop");
+                break;
+            }
+
+            // There aren't any callbacks, so look at the first plan. If
+            // getting to it crosses a boundary, run the boundary hooks first.
+            match (self.plan_queue.next_plan_time(), end_time) {
+                (Some(next_time), Some(end_time)) if next_time > end_time => {
+                    // Boundaries up to the end time are still crossed
+                    if self.queue_boundary_hooks(end_time) {
+                        continue;
+                    }
+                    if end_time > self.current_time {
+                        self.advance_time(end_time);
+                        continue;
+                    }
+                    trace!("No callbacks or plans before {end_time}; exiting event loop");
+                    break;
+                }
+                (Some(next_time), _) => {
+                    if self.queue_boundary_hooks(next_time) {
+                        continue;
+                    }
+                    // Let the handlers of the time step run before the plan
+                    if next_time > self.current_time {
+                        self.advance_time(next_time);
+                        continue;
+                    }
+                    self.current_phase = self.plan_queue.next_plan_priority().map(|p| p.0);
+                    let plan = self.plan_queue.get_next_plan().unwrap();
+                    trace!("calling plan at {}", plan.time);
+                    self.counters.plans_executed += 1;
+                    plans_executed += 1;
+                    (plan.data)(self);
+                }
+                (None, Some(end_time)) if end_time > self.current_time => {
+                    self.advance_time(end_time);
+                }
+                (None, _) => {
+                    trace!("No callbacks or plans; exiting event loop");
+                    // OK, there aren't any plans, so we're done.
+                    break;
+                }
+            }
+        }
+
+        self.current_phase = None;
+        if self.shutdown_requested && !self.shutdown_emitted {
+            self.run_shutdown_handlers();
+        }
+        if self.shutdown_requested {
+            crate::report::finish_reports(self);
+        }
+        self.counters.stop_running();
+        match self.handler_error.take() {
+            Some(error) => Err(error),
+            None => Ok(plans_executed),
+        }
+    }
+
+    // Runs the handlers of `SimulationShutdown`, and the callbacks they
+    // queue, leaving the callbacks that shutting down abandoned in place.
+    fn run_shutdown_handlers(&mut self) {
+        self.shutdown_emitted = true;
+        let abandoned = std::mem::take(&mut self.callback_queue);
+        self.emit_lifecycle_event(SimulationShutdown);
+        while let Some(callback) = self.callback_queue.pop_front() {
+            self.counters.callbacks_executed += 1;
+            callback(self);
+        }
+        self.callback_queue = abandoned;
+    }
+
+    /// Get statistics about the execution so far, such as the number of
+    /// plans executed and the wall clock time spent running
+    #[must_use]
+    pub fn execution_statistics(&self) -> ExecutionStatistics {
+        execution_stats::collect(self, &self.counters)
+    }
+
+    /// Stop the simulation if it runs for longer than `timeout` of wall
+    /// clock time, e.g., so that a model stuck in an endless loop of plans
+    /// or events doesn't stall a parameter sweep
+    ///
+    /// The time is the total spent in [`Context::execute()`] and the other
+    /// functions that run the simulation, and is checked between plans and
+    /// callbacks, so a single long-running plan isn't interrupted. When it
+    /// is exceeded, a warning is logged and [`Context::try_execute()`]
+    /// returns [`IxaError::WallClockTimeout`], while `execute()` just
+    /// returns. The other functions that run the simulation panic with it.
+    pub fn set_wall_clock_timeout(&mut self, timeout: Duration) {
+        self.wall_clock_timeout = Some(timeout);
+    }
+
+    /// Print the execution statistics to stderr as a line of JSON every
+    /// `interval` of wall clock time while the simulation runs, to follow
+    /// its progress from another program. The statistics are written
+    /// between plans and callbacks, so a long-running plan delays them.
+    ///
+    /// # Panics
+    /// Panics if `interval` is zero.
+    pub fn print_execution_statistics_every(&mut self, interval: Duration) {
+        self.publish_execution_statistics_every(interval, |statistics| {
+            eprintln!("{}", serde_json::to_string(statistics).unwrap());
+        });
+    }
+
+    // Call `sink` with the execution statistics every `interval` of wall
+    // clock time while the simulation runs.
+    pub(crate) fn publish_execution_statistics_every(
+        &mut self,
+        interval: Duration,
+        sink: impl FnMut(&ExecutionStatistics) + 'static,
+    ) {
+        assert!(!interval.is_zero(), "The interval must be positive");
+        self.counters
+            .periodic
+            .push(PeriodicStatistics::new(interval, sink));
+    }
+
+    fn publish_due_statistics(&mut self) {
+        let now = Instant::now();
+        if self
+            .counters
+            .periodic
+            .iter()
+            .all(|periodic| !periodic.is_due(now))
+        {
+            return;
+        }
+        let statistics = self.execution_statistics();
+        for periodic in &mut self.counters.periodic {
+            if periodic.is_due(now) {
+                periodic.publish(now, &statistics);
+            }
+        }
+    }
+
+    /// Call `f` with a [`Scratch`] arena for temporary allocations, and
+    /// return its result. The arena is reset when `f` returns, but its
+    /// memory is kept for later calls, so using it avoids allocating on
+    /// the heap each time a plan runs. Calls may be nested.
+    pub fn with_scratch<R>(&self, f: impl FnOnce(&Scratch<'_>) -> R) -> R {
+        self.scratch.with(f)
+    }
+}
+
+// TODO(cym4@cdc.gov): This is a temporary hack to let you
+// run a plan with mutable references to both the context
 // and a plugin's data. In the future we hope to make a
 // convenient public API for this, which is why it's not
 // public now.
@@ -374,6 +1315,7 @@ mod tests {
     use ixa_derive::IxaEvent;
 
     define_data_plugin!(ComponentA, Vec<u32>, vec![]);
+    define_data_plugin!(ComponentB, Vec<(f64, u32)>, vec![]);
 
     #[test]
     fn empty_context() {
@@ -401,6 +1343,17 @@ mod tests {
         })
     }
 
+    fn add_plan_with_priority(
+        context: &mut Context,
+        time: f64,
+        value: u32,
+        priority: i32,
+    ) -> PlanId {
+        context.add_plan_with_priority(time, priority, move |context| {
+            context.get_data_container_mut(ComponentA).push(value);
+        })
+    }
+
     fn add_plan_with_phase(
         context: &mut Context,
         time: f64,
@@ -530,6 +1483,74 @@ mod tests {
         assert_eq!(*context.get_data_container_mut(ComponentA), test_vec);
     }
 
+    #[test]
+    fn try_cancel_plan() {
+        let mut context = Context::new();
+        // Immunity wanes at t=5 unless the person is reinfected first
+        let waning = add_plan(&mut context, 5.0, 1);
+        let fired = add_plan(&mut context, 1.0, 2);
+        context.add_plan(2.0, move |context| {
+            assert!(!context.try_cancel_plan(&fired));
+            assert!(context.try_cancel_plan(&waning));
+            assert!(!context.try_cancel_plan(&waning));
+        });
+        context.execute();
+        assert_eq!(context.get_current_time(), 2.0);
+        assert_eq!(*context.get_data_container_mut(ComponentA), vec![2]);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn inspect_plan_queue() {
+        let mut context = Context::new();
+        assert!(!context.has_pending_plans());
+        assert_eq!(context.get_next_plan_time(), None);
+        let to_cancel = add_plan(&mut context, 1.0, 1);
+        add_plan(&mut context, 2.0, 2);
+        add_plan(&mut context, 2.0, 3);
+        assert!(context.has_pending_plans());
+        assert_eq!(context.get_pending_plan_count(), 3);
+        assert_eq!(context.get_next_plan_time(), Some(1.0));
+        assert_eq!(context.count_plans_at_time(2.0), 2);
+
+        context.cancel_plan(&to_cancel);
+        assert_eq!(context.get_pending_plan_count(), 2);
+        assert_eq!(context.get_next_plan_time(), Some(2.0));
+        assert_eq!(context.count_plans_at_time(1.0), 0);
+        context.add_plan(2.0, |context| {
+            // This plan has been taken off the queue.
+            assert_eq!(context.count_plans_at_time(2.0), 0);
+            assert!(!context.has_pending_plans());
+        });
+        context.execute();
+        assert_eq!(context.get_pending_plan_count(), 0);
+        assert_eq!(context.get_next_plan_time(), None);
+    }
+
+    #[test]
+    fn keyed_plans() {
+        let mut context = Context::new();
+        let person = 3usize;
+        for (time, value) in [(2.0, 1), (3.0, 2)] {
+            context.add_plan_keyed(time, (person, "recovery"), move |context| {
+                context.get_data_container_mut(ComponentA).push(value);
+            });
+        }
+        context.add_plan_keyed(4.0, (person + 1, "recovery"), |context| {
+            context.get_data_container_mut(ComponentA).push(3);
+        });
+        context.add_plan(2.5, move |context| {
+            // The plan at t=2 has run
+            assert!(context.has_plan(&(person, "recovery")));
+            assert_eq!(context.cancel_plans_matching(&(person, "recovery")), 1);
+            assert!(!context.has_plan(&(person, "recovery")));
+            assert_eq!(context.cancel_plans_matching(&(person, "recovery")), 0);
+        });
+        context.execute();
+        assert!(!context.has_plan(&(person + 1, "recovery")));
+        assert_eq!(*context.get_data_container_mut(ComponentA), vec![1, 3]);
+    }
+
     #[test]
     fn add_plan_with_current_time() {
         let mut context = Context::new();
@@ -578,6 +1599,102 @@ mod tests {
         );
     }
 
+    #[test]
+    fn plans_at_same_time_follow_priority() {
+        let mut context = Context::new();
+        add_plan_with_priority(&mut context, 5.0, 5, 1);
+        add_plan_with_priority(&mut context, 5.0, 1, -1);
+        add_plan_with_priority(&mut context, 5.0, 6, 1);
+        add_plan_with_priority(&mut context, 5.0, 2, -1);
+        add_plan(&mut context, 5.0, 3);
+        add_plan_with_priority(&mut context, 5.0, 4, 0);
+        add_plan_with_phase(&mut context, 5.0, 7, ExecutionPhase::Last);
+        add_plan_with_phase(&mut context, 5.0, 0, ExecutionPhase::First);
+        context.execute();
+        assert_eq!(
+            *context.get_data_container_mut(ComponentA),
+            vec![0, 1, 2, 3, 4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn priorities_within_each_phase() {
+        let mut context = Context::new();
+        for (value, phase, priority) in [
+            (8, ExecutionPhase::Last, 1),
+            (5, ExecutionPhase::Normal, 2),
+            (1, ExecutionPhase::First, 3),
+            (7, ExecutionPhase::Last, -1),
+            (0, ExecutionPhase::First, -3),
+            (4, ExecutionPhase::Normal, 0),
+            (6, ExecutionPhase::Last, -1),
+            (3, ExecutionPhase::Normal, -2),
+            (2, ExecutionPhase::First, 3),
+        ] {
+            context.add_plan_with_phase_and_priority(
+                5.0,
+                move |context| context.get_data_container_mut(ComponentA).push(value),
+                phase,
+                priority,
+            );
+        }
+        context.execute();
+        // Ties go to the plan that was added first.
+        assert_eq!(
+            *context.get_data_container_mut(ComponentA),
+            vec![0, 1, 2, 3, 4, 5, 7, 6, 8]
+        );
+    }
+
+    // A model with a transmission module and an accounting module that
+    // records the counts at the end of each day, set up in either order.
+    fn end_of_day_accounting(accounting_first: bool) -> Vec<(f64, u32)> {
+        fn init_transmission(context: &mut Context) {
+            for day in 1..=3 {
+                for _ in 0..2 {
+                    add_plan(context, f64::from(day), 1);
+                }
+            }
+        }
+        fn init_accounting(context: &mut Context) {
+            for day in 1..=3 {
+                context.add_plan_with_priority(f64::from(day), 10, |context| {
+                    let infections = context.get_data_container_mut(ComponentA).iter().sum();
+                    let time = context.get_current_time();
+                    context
+                        .get_data_container_mut(ComponentB)
+                        .push((time, infections));
+                });
+            }
+        }
+        let mut context = Context::new();
+        if accounting_first {
+            init_accounting(&mut context);
+            init_transmission(&mut context);
+        } else {
+            init_transmission(&mut context);
+            init_accounting(&mut context);
+        }
+        context.execute();
+        context.get_data_container_mut(ComponentB).clone()
+    }
+
+    #[test]
+    fn accounting_runs_after_same_time_plans() {
+        let expected = vec![(1.0, 2), (2.0, 4), (3.0, 6)];
+        assert_eq!(end_of_day_accounting(true), expected);
+        assert_eq!(end_of_day_accounting(false), expected);
+    }
+
+    #[test]
+    fn priority_does_not_override_time() {
+        let mut context = Context::new();
+        add_plan_with_priority(&mut context, 2.0, 2, -10);
+        add_plan_with_priority(&mut context, 1.0, 1, 10);
+        context.execute();
+        assert_eq!(*context.get_data_container_mut(ComponentA), vec![1, 2]);
+    }
+
     #[derive(Copy, Clone, IxaEvent)]
     struct Event1 {
         pub data: usize,
@@ -677,6 +1794,249 @@ mod tests {
         assert_eq!(*obs_data.borrow(), 0);
     }
 
+    #[test]
+    fn run_until_stops_before_later_plans() {
+        let mut context = Context::new();
+        add_plan(&mut context, 1.0, 1);
+        add_plan(&mut context, 2.0, 2);
+        add_plan(&mut context, 3.0, 3);
+        context.run_until(2.0);
+        assert_eq!(context.get_current_time(), 2.0);
+        assert_eq!(*context.get_data_container_mut(ComponentA), vec![1, 2]);
+        context.run_until(2.5);
+        assert_eq!(context.get_current_time(), 2.5);
+        context.execute();
+        assert_eq!(context.get_current_time(), 3.0);
+        assert_eq!(*context.get_data_container_mut(ComponentA), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn run_until_crosses_boundaries() {
+        let mut context = Context::new();
+        let count = Rc::new(RefCell::new(0));
+        let count_clone = Rc::clone(&count);
+        context.add_boundary_hook(1.0, 0.0, move |_| *count_clone.borrow_mut() += 1);
+        add_plan(&mut context, 10.0, 1);
+        context.run_until(3.5);
+        assert_eq!(*count.borrow(), 3);
+        assert_eq!(context.get_current_time(), 3.5);
+    }
+
+    #[test]
+    fn with_subscription_removes_handler() {
+        let mut context = Context::new();
+        let scoped = Rc::new(RefCell::new(Vec::new()));
+        let scoped_clone = Rc::clone(&scoped);
+        let inner = Rc::new(RefCell::new(Vec::new()));
+        let inner_clone = Rc::clone(&inner);
+
+        let result = context.with_subscription::<Event1, _>(
+            move |_, event| scoped_clone.borrow_mut().push(event.data),
+            |context| {
+                // Subscriptions made inside the scope are kept
+                context.subscribe_to_event::<Event1>(move |_, event| {
+                    inner_clone.borrow_mut().push(event.data);
+                });
+                context.emit_event(Event1 { data: 1 });
+                context.execute();
+                "done"
+            },
+        );
+        assert_eq!(result, "done");
+
+        context.emit_event(Event1 { data: 2 });
+        context.execute();
+        assert_eq!(*scoped.borrow(), vec![1]);
+        assert_eq!(*inner.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn unsubscribe_removes_only_that_handler() {
+        let mut context = Context::new();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let mut handles = Vec::new();
+        for name in ["a", "b", "c"] {
+            let received = Rc::clone(&received);
+            handles.push(context.subscribe_to_event::<Event1>(move |_, event| {
+                received.borrow_mut().push((name, event.data));
+            }));
+        }
+        let other = Rc::new(RefCell::new(0));
+        let other_clone = Rc::clone(&other);
+        let other_handle = context.subscribe_to_event::<Event2>(move |_, _| {
+            *other_clone.borrow_mut() += 1;
+        });
+
+        context.emit_event(Event1 { data: 1 });
+        context.execute();
+        context.unsubscribe(handles[1]);
+        context.emit_event(Event1 { data: 2 });
+        context.emit_event(Event2 { data: 2 });
+        context.execute();
+        assert_eq!(
+            *received.borrow(),
+            vec![("a", 1), ("b", 1), ("c", 1), ("a", 2), ("c", 2)]
+        );
+        assert_eq!(*other.borrow(), 1);
+
+        // Unsubscribing again does nothing
+        context.unsubscribe(handles[1]);
+        context.unsubscribe(other_handle);
+        context.emit_event(Event2 { data: 3 });
+        context.execute();
+        assert_eq!(*other.borrow(), 1);
+    }
+
+    #[test]
+    fn unsubscribe_from_own_handler() {
+        // A handler that removes itself after the first event
+        let mut context = Context::new();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let received_clone = Rc::clone(&received);
+        let handle = Rc::new(Cell::new(None));
+        let handle_clone = Rc::clone(&handle);
+        handle.set(Some(context.subscribe_to_event::<Event1>(
+            move |context, event| {
+                received_clone.borrow_mut().push(event.data);
+                context.unsubscribe(handle_clone.get().unwrap());
+            },
+        )));
+
+        for (time, data) in [(1.0, 1), (2.0, 2), (3.0, 3)] {
+            context.add_plan(time, move |context| {
+                context.emit_event(Event1 { data });
+            });
+        }
+        context.execute();
+        assert_eq!(*received.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn unsubscribe_skips_queued_events() {
+        let mut context = Context::new();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let received_clone = Rc::clone(&received);
+        let handle = Rc::new(Cell::new(None));
+        let handle_clone = Rc::clone(&handle);
+        handle.set(Some(context.subscribe_to_event::<Event1>(
+            move |context, event| {
+                received_clone.borrow_mut().push(event.data);
+                context.unsubscribe(handle_clone.get().unwrap());
+            },
+        )));
+
+        // Both events are queued before the handler receives the first
+        context.add_plan(1.0, |context| {
+            context.emit_event(Event1 { data: 1 });
+            context.emit_event(Event1 { data: 2 });
+        });
+        context.add_plan(2.0, |context| context.emit_event(Event1 { data: 3 }));
+        context.execute();
+        assert_eq!(*received.borrow(), vec![1]);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn subscribe_to_event_with_handle_is_subscribe_to_event() {
+        let mut context = Context::new();
+        let received = Rc::new(Cell::new(0));
+        let received_clone = Rc::clone(&received);
+        let handle: SubscriptionId =
+            context.subscribe_to_event_with_handle(move |_, event: Event1| {
+                received_clone.set(received_clone.get() + event.data);
+            });
+        context.emit_event(Event1 { data: 1 });
+        context.execute();
+        context.unsubscribe(handle);
+        context.emit_event(Event1 { data: 2 });
+        context.execute();
+        assert_eq!(received.get(), 1);
+    }
+
+    #[test]
+    fn unsubscribe_observer_and_fallible_handler() {
+        let mut context = Context::new();
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        let observed_clone = Rc::clone(&observed);
+        let observer_handle = context.subscribe_observer::<Event1>(move |_, event| {
+            observed_clone.borrow_mut().push(event.data);
+        });
+        let handled = Rc::new(RefCell::new(Vec::new()));
+        let handled_clone = Rc::clone(&handled);
+        let fallible_handle = context.subscribe_to_event_fallible::<Event1>(move |_, event| {
+            handled_clone.borrow_mut().push(event.data);
+            Ok(())
+        });
+
+        context.emit_event(Event1 { data: 1 });
+        context.execute();
+        context.unsubscribe(observer_handle);
+        context.emit_event(Event1 { data: 2 });
+        context.execute();
+        context.unsubscribe(fallible_handle);
+        context.emit_event(Event1 { data: 3 });
+        context.execute();
+        assert_eq!(*observed.borrow(), vec![1]);
+        assert_eq!(*handled.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn subscribe_to_event_once() {
+        let mut context = Context::new();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let received_clone = Rc::clone(&received);
+        context.subscribe_to_event_once::<Event1>(move |_, event| {
+            received_clone.borrow_mut().push(event.data);
+        });
+        let cancelled = context.subscribe_to_event_once::<Event1>(|_, _| {
+            panic!("Cancelled handler ran");
+        });
+        context.unsubscribe(cancelled);
+
+        context.emit_event(Event1 { data: 1 });
+        context.emit_event(Event1 { data: 2 });
+        context.execute();
+        context.emit_event(Event1 { data: 3 });
+        context.execute();
+        assert_eq!(*received.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn with_subscription_removes_handler_on_panic() {
+        let mut context = Context::new();
+        let count = Rc::new(RefCell::new(0));
+        let count_clone = Rc::clone(&count);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            context.with_subscription::<Event1, ()>(
+                move |_, _| *count_clone.borrow_mut() += 1,
+                |_| panic!("analysis failed"),
+            );
+        }));
+        assert!(result.is_err());
+
+        context.emit_event(Event1 { data: 1 });
+        context.execute();
+        assert_eq!(*count.borrow(), 0);
+    }
+
+    #[test]
+    fn count_events_until_time() {
+        let mut context = Context::new();
+        for time in [1.0, 2.0, 3.0, 4.0] {
+            context.add_plan(time, |context| context.emit_event(Event1 { data: 1 }));
+        }
+        assert_eq!(context.count_events::<Event1>(2.5), 2);
+        assert_eq!(context.count_events::<Event2>(3.0), 0);
+
+        // The counting handler is gone afterwards
+        let count = Rc::new(RefCell::new(0));
+        let count_clone = Rc::clone(&count);
+        context.subscribe_to_event::<Event1>(move |_, _| *count_clone.borrow_mut() += 1);
+        context.execute();
+        assert_eq!(*count.borrow(), 1);
+    }
+
     #[test]
     fn shutdown_cancels_plans() {
         let mut context = Context::new();
@@ -719,6 +2079,195 @@ mod tests {
         assert_eq!(*obs_data.borrow(), 0);
     }
 
+    // Adds a plan that schedules itself again forever
+    fn add_endless_plan(context: &mut Context, time: f64) {
+        context.add_plan(time, move |context| add_endless_plan(context, time + 1.0));
+    }
+
+    #[test]
+    fn wall_clock_timeout_stops_endless_plans() {
+        let mut context = Context::new();
+        context.set_wall_clock_timeout(Duration::from_millis(20));
+        add_endless_plan(&mut context, 0.0);
+        let Err(IxaError::WallClockTimeout { timeout, time }) = context.try_execute() else {
+            panic!("expected a timeout");
+        };
+        assert_eq!(timeout, Duration::from_millis(20));
+        assert!(time > 0.0);
+        assert_eq!(time, context.get_current_time());
+        assert!(context.execution_statistics().wall_time_seconds >= 0.02);
+    }
+
+    #[test]
+    fn wall_clock_timeout_stops_endless_events() {
+        let mut context = Context::new();
+        context.set_wall_clock_timeout(Duration::from_millis(20));
+        context.subscribe_to_event::<Event1>(|context, event| {
+            context.emit_event(event);
+        });
+        context.add_plan(1.0, |context| context.emit_event(Event1 { data: 1 }));
+        assert!(matches!(
+            context.try_execute(),
+            Err(IxaError::WallClockTimeout { time, .. }) if time == 1.0
+        ));
+    }
+
+    #[test]
+    fn wall_clock_timeout_returns_from_execute() {
+        let mut context = Context::new();
+        context.set_wall_clock_timeout(Duration::from_millis(20));
+        add_endless_plan(&mut context, 0.0);
+        context.execute();
+        assert!(context.get_current_time() > 0.0);
+        assert!(context.execution_statistics().wall_time_seconds >= 0.02);
+    }
+
+    #[test]
+    fn wall_clock_timeout_not_reached() {
+        let mut context = Context::new();
+        context.set_wall_clock_timeout(Duration::from_secs(30));
+        add_plan(&mut context, 1.0, 1);
+        context.try_execute().unwrap();
+        assert_eq!(*context.get_data_container_mut(ComponentA), vec![1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "after running for longer than")]
+    fn wall_clock_timeout_panics_in_execute_steps() {
+        let mut context = Context::new();
+        context.set_wall_clock_timeout(Duration::ZERO);
+        add_endless_plan(&mut context, 0.0);
+        context.execute_steps(usize::MAX);
+    }
+
+    #[test]
+    fn execute_steps_runs_plans_in_order() {
+        let mut context = Context::new();
+        let obs_data = Rc::new(RefCell::new(Vec::new()));
+        let obs_data_clone = Rc::clone(&obs_data);
+        context.subscribe_to_event::<Event1>(move |_, event| {
+            obs_data_clone.borrow_mut().push(event.data);
+        });
+        add_plan(&mut context, 3.0, 3);
+        context.add_plan(1.0, |context| {
+            context.get_data_container_mut(ComponentA).push(1);
+            context.emit_event(Event1 { data: 1 });
+        });
+        add_plan(&mut context, 2.0, 2);
+
+        assert_eq!(context.execute_steps(0), 0);
+        assert_eq!(context.execute_steps(2), 2);
+        assert_eq!(context.get_current_time(), 2.0);
+        assert_eq!(*context.get_data_container_mut(ComponentA), vec![1, 2]);
+        // The handlers of events emitted by the last plan have run
+        assert_eq!(*obs_data.borrow(), vec![1]);
+
+        add_plan(&mut context, 2.5, 4);
+        assert_eq!(context.execute_steps(5), 2);
+        assert_eq!(
+            *context.get_data_container_mut(ComponentA),
+            vec![1, 2, 4, 3]
+        );
+        assert_eq!(context.execute_steps(1), 0);
+    }
+
+    #[test]
+    fn execute_steps_stops_at_shutdown() {
+        let mut context = Context::new();
+        add_plan(&mut context, 1.0, 1);
+        context.add_plan(1.5, Context::shutdown);
+        add_plan(&mut context, 2.0, 2);
+        assert_eq!(context.execute_steps(5), 2);
+        assert_eq!(context.get_current_time(), 1.5);
+        assert_eq!(*context.get_data_container_mut(ComponentA), vec![1]);
+    }
+
+    // Records the lifecycle events, and the plans at 1.0 and 2.0, in order
+    fn record_lifecycle(context: &mut Context) -> Rc<RefCell<Vec<String>>> {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let log_clone = Rc::clone(&log);
+        context.subscribe_to_event(move |_, _: SimulationStarted| {
+            log_clone.borrow_mut().push("started".to_string());
+        });
+        let log_clone = Rc::clone(&log);
+        context.subscribe_to_event(move |context, event: TimeStepCompleted| {
+            assert_eq!(context.get_current_time(), event.to);
+            log_clone
+                .borrow_mut()
+                .push(format!("step {} -> {}", event.from, event.to));
+        });
+        let log_clone = Rc::clone(&log);
+        context.subscribe_to_event(move |context, _: SimulationShutdown| {
+            log_clone
+                .borrow_mut()
+                .push(format!("shutdown at {}", context.get_current_time()));
+        });
+        for time in [1.0, 2.0] {
+            let log_clone = Rc::clone(&log);
+            context.add_plan(time, move |_| {
+                log_clone.borrow_mut().push(format!("plan at {time}"));
+            });
+        }
+        log
+    }
+
+    #[test]
+    fn lifecycle_events() {
+        let mut context = Context::new();
+        let log = record_lifecycle(&mut context);
+        context.add_plan(2.0, Context::shutdown);
+        context.add_plan(3.0, |_| panic!("Plan after shutdown ran"));
+        context.execute();
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "started",
+                "step 0 -> 1",
+                "plan at 1",
+                "step 1 -> 2",
+                "plan at 2",
+                "shutdown at 2",
+            ]
+        );
+
+        // The simulation only starts and shuts down once
+        context.execute();
+        assert_eq!(log.borrow().len(), 6);
+    }
+
+    #[test]
+    fn lifecycle_events_with_run_until() {
+        let mut context = Context::new();
+        let log = record_lifecycle(&mut context);
+        context.run_until(1.5);
+        assert_eq!(
+            *log.borrow(),
+            vec!["started", "step 0 -> 1", "plan at 1", "step 1 -> 1.5"]
+        );
+        context.execute();
+        assert_eq!(log.borrow()[4..], ["step 1.5 -> 2", "plan at 2"]);
+    }
+
+    #[test]
+    fn shutdown_handlers_run_their_events() {
+        let mut context = Context::new();
+        let handled = Rc::new(RefCell::new(Vec::new()));
+        let handled_clone = Rc::clone(&handled);
+        context.subscribe_to_event(move |_, event: Event1| {
+            handled_clone.borrow_mut().push(event.data);
+        });
+        context.subscribe_to_event(|context, _: SimulationShutdown| {
+            context.emit_event(Event1 { data: 2 });
+        });
+        context.add_plan(1.0, |context| {
+            // Abandoned by the shutdown
+            context.emit_event(Event1 { data: 1 });
+            context.shutdown();
+        });
+        context.execute();
+        assert_eq!(*handled.borrow(), vec![2]);
+    }
+
     #[test]
     #[allow(clippy::cast_sign_loss)]
     #[allow(clippy::cast_possible_truncation)]
@@ -744,4 +2293,257 @@ mod tests {
             vec![0, 1, 2]
         ); // time 0.0, 1.0, and 2.0
     }
+
+    #[test]
+    fn periodic_plan_runs_until_shutdown() {
+        let mut context = Context::new();
+        let times = Rc::new(RefCell::new(Vec::new()));
+        let times_clone = Rc::clone(&times);
+        context.add_periodic_plan(0.5, 0.1, move |context| {
+            times_clone.borrow_mut().push(context.get_current_time());
+            if times_clone.borrow().len() == 11 {
+                context.shutdown();
+            }
+        });
+        context.execute();
+
+        let expected: Vec<f64> = (0..11).map(|n| 0.5 + f64::from(n) * 0.1).collect();
+        assert_eq!(*times.borrow(), expected);
+        assert_eq!(context.get_current_time(), 1.5);
+    }
+
+    #[test]
+    fn periodic_plan_until_end_time() {
+        let mut context = Context::new();
+        let times = Rc::new(RefCell::new(Vec::new()));
+        let times_clone = Rc::clone(&times);
+        context.add_periodic_plan_until(1.0, 2.0, 7.0, move |context| {
+            times_clone.borrow_mut().push(context.get_current_time());
+        });
+        context.execute();
+        assert_eq!(*times.borrow(), vec![1.0, 3.0, 5.0, 7.0]);
+        assert_eq!(context.remaining_plan_count(), 0);
+    }
+
+    #[test]
+    fn periodic_plan_with_run_until() {
+        let mut context = Context::new();
+        context.add_periodic_plan(0.0, 1.0, |context| {
+            let time = context.get_current_time();
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            context.get_data_container_mut(ComponentA).push(time as u32);
+        });
+        context.run_until(3.5);
+        assert_eq!(
+            *context.get_data_container(ComponentA).unwrap(),
+            vec![0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Interval must be positive and finite")]
+    fn periodic_plan_zero_interval() {
+        let mut context = Context::new();
+        context.add_periodic_plan(0.0, 0.0, |_| {});
+    }
+
+    fn record(log: &Rc<RefCell<Vec<String>>>, label: &'static str) -> impl Fn(&mut Context) {
+        let log = Rc::clone(log);
+        move |context| {
+            log.borrow_mut()
+                .push(format!("{label}@{}", context.get_current_time()));
+        }
+    }
+
+    #[test]
+    fn boundary_hooks_run_between_plans() {
+        let mut context = Context::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        context.add_boundary_hook(1.0, 0.0, record(&log, "a"));
+        context.add_boundary_hook(1.0, 0.0, record(&log, "b"));
+        context.add_plan(0.5, record(&log, "plan"));
+        context.add_plan_with_phase(1.0, record(&log, "plan"), ExecutionPhase::First);
+        context.add_plan(1.5, record(&log, "plan"));
+        context.execute();
+        assert_eq!(
+            *log.borrow(),
+            vec!["plan@0.5", "a@1", "b@1", "plan@1", "plan@1.5"]
+        );
+    }
+
+    #[test]
+    fn boundary_hooks_fire_once_per_crossed_boundary() {
+        let mut context = Context::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        context.add_boundary_hook(1.0, 0.25, record(&log, "hook"));
+        context.add_plan(0.1, record(&log, "plan"));
+        context.add_plan(0.2, record(&log, "plan"));
+        context.add_plan(3.7, record(&log, "plan"));
+        context.add_plan(3.8, record(&log, "plan"));
+        context.execute();
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                "plan@0.1",
+                "plan@0.2",
+                "hook@0.25",
+                "hook@1.25",
+                "hook@2.25",
+                "hook@3.25",
+                "plan@3.7",
+                "plan@3.8"
+            ]
+        );
+    }
+
+    #[test]
+    fn boundary_hooks_stop_after_final_plan() {
+        let mut context = Context::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        context.add_boundary_hook(1.0, 0.0, record(&log, "hook"));
+        context.execute();
+        assert!(log.borrow().is_empty());
+
+        context.add_plan(2.5, record(&log, "plan"));
+        context.execute();
+        assert_eq!(*log.borrow(), vec!["hook@1", "hook@2", "plan@2.5"]);
+        assert_eq!(context.get_current_time(), 2.5);
+    }
+
+    #[test]
+    fn boundary_hooks_only_fire_after_registration() {
+        let mut context = Context::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let log_clone = Rc::clone(&log);
+        context.add_plan(2.0, move |context| {
+            context.add_boundary_hook(2.0, 0.0, record(&log_clone, "hook"));
+        });
+        context.add_plan(4.0, record(&log, "plan"));
+        context.execute();
+        assert_eq!(*log.borrow(), vec!["hook@4", "plan@4"]);
+    }
+
+    #[test]
+    fn boundary_hook_can_schedule_plans_and_shutdown() {
+        let mut context = Context::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let log_clone = Rc::clone(&log);
+        context.add_boundary_hook(1.0, 0.0, move |context| {
+            record(&log_clone, "hook")(context);
+            if context.get_current_time() == 2.0 {
+                context.shutdown();
+            } else {
+                context.add_plan(context.get_current_time(), record(&log_clone, "plan"));
+            }
+        });
+        context.add_plan(5.0, record(&log, "plan"));
+        context.execute();
+        assert_eq!(*log.borrow(), vec!["hook@1", "plan@1", "hook@2"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Stride must be greater than 0")]
+    fn boundary_hook_invalid_stride() {
+        let mut context = Context::new();
+        context.add_boundary_hook(0.0, 0.0, |_| {});
+    }
+
+    // Subscribes three handlers for Event1 that log their index, the
+    // second of which fails for events with data 2.
+    fn subscribe_three_handlers(context: &mut Context) -> Rc<RefCell<Vec<(usize, usize)>>> {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for index in 0..3 {
+            let log = Rc::clone(&log);
+            context.subscribe_to_event_fallible(move |_, event: Event1| {
+                if index == 1 && event.data == 2 {
+                    return Err(IxaError::IxaError(format!("bad data {}", event.data)));
+                }
+                log.borrow_mut().push((event.data, index));
+                Ok(())
+            });
+        }
+        log
+    }
+
+    #[test]
+    fn handler_error_stops_execution() {
+        let mut context = Context::new();
+        let log = subscribe_three_handlers(&mut context);
+        for (time, data) in [(1.0, 1), (2.0, 2), (3.0, 3)] {
+            context.add_plan(time, move |context| {
+                context.emit_event(Event1 { data });
+            });
+        }
+
+        match context.try_execute() {
+            Err(IxaError::HandlerFailed {
+                event_type,
+                location,
+                time,
+                source,
+            }) => {
+                assert_eq!(event_type, std::any::type_name::<Event1>());
+                assert_eq!(time, 2.0);
+                // The location of the subscribe call
+                assert!(location.starts_with(concat!(file!(), ":")));
+                assert!(
+                    matches!(*source, IxaError::IxaError(ref message) if message == "bad data 2")
+                );
+            }
+            _ => panic!("Expected HandlerFailed"),
+        }
+        // The third handler didn't run for the failed emission, and the
+        // plan after it didn't run either
+        assert_eq!(*log.borrow(), vec![(1, 0), (1, 1), (1, 2), (2, 0)]);
+        assert_eq!(context.get_current_time(), 2.0);
+
+        // The simulation can be continued
+        context.try_execute().unwrap();
+        assert_eq!(
+            *log.borrow(),
+            vec![(1, 0), (1, 1), (1, 2), (2, 0), (3, 0), (3, 1), (3, 2)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "failed at time 1\n  caused by: bad data 2")]
+    fn handler_error_panics_in_execute() {
+        let mut context = Context::new();
+        subscribe_three_handlers(&mut context);
+        context.add_plan(1.0, |context| context.emit_event(Event1 { data: 2 }));
+        context.execute();
+    }
+
+    #[test]
+    fn handler_error_policy_records_errors() {
+        let mut context = Context::new();
+        let log = subscribe_three_handlers(&mut context);
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let errors_clone = Rc::clone(&errors);
+        context.set_handler_error_policy(move |context, error| {
+            errors_clone
+                .borrow_mut()
+                .push((context.get_current_time(), error.display_chain()));
+            Ok(())
+        });
+        for data in [2, 1, 2] {
+            context.add_plan(1.0, move |context| {
+                context.emit_event(Event1 { data });
+            });
+        }
+        context.try_execute().unwrap();
+
+        // Only the remaining handlers of the failed emissions were skipped
+        assert_eq!(*log.borrow(), vec![(2, 0), (1, 0), (1, 1), (1, 2), (2, 0)]);
+        assert_eq!(errors.borrow().len(), 2);
+        assert!(errors.borrow()[0].1.contains("bad data 2"));
+
+        // A policy can also turn errors into a different error
+        context.set_handler_error_policy(|_, _| Err(IxaError::IxaError("stop".to_string())));
+        context.add_plan(2.0, |context| context.emit_event(Event1 { data: 2 }));
+        assert!(matches!(
+            context.try_execute(),
+            Err(IxaError::IxaError(message)) if message == "stop"
+        ));
+    }
 }
@@ -1,13 +1,19 @@
 use crate::context::run_with_plugin;
 use crate::define_data_plugin;
-use crate::external_api::{global_properties, next, population, run_ext_api, EmptyArgs};
-use crate::Context;
+use crate::external_api::ids::{decode_person_id, encode_person_id};
+use crate::external_api::{
+    export, global_properties, next, people, population, run_ext_api, status, summary, tabulate,
+    values, EmptyArgs, POST_RUN_COMMANDS,
+};
+use crate::people::external_api::ContextPeopleExtCrate;
+use crate::people::ContextPeopleExt;
 use crate::IxaError;
+use crate::{Context, PersonId};
 use clap::{ArgMatches, Command, FromArgMatches, Parser, Subcommand};
 use rustyline;
 
 use log::trace;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
 
 trait DebuggerCommand {
@@ -24,9 +30,63 @@ struct Debugger {
     rl: rustyline::DefaultEditor,
     cli: Command,
     commands: HashMap<&'static str, Box<dyn DebuggerCommand>>,
+    // Whether the simulation has finished, so only the post-run commands
+    // are available.
+    finished: bool,
 }
 define_data_plugin!(DebuggerPlugin, Option<Debugger>, None);
 
+// The debugger's state belongs to the current run, so it isn't saved in checkpoints
+pub(crate) fn plugin_type_id() -> std::any::TypeId {
+    std::any::TypeId::of::<DebuggerPlugin>()
+}
+
+// Pauses the simulation when a person property changes, for one person or
+// for anyone.
+struct Watch {
+    id: usize,
+    property: String,
+    person_id: Option<PersonId>,
+}
+
+impl Watch {
+    fn matches(&self, property: &str, person_id: PersonId) -> bool {
+        self.property == property && self.person_id.is_none_or(|id| id == person_id)
+    }
+}
+
+impl std::fmt::Display for Watch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.person_id {
+            Some(person_id) => write!(
+                f,
+                "Watch {}: {} of {}",
+                self.id,
+                self.property,
+                encode_person_id(person_id)
+            ),
+            None => write!(f, "Watch {}: {} of anyone", self.id, self.property),
+        }
+    }
+}
+
+// The watches are kept apart from the debugger, which is taken out of the
+// context while it runs commands.
+#[derive(Default)]
+struct WatchData {
+    watches: Vec<Watch>,
+    next_id: usize,
+    // The properties whose changes we've subscribed to. There's no way to
+    // unsubscribe, so the handler ignores changes that aren't watched.
+    subscribed: HashSet<String>,
+}
+define_data_plugin!(WatchPlugin, WatchData, WatchData::default());
+
+// The watches belong to the current run too
+pub(crate) fn watch_plugin_type_id() -> std::any::TypeId {
+    std::any::TypeId::of::<WatchPlugin>()
+}
+
 impl Debugger {
     fn get_command(&self, name: &str) -> Option<&dyn DebuggerCommand> {
         self.commands.get(name).map(|command| &**command)
@@ -47,6 +107,14 @@ impl Debugger {
         if let Some((command, _)) = matches.subcommand() {
             // If the provided command is known, run its handler
 
+            if self.finished && !POST_RUN_COMMANDS.contains(&command) {
+                return Ok((
+                    false,
+                    Some(format!(
+                        "error: The simulation has finished, so {command} is not available"
+                    )),
+                ));
+            }
             if let Some(handler) = self.get_command(command) {
                 return handler.handle(context, &matches);
             }
@@ -106,6 +174,67 @@ impl DebuggerCommand for GlobalPropertyCommand {
     }
 }
 
+struct ValuesCommand;
+impl DebuggerCommand for ValuesCommand {
+    fn extend(&self, command: Command) -> Command {
+        values::Args::augment_subcommands(command)
+    }
+    fn handle(
+        &self,
+        context: &mut Context,
+        matches: &ArgMatches,
+    ) -> Result<(bool, Option<String>), String> {
+        let args = values::Args::from_arg_matches(matches).unwrap();
+        match run_ext_api::<values::Api>(context, &args) {
+            Err(IxaError::IxaError(e)) => Ok((false, Some(format!("error: {e}")))),
+            Err(e) => Ok((false, Some(format!("error: {e}")))),
+            Ok(retval) => Ok((
+                false,
+                Some(
+                    retval
+                        .values
+                        .iter()
+                        .map(|(value, count)| format!("{value}: {count}"))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+            )),
+        }
+    }
+}
+
+struct SummaryCommand;
+impl DebuggerCommand for SummaryCommand {
+    fn extend(&self, command: Command) -> Command {
+        summary::Args::augment_subcommands(command)
+    }
+    fn handle(
+        &self,
+        context: &mut Context,
+        matches: &ArgMatches,
+    ) -> Result<(bool, Option<String>), String> {
+        let args = summary::Args::from_arg_matches(matches).unwrap();
+        match run_ext_api::<summary::Api>(context, &args) {
+            Err(IxaError::IxaError(e)) => Ok((false, Some(format!("error: {e}")))),
+            Err(e) => Ok((false, Some(format!("error: {e}")))),
+            Ok(retval) => Ok((
+                false,
+                Some(
+                    retval
+                        .summaries
+                        .iter()
+                        .map(|(group, summary)| match group {
+                            Some(group) => format!("{group}: {summary}"),
+                            None => summary.to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+            )),
+        }
+    }
+}
+
 struct NextCommand;
 /// Adds a new debugger breakpoint at t
 impl DebuggerCommand for NextCommand {
@@ -149,6 +278,335 @@ impl DebuggerCommand for ContinueCommand {
     }
 }
 
+struct ExitCommand;
+#[derive(Parser, Debug)]
+enum ExitSubcommand {
+    /// Exits the debugger; the simulation continues if it hasn't finished
+    Exit,
+}
+impl DebuggerCommand for ExitCommand {
+    fn handle(
+        &self,
+        _context: &mut Context,
+        _matches: &ArgMatches,
+    ) -> Result<(bool, Option<String>), String> {
+        Ok((true, None))
+    }
+    fn extend(&self, command: Command) -> Command {
+        ExitSubcommand::augment_subcommands(command)
+    }
+}
+
+// Debugger commands are typed by hand, so they take bare ids too.
+fn parse_person_id(id: &str) -> Result<PersonId, String> {
+    decode_person_id(id, true).map_err(|error| error.to_string())
+}
+
+struct PeopleCommand;
+#[derive(Parser, Debug)]
+enum PeopleArgs {
+    /// Inspect people
+    #[command(subcommand)]
+    People(PeopleSubcommand),
+}
+#[derive(Subcommand, Debug)]
+enum PeopleSubcommand {
+    /// Get the value of a property of a person, or of all the registered
+    /// properties
+    Get {
+        /// The person's id, e.g., person:1, or just 1
+        #[arg(value_parser = parse_person_id)]
+        person_id: PersonId,
+        /// The property name
+        property: Option<String>,
+    },
+    /// List the people whose properties have all the given values
+    Query {
+        /// The values, e.g., Age=10 InfectionStatus=Infected
+        #[arg(required = true, value_parser = parse_property_value)]
+        properties: Vec<(String, String)>,
+    },
+}
+fn parse_property_value(pair: &str) -> Result<(String, String), String> {
+    pair.split_once('=')
+        .map(|(property, value)| (property.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected <property>=<value>, found '{pair}'"))
+}
+impl DebuggerCommand for PeopleCommand {
+    fn extend(&self, command: Command) -> Command {
+        PeopleArgs::augment_subcommands(command)
+    }
+    fn handle(
+        &self,
+        context: &mut Context,
+        matches: &ArgMatches,
+    ) -> Result<(bool, Option<String>), String> {
+        let PeopleArgs::People(args) = PeopleArgs::from_arg_matches(matches).unwrap();
+        let args = people::Args::People(match args {
+            PeopleSubcommand::Get {
+                person_id,
+                property,
+            } => people::ArgsEnum::Get {
+                person_id: person_id.into(),
+                property,
+            },
+            PeopleSubcommand::Query { properties } => people::ArgsEnum::Query { properties },
+        });
+        match run_ext_api::<people::Api>(context, &args) {
+            Err(IxaError::IxaError(e)) => Ok((false, Some(format!("error: {e}")))),
+            Err(e) => Ok((false, Some(format!("error: {e}")))),
+            Ok(people::Retval::Properties(properties)) => Ok((
+                false,
+                Some(
+                    properties
+                        .iter()
+                        .map(|(property, value)| format!("{property}: {value}"))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+            )),
+            Ok(people::Retval::People(people)) => {
+                let mut output = format!("{} people", people.len());
+                for person in people {
+                    output.push('\n');
+                    output.push_str(&person);
+                }
+                Ok((false, Some(output)))
+            }
+        }
+    }
+}
+
+struct TabulateCommand;
+impl DebuggerCommand for TabulateCommand {
+    fn extend(&self, command: Command) -> Command {
+        tabulate::Args::augment_subcommands(command)
+    }
+    fn handle(
+        &self,
+        context: &mut Context,
+        matches: &ArgMatches,
+    ) -> Result<(bool, Option<String>), String> {
+        let args = tabulate::Args::from_arg_matches(matches).unwrap();
+        match run_ext_api::<tabulate::Api>(context, &args) {
+            Err(IxaError::IxaError(e)) => Ok((false, Some(format!("error: {e}")))),
+            Err(e) => Ok((false, Some(format!("error: {e}")))),
+            Ok(retval) => Ok((
+                false,
+                Some(
+                    retval
+                        .rows
+                        .iter()
+                        .map(|(values, count)| format!("{}: {count}", values.join(", ")))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+            )),
+        }
+    }
+}
+
+struct StatusCommand;
+#[derive(Parser, Debug)]
+enum StatusSubcommand {
+    /// Print the execution statistics
+    Status,
+}
+impl DebuggerCommand for StatusCommand {
+    fn extend(&self, command: Command) -> Command {
+        StatusSubcommand::augment_subcommands(command)
+    }
+    fn handle(
+        &self,
+        context: &mut Context,
+        _matches: &ArgMatches,
+    ) -> Result<(bool, Option<String>), String> {
+        let statistics = run_ext_api::<status::Api>(context, &EmptyArgs {}).unwrap();
+        let output = serde_json::to_string_pretty(&statistics).map_err(|e| e.to_string())?;
+        Ok((false, Some(output)))
+    }
+}
+
+struct ExportCommand;
+impl DebuggerCommand for ExportCommand {
+    fn extend(&self, command: Command) -> Command {
+        export::Args::augment_subcommands(command)
+    }
+    fn handle(
+        &self,
+        context: &mut Context,
+        matches: &ArgMatches,
+    ) -> Result<(bool, Option<String>), String> {
+        let args = export::Args::from_arg_matches(matches).unwrap();
+        match run_ext_api::<export::Api>(context, &args) {
+            Err(IxaError::IxaError(e)) => Ok((false, Some(format!("error: {e}")))),
+            Err(e) => Ok((false, Some(format!("error: {e}")))),
+            Ok(retval) => Ok((
+                false,
+                Some(format!("Wrote {} people to {}", retval.people, retval.path)),
+            )),
+        }
+    }
+}
+
+struct WatchCommand;
+#[derive(Parser, Debug)]
+enum WatchArgs {
+    /// Pause when a person property changes
+    #[command(subcommand)]
+    Watch(WatchSubcommand),
+}
+#[derive(Subcommand, Debug)]
+enum WatchSubcommand {
+    /// Pause when a property of a person, or of anyone if the id is
+    /// omitted, changes
+    #[command(allow_missing_positional = true)]
+    Person {
+        /// The person's id, e.g., person:1, or just 1
+        #[arg(value_parser = parse_person_id)]
+        person_id: Option<PersonId>,
+        /// The property name
+        property: String,
+    },
+    /// List the active watches
+    List,
+    /// Delete a watch
+    Delete {
+        /// The watch's id
+        #[arg(required_unless_present = "all")]
+        id: Option<usize>,
+        /// Delete all the watches
+        #[arg(long, conflicts_with = "id")]
+        all: bool,
+    },
+}
+impl DebuggerCommand for WatchCommand {
+    fn extend(&self, command: Command) -> Command {
+        WatchArgs::augment_subcommands(command)
+    }
+    fn handle(
+        &self,
+        context: &mut Context,
+        matches: &ArgMatches,
+    ) -> Result<(bool, Option<String>), String> {
+        let WatchArgs::Watch(args) = WatchArgs::from_arg_matches(matches).unwrap();
+        let output = match args {
+            WatchSubcommand::Person {
+                person_id,
+                property,
+            } => add_watch(context, person_id, property),
+            WatchSubcommand::List => {
+                let watches = &context.get_data_container_mut(WatchPlugin).watches;
+                if watches.is_empty() {
+                    Ok(String::from("No active watches"))
+                } else {
+                    Ok(watches
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join("\n"))
+                }
+            }
+            WatchSubcommand::Delete { id, all } => {
+                let watches = &mut context.get_data_container_mut(WatchPlugin).watches;
+                if all {
+                    let count = watches.len();
+                    watches.clear();
+                    Ok(format!("Deleted {count} watches"))
+                } else {
+                    let id = id.unwrap();
+                    match watches.iter().position(|watch| watch.id == id) {
+                        Some(position) => {
+                            watches.remove(position);
+                            Ok(format!("Deleted watch {id}"))
+                        }
+                        None => Err(IxaError::IxaError(format!("No watch {id}"))),
+                    }
+                }
+            }
+        };
+        match output {
+            Ok(output) => Ok((false, Some(output))),
+            Err(IxaError::IxaError(e)) => Ok((false, Some(format!("error: {e}")))),
+            Err(e) => Ok((false, Some(format!("error: {e}")))),
+        }
+    }
+}
+
+fn add_watch(
+    context: &mut Context,
+    person_id: Option<PersonId>,
+    property: String,
+) -> Result<String, IxaError> {
+    if let Some(person_id) = person_id {
+        if !context.person_exists(person_id) {
+            return Err(IxaError::PersonNotFound(person_id));
+        }
+    }
+    if !context
+        .get_data_container_mut(WatchPlugin)
+        .subscribed
+        .contains(&property)
+    {
+        let name = property.clone();
+        context.subscribe_to_property_changes_by_name(
+            &property,
+            move |context, person_id, previous, current| {
+                on_watched_change(context, &name, person_id, previous, current);
+            },
+        )?;
+        context
+            .get_data_container_mut(WatchPlugin)
+            .subscribed
+            .insert(property.clone());
+    }
+
+    let data_container = context.get_data_container_mut(WatchPlugin);
+    data_container.next_id += 1;
+    let watch = Watch {
+        id: data_container.next_id,
+        property,
+        person_id,
+    };
+    let output = watch.to_string();
+    data_container.watches.push(watch);
+    Ok(output)
+}
+
+// Starts the debugger if a watch matches a change.
+fn on_watched_change(
+    context: &mut Context,
+    property: &str,
+    person_id: PersonId,
+    previous: &str,
+    current: &str,
+) {
+    let Some(data_container) = context.get_data_container(WatchPlugin) else {
+        return;
+    };
+    let ids: Vec<usize> = data_container
+        .watches
+        .iter()
+        .filter(|watch| watch.matches(property, person_id))
+        .map(|watch| watch.id)
+        .collect();
+    if ids.is_empty() {
+        return;
+    }
+
+    let t = context.get_current_time();
+    for id in ids {
+        println!(
+            "Watch {id}: {property} of {} changed from {previous} to {current} at t={t}",
+            encode_person_id(person_id)
+        );
+    }
+    init(context);
+    run_with_plugin::<DebuggerPlugin>(context, |context, data_container| {
+        start_debugger(context, data_container.as_mut().unwrap()).expect("Error in debugger");
+    });
+}
+
 // Build the debugger context.
 fn init(context: &mut Context) {
     let debugger = context.get_data_container_mut(DebuggerPlugin);
@@ -159,6 +617,14 @@ fn init(context: &mut Context) {
         commands.insert("next", Box::new(NextCommand));
         commands.insert("continue", Box::new(ContinueCommand));
         commands.insert("global", Box::new(GlobalPropertyCommand));
+        commands.insert("values", Box::new(ValuesCommand));
+        commands.insert("summary", Box::new(SummaryCommand));
+        commands.insert("people", Box::new(PeopleCommand));
+        commands.insert("watch", Box::new(WatchCommand));
+        commands.insert("tabulate", Box::new(TabulateCommand));
+        commands.insert("status", Box::new(StatusCommand));
+        commands.insert("export", Box::new(ExportCommand));
+        commands.insert("exit", Box::new(ExitCommand));
 
         let mut cli = Command::new("repl")
             .multicall(true)
@@ -176,6 +642,7 @@ fn init(context: &mut Context) {
             rl: rustyline::DefaultEditor::new().unwrap(),
             cli,
             commands,
+            finished: false,
         });
     }
 }
@@ -185,7 +652,16 @@ fn start_debugger(context: &mut Context, debugger: &mut Debugger) -> Result<(),
     init(context);
     let t = context.get_current_time();
 
-    println!("Debugging simulation at t={t}");
+    if debugger.finished {
+        let statistics = context.execution_statistics();
+        println!(
+            "Simulation complete at t={t}: population {}, {} plans executed in {:.3}s",
+            statistics.population, statistics.plans_executed, statistics.wall_time_seconds
+        );
+        println!("Inspect the final state, or type exit to leave");
+    } else {
+        println!("Debugging simulation at t={t}");
+    }
     loop {
         let line = match debugger.rl.readline(&format!("t={t} $ ")) {
             Ok(line) => line,
@@ -234,6 +710,20 @@ pub trait ContextDebugExt {
     /// Internal debugger errors e.g., reading or writing to stdin/stdout;
     /// errors in Ixa are printed to stdout
     fn schedule_debugger(&mut self, t: f64);
+
+    /// Print a summary of the finished simulation and start the debugger
+    /// so that its final state can be inspected, queried, tabulated and
+    /// exported. Commands that advance the simulation, such as `next` and
+    /// `continue`, aren't available; `exit` leaves the debugger. The
+    /// runner calls this after `execute()` with `--explore` or
+    /// `--debugger`.
+    ///
+    /// # Panics
+    /// If the debugger can't read from stdin or write to stdout
+    fn explore(&mut self);
+
+    /// The same as [`ContextDebugExt::explore()`]
+    fn finish_debugger(&mut self);
 }
 
 impl ContextDebugExt for Context {
@@ -247,13 +737,26 @@ impl ContextDebugExt for Context {
             });
         });
     }
+
+    fn explore(&mut self) {
+        init(self);
+        run_with_plugin::<DebuggerPlugin>(self, |context, data_container| {
+            let debugger = data_container.as_mut().unwrap();
+            debugger.finished = true;
+            start_debugger(context, debugger).expect("Error in debugger");
+        });
+    }
+
+    fn finish_debugger(&mut self) {
+        self.explore();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{init, run_with_plugin, DebuggerPlugin};
-    use crate::{define_global_property, ContextGlobalPropertiesExt};
-    use crate::{Context, ContextPeopleExt};
+    use crate::{define_global_property, define_person_property, ContextGlobalPropertiesExt};
+    use crate::{Context, ContextPeopleExt, PersonId};
 
     fn process_line(line: &str, context: &mut Context) -> (bool, Option<String>) {
         // Temporarily take the data container out of context so that
@@ -295,6 +798,56 @@ mod tests {
         assert!(output.unwrap().contains('2'));
     }
 
+    #[test]
+    fn test_cli_debugger_values() {
+        define_person_property!(Age, u8);
+        let context = &mut Context::new();
+        for age in [10, 20, 10] {
+            context.add_person((Age, age)).unwrap();
+        }
+        context.index_property(Age);
+
+        let (quits, output) = process_line("values Age\n", context);
+        assert!(!quits, "should not exit");
+        assert_eq!(output.unwrap(), "10: 2\n20: 1");
+
+        let (_quits, output) = process_line("values Unknown\n", context);
+        assert_eq!(output.unwrap(), "error: No property 'Unknown'");
+    }
+
+    #[test]
+    fn test_cli_debugger_summary() {
+        define_person_property!(Weight, u8);
+        define_person_property!(Group, bool);
+        define_person_property!(Name, &'static str);
+        let context = &mut Context::new();
+        for (weight, group) in [(10, true), (20, false), (30, true)] {
+            context
+                .add_person(((Weight, weight), (Group, group), (Name, "x")))
+                .unwrap();
+        }
+
+        let (quits, output) = process_line("summary Weight\n", context);
+        assert!(!quits, "should not exit");
+        assert_eq!(
+            output.unwrap(),
+            "count=3 mean=20 sd=10 min=10 q25=15 median=20 q75=25 max=30"
+        );
+
+        let (_quits, output) = process_line("summary Weight --by Group\n", context);
+        assert_eq!(
+            output.unwrap(),
+            "false: count=1 mean=20 sd=0 min=20 q25=20 median=20 q75=20 max=20\n\
+             true: count=2 mean=20 sd=14.142135623730951 min=10 q25=15 median=20 q75=25 max=30"
+        );
+
+        let (_quits, output) = process_line("summary Name\n", context);
+        assert_eq!(
+            output.unwrap(),
+            "error: Property 'Name' is not numeric: found value \"x\""
+        );
+    }
+
     #[test]
     fn test_cli_debugger_global_list() {
         let context = &mut Context::new();
@@ -363,6 +916,216 @@ mod tests {
         assert!(quits, "should exit");
     }
 
+    #[test]
+    fn test_cli_debugger_after_run() {
+        let output = assert_cmd::Command::cargo_bin("runner_test_debug")
+            .unwrap()
+            .args(["--debugger", "0.0"])
+            .write_stdin("continue\npopulation\nnext 2\n")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Simulation complete at t=0: population 3"));
+        assert!(output.contains("error: The simulation has finished"));
+    }
+
+    #[test]
+    fn test_cli_next_after_finish() {
+        let context = &mut Context::new();
+        init(context);
+        context
+            .get_data_container_mut(DebuggerPlugin)
+            .as_mut()
+            .unwrap()
+            .finished = true;
+        let (quits, output) = process_line("next 2\n", context);
+        assert!(!quits, "should not exit");
+        assert_eq!(
+            output.unwrap(),
+            "error: The simulation has finished, so next is not available"
+        );
+        assert_eq!(context.remaining_plan_count(), 0);
+    }
+
+    #[test]
+    fn test_cli_post_run_mode() {
+        define_person_property!(Age, u8);
+        define_person_property!(Group, bool);
+        let context = &mut Context::new();
+        for (age, group) in [(10, true), (20, false), (10, true)] {
+            context.add_person(((Age, age), (Group, group))).unwrap();
+        }
+        context.execute();
+        init(context);
+        context
+            .get_data_container_mut(DebuggerPlugin)
+            .as_mut()
+            .unwrap()
+            .finished = true;
+
+        let (quits, output) = process_line("continue\n", context);
+        assert!(!quits, "should not exit");
+        assert_eq!(
+            output.unwrap(),
+            "error: The simulation has finished, so continue is not available"
+        );
+
+        let (_quits, output) = process_line("people query Age=10 Group=true\n", context);
+        assert_eq!(output.unwrap(), "2 people\nperson:0\nperson:2");
+        let (_quits, output) = process_line("people query Age=30\n", context);
+        assert_eq!(output.unwrap(), "0 people");
+        let (_quits, output) = process_line("people query Unknown=1\n", context);
+        assert_eq!(output.unwrap(), "error: No property 'Unknown'");
+
+        let (_quits, output) = process_line("tabulate Group Age\n", context);
+        assert_eq!(output.unwrap(), "false, 20: 1\ntrue, 10: 2");
+
+        let (_quits, output) = process_line("status\n", context);
+        assert!(output.unwrap().contains("\"population\": 3"));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("people.csv");
+        let (_quits, output) =
+            process_line(&format!("export people {}\n", path.display()), context);
+        assert_eq!(
+            output.unwrap(),
+            format!("Wrote 3 people to {}", path.display())
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "person_id,Age,Group\nperson:0,10,true\nperson:1,20,false\nperson:2,10,true\n"
+        );
+        let path = temp_dir.path().join("snapshot.json");
+        process_line(&format!("export snapshot {}\n", path.display()), context);
+        let snapshot: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(snapshot["time"], serde_json::json!(0.0));
+        assert_eq!(
+            snapshot["people"][1],
+            serde_json::json!({"person_id": "person:1", "Age": "20", "Group": "false"})
+        );
+
+        let (quits, _) = process_line("exit\n", context);
+        assert!(quits, "should exit");
+    }
+
+    #[test]
+    fn test_cli_explore() {
+        let output = assert_cmd::Command::cargo_bin("runner_test_debug")
+            .unwrap()
+            .args(["--explore"])
+            .write_stdin("continue\npopulation\nexit\n")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Simulation complete at t=0: population 3"));
+        assert!(output
+            .contains("error: The simulation has finished, so continue is not available\n3\n"));
+    }
+
+    #[test]
+    fn test_cli_people_get() {
+        define_person_property!(Age, u8);
+        define_person_property!(Group, bool);
+        let context = &mut Context::new();
+        context.add_person(((Age, 10), (Group, true))).unwrap();
+
+        let (quits, output) = process_line("people get 0\n", context);
+        assert!(!quits, "should not exit");
+        assert_eq!(output.unwrap(), "Age: 10\nGroup: true");
+
+        let (_quits, output) = process_line("people get 0 Age\n", context);
+        assert_eq!(output.unwrap(), "Age: 10");
+
+        let (_quits, output) = process_line("people get 1\n", context);
+        assert_eq!(output.unwrap(), "error: Person 1 doesn't exist");
+    }
+
+    #[test]
+    fn test_cli_watch() {
+        define_person_property!(Age, u8);
+        let context = &mut Context::new();
+        context.add_person((Age, 10)).unwrap();
+
+        let (_quits, output) = process_line("watch list\n", context);
+        assert_eq!(output.unwrap(), "No active watches");
+
+        let (quits, output) = process_line("watch person 0 Age\n", context);
+        assert!(!quits, "should not exit");
+        assert_eq!(output.unwrap(), "Watch 1: Age of person:0");
+        let (_quits, output) = process_line("watch person Age\n", context);
+        assert_eq!(output.unwrap(), "Watch 2: Age of anyone");
+        let (_quits, output) = process_line("watch list\n", context);
+        assert_eq!(
+            output.unwrap(),
+            "Watch 1: Age of person:0\nWatch 2: Age of anyone"
+        );
+        // Tagged ids, as the Web API takes them, work too
+        let (_quits, output) = process_line("watch person person:0 Age\n", context);
+        assert_eq!(output.unwrap(), "Watch 3: Age of person:0");
+        let (_quits, output) = process_line("watch delete 3\n", context);
+        assert_eq!(output.unwrap(), "Deleted watch 3");
+
+        let (_quits, output) = process_line("watch delete 1\n", context);
+        assert_eq!(output.unwrap(), "Deleted watch 1");
+        let (_quits, output) = process_line("watch delete 1\n", context);
+        assert_eq!(output.unwrap(), "error: No watch 1");
+        let (_quits, output) = process_line("watch list\n", context);
+        assert_eq!(output.unwrap(), "Watch 2: Age of anyone");
+
+        let (_quits, output) = process_line("watch delete --all\n", context);
+        assert_eq!(output.unwrap(), "Deleted 1 watches");
+        let (_quits, output) = process_line("watch list\n", context);
+        assert_eq!(output.unwrap(), "No active watches");
+
+        // Changes without a matching watch don't start the debugger.
+        context.add_plan(1.0, |context| {
+            context.set_person_property(PersonId(0), Age, 11);
+        });
+        context.execute();
+    }
+
+    #[test]
+    fn test_cli_watch_errors() {
+        define_person_property!(Age, u8);
+        let context = &mut Context::new();
+        context.add_person((Age, 10)).unwrap();
+
+        let (_quits, output) = process_line("watch person 0 Unknown\n", context);
+        assert_eq!(output.unwrap(), "error: No property 'Unknown'");
+        let (_quits, output) = process_line("watch person 1 Age\n", context);
+        assert_eq!(output.unwrap(), "error: Person 1 doesn't exist");
+        let (_quits, output) = process_line("watch list\n", context);
+        assert_eq!(output.unwrap(), "No active watches");
+    }
+
+    #[test]
+    fn test_cli_watch_pauses_on_change() {
+        let output = assert_cmd::Command::cargo_bin("runner_test_watch")
+            .unwrap()
+            .args(["--debugger", "0.0"])
+            .write_stdin("watch person 1 Recovered\ncontinue\npeople get 1\ncontinue\ncontinue\n")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        let output = String::from_utf8(output).unwrap();
+        // Person 2 recovers at t=1, which isn't watched.
+        assert!(output.contains(
+            "Watch 1: Recovered of person:1 changed from false to true at t=2\n\
+             Debugging simulation at t=2\n\
+             Recovered: true\n"
+        ));
+        assert!(!output.contains("at t=1"));
+    }
+
     #[test]
     fn test_cli_next() {
         let context = &mut Context::new();
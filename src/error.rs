@@ -1,6 +1,15 @@
 //! Provides `IxaError` and wraps other errors.
-use std::fmt::{self, Debug, Display};
+//!
+//! Errors can say what was being done when they happened with
+//! [`IxaResultExt::with_ctx()`], which wraps them in
+//! [`IxaError::Context`]. [`IxaError::display_chain()`] shows an error with
+//! everything it wraps.
+use crate::PersonId;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Write};
 use std::io;
+use std::panic::Location;
+use std::time::Duration;
 
 #[derive(Debug)]
 #[allow(clippy::module_name_repetitions)]
@@ -10,9 +19,116 @@ pub enum IxaError {
     IoError(io::Error),
     JsonError(serde_json::Error),
     CsvError(csv::Error),
+    BincodeError(bincode::Error),
     Utf8Error(std::string::FromUtf8Error),
     ParseIntError(std::num::ParseIntError),
     IxaError(String),
+    /// The person doesn't exist, e.g., because they were removed with
+    /// [`Context::remove_person()`](crate::Context::remove_person)
+    PersonNotFound(PersonId),
+    /// An entity, such as a person, was added without values for
+    /// properties that have no default or initializer. Lists all of them,
+    /// in alphabetical order.
+    MissingRequiredProperties {
+        entity: &'static str,
+        properties: Vec<&'static str>,
+    },
+    /// The same property was given more than once when adding an entity
+    DuplicateProperty {
+        entity: &'static str,
+        property: &'static str,
+    },
+    /// There's no property with this name, e.g., in a property name given
+    /// on the command line or to the web API
+    PropertyNotFound {
+        name: String,
+    },
+    /// A row of an input file that couldn't be read
+    InvalidRow {
+        /// The line of the file the row is on, counting the header
+        line: u64,
+        /// The column with the bad value, if the error is about one
+        column: Option<String>,
+        message: String,
+    },
+    /// An event handler subscribed with
+    /// [`Context::subscribe_to_event_fallible()`](crate::Context::subscribe_to_event_fallible)
+    /// returned an error
+    HandlerFailed {
+        /// The type name of the event being handled
+        event_type: &'static str,
+        /// Where the handler was subscribed, as `file:line:column`
+        location: String,
+        /// The simulation time the handler ran at
+        time: f64,
+        /// The error returned by the handler
+        source: Box<IxaError>,
+    },
+    /// The simulation ran for longer than the limit set with
+    /// [`Context::set_wall_clock_timeout()`](crate::Context::set_wall_clock_timeout)
+    WallClockTimeout {
+        /// The limit that was exceeded
+        timeout: Duration,
+        /// The simulation time the simulation stopped at
+        time: f64,
+    },
+    /// Several errors happened in one operation, e.g., in different rows
+    /// of a file, in the order they happened
+    MultipleErrors(Vec<IxaError>),
+    /// An error with what was being done when it happened, attached with
+    /// [`IxaResultExt::with_ctx()`]
+    Context {
+        /// What was being done, e.g., "Initializing person from line 3"
+        context: String,
+        /// Where the context was attached, as `file:line:column`
+        location: String,
+        source: Box<IxaError>,
+    },
+}
+
+impl IxaError {
+    /// The error and the errors it wraps (see [`Error::source()`]), one
+    /// per line, e.g.:
+    ///
+    /// ```text
+    /// Loading people from people.csv (at src/main.rs:12:5)
+    ///   caused by: Initializing person from line 3 (at src/people/loader.rs:50:14)
+    ///   caused by: person is missing values for required properties: Age
+    /// ```
+    #[must_use]
+    pub fn display_chain(&self) -> String {
+        let mut chain = self.to_string();
+        let mut source = self.source();
+        while let Some(error) = source {
+            let _ = write!(chain, "\n  caused by: {error}");
+            source = error.source();
+        }
+        chain
+    }
+}
+
+/// Adds context to the errors of a `Result`
+pub trait IxaResultExt<T> {
+    /// Wrap an error in [`IxaError::Context`] with the message `context`
+    /// returns and the location of the call, e.g.,
+    /// `.with_ctx(|| format!("Initializing person from row {row}"))`.
+    /// `context` is only called if there's an error.
+    ///
+    /// # Errors
+    /// Returns the wrapped error, if any.
+    fn with_ctx<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T, IxaError>;
+}
+
+impl<T, E: Into<IxaError>> IxaResultExt<T> for Result<T, E> {
+    #[track_caller]
+    fn with_ctx<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T, IxaError> {
+        let location = Location::caller();
+        self.map_err(|error| IxaError::Context {
+            context: context().into(),
+            location: location.to_string(),
+            source: Box::new(error.into()),
+        })
+    }
 }
 
 impl From<io::Error> for IxaError {
@@ -33,6 +149,12 @@ impl From<csv::Error> for IxaError {
     }
 }
 
+impl From<bincode::Error> for IxaError {
+    fn from(error: bincode::Error) -> Self {
+        IxaError::BincodeError(error)
+    }
+}
+
 impl From<std::string::FromUtf8Error> for IxaError {
     fn from(error: std::string::FromUtf8Error) -> Self {
         IxaError::Utf8Error(error)
@@ -57,11 +179,90 @@ impl From<&str> for IxaError {
     }
 }
 
-impl std::error::Error for IxaError {}
+impl Error for IxaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            // Errors from other crates are shown as they are, so their
+            // sources are the sources of this error.
+            IxaError::IoError(error) => error.source(),
+            IxaError::JsonError(error) => error.source(),
+            IxaError::CsvError(error) => error.source(),
+            IxaError::BincodeError(error) => error.source(),
+            IxaError::Utf8Error(error) => error.source(),
+            IxaError::ParseIntError(error) => error.source(),
+            IxaError::HandlerFailed { source, .. } | IxaError::Context { source, .. } => {
+                Some(source.as_ref())
+            }
+            IxaError::IxaError(_)
+            | IxaError::PersonNotFound(_)
+            | IxaError::MissingRequiredProperties { .. }
+            | IxaError::DuplicateProperty { .. }
+            | IxaError::PropertyNotFound { .. }
+            | IxaError::InvalidRow { .. }
+            | IxaError::WallClockTimeout { .. }
+            | IxaError::MultipleErrors(_) => None,
+        }
+    }
+}
 
 impl Display for IxaError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error: {self:?}")?;
-        Ok(())
+        match self {
+            IxaError::IoError(error) => write!(f, "{error}"),
+            IxaError::JsonError(error) => write!(f, "{error}"),
+            IxaError::CsvError(error) => write!(f, "{error}"),
+            IxaError::BincodeError(error) => write!(f, "{error}"),
+            IxaError::Utf8Error(error) => write!(f, "{error}"),
+            IxaError::ParseIntError(error) => write!(f, "{error}"),
+            IxaError::IxaError(message) => write!(f, "{message}"),
+            IxaError::PersonNotFound(person_id) => {
+                write!(f, "Person {person_id} doesn't exist")
+            }
+            IxaError::MissingRequiredProperties { entity, properties } => write!(
+                f,
+                "{entity} is missing values for required properties: {}",
+                properties.join(", ")
+            ),
+            IxaError::DuplicateProperty { entity, property } => {
+                write!(f, "{property} was given more than once for a {entity}")
+            }
+            IxaError::PropertyNotFound { name } => write!(f, "No property '{name}'"),
+            IxaError::InvalidRow {
+                line,
+                column: Some(column),
+                message,
+            } => write!(f, "Line {line}, column '{column}': {message}"),
+            IxaError::InvalidRow {
+                line,
+                column: None,
+                message,
+            } => write!(f, "Line {line}: {message}"),
+            IxaError::HandlerFailed {
+                event_type,
+                location,
+                time,
+                ..
+            } => write!(
+                f,
+                "Handler for {event_type} subscribed at {location} failed at time {time}"
+            ),
+            IxaError::WallClockTimeout { timeout, time } => write!(
+                f,
+                "Simulation stopped at time {time} after running for longer than {timeout:?}"
+            ),
+            IxaError::MultipleErrors(errors) => {
+                write!(f, "{} errors: ", errors.len())?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", error.display_chain())?;
+                }
+                Ok(())
+            }
+            IxaError::Context {
+                context, location, ..
+            } => write!(f, "{context} (at {location})"),
+        }
     }
 }
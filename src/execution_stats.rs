@@ -0,0 +1,370 @@
+//! Statistics about the execution of a simulation, for programs that run
+//! and monitor models.
+//!
+//! [`Context::execution_statistics()`] returns an [`ExecutionStatistics`],
+//! which can be read directly or serialized with serde. The serialized
+//! field names are a stable schema: fields are only removed or renamed, or
+//! their meaning changed, together with an increase of
+//! [`EXECUTION_STATISTICS_SCHEMA_VERSION`], which is included in the
+//! serialized form as `schema_version`. New fields may be added without
+//! changing the version, so readers should ignore fields they don't know.
+//!
+//! To follow a long run from another program,
+//! [`Context::print_execution_statistics_every()`] prints the statistics
+//! to stderr as a line of JSON periodically, which the runner's
+//! `--stats-interval` option turns on. The Web API also serves them at
+//! `/stats`, updated every second while the simulation runs.
+use crate::people::ContextPeopleExt;
+use crate::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// The version of the serialized form of [`ExecutionStatistics`]. This is
+/// increased whenever a change to the schema would break existing readers.
+pub const EXECUTION_STATISTICS_SCHEMA_VERSION: u32 = 1;
+
+/// Statistics about the execution of a simulation, returned by
+/// [`Context::execution_statistics()`].
+///
+/// The statistics can be collected while the simulation is running, e.g.,
+/// from a plan or the Web API, in which case they cover the execution so
+/// far and `running` is `true`. The optional sections are `None` when the
+/// corresponding feature isn't used.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionStatistics {
+    /// The version of this schema; see [`EXECUTION_STATISTICS_SCHEMA_VERSION`]
+    pub schema_version: u32,
+    /// The number of plans that have been executed, including one that is
+    /// running
+    pub plans_executed: u64,
+    /// The number of callbacks that have been executed, including event
+    /// handlers
+    pub callbacks_executed: u64,
+    /// The number of events that have been emitted, whether or not any
+    /// handlers were subscribed to them. The events Ixa emits as the
+    /// simulation runs, such as
+    /// [`TimeStepCompleted`](crate::TimeStepCompleted), aren't counted.
+    pub events_emitted: u64,
+    /// The number of plans that are scheduled and haven't been cancelled
+    #[serde(default)]
+    pub plans_pending: usize,
+    /// The wall clock time spent in `execute()` and `run_until()`, in
+    /// seconds
+    pub wall_time_seconds: f64,
+    /// The current simulation time; the final time once execution is done
+    pub simulation_time: f64,
+    /// The current population
+    pub population: usize,
+    /// Whether the statistics were collected while the simulation was
+    /// running
+    pub running: bool,
+    /// The value of each simulation mode, by name (see
+    /// [`modes`](crate::modes))
+    #[serde(default)]
+    pub modes: BTreeMap<String, String>,
+    /// The reports that have been added, if any
+    pub reports: Option<ReportStatistics>,
+}
+
+/// The reports written by a simulation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportStatistics {
+    /// Each report, in the order they were added
+    pub files: Vec<ReportFileStatistics>,
+}
+
+/// A report file written by a simulation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportFileStatistics {
+    /// The name the report was added with
+    pub name: String,
+    /// The path of the report file
+    pub path: String,
+    /// The number of rows written, not counting the header
+    pub rows: u64,
+}
+
+type StatisticsSink = dyn FnMut(&ExecutionStatistics);
+
+// Somewhere the statistics are sent every `interval` of wall clock time
+// while the simulation runs.
+pub(crate) struct PeriodicStatistics {
+    interval: Duration,
+    next: Instant,
+    sink: Box<StatisticsSink>,
+}
+
+impl PeriodicStatistics {
+    pub(crate) fn new(
+        interval: Duration,
+        sink: impl FnMut(&ExecutionStatistics) + 'static,
+    ) -> Self {
+        PeriodicStatistics {
+            interval,
+            next: Instant::now() + interval,
+            sink: Box::new(sink),
+        }
+    }
+
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        now >= self.next
+    }
+
+    pub(crate) fn publish(&mut self, now: Instant, statistics: &ExecutionStatistics) {
+        (self.sink)(statistics);
+        self.next = now + self.interval;
+    }
+}
+
+// Counters that are updated by the event loop.
+#[derive(Default)]
+pub(crate) struct ExecutionCounters {
+    pub(crate) plans_executed: u64,
+    pub(crate) callbacks_executed: u64,
+    pub(crate) events_emitted: u64,
+    pub(crate) wall_time: Duration,
+    pub(crate) running_since: Option<Instant>,
+    pub(crate) periodic: Vec<PeriodicStatistics>,
+}
+
+impl ExecutionCounters {
+    pub(crate) fn start_running(&mut self) {
+        self.running_since = Some(Instant::now());
+    }
+
+    pub(crate) fn stop_running(&mut self) {
+        if let Some(start) = self.running_since.take() {
+            self.wall_time += start.elapsed();
+        }
+    }
+
+    // The wall clock time spent running so far, including the current run
+    pub(crate) fn wall_time_so_far(&self) -> Duration {
+        self.wall_time
+            + self
+                .running_since
+                .map_or(Duration::ZERO, |start| start.elapsed())
+    }
+}
+
+pub(crate) fn collect(context: &Context, counters: &ExecutionCounters) -> ExecutionStatistics {
+    let wall_time = counters.wall_time_so_far();
+    ExecutionStatistics {
+        schema_version: EXECUTION_STATISTICS_SCHEMA_VERSION,
+        plans_executed: counters.plans_executed,
+        callbacks_executed: counters.callbacks_executed,
+        events_emitted: counters.events_emitted,
+        plans_pending: context.get_pending_plan_count(),
+        wall_time_seconds: wall_time.as_secs_f64(),
+        simulation_time: context.get_current_time(),
+        population: context.get_current_population(),
+        running: counters.running_since.is_some(),
+        modes: crate::modes::mode_values(context),
+        reports: context.report_statistics().map(|files| ReportStatistics {
+            files: files
+                .into_iter()
+                .map(|(name, path, rows)| ReportFileStatistics {
+                    name,
+                    path: path.display().to_string(),
+                    rows,
+                })
+                .collect(),
+        }),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::{
+        ExecutionStatistics, ReportFileStatistics, ReportStatistics,
+        EXECUTION_STATISTICS_SCHEMA_VERSION,
+    };
+    use crate::{
+        create_report_trait, Context, ContextPeopleExt, ContextReportExt, IxaEvent, Report,
+    };
+    use ixa_derive::IxaEvent;
+    use serde::Serialize;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[derive(Copy, Clone, IxaEvent)]
+    struct StatsEvent;
+
+    #[derive(Copy, Clone, IxaEvent)]
+    struct UnhandledEvent;
+
+    #[derive(Serialize)]
+    struct StatsReport {
+        time: f64,
+    }
+    create_report_trait!(StatsReport);
+
+    // Changing this file means the schema changed. If the change breaks
+    // existing readers, increase EXECUTION_STATISTICS_SCHEMA_VERSION too.
+    const GOLDEN_SCHEMA: &str = include_str!("../tests/data/execution_statistics_schema.json");
+
+    #[test]
+    fn serialized_schema_matches_golden_file() {
+        let statistics = ExecutionStatistics {
+            schema_version: EXECUTION_STATISTICS_SCHEMA_VERSION,
+            plans_executed: 10,
+            callbacks_executed: 5,
+            events_emitted: 4,
+            plans_pending: 7,
+            wall_time_seconds: 0.5,
+            simulation_time: 100.0,
+            population: 1000,
+            running: false,
+            modes: BTreeMap::from([("MaskPolicy".to_string(), "Schools".to_string())]),
+            reports: Some(ReportStatistics {
+                files: vec![ReportFileStatistics {
+                    name: "incidence".to_string(),
+                    path: "output/incidence.csv".to_string(),
+                    rows: 20,
+                }],
+            }),
+        };
+        let serialized = serde_json::to_value(&statistics).unwrap();
+        let golden: serde_json::Value = serde_json::from_str(GOLDEN_SCHEMA).unwrap();
+        assert_eq!(serialized, golden);
+        let deserialized: ExecutionStatistics = serde_json::from_value(golden).unwrap();
+        assert_eq!(deserialized, statistics);
+    }
+
+    #[test]
+    fn counts_execution() {
+        let mut context = Context::new();
+        for _ in 0..3 {
+            context.add_person(()).unwrap();
+        }
+        let before = context.execution_statistics();
+        assert_eq!(before.plans_executed, 0);
+        // Adding people emits events
+        assert_eq!(before.events_emitted, 3);
+        assert!(!before.running);
+        assert!(before.reports.is_none());
+
+        context.subscribe_to_event(|_, _: StatsEvent| {});
+        for t in 0..4 {
+            context.add_plan(f64::from(t), |context| context.emit_event(StatsEvent));
+        }
+        // No handlers are subscribed to this event
+        context.add_plan(5.0, |context| context.emit_event(UnhandledEvent));
+        context.execute();
+
+        let after = context.execution_statistics();
+        assert_eq!(after.schema_version, EXECUTION_STATISTICS_SCHEMA_VERSION);
+        assert_eq!(after.plans_executed, 5);
+        assert_eq!(after.callbacks_executed, 4);
+        // Not including the start of the simulation or the time steps
+        assert_eq!(after.events_emitted, 8);
+        assert_eq!(after.simulation_time, 5.0);
+        assert_eq!(after.population, 3);
+        assert!(!after.running);
+        assert!(after.wall_time_seconds > 0.0);
+    }
+
+    #[test]
+    fn partial_statistics_while_running() {
+        let mut context = Context::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        for t in 1..=3 {
+            let seen = Rc::clone(&seen);
+            context.add_plan(f64::from(t), move |context| {
+                seen.borrow_mut().push(context.execution_statistics());
+            });
+        }
+        context.execute();
+
+        let seen = seen.borrow();
+        // Each plan is counted from when it starts
+        for (t, statistics) in (1..=3u32).zip(seen.iter()) {
+            assert!(statistics.running);
+            assert_eq!(statistics.plans_executed, u64::from(t));
+            assert_eq!(statistics.simulation_time, f64::from(t));
+        }
+        assert!(seen[0].wall_time_seconds <= seen[2].wall_time_seconds);
+    }
+
+    #[test]
+    fn counts_pending_plans() {
+        let mut context = Context::new();
+        let plans: Vec<_> = (0..4)
+            .map(|t| context.add_plan(f64::from(t), |_| {}))
+            .collect();
+        assert_eq!(context.execution_statistics().plans_pending, 4);
+        context.cancel_plan(&plans[2]);
+        assert_eq!(context.execution_statistics().plans_pending, 3);
+        context.add_plan(1.5, |context| {
+            assert_eq!(context.execution_statistics().plans_pending, 1);
+        });
+        context.execute();
+        assert_eq!(context.execution_statistics().plans_pending, 0);
+    }
+
+    #[test]
+    fn publishes_periodically_while_running() {
+        let mut context = Context::new();
+        let published = Rc::new(RefCell::new(Vec::new()));
+        let published_clone = Rc::clone(&published);
+        context.publish_execution_statistics_every(Duration::from_millis(20), move |statistics| {
+            published_clone.borrow_mut().push(statistics.clone());
+        });
+        for t in 0..5 {
+            context.add_plan(f64::from(t), |_| thread::sleep(Duration::from_millis(25)));
+        }
+        // Nothing is published before the simulation runs.
+        assert!(published.borrow().is_empty());
+        context.execute();
+
+        let published = published.borrow();
+        // Before each plan after the first, and before the loop exits
+        assert_eq!(published.len(), 5);
+        for (statistics, plans) in published.iter().zip(1u64..) {
+            assert!(statistics.running);
+            assert_eq!(statistics.plans_executed, plans);
+            assert_eq!(
+                statistics.plans_pending,
+                5 - usize::try_from(plans).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The interval must be positive")]
+    fn zero_interval_panics() {
+        Context::new().print_execution_statistics_every(Duration::ZERO);
+    }
+
+    #[test]
+    fn report_rows() {
+        let dir = tempdir().unwrap();
+        let mut context = Context::new();
+        context.report_options().directory(dir.path().to_path_buf());
+        context.add_report::<StatsReport>("stats").unwrap();
+        for t in 0..7 {
+            context.add_plan(f64::from(t), |context| {
+                context.send_report(StatsReport {
+                    time: context.get_current_time(),
+                });
+            });
+        }
+        context.execute();
+
+        let reports = context.execution_statistics().reports.unwrap();
+        assert_eq!(
+            reports.files,
+            vec![ReportFileStatistics {
+                name: "stats".to_string(),
+                path: dir.path().join("stats.csv").display().to_string(),
+                rows: 7,
+            }]
+        );
+    }
+}
@@ -0,0 +1,772 @@
+//! Runs an experiment described by a YAML manifest: a set of scenarios,
+//! each with its own parameter values, modes and number of replications.
+//!
+//! A manifest looks like this:
+//!
+//! ```yaml
+//! name: school-closures
+//! # Where the results go. Relative paths are relative to the manifest.
+//! output_dir: results
+//! # The seed that the seeds of replications are derived from
+//! base_seed: 42
+//! # A global properties file loaded for every run
+//! config: base.json
+//! # Global property values for every run
+//! parameters:
+//!   ixa.Population: 10000
+//! scenarios:
+//!   - name: baseline
+//!     replications: 10
+//!   - name: closures
+//!     # Overrides the values above for this scenario
+//!     parameters:
+//!       ixa.ClosureDays: 14
+//!     modes:
+//!       SchoolPolicy: Closed
+//!     seeds: [1, 2, 3]
+//!     # The directory of this scenario, relative to `output_dir`.
+//!     # Defaults to the name of the scenario.
+//!     output: closed-schools
+//! ```
+//!
+//! Every scenario is run once per replication, or once per seed if it
+//! lists `seeds`, with [`run_experiment()`] from Rust or with
+//! `run-experiment <manifest>` on the command line of a model that uses
+//! [`run_with_args()`](crate::runner::run_with_args). The manifest is
+//! checked before anything runs, so that a misspelled property doesn't
+//! show up halfway through.
+//!
+//! Each run writes its reports to `<output_dir>/<output>/rep<n>/` and,
+//! once it finishes successfully, a `run.json` file with its
+//! [`RunSummary`]. A failed run doesn't stop the others. When all of them
+//! are done, `experiment_summary.json` is written to `output_dir` with the
+//! [`ExperimentSummary`].
+//!
+//! Running an experiment again resumes it: runs that have a `run.json`
+//! file are skipped, unless [`ExperimentOptions::force`] is set.
+use crate::context::Context;
+use crate::error::IxaError;
+use crate::global_properties::{
+    parse_global_property_value, replace_global_property_value, ContextGlobalPropertiesExt,
+};
+use crate::modes::ContextModeExt;
+use crate::random::{mix_seed, ContextRandomExt};
+use crate::report::ContextReportExt;
+use crate::runner::run_in_parallel;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Instant;
+
+/// The name of the file written to a run's directory when it succeeds
+pub const RUN_FILE: &str = "run.json";
+
+/// The name of the file written to the output directory of an experiment
+pub const SUMMARY_FILE: &str = "experiment_summary.json";
+
+/// An experiment, as read from a manifest
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentManifest {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub base_seed: u64,
+    #[serde(default)]
+    pub config: Option<PathBuf>,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, serde_json::Value>,
+    pub scenarios: Vec<ScenarioManifest>,
+}
+
+/// A scenario of an [`ExperimentManifest`]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioManifest {
+    pub name: String,
+    #[serde(default)]
+    pub parameters: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub modes: BTreeMap<String, String>,
+    #[serde(default)]
+    pub replications: Option<usize>,
+    #[serde(default)]
+    pub seeds: Option<Vec<u64>>,
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+}
+
+/// Options for [`run_experiment_with_options()`]
+#[derive(Debug, Clone)]
+pub struct ExperimentOptions {
+    /// Rerun runs that already finished
+    pub force: bool,
+    /// The number of runs to run at once, each on its own thread
+    pub threads: usize,
+}
+
+impl Default for ExperimentOptions {
+    fn default() -> Self {
+        ExperimentOptions {
+            force: false,
+            threads: thread::available_parallelism().map_or(1, usize::from),
+        }
+    }
+}
+
+/// A run of an experiment: one replication of one scenario
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExperimentRun {
+    /// The name of the scenario
+    pub scenario: String,
+    /// The index of the replication within the scenario
+    pub replication: usize,
+    /// The random seed
+    pub seed: u64,
+    /// The directory that the run's reports are written to
+    pub output_dir: PathBuf,
+    /// The mode values of the scenario, as `MODE=VALUE`
+    pub modes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Succeeded,
+    Failed,
+    /// Finished by an earlier invocation of the experiment
+    Skipped,
+}
+
+/// What's needed to reproduce a run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReproducibilityStamp {
+    pub seed: u64,
+    pub ixa_version: String,
+    /// A hash of the manifest's contents
+    pub manifest_hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub scenario: String,
+    pub replication: usize,
+    pub output_dir: PathBuf,
+    pub status: RunStatus,
+    /// Why the run failed
+    pub error: Option<String>,
+    pub duration_secs: f64,
+    pub stamp: ReproducibilityStamp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExperimentSummary {
+    pub name: Option<String>,
+    pub runs: Vec<RunSummary>,
+}
+
+impl ExperimentSummary {
+    /// The number of runs that failed
+    #[must_use]
+    pub fn failed(&self) -> usize {
+        self.runs
+            .iter()
+            .filter(|run| run.status == RunStatus::Failed)
+            .count()
+    }
+}
+
+impl ExperimentManifest {
+    /// Read and validate a manifest.
+    ///
+    /// # Errors
+    /// Returns [`IxaError`] if the file can't be read or parsed, or
+    /// [`IxaError::MultipleErrors`] with every problem found by
+    /// [`ExperimentManifest::validate()`].
+    pub fn from_path(path: &Path) -> Result<ExperimentManifest, IxaError> {
+        Self::from_yaml(&fs::read_to_string(path)?)
+    }
+
+    /// Parse and validate a manifest.
+    ///
+    /// # Errors
+    /// Returns [`IxaError`] if the manifest can't be parsed, or
+    /// [`IxaError::MultipleErrors`] with every problem found by
+    /// [`ExperimentManifest::validate()`].
+    pub fn from_yaml(yaml: &str) -> Result<ExperimentManifest, IxaError> {
+        let manifest: ExperimentManifest = serde_yaml::from_str(yaml)
+            .map_err(|error| IxaError::from(format!("Invalid manifest: {error}")))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Check that every global property and mode in the manifest exists
+    /// and has a valid value, and that the scenarios have distinct names
+    /// and output directories and at least one run each. The config file
+    /// isn't checked, since it's relative to the manifest's directory.
+    ///
+    /// # Errors
+    /// Returns [`IxaError::MultipleErrors`] with every problem found.
+    pub fn validate(&self) -> Result<(), IxaError> {
+        let mut errors = Vec::new();
+        if self.scenarios.is_empty() {
+            errors.push(IxaError::from("The manifest has no scenarios"));
+        }
+        for (name, value) in &self.parameters {
+            if let Err(error) = parse_global_property_value(name, value.clone()) {
+                errors.push(error);
+            }
+        }
+        let mut names = HashSet::new();
+        let mut outputs = HashSet::new();
+        for scenario in &self.scenarios {
+            let name = &scenario.name;
+            if !names.insert(name) {
+                errors.push(IxaError::from(format!("Duplicate scenario {name}")));
+            }
+            if !outputs.insert(scenario.output()) {
+                errors.push(IxaError::from(format!(
+                    "Scenario {name} has the same output directory as another scenario"
+                )));
+            }
+            match (scenario.replications, &scenario.seeds) {
+                (Some(_), Some(_)) => errors.push(IxaError::from(format!(
+                    "Scenario {name} has both replications and seeds"
+                ))),
+                (Some(0), None) => {
+                    errors.push(IxaError::from(format!(
+                        "Scenario {name} has no replications"
+                    )));
+                }
+                (None, Some(seeds)) if seeds.is_empty() => {
+                    errors.push(IxaError::from(format!("Scenario {name} has no seeds")));
+                }
+                _ => {}
+            }
+            for (property, value) in &scenario.parameters {
+                if let Err(error) = parse_global_property_value(property, value.clone()) {
+                    errors.push(IxaError::from(format!("Scenario {name}: {error}")));
+                }
+            }
+            let mut context = Context::new();
+            for (mode, value) in &scenario.modes {
+                if let Err(error) = context.set_mode_by_name(mode, value) {
+                    errors.push(IxaError::from(format!("Scenario {name}: {error}")));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(IxaError::MultipleErrors(errors))
+        }
+    }
+
+    /// Every run of the experiment, scenario by scenario, with output
+    /// directories under `output_dir`.
+    #[must_use]
+    pub fn runs(&self, output_dir: &Path) -> Vec<ExperimentRun> {
+        self.scenario_runs(output_dir)
+            .into_iter()
+            .map(|(_, run)| run)
+            .collect()
+    }
+
+    // The runs of `runs()`, each with its scenario.
+    fn scenario_runs(&self, output_dir: &Path) -> Vec<(&ScenarioManifest, ExperimentRun)> {
+        let mut runs = Vec::new();
+        for scenario in &self.scenarios {
+            let seeds = scenario.seeds.clone().unwrap_or_else(|| {
+                (0..scenario.replications.unwrap_or(1))
+                    .map(|replication| {
+                        replication_seed(self.base_seed, &scenario.name, replication)
+                    })
+                    .collect()
+            });
+            let modes: Vec<String> = scenario
+                .modes
+                .iter()
+                .map(|(mode, value)| format!("{mode}={value}"))
+                .collect();
+            for (replication, seed) in seeds.into_iter().enumerate() {
+                runs.push((
+                    scenario,
+                    ExperimentRun {
+                        scenario: scenario.name.clone(),
+                        replication,
+                        seed,
+                        output_dir: output_dir
+                            .join(scenario.output())
+                            .join(format!("rep{replication}")),
+                        modes: modes.clone(),
+                    },
+                ));
+            }
+        }
+        runs
+    }
+}
+
+impl ScenarioManifest {
+    fn output(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(&self.name))
+    }
+}
+
+// The seed of a replication of a scenario that doesn't list its seeds.
+// The scenario's name is mixed into the base seed, and then the
+// replication as a `u64`, so the seed is the same on every platform.
+fn replication_seed(base_seed: u64, scenario: &str, replication: usize) -> u64 {
+    mix_seed(
+        mix_seed(base_seed, scenario.as_bytes()),
+        &(replication as u64).to_le_bytes(),
+    )
+}
+
+/// Runs the experiment described by the manifest at `path` with the
+/// default [`ExperimentOptions`]. See [`run_experiment_with_options()`].
+///
+/// # Errors
+/// Returns [`IxaError`] if the manifest or the config file can't be read
+/// or isn't valid, or the summary can't be written.
+pub fn run_experiment<F>(path: &Path, setup_fn: F) -> Result<ExperimentSummary, IxaError>
+where
+    F: Fn(&mut Context, &ExperimentRun) -> Result<(), IxaError> + Sync,
+{
+    run_experiment_with_options(path, setup_fn, &ExperimentOptions::default())
+}
+
+/// Runs the experiment described by the manifest at `path`, on up to
+/// `options.threads` threads at once.
+///
+/// For each run, a new `Context` is set up with the config file, the
+/// parameters and modes of the scenario, the run's seed, and reports
+/// written to the run's directory, overwriting any left over from an
+/// unfinished run. `setup_fn` is then called with the context and the
+/// run, and the context is executed. A run fails if `setup_fn` returns an
+/// error or the simulation panics.
+///
+/// Returns the summary, which is also written to the output directory.
+///
+/// # Errors
+/// Returns [`IxaError`] if the manifest or the config file can't be read
+/// or isn't valid, or the summary can't be written. Failed runs aren't
+/// errors; see [`ExperimentSummary::failed()`].
+pub fn run_experiment_with_options<F>(
+    path: &Path,
+    setup_fn: F,
+    options: &ExperimentOptions,
+) -> Result<ExperimentSummary, IxaError>
+where
+    F: Fn(&mut Context, &ExperimentRun) -> Result<(), IxaError> + Sync,
+{
+    let contents = fs::read_to_string(path)?;
+    let manifest = ExperimentManifest::from_yaml(&contents)?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let output_dir = base_dir.join(manifest.output_dir.clone().unwrap_or_default());
+    let config = manifest.config.as_ref().map(|config| base_dir.join(config));
+    if let Some(config) = &config {
+        // Check the config file too before running anything.
+        Context::new().load_global_properties(config)?;
+    }
+    let stamp = |seed| ReproducibilityStamp {
+        seed,
+        ixa_version: env!("CARGO_PKG_VERSION").to_string(),
+        manifest_hash: format!("{:016x}", fxhash::hash64(&contents)),
+    };
+
+    let runs = manifest.scenario_runs(&output_dir);
+    let run_one = |index: usize| -> RunSummary {
+        let (scenario, run) = &runs[index];
+        let run_file = run.output_dir.join(RUN_FILE);
+        if !options.force {
+            if let Some(mut summary) = fs::read_to_string(&run_file)
+                .ok()
+                .and_then(|previous| serde_json::from_str::<RunSummary>(&previous).ok())
+            {
+                summary.status = RunStatus::Skipped;
+                return summary;
+            }
+        }
+
+        let start = Instant::now();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            execute_run(&manifest, scenario, config.as_deref(), run, &setup_fn)
+        }))
+        .unwrap_or_else(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(ToString::to_string)
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(IxaError::from(format!("Run panicked: {message}")))
+        });
+        let mut summary = RunSummary {
+            scenario: run.scenario.clone(),
+            replication: run.replication,
+            output_dir: run.output_dir.clone(),
+            status: RunStatus::Succeeded,
+            error: None,
+            duration_secs: start.elapsed().as_secs_f64(),
+            stamp: stamp(run.seed),
+        };
+        let result = result.and_then(|()| {
+            fs::create_dir_all(&run.output_dir)?;
+            fs::write(&run_file, serde_json::to_string_pretty(&summary)?)?;
+            Ok(())
+        });
+        if let Err(error) = result {
+            summary.status = RunStatus::Failed;
+            summary.error = Some(error.to_string());
+        }
+        summary
+    };
+
+    let summary = ExperimentSummary {
+        name: manifest.name.clone(),
+        runs: run_in_parallel(runs.len(), options.threads, run_one),
+    };
+    fs::create_dir_all(&output_dir)?;
+    fs::write(
+        output_dir.join(SUMMARY_FILE),
+        serde_json::to_string_pretty(&summary)?,
+    )?;
+    Ok(summary)
+}
+
+fn execute_run<F>(
+    manifest: &ExperimentManifest,
+    scenario: &ScenarioManifest,
+    config: Option<&Path>,
+    run: &ExperimentRun,
+    setup_fn: &F,
+) -> Result<(), IxaError>
+where
+    F: Fn(&mut Context, &ExperimentRun) -> Result<(), IxaError>,
+{
+    let mut context = Context::new();
+    if let Some(config) = config {
+        context.load_global_properties(config)?;
+    }
+    // The scenario's values replace the experiment's, which replace the
+    // config file's.
+    for (name, value) in manifest.parameters.iter().chain(&scenario.parameters) {
+        let parsed = parse_global_property_value(name, value.clone())?;
+        replace_global_property_value(&mut context, parsed);
+    }
+    for (mode, value) in &scenario.modes {
+        context.set_mode_by_name(mode, value)?;
+    }
+    context
+        .report_options()
+        .directory(run.output_dir.clone())
+        .overwrite(true);
+    context.init_random(run.seed);
+    setup_fn(&mut context, run)?;
+    context.execute();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::define_global_property;
+    use crate::define_simulation_mode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    define_global_property!(ExperimentContacts, u32);
+    define_global_property!(ExperimentFails, bool);
+    define_simulation_mode!(ExperimentPolicy { Open, Closed });
+
+    fn write_manifest(dir: &Path, yaml: &str) -> PathBuf {
+        let path = dir.join("manifest.yaml");
+        fs::write(&path, yaml).unwrap();
+        path
+    }
+
+    fn errors(result: Result<ExperimentManifest, IxaError>) -> Vec<String> {
+        match result {
+            Err(IxaError::MultipleErrors(errors)) => {
+                errors.iter().map(ToString::to_string).collect()
+            }
+            other => panic!("Expected MultipleErrors, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = ExperimentManifest::from_yaml("scenarios: [{name: a, replicates: 2}]")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("unknown field `replicates`"), "{error}");
+        assert!(ExperimentManifest::from_yaml("name: x").is_err());
+        assert!(ExperimentManifest::from_yaml("scenarios: [").is_err());
+    }
+
+    #[test]
+    fn validation_errors() {
+        let errors = errors(ExperimentManifest::from_yaml(
+            "
+parameters:
+  ixa.NoSuchProperty: 1
+scenarios:
+  - name: a
+    replications: 2
+    seeds: [1]
+  - name: a
+    output: b
+  - name: b
+    replications: 0
+    parameters:
+      ixa.ExperimentContacts: -1
+    modes:
+      ExperimentPolicy: Ajar
+",
+        ));
+        assert_eq!(errors.len(), 7, "{errors:?}");
+        assert!(errors[0].contains("No global property: ixa.NoSuchProperty"));
+        assert!(errors[1].contains("both replications and seeds"));
+        assert!(errors[2].contains("Duplicate scenario a"));
+        assert!(errors[3].contains("Scenario b has the same output directory"));
+        assert!(errors[4].contains("Scenario b has no replications"));
+        assert!(errors[5].contains("Scenario b: "));
+        assert!(errors[6].contains("Ajar"));
+    }
+
+    #[test]
+    fn invalid_manifest_runs_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_manifest(
+            dir.path(),
+            "scenarios: [{name: a}, {name: b, parameters: {ixa.Misspelled: 1}}]",
+        );
+        let calls = AtomicUsize::new(0);
+        let result = run_experiment(&path, |_, _| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        });
+        assert!(matches!(result, Err(IxaError::MultipleErrors(_))));
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+        assert!(!dir.path().join(SUMMARY_FILE).exists());
+    }
+
+    #[test]
+    fn expands_scenarios_and_replications() {
+        let manifest = ExperimentManifest::from_yaml(
+            "
+base_seed: 7
+scenarios:
+  - name: baseline
+    replications: 3
+  - name: seeded
+    seeds: [10, 20]
+    output: other
+  - name: once
+",
+        )
+        .unwrap();
+        let runs = manifest.runs(Path::new("out"));
+        assert_eq!(runs.len(), 6);
+        assert_eq!(
+            runs.iter().filter(|run| run.scenario == "baseline").count(),
+            3
+        );
+        assert_eq!(runs[2].replication, 2);
+        assert_eq!(runs[2].output_dir, Path::new("out/baseline/rep2"));
+        assert_eq!(runs[3].seed, 10);
+        assert_eq!(runs[4].seed, 20);
+        assert_eq!(runs[4].output_dir, Path::new("out/other/rep1"));
+        assert_eq!(runs[5].output_dir, Path::new("out/once/rep0"));
+        // Replications have distinct seeds that only depend on the base
+        // seed, the scenario and the replication.
+        assert_ne!(runs[0].seed, runs[1].seed);
+        let reordered = ExperimentManifest::from_yaml(
+            "{base_seed: 7, scenarios: [{name: once}, {name: baseline, replications: 1}]}",
+        )
+        .unwrap()
+        .runs(Path::new("out"));
+        assert_eq!(reordered[1].seed, runs[0].seed);
+        // The seeds are the same on every platform.
+        assert_eq!(
+            runs[..3].iter().map(|run| run.seed).collect::<Vec<_>>(),
+            vec![
+                1_379_420_842_678_650_928,
+                5_790_700_212_673_891_832,
+                6_516_707_078_536_856_129
+            ]
+        );
+    }
+
+    const MIXED: &str = "
+name: mixed
+output_dir: results
+parameters:
+  ixa.ExperimentContacts: 3
+  ixa.ExperimentFails: false
+scenarios:
+  - name: works
+    replications: 2
+    modes:
+      ExperimentPolicy: Closed
+  - name: fails
+    parameters:
+      ixa.ExperimentFails: true
+  - name: panics
+    parameters:
+      ixa.ExperimentContacts: 0
+";
+
+    fn setup(context: &mut Context, run: &ExperimentRun) -> Result<(), IxaError> {
+        assert_eq!(context.report_options().output_dir, run.output_dir);
+        if *context.get_global_property_value(ExperimentFails).unwrap() {
+            return Err(IxaError::from("Failed on purpose"));
+        }
+        let contacts = *context
+            .get_global_property_value(ExperimentContacts)
+            .unwrap();
+        assert!(contacts > 0, "No contacts");
+        if run.scenario == "works" {
+            assert_eq!(
+                context.mode(ExperimentPolicy),
+                ExperimentPolicyValue::Closed
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn summary_of_mixed_experiment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_manifest(dir.path(), MIXED);
+        let options = ExperimentOptions {
+            force: false,
+            threads: 2,
+        };
+        let summary = run_experiment_with_options(&path, setup, &options).unwrap();
+
+        assert_eq!(summary.name.as_deref(), Some("mixed"));
+        assert_eq!(summary.runs.len(), 4);
+        assert_eq!(summary.failed(), 2);
+        let statuses: Vec<_> = summary.runs.iter().map(|run| run.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                RunStatus::Succeeded,
+                RunStatus::Succeeded,
+                RunStatus::Failed,
+                RunStatus::Failed
+            ]
+        );
+        assert!(summary.runs[2]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("Failed on purpose"));
+        assert!(summary.runs[3]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("Run panicked: No contacts"));
+        let stamp = &summary.runs[0].stamp;
+        assert_eq!(stamp.ixa_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(stamp.manifest_hash, summary.runs[3].stamp.manifest_hash);
+        assert_ne!(stamp.seed, summary.runs[1].stamp.seed);
+
+        // The summary is written at the root and each successful run
+        // records itself in its directory.
+        let results = dir.path().join("results");
+        let written: ExperimentSummary =
+            serde_json::from_str(&fs::read_to_string(results.join(SUMMARY_FILE)).unwrap()).unwrap();
+        assert_eq!(written, summary);
+        assert!(results.join("works/rep1").join(RUN_FILE).exists());
+        assert!(!results.join("fails/rep0").join(RUN_FILE).exists());
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn resume_skips_finished_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_manifest(dir.path(), MIXED);
+        let options = ExperimentOptions {
+            force: false,
+            threads: 1,
+        };
+        let first = run_experiment_with_options(&path, setup, &options).unwrap();
+
+        // Only the failed runs run again.
+        let calls = AtomicUsize::new(0);
+        let count_calls = |_: &mut Context, _: &ExperimentRun| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        };
+        let second = run_experiment_with_options(&path, count_calls, &options).unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(second.failed(), 0);
+        assert_eq!(second.runs[0].status, RunStatus::Skipped);
+        assert_eq!(second.runs[0].duration_secs, first.runs[0].duration_secs);
+        assert_eq!(second.runs[2].status, RunStatus::Succeeded);
+
+        // Now everything is finished, so nothing runs.
+        let third = run_experiment_with_options(&path, count_calls, &options).unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert!(third
+            .runs
+            .iter()
+            .all(|run| run.status == RunStatus::Skipped));
+
+        // Unless it's forced.
+        let forced = ExperimentOptions {
+            force: true,
+            threads: 1,
+        };
+        let fourth = run_experiment_with_options(&path, count_calls, &forced).unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 6);
+        assert!(fourth
+            .runs
+            .iter()
+            .all(|run| run.status == RunStatus::Succeeded));
+    }
+
+    #[test]
+    fn config_file_relative_to_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("base.json"),
+            r#"{"ixa.ExperimentContacts": 5, "ixa.ExperimentFails": false}"#,
+        )
+        .unwrap();
+        let path = write_manifest(
+            dir.path(),
+            "
+config: base.json
+scenarios:
+  - name: from_config
+  - name: overridden
+    parameters:
+      ixa.ExperimentContacts: 8
+",
+        );
+        let seen = std::sync::Mutex::new(Vec::new());
+        let summary = run_experiment(&path, |context, _| {
+            seen.lock().unwrap().push(
+                *context
+                    .get_global_property_value(ExperimentContacts)
+                    .unwrap(),
+            );
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(summary.failed(), 0);
+        let mut seen = seen.into_inner().unwrap();
+        seen.sort_unstable();
+        assert_eq!(seen, [5, 8]);
+        assert!(dir.path().join(SUMMARY_FILE).exists());
+    }
+}
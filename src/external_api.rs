@@ -19,6 +19,174 @@ pub(crate) fn run_ext_api<T: ExtApi>(
     T::run(context, args)
 }
 
+/// The commands that are available once the simulation has finished, in
+/// the debugger's post-run mode and the Web API's keep-alive window. They
+/// inspect or export the final state; commands that would advance or
+/// reload the simulation aren't in the list.
+pub(crate) const POST_RUN_COMMANDS: [&str; 10] = [
+    "population",
+    "global",
+    "people",
+    "values",
+    "summary",
+    "tabulate",
+    "status",
+    "watch",
+    "export",
+    "exit",
+];
+
+// Check the property names up front, so that commands that read every
+// person's values report unknown properties even if there's no one.
+fn check_property_names<'a>(
+    context: &Context,
+    names: impl IntoIterator<Item = &'a String>,
+) -> Result<(), IxaError> {
+    use crate::people::external_api::ContextPeopleExtCrate;
+
+    let registered = context.get_property_names();
+    match names.into_iter().find(|name| !registered.contains(name)) {
+        Some(name) => Err(IxaError::PropertyNotFound { name: name.clone() }),
+        None => Ok(()),
+    }
+}
+
+/// The identifiers of entities as external tools see them: requests to and
+/// responses from the Web API, debugger commands and output, and the
+/// [`PropertyDiffReport`](crate::people::PropertyDiffReport).
+///
+/// A person is written as a tagged string, `person:12`, so that it can't be
+/// mixed up with another number, such as a household id kept in a person
+/// property or a scenario index. People are the only kind of entity, so
+/// `person` is the only tag.
+pub mod ids {
+    use crate::define_data_plugin;
+    use crate::{Context, IxaError, PersonId};
+    use serde::{Deserialize, Serialize};
+
+    /// The tag of a person's id
+    pub const PERSON_TAG: &str = "person";
+
+    /// Write `person_id` as a tagged id, e.g., `person:12`.
+    #[must_use]
+    pub fn encode_person_id(person_id: PersonId) -> String {
+        format!("{PERSON_TAG}:{}", person_id.0)
+    }
+
+    /// Read a person id written by [`encode_person_id()`], or, if
+    /// `accept_bare` is true, a bare integer such as `12`. The person
+    /// doesn't have to exist.
+    ///
+    /// # Errors
+    /// If `id` isn't a tagged person id, or a bare integer that's accepted.
+    pub fn decode_person_id(id: &str, accept_bare: bool) -> Result<PersonId, IxaError> {
+        let invalid = |reason: &str| {
+            IxaError::IxaError(format!(
+                "Invalid person id '{id}': {reason}; expected the form '{PERSON_TAG}:<number>', e.g., '{PERSON_TAG}:12'"
+            ))
+        };
+        match id.split_once(':') {
+            Some((PERSON_TAG, number)) => number
+                .parse()
+                .map(PersonId)
+                .map_err(|_| invalid("the number isn't a non-negative integer")),
+            Some((tag, _)) => Err(invalid(&format!("'{tag}' isn't the tag of a person"))),
+            None if accept_bare => id
+                .parse()
+                .map(PersonId)
+                .map_err(|_| invalid("it isn't tagged or a non-negative integer")),
+            None => Err(invalid("it isn't tagged")),
+        }
+    }
+
+    // Whether requests may give a person id as a bare integer
+    define_data_plugin!(BarePersonIdsPlugin, bool, false);
+
+    pub(crate) fn set_accept_bare_person_ids(context: &mut Context, accept: bool) {
+        *context.get_data_container_mut(BarePersonIdsPlugin) = accept;
+    }
+
+    fn accepts_bare_person_ids(context: &Context) -> bool {
+        context
+            .get_data_container(BarePersonIdsPlugin)
+            .is_some_and(|accept| *accept)
+    }
+
+    /// A person id in a request, which is checked against the tag and
+    /// whether bare integers are accepted when the request is run.
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[serde(untagged)]
+    pub(crate) enum PersonIdArg {
+        Tagged(String),
+        Bare(usize),
+    }
+
+    impl PersonIdArg {
+        pub(crate) fn resolve(&self, context: &Context) -> Result<PersonId, IxaError> {
+            let accept_bare = accepts_bare_person_ids(context);
+            match self {
+                PersonIdArg::Tagged(id) => decode_person_id(id, accept_bare),
+                PersonIdArg::Bare(id) if accept_bare => Ok(PersonId(*id)),
+                PersonIdArg::Bare(id) => decode_person_id(&id.to_string(), false),
+            }
+        }
+    }
+
+    impl From<PersonId> for PersonIdArg {
+        fn from(person_id: PersonId) -> Self {
+            PersonIdArg::Tagged(encode_person_id(person_id))
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn person_ids_round_trip() {
+            for id in [0, 12, 12345, usize::MAX] {
+                let encoded = encode_person_id(PersonId(id));
+                assert_eq!(encoded, format!("person:{id}"));
+                assert_eq!(decode_person_id(&encoded, false).unwrap(), PersonId(id));
+                assert_eq!(decode_person_id(&encoded, true).unwrap(), PersonId(id));
+            }
+        }
+
+        #[test]
+        fn bare_person_ids_need_flag() {
+            assert_eq!(decode_person_id("12", true).unwrap(), PersonId(12));
+            let error = decode_person_id("12", false).unwrap_err().to_string();
+            assert!(error.contains("isn't tagged"), "{error}");
+
+            let mut context = Context::new();
+            let tagged: PersonIdArg = serde_json::from_str("\"person:3\"").unwrap();
+            let bare: PersonIdArg = serde_json::from_str("3").unwrap();
+            assert_eq!(tagged.resolve(&context).unwrap(), PersonId(3));
+            assert!(bare.resolve(&context).is_err());
+            set_accept_bare_person_ids(&mut context, true);
+            assert_eq!(tagged.resolve(&context).unwrap(), PersonId(3));
+            assert_eq!(bare.resolve(&context).unwrap(), PersonId(3));
+        }
+
+        #[test]
+        fn malformed_person_ids() {
+            for (id, reason) in [
+                ("household:678", "'household' isn't the tag of a person"),
+                ("person:", "the number isn't a non-negative integer"),
+                ("person:-1", "the number isn't a non-negative integer"),
+                ("person:1:2", "the number isn't a non-negative integer"),
+                ("abc", "it isn't tagged or a non-negative integer"),
+            ] {
+                let error = decode_person_id(id, true).unwrap_err().to_string();
+                assert_eq!(
+                    error,
+                    format!("Invalid person id '{id}': {reason}; expected the form 'person:<number>', e.g., 'person:12'")
+                );
+            }
+        }
+    }
+}
+
 pub(crate) mod population {
     use crate::context::Context;
     use crate::external_api::EmptyArgs;
@@ -135,6 +303,30 @@ pub(crate) mod next {
     }
 }
 
+pub(crate) mod reload {
+    use crate::context::Context;
+    use crate::external_api::EmptyArgs;
+    use crate::live_overrides::reload_overrides_from;
+    use crate::IxaError;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub(crate) struct Retval {
+        pub changed: usize,
+    }
+    pub(crate) struct Api {}
+    impl super::ExtApi for Api {
+        type Args = EmptyArgs;
+        type Retval = Retval;
+
+        fn run(context: &mut Context, _args: &EmptyArgs) -> Result<Retval, IxaError> {
+            Ok(Retval {
+                changed: reload_overrides_from(context, "web")?,
+            })
+        }
+    }
+}
+
 pub(crate) mod r#continue {
     use crate::context::Context;
     use crate::IxaError;
@@ -144,6 +336,7 @@ pub(crate) mod r#continue {
     #[derive(Parser, Debug, Deserialize)]
     pub(crate) enum Args {}
     #[derive(Serialize)]
+    #[allow(unused)]
     pub(crate) struct Retval {}
     #[allow(unused)]
     pub(crate) struct Api {}
@@ -158,21 +351,122 @@ pub(crate) mod r#continue {
     }
 }
 
+pub(crate) mod status {
+    use crate::context::Context;
+    use crate::execution_stats::ExecutionStatistics;
+    use crate::external_api::EmptyArgs;
+    use crate::IxaError;
+
+    pub(crate) struct Api {}
+    impl super::ExtApi for Api {
+        type Args = EmptyArgs;
+        type Retval = ExecutionStatistics;
+
+        fn run(context: &mut Context, _args: &EmptyArgs) -> Result<ExecutionStatistics, IxaError> {
+            Ok(context.execution_statistics())
+        }
+    }
+}
+
+pub(crate) mod values {
+    use crate::context::Context;
+    use crate::people::external_api::ContextPeopleExtCrate;
+    use crate::people::ContextPeopleExt;
+    use crate::IxaError;
+    use clap::Parser;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Parser, Debug, Deserialize)]
+    pub(crate) enum Args {
+        /// List the values of a person property with the number of people
+        /// who have each one
+        Values {
+            /// The property name
+            property: String,
+        },
+    }
+
+    #[derive(Serialize, Debug, PartialEq)]
+    pub(crate) struct Retval {
+        pub values: Vec<(String, usize)>,
+    }
+    pub(crate) struct Api {}
+    impl super::ExtApi for Api {
+        type Args = Args;
+        type Retval = Retval;
+
+        fn run(context: &mut Context, args: &Args) -> Result<Retval, IxaError> {
+            let Args::Values { property } = args;
+            if context.get_current_population() == 0 {
+                return Err(IxaError::IxaError(String::from("Empty population")));
+            }
+            Ok(Retval {
+                values: context.get_distinct_property_values_by_name(property)?,
+            })
+        }
+    }
+}
+
+pub(crate) mod summary {
+    use crate::context::Context;
+    use crate::people::{summarize_by_name, ContextPeopleExt, PropertySummary};
+    use crate::IxaError;
+    use clap::Parser;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Parser, Debug, Deserialize)]
+    pub(crate) enum Args {
+        /// Summarize a numeric person property with its count, mean,
+        /// standard deviation, minimum, quartiles and maximum
+        Summary {
+            /// The property name
+            property: String,
+            /// Summarize separately for each value of this property
+            #[arg(long)]
+            by: Option<String>,
+        },
+    }
+
+    #[derive(Serialize, Debug, PartialEq)]
+    pub(crate) struct Retval {
+        pub summaries: Vec<(Option<String>, PropertySummary)>,
+    }
+    pub(crate) struct Api {}
+    impl super::ExtApi for Api {
+        type Args = Args;
+        type Retval = Retval;
+
+        fn run(context: &mut Context, args: &Args) -> Result<Retval, IxaError> {
+            let Args::Summary { property, by } = args;
+            if context.get_current_population() == 0 {
+                return Err(IxaError::IxaError(String::from("Empty population")));
+            }
+            Ok(Retval {
+                summaries: summarize_by_name(context, property, by.as_deref())?,
+            })
+        }
+    }
+}
+
 pub(crate) mod people {
-    use crate::people::{external_api::ContextPeopleExtCrate, ContextPeopleExt, PersonId};
+    use crate::external_api::ids::{encode_person_id, PersonIdArg};
+    use crate::people::{external_api::ContextPeopleExtCrate, ContextPeopleExt};
     use crate::Context;
     use crate::IxaError;
     use serde::{Deserialize, Serialize};
 
     #[derive(Deserialize)]
     pub(crate) enum ArgsEnum {
+        /// Get the value of a property of a person, or of all the registered
+        /// properties if `property` is omitted
         Get {
-            person_id: PersonId,
-            property: String,
-        },
-        Query {
-            properties: Vec<(String, String)>,
+            person_id: PersonIdArg,
+            #[serde(default)]
+            property: Option<String>,
         },
+        /// List the people whose properties have all the given values,
+        /// compared as they're displayed
+        Query { properties: Vec<(String, String)> },
     }
 
     #[derive(Deserialize)]
@@ -183,6 +477,7 @@ pub(crate) mod people {
     #[derive(Serialize, Debug, Eq, PartialEq)]
     pub(crate) enum Retval {
         Properties(Vec<(String, String)>),
+        People(Vec<String>),
     }
     pub(crate) struct Api {}
 
@@ -198,17 +493,40 @@ pub(crate) mod people {
                     person_id,
                     property,
                 } => {
-                    if person_id.0 >= context.get_current_population() {
-                        return Err(IxaError::IxaError(format!("No person with id {person_id}")));
+                    let person_id = person_id.resolve(context)?;
+                    if !context.person_exists(person_id) {
+                        return Err(IxaError::PersonNotFound(person_id));
                     }
 
-                    let value = context.get_person_property_by_name(property, *person_id)?;
-                    Ok(Retval::Properties(vec![(property.to_string(), value)]))
+                    let properties = match property {
+                        Some(property) => vec![property.clone()],
+                        None => context.get_property_names(),
+                    };
+                    let values = properties
+                        .into_iter()
+                        .map(|property| {
+                            let value =
+                                context.get_person_property_by_name(&property, person_id)?;
+                            Ok((property, value))
+                        })
+                        .collect::<Result<_, IxaError>>()?;
+                    Ok(Retval::Properties(values))
                 }
-                ArgsEnum::Query {
-                    properties: _global_properties,
-                } => {
-                    unimplemented!();
+                ArgsEnum::Query { properties } => {
+                    super::check_property_names(
+                        context,
+                        properties.iter().map(|(property, _)| property),
+                    )?;
+                    let mut people = Vec::new();
+                    'people: for person_id in context.query_people(()) {
+                        for (property, value) in properties {
+                            if context.get_person_property_by_name(property, person_id)? != *value {
+                                continue 'people;
+                            }
+                        }
+                        people.push(encode_person_id(person_id));
+                    }
+                    Ok(Retval::People(people))
                 }
             }
         }
@@ -218,7 +536,9 @@ pub(crate) mod people {
     mod test {
         use super::*;
         use crate::external_api::run_ext_api;
-        use crate::Context;
+        use crate::{
+            define_person_property, define_person_property_with_default, Context, PersonId,
+        };
         #[test]
         fn query_nonexistent_user() {
             let mut context = Context::new();
@@ -226,13 +546,13 @@ pub(crate) mod people {
             let res = run_ext_api::<super::Api>(
                 &mut context,
                 &Args::People(ArgsEnum::Get {
-                    person_id: PersonId(0),
-                    property: String::from("abc"),
+                    person_id: PersonId(0).into(),
+                    property: Some(String::from("abc")),
                 }),
             );
 
             println!("{res:?}");
-            assert!(matches!(res, Err(IxaError::IxaError(_))));
+            assert!(matches!(res, Err(IxaError::PersonNotFound(PersonId(0)))));
         }
 
         #[test]
@@ -242,13 +562,201 @@ pub(crate) mod people {
             let res = run_ext_api::<super::Api>(
                 &mut context,
                 &Args::People(ArgsEnum::Get {
-                    person_id: PersonId(0),
-                    property: String::from("abc"),
+                    person_id: PersonId(0).into(),
+                    property: Some(String::from("abc")),
                 }),
             );
 
             println!("{res:?}");
-            assert!(matches!(res, Err(IxaError::IxaError(_))));
+            assert!(matches!(res, Err(IxaError::PropertyNotFound { .. })));
+        }
+
+        #[test]
+        fn query_all_properties() {
+            define_person_property!(Age, u8);
+            define_person_property_with_default!(IsRunner, bool, false);
+            let mut context = Context::new();
+            let person = context.add_person((Age, 10)).unwrap();
+            context.get_person_property(person, IsRunner);
+            let res = run_ext_api::<super::Api>(
+                &mut context,
+                &Args::People(ArgsEnum::Get {
+                    person_id: person.into(),
+                    property: None,
+                }),
+            );
+            assert_eq!(
+                res.unwrap(),
+                Retval::Properties(vec![
+                    (String::from("Age"), String::from("10")),
+                    (String::from("IsRunner"), String::from("false")),
+                ])
+            );
+        }
+    }
+}
+
+pub(crate) mod tabulate {
+    use crate::context::Context;
+    use crate::people::external_api::ContextPeopleExtCrate;
+    use crate::people::ContextPeopleExt;
+    use crate::IxaError;
+    use clap::Parser;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Parser, Debug, Deserialize)]
+    pub(crate) enum Args {
+        /// Count the people with each combination of values of some person
+        /// properties
+        Tabulate {
+            /// The property names
+            #[arg(required = true)]
+            properties: Vec<String>,
+        },
+    }
+
+    #[derive(Serialize, Debug, PartialEq)]
+    pub(crate) struct Retval {
+        /// The values of the properties, in the order given, and the number
+        /// of people who have them, sorted by the values
+        pub rows: Vec<(Vec<String>, usize)>,
+    }
+    pub(crate) struct Api {}
+    impl super::ExtApi for Api {
+        type Args = Args;
+        type Retval = Retval;
+
+        fn run(context: &mut Context, args: &Args) -> Result<Retval, IxaError> {
+            let Args::Tabulate { properties } = args;
+            super::check_property_names(context, properties)?;
+            let mut counts = BTreeMap::<Vec<String>, usize>::new();
+            for person_id in context.query_people(()) {
+                let values = properties
+                    .iter()
+                    .map(|property| context.get_person_property_by_name(property, person_id))
+                    .collect::<Result<_, _>>()?;
+                *counts.entry(values).or_default() += 1;
+            }
+            Ok(Retval {
+                rows: counts.into_iter().collect(),
+            })
+        }
+    }
+}
+
+pub(crate) mod export {
+    use crate::context::Context;
+    use crate::external_api::ids::encode_person_id;
+    use crate::global_properties::ContextGlobalPropertiesExt;
+    use crate::people::external_api::ContextPeopleExtCrate;
+    use crate::people::ContextPeopleExt;
+    use crate::IxaError;
+    use clap::{Parser, Subcommand};
+    use serde::{Deserialize, Serialize};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[derive(Subcommand, Debug, Deserialize)]
+    pub(crate) enum ArgsEnum {
+        /// Write a CSV file with a row for each person and a column for each
+        /// person property
+        People {
+            /// The file to write
+            path: PathBuf,
+        },
+        /// Write a JSON file with the current time, the global properties
+        /// that are set, and each person's property values
+        Snapshot {
+            /// The file to write
+            path: PathBuf,
+        },
+    }
+
+    #[derive(Parser, Debug, Deserialize)]
+    pub(crate) enum Args {
+        /// Export the state of the simulation
+        #[command(subcommand)]
+        Export(ArgsEnum),
+    }
+
+    #[derive(Serialize, Debug, PartialEq)]
+    pub(crate) struct Retval {
+        pub path: String,
+        pub people: usize,
+    }
+    pub(crate) struct Api {}
+    impl super::ExtApi for Api {
+        type Args = Args;
+        type Retval = Retval;
+
+        fn run(context: &mut Context, args: &Args) -> Result<Retval, IxaError> {
+            let Args::Export(args) = args;
+            let (ArgsEnum::People { path } | ArgsEnum::Snapshot { path }) = args;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let people = match args {
+                ArgsEnum::People { path } => write_people_csv(context, path)?,
+                ArgsEnum::Snapshot { path } => write_snapshot_json(context, path)?,
+            };
+            Ok(Retval {
+                path: path.display().to_string(),
+                people,
+            })
+        }
+    }
+
+    fn write_people_csv(context: &Context, path: &Path) -> Result<usize, IxaError> {
+        let properties = context.get_property_names();
+        let mut writer = csv::Writer::from_path(path)?;
+        let mut header = vec!["person_id"];
+        header.extend(properties.iter().map(String::as_str));
+        writer.write_record(header)?;
+        let people = context.query_people(());
+        for &person_id in &people {
+            let mut record = vec![encode_person_id(person_id)];
+            for property in &properties {
+                record.push(context.get_person_property_by_name(property, person_id)?);
+            }
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+        Ok(people.len())
+    }
+
+    fn write_snapshot_json(context: &Context, path: &Path) -> Result<usize, IxaError> {
+        let mut global_properties = serde_json::Map::new();
+        for name in context.list_registered_global_properties() {
+            if let Some(value) = context.get_serialized_value_by_string(&name)? {
+                global_properties.insert(name, serde_json::from_str(&value)?);
+            }
+        }
+        let properties = context.get_property_names();
+        let people = context.query_people(());
+        let mut rows = Vec::with_capacity(people.len());
+        for &person_id in &people {
+            let mut row = serde_json::Map::new();
+            row.insert(
+                String::from("person_id"),
+                encode_person_id(person_id).into(),
+            );
+            for property in &properties {
+                row.insert(
+                    property.clone(),
+                    context
+                        .get_person_property_by_name(property, person_id)?
+                        .into(),
+                );
+            }
+            rows.push(serde_json::Value::Object(row));
         }
+        let snapshot = serde_json::json!({
+            "time": context.get_current_time(),
+            "global_properties": global_properties,
+            "people": rows,
+        });
+        fs::write(path, serde_json::to_string_pretty(&snapshot)?)?;
+        Ok(people.len())
     }
 }
@@ -1,8 +1,8 @@
 //! A generic mechanism for storing context-wide data.
 //!
-//! Global properties are not mutable and represent variables that are
-//! required in a global scope during the simulation, such as
-//! simulation parameters.
+//! Global properties represent variables that are required in a global
+//! scope during the simulation, such as simulation parameters, and are
+//! usually not mutable.
 //! A global property can be of any type, and is is just a value
 //! stored in the context. Global properties are defined by the
 //! [`define_global_property!()`] macro and can then be
@@ -12,10 +12,14 @@
 //! * Loaded from a configuration file using [`Context::load_global_properties()`]
 //!
 //! Attempting to change a global property which has been set already
-//! will result in an error.
+//! will result in an error, unless the property was defined with
+//! [`define_mutable_global_property!()`], such as an intervention that
+//! starts partway through the simulation. Changing a mutable property
+//! emits a [`GlobalPropertyChangeEvent`]. Mutable properties can also be
+//! reloaded from a file with live overrides (see [`crate::live_overrides`]).
 //!
 //! Global properties can be read with [`Context::get_global_property_value()`]
-use crate::context::Context;
+use crate::context::{Context, IxaEvent};
 use crate::error::IxaError;
 use log::trace;
 use serde::de::DeserializeOwned;
@@ -26,6 +30,7 @@ use std::fmt::Debug;
 use std::fs;
 use std::io::BufReader;
 use std::path::Path;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::LazyLock;
 use std::sync::Mutex;
@@ -35,9 +40,14 @@ type PropertySetterFn =
 
 type PropertyGetterFn = dyn Fn(&Context) -> Result<Option<String>, IxaError> + Send + Sync;
 
+type PropertyParserFn =
+    dyn Fn(serde_json::Value) -> Result<ParsedGlobalProperty, IxaError> + Send + Sync;
+
 pub struct PropertyAccessors {
+    type_id: TypeId,
     setter: Box<PropertySetterFn>,
     getter: Box<PropertyGetterFn>,
+    parser: Box<PropertyParserFn>,
 }
 
 #[allow(clippy::type_complexity)]
@@ -56,13 +66,14 @@ pub fn add_global_property<T: GlobalProperty>(name: &str)
 where
     for<'de> <T as GlobalProperty>::Value: serde::Deserialize<'de> + serde::Serialize,
 {
-    trace!("Adding global property {}", name);
+    trace!("Adding global property {name}");
     let properties = GLOBAL_PROPERTIES.lock().unwrap();
     assert!(properties
         .borrow_mut()
         .insert(
             name.to_string(),
             Arc::new(PropertyAccessors {
+                type_id: TypeId::of::<T>(),
                 setter: Box::new(
                     |context: &mut Context, name, value| -> Result<(), IxaError> {
                         let val: T::Value = serde_json::from_value(value)?;
@@ -81,6 +92,16 @@ where
                         None => Ok(None),
                     }
                 }),
+                parser: Box::new(|value| -> Result<ParsedGlobalProperty, IxaError> {
+                    let val: T::Value = serde_json::from_value(value)?;
+                    T::validate(&val)?;
+                    Ok(ParsedGlobalProperty {
+                        type_id: TypeId::of::<T>(),
+                        is_mutable: T::IS_MUTABLE,
+                        value: Box::new(val),
+                        setter: set_parsed_global_property_value::<T>,
+                    })
+                }),
             })
         )
         .is_none());
@@ -92,6 +113,60 @@ fn get_global_property_accessor(name: &str) -> Option<Arc<PropertyAccessors>> {
     tmp.get(name).map(Arc::clone)
 }
 
+type ParsedSetterFn = fn(&mut Context, Box<dyn Any>) -> Result<(), IxaError>;
+
+/// A validated new value for a global property that has not been stored yet.
+pub(crate) struct ParsedGlobalProperty {
+    type_id: TypeId,
+    /// Whether the property was defined with
+    /// [`define_mutable_global_property!()`]
+    pub(crate) is_mutable: bool,
+    value: Box<dyn Any>,
+    setter: ParsedSetterFn,
+}
+
+impl ParsedGlobalProperty {
+    /// Store the value with [`Context::set_global_property_value()`], so
+    /// changing a property that isn't mutable is an error and replacing
+    /// the value of a mutable one emits a [`GlobalPropertyChangeEvent`].
+    pub(crate) fn set(self, context: &mut Context) -> Result<(), IxaError> {
+        (self.setter)(context, self.value)
+    }
+}
+
+fn set_parsed_global_property_value<T: GlobalProperty>(
+    context: &mut Context,
+    value: Box<dyn Any>,
+) -> Result<(), IxaError> {
+    let value = value.downcast::<T::Value>().unwrap();
+    context.set_global_property_value(T::new(), *value)
+}
+
+/// Deserialize and validate a value for the global property `name` without
+/// storing it, so that several values can be checked before any of them
+/// are applied with [`ParsedGlobalProperty::set()`] or
+/// [`replace_global_property_value()`].
+pub(crate) fn parse_global_property_value(
+    name: &str,
+    value: serde_json::Value,
+) -> Result<ParsedGlobalProperty, IxaError> {
+    let Some(accessor) = get_global_property_accessor(name) else {
+        return Err(IxaError::from(format!("No global property: {name}")));
+    };
+    (accessor.parser)(value)
+}
+
+/// Store a value from [`parse_global_property_value()`], replacing the
+/// current value if there is one, even if the property isn't mutable.
+/// This is for setting up a scenario before it runs, so no
+/// [`GlobalPropertyChangeEvent`] is emitted.
+pub(crate) fn replace_global_property_value(context: &mut Context, parsed: ParsedGlobalProperty) {
+    context
+        .get_data_container_mut(GlobalPropertiesPlugin)
+        .global_property_container
+        .insert(parsed.type_id, parsed.value);
+}
+
 /// Defines a global property with the following parameters:
 /// * `$global_property`: Name for the identifier type of the global property
 /// * `$value`: The type of the property's value
@@ -114,6 +189,14 @@ macro_rules! define_global_property {
             }
         }
 
+        $crate::define_global_property!(@register $global_property);
+    };
+
+    ($global_property: ident, $value: ty) => {
+        define_global_property!($global_property, $value, |_| { Ok(()) });
+    };
+
+    (@register $global_property:ident) => {
         paste::paste! {
             #[ctor::ctor]
             fn [<$global_property:snake _register>]() {
@@ -125,11 +208,80 @@ macro_rules! define_global_property {
             }
         }
     };
+}
+
+/// Defines a global property whose value can be changed after it has been
+/// set, e.g., a transmission reduction that changes when a mask mandate
+/// starts. The parameters are the same as for [`define_global_property!()`],
+/// but the value must be `Clone`.
+///
+/// Setting the value again with [`Context::set_global_property_value()`]
+/// replaces it, after validating the new value, and emits a
+/// [`GlobalPropertyChangeEvent`] with the previous and new values:
+///
+/// ```
+/// # use ixa::{define_mutable_global_property, Context, ContextGlobalPropertiesExt};
+/// # use ixa::global_properties::GlobalPropertyChangeEvent;
+/// define_mutable_global_property!(TransmissionReduction, f64);
+///
+/// let mut context = Context::new();
+/// context.set_global_property_value(TransmissionReduction, 0.0).unwrap();
+/// context.subscribe_to_event(|_context, event: GlobalPropertyChangeEvent<TransmissionReduction>| {
+///     println!("Reduction changed from {} to {}", event.previous, event.current);
+/// });
+/// context.add_plan(30.0, |context| {
+///     context.set_global_property_value(TransmissionReduction, 0.4).unwrap();
+/// });
+/// context.execute();
+/// ```
+///
+/// Derived person properties can't depend on mutable global properties,
+/// because their indexed values wouldn't be updated when the global
+/// property changes:
+///
+/// ```compile_fail
+/// # use ixa::{define_derived_property, define_mutable_global_property, define_person_property};
+/// # use ixa::ContextPeopleExt;
+/// define_person_property!(Age, u8);
+/// define_mutable_global_property!(RiskMultiplier, f64);
+/// define_derived_property!(Risk, u8, [Age], [RiskMultiplier], |age, multiplier| {
+///     (f64::from(age) * multiplier) as u8
+/// });
+/// ```
+#[macro_export]
+macro_rules! define_mutable_global_property {
+    ($global_property:ident, $value:ty, $validate: expr) => {
+        #[derive(Copy, Clone)]
+        pub struct $global_property;
+
+        impl $crate::global_properties::GlobalProperty for $global_property {
+            type Value = $value;
+
+            const IS_MUTABLE: bool = true;
+
+            fn new() -> Self {
+                $global_property
+            }
+
+            fn validate(val: &$value) -> Result<(), $crate::error::IxaError> {
+                $validate(val)
+            }
+
+            fn emit_change(context: &mut $crate::context::Context, previous: $value) {
+                $crate::global_properties::emit_global_property_change::<$global_property>(
+                    context, previous,
+                );
+            }
+        }
+
+        $crate::define_global_property!(@register $global_property);
+    };
 
     ($global_property: ident, $value: ty) => {
-        define_global_property!($global_property, $value, |_| { Ok(()) });
+        $crate::define_mutable_global_property!($global_property, $value, |_| { Ok(()) });
     };
 }
+pub use define_mutable_global_property;
 
 /// The trait representing a global property. Do not use this
 /// directly, but instead define global properties with
@@ -137,12 +289,61 @@ macro_rules! define_global_property {
 pub trait GlobalProperty: Any {
     type Value: Any; // The actual type of the data.
 
+    /// Whether the value can be changed after it has been set; see
+    /// [`define_mutable_global_property!()`]
+    const IS_MUTABLE: bool = false;
+
     fn new() -> Self;
     #[allow(clippy::missing_errors_doc)]
     // A function which validates the global property.
     fn validate(value: &Self::Value) -> Result<(), IxaError>;
+
+    #[doc(hidden)]
+    // Emits a `GlobalPropertyChangeEvent` after the value of a mutable
+    // property has been replaced.
+    fn emit_change(_context: &mut Context, _previous: Self::Value) {}
+}
+
+/// Emitted when the value of a mutable global property is changed (see
+/// [`define_mutable_global_property!()`]), but not when it is first set.
+/// The values are shared between the handlers of the event, so they don't
+/// need to be `Copy`.
+#[non_exhaustive]
+pub struct GlobalPropertyChangeEvent<T: GlobalProperty> {
+    /// The new value
+    pub current: Rc<T::Value>,
+    /// The old value
+    pub previous: Rc<T::Value>,
 }
 
+// Implemented by hand because deriving it would require the property type
+// to be `Clone`
+impl<T: GlobalProperty> Clone for GlobalPropertyChangeEvent<T> {
+    fn clone(&self) -> Self {
+        GlobalPropertyChangeEvent {
+            current: Rc::clone(&self.current),
+            previous: Rc::clone(&self.previous),
+        }
+    }
+}
+
+#[doc(hidden)]
+// Emits a `GlobalPropertyChangeEvent` for a mutable property whose value
+// has just been replaced. The event is constructed here because it's
+// `#[non_exhaustive]`.
+pub fn emit_global_property_change<T: GlobalProperty>(context: &mut Context, previous: T::Value)
+where
+    T::Value: Clone,
+{
+    let current = context.get_global_property_value(T::new()).unwrap().clone();
+    context.emit_event(GlobalPropertyChangeEvent::<T> {
+        current: Rc::new(current),
+        previous: Rc::new(previous),
+    });
+}
+
+impl<T: GlobalProperty> IxaEvent for GlobalPropertyChangeEvent<T> {}
+
 pub use define_global_property;
 
 struct GlobalPropertiesDataContainer {
@@ -157,11 +358,41 @@ crate::context::define_data_plugin!(
     }
 );
 
+pub(crate) fn plugin_type_id() -> TypeId {
+    TypeId::of::<GlobalPropertiesPlugin>()
+}
+
+/// Returns the type ids and names of the global properties that have been
+/// set, for checkpoints to check that they're all saved
+pub(crate) fn set_global_property_types(context: &Context) -> Vec<(TypeId, String)> {
+    let Some(data_container) = context.get_data_container(GlobalPropertiesPlugin) else {
+        return Vec::new();
+    };
+    let properties = GLOBAL_PROPERTIES.lock().unwrap();
+    let properties = properties.borrow();
+    data_container
+        .global_property_container
+        .keys()
+        .map(|type_id| {
+            let name = properties
+                .iter()
+                .find(|(_, accessors)| accessors.type_id == *type_id)
+                .map_or_else(|| format!("{type_id:?}"), |(name, _)| name.clone());
+            (*type_id, name)
+        })
+        .collect()
+}
+
 pub trait ContextGlobalPropertiesExt {
     /// Set the value of a global property of type T
     ///
+    /// If the property was defined with [`define_mutable_global_property!()`]
+    /// and already has a value, the value is replaced and a
+    /// [`GlobalPropertyChangeEvent`] is emitted.
+    ///
     /// # Errors
-    /// Will return an error if an attempt is made to change a value.
+    /// Will return an error if the value is invalid, or if an attempt is
+    /// made to change the value of a property that isn't mutable.
     fn set_global_property_value<T: GlobalProperty + 'static>(
         &mut self,
         property: T,
@@ -200,7 +431,9 @@ pub trait ContextGlobalPropertiesExt {
     /// The expected structure is a dictionary with each name being
     /// the name of the struct prefixed with the crate name, as in:
     /// `ixa.NumFluVariants` and the value being an object which can
-    /// serde deserialize into the relevant struct.
+    /// serde deserialize into the relevant struct. A `sweep` key, which
+    /// describes a parameter sweep for the runner (see [`crate::sweep`]),
+    /// is ignored.
     ///
     /// # Errors
     /// Will return an `IxaError` if:
@@ -220,19 +453,23 @@ pub trait ContextGlobalPropertiesExt {
 }
 
 impl GlobalPropertiesDataContainer {
+    // Returns the previous value if a mutable property's value is replaced
     fn set_global_property_value<T: GlobalProperty + 'static>(
         &mut self,
         _property: &T,
         value: T::Value,
-    ) -> Result<(), IxaError> {
+    ) -> Result<Option<T::Value>, IxaError> {
         match self.global_property_container.entry(TypeId::of::<T>()) {
             Entry::Vacant(entry) => {
                 entry.insert(Box::new(value));
-                Ok(())
+                Ok(None)
+            }
+            // Derived properties can't depend on mutable properties, so
+            // their values don't need to be updated here.
+            Entry::Occupied(mut entry) if T::IS_MUTABLE => {
+                let stored = entry.get_mut().downcast_mut::<T::Value>().unwrap();
+                Ok(Some(std::mem::replace(stored, value)))
             }
-            // Note: If we change global properties to be mutable, we'll need to
-            // update define_derived_property to either handle updates or only
-            // allow immutable properties.
             Entry::Occupied(_) => Err(IxaError::from("Entry already exists")),
         }
     }
@@ -256,7 +493,10 @@ impl ContextGlobalPropertiesExt for Context {
     ) -> Result<(), IxaError> {
         T::validate(&value)?;
         let data_container = self.get_data_container_mut(GlobalPropertiesPlugin);
-        data_container.set_global_property_value(&property, value)
+        if let Some(previous) = data_container.set_global_property_value(&property, value)? {
+            T::emit_change(self, previous);
+        }
+        Ok(())
     }
 
     #[allow(unused_variables)]
@@ -266,7 +506,7 @@ impl ContextGlobalPropertiesExt for Context {
     ) -> Option<&T::Value> {
         if let Some(data_container) = self.get_data_container(GlobalPropertiesPlugin) {
             return data_container.get_global_property_value::<T>();
-        };
+        }
         None
     }
 
@@ -288,7 +528,7 @@ impl ContextGlobalPropertiesExt for Context {
         &mut self,
         file_name: &Path,
     ) -> Result<T, IxaError> {
-        trace!("Loading parameters from JSON: {:?}", file_name);
+        trace!("Loading parameters from JSON: {}", file_name.display());
         let config_file = fs::File::open(file_name)?;
         let reader = BufReader::new(config_file);
         let config = serde_json::from_reader(reader)?;
@@ -296,13 +536,19 @@ impl ContextGlobalPropertiesExt for Context {
     }
 
     fn load_global_properties(&mut self, file_name: &Path) -> Result<(), IxaError> {
-        trace!("Loading global properties from {:?}", file_name);
+        trace!("Loading global properties from {}", file_name.display());
         let config_file = fs::File::open(file_name)?;
         let reader = BufReader::new(config_file);
         let val: serde_json::Map<String, serde_json::Value> = serde_json::from_reader(reader)?;
 
         for (k, v) in val {
-            if let Some(accessor) = get_global_property_accessor(&k) {
+            // Global property names always contain a '.', so this can't
+            // collide with one
+            if k == "modes" {
+                crate::modes::set_modes_from_json(self, v)?;
+            } else if k == crate::sweep::SWEEP_KEY {
+                // The runner expands sweeps; see `crate::sweep`
+            } else if let Some(accessor) = get_global_property_accessor(&k) {
                 (accessor.setter)(self, &k, v)?;
             } else {
                 return Err(IxaError::from(format!("No global property: {k}")));
@@ -314,12 +560,14 @@ impl ContextGlobalPropertiesExt for Context {
 }
 
 #[cfg(test)]
+#[allow(clippy::float_cmp)]
 mod test {
     use super::*;
     use crate::context::Context;
     use crate::error::IxaError;
     use serde::{Deserialize, Serialize};
     use std::path::PathBuf;
+    use std::rc::Rc;
     use tempfile::tempdir;
     #[derive(Serialize, Deserialize, Debug, Clone)]
     pub struct ParamType {
@@ -367,6 +615,105 @@ mod test {
         assert_eq!(global_params.diseases, params.diseases);
     }
 
+    define_mutable_global_property!(TransmissionReduction, f64, |value: &f64| {
+        if (0.0..=1.0).contains(value) {
+            Ok(())
+        } else {
+            Err(IxaError::IxaError(
+                "Reduction must be in [0, 1]".to_string(),
+            ))
+        }
+    });
+
+    #[test]
+    fn mutable_global_property_change_event() {
+        let mut context = Context::new();
+        context
+            .set_global_property_value(TransmissionReduction, 0.0)
+            .unwrap();
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let changes_clone = Rc::clone(&changes);
+        context.subscribe_to_event(
+            move |context, event: GlobalPropertyChangeEvent<TransmissionReduction>| {
+                changes_clone.borrow_mut().push((
+                    context.get_current_time(),
+                    *event.previous,
+                    *event.current,
+                ));
+            },
+        );
+        context.add_plan(30.0, |context| {
+            context
+                .set_global_property_value(TransmissionReduction, 0.4)
+                .unwrap();
+        });
+        context.add_plan(60.0, |context| {
+            context
+                .set_global_property_value(TransmissionReduction, 0.1)
+                .unwrap();
+        });
+        context.execute();
+
+        assert_eq!(*changes.borrow(), vec![(30.0, 0.0, 0.4), (60.0, 0.4, 0.1)]);
+        assert_eq!(
+            *context
+                .get_global_property_value(TransmissionReduction)
+                .unwrap(),
+            0.1
+        );
+    }
+
+    define_mutable_global_property!(SchoolClosures, Vec<String>);
+
+    #[test]
+    fn mutable_global_property_change_event_without_copy() {
+        let mut context = Context::new();
+        context
+            .set_global_property_value(SchoolClosures, vec!["north".to_string()])
+            .unwrap();
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        for _ in 0..2 {
+            let changes_clone = Rc::clone(&changes);
+            context.subscribe_to_event(
+                move |_context, event: GlobalPropertyChangeEvent<SchoolClosures>| {
+                    changes_clone
+                        .borrow_mut()
+                        .push((event.previous.len(), event.current.len()));
+                },
+            );
+        }
+        context.add_plan(10.0, |context| {
+            context
+                .set_global_property_value(
+                    SchoolClosures,
+                    vec!["north".to_string(), "south".to_string()],
+                )
+                .unwrap();
+        });
+        context.execute();
+
+        assert_eq!(*changes.borrow(), vec![(1, 2), (1, 2)]);
+    }
+
+    #[test]
+    fn mutable_global_property_validates_updates() {
+        let mut context = Context::new();
+        context
+            .set_global_property_value(TransmissionReduction, 0.2)
+            .unwrap();
+        assert!(context
+            .set_global_property_value(TransmissionReduction, 1.5)
+            .is_err());
+        assert_eq!(
+            *context
+                .get_global_property_value(TransmissionReduction)
+                .unwrap(),
+            0.2
+        );
+    }
+
     #[test]
     fn get_global_propert_missing() {
         let context = Context::new();
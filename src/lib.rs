@@ -26,42 +26,91 @@
 //!   stages of disease until recovery.
 //! * A transmission manager that models the process of an infected
 //!   person trying to infect susceptible people in the population.
+pub mod alarm;
+pub use alarm::ContextAlarmExt;
+
+pub mod analysis;
+pub use analysis::ContextAnalysisExt;
+
+pub mod checkpoint;
+pub use checkpoint::{Checkpointable, CheckpointablePlan, ContextCheckpointExt};
+
+pub mod contact_diary;
+pub use contact_diary::{ContactDiaryConfig, ContactSource, ContextContactDiaryExt};
+
 pub mod context;
-pub use context::{Context, ExecutionPhase, IxaEvent};
+pub use context::{
+    Context, ExecutionPhase, IxaEvent, SimulationShutdown, SimulationStarted, SubscriptionHandle,
+    SubscriptionId, TimeStepCompleted,
+};
 
 pub mod error;
-pub use error::IxaError;
+pub use error::{IxaError, IxaResultExt};
+
+pub mod execution_stats;
+pub use execution_stats::ExecutionStatistics;
 
 pub mod global_properties;
-pub use global_properties::{ContextGlobalPropertiesExt, GlobalProperty};
+pub use global_properties::{
+    ContextGlobalPropertiesExt, GlobalProperty, GlobalPropertyChangeEvent,
+};
+
+pub mod live_overrides;
+pub use live_overrides::{ContextLiveOverridesExt, LiveOverridePolicy};
+
+pub mod modes;
+pub use modes::{ContextModeExt, ModeValue, SimulationMode};
 
 pub mod network;
 pub use network::{ContextNetworkExt, Edge, EdgeType};
 
+pub mod neighborhood;
+pub use neighborhood::ContextNeighborhoodExt;
+
+pub mod observer;
+pub use observer::ReadOnlyContext;
+
 pub mod people;
 pub use people::{
-    ContextPeopleExt, PersonCreatedEvent, PersonId, PersonProperty, PersonPropertyChangeEvent,
+    ContextPeopleExt, IndexChange, PersonCreatedEvent, PersonId, PersonProperty,
+    PersonPropertyChangeEvent, PersonRemovedEvent,
 };
 
 pub mod plan;
 pub mod random;
 pub use random::{ContextRandomExt, RngId};
 
+pub mod scratch;
+pub use scratch::{Scratch, ScratchVec};
+
+pub mod state_machine;
+pub use state_machine::{ContextStateMachineExt, StateMachine, TransitionEvent, Trigger};
+
 pub mod tabulator;
-pub use tabulator::Tabulator;
+pub use tabulator::{StreamingMode, TabulationKey, Tabulator};
+
+pub mod testing;
+
+pub mod transmission;
+pub use transmission::ContextTransmissionExt;
+
+pub mod value_map;
+pub use value_map::{ContextValueMapExt, ValueMap};
 
 pub mod report;
 pub use report::{ConfigReportOptions, ContextReportExt, Report};
 
+pub mod experiment;
 pub mod runner;
-pub use runner::{run_with_args, run_with_custom_args, BaseArgs};
+pub mod sweep;
+pub use runner::{run_scenarios, run_with_args, run_with_custom_args, BaseArgs, ScenarioOptions};
 
 pub mod debugger;
 
 pub mod log;
 pub use log::{
     debug, disable_logging, enable_logging, error, info, set_log_level, set_module_filter,
-    set_module_filters, trace, warn, LevelFilter,
+    set_module_filters, trace, warn, ContextLogExt, LevelFilter,
 };
 
 pub mod external_api;
@@ -0,0 +1,397 @@
+//! Reloading selected global properties from a file during a run.
+//!
+//! Global properties are normally immutable, but during long interactive
+//! runs it can be useful to adjust some parameters without restarting.
+//! After [`Context::enable_live_overrides()`] is called with a JSON file
+//! in the same format as [`Context::load_global_properties()`], the file
+//! is re-read and applied whenever a reload is requested:
+//!
+//! * By setting the flag returned by [`Context::enable_live_overrides()`],
+//!   for instance from a signal handler or another thread. The flag is
+//!   checked at every `check_interval` time boundary (see
+//!   [`Context::add_boundary_hook()`]), so reloads happen between plans.
+//! * With the web API `reload` command.
+//! * Directly with [`Context::reload_overrides()`].
+//!
+//! Only properties defined with
+//! [`define_mutable_global_property!()`](crate::define_mutable_global_property)
+//! may change, so derived person properties, which can't depend on
+//! mutable properties, never go stale. Every value in the file is parsed
+//! and checked with the property's validator before any of them is
+//! applied, so a malformed file, an unknown or immutable property, or a
+//! failed validation leaves all properties untouched.
+//!
+//! Changes are applied with [`Context::set_global_property_value()`], so
+//! each one emits a
+//! [`GlobalPropertyChangeEvent`](crate::global_properties::GlobalPropertyChangeEvent).
+//! Each applied change is also recorded in the `global_property_overrides`
+//! report, and a [`GlobalPropertiesReloadedEvent`] is emitted afterwards.
+use crate::context::Context;
+use crate::error::IxaError;
+use crate::global_properties::{parse_global_property_value, ContextGlobalPropertiesExt};
+use crate::report::{ContextReportExt, Report};
+use crate::{create_report_trait, define_data_plugin, error, trace, IxaEvent};
+use ixa_derive::IxaEvent;
+use serde::Serialize;
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// How often to check for reload requests. The properties that can be
+/// changed are the mutable ones (see
+/// [`define_mutable_global_property!()`](crate::define_mutable_global_property)).
+#[derive(Clone, Debug)]
+pub struct LiveOverridePolicy {
+    /// The interval between checks of the reload flag.
+    pub check_interval: f64,
+}
+
+/// Emitted after a reload changes at least one global property
+#[derive(Clone, Copy, IxaEvent)]
+pub struct GlobalPropertiesReloadedEvent {
+    /// The number of global properties that changed
+    pub changed: usize,
+}
+
+#[derive(Serialize)]
+struct GlobalPropertyOverrideReport {
+    time: f64,
+    property: String,
+    old: String,
+    new: String,
+    source: String,
+}
+
+create_report_trait!(GlobalPropertyOverrideReport);
+
+struct LiveOverridesData {
+    path: PathBuf,
+}
+
+define_data_plugin!(LiveOverridesPlugin, Option<LiveOverridesData>, None);
+
+// The watched overrides file belong to the current run, so they aren't saved in checkpoints
+pub(crate) fn plugin_type_id() -> std::any::TypeId {
+    std::any::TypeId::of::<LiveOverridesPlugin>()
+}
+
+/// Re-read the overrides file and apply any changes, recording `source` in
+/// the audit report. Returns the number of properties that changed.
+pub(crate) fn reload_overrides_from(
+    context: &mut Context,
+    source: &str,
+) -> Result<usize, IxaError> {
+    let Some(data) = context
+        .get_data_container(LiveOverridesPlugin)
+        .and_then(Option::as_ref)
+    else {
+        return Err(IxaError::from("Live overrides are not enabled"));
+    };
+    trace!("Reloading global properties from {}", data.path.display());
+    let reader = BufReader::new(fs::File::open(&data.path)?);
+    let values: serde_json::Map<String, serde_json::Value> = serde_json::from_reader(reader)?;
+
+    // Check everything before changing anything.
+    let mut pending = Vec::new();
+    for (name, value) in values {
+        let parsed = parse_global_property_value(&name, value.clone())?;
+        let old = context.get_serialized_value_by_string(&name)?;
+        if let Some(old) = &old {
+            if serde_json::from_str::<serde_json::Value>(old)? == value {
+                continue;
+            }
+        }
+        if !parsed.is_mutable {
+            return Err(IxaError::from(format!(
+                "Global property {name} is not mutable"
+            )));
+        }
+        let new = serde_json::to_string(&value)?;
+        pending.push((name, old.unwrap_or_default(), new, parsed));
+    }
+
+    let changed = pending.len();
+    for (property, old, new, parsed) in pending {
+        // This can't fail, since the value has been validated and the
+        // property is mutable.
+        parsed.set(context)?;
+        context.send_report(GlobalPropertyOverrideReport {
+            time: context.get_current_time(),
+            property,
+            old,
+            new,
+            source: source.to_string(),
+        });
+    }
+    if changed > 0 {
+        context.emit_event(GlobalPropertiesReloadedEvent { changed });
+    }
+    Ok(changed)
+}
+
+pub trait ContextLiveOverridesExt {
+    /// Enable reloading mutable global properties from the JSON file at
+    /// `path`. This adds the `global_property_overrides`
+    /// report, so the report options must be configured first.
+    ///
+    /// Returns a flag that requests a reload when set to `true`. It is
+    /// checked every `policy.check_interval` and cleared when a reload
+    /// happens. Errors from these reloads are logged and the simulation
+    /// continues with the previous values.
+    ///
+    /// # Errors
+    /// Will return an `IxaError` if live overrides are already enabled or
+    /// the report can't be created.
+    fn enable_live_overrides(
+        &mut self,
+        path: &Path,
+        policy: LiveOverridePolicy,
+    ) -> Result<Arc<AtomicBool>, IxaError>;
+
+    /// Re-read the overrides file and apply any changes immediately.
+    /// Returns the number of global properties that changed.
+    ///
+    /// # Errors
+    /// Will return an `IxaError`, without changing any properties, if:
+    /// * Live overrides are not enabled
+    /// * The file can't be read or isn't valid JSON
+    /// * A property doesn't exist, isn't mutable, or fails validation
+    fn reload_overrides(&mut self) -> Result<usize, IxaError>;
+}
+
+impl ContextLiveOverridesExt for Context {
+    fn enable_live_overrides(
+        &mut self,
+        path: &Path,
+        policy: LiveOverridePolicy,
+    ) -> Result<Arc<AtomicBool>, IxaError> {
+        if self.get_data_container_mut(LiveOverridesPlugin).is_some() {
+            return Err(IxaError::from("Live overrides already enabled"));
+        }
+        self.add_report::<GlobalPropertyOverrideReport>("global_property_overrides")?;
+        *self.get_data_container_mut(LiveOverridesPlugin) = Some(LiveOverridesData {
+            path: path.to_path_buf(),
+        });
+
+        let trigger = Arc::new(AtomicBool::new(false));
+        let hook_trigger = Arc::clone(&trigger);
+        self.add_boundary_hook(policy.check_interval, 0.0, move |context| {
+            if hook_trigger.swap(false, Ordering::SeqCst) {
+                if let Err(err) = reload_overrides_from(context, "trigger") {
+                    error!("Failed to reload global properties: {err}");
+                }
+            }
+        });
+        Ok(trigger)
+    }
+
+    fn reload_overrides(&mut self) -> Result<usize, IxaError> {
+        reload_overrides_from(self, "api")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ContextLiveOverridesExt, GlobalPropertiesReloadedEvent, LiveOverridePolicy};
+    use crate::global_properties::GlobalPropertyChangeEvent;
+    use crate::{
+        define_global_property, define_mutable_global_property, Context,
+        ContextGlobalPropertiesExt, ContextReportExt, IxaError,
+    };
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::Path;
+    use std::rc::Rc;
+    use std::sync::atomic::Ordering;
+    use tempfile::tempdir;
+
+    define_mutable_global_property!(OverrideRate, f64, |value: &f64| {
+        if *value < 0.0 {
+            return Err(IxaError::from("Rate must be non-negative"));
+        }
+        Ok(())
+    });
+    define_mutable_global_property!(OverrideDays, u32);
+    define_global_property!(OverrideFrozen, u32);
+
+    fn setup(dir: &Path, contents: &str) -> Context {
+        let mut context = Context::new();
+        context.report_options().directory(dir.to_path_buf());
+        context
+            .set_global_property_value(OverrideRate, 0.5)
+            .unwrap();
+        context.set_global_property_value(OverrideDays, 10).unwrap();
+        context
+            .set_global_property_value(OverrideFrozen, 1)
+            .unwrap();
+        let path = dir.join("overrides.json");
+        fs::write(&path, contents).unwrap();
+        context
+    }
+
+    fn policy() -> LiveOverridePolicy {
+        LiveOverridePolicy {
+            check_interval: 1.0,
+        }
+    }
+
+    fn values(context: &Context) -> (f64, u32, u32) {
+        (
+            *context.get_global_property_value(OverrideRate).unwrap(),
+            *context.get_global_property_value(OverrideDays).unwrap(),
+            *context.get_global_property_value(OverrideFrozen).unwrap(),
+        )
+    }
+
+    #[test]
+    fn reload_is_visible_to_later_plans() {
+        let dir = tempdir().unwrap();
+        let mut context = setup(
+            dir.path(),
+            r#"{"ixa.OverrideRate": 0.25, "ixa.OverrideFrozen": 1}"#,
+        );
+        let trigger = context
+            .enable_live_overrides(&dir.path().join("overrides.json"), policy())
+            .unwrap();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let events_clone = Rc::clone(&events);
+        context.subscribe_to_event(move |_, event: GlobalPropertiesReloadedEvent| {
+            events_clone.borrow_mut().push(event.changed);
+        });
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        for time in [0.5, 0.75, 1.5] {
+            let seen = Rc::clone(&seen);
+            context.add_plan(time, move |context| {
+                seen.borrow_mut()
+                    .push(*context.get_global_property_value(OverrideRate).unwrap());
+            });
+        }
+        let trigger_clone = trigger.clone();
+        context.add_plan(0.6, move |_| trigger_clone.store(true, Ordering::SeqCst));
+        context.execute();
+
+        // The reload waits for the boundary at 1.0.
+        assert_eq!(*seen.borrow(), vec![0.5, 0.5, 0.25]);
+        assert_eq!(*events.borrow(), vec![1]);
+        assert!(!trigger.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn reload_emits_change_events() {
+        let dir = tempdir().unwrap();
+        let mut context = setup(
+            dir.path(),
+            r#"{"ixa.OverrideRate": 0.25, "ixa.OverrideDays": 10}"#,
+        );
+        context
+            .enable_live_overrides(&dir.path().join("overrides.json"), policy())
+            .unwrap();
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let changes_clone = Rc::clone(&changes);
+        context.subscribe_to_event(move |_, event: GlobalPropertyChangeEvent<OverrideRate>| {
+            changes_clone
+                .borrow_mut()
+                .push((*event.previous, *event.current));
+        });
+        let days_changes = Rc::new(RefCell::new(0));
+        let days_changes_clone = Rc::clone(&days_changes);
+        context.subscribe_to_event(move |_, _: GlobalPropertyChangeEvent<OverrideDays>| {
+            *days_changes_clone.borrow_mut() += 1;
+        });
+
+        assert_eq!(context.reload_overrides().unwrap(), 1);
+        context.execute();
+        // Only the value that changed emits an event.
+        assert_eq!(*changes.borrow(), vec![(0.5, 0.25)]);
+        assert_eq!(*days_changes.borrow(), 0);
+    }
+
+    #[test]
+    fn bad_file_changes_nothing() {
+        let dir = tempdir().unwrap();
+        let mut context = setup(dir.path(), r#"{"ixa.OverrideRate": 0.25,"#);
+        let path = dir.path().join("overrides.json");
+        context.enable_live_overrides(&path, policy()).unwrap();
+        assert!(matches!(
+            context.reload_overrides(),
+            Err(IxaError::JsonError(_))
+        ));
+        assert_eq!(values(&context), (0.5, 10, 1));
+
+        // One invalid value means none are applied.
+        fs::write(
+            &path,
+            r#"{"ixa.OverrideDays": 20, "ixa.OverrideRate": -1.0}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            context.reload_overrides(),
+            Err(IxaError::IxaError(_))
+        ));
+        assert_eq!(values(&context), (0.5, 10, 1));
+
+        fs::write(&path, r#"{"ixa.OverrideDays": 20, "ixa.Unknown": 1}"#).unwrap();
+        assert!(matches!(
+            context.reload_overrides(),
+            Err(IxaError::IxaError(_))
+        ));
+        assert_eq!(values(&context), (0.5, 10, 1));
+    }
+
+    #[test]
+    fn immutable_property_is_rejected() {
+        let dir = tempdir().unwrap();
+        let mut context = setup(
+            dir.path(),
+            r#"{"ixa.OverrideDays": 20, "ixa.OverrideFrozen": 2}"#,
+        );
+        context
+            .enable_live_overrides(&dir.path().join("overrides.json"), policy())
+            .unwrap();
+        match context.reload_overrides() {
+            Err(IxaError::IxaError(msg)) => {
+                assert_eq!(msg, "Global property ixa.OverrideFrozen is not mutable");
+            }
+            _ => panic!("Unexpected result"),
+        }
+        assert_eq!(values(&context), (0.5, 10, 1));
+    }
+
+    #[test]
+    fn reload_without_enable() {
+        let mut context = Context::new();
+        assert!(matches!(
+            context.reload_overrides(),
+            Err(IxaError::IxaError(_))
+        ));
+    }
+
+    #[test]
+    fn audit_report_rows() {
+        let dir = tempdir().unwrap();
+        let mut context = setup(
+            dir.path(),
+            r#"{"ixa.OverrideDays": 20, "ixa.OverrideRate": 0.5}"#,
+        );
+        context
+            .enable_live_overrides(&dir.path().join("overrides.json"), policy())
+            .unwrap();
+        context.add_plan(2.0, |context| {
+            assert_eq!(context.reload_overrides().unwrap(), 1);
+            // Reloading the same file again doesn't change anything.
+            assert_eq!(context.reload_overrides().unwrap(), 0);
+        });
+        context.execute();
+        drop(context);
+
+        let contents =
+            fs::read_to_string(dir.path().join("global_property_overrides.csv")).unwrap();
+        assert_eq!(
+            contents,
+            "time,property,old,new,source\n2.0,ixa.OverrideDays,10,20,api\n"
+        );
+    }
+}
@@ -39,18 +39,39 @@
 //!     set_module_filter("transmission_manager", LevelFilter::Trace);
 //! }
 //! ```
+//!
+//! Log messages are written to the console. A `Context` can also write the messages logged while
+//! it runs to a file with `context.set_log_file(path)`. Log files are rotated when they exceed a
+//! maximum size, which can be changed with `context.set_log_rotation()`. Because each context has
+//! its own file, several contexts in one process write their messages to separate files. The
+//! messages logged on a thread go to the file of the context that is running on that thread or,
+//! outside of `execute()`, the context that most recently set its log file on that thread.
 
 pub use log::{debug, error, info, trace, warn, LevelFilter};
 
+use crate::context::Context;
+use crate::define_data_plugin;
+use crate::error::IxaError;
+use log::Record;
 use log4rs;
 use log4rs::append::console::ConsoleAppender;
+use log4rs::append::rolling_file::policy::compound::roll::delete::DeleteRoller;
+use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
+use log4rs::append::rolling_file::policy::compound::roll::Roll;
+use log4rs::append::rolling_file::policy::compound::trigger::size::SizeTrigger;
+use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
+use log4rs::append::rolling_file::RollingFileAppender;
+use log4rs::append::Append;
 use log4rs::config::runtime::ConfigBuilder;
 use log4rs::config::{Appender, Logger, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::{Config, Handle};
+use std::cell::Cell;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::sync::LazyLock;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, RwLock};
 use std::sync::{Mutex, MutexGuard};
 
 // Logging disabled
@@ -62,10 +83,48 @@ const DEFAULT_MODULE_FILTERS: [(&str, LevelFilter); 1] = [
 ];
 // Use an ISO 8601 timestamp format and color coded level tag
 const DEFAULT_LOG_PATTERN: &str = "{d(%Y-%m-%dT%H:%M:%SZ)} {h({l})} {t} - {m}{n}";
+// Log files are not color coded
+const LOG_FILE_PATTERN: &str = "{d(%Y-%m-%dT%H:%M:%SZ)} {l} {t} - {m}{n}";
+/// The default size in bytes at which log files are rotated
+pub const DEFAULT_LOG_FILE_MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// The default number of rotated log files to keep
+pub const DEFAULT_LOG_FILE_COUNT: u32 = 5;
 
 /// A global instance of the logging configuration.
 static LOG_CONFIGURATION: LazyLock<Mutex<LogConfiguration>> = LazyLock::new(Mutex::default);
 
+/// The log file of each context that has one, keyed by an id that is unique within the process.
+static LOG_FILES: LazyLock<RwLock<HashMap<usize, RollingFileAppender>>> =
+    LazyLock::new(RwLock::default);
+
+thread_local! {
+    /// The id of the log file that messages logged on this thread are written to
+    static CURRENT_LOG_FILE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// An appender that writes each record to the log file of the context running on the current
+/// thread, if it has one.
+#[derive(Debug)]
+struct LogFileAppender;
+
+impl Append for LogFileAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        let Some(id) = CURRENT_LOG_FILE.get() else {
+            return Ok(());
+        };
+        match LOG_FILES.read().expect("Lock poisoned").get(&id) {
+            Some(appender) => appender.append(record),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&self) {
+        for appender in LOG_FILES.read().expect("Lock poisoned").values() {
+            appender.flush();
+        }
+    }
+}
+
 /// Different log level filters can be applied to the log messages emitted from different modules
 /// according to the module path (e.g. `"ixa::people"`). These are stored in the global
 /// `LogConfiguration`.
@@ -126,8 +185,9 @@ impl LogConfiguration {
         let stdout: ConsoleAppender = ConsoleAppender::builder()
             .encoder(Box::new(PatternEncoder::new(DEFAULT_LOG_PATTERN)))
             .build();
-        let mut config: ConfigBuilder =
-            Config::builder().appender(Appender::builder().build("stdout", Box::new(stdout)));
+        let mut config: ConfigBuilder = Config::builder()
+            .appender(Appender::builder().build("stdout", Box::new(stdout)))
+            .appender(Appender::builder().build("files", Box::new(LogFileAppender)));
 
         // Add module specific configuration
         for module_config in self.module_configurations.values() {
@@ -137,6 +197,7 @@ impl LogConfiguration {
         // The `Root` determines the global log level
         let root = Root::builder()
             .appender("stdout")
+            .appender("files")
             .build(self.global_log_level);
         let new_config = match config.build(root) {
             Err(e) => {
@@ -164,8 +225,8 @@ impl LogConfiguration {
     }
 
     /// Returns true if the configuration was mutated, false otherwise.
-    fn insert_module_filter(&mut self, module: &String, level: LevelFilter) -> bool {
-        match self.module_configurations.entry(module.clone()) {
+    fn insert_module_filter(&mut self, module: &str, level: LevelFilter) -> bool {
+        match self.module_configurations.entry(module.to_string()) {
             Entry::Occupied(mut entry) => {
                 let module_config = entry.get_mut();
                 if module_config.level == level {
@@ -259,14 +320,180 @@ fn get_log_configuration() -> MutexGuard<'static, LogConfiguration> {
     LOG_CONFIGURATION.lock().expect("Mutex poisoned")
 }
 
+/// The registration of a context's log file, which is removed when the context is dropped.
+struct LogFileRoute {
+    id: usize,
+}
+
+impl LogFileRoute {
+    fn new() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        LogFileRoute {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for LogFileRoute {
+    fn drop(&mut self) {
+        if let Some(appender) = LOG_FILES.write().expect("Lock poisoned").remove(&self.id) {
+            appender.flush();
+        }
+        if CURRENT_LOG_FILE.get() == Some(self.id) {
+            CURRENT_LOG_FILE.set(None);
+        }
+    }
+}
+
+struct LogFileData {
+    route: Option<LogFileRoute>,
+    path: Option<PathBuf>,
+    max_size: u64,
+    max_files: u32,
+}
+
+define_data_plugin!(
+    LogFilePlugin,
+    LogFileData,
+    LogFileData {
+        route: None,
+        path: None,
+        max_size: DEFAULT_LOG_FILE_MAX_SIZE,
+        max_files: DEFAULT_LOG_FILE_COUNT,
+    }
+);
+
+// The log file settings belong to the current run, so they aren't saved in checkpoints
+pub(crate) fn plugin_type_id() -> std::any::TypeId {
+    std::any::TypeId::of::<LogFilePlugin>()
+}
+
+/// Directs the messages logged on the current thread to the log file of `context` until the
+/// returned guard is dropped.
+pub(crate) struct LogFileGuard {
+    previous: Option<usize>,
+}
+
+impl LogFileGuard {
+    pub(crate) fn enter(context: &Context) -> Self {
+        let previous = CURRENT_LOG_FILE.get();
+        if let Some(route) = context
+            .get_data_container(LogFilePlugin)
+            .and_then(|data_container| data_container.route.as_ref())
+        {
+            CURRENT_LOG_FILE.set(Some(route.id));
+        }
+        LogFileGuard { previous }
+    }
+}
+
+impl Drop for LogFileGuard {
+    fn drop(&mut self) {
+        CURRENT_LOG_FILE.set(self.previous);
+    }
+}
+
+fn build_log_file_appender(
+    path: &Path,
+    max_size: u64,
+    max_files: u32,
+) -> Result<RollingFileAppender, IxaError> {
+    let roller: Box<dyn Roll> = if max_files == 0 {
+        Box::new(DeleteRoller::new())
+    } else {
+        let pattern = format!("{}.{{}}", path.display());
+        Box::new(
+            FixedWindowRoller::builder()
+                .base(1)
+                .build(&pattern, max_files)
+                .map_err(|e| IxaError::IxaError(format!("Invalid log file path: {e}")))?,
+        )
+    };
+    let policy = CompoundPolicy::new(Box::new(SizeTrigger::new(max_size)), roller);
+    Ok(RollingFileAppender::builder()
+        .encoder(Box::new(PatternEncoder::new(LOG_FILE_PATTERN)))
+        .build(path, Box::new(policy))?)
+}
+
+// (Re)opens the log file of `context` with its current settings and makes it the log file for
+// the current thread.
+fn open_log_file(context: &mut Context) -> Result<(), IxaError> {
+    let data_container = context.get_data_container_mut(LogFilePlugin);
+    let Some(path) = &data_container.path else {
+        return Ok(());
+    };
+    let appender =
+        build_log_file_appender(path, data_container.max_size, data_container.max_files)?;
+    let id = data_container
+        .route
+        .get_or_insert_with(LogFileRoute::new)
+        .id;
+    LOG_FILES
+        .write()
+        .expect("Lock poisoned")
+        .insert(id, appender);
+    CURRENT_LOG_FILE.set(Some(id));
+    Ok(())
+}
+
+/// Returns the default log file path for `context`, `<output_dir>/<file_prefix>run.log`, based on
+/// its report options.
+pub fn default_log_file_path(context: &mut Context) -> PathBuf {
+    use crate::report::ContextReportExt;
+    let options = context.report_options();
+    options
+        .output_dir
+        .join(format!("{}run.log", options.file_prefix))
+}
+
+pub trait ContextLogExt {
+    /// Write the messages logged while this context runs to the file at `path`, in addition to
+    /// the console. This replaces any log file that was set before. The file is appended to if
+    /// it already exists. Note that messages are only logged at all if logging is enabled, e.g.,
+    /// with `set_log_level()`.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if the file can't be opened.
+    fn set_log_file(&mut self, path: PathBuf) -> Result<(), IxaError>;
+
+    /// Rotate the log file when it grows beyond `max_size` bytes, keeping at most `max_files`
+    /// rotated files, named `<path>.1` (the most recent) through `<path>.<max_files>`. The
+    /// defaults are [`DEFAULT_LOG_FILE_MAX_SIZE`] and [`DEFAULT_LOG_FILE_COUNT`].
+    ///
+    /// # Errors
+    /// Returns `IxaError` if a log file has been set and can't be reopened.
+    fn set_log_rotation(&mut self, max_size: u64, max_files: u32) -> Result<(), IxaError>;
+}
+
+impl ContextLogExt for Context {
+    fn set_log_file(&mut self, path: PathBuf) -> Result<(), IxaError> {
+        self.get_data_container_mut(LogFilePlugin).path = Some(path);
+        open_log_file(self)
+    }
+
+    fn set_log_rotation(&mut self, max_size: u64, max_files: u32) -> Result<(), IxaError> {
+        let data_container = self.get_data_container_mut(LogFilePlugin);
+        data_container.max_size = max_size;
+        data_container.max_files = max_files;
+        open_log_file(self)
+    }
+}
+
+// Force tests that change the logging configuration to run serially for consistent behavior.
 #[cfg(test)]
-mod tests {
-    use super::{get_log_configuration, remove_module_filter, set_log_level, set_module_filters};
-    use log::{error, trace, LevelFilter};
-    use std::sync::{LazyLock, Mutex};
+pub(crate) static TEST_MUTEX: LazyLock<Mutex<()>> = LazyLock::new(Mutex::default);
 
-    // Force logging tests to run serially for consistent behavior.
-    static TEST_MUTEX: LazyLock<Mutex<()>> = LazyLock::new(Mutex::default);
+#[cfg(test)]
+mod tests {
+    use super::{
+        get_log_configuration, remove_module_filter, set_log_level, set_module_filters,
+        ContextLogExt, TEST_MUTEX,
+    };
+    use crate::Context;
+    use log::{error, info, trace, LevelFilter};
+    use std::fs;
+    use std::thread;
+    use tempfile::tempdir;
 
     #[test]
     fn command_line_args_sets_level() {
@@ -351,4 +578,96 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn log_file_rotation() {
+        let _guard = TEST_MUTEX.lock().expect("Mutex poisoned");
+        set_log_level(LevelFilter::Trace);
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("run.log");
+
+        let mut context = Context::new();
+        context.set_log_file(path.clone()).unwrap();
+        context.set_log_rotation(200, 2).unwrap();
+        context.add_plan(1.0, |_| {
+            for i in 0..50 {
+                info!("log_file_rotation: message {i}");
+            }
+        });
+        context.execute();
+        drop(context);
+
+        let rotated = |n: usize| dir.path().join(format!("run.log.{n}"));
+        assert!(rotated(1).exists());
+        assert!(rotated(2).exists());
+        assert!(!rotated(3).exists());
+        // Files are rotated after the write that takes them over the size limit, so the
+        // current file may not have been created again yet.
+        let read = |path| fs::read_to_string(path).unwrap_or_default();
+        let recent = read(path) + &read(rotated(1));
+        assert!(recent.contains("log_file_rotation: message 49\n"));
+        // The oldest messages were deleted
+        let all = recent + &read(rotated(2));
+        assert!(!all.contains("log_file_rotation: message 0\n"));
+        assert!(fs::metadata(rotated(2)).unwrap().len() < 400);
+    }
+
+    #[test]
+    fn contexts_write_separate_log_files() {
+        let _guard = TEST_MUTEX.lock().expect("Mutex poisoned");
+        set_log_level(LevelFilter::Trace);
+        let dir = tempdir().unwrap();
+
+        let make_context = |name: &'static str| {
+            let mut context = Context::new();
+            context
+                .set_log_file(dir.path().join(format!("{name}.log")))
+                .unwrap();
+            for t in 0..5 {
+                context.add_plan(f64::from(t), move |_| {
+                    info!("contexts_write_separate_log_files: from {name}");
+                });
+            }
+            context
+        };
+        // Contexts run one after the other on the same thread...
+        let mut first = make_context("first");
+        let mut second = make_context("second");
+        second.execute();
+        first.execute();
+        drop(first);
+        drop(second);
+        // ...and at the same time on different threads
+        let handles: Vec<_> = ["third", "fourth"]
+            .into_iter()
+            .map(|name| {
+                let path = dir.path().join(format!("{name}.log"));
+                thread::spawn(move || {
+                    let mut context = Context::new();
+                    context.set_log_file(path).unwrap();
+                    for t in 0..100 {
+                        context.add_plan(f64::from(t), move |_| {
+                            info!("contexts_write_separate_log_files: from {name}");
+                        });
+                    }
+                    context.execute();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for name in ["first", "second", "third", "fourth"] {
+            let contents = fs::read_to_string(dir.path().join(format!("{name}.log"))).unwrap();
+            let lines: Vec<&str> = contents
+                .lines()
+                .filter(|line| line.contains("contexts_write_separate_log_files"))
+                .collect();
+            assert!(!lines.is_empty());
+            assert!(lines
+                .iter()
+                .all(|line| line.ends_with(&format!("from {name}"))));
+        }
+    }
 }
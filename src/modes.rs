@@ -0,0 +1,374 @@
+//! Simulation modes, for switching between variants of a model, such as a
+//! baseline and an intervention, in a single binary.
+//!
+//! A mode is an enum defined with [`define_simulation_mode!()`]:
+//!
+//! ```
+//! use ixa::{define_simulation_mode, Context, ContextModeExt};
+//!
+//! define_simulation_mode!(MaskPolicy { Off, Schools, Everywhere });
+//!
+//! let mut context = Context::new();
+//! context.set_mode_by_name("MaskPolicy", "Schools").unwrap();
+//! assert_eq!(context.mode(MaskPolicy), MaskPolicyValue::Schools);
+//! context.when_mode(MaskPolicyValue::Schools, |context| {
+//!     // Subscribe to events or add plans that only apply to this mode
+//!     context.add_plan(1.0, |_context| {});
+//! });
+//! ```
+//!
+//! Each mode has the value of its first variant unless it is set, e.g.,
+//! with `--mode MaskPolicy=Schools` on the command line (see
+//! [`BaseArgs`](crate::BaseArgs)) or in the `"modes"` object of a config
+//! file:
+//!
+//! ```json
+//! { "modes": { "MaskPolicy": "Schools" } }
+//! ```
+//!
+//! The values of all modes are included in the
+//! [`ExecutionStatistics`](crate::ExecutionStatistics) of a run.
+use crate::context::Context;
+use crate::define_data_plugin;
+use crate::error::IxaError;
+use std::any::TypeId;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::sync::{LazyLock, Mutex};
+
+/// A simulation mode. Don't implement this directly, but define modes
+/// with [`define_simulation_mode!()`].
+pub trait SimulationMode: Copy + 'static {
+    /// The enum of the mode's values
+    type Value: Copy + Debug + PartialEq + 'static;
+    fn name() -> &'static str;
+    /// The names of the values, in the order they were defined
+    fn values() -> &'static [&'static str];
+    #[doc(hidden)]
+    fn from_index(index: usize) -> Self::Value;
+    #[doc(hidden)]
+    fn to_index(value: Self::Value) -> usize;
+}
+
+/// A value of a [`SimulationMode`], which is implemented for the value
+/// enums defined by [`define_simulation_mode!()`].
+pub trait ModeValue: Copy + 'static {
+    type Mode: SimulationMode<Value = Self>;
+}
+
+/// Defines a simulation mode, `$mode`, and an enum of its values,
+/// `<$mode>Value`, with the given variants. The mode has the value of the
+/// first variant unless it is set.
+#[macro_export]
+macro_rules! define_simulation_mode {
+    ($mode:ident { $($variant:ident),+ $(,)? }) => {
+        #[derive(Debug, Copy, Clone)]
+        pub struct $mode;
+
+        paste::paste! {
+            #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+            pub enum [<$mode Value>] {
+                $($variant),+
+            }
+
+            impl $crate::modes::SimulationMode for $mode {
+                type Value = [<$mode Value>];
+
+                fn name() -> &'static str {
+                    stringify!($mode)
+                }
+
+                fn values() -> &'static [&'static str] {
+                    &[$(stringify!($variant)),+]
+                }
+
+                fn from_index(index: usize) -> Self::Value {
+                    const VALUES: &[[<$mode Value>]] = &[$([<$mode Value>]::$variant),+];
+                    VALUES[index]
+                }
+
+                fn to_index(value: Self::Value) -> usize {
+                    value as usize
+                }
+            }
+
+            impl $crate::modes::ModeValue for [<$mode Value>] {
+                type Mode = $mode;
+            }
+
+            #[ctor::ctor]
+            fn [<$mode:snake _register_mode>]() {
+                $crate::modes::register_mode::<$mode>();
+            }
+        }
+    };
+}
+pub use define_simulation_mode;
+
+struct ModeInfo {
+    type_id: TypeId,
+    values: &'static [&'static str],
+}
+
+static SIMULATION_MODES: LazyLock<Mutex<HashMap<&'static str, ModeInfo>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[doc(hidden)]
+#[allow(clippy::missing_panics_doc)]
+pub fn register_mode<M: SimulationMode>() {
+    let previous = SIMULATION_MODES.lock().unwrap().insert(
+        M::name(),
+        ModeInfo {
+            type_id: TypeId::of::<M>(),
+            values: M::values(),
+        },
+    );
+    assert!(
+        previous.is_none(),
+        "Duplicate simulation mode {}",
+        M::name()
+    );
+}
+
+// The index of each mode's value, for modes that have been set. This is
+// a plain map rather than a `RefCell` so that reading a mode is cheap.
+struct ModesData {
+    values: HashMap<TypeId, usize>,
+}
+
+define_data_plugin!(
+    ModesPlugin,
+    ModesData,
+    ModesData {
+        values: HashMap::new(),
+    }
+);
+
+// The selected modes belong to the current run, so they aren't saved in checkpoints
+pub(crate) fn plugin_type_id() -> TypeId {
+    TypeId::of::<ModesPlugin>()
+}
+
+fn mode_index(context: &Context, type_id: TypeId) -> usize {
+    context
+        .get_data_container(ModesPlugin)
+        .and_then(|data_container| data_container.values.get(&type_id).copied())
+        .unwrap_or(0)
+}
+
+/// The current value of every mode, by name, for recording with the
+/// results of a run.
+pub(crate) fn mode_values(context: &Context) -> BTreeMap<String, String> {
+    SIMULATION_MODES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, info)| {
+            (
+                (*name).to_string(),
+                info.values[mode_index(context, info.type_id)].to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Set modes from the `"modes"` object of a config file.
+pub(crate) fn set_modes_from_json(
+    context: &mut Context,
+    modes: serde_json::Value,
+) -> Result<(), IxaError> {
+    let serde_json::Value::Object(modes) = modes else {
+        return Err(IxaError::from("\"modes\" must be an object"));
+    };
+    for (name, value) in modes {
+        let serde_json::Value::String(value) = value else {
+            return Err(IxaError::from(format!(
+                "The value of mode {name} must be a string"
+            )));
+        };
+        context.set_mode_by_name(&name, &value)?;
+    }
+    Ok(())
+}
+
+/// Parse a `Mode=Value` command line argument and set the mode.
+pub(crate) fn set_mode_from_arg(context: &mut Context, arg: &str) -> Result<(), IxaError> {
+    let Some((name, value)) = arg.split_once('=') else {
+        return Err(IxaError::from(format!(
+            "Invalid mode {arg}; expected MODE=VALUE"
+        )));
+    };
+    context.set_mode_by_name(name.trim(), value.trim())
+}
+
+pub trait ContextModeExt {
+    /// Get the current value of mode `M`.
+    fn mode<M: SimulationMode>(&self, mode: M) -> M::Value;
+
+    /// Set the value of the mode that `value` belongs to.
+    fn set_mode<V: ModeValue>(&mut self, value: V);
+
+    /// Set a mode by the names of the mode and the value, as on the
+    /// command line.
+    ///
+    /// # Errors
+    /// Returns `IxaError` listing the valid names if there's no mode with
+    /// the given name or it doesn't have the given value.
+    fn set_mode_by_name(&mut self, name: &str, value: &str) -> Result<(), IxaError>;
+
+    /// Call `setup` if the mode that `value` belongs to currently has that
+    /// value, e.g., to subscribe to events or add plans that only apply in
+    /// that mode. Returns whether `setup` was called.
+    fn when_mode<V: ModeValue>(&mut self, value: V, setup: impl FnOnce(&mut Context)) -> bool;
+}
+
+impl ContextModeExt for Context {
+    #[allow(clippy::needless_pass_by_value)]
+    fn mode<M: SimulationMode>(&self, _mode: M) -> M::Value {
+        M::from_index(mode_index(self, TypeId::of::<M>()))
+    }
+
+    fn set_mode<V: ModeValue>(&mut self, value: V) {
+        self.get_data_container_mut(ModesPlugin)
+            .values
+            .insert(TypeId::of::<V::Mode>(), V::Mode::to_index(value));
+    }
+
+    fn set_mode_by_name(&mut self, name: &str, value: &str) -> Result<(), IxaError> {
+        let (type_id, index) = {
+            let modes = SIMULATION_MODES.lock().unwrap();
+            let Some(info) = modes.get(name) else {
+                let mut names = modes.keys().copied().collect::<Vec<_>>();
+                names.sort_unstable();
+                return Err(IxaError::from(format!(
+                    "Unknown mode {name}; valid modes are: {}",
+                    names.join(", ")
+                )));
+            };
+            let Some(index) = info.values.iter().position(|v| *v == value) else {
+                return Err(IxaError::from(format!(
+                    "Unknown value {value} for mode {name}; valid values are: {}",
+                    info.values.join(", ")
+                )));
+            };
+            (info.type_id, index)
+        };
+        self.get_data_container_mut(ModesPlugin)
+            .values
+            .insert(type_id, index);
+        Ok(())
+    }
+
+    fn when_mode<V: ModeValue>(&mut self, value: V, setup: impl FnOnce(&mut Context)) -> bool {
+        let matches = mode_index(self, TypeId::of::<V::Mode>()) == V::Mode::to_index(value);
+        if matches {
+            setup(self);
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{mode_values, set_mode_from_arg, ContextModeExt};
+    use crate::{Context, ContextGlobalPropertiesExt};
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+    use tempfile::NamedTempFile;
+
+    define_simulation_mode!(MaskPolicy {
+        Off,
+        Schools,
+        Everywhere
+    });
+    define_simulation_mode!(Vaccination { Baseline, Expanded });
+
+    #[test]
+    fn defaults_to_first_value() {
+        let context = Context::new();
+        assert_eq!(context.mode(MaskPolicy), MaskPolicyValue::Off);
+        assert_eq!(context.mode(Vaccination), VaccinationValue::Baseline);
+    }
+
+    #[test]
+    fn set_mode() {
+        let mut context = Context::new();
+        context.set_mode(MaskPolicyValue::Everywhere);
+        assert_eq!(context.mode(MaskPolicy), MaskPolicyValue::Everywhere);
+        assert_eq!(context.mode(Vaccination), VaccinationValue::Baseline);
+        context.set_mode_by_name("Vaccination", "Expanded").unwrap();
+        assert_eq!(context.mode(Vaccination), VaccinationValue::Expanded);
+    }
+
+    #[test]
+    fn unknown_mode_or_value() {
+        let mut context = Context::new();
+        let error = context
+            .set_mode_by_name("MaskPolicy", "Nowhere")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Off, Schools, Everywhere"), "{error}");
+        let error = context
+            .set_mode_by_name("Masks", "Off")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("MaskPolicy"), "{error}");
+        assert!(error.contains("Vaccination"), "{error}");
+        assert_eq!(context.mode(MaskPolicy), MaskPolicyValue::Off);
+    }
+
+    #[test]
+    fn parse_arg() {
+        let mut context = Context::new();
+        set_mode_from_arg(&mut context, "MaskPolicy=Schools").unwrap();
+        assert_eq!(context.mode(MaskPolicy), MaskPolicyValue::Schools);
+        assert!(set_mode_from_arg(&mut context, "MaskPolicy").is_err());
+        assert!(set_mode_from_arg(&mut context, "MaskPolicy=schools").is_err());
+    }
+
+    #[test]
+    fn when_mode_only_wires_matching_mode() {
+        let ran = Rc::new(RefCell::new(Vec::new()));
+        for mode in ["Off", "Schools", "Everywhere"] {
+            let mut context = Context::new();
+            context.set_mode_by_name("MaskPolicy", mode).unwrap();
+            let ran_clone = Rc::clone(&ran);
+            let wired = context.when_mode(MaskPolicyValue::Schools, move |context| {
+                context.add_plan(1.0, move |context| {
+                    ran_clone.borrow_mut().push(context.mode(MaskPolicy));
+                });
+            });
+            assert_eq!(wired, mode == "Schools");
+            context.execute();
+        }
+        assert_eq!(*ran.borrow(), vec![MaskPolicyValue::Schools]);
+    }
+
+    #[test]
+    fn modes_from_config_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, r#"{{"modes": {{"MaskPolicy": "Everywhere"}}}}"#).unwrap();
+        let mut context = Context::new();
+        context.load_global_properties(file.path()).unwrap();
+        assert_eq!(context.mode(MaskPolicy), MaskPolicyValue::Everywhere);
+
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, r#"{{"modes": {{"MaskPolicy": "Sometimes"}}}}"#).unwrap();
+        let error = Context::new()
+            .load_global_properties(file.path())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Off, Schools, Everywhere"), "{error}");
+    }
+
+    #[test]
+    fn modes_in_execution_statistics() {
+        let mut context = Context::new();
+        context.set_mode(VaccinationValue::Expanded);
+        let modes = mode_values(&context);
+        assert_eq!(modes["MaskPolicy"], "Off");
+        assert_eq!(modes["Vaccination"], "Expanded");
+        assert_eq!(context.execution_statistics().modes, modes);
+    }
+}
@@ -0,0 +1,661 @@
+//! Person properties that aggregate the properties of a person's network
+//! neighbors.
+//!
+//! A neighborhood aggregate counts the people that a person has an edge of
+//! a given type to whose value of a source property matches some
+//! condition, e.g., the number of infectious household contacts. Rather
+//! than iterating over the edges on every read, the counts are maintained
+//! incrementally: when a neighbor's source property changes, or an edge of
+//! the type is added or removed, the counts of the affected people are
+//! updated. Aggregates are regular person properties, so they can be
+//! indexed and queried and emit [`PersonPropertyChangeEvent`]s when they
+//! change.
+//!
+//! Aggregates are defined with [`define_neighborhood_aggregate!()`] and
+//! must be tracked with [`ContextNeighborhoodExt::track_neighborhood_aggregate()`]
+//! before they are read:
+//!
+//! ```
+//! use ixa::neighborhood::ContextNeighborhoodExt;
+//! use ixa::{
+//!     define_edge_type, define_neighborhood_aggregate, define_person_property_with_default,
+//!     Context, ContextNetworkExt, ContextPeopleExt,
+//! };
+//!
+//! #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+//! pub enum InfectionStatusValue {
+//!     Susceptible,
+//!     Infectious,
+//! }
+//! define_person_property_with_default!(
+//!     InfectionStatus,
+//!     InfectionStatusValue,
+//!     InfectionStatusValue::Susceptible
+//! );
+//! define_edge_type!(HouseholdEdge, ());
+//! define_neighborhood_aggregate!(
+//!     InfectiousContacts,
+//!     edge_type = HouseholdEdge,
+//!     source = InfectionStatus,
+//!     aggregate = CountMatching(InfectionStatusValue::Infectious)
+//! );
+//!
+//! let mut context = Context::new();
+//! context.track_neighborhood_aggregate(InfectiousContacts);
+//! let person = context.add_person(()).unwrap();
+//! let contact = context.add_person(()).unwrap();
+//! context.add_edge_bidi::<HouseholdEdge>(person, contact, 1.0, ()).unwrap();
+//! context.set_person_property(contact, InfectionStatus, InfectionStatusValue::Infectious);
+//! context.execute();
+//! assert_eq!(context.get_person_property(person, InfectiousContacts), 1);
+//! ```
+//!
+//! The counts are updated by event handlers, so like other event-driven
+//! state they are current once the events emitted by a change have been
+//! handled, i.e., by the time the next plan runs.
+use crate::network::{ContextNetworkExt, Edge, EdgeAddedEvent, EdgeRemovedEvent, EdgeType};
+use crate::PersonPropertyChangeEvent;
+use crate::{define_data_plugin, Context, ContextPeopleExt, PersonId, PersonProperty};
+use std::any::TypeId;
+use std::collections::HashMap;
+
+/// A person property that counts a person's neighbors over edges of type
+/// `Edge` whose value of `Source` matches a condition.
+///
+/// Use [`define_neighborhood_aggregate!()`] to implement this trait.
+pub trait NeighborhoodAggregate: PersonProperty<Value = usize> + 'static {
+    type Edge: EdgeType + 'static;
+    type Source: PersonProperty + 'static;
+
+    /// Whether a neighbor with `value` for `Source` is counted
+    fn counts(value: <Self::Source as PersonProperty>::Value) -> bool;
+}
+
+/// Defines a person property that counts the neighbors of each person
+/// over edges of a given type whose value of a source property matches a
+/// condition, with the following parameters:
+/// * `$aggregate`: A name for the identifier type of the property
+/// * `edge_type`: The edge type to follow from each person
+/// * `source`: The person property of the neighbors to examine
+/// * `aggregate`: Either `CountMatching(value)`, which counts neighbors
+///   whose source value equals `value`, or `CountWhere(predicate)`, which
+///   counts neighbors whose source value satisfies `predicate`
+///
+/// The value of the property is a `usize`. It must be tracked with
+/// [`ContextNeighborhoodExt::track_neighborhood_aggregate()`] before it is
+/// read.
+#[macro_export]
+macro_rules! define_neighborhood_aggregate {
+    (
+        $aggregate:ident,
+        edge_type = $edge_type:ty,
+        source = $source:ty,
+        aggregate = CountMatching($value:expr)
+    ) => {
+        $crate::define_neighborhood_aggregate!(
+            $aggregate,
+            edge_type = $edge_type,
+            source = $source,
+            aggregate = CountWhere(|value| value == $value)
+        );
+    };
+    (
+        $aggregate:ident,
+        edge_type = $edge_type:ty,
+        source = $source:ty,
+        aggregate = CountWhere($predicate:expr)
+    ) => {
+        #[derive(Debug, Copy, Clone)]
+        pub struct $aggregate;
+        impl $crate::people::PersonProperty for $aggregate {
+            type Value = usize;
+            fn compute(
+                context: &$crate::context::Context,
+                person_id: $crate::people::PersonId,
+            ) -> Self::Value {
+                $crate::neighborhood::aggregate_count::<$aggregate>(context, person_id)
+            }
+            fn get_instance() -> Self {
+                $aggregate
+            }
+            fn name() -> &'static str {
+                stringify!($aggregate)
+            }
+        }
+        impl $crate::neighborhood::NeighborhoodAggregate for $aggregate {
+            type Edge = $edge_type;
+            type Source = $source;
+            fn counts(value: <$source as $crate::people::PersonProperty>::Value) -> bool {
+                let predicate: fn(<$source as $crate::people::PersonProperty>::Value) -> bool =
+                    $predicate;
+                predicate(value)
+            }
+        }
+    };
+}
+pub use define_neighborhood_aggregate;
+
+#[derive(Default)]
+struct AggregateState {
+    // For each person, the people whose edges to them have been counted.
+    // This is a `Vec` so that updates are applied in a deterministic order.
+    incoming: HashMap<PersonId, Vec<PersonId>>,
+    // Whether each person with incoming edges is counted, as of the last
+    // change to their source property that was handled.
+    counted: HashMap<PersonId, bool>,
+    // The current count for each person, indexed by id.
+    counts: Vec<usize>,
+}
+
+impl AggregateState {
+    fn count_mut(&mut self, person_id: PersonId) -> &mut usize {
+        if person_id.0 >= self.counts.len() {
+            self.counts.resize(person_id.0 + 1, 0);
+        }
+        &mut self.counts[person_id.0]
+    }
+}
+
+#[derive(Default)]
+struct NeighborhoodData {
+    aggregates: HashMap<TypeId, AggregateState>,
+}
+
+define_data_plugin!(
+    NeighborhoodPlugin,
+    NeighborhoodData,
+    NeighborhoodData::default()
+);
+
+fn state_mut<A: NeighborhoodAggregate>(context: &mut Context) -> &mut AggregateState {
+    context
+        .get_data_container_mut(NeighborhoodPlugin)
+        .aggregates
+        .get_mut(&TypeId::of::<A>())
+        .expect("Neighborhood aggregate is not tracked")
+}
+
+#[doc(hidden)]
+/// Returns the current count for `person_id`. This is used to initialize
+/// the value of the aggregate property.
+///
+/// # Panics
+/// Panics if the aggregate isn't tracked.
+#[must_use]
+pub fn aggregate_count<A: PersonProperty + 'static>(
+    context: &Context,
+    person_id: PersonId,
+) -> usize {
+    let state = context
+        .get_data_container(NeighborhoodPlugin)
+        .and_then(|data_container| data_container.aggregates.get(&TypeId::of::<A>()));
+    match state {
+        Some(state) => state.counts.get(person_id.0).copied().unwrap_or(0),
+        None => panic!(
+            "Neighborhood aggregate {} is not tracked; call track_neighborhood_aggregate() first",
+            A::name()
+        ),
+    }
+}
+
+// Add one to or subtract one from the aggregate of each of `people`,
+// emitting a change event for each.
+fn adjust<A: NeighborhoodAggregate>(context: &mut Context, people: &[PersonId], increase: bool) {
+    for person_id in people {
+        // Make sure the stored value is initialized from the count before
+        // the count changes.
+        context.get_person_property(*person_id, A::get_instance());
+        let count = state_mut::<A>(context).count_mut(*person_id);
+        if increase {
+            *count += 1;
+        } else {
+            *count -= 1;
+        }
+        let value = *count;
+        context.set_person_property(*person_id, A::get_instance(), value);
+    }
+}
+
+// The handlers below check the network and the recorded state rather than
+// trusting the event, because several changes may have been made before
+// the first of their events is handled. Each one brings the state in line
+// with the network and ignores changes that have already been accounted
+// for.
+
+// An undirected edge is added and removed with one event, but each of the
+// two people counts the other, so it's handled as an edge each way.
+fn orientations<V: Copy>(edge: Edge<V>, undirected: bool) -> Vec<Edge<V>> {
+    let mut edges = vec![edge];
+    if undirected {
+        edges.push(Edge {
+            person: edge.neighbor,
+            neighbor: edge.person,
+            ..edge
+        });
+    }
+    edges
+}
+
+fn edge_added<A: NeighborhoodAggregate>(
+    context: &mut Context,
+    edge: Edge<<A::Edge as EdgeType>::Value>,
+) {
+    for edge in orientations(edge, <A::Edge as EdgeType>::UNDIRECTED) {
+        count_edge::<A>(context, edge);
+    }
+}
+
+fn count_edge<A: NeighborhoodAggregate>(
+    context: &mut Context,
+    edge: Edge<<A::Edge as EdgeType>::Value>,
+) {
+    if context
+        .get_edge::<A::Edge>(edge.person, edge.neighbor)
+        .is_none()
+    {
+        // Removed before this event was handled
+        return;
+    }
+    let counted = A::counts(
+        context.get_person_property(edge.neighbor, <A::Source as PersonProperty>::get_instance()),
+    );
+    let state = state_mut::<A>(context);
+    let incoming = state.incoming.entry(edge.neighbor).or_default();
+    if incoming.contains(&edge.person) {
+        return;
+    }
+    incoming.push(edge.person);
+    if *state.counted.entry(edge.neighbor).or_insert(counted) {
+        adjust::<A>(context, &[edge.person], true);
+    }
+}
+
+fn edge_removed<A: NeighborhoodAggregate>(
+    context: &mut Context,
+    edge: Edge<<A::Edge as EdgeType>::Value>,
+) {
+    for edge in orientations(edge, <A::Edge as EdgeType>::UNDIRECTED) {
+        uncount_edge::<A>(context, edge);
+    }
+}
+
+fn uncount_edge<A: NeighborhoodAggregate>(
+    context: &mut Context,
+    edge: Edge<<A::Edge as EdgeType>::Value>,
+) {
+    if context
+        .get_edge::<A::Edge>(edge.person, edge.neighbor)
+        .is_some()
+    {
+        // Added back before this event was handled
+        return;
+    }
+    let state = state_mut::<A>(context);
+    let Some(incoming) = state.incoming.get_mut(&edge.neighbor) else {
+        return;
+    };
+    let Some(index) = incoming.iter().position(|person| *person == edge.person) else {
+        return;
+    };
+    incoming.remove(index);
+    if state.counted.get(&edge.neighbor) == Some(&true) {
+        adjust::<A>(context, &[edge.person], false);
+    }
+}
+
+fn source_changed<A: NeighborhoodAggregate>(
+    context: &mut Context,
+    event: PersonPropertyChangeEvent<A::Source>,
+) {
+    let now = A::counts(event.current);
+    let state = state_mut::<A>(context);
+    let before = state
+        .counted
+        .insert(event.person_id, now)
+        .unwrap_or_else(|| A::counts(event.previous));
+    if before == now {
+        return;
+    }
+    if let Some(affected) = state.incoming.get(&event.person_id).cloned() {
+        adjust::<A>(context, &affected, now);
+    }
+}
+
+pub trait ContextNeighborhoodExt {
+    /// Start maintaining the values of the neighborhood aggregate `A`,
+    /// counting the edges that already exist. This has no effect if `A`
+    /// is already tracked.
+    fn track_neighborhood_aggregate<A: NeighborhoodAggregate>(&mut self, aggregate: A);
+}
+
+impl ContextNeighborhoodExt for Context {
+    #[allow(clippy::needless_pass_by_value)]
+    fn track_neighborhood_aggregate<A: NeighborhoodAggregate>(&mut self, _aggregate: A) {
+        if self
+            .get_data_container(NeighborhoodPlugin)
+            .is_some_and(|data_container| {
+                data_container.aggregates.contains_key(&TypeId::of::<A>())
+            })
+        {
+            return;
+        }
+
+        let mut state = AggregateState::default();
+        for person_id in self.query_people(()) {
+            for edge in self.get_edges::<A::Edge>(person_id) {
+                let counted = *state.counted.entry(edge.neighbor).or_insert_with(|| {
+                    A::counts(self.get_person_property(
+                        edge.neighbor,
+                        <A::Source as PersonProperty>::get_instance(),
+                    ))
+                });
+                state
+                    .incoming
+                    .entry(edge.neighbor)
+                    .or_default()
+                    .push(person_id);
+                if counted {
+                    *state.count_mut(person_id) += 1;
+                }
+            }
+        }
+        self.get_data_container_mut(NeighborhoodPlugin)
+            .aggregates
+            .insert(TypeId::of::<A>(), state);
+
+        self.subscribe_to_event(|context, event: EdgeAddedEvent<A::Edge>| {
+            edge_added::<A>(context, event.edge);
+        });
+        self.subscribe_to_event(|context, event: EdgeRemovedEvent<A::Edge>| {
+            edge_removed::<A>(context, event.edge);
+        });
+        self.subscribe_to_event(source_changed::<A>);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ContextNeighborhoodExt;
+    use crate::{
+        define_edge_type, define_person_property_with_default, define_rng, Context,
+        ContextNetworkExt, ContextPeopleExt, ContextRandomExt, PersonId, PersonPropertyChangeEvent,
+    };
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub enum InfectionStatusValue {
+        Susceptible,
+        Infectious,
+        Recovered,
+    }
+    define_person_property_with_default!(
+        InfectionStatus,
+        InfectionStatusValue,
+        InfectionStatusValue::Susceptible
+    );
+    define_edge_type!(HouseholdEdge, ());
+    define_edge_type!(WorkEdge, ());
+    define_neighborhood_aggregate!(
+        InfectiousContacts,
+        edge_type = HouseholdEdge,
+        source = InfectionStatus,
+        aggregate = CountMatching(InfectionStatusValue::Infectious)
+    );
+    define_neighborhood_aggregate!(
+        ExposedCoworkers,
+        edge_type = WorkEdge,
+        source = InfectionStatus,
+        aggregate = CountWhere(|status| status != InfectionStatusValue::Susceptible)
+    );
+    define_edge_type!(SharedRoomEdge, (), undirected);
+    define_neighborhood_aggregate!(
+        InfectiousRoommates,
+        edge_type = SharedRoomEdge,
+        source = InfectionStatus,
+        aggregate = CountMatching(InfectionStatusValue::Infectious)
+    );
+    define_rng!(NeighborhoodRng);
+
+    fn brute_force(context: &Context, person_id: PersonId) -> usize {
+        context
+            .get_edges::<HouseholdEdge>(person_id)
+            .iter()
+            .filter(|edge| {
+                context.get_person_property(edge.neighbor, InfectionStatus)
+                    == InfectionStatusValue::Infectious
+            })
+            .count()
+    }
+
+    fn setup(population: usize) -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        let people = (0..population)
+            .map(|_| context.add_person(()).unwrap())
+            .collect();
+        (context, people)
+    }
+
+    #[test]
+    fn matches_brute_force_through_churn() {
+        let (mut context, people) = setup(30);
+        context.init_random(42);
+        context.track_neighborhood_aggregate(InfectiousContacts);
+
+        for step in 0..200 {
+            let script_people = people.clone();
+            context.add_plan(f64::from(step), move |context| {
+                let people = &script_people;
+                // Make several changes before any of their events are handled
+                for _ in 0..10 {
+                    let person = people[context.sample_range(NeighborhoodRng, 0..people.len())];
+                    let other = people[context.sample_range(NeighborhoodRng, 0..people.len())];
+                    match context.sample_range(NeighborhoodRng, 0..5) {
+                        0 => {
+                            let status = match context.sample_range(NeighborhoodRng, 0..3) {
+                                0 => InfectionStatusValue::Susceptible,
+                                1 => InfectionStatusValue::Infectious,
+                                _ => InfectionStatusValue::Recovered,
+                            };
+                            context.set_person_property(person, InfectionStatus, status);
+                        }
+                        // These fail for existing edges and self edges
+                        1 => {
+                            let _ = context.add_edge::<HouseholdEdge>(person, other, 1.0, ());
+                        }
+                        2 => {
+                            let _ = context.add_edge_bidi::<HouseholdEdge>(person, other, 1.0, ());
+                        }
+                        3 => {
+                            let _ = context.remove_edge::<HouseholdEdge>(person, other);
+                        }
+                        _ => {
+                            // Remove and add back
+                            if context.remove_edge::<HouseholdEdge>(person, other).is_ok() {
+                                context
+                                    .add_edge::<HouseholdEdge>(person, other, 1.0, ())
+                                    .unwrap();
+                            }
+                        }
+                    }
+                }
+            });
+            let check_people = people.clone();
+            context.add_plan(f64::from(step) + 0.5, move |context| {
+                for person_id in &check_people {
+                    assert_eq!(
+                        context.get_person_property(*person_id, InfectiousContacts),
+                        brute_force(context, *person_id)
+                    );
+                }
+            });
+        }
+        context.execute();
+        assert!(people
+            .iter()
+            .any(|person_id| context.get_person_property(*person_id, InfectiousContacts) > 1));
+    }
+
+    #[test]
+    fn undirected_edges_count_both_ends() {
+        let (mut context, people) = setup(3);
+        context
+            .add_edge::<SharedRoomEdge>(people[0], people[1], 1.0, ())
+            .unwrap();
+        context.track_neighborhood_aggregate(InfectiousRoommates);
+        context
+            .add_edge::<SharedRoomEdge>(people[2], people[1], 1.0, ())
+            .unwrap();
+        context.set_person_property(people[1], InfectionStatus, InfectionStatusValue::Infectious);
+        context.set_person_property(people[2], InfectionStatus, InfectionStatusValue::Infectious);
+        context.execute();
+        let counts = |context: &Context| {
+            people
+                .iter()
+                .map(|person_id| context.get_person_property(*person_id, InfectiousRoommates))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(counts(&context), [1, 1, 1]);
+
+        // Removing the edge from the other end uncounts it for both people.
+        context
+            .remove_edge::<SharedRoomEdge>(people[1], people[0])
+            .unwrap();
+        context.execute();
+        assert_eq!(counts(&context), [0, 1, 1]);
+        context
+            .remove_edge::<SharedRoomEdge>(people[1], people[2])
+            .unwrap();
+        context.execute();
+        assert_eq!(counts(&context), [0, 0, 0]);
+    }
+
+    #[test]
+    fn counts_existing_edges() {
+        let (mut context, people) = setup(4);
+        for neighbor in &people[1..] {
+            context
+                .add_edge::<HouseholdEdge>(people[0], *neighbor, 1.0, ())
+                .unwrap();
+        }
+        context.set_person_property(people[1], InfectionStatus, InfectionStatusValue::Infectious);
+        context.set_person_property(people[2], InfectionStatus, InfectionStatusValue::Infectious);
+        context.track_neighborhood_aggregate(InfectiousContacts);
+        // Tracking twice has no effect
+        context.track_neighborhood_aggregate(InfectiousContacts);
+        context.execute();
+
+        assert_eq!(
+            context.get_person_property(people[0], InfectiousContacts),
+            2
+        );
+        assert_eq!(
+            context.get_person_property(people[1], InfectiousContacts),
+            0
+        );
+    }
+
+    #[test]
+    fn change_events() {
+        let (mut context, people) = setup(4);
+        context.track_neighborhood_aggregate(InfectiousContacts);
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let changes_clone = Rc::clone(&changes);
+        context.subscribe_to_event(
+            move |_, event: PersonPropertyChangeEvent<InfectiousContacts>| {
+                changes_clone
+                    .borrow_mut()
+                    .push((event.person_id, event.previous, event.current));
+            },
+        );
+
+        context
+            .add_edge_bidi::<HouseholdEdge>(people[0], people[1], 1.0, ())
+            .unwrap();
+        context
+            .add_edge::<HouseholdEdge>(people[0], people[2], 1.0, ())
+            .unwrap();
+        context.execute();
+        // Edges to susceptible people don't change anything
+        assert!(changes.borrow().is_empty());
+
+        context.set_person_property(people[1], InfectionStatus, InfectionStatusValue::Infectious);
+        context.set_person_property(people[2], InfectionStatus, InfectionStatusValue::Infectious);
+        // Neither setting the same value again nor a change that is
+        // counted the same way emits events
+        context.set_person_property(people[2], InfectionStatus, InfectionStatusValue::Infectious);
+        context.set_person_property(people[3], InfectionStatus, InfectionStatusValue::Recovered);
+        context.execute();
+        assert_eq!(
+            *changes.borrow(),
+            vec![(people[0], 0, 1), (people[0], 1, 2)]
+        );
+
+        changes.borrow_mut().clear();
+        context
+            .remove_edge::<HouseholdEdge>(people[0], people[1])
+            .unwrap();
+        context
+            .add_edge::<HouseholdEdge>(people[3], people[2], 1.0, ())
+            .unwrap();
+        context.set_person_property(people[1], InfectionStatus, InfectionStatusValue::Recovered);
+        context.execute();
+        assert_eq!(
+            *changes.borrow(),
+            vec![(people[0], 2, 1), (people[3], 0, 1)]
+        );
+    }
+
+    #[test]
+    fn queries_and_indexes() {
+        let (mut context, people) = setup(6);
+        context.track_neighborhood_aggregate(InfectiousContacts);
+        context.track_neighborhood_aggregate(ExposedCoworkers);
+        context.index_property(InfectiousContacts);
+
+        // People 0 and 1 each live with 2 and 3; person 4 works with 2 and 5
+        for person in &people[0..2] {
+            for neighbor in &people[2..4] {
+                context
+                    .add_edge_bidi::<HouseholdEdge>(*person, *neighbor, 1.0, ())
+                    .unwrap();
+            }
+        }
+        context
+            .add_edge::<WorkEdge>(people[4], people[2], 1.0, ())
+            .unwrap();
+        context
+            .add_edge::<WorkEdge>(people[4], people[5], 1.0, ())
+            .unwrap();
+        context.execute();
+        assert_eq!(context.query_people_count((InfectiousContacts, 0)), 6);
+
+        context.set_person_property(people[2], InfectionStatus, InfectionStatusValue::Infectious);
+        context.set_person_property(people[3], InfectionStatus, InfectionStatusValue::Infectious);
+        context.set_person_property(people[5], InfectionStatus, InfectionStatusValue::Recovered);
+        context.execute();
+
+        let mut two = context.query_people((InfectiousContacts, 2));
+        two.sort_by_key(|person_id| person_id.0);
+        assert_eq!(two, vec![people[0], people[1]]);
+        assert_eq!(context.query_people_count((InfectiousContacts, 0)), 4);
+        assert_eq!(
+            context.query_people(((InfectiousContacts, 0), (ExposedCoworkers, 2))),
+            vec![people[4]]
+        );
+
+        let counts: HashMap<usize, usize> = context
+            .distinct_property_values(InfectiousContacts)
+            .into_iter()
+            .collect();
+        assert_eq!(counts, HashMap::from([(0, 4), (2, 2)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Neighborhood aggregate InfectiousContacts is not tracked")]
+    fn untracked_aggregate_panics() {
+        let (context, people) = setup(1);
+        context.get_person_property(people[0], InfectiousContacts);
+    }
+}
@@ -0,0 +1,320 @@
+//! Centrality of people in a network, for finding the people who are best
+//! placed to spread an infection.
+//!
+//! As with the path lengths of the network metrics, distances follow edges
+//! in their direction and count edges rather than adding up weights.
+//! Betweenness uses Brandes' algorithm, which does a breadth-first search
+//! from every person, so it takes time proportional to the population
+//! times the number of edges. For large networks,
+//! [`Context::approximate_betweenness_centrality()`](super::ContextNetworkExt::approximate_betweenness_centrality)
+//! only searches from a sample of people.
+use super::{EdgeType, NetworkData, NetworkPlugin};
+use crate::{Context, ContextPeopleExt, ContextRandomExt, IxaError, PersonId, RngId};
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+
+// The state of the searches from each source, with an entry for each id up
+// to the largest, reused between searches.
+struct Searches {
+    distance: Vec<Option<usize>>,
+    // The number of shortest paths from the source
+    paths: Vec<f64>,
+    predecessors: Vec<Vec<usize>>,
+    dependency: Vec<f64>,
+    // The people reached by the current search, in order of distance
+    order: Vec<usize>,
+    queue: VecDeque<usize>,
+}
+
+impl Searches {
+    fn new(size: usize) -> Self {
+        Searches {
+            distance: vec![None; size],
+            paths: vec![0.0; size],
+            predecessors: vec![Vec::new(); size],
+            dependency: vec![0.0; size],
+            order: Vec::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    // Add the dependencies of `source` on everyone else to `betweenness`.
+    fn accumulate<T: EdgeType + 'static>(
+        &mut self,
+        data: &NetworkData,
+        source: usize,
+        scale: f64,
+        betweenness: &mut [f64],
+    ) {
+        for &person in &self.order {
+            self.distance[person] = None;
+            self.paths[person] = 0.0;
+            self.predecessors[person].clear();
+            self.dependency[person] = 0.0;
+        }
+        self.order.clear();
+
+        self.distance[source] = Some(0);
+        self.paths[source] = 1.0;
+        self.queue.push_back(source);
+        while let Some(person) = self.queue.pop_front() {
+            self.order.push(person);
+            let next = self.distance[person].unwrap() + 1;
+            for edge in data.edges_ref::<T>(PersonId(person)).iter() {
+                let neighbor = edge.neighbor.0;
+                if self.distance[neighbor].is_none() {
+                    self.distance[neighbor] = Some(next);
+                    self.queue.push_back(neighbor);
+                }
+                if self.distance[neighbor] == Some(next) {
+                    self.paths[neighbor] += self.paths[person];
+                    self.predecessors[neighbor].push(person);
+                }
+            }
+        }
+
+        // The people furthest away first, so that each person's dependency
+        // is complete before it's passed on to their predecessors.
+        for &person in self.order.iter().rev() {
+            let share = (1.0 + self.dependency[person]) / self.paths[person];
+            for &predecessor in &self.predecessors[person] {
+                self.dependency[predecessor] += self.paths[predecessor] * share;
+            }
+            if person != source {
+                betweenness[person] += scale * self.dependency[person];
+            }
+        }
+    }
+}
+
+// Everyone, sorted by id, and the size of the arrays indexed by id.
+fn people_by_id(context: &Context) -> (Vec<PersonId>, usize) {
+    let mut people = context.query_people(());
+    people.sort_by_key(|person_id| person_id.0);
+    let size = people.last().map_or(0, |person_id| person_id.0 + 1);
+    (people, size)
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub(super) fn closeness_centrality<T: EdgeType + 'static>(
+    context: &Context,
+    person: PersonId,
+) -> f64 {
+    let (people, size) = people_by_id(context);
+    let Some(data) = context.get_data_container(NetworkPlugin) else {
+        return 0.0;
+    };
+    let mut visited = vec![false; size];
+    let mut queue = VecDeque::new();
+    let (mut reached, mut total_distance) = (0_usize, 0_usize);
+    visited[person.0] = true;
+    queue.push_back((person, 0));
+    while let Some((current, distance)) = queue.pop_front() {
+        for edge in data.edges_ref::<T>(current).iter() {
+            if !visited[edge.neighbor.0] {
+                visited[edge.neighbor.0] = true;
+                reached += 1;
+                total_distance += distance + 1;
+                queue.push_back((edge.neighbor, distance + 1));
+            }
+        }
+    }
+    if reached == 0 {
+        return 0.0;
+    }
+    // Scaled by the fraction of other people reached, so that people who
+    // reach a few people nearby don't score higher than people who reach
+    // everyone.
+    let reached = reached as f64;
+    reached / total_distance as f64 * reached / (people.len() - 1) as f64
+}
+
+fn betweenness_from<T: EdgeType + 'static>(
+    context: &Context,
+    sources: impl Iterator<Item = PersonId>,
+    scale: f64,
+) -> HashMap<PersonId, f64> {
+    let (people, size) = people_by_id(context);
+    let mut betweenness = vec![0.0; size];
+    if let Some(data) = context.get_data_container(NetworkPlugin) {
+        let mut searches = Searches::new(size);
+        for source in sources {
+            searches.accumulate::<T>(data, source.0, scale, &mut betweenness);
+        }
+    }
+    people
+        .into_iter()
+        .map(|person| (person, betweenness[person.0]))
+        .collect()
+}
+
+pub(super) fn betweenness_centrality<T: EdgeType + 'static>(
+    context: &Context,
+) -> HashMap<PersonId, f64> {
+    let (people, _) = people_by_id(context);
+    betweenness_from::<T>(context, people.into_iter(), 1.0)
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub(super) fn approximate_betweenness_centrality<T: EdgeType + 'static, R: RngId + 'static>(
+    context: &Context,
+    rng_id: R,
+    n_sources: usize,
+) -> Result<HashMap<PersonId, f64>, IxaError>
+where
+    R::RngType: Rng,
+{
+    if n_sources == 0 {
+        return Err(IxaError::IxaError(String::from(
+            "Need at least one source to approximate betweenness",
+        )));
+    }
+    let (people, _) = people_by_id(context);
+    if n_sources >= people.len() {
+        return Ok(betweenness_centrality::<T>(context));
+    }
+    // Each source is equally likely, so scaling by the population over the
+    // number of sources gives the exact value on average.
+    let scale = people.len() as f64 / n_sources as f64;
+    let sources: Vec<PersonId> = (0..n_sources)
+        .map(|_| people[context.sample_range(rng_id, 0..people.len())])
+        .collect();
+    Ok(betweenness_from::<T>(context, sources.into_iter(), scale))
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use crate::network::{ContextNetworkExt, EdgeType};
+    use crate::{define_rng, Context, ContextPeopleExt, ContextRandomExt, IxaError, PersonId};
+
+    define_rng!(CentralityRng);
+
+    struct Contact;
+    impl EdgeType for Contact {
+        type Value = ();
+    }
+
+    fn setup(n: usize, edges: &[(usize, usize)]) -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        context.init_random(42);
+        let people: Vec<PersonId> = (0..n).map(|_| context.add_person(()).unwrap()).collect();
+        for &(a, b) in edges {
+            context
+                .add_edge::<Contact>(people[a], people[b], 1.0, ())
+                .unwrap();
+        }
+        (context, people)
+    }
+
+    // Edges in both directions between each pair
+    fn undirected(edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        edges.iter().flat_map(|&(a, b)| [(a, b), (b, a)]).collect()
+    }
+
+    #[test]
+    fn closeness_known_values() {
+        // A path 0 - 1 - 2 - 3
+        let (context, people) = setup(4, &undirected(&[(0, 1), (1, 2), (2, 3)]));
+        let closeness: Vec<f64> = people
+            .iter()
+            .map(|&person| context.closeness_centrality::<Contact>(person))
+            .collect();
+        assert_eq!(closeness, [0.5, 0.75, 0.75, 0.5]);
+
+        // Edges are followed in their direction, and people who can't
+        // reach everyone are scaled down: 0 reaches 1 and 2 at distances 1
+        // and 2, out of 4 other people.
+        let (context, people) = setup(5, &[(0, 1), (1, 2)]);
+        let closeness = context.closeness_centrality::<Contact>(people[0]);
+        assert!((closeness - 2.0 / 3.0 * 2.0 / 4.0).abs() < 1e-12);
+        assert_eq!(context.closeness_centrality::<Contact>(people[2]), 0.0);
+        assert_eq!(context.closeness_centrality::<Contact>(people[4]), 0.0);
+    }
+
+    #[test]
+    fn betweenness_known_values() {
+        // In a star, every path between two leaves goes through the center.
+        let (context, people) = setup(5, &undirected(&[(0, 1), (0, 2), (0, 3), (0, 4)]));
+        let betweenness = context.betweenness_centrality::<Contact>();
+        assert_eq!(betweenness.len(), 5);
+        assert_eq!(betweenness[&people[0]], 12.0);
+        for leaf in &people[1..] {
+            assert_eq!(betweenness[leaf], 0.0);
+        }
+
+        // In a square, the two shortest paths between opposite corners
+        // each go through one of the other corners.
+        let (context, people) = setup(4, &undirected(&[(0, 1), (1, 2), (2, 3), (3, 0)]));
+        let betweenness = context.betweenness_centrality::<Contact>();
+        for person in &people {
+            assert_eq!(betweenness[person], 1.0);
+        }
+
+        // A directed path: 1 is on the path from 0 to 2 and 3, and 2 on
+        // the paths from 0 and 1 to 3.
+        let (context, people) = setup(4, &[(0, 1), (1, 2), (2, 3)]);
+        let betweenness = context.betweenness_centrality::<Contact>();
+        let values: Vec<f64> = people.iter().map(|person| betweenness[person]).collect();
+        assert_eq!(values, [0.0, 2.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn betweenness_without_edges() {
+        let (context, people) = setup(3, &[]);
+        let betweenness = context.betweenness_centrality::<Contact>();
+        assert!(people.iter().all(|person| betweenness[person] == 0.0));
+        assert!(Context::new()
+            .betweenness_centrality::<Contact>()
+            .is_empty());
+    }
+
+    #[test]
+    fn removed_people_are_left_out() {
+        let (mut context, people) = setup(3, &undirected(&[(0, 1), (1, 2)]));
+        context.remove_person(people[2]).unwrap();
+        context.execute();
+        let betweenness = context.betweenness_centrality::<Contact>();
+        assert_eq!(betweenness.len(), 2);
+        assert_eq!(context.closeness_centrality::<Contact>(people[0]), 1.0);
+    }
+
+    #[test]
+    fn approximate_betweenness_is_close() {
+        let (mut context, people) = setup(200, &[]);
+        context
+            .generate_barabasi_albert::<Contact, _>(CentralityRng, 2)
+            .unwrap();
+        let exact = context.betweenness_centrality::<Contact>();
+        let approximate = context
+            .approximate_betweenness_centrality::<Contact, _>(CentralityRng, 100)
+            .unwrap();
+        let total_exact: f64 = exact.values().sum();
+        let total_approximate: f64 = approximate.values().sum();
+        assert!((total_approximate / total_exact - 1.0).abs() < 0.1);
+
+        // The most central person is still near the top.
+        let top = people
+            .iter()
+            .max_by(|a, b| exact[a].total_cmp(&exact[b]))
+            .unwrap();
+        let higher = people
+            .iter()
+            .filter(|person| approximate[person] > approximate[top])
+            .count();
+        assert!(higher < 5);
+
+        // With at least as many sources as people, the result is exact.
+        let all = context
+            .approximate_betweenness_centrality::<Contact, _>(CentralityRng, 200)
+            .unwrap();
+        assert_eq!(all, exact);
+    }
+
+    #[test]
+    fn approximate_betweenness_needs_sources() {
+        let (context, _) = setup(3, &[]);
+        let result = context.approximate_betweenness_centrality::<Contact, _>(CentralityRng, 0);
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+    }
+}
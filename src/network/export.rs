@@ -0,0 +1,188 @@
+use super::{EdgeType, NetworkPlugin};
+use crate::{Context, ContextPeopleExt, IxaError, PersonId};
+use csv::WriterBuilder;
+use serde::Serialize;
+use std::any::TypeId;
+use std::path::Path;
+
+#[derive(Serialize)]
+struct EdgeColumns {
+    source: PersonId,
+    target: PersonId,
+    weight: f32,
+}
+
+// The columns for edge data of type `V`: the field names if it's a struct,
+// none if it's `()`, and otherwise a single column named "inner".
+fn inner_headers<V: Serialize + Default + 'static>() -> Vec<String> {
+    if TypeId::of::<V>() == TypeId::of::<()>() {
+        return Vec::new();
+    }
+    // The csv crate only writes headers for structs, so write a row with
+    // headers to memory and see what they are.
+    let columns = EdgeColumns {
+        source: PersonId(0),
+        target: PersonId(0),
+        weight: 0.0,
+    };
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    if writer.serialize((columns, V::default())).is_err() {
+        return vec![String::from("inner")];
+    }
+    let output = writer.into_inner().unwrap_or_default();
+    let mut reader = csv::Reader::from_reader(output.as_slice());
+    reader
+        .headers()
+        .map(|headers| headers.iter().skip(3).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+pub(super) fn export_network_csv<T: EdgeType + 'static>(
+    context: &Context,
+    path: &Path,
+) -> Result<(), IxaError>
+where
+    T::Value: Serialize + 'static,
+{
+    let mut writer = WriterBuilder::new().has_headers(false).from_path(path)?;
+    let mut headers = vec![
+        String::from("source"),
+        String::from("target"),
+        String::from("weight"),
+    ];
+    let inner = inner_headers::<T::Value>();
+    let has_inner = !inner.is_empty();
+    headers.extend(inner);
+    writer.write_record(&headers)?;
+
+    if let Some(data_container) = context.get_data_container(NetworkPlugin) {
+        for person in context.query_people(()) {
+            for edge in data_container.edges_ref::<T>(person).iter() {
+                // Undirected edges are written once, from the lower id.
+                if T::UNDIRECTED && edge.person.0 > edge.neighbor.0 {
+                    continue;
+                }
+                let columns = EdgeColumns {
+                    source: edge.person,
+                    target: edge.neighbor,
+                    weight: edge.weight,
+                };
+                if has_inner {
+                    writer.serialize((columns, edge.inner))?;
+                } else {
+                    writer.serialize(columns)?;
+                }
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::network::{ContextNetworkExt, EdgeType};
+    use crate::{define_edge_type, Context, ContextPeopleExt, PersonId};
+    use serde::Serialize;
+    use tempfile::NamedTempFile;
+
+    #[derive(Copy, Clone, Default, Serialize)]
+    pub struct ContactInfo {
+        setting: u8,
+        hours: f32,
+    }
+
+    define_edge_type!(Plain, ());
+    define_edge_type!(Detailed, ContactInfo);
+    define_edge_type!(Scalar, u32);
+
+    fn setup(n: usize) -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        let people = (0..n).map(|_| context.add_person(()).unwrap()).collect();
+        (context, people)
+    }
+
+    fn export<T: EdgeType + 'static>(context: &Context) -> String
+    where
+        T::Value: Serialize,
+    {
+        let file = NamedTempFile::new().unwrap();
+        context.export_network_csv::<T>(file.path()).unwrap();
+        std::fs::read_to_string(file.path()).unwrap()
+    }
+
+    #[test]
+    fn directed_and_bidirectional_edges() {
+        let (mut context, people) = setup(3);
+        context
+            .add_edge::<Plain>(people[0], people[1], 0.5, ())
+            .unwrap();
+        context
+            .add_edge_bidi::<Plain>(people[1], people[2], 2.0, ())
+            .unwrap();
+
+        assert_eq!(
+            export::<Plain>(&context),
+            "source,target,weight\n0,1,0.5\n1,2,2.0\n2,1,2.0\n"
+        );
+    }
+
+    #[test]
+    fn struct_fields_are_columns() {
+        let (mut context, people) = setup(2);
+        context
+            .add_edge::<Detailed>(
+                people[1],
+                people[0],
+                1.0,
+                ContactInfo {
+                    setting: 3,
+                    hours: 1.5,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            export::<Detailed>(&context),
+            "source,target,weight,setting,hours\n1,0,1.0,3,1.5\n"
+        );
+    }
+
+    #[test]
+    fn other_values_are_one_column() {
+        let (mut context, people) = setup(2);
+        context
+            .add_edge::<Scalar>(people[0], people[1], 1.0, 7)
+            .unwrap();
+
+        assert_eq!(
+            export::<Scalar>(&context),
+            "source,target,weight,inner\n0,1,1.0,7\n"
+        );
+    }
+
+    #[test]
+    fn header_without_edges() {
+        let (context, _) = setup(2);
+        assert_eq!(
+            export::<Detailed>(&context),
+            "source,target,weight,setting,hours\n"
+        );
+        assert_eq!(export::<Plain>(&Context::new()), "source,target,weight\n");
+    }
+
+    #[test]
+    fn removed_people_are_left_out() {
+        let (mut context, people) = setup(3);
+        context
+            .add_edge::<Plain>(people[0], people[1], 1.0, ())
+            .unwrap();
+        context
+            .add_edge::<Plain>(people[1], people[2], 1.0, ())
+            .unwrap();
+        context.remove_person(people[2]).unwrap();
+        context.execute();
+
+        assert_eq!(export::<Plain>(&context), "source,target,weight\n0,1,1.0\n");
+    }
+}
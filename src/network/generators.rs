@@ -0,0 +1,362 @@
+//! Generators for random networks.
+use super::{ContextNetworkExt, EdgeType};
+use crate::{Context, ContextPeopleExt, ContextRandomExt, IxaError, PersonId, RngId};
+use rand::Rng;
+
+// Returns the index of the next pair from `from` on that's chosen with
+// probability `q`, where `log_q` is ln(1 - q), or `pairs` if there isn't
+// one. The gaps between chosen pairs are geometrically distributed, so
+// this takes one draw per chosen pair rather than one per pair.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn next_pair<R: RngId + 'static>(
+    context: &Context,
+    rng_id: R,
+    log_q: f64,
+    from: usize,
+    pairs: usize,
+) -> usize
+where
+    R::RngType: Rng,
+{
+    if log_q == 0.0 {
+        return pairs;
+    }
+    let u: f64 = context.sample(rng_id, Rng::gen);
+    let skip = ((1.0 - u).ln() / log_q).floor();
+    if skip >= (pairs - from) as f64 {
+        pairs
+    } else {
+        from + skip as usize
+    }
+}
+
+pub(super) fn erdos_renyi<T: EdgeType + 'static, R: RngId + 'static>(
+    context: &mut Context,
+    rng_id: R,
+    p: f64,
+) -> Result<(), IxaError>
+where
+    R::RngType: Rng,
+{
+    if !(0.0..=1.0).contains(&p) {
+        return Err(IxaError::IxaError(format!("Invalid edge probability {p}")));
+    }
+    let mut people = context.query_people(());
+    people.sort_by_key(|person_id| person_id.0);
+    let n = people.len();
+    if n < 2 {
+        return Ok(());
+    }
+
+    // The ordered pairs are numbered from 0 to n(n - 1) - 1, n - 1 pairs
+    // for each source.
+    let pairs = n * (n - 1);
+    let add_pair = |context: &mut Context, pair: usize| -> Result<(), IxaError> {
+        let source = pair / (n - 1);
+        let mut target = pair % (n - 1);
+        if target >= source {
+            target += 1;
+        }
+        add_default_edge::<T>(context, people[source], people[target])
+    };
+
+    if p <= 0.5 {
+        let log_q = (1.0 - p).ln();
+        let mut pair = next_pair(context, rng_id, log_q, 0, pairs);
+        while pair < pairs {
+            add_pair(context, pair)?;
+            pair = next_pair(context, rng_id, log_q, pair + 1, pairs);
+        }
+    } else {
+        // Choose the pairs that don't get an edge, with probability 1 - p,
+        // and add all the others.
+        let log_q = p.ln();
+        let mut skipped = next_pair(context, rng_id, log_q, 0, pairs);
+        for pair in 0..pairs {
+            if pair == skipped {
+                skipped = next_pair(context, rng_id, log_q, pair + 1, pairs);
+            } else {
+                add_pair(context, pair)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Adds an edge of type T from `person` to `neighbor` unless there already
+// is one.
+fn add_default_edge<T: EdgeType + 'static>(
+    context: &mut Context,
+    person: PersonId,
+    neighbor: PersonId,
+) -> Result<(), IxaError> {
+    if context.get_edge::<T>(person, neighbor).is_none() {
+        context.add_edge::<T>(person, neighbor, 1.0, T::Value::default())?;
+    }
+    Ok(())
+}
+
+pub(super) fn barabasi_albert<T: EdgeType + 'static, R: RngId + 'static>(
+    context: &mut Context,
+    rng_id: R,
+    m: usize,
+) -> Result<(), IxaError>
+where
+    R::RngType: Rng,
+{
+    let mut people = context.query_people(());
+    people.sort_by_key(|person_id| person_id.0);
+    if m == 0 {
+        return Err(IxaError::IxaError(String::from(
+            "Each person must attach to at least one other",
+        )));
+    }
+    if people.len() < m + 1 {
+        return Err(IxaError::IxaError(format!(
+            "Need at least {} people to attach each to {m} others, but there are {}",
+            m + 1,
+            people.len()
+        )));
+    }
+
+    // Each person appears here once for each connection to them, so a
+    // uniform draw picks people in proportion to their degree.
+    let mut endpoints = Vec::new();
+    let (seed, rest) = people.split_at(m + 1);
+    for &person in seed {
+        for &neighbor in seed {
+            if person != neighbor {
+                add_default_edge::<T>(context, person, neighbor)?;
+                endpoints.push(neighbor);
+            }
+        }
+    }
+
+    let mut targets = Vec::with_capacity(m);
+    for &person in rest {
+        targets.clear();
+        while targets.len() < m {
+            let target = endpoints[context.sample_range(rng_id, 0..endpoints.len())];
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        for &target in &targets {
+            add_default_edge::<T>(context, person, target)?;
+            add_default_edge::<T>(context, target, person)?;
+            endpoints.push(target);
+            endpoints.push(person);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::cast_precision_loss, clippy::float_cmp)]
+mod test {
+    use crate::network::{ContextNetworkExt, EdgeType};
+    use crate::{define_rng, Context, ContextPeopleExt, ContextRandomExt, IxaError, PersonId};
+
+    define_rng!(NetworkRng);
+
+    #[derive(Copy, Clone, Default, Debug, PartialEq)]
+    struct Contact(u8);
+    impl EdgeType for Contact {
+        type Value = Contact;
+    }
+
+    fn setup(n: usize) -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        context.init_random(42);
+        let people = (0..n).map(|_| context.add_person(()).unwrap()).collect();
+        (context, people)
+    }
+
+    fn edge_count(context: &Context, people: &[PersonId]) -> usize {
+        people
+            .iter()
+            .map(|person| context.get_edges::<Contact>(*person).len())
+            .sum()
+    }
+
+    #[test]
+    fn edge_counts() {
+        let n = 200;
+        let pairs = (n * (n - 1)) as f64;
+        for p in [0.05, 0.3, 0.5, 0.7, 0.95] {
+            let (mut context, people) = setup(n);
+            context
+                .generate_erdos_renyi::<Contact, _>(NetworkRng, p)
+                .unwrap();
+            let count = edge_count(&context, &people) as f64;
+            let sd = (pairs * p * (1.0 - p)).sqrt();
+            assert!((count - pairs * p).abs() < 4.0 * sd, "p={p}: {count}");
+        }
+    }
+
+    #[test]
+    fn edges_are_default_with_weight_one() {
+        let (mut context, people) = setup(20);
+        context
+            .generate_erdos_renyi::<Contact, _>(NetworkRng, 0.8)
+            .unwrap();
+        for person in &people {
+            for edge in context.get_edges::<Contact>(*person) {
+                assert_eq!(edge.weight, 1.0);
+                assert_eq!(edge.inner, Contact(0));
+                assert_ne!(edge.neighbor, *person);
+            }
+        }
+    }
+
+    #[test]
+    fn extreme_probabilities() {
+        let (mut context, people) = setup(10);
+        context
+            .generate_erdos_renyi::<Contact, _>(NetworkRng, 0.0)
+            .unwrap();
+        assert_eq!(edge_count(&context, &people), 0);
+        context
+            .generate_erdos_renyi::<Contact, _>(NetworkRng, 1.0)
+            .unwrap();
+        assert_eq!(edge_count(&context, &people), 90);
+    }
+
+    #[test]
+    fn keeps_existing_edges() {
+        let (mut context, people) = setup(10);
+        context
+            .add_edge::<Contact>(people[0], people[1], 0.5, Contact(7))
+            .unwrap();
+        context
+            .generate_erdos_renyi::<Contact, _>(NetworkRng, 1.0)
+            .unwrap();
+        assert_eq!(edge_count(&context, &people), 90);
+        let edge = context.get_edge::<Contact>(people[0], people[1]).unwrap();
+        assert_eq!((edge.weight, edge.inner), (0.5, Contact(7)));
+    }
+
+    #[test]
+    fn skips_removed_people() {
+        let (mut context, people) = setup(5);
+        context.remove_person(people[2]).unwrap();
+        context
+            .generate_erdos_renyi::<Contact, _>(NetworkRng, 1.0)
+            .unwrap();
+        assert_eq!(edge_count(&context, &people), 12);
+    }
+
+    #[test]
+    fn deterministic() {
+        let edges = |seed| {
+            let (mut context, people) = setup(30);
+            context.init_random(seed);
+            context
+                .generate_erdos_renyi::<Contact, _>(NetworkRng, 0.2)
+                .unwrap();
+            people
+                .iter()
+                .flat_map(|person| context.get_edges::<Contact>(*person))
+                .map(|edge| (edge.person.0, edge.neighbor.0))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(edges(1), edges(1));
+        assert_ne!(edges(1), edges(2));
+    }
+
+    #[test]
+    fn invalid_probability() {
+        let (mut context, _) = setup(3);
+        for p in [-0.1, 1.5, f64::NAN] {
+            let result = context.generate_erdos_renyi::<Contact, _>(NetworkRng, p);
+            assert!(matches!(result, Err(IxaError::IxaError(_))));
+        }
+    }
+
+    #[test]
+    fn barabasi_albert_edge_counts() {
+        let (n, m) = (100, 3);
+        let (mut context, people) = setup(n);
+        context
+            .generate_barabasi_albert::<Contact, _>(NetworkRng, m)
+            .unwrap();
+        // A complete graph on the first m + 1 people, then m connections
+        // in each direction for each later person.
+        assert_eq!(
+            edge_count(&context, &people),
+            (m + 1) * m + 2 * m * (n - m - 1)
+        );
+        for (i, person) in people.iter().enumerate() {
+            let edges = context.get_edges::<Contact>(*person);
+            assert!(edges.len() >= m);
+            for edge in edges {
+                assert_eq!((edge.weight, edge.inner), (1.0, Contact(0)));
+                assert!(context
+                    .get_edge::<Contact>(edge.neighbor, *person)
+                    .is_some());
+            }
+            if i > m {
+                // Each person attaches to people who came before them.
+                let earlier = context
+                    .get_edges::<Contact>(*person)
+                    .iter()
+                    .filter(|edge| edge.neighbor.0 < person.0)
+                    .count();
+                assert_eq!(earlier, m);
+            }
+        }
+    }
+
+    #[test]
+    fn barabasi_albert_prefers_high_degree() {
+        let (mut context, people) = setup(2000);
+        context
+            .generate_barabasi_albert::<Contact, _>(NetworkRng, 2)
+            .unwrap();
+        let degrees: Vec<usize> = people
+            .iter()
+            .map(|person| context.get_edges::<Contact>(*person).len())
+            .collect();
+        let oldest = degrees[..100].iter().sum::<usize>();
+        let newest = degrees[degrees.len() - 100..].iter().sum::<usize>();
+        assert!(oldest > 3 * newest, "{oldest} vs {newest}");
+        assert!(*degrees.iter().max().unwrap() > 40);
+    }
+
+    #[test]
+    fn barabasi_albert_minimum_population() {
+        let (mut context, people) = setup(3);
+        context
+            .generate_barabasi_albert::<Contact, _>(NetworkRng, 2)
+            .unwrap();
+        assert_eq!(edge_count(&context, &people), 6);
+
+        let (mut context, _) = setup(2);
+        let result = context.generate_barabasi_albert::<Contact, _>(NetworkRng, 2);
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+        let result = context.generate_barabasi_albert::<Contact, _>(NetworkRng, 0);
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+    }
+
+    #[test]
+    fn barabasi_albert_deterministic() {
+        let edges = |seed| {
+            let (mut context, people) = setup(50);
+            context.init_random(seed);
+            context
+                .generate_barabasi_albert::<Contact, _>(NetworkRng, 2)
+                .unwrap();
+            people
+                .iter()
+                .flat_map(|person| context.get_edges::<Contact>(*person))
+                .map(|edge| (edge.person.0, edge.neighbor.0))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(edges(1), edges(1));
+        assert_ne!(edges(1), edges(2));
+    }
+}
@@ -0,0 +1,143 @@
+use super::{ContextNetworkExt, EdgeType};
+use crate::{Context, IxaError, PersonId};
+use csv::{ReaderBuilder, StringRecord};
+use std::path::Path;
+
+pub(super) fn load_network_from_csv<T: EdgeType + 'static>(
+    context: &mut Context,
+    path: &Path,
+    mapper: impl Fn(&StringRecord) -> Result<(PersonId, PersonId, f32, T::Value), IxaError>,
+) -> Result<(), IxaError> {
+    let mut reader = ReaderBuilder::new().from_path(path)?;
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let result = record
+            .map_err(IxaError::from)
+            .and_then(|record| mapper(&record))
+            .and_then(|(person, neighbor, weight, inner)| {
+                context.add_edge::<T>(person, neighbor, weight, inner)
+            });
+        if let Err(error) = result {
+            errors.push(error);
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(IxaError::MultipleErrors(errors))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use crate::network::ContextNetworkExt;
+    use crate::{define_edge_type, Context, ContextPeopleExt, IxaError, PersonId};
+    use csv::StringRecord;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    define_edge_type!(Contact, u8);
+
+    fn setup(n: usize) -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        let people = (0..n).map(|_| context.add_person(()).unwrap()).collect();
+        (context, people)
+    }
+
+    fn write_csv(contents: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    fn parse(
+        people: &[PersonId],
+        record: &StringRecord,
+    ) -> Result<(PersonId, PersonId, f32, u8), IxaError> {
+        let source: usize = record[0].parse()?;
+        let target: usize = record[1].parse()?;
+        let weight = record[2]
+            .parse()
+            .map_err(|_| IxaError::from(format!("Invalid weight {}", &record[2])))?;
+        let setting: u8 = record[3].parse()?;
+        let person = |index: usize| {
+            people
+                .get(index)
+                .copied()
+                .ok_or_else(|| IxaError::from(format!("No person {index}")))
+        };
+        Ok((person(source)?, person(target)?, weight, setting))
+    }
+
+    #[test]
+    fn loads_edges() {
+        let (mut context, people) = setup(3);
+        let file = write_csv("source,target,weight,setting\n0,1,0.5,2\n1,2,1.0,3\n2,1,1.0,3\n");
+
+        context
+            .load_network_from_csv::<Contact>(file.path(), |record| parse(&people, record))
+            .unwrap();
+
+        let edges = context.get_edges::<Contact>(people[1]);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].neighbor, people[2]);
+        assert_eq!(edges[0].inner, 3);
+        assert_eq!(context.get_edges::<Contact>(people[0])[0].weight, 0.5);
+        assert_eq!(context.get_edges::<Contact>(people[2]).len(), 1);
+    }
+
+    #[test]
+    fn collects_errors_from_all_rows() {
+        let (mut context, people) = setup(3);
+        let file = write_csv(
+            "source,target,weight,setting\n0,1,1.0,2\n0,x,1.0,2\n1,1,1.0,2\n1,2,1.0,2\n2,7,1.0,2\n",
+        );
+
+        let result =
+            context.load_network_from_csv::<Contact>(file.path(), |record| parse(&people, record));
+
+        match result {
+            Err(IxaError::MultipleErrors(errors)) => {
+                assert_eq!(errors.len(), 3);
+                assert!(matches!(errors[0], IxaError::ParseIntError(_)));
+                assert!(
+                    matches!(&errors[1], IxaError::IxaError(msg) if msg == "Cannot make edge to self")
+                );
+                assert!(matches!(&errors[2], IxaError::IxaError(msg) if msg == "No person 7"));
+            }
+            _ => panic!("Expected MultipleErrors"),
+        }
+        // The good rows are still loaded.
+        assert_eq!(context.get_edges::<Contact>(people[0]).len(), 1);
+        assert_eq!(context.get_edges::<Contact>(people[1]).len(), 1);
+    }
+
+    #[test]
+    fn malformed_rows_are_errors() {
+        let (mut context, people) = setup(2);
+        let file = write_csv("source,target,weight,setting\n0,1,1.0\n1,0,1.0,4\n");
+
+        let result =
+            context.load_network_from_csv::<Contact>(file.path(), |record| parse(&people, record));
+
+        match result {
+            Err(IxaError::MultipleErrors(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert!(matches!(errors[0], IxaError::CsvError(_)));
+            }
+            _ => panic!("Expected MultipleErrors"),
+        }
+        assert_eq!(context.get_edges::<Contact>(people[1]).len(), 1);
+    }
+
+    #[test]
+    fn missing_file() {
+        let (mut context, people) = setup(2);
+        let result = context.load_network_from_csv::<Contact>(
+            std::path::Path::new("./does/not/exist.csv"),
+            |record| parse(&people, record),
+        );
+        assert!(matches!(result, Err(IxaError::CsvError(_))));
+    }
+}
@@ -0,0 +1,586 @@
+//! Summary statistics of a network's structure, for checking that a
+//! loaded or generated network looks the way it should.
+//!
+//! The clustering coefficient and degree assortativity treat edges as
+//! undirected: two people are neighbors if there's an edge between them in
+//! either direction. Both build an undirected adjacency list, so they take
+//! memory proportional to the number of edges. Connected components also
+//! ignore the direction of edges. Path lengths follow edges in their
+//! direction and count edges rather than adding up weights.
+use super::{EdgeType, NetworkData, NetworkPlugin};
+use crate::{Context, ContextPeopleExt, ContextRandomExt, IxaError, PersonId, RngId};
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// An estimate of the mean shortest path length of a network from sampled
+/// pairs of people (see
+/// [`Context::sample_mean_path_length()`](super::ContextNetworkExt::sample_mean_path_length)).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PathLengthEstimate {
+    /// The mean number of edges on the shortest path between the sampled
+    /// pairs that are connected, or NaN if none of them are.
+    pub mean: f64,
+    /// The fraction of sampled pairs with no path between them.
+    pub unreachable_fraction: f64,
+}
+
+// The sorted, deduplicated undirected neighbors of each person, indexed by
+// `PersonId`.
+fn undirected_neighbors<T: EdgeType + 'static>(context: &Context) -> Vec<Vec<usize>> {
+    let people = context.query_people(());
+    let size = people.iter().map(|person| person.0 + 1).max().unwrap_or(0);
+    let mut neighbors = vec![Vec::new(); size];
+    if let Some(data) = context.get_data_container(NetworkPlugin) {
+        for person in people {
+            for edge in data.edges_ref::<T>(person).iter() {
+                neighbors[person.0].push(edge.neighbor.0);
+                neighbors[edge.neighbor.0].push(person.0);
+            }
+        }
+    }
+    for list in &mut neighbors {
+        list.sort_unstable();
+        list.dedup();
+    }
+    neighbors
+}
+
+// The number of people in both sorted lists
+fn common_count(a: &[usize], b: &[usize]) -> usize {
+    let (mut i, mut j, mut count) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                count += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    count
+}
+
+// The fraction of pairs of people in the sorted list `list` who are
+// neighbors of each other, given the number of those pairs.
+#[allow(clippy::cast_precision_loss)]
+fn fraction_linked(list: &[usize], links: usize) -> f64 {
+    let degree = list.len();
+    if degree < 2 {
+        return 0.0;
+    }
+    links as f64 / (degree * (degree - 1) / 2) as f64
+}
+
+pub(super) fn local_clustering_coefficient<T: EdgeType + 'static>(
+    context: &Context,
+    person: PersonId,
+) -> f64 {
+    let Some(data) = context.get_data_container(NetworkPlugin) else {
+        return 0.0;
+    };
+    let mut list: Vec<usize> = data
+        .edges_ref::<T>(person)
+        .iter()
+        .map(|edge| edge.neighbor.0)
+        .chain(
+            data.incoming_edges::<T>(person)
+                .iter()
+                .map(|edge| edge.person.0),
+        )
+        .collect();
+    list.sort_unstable();
+    list.dedup();
+    // Each link between two neighbors may be an edge in either direction
+    // or both, so collect the pairs rather than counting edges.
+    let mut linked = HashSet::new();
+    for &neighbor in &list {
+        for edge in data.edges_ref::<T>(PersonId(neighbor)).iter() {
+            let other = edge.neighbor.0;
+            if other != neighbor && list.binary_search(&other).is_ok() {
+                linked.insert((neighbor.min(other), neighbor.max(other)));
+            }
+        }
+    }
+    fraction_linked(&list, linked.len())
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub(super) fn global_clustering_coefficient<T: EdgeType + 'static>(context: &Context) -> f64 {
+    let population = context.get_current_population();
+    if population == 0 {
+        return 0.0;
+    }
+    let neighbors = undirected_neighbors::<T>(context);
+    let mut total = 0.0;
+    for list in &neighbors {
+        // Each link between two neighbors is counted from both ends.
+        let links: usize = list
+            .iter()
+            .map(|&neighbor| common_count(list, &neighbors[neighbor]))
+            .sum::<usize>()
+            / 2;
+        total += fraction_linked(list, links);
+    }
+    total / population as f64
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub(super) fn degree_assortativity<T: EdgeType + 'static>(context: &Context) -> f64 {
+    let neighbors = undirected_neighbors::<T>(context);
+    // Sums over both ends of each edge, so the result is symmetric.
+    let (mut ends, mut sum, mut sum_products, mut sum_squares) = (0.0, 0.0, 0.0, 0.0);
+    for list in &neighbors {
+        let degree = list.len() as f64;
+        for &neighbor in list {
+            let neighbor_degree = neighbors[neighbor].len() as f64;
+            ends += 1.0;
+            sum += degree;
+            sum_products += degree * neighbor_degree;
+            sum_squares += degree * degree;
+        }
+    }
+    let mean = sum / ends;
+    (sum_products / ends - mean * mean) / (sum_squares / ends - mean * mean)
+}
+
+// The number of edges on the shortest path from `from` to `to`, or `None`
+// if there isn't one. `visited` holds the search that last reached each
+// person, so that it doesn't have to be cleared between searches.
+fn hop_count<T: EdgeType + 'static>(
+    data: &NetworkData,
+    from: PersonId,
+    to: PersonId,
+    search: usize,
+    visited: &mut [usize],
+    queue: &mut VecDeque<(PersonId, usize)>,
+) -> Option<usize> {
+    queue.clear();
+    queue.push_back((from, 0));
+    visited[from.0] = search;
+    while let Some((person, distance)) = queue.pop_front() {
+        for edge in data.edges_ref::<T>(person).iter() {
+            if edge.neighbor == to {
+                return Some(distance + 1);
+            }
+            if visited[edge.neighbor.0] != search {
+                visited[edge.neighbor.0] = search;
+                queue.push_back((edge.neighbor, distance + 1));
+            }
+        }
+    }
+    None
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub(super) fn sample_mean_path_length<T: EdgeType + 'static, R: RngId + 'static>(
+    context: &Context,
+    rng_id: R,
+    n_pairs: usize,
+) -> Result<PathLengthEstimate, IxaError>
+where
+    R::RngType: Rng,
+{
+    let mut people = context.query_people(());
+    if people.len() < 2 {
+        return Err(IxaError::IxaError(String::from(
+            "Need at least two people to sample path lengths",
+        )));
+    }
+    if n_pairs == 0 {
+        return Err(IxaError::IxaError(String::from(
+            "Need at least one pair to sample path lengths",
+        )));
+    }
+    people.sort_by_key(|person_id| person_id.0);
+
+    let size = people[people.len() - 1].0 + 1;
+    let mut visited = vec![0; size];
+    let mut queue = VecDeque::new();
+    let (mut reachable, mut total_length) = (0, 0);
+    for search in 1..=n_pairs {
+        let from = context.sample_range(rng_id, 0..people.len());
+        let mut to = context.sample_range(rng_id, 0..people.len() - 1);
+        // Skip over `from` so that every other person is equally likely.
+        if to >= from {
+            to += 1;
+        }
+        let length = context.get_data_container(NetworkPlugin).and_then(|data| {
+            hop_count::<T>(
+                data,
+                people[from],
+                people[to],
+                search,
+                &mut visited,
+                &mut queue,
+            )
+        });
+        if let Some(length) = length {
+            reachable += 1;
+            total_length += length;
+        }
+    }
+
+    Ok(PathLengthEstimate {
+        mean: if reachable == 0 {
+            f64::NAN
+        } else {
+            total_length as f64 / reachable as f64
+        },
+        unreachable_fraction: (n_pairs - reachable) as f64 / n_pairs as f64,
+    })
+}
+
+// A union-find over everyone's ids, with an entry for each id up to the
+// largest, joined along edges of type `T` in either direction. Returns the
+// people, sorted by id, and the structure.
+fn union_find<T: EdgeType + 'static>(context: &Context) -> (Vec<PersonId>, Vec<usize>) {
+    let mut people = context.query_people(());
+    people.sort_by_key(|person_id| person_id.0);
+    let size = people.last().map_or(0, |person_id| person_id.0 + 1);
+    let mut parent: Vec<usize> = (0..size).collect();
+    let mut component_size = vec![1; size];
+    if let Some(data) = context.get_data_container(NetworkPlugin) {
+        for &person in &people {
+            for edge in data.edges_ref::<T>(person).iter() {
+                let a = find_root(&mut parent, person.0);
+                let b = find_root(&mut parent, edge.neighbor.0);
+                if a == b {
+                    continue;
+                }
+                // Attach the smaller tree to the larger one.
+                let (small, large) = if component_size[a] < component_size[b] {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                parent[small] = large;
+                component_size[large] += component_size[small];
+            }
+        }
+    }
+    (people, parent)
+}
+
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        // Path halving
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+pub(super) fn find_connected_components<T: EdgeType + 'static>(
+    context: &Context,
+) -> Vec<Vec<PersonId>> {
+    let (people, mut parent) = union_find::<T>(context);
+    // The position in `components` of each root's component
+    let mut positions = HashMap::new();
+    let mut components: Vec<Vec<PersonId>> = Vec::new();
+    for person in people {
+        let root = find_root(&mut parent, person.0);
+        let position = *positions.entry(root).or_insert_with(|| {
+            components.push(Vec::new());
+            components.len() - 1
+        });
+        components[position].push(person);
+    }
+    components
+}
+
+pub(super) fn count_connected_components<T: EdgeType + 'static>(context: &Context) -> usize {
+    let (people, mut parent) = union_find::<T>(context);
+    people
+        .iter()
+        .filter(|person| find_root(&mut parent, person.0) == person.0)
+        .count()
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use crate::network::{ContextNetworkExt, EdgeType};
+    use crate::{define_rng, Context, ContextPeopleExt, ContextRandomExt, IxaError, PersonId};
+
+    define_rng!(MetricsRng);
+
+    struct Contact;
+    impl EdgeType for Contact {
+        type Value = ();
+    }
+
+    fn setup(n: usize) -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        context.init_random(42);
+        let people = (0..n).map(|_| context.add_person(()).unwrap()).collect();
+        (context, people)
+    }
+
+    fn connect(context: &mut Context, person: PersonId, neighbor: PersonId) {
+        if context.get_edge::<Contact>(person, neighbor).is_none() {
+            context
+                .add_edge::<Contact>(person, neighbor, 1.0, ())
+                .unwrap();
+        }
+        if context.get_edge::<Contact>(neighbor, person).is_none() {
+            context
+                .add_edge::<Contact>(neighbor, person, 1.0, ())
+                .unwrap();
+        }
+    }
+
+    // People in a ring, each connected to the `k` nearest on either side.
+    fn ring_lattice(n: usize, k: usize) -> (Context, Vec<PersonId>) {
+        let (mut context, people) = setup(n);
+        for i in 0..n {
+            for j in 1..=k {
+                connect(&mut context, people[i], people[(i + j) % n]);
+            }
+        }
+        (context, people)
+    }
+
+    #[test]
+    fn clustering_coefficient_known_values() {
+        // A triangle of 0, 1 and 2 with 3 attached to 0: the coefficients
+        // are 1/3, 1, 1 and 0.
+        let (mut context, people) = setup(4);
+        for (a, b) in [(0, 1), (1, 2), (2, 0), (0, 3)] {
+            context
+                .add_edge::<Contact>(people[a], people[b], 1.0, ())
+                .unwrap();
+        }
+        let coefficient = context.global_clustering_coefficient::<Contact>();
+        assert!((coefficient - 7.0 / 12.0).abs() < 1e-12);
+
+        let (mut context, people) = setup(5);
+        for i in 0..5 {
+            for j in 0..i {
+                connect(&mut context, people[i], people[j]);
+            }
+        }
+        assert_eq!(context.global_clustering_coefficient::<Contact>(), 1.0);
+
+        let (mut context, people) = setup(5);
+        for leaf in &people[1..] {
+            connect(&mut context, people[0], *leaf);
+        }
+        assert_eq!(context.global_clustering_coefficient::<Contact>(), 0.0);
+        assert_eq!(
+            Context::new().global_clustering_coefficient::<Contact>(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn local_clustering_coefficient_known_values() {
+        // A triangle of 0, 1 and 2 with 3 attached to 0, with the edges in
+        // one direction only.
+        let (mut context, people) = setup(5);
+        for (a, b) in [(0, 1), (1, 2), (2, 0), (0, 3)] {
+            context
+                .add_edge::<Contact>(people[a], people[b], 1.0, ())
+                .unwrap();
+        }
+        let coefficients: Vec<f64> = people
+            .iter()
+            .map(|&person| context.local_clustering_coefficient::<Contact>(person))
+            .collect();
+        assert!((coefficients[0] - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(coefficients[1..], [1.0, 1.0, 0.0, 0.0]);
+
+        // Edges in both directions count once.
+        connect(&mut context, people[1], people[3]);
+        assert!(
+            (context.local_clustering_coefficient::<Contact>(people[0]) - 2.0 / 3.0).abs() < 1e-12
+        );
+
+        // The same with the reverse index
+        context.enable_reverse_edges::<Contact>();
+        assert!(
+            (context.local_clustering_coefficient::<Contact>(people[0]) - 2.0 / 3.0).abs() < 1e-12
+        );
+        assert_eq!(
+            context.local_clustering_coefficient::<Contact>(people[4]),
+            0.0
+        );
+    }
+
+    #[test]
+    fn global_clustering_coefficient_is_mean_of_local() {
+        let (mut context, people) = setup(30);
+        context
+            .generate_erdos_renyi::<Contact, _>(MetricsRng, 0.2)
+            .unwrap();
+        let mean = people
+            .iter()
+            .map(|&person| context.local_clustering_coefficient::<Contact>(person))
+            .sum::<f64>()
+            / 30.0;
+        assert!((context.global_clustering_coefficient::<Contact>() - mean).abs() < 1e-12);
+    }
+
+    #[test]
+    fn connected_components() {
+        let (mut context, people) = setup(7);
+        // 0 - 1 - 2 in one direction only, 3 <-> 5, and 4 and 6 alone.
+        context
+            .add_edge::<Contact>(people[1], people[0], 1.0, ())
+            .unwrap();
+        context
+            .add_edge::<Contact>(people[1], people[2], 1.0, ())
+            .unwrap();
+        connect(&mut context, people[5], people[3]);
+        assert_eq!(
+            context.find_connected_components::<Contact>(),
+            vec![
+                vec![people[0], people[1], people[2]],
+                vec![people[3], people[5]],
+                vec![people[4]],
+                vec![people[6]],
+            ]
+        );
+        assert_eq!(context.count_connected_components::<Contact>(), 4);
+
+        // Removed people aren't in any component.
+        context.remove_person(people[1]).unwrap();
+        context.remove_person(people[6]).unwrap();
+        assert_eq!(
+            context.find_connected_components::<Contact>(),
+            vec![
+                vec![people[0]],
+                vec![people[2]],
+                vec![people[3], people[5]],
+                vec![people[4]],
+            ]
+        );
+        assert_eq!(context.count_connected_components::<Contact>(), 4);
+
+        assert!(Context::new()
+            .find_connected_components::<Contact>()
+            .is_empty());
+        assert_eq!(Context::new().count_connected_components::<Contact>(), 0);
+    }
+
+    #[test]
+    fn ring_is_one_component() {
+        let (context, _) = ring_lattice(50, 1);
+        assert_eq!(context.count_connected_components::<Contact>(), 1);
+        assert_eq!(context.find_connected_components::<Contact>()[0].len(), 50);
+    }
+
+    #[test]
+    fn degree_assortativity_known_values() {
+        // A star is perfectly disassortative.
+        let (mut context, people) = setup(5);
+        for leaf in &people[1..] {
+            connect(&mut context, people[0], *leaf);
+        }
+        assert!((context.degree_assortativity::<Contact>() + 1.0).abs() < 1e-12);
+
+        // A path of four people has degrees 1, 2, 2, 1.
+        let (mut context, people) = setup(4);
+        for i in 0..3 {
+            connect(&mut context, people[i], people[i + 1]);
+        }
+        assert!((context.degree_assortativity::<Contact>() + 0.5).abs() < 1e-12);
+
+        // Undefined when everyone has the same degree.
+        let (context, _) = ring_lattice(10, 2);
+        assert!(context.degree_assortativity::<Contact>().is_nan());
+    }
+
+    #[test]
+    fn path_length_known_values() {
+        let (mut context, people) = setup(6);
+        for i in 0..6 {
+            for j in 0..i {
+                connect(&mut context, people[i], people[j]);
+            }
+        }
+        let estimate = context
+            .sample_mean_path_length::<Contact, _>(MetricsRng, 100)
+            .unwrap();
+        assert_eq!(estimate.mean, 1.0);
+        assert_eq!(estimate.unreachable_fraction, 0.0);
+
+        // Along a one-way path, the average over the reachable pairs is
+        // (3 * 1 + 2 * 2 + 1 * 3) / 6 and half the pairs are unreachable.
+        let (mut context, people) = setup(4);
+        for i in 0..3 {
+            context
+                .add_edge::<Contact>(people[i], people[i + 1], 1.0, ())
+                .unwrap();
+        }
+        let estimate = context
+            .sample_mean_path_length::<Contact, _>(MetricsRng, 20000)
+            .unwrap();
+        assert!((estimate.mean - 10.0 / 6.0).abs() < 0.05, "{estimate:?}");
+        assert!((estimate.unreachable_fraction - 0.5).abs() < 0.02);
+
+        let (mut context, _) = setup(3);
+        let estimate = context
+            .sample_mean_path_length::<Contact, _>(MetricsRng, 10)
+            .unwrap();
+        assert!(estimate.mean.is_nan());
+        assert_eq!(estimate.unreachable_fraction, 1.0);
+        assert!(matches!(
+            context.sample_mean_path_length::<Contact, _>(MetricsRng, 0),
+            Err(IxaError::IxaError(_))
+        ));
+        context.remove_person(PersonId(0)).unwrap();
+        context.remove_person(PersonId(1)).unwrap();
+        assert!(matches!(
+            context.sample_mean_path_length::<Contact, _>(MetricsRng, 10),
+            Err(IxaError::IxaError(_))
+        ));
+    }
+
+    #[test]
+    fn path_length_deterministic() {
+        let estimate = |seed| {
+            let (mut context, _) = ring_lattice(50, 1);
+            context.init_random(seed);
+            context
+                .sample_mean_path_length::<Contact, _>(MetricsRng, 10)
+                .unwrap()
+        };
+        assert_eq!(estimate(1), estimate(1));
+        assert_ne!(estimate(1), estimate(2));
+    }
+
+    #[test]
+    fn small_world() {
+        let (lattice, _) = ring_lattice(200, 2);
+        // The same lattice with a few random shortcuts
+        let (mut small_world, people) = ring_lattice(200, 2);
+        for _ in 0..20 {
+            let a = small_world.sample_range(MetricsRng, 0..people.len());
+            let b = small_world.sample_range(MetricsRng, 0..people.len());
+            if a != b {
+                connect(&mut small_world, people[a], people[b]);
+            }
+        }
+
+        // Each person in the lattice has 4 neighbors with 3 links among
+        // them.
+        let lattice_clustering = lattice.global_clustering_coefficient::<Contact>();
+        assert!((lattice_clustering - 0.5).abs() < 1e-12);
+        let small_world_clustering = small_world.global_clustering_coefficient::<Contact>();
+        assert!(small_world_clustering > 0.4);
+
+        let lattice_length = lattice
+            .sample_mean_path_length::<Contact, _>(MetricsRng, 500)
+            .unwrap();
+        let small_world_length = small_world
+            .sample_mean_path_length::<Contact, _>(MetricsRng, 500)
+            .unwrap();
+        assert_eq!(lattice_length.unreachable_fraction, 0.0);
+        assert!(
+            small_world_length.mean < 0.5 * lattice_length.mean,
+            "{small_world_length:?} vs {lattice_length:?}"
+        );
+    }
+}
@@ -0,0 +1,2548 @@
+//! A module for modeling contact networks.
+//!
+//! A network is modeled as a directed graph.  Edges are typed in the
+//! usual fashion, i.e., keyed by a Rust type, and each person can have an
+//! arbitrary number of outgoing edges of a given type, with each edge
+//! having a weight. Edge types can also specify their own per-type
+//! data which will be stored along with the edge.
+//!
+//! Symmetric relationships such as households can instead use an
+//! undirected edge type, defined with
+//! `define_edge_type!(HouseholdEdge, (), undirected)`, which stores each
+//! edge once for both people (see [`EdgeType::UNDIRECTED`]).
+//!
+//! When a person is removed with
+//! [`remove_person()`](crate::people::ContextPeopleExt::remove_person), the
+//! edges from and to them are removed when the
+//! [`PersonRemovedEvent`](crate::people::PersonRemovedEvent) is handled,
+//! with an [`EdgeRemovedEvent`] for each one.
+//!
+//! Edges are stored with the person they come from, so finding the edges
+//! *to* a person with [`Context::get_incoming_edges()`] means looking at
+//! everyone's edges unless [`Context::enable_reverse_edges()`] has been
+//! called for that edge type.
+//!
+//! [`Context::shortest_path()`] finds the path between two people with the
+//! lowest total edge weight, and [`Context::random_walk()`] follows a random
+//! path from a person, choosing edges by weight.
+//!
+//! [`Context::global_clustering_coefficient()`],
+//! [`Context::sample_mean_path_length()`] and
+//! [`Context::degree_assortativity()`] summarize the structure of a
+//! network, e.g., to check one that was just loaded or generated, and
+//! [`Context::find_connected_components()`] shows whether it's connected.
+//! [`Context::closeness_centrality()`] and
+//! [`Context::betweenness_centrality()`] pick out the people who are best
+//! placed to spread something through the network.
+//!
+//! To follow how a network changes over a simulation, use
+//! [`Context::add_network_periodic_report()`] to write the number of
+//! edges and the distribution of degrees periodically. To analyze the
+//! final network with another tool, write it to a CSV edge list with
+//! [`Context::export_network_csv()`]. To load an existing network, e.g., a
+//! synthetic contact network, from an edge list, use
+//! [`Context::load_network_from_csv()`].
+use crate::{
+    context::Context,
+    context::ExecutionPhase,
+    context::IxaEvent,
+    define_data_plugin,
+    error::IxaError,
+    people::{ContextPeopleExt, PersonId, PersonRemovedEvent},
+    random::ContextRandomExt,
+    random::RngId,
+    report::{ContextReportExt, Report, ReportFile},
+    trace,
+};
+use csv::StringRecord;
+use csv::Writer;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::{
+    any::{Any, TypeId},
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    marker::PhantomData,
+    path::Path,
+};
+
+mod centrality;
+mod export;
+mod generators;
+mod import;
+mod metrics;
+mod random_walk;
+mod shortest_path;
+
+pub use metrics::PathLengthEstimate;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// An edge in network graph. Edges are directed, so the
+/// source person is implicit, unless the edge type is undirected (see
+/// [`EdgeType::UNDIRECTED`]).
+pub struct Edge<T: Sized> {
+    /// The person this edge comes from.
+    pub person: PersonId,
+    /// The person this edge points to.
+    pub neighbor: PersonId,
+    /// The weight associated with the edge.
+    pub weight: f32,
+    /// An inner value defined by type `T`.
+    pub inner: T,
+}
+
+pub trait EdgeType {
+    type Value: Sized + Default + Copy;
+
+    /// Whether edges of this type are undirected, i.e., connect two people
+    /// both ways, as in a household. An undirected edge is stored once, so
+    /// it takes much less memory than a pair of directed edges and its two
+    /// directions can't have different weights. It's returned by
+    /// [`Context::get_edges()`] for both people, with `person` set to the
+    /// person whose edges were asked for, and removing it from either end
+    /// removes it. [`Context::get_edge()`] returns it for either order of
+    /// the two people, with `person` set to the one with the lower id.
+    ///
+    /// Use `define_edge_type!(Name, Value, undirected)` to define an
+    /// undirected edge type.
+    const UNDIRECTED: bool = false;
+}
+
+// The two people in order of id, as they're stored in an undirected edge.
+fn ordered(person: PersonId, neighbor: PersonId) -> (PersonId, PersonId) {
+    if person.0 < neighbor.0 {
+        (person, neighbor)
+    } else {
+        (neighbor, person)
+    }
+}
+
+// The edges of an undirected edge type, each stored once with `person`
+// the one of the two with the lower id.
+struct UndirectedEdges<V> {
+    edges: Vec<Edge<V>>,
+    // The positions in `edges` of the edges of each person, indexed by
+    // person.
+    slots: Vec<Vec<u32>>,
+}
+
+impl<V: Copy> UndirectedEdges<V> {
+    fn slots(&self, person: PersonId) -> &[u32] {
+        self.slots.get(person.0).map_or(&[], Vec::as_slice)
+    }
+
+    fn find(&self, person: PersonId, neighbor: PersonId) -> Option<u32> {
+        self.slots(person).iter().copied().find(|&slot| {
+            let edge = &self.edges[slot as usize];
+            (edge.person, edge.neighbor) == ordered(person, neighbor)
+        })
+    }
+
+    fn add(&mut self, person: PersonId, neighbor: PersonId, weight: f32, inner: V) {
+        let slot = u32::try_from(self.edges.len()).expect("Too many undirected edges");
+        let (low, high) = ordered(person, neighbor);
+        self.edges.push(Edge {
+            person: low,
+            neighbor: high,
+            weight,
+            inner,
+        });
+        if high.0 >= self.slots.len() {
+            self.slots.resize_with(high.0 + 1, Vec::new);
+        }
+        self.slots[low.0].push(slot);
+        self.slots[high.0].push(slot);
+    }
+
+    fn remove(&mut self, slot: u32) -> Edge<V> {
+        let edge = self.edges.swap_remove(slot as usize);
+        for end in [edge.person, edge.neighbor] {
+            let slots = &mut self.slots[end.0];
+            let index = slots.iter().position(|&s| s == slot).unwrap();
+            slots.swap_remove(index);
+        }
+        // The last edge took the place of the removed one.
+        if let Some(moved) = self.edges.get(slot as usize) {
+            let last = u32::try_from(self.edges.len()).unwrap();
+            for end in [moved.person, moved.neighbor] {
+                for s in &mut self.slots[end.0] {
+                    if *s == last {
+                        *s = slot;
+                    }
+                }
+            }
+        }
+        edge
+    }
+}
+
+#[derive(Default)]
+struct PersonNetwork {
+    // A vector of vectors of NetworkEdge, indexed by edge type.
+    neighbors: HashMap<TypeId, Box<dyn Any>>,
+}
+
+type RemovePersonEdgesFn = fn(&mut Context, PersonId);
+
+struct NetworkData {
+    network: Vec<PersonNetwork>,
+    // For each edge type that has been used, its name and a function that
+    // removes the edges of that type from and to a person.
+    edge_types: HashMap<TypeId, (&'static str, RemovePersonEdgesFn)>,
+    // For each edge type with reverse edges enabled, the people with an
+    // edge to each person, indexed by that person.
+    reverse_edges: HashMap<TypeId, Vec<Vec<PersonId>>>,
+    // The total number of edges of each type
+    edge_counts: HashMap<TypeId, usize>,
+    // The edges of each undirected edge type, which aren't stored in
+    // `network`
+    undirected_edges: HashMap<TypeId, Box<dyn Any>>,
+    subscribed_to_removals: bool,
+}
+
+impl NetworkData {
+    fn new() -> Self {
+        NetworkData {
+            network: Vec::new(),
+            edge_types: HashMap::new(),
+            reverse_edges: HashMap::new(),
+            edge_counts: HashMap::new(),
+            undirected_edges: HashMap::new(),
+            subscribed_to_removals: false,
+        }
+    }
+
+    fn undirected<T: EdgeType + 'static>(&self) -> Option<&UndirectedEdges<T::Value>> {
+        self.undirected_edges
+            .get(&TypeId::of::<T>())
+            .map(|edges| edges.downcast_ref().expect("Type mismatch"))
+    }
+
+    fn undirected_mut<T: EdgeType + 'static>(&mut self) -> &mut UndirectedEdges<T::Value> {
+        self.undirected_edges
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                Box::new(UndirectedEdges::<T::Value> {
+                    edges: Vec::new(),
+                    slots: Vec::new(),
+                })
+            })
+            .downcast_mut()
+            .expect("Type mismatch")
+    }
+
+    // The number of edges of type T from `person`
+    fn degree<T: EdgeType + 'static>(&self, person: PersonId) -> usize {
+        if T::UNDIRECTED {
+            self.undirected::<T>()
+                .map_or(0, |undirected| undirected.slots(person).len())
+        } else {
+            self.edges_ref::<T>(person).len()
+        }
+    }
+
+    fn add_edge<T: EdgeType + 'static>(
+        &mut self,
+        person: PersonId,
+        neighbor: PersonId,
+        weight: f32,
+        inner: T::Value,
+    ) -> Result<(), IxaError> {
+        if person == neighbor {
+            return Err(IxaError::IxaError(String::from("Cannot make edge to self")));
+        }
+
+        if weight.is_infinite() || weight.is_nan() || weight.is_sign_negative() {
+            return Err(IxaError::IxaError(String::from("Invalid weight")));
+        }
+
+        if T::UNDIRECTED {
+            return self.add_undirected_edge::<T>(person, neighbor, weight, inner);
+        }
+
+        // Make sure we have data for this person.
+        if person.0 >= self.network.len() {
+            self.network.resize_with(person.0 + 1, Default::default);
+        }
+
+        let entry = self.network[person.0]
+            .neighbors
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<Edge<T::Value>>::new()));
+        let edges: &mut Vec<Edge<T::Value>> = entry.downcast_mut().expect("Type mismatch");
+
+        for edge in edges.iter_mut() {
+            if edge.neighbor == neighbor {
+                return Err(IxaError::IxaError(String::from("Edge already exists")));
+            }
+        }
+
+        edges.push(Edge {
+            person,
+            neighbor,
+            weight,
+            inner,
+        });
+        if let Some(reverse) = self.reverse_edges.get_mut(&TypeId::of::<T>()) {
+            if neighbor.0 >= reverse.len() {
+                reverse.resize_with(neighbor.0 + 1, Vec::new);
+            }
+            reverse[neighbor.0].push(person);
+        }
+        *self.edge_counts.entry(TypeId::of::<T>()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    fn add_undirected_edge<T: EdgeType + 'static>(
+        &mut self,
+        person: PersonId,
+        neighbor: PersonId,
+        weight: f32,
+        inner: T::Value,
+    ) -> Result<(), IxaError> {
+        let undirected = self.undirected_mut::<T>();
+        if undirected.find(person, neighbor).is_some() {
+            return Err(IxaError::IxaError(String::from("Edge already exists")));
+        }
+        undirected.add(person, neighbor, weight, inner);
+        *self.edge_counts.entry(TypeId::of::<T>()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    fn remove_undirected_edge<T: EdgeType + 'static>(
+        &mut self,
+        person: PersonId,
+        neighbor: PersonId,
+    ) -> Result<Edge<T::Value>, IxaError> {
+        let undirected = self.undirected_mut::<T>();
+        let Some(slot) = undirected.find(person, neighbor) else {
+            return Err(IxaError::IxaError(String::from("Edge does not exist")));
+        };
+        let edge = undirected.remove(slot);
+        self.uncount_edges::<T>(1);
+        Ok(edge)
+    }
+
+    fn uncount_edges<T: EdgeType + 'static>(&mut self, removed: usize) {
+        if let Some(count) = self.edge_counts.get_mut(&TypeId::of::<T>()) {
+            *count -= removed;
+        }
+    }
+
+    fn count_edges_total<T: EdgeType + 'static>(&self) -> usize {
+        self.edge_counts
+            .get(&TypeId::of::<T>())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // Forget that `person` has an edge of type T to `neighbor` in the
+    // reverse index, if there is one.
+    fn unlink_reverse_edge<T: EdgeType + 'static>(&mut self, person: PersonId, neighbor: PersonId) {
+        let Some(sources) = self
+            .reverse_edges
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|reverse| reverse.get_mut(neighbor.0))
+        else {
+            return;
+        };
+        if let Some(index) = sources.iter().position(|&source| source == person) {
+            sources.swap_remove(index);
+        }
+    }
+
+    fn remove_edge<T: EdgeType + 'static>(
+        &mut self,
+        person: PersonId,
+        neighbor: PersonId,
+    ) -> Result<Edge<T::Value>, IxaError> {
+        if T::UNDIRECTED {
+            return self.remove_undirected_edge::<T>(person, neighbor);
+        }
+        if person.0 >= self.network.len() {
+            return Err(IxaError::IxaError(String::from("Edge does not exist")));
+        }
+
+        let Some(entry) = self.network[person.0].neighbors.get_mut(&TypeId::of::<T>()) else {
+            return Err(IxaError::IxaError(String::from("Edge does not exist")));
+        };
+
+        let edges: &mut Vec<Edge<T::Value>> = entry.downcast_mut().expect("Type mismatch");
+        for index in 0..edges.len() {
+            if edges[index].neighbor == neighbor {
+                let edge = edges.remove(index);
+                self.unlink_reverse_edge::<T>(person, neighbor);
+                self.uncount_edges::<T>(1);
+                return Ok(edge);
+            }
+        }
+
+        Err(IxaError::IxaError(String::from("Edge does not exist")))
+    }
+
+    fn get_edge<T: EdgeType + 'static>(
+        &self,
+        person: PersonId,
+        neighbor: PersonId,
+    ) -> Option<&Edge<T::Value>> {
+        if T::UNDIRECTED {
+            let undirected = self.undirected::<T>()?;
+            let slot = undirected.find(person, neighbor)?;
+            return Some(&undirected.edges[slot as usize]);
+        }
+        if person.0 >= self.network.len() {
+            return None;
+        }
+
+        let entry = self.network[person.0].neighbors.get(&TypeId::of::<T>())?;
+        let edges: &Vec<Edge<T::Value>> = entry.downcast_ref().expect("Type mismatch");
+        edges.iter().find(|&edge| edge.neighbor == neighbor)
+    }
+
+    fn get_edges<T: EdgeType + 'static>(&self, person: PersonId) -> Vec<Edge<T::Value>> {
+        self.edges_ref::<T>(person).into_owned()
+    }
+
+    // Like get_edges() but without copying the edges, unless T is
+    // undirected, in which case they're copied with `person` first.
+    fn edges_ref<T: EdgeType + 'static>(&self, person: PersonId) -> Cow<'_, [Edge<T::Value>]> {
+        if T::UNDIRECTED {
+            let Some(undirected) = self.undirected::<T>() else {
+                return Cow::Borrowed(&[]);
+            };
+            return Cow::Owned(
+                undirected
+                    .slots(person)
+                    .iter()
+                    .map(|&slot| {
+                        let edge = undirected.edges[slot as usize];
+                        if edge.person == person {
+                            edge
+                        } else {
+                            Edge {
+                                person,
+                                neighbor: edge.person,
+                                ..edge
+                            }
+                        }
+                    })
+                    .collect(),
+            );
+        }
+        match self
+            .network
+            .get(person.0)
+            .and_then(|entry| entry.neighbors.get(&TypeId::of::<T>()))
+        {
+            None => Cow::Borrowed(&[]),
+            Some(edges) => Cow::Borrowed(
+                edges
+                    .downcast_ref::<Vec<Edge<T::Value>>>()
+                    .expect("Type mismatch"),
+            ),
+        }
+    }
+
+    // Remove all edges of type T from and to `person`, returning them.
+    fn remove_person_edges<T: EdgeType + 'static>(
+        &mut self,
+        person: PersonId,
+    ) -> Vec<Edge<T::Value>> {
+        let mut removed = Vec::new();
+        if T::UNDIRECTED {
+            let undirected = self.undirected_mut::<T>();
+            while let Some(&slot) = undirected.slots(person).first() {
+                removed.push(undirected.remove(slot));
+            }
+            self.uncount_edges::<T>(removed.len());
+            return removed;
+        }
+        if let Some(entry) = self
+            .network
+            .get_mut(person.0)
+            .and_then(|entry| entry.neighbors.remove(&TypeId::of::<T>()))
+        {
+            let edges: Box<Vec<Edge<T::Value>>> = entry.downcast().expect("Type mismatch");
+            for edge in edges.iter() {
+                self.unlink_reverse_edge::<T>(person, edge.neighbor);
+            }
+            removed.extend(*edges);
+        }
+
+        // Without a reverse index, finding the incoming edges means looking
+        // at everyone's edges.
+        let sources = match self.reverse_edges.get_mut(&TypeId::of::<T>()) {
+            Some(reverse) => {
+                let mut sources = reverse
+                    .get_mut(person.0)
+                    .map(std::mem::take)
+                    .unwrap_or_default();
+                sources.sort_unstable_by_key(|source| source.0);
+                sources
+            }
+            None => (0..self.network.len()).map(PersonId).collect(),
+        };
+        for source in sources {
+            let Some(edges) = self.network[source.0].neighbors.get_mut(&TypeId::of::<T>()) else {
+                continue;
+            };
+            let edges: &mut Vec<Edge<T::Value>> = edges.downcast_mut().expect("Type mismatch");
+            if let Some(index) = edges.iter().position(|edge| edge.neighbor == person) {
+                removed.push(edges.remove(index));
+            }
+        }
+        self.uncount_edges::<T>(removed.len());
+        removed
+    }
+
+    fn enable_reverse_edges<T: EdgeType + 'static>(&mut self) {
+        // The edges to a person are already stored with them.
+        if T::UNDIRECTED || self.reverse_edges.contains_key(&TypeId::of::<T>()) {
+            return;
+        }
+        let mut reverse: Vec<Vec<PersonId>> = Vec::new();
+        for person_id in 0..self.network.len() {
+            for edge in self.edges_ref::<T>(PersonId(person_id)).iter() {
+                if edge.neighbor.0 >= reverse.len() {
+                    reverse.resize_with(edge.neighbor.0 + 1, Vec::new);
+                }
+                reverse[edge.neighbor.0].push(edge.person);
+            }
+        }
+        self.reverse_edges.insert(TypeId::of::<T>(), reverse);
+    }
+
+    // The edges of type T to `person`, ordered by the person they come
+    // from.
+    fn incoming_edges<T: EdgeType + 'static>(&self, person: PersonId) -> Vec<Edge<T::Value>> {
+        if T::UNDIRECTED {
+            let mut edges: Vec<Edge<T::Value>> = self
+                .edges_ref::<T>(person)
+                .iter()
+                .map(|edge| Edge {
+                    person: edge.neighbor,
+                    neighbor: person,
+                    ..*edge
+                })
+                .collect();
+            edges.sort_unstable_by_key(|edge| edge.person.0);
+            return edges;
+        }
+        match self.reverse_edges.get(&TypeId::of::<T>()) {
+            Some(reverse) => {
+                let mut edges: Vec<Edge<T::Value>> = reverse
+                    .get(person.0)
+                    .map_or(&[][..], Vec::as_slice)
+                    .iter()
+                    .map(|&source| {
+                        *self
+                            .get_edge::<T>(source, person)
+                            .expect("Reverse edge index out of date")
+                    })
+                    .collect();
+                edges.sort_unstable_by_key(|edge| edge.person.0);
+                edges
+            }
+            None => (0..self.network.len())
+                .filter_map(|source| {
+                    self.edges_ref::<T>(PersonId(source))
+                        .iter()
+                        .find(|edge| edge.neighbor == person)
+                        .copied()
+                })
+                .collect(),
+        }
+    }
+
+    fn find_people_by_degree<T: EdgeType + 'static>(&self, degree: usize) -> Vec<PersonId> {
+        let mut result = Vec::new();
+
+        if T::UNDIRECTED {
+            if let Some(undirected) = self.undirected::<T>() {
+                for (person_id, slots) in undirected.slots.iter().enumerate() {
+                    if slots.len() == degree {
+                        result.push(PersonId(person_id));
+                    }
+                }
+            }
+            return result;
+        }
+
+        for person_id in 0..self.network.len() {
+            if !self.network[person_id]
+                .neighbors
+                .contains_key(&TypeId::of::<T>())
+            {
+                continue;
+            }
+            if self.degree::<T>(PersonId(person_id)) == degree {
+                result.push(PersonId(person_id));
+            }
+        }
+        result
+    }
+}
+
+/// Define a new edge type for use with `network`.
+///
+/// Defines a new edge type of type `$edge_type`, with inner type `$value`.
+/// Use `()` for `$value` to have no inner type. Add `undirected` after the
+/// inner type, as in `define_edge_type!(HouseholdEdge, (), undirected)`,
+/// for an undirected edge type (see [`EdgeType::UNDIRECTED`]).
+#[allow(unused_macros)]
+#[macro_export]
+macro_rules! define_edge_type {
+    ($edge_type:ident, $value:ty) => {
+        #[derive(Debug, Copy, Clone)]
+        pub struct $edge_type;
+
+        impl $crate::network::EdgeType for $edge_type {
+            type Value = $value;
+        }
+    };
+    ($edge_type:ident, $value:ty, undirected) => {
+        #[derive(Debug, Copy, Clone)]
+        pub struct $edge_type;
+
+        impl $crate::network::EdgeType for $edge_type {
+            type Value = $value;
+            const UNDIRECTED: bool = true;
+        }
+    };
+}
+
+define_data_plugin!(NetworkPlugin, NetworkData, NetworkData::new());
+
+fn remove_person_edges<T: EdgeType + 'static>(context: &mut Context, person: PersonId) {
+    let removed = context
+        .get_data_container_mut(NetworkPlugin)
+        .remove_person_edges::<T>(person);
+    for edge in removed {
+        context.emit_event(EdgeRemovedEvent::<T> { edge });
+    }
+}
+
+// Makes sure that the edges of type T are removed along with people.
+fn track_edge_type<T: EdgeType + 'static>(context: &mut Context) {
+    let data_container = context.get_data_container_mut(NetworkPlugin);
+    data_container
+        .edge_types
+        .entry(TypeId::of::<T>())
+        .or_insert((std::any::type_name::<T>(), remove_person_edges::<T>));
+    if !data_container.subscribed_to_removals {
+        data_container.subscribed_to_removals = true;
+        context.subscribe_to_event(handle_person_removed);
+    }
+}
+
+pub(crate) fn plugin_type_id() -> TypeId {
+    TypeId::of::<NetworkPlugin>()
+}
+
+/// Returns the type ids and names of the edge types that have been used
+pub(crate) fn edge_types(context: &Context) -> Vec<(TypeId, &'static str)> {
+    context
+        .get_data_container(NetworkPlugin)
+        .map(|data_container| {
+            data_container
+                .edge_types
+                .iter()
+                .map(|(type_id, (name, _))| (*type_id, *name))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Serializes the edges of type T in order of the person they come from,
+/// or returns `None` if edges of type T have never been added.
+pub(crate) fn save_edges<T: EdgeType + 'static>(
+    context: &Context,
+) -> Result<Option<Vec<u8>>, IxaError>
+where
+    T::Value: Serialize,
+{
+    let Some(data_container) = context.get_data_container(NetworkPlugin) else {
+        return Ok(None);
+    };
+    if !data_container.edge_types.contains_key(&TypeId::of::<T>()) {
+        return Ok(None);
+    }
+    let people = if T::UNDIRECTED {
+        data_container
+            .undirected::<T>()
+            .map_or(0, |undirected| undirected.slots.len())
+    } else {
+        data_container.network.len()
+    };
+    // Each undirected edge is saved once, from the person with the lower id.
+    let edges: Vec<Edge<T::Value>> = (0..people)
+        .flat_map(|person| data_container.edges_ref::<T>(PersonId(person)).into_owned())
+        .filter(|edge| !T::UNDIRECTED || edge.person.0 < edge.neighbor.0)
+        .collect();
+    Ok(Some(bincode::serialize(&edges)?))
+}
+
+/// Restores the edges saved with `save_edges()`, without emitting events.
+/// The people must have been restored first.
+pub(crate) fn restore_edges<T: EdgeType + 'static>(
+    context: &mut Context,
+    data: &[u8],
+) -> Result<(), IxaError>
+where
+    T::Value: DeserializeOwned,
+{
+    let edges: Vec<Edge<T::Value>> = bincode::deserialize(data)?;
+    let data_container = context.get_data_container_mut(NetworkPlugin);
+    for edge in edges {
+        data_container.add_edge::<T>(edge.person, edge.neighbor, edge.weight, edge.inner)?;
+    }
+    track_edge_type::<T>(context);
+    Ok(())
+}
+
+fn handle_person_removed(context: &mut Context, event: PersonRemovedEvent) {
+    let mut edge_types = context
+        .get_data_container(NetworkPlugin)
+        .unwrap()
+        .edge_types
+        .iter()
+        .map(|(type_id, (_, remove))| (*type_id, *remove))
+        .collect::<Vec<_>>();
+    // Emit the events in the same order every run.
+    edge_types.sort_by_key(|(type_id, _)| *type_id);
+    for (_, remove) in edge_types {
+        remove(context, event.person_id);
+    }
+}
+
+// Visits the people reachable from `start` over edges of type T in
+// breadth-first or depth-first order. The edges of each person are read
+// when they are visited, so the visitor can change the network.
+fn traverse<T: EdgeType + 'static>(
+    context: &mut Context,
+    start: PersonId,
+    max_depth: Option<usize>,
+    depth_first: bool,
+    mut visitor: impl FnMut(&mut Context, PersonId) -> bool,
+) {
+    let mut visited = HashSet::new();
+    let mut pending = VecDeque::from([(start, 0)]);
+    while let Some((person, depth)) = if depth_first {
+        pending.pop_back()
+    } else {
+        pending.pop_front()
+    } {
+        if !visited.insert(person) {
+            continue;
+        }
+        if !visitor(context, person) {
+            return;
+        }
+        if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            continue;
+        }
+        let Some(data_container) = context.get_data_container(NetworkPlugin) else {
+            continue;
+        };
+        let edges = data_container.edges_ref::<T>(person);
+        let neighbors = edges
+            .iter()
+            .map(|edge| edge.neighbor)
+            .filter(|neighbor| !visited.contains(neighbor));
+        if depth_first {
+            // Reversed so that neighbors are visited in the order of the edges
+            let neighbors: Vec<_> = neighbors.collect();
+            pending.extend(
+                neighbors
+                    .into_iter()
+                    .rev()
+                    .map(|neighbor| (neighbor, depth + 1)),
+            );
+        } else {
+            pending.extend(neighbors.map(|neighbor| (neighbor, depth + 1)));
+        }
+    }
+}
+
+/// Emitted when an edge of type `T` is added
+/// A row of a network report (see
+/// [`Context::add_network_periodic_report()`]). The degree of a person is
+/// their number of outgoing edges of type `T`, and the quantiles are over
+/// everyone in the population, including people with no edges.
+#[derive(Serialize)]
+#[serde(bound = "")]
+struct NetworkStatsReport<T: EdgeType> {
+    t: f64,
+    edge_type: &'static str,
+    edges: usize,
+    mean_degree: f64,
+    min_degree: usize,
+    q25_degree: usize,
+    median_degree: usize,
+    q75_degree: usize,
+    max_degree: usize,
+    #[serde(skip)]
+    edge_type_marker: PhantomData<T>,
+}
+
+impl<T: EdgeType + 'static> Report for NetworkStatsReport<T> {
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<NetworkStatsReport<T>>()
+    }
+
+    fn serialize(&self, writer: &mut Writer<ReportFile>) {
+        writer.serialize(self).unwrap();
+    }
+}
+
+// The nearest-rank quantile `numerator / denominator` of `sorted`, which
+// must not be empty.
+fn nearest_rank(sorted: &[usize], numerator: usize, denominator: usize) -> usize {
+    let rank = (sorted.len() * numerator).div_ceil(denominator);
+    sorted[rank.max(1) - 1]
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn network_stats<T: EdgeType + 'static>(context: &Context) -> NetworkStatsReport<T> {
+    let mut degrees: Vec<usize> = match context.get_data_container(NetworkPlugin) {
+        None => vec![0; context.get_current_population()],
+        Some(data_container) => context
+            .query_people(())
+            .into_iter()
+            .map(|person| data_container.degree::<T>(person))
+            .collect(),
+    };
+    degrees.sort_unstable();
+
+    let degree_sum: usize = degrees.iter().sum();
+    // An undirected edge counts towards the degrees of both people.
+    let edges = if T::UNDIRECTED {
+        degree_sum / 2
+    } else {
+        degree_sum
+    };
+    let (mean_degree, min_degree, q25_degree, median_degree, q75_degree, max_degree) =
+        if degrees.is_empty() {
+            (0.0, 0, 0, 0, 0, 0)
+        } else {
+            (
+                degree_sum as f64 / degrees.len() as f64,
+                degrees[0],
+                nearest_rank(&degrees, 1, 4),
+                nearest_rank(&degrees, 1, 2),
+                nearest_rank(&degrees, 3, 4),
+                degrees[degrees.len() - 1],
+            )
+        };
+
+    let type_name = std::any::type_name::<T>();
+    NetworkStatsReport {
+        t: context.get_current_time(),
+        edge_type: type_name.rsplit("::").next().unwrap_or(type_name),
+        edges,
+        mean_degree,
+        min_degree,
+        q25_degree,
+        median_degree,
+        q75_degree,
+        max_degree,
+        edge_type_marker: PhantomData,
+    }
+}
+
+pub struct EdgeAddedEvent<T: EdgeType> {
+    /// The new edge
+    pub edge: Edge<T::Value>,
+}
+
+/// Emitted when an edge of type `T` is removed
+pub struct EdgeRemovedEvent<T: EdgeType> {
+    /// The edge that was removed
+    pub edge: Edge<T::Value>,
+}
+
+// These are implemented by hand because deriving them would require
+// `T: Copy`.
+impl<T: EdgeType> Clone for EdgeAddedEvent<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T: EdgeType> Copy for EdgeAddedEvent<T> {}
+impl<T: EdgeType> IxaEvent for EdgeAddedEvent<T> {}
+
+impl<T: EdgeType> Clone for EdgeRemovedEvent<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T: EdgeType> Copy for EdgeRemovedEvent<T> {}
+impl<T: EdgeType> IxaEvent for EdgeRemovedEvent<T> {}
+
+pub trait ContextNetworkExt {
+    /// Add an edge of type `T` between `person` and `neighbor` with a
+    /// given `weight`.  `inner` is a value of whatever type is
+    /// associated with `T`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IxaError` if:
+    ///
+    /// * `person` and `neighbor` are the same or an edge already
+    ///   exists between them.
+    /// * `weight` is invalid
+    /// * either person doesn't exist or has been removed
+    fn add_edge<T: EdgeType + 'static>(
+        &mut self,
+        person: PersonId,
+        neighbor: PersonId,
+        weight: f32,
+        inner: T::Value,
+    ) -> Result<(), IxaError>;
+
+    /// Add a pair of edges of type `T` between `person1` and
+    /// `neighbor2` with a given `weight`, one edge in each
+    /// direction. `inner` is a value of whatever type is associated
+    /// with `T`. This is syntactic sugar for calling `add_edge()`
+    /// twice, or once if `T` is undirected.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IxaError` if:
+    ///
+    /// * `person` and `neighbor` are the same or an edge already
+    ///   exists between them.
+    /// * `weight` is invalid
+    /// * either person doesn't exist or has been removed
+    fn add_edge_bidi<T: EdgeType + 'static>(
+        &mut self,
+        person1: PersonId,
+        person2: PersonId,
+        weight: f32,
+        inner: T::Value,
+    ) -> Result<(), IxaError>;
+
+    /// Remove an edge of type `T` between `person` and `neighbor`
+    /// if one exists.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if no edge exists.
+    fn remove_edge<T: EdgeType + 'static>(
+        &mut self,
+        person: PersonId,
+        neighbor: PersonId,
+    ) -> Result<(), IxaError>;
+
+    /// Get an edge of type `T` between `person` and `neighbor`
+    /// if one exists.
+    fn get_edge<T: EdgeType + 'static>(
+        &self,
+        person: PersonId,
+        neighbor: PersonId,
+    ) -> Option<&Edge<T::Value>>;
+
+    /// Get all edges of type `T` from `person`.
+    fn get_edges<T: EdgeType + 'static>(&self, person: PersonId) -> Vec<Edge<T::Value>>;
+
+    /// Get all edges of type `T` from `person` that match the predicate
+    /// provided in `filter`. Note that because `filter` has access to
+    /// both the edge, which contains the neighbor and `Context`, it is
+    /// possible to filter on properties of the neighbor. The function
+    /// `context.matching_person()` might be helpful here.
+    ///
+    fn get_matching_edges<T: EdgeType + 'static>(
+        &self,
+        person: PersonId,
+        filter: impl Fn(&Context, &Edge<T::Value>) -> bool + 'static,
+    ) -> Vec<Edge<T::Value>>;
+
+    /// Get all edges of type `T` to `person`, i.e., the edges of the
+    /// people who have `person` as a neighbor, ordered by the person they
+    /// come from.
+    ///
+    /// By default this looks at the outgoing edges of everyone in the
+    /// population, so it takes time proportional to the number of edges
+    /// of type `T`. Call [`Context::enable_reverse_edges()`] first to make
+    /// it proportional to the number of incoming edges instead.
+    fn get_incoming_edges<T: EdgeType + 'static>(&self, person: PersonId) -> Vec<Edge<T::Value>>;
+
+    /// Keep an index of the incoming edges of type `T` for each person, so
+    /// that [`Context::get_incoming_edges()`] and removing people don't
+    /// have to look at everyone's edges. The index is built from the
+    /// existing edges and then kept up to date as edges are added and
+    /// removed, at the cost of extra memory and a little extra work for
+    /// each change. Calling this again has no effect.
+    fn enable_reverse_edges<T: EdgeType + 'static>(&mut self);
+
+    /// Find all people who have an edge of type `T` and degree `degree`.
+    fn find_people_by_degree<T: EdgeType + 'static>(&self, degree: usize) -> Vec<PersonId>;
+
+    /// The number of edges of type `T` from `person`, without copying
+    /// them as `get_edges(person).len()` would. This takes constant time.
+    fn count_outgoing_edges<T: EdgeType + 'static>(&self, person: PersonId) -> usize;
+
+    /// The total number of edges of type `T`. The count is kept up to date
+    /// as edges are added and removed, so this takes constant time.
+    fn count_edges_total<T: EdgeType + 'static>(&self) -> usize;
+
+    /// Visit `start` and the people reachable from them by following
+    /// edges of type `T`, in breadth-first order, calling `visitor` once
+    /// for each person. Neighbors are visited in the order their edges were
+    /// added, and nobody is visited twice. If `max_depth` is set, only
+    /// people at most that many edges away from `start` are visited.
+    /// Returning `false` from `visitor` stops the traversal.
+    ///
+    /// The edges of each person are looked up when they're visited, so
+    /// `visitor` can change the network, e.g., to remove edges as an
+    /// infection spreads along them.
+    fn bfs_from<T: EdgeType + 'static>(
+        &mut self,
+        start: PersonId,
+        max_depth: Option<usize>,
+        visitor: impl FnMut(&mut Context, PersonId) -> bool,
+    );
+
+    /// Like [`Context::bfs_from()`], but visits people in depth-first
+    /// order.
+    fn dfs_from<T: EdgeType + 'static>(
+        &mut self,
+        start: PersonId,
+        max_depth: Option<usize>,
+        visitor: impl FnMut(&mut Context, PersonId) -> bool,
+    );
+
+    /// Find the path from `from` to `to` over edges of type `T` with the
+    /// lowest total weight, using the weights as distances. Returns the
+    /// total weight and the people on the path, including `from` and `to`,
+    /// or `None` if `to` can't be reached.
+    fn shortest_path<T: EdgeType + 'static>(
+        &self,
+        from: PersonId,
+        to: PersonId,
+    ) -> Option<(f32, Vec<PersonId>)>;
+
+    /// Like [`Context::shortest_path()`], but only returns the total
+    /// weight, which saves keeping track of the path.
+    fn shortest_path_cost_only<T: EdgeType + 'static>(
+        &self,
+        from: PersonId,
+        to: PersonId,
+    ) -> Option<f32>;
+
+    /// Select a random edge out of the list of outgoing edges of type
+    /// `T` from `person_id`, weighted by the edge weights.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if there are no edges.
+    fn select_random_edge<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        person_id: PersonId,
+    ) -> Result<Edge<T::Value>, IxaError>
+    where
+        R::RngType: Rng;
+
+    /// Walk `steps` edges of type `T` at random from `start`, choosing
+    /// each edge as [`Context::select_random_edge()`] does, and return the
+    /// people on the walk, starting with `start`. The walk never goes back
+    /// to someone it has already visited, and stops early, returning fewer
+    /// than `steps + 1` people, when the current person has no edges with
+    /// a positive weight to anyone new.
+    fn random_walk<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        start: PersonId,
+        steps: usize,
+    ) -> Vec<PersonId>
+    where
+        R::RngType: Rng;
+
+    /// Like [`Context::random_walk()`], but the walk can go back to people
+    /// it has already visited, so it only stops early when the current
+    /// person has no edges with a positive weight.
+    fn random_walk_with_revisits<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        start: PersonId,
+        steps: usize,
+    ) -> Vec<PersonId>
+    where
+        R::RngType: Rng;
+
+    /// Add an edge of type `T` from each person to each other person with
+    /// probability `p`, independently, with weight 1.0 and the default
+    /// value of `T::Value`. Pairs that already have an edge are left as
+    /// they are. The number of draws from `rng_id` is proportional to the
+    /// number of edges added when `p` is at most 0.5, and to the number of
+    /// pairs left out otherwise.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if `p` isn't between 0 and 1.
+    fn generate_erdos_renyi<T: EdgeType + 'static, R: RngId + 'static>(
+        &mut self,
+        rng_id: R,
+        p: f64,
+    ) -> Result<(), IxaError>
+    where
+        R::RngType: Rng;
+
+    /// Build a scale-free network of edges of type `T` by preferential
+    /// attachment, with weight 1.0 and the default value of `T::Value`.
+    /// The first `m + 1` people, in order of creation, are connected to
+    /// each other. Each later person is then connected to `m` distinct
+    /// people who came before them, chosen with probability proportional
+    /// to their in-degree. Connections are made with an edge in each
+    /// direction, so in-degree and out-degree are equal in the generated
+    /// network. Pairs that already have an edge keep it, but edges added
+    /// before this call don't affect the choice of people.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if `m` is 0 or there are fewer than `m + 1`
+    /// people.
+    fn generate_barabasi_albert<T: EdgeType + 'static, R: RngId + 'static>(
+        &mut self,
+        rng_id: R,
+        m: usize,
+    ) -> Result<(), IxaError>
+    where
+        R::RngType: Rng;
+
+    /// The local clustering coefficient of `person` for edges of type `T`:
+    /// the fraction of the pairs of `person`'s neighbors that are neighbors
+    /// of each other. Edges are treated as undirected, so people are
+    /// neighbors if there's an edge between them in either direction. A
+    /// person with fewer than two neighbors has a coefficient of 0.
+    ///
+    /// Finding the edges to `person` means looking at everyone's edges
+    /// unless [`Context::enable_reverse_edges()`] has been called for `T`.
+    fn local_clustering_coefficient<T: EdgeType + 'static>(&self, person: PersonId) -> f64;
+
+    /// The average over everyone of
+    /// [`Context::local_clustering_coefficient()`] for edges of type `T`.
+    /// People with fewer than two neighbors count as 0, and an empty
+    /// population has a coefficient of 0.
+    fn global_clustering_coefficient<T: EdgeType + 'static>(&self) -> f64;
+
+    /// Estimate the mean length of the shortest paths along edges of type
+    /// `T` from `n_pairs` pairs of distinct people chosen uniformly at
+    /// random, with a breadth-first search from the first person of each
+    /// pair. Lengths are numbers of edges, ignoring weights, and edges are
+    /// followed in their direction. Pairs with no path between them aren't
+    /// included in the mean but are counted in
+    /// [`PathLengthEstimate::unreachable_fraction`].
+    ///
+    /// # Errors
+    /// Returns `IxaError` if `n_pairs` is 0 or there are fewer than two
+    /// people.
+    fn sample_mean_path_length<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        n_pairs: usize,
+    ) -> Result<PathLengthEstimate, IxaError>
+    where
+        R::RngType: Rng;
+
+    /// The degree assortativity of the network of edges of type `T`: the
+    /// correlation between the degrees of the people at either end of an
+    /// edge, from -1 when people with many neighbors are connected to
+    /// people with few to 1 when they're connected to each other. Edges
+    /// are treated as undirected, as for
+    /// [`Context::global_clustering_coefficient()`]. The result is NaN if there
+    /// are no edges or everyone with an edge has the same degree.
+    fn degree_assortativity<T: EdgeType + 'static>(&self) -> f64;
+
+    /// The connected components of the network of edges of type `T`,
+    /// treating edges as undirected, so that two people are in the same
+    /// component if there's a path between them ignoring the directions of
+    /// the edges. Everyone is in exactly one component, and people without
+    /// any edges are in a component of their own. Each component is sorted
+    /// by id, and the components are in order of their first person.
+    fn find_connected_components<T: EdgeType + 'static>(&self) -> Vec<Vec<PersonId>>;
+
+    /// The number of components [`Context::find_connected_components()`]
+    /// would return, without collecting them.
+    fn count_connected_components<T: EdgeType + 'static>(&self) -> usize;
+
+    /// The closeness centrality of `person` in the network of edges of type
+    /// `T`: the number of people `person` can reach over the sum of their
+    /// distances from `person`, times the fraction of the rest of the
+    /// population they are. Distances are numbers of edges, ignoring
+    /// weights, and edges are followed in their direction. Someone who
+    /// can't reach anyone has a centrality of 0.
+    fn closeness_centrality<T: EdgeType + 'static>(&self, person: PersonId) -> f64;
+
+    /// The betweenness centrality of everyone in the network of edges of
+    /// type `T`: for each person, the sum over all pairs of other people of
+    /// the fraction of the shortest paths between them that go through that
+    /// person. Paths are directed and lengths are numbers of edges, as for
+    /// [`Context::closeness_centrality()`]. The values aren't normalized,
+    /// so they grow with the size of the network.
+    ///
+    /// This uses Brandes' algorithm, which takes time proportional to the
+    /// population times the number of edges; see
+    /// [`Context::approximate_betweenness_centrality()`] for large networks.
+    fn betweenness_centrality<T: EdgeType + 'static>(&self) -> HashMap<PersonId, f64>;
+
+    /// An unbiased estimate of [`Context::betweenness_centrality()`] from
+    /// the shortest paths starting at `n_sources` people chosen uniformly
+    /// at random, scaled up to the whole population. This takes time
+    /// proportional to `n_sources` times the number of edges. If
+    /// `n_sources` is at least the population, the result is exact.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if `n_sources` is 0.
+    fn approximate_betweenness_centrality<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        n_sources: usize,
+    ) -> Result<HashMap<PersonId, f64>, IxaError>
+    where
+        R::RngType: Rng;
+
+    /// Add a periodic report at the end of period `period` with statistics
+    /// about the edges of type `T`: the number of edges, the mean degree,
+    /// and the minimum, quartiles and maximum of the degree distribution.
+    /// The degree of a person is their number of outgoing edges of type
+    /// `T`, and people without any edges count as having degree 0. There is
+    /// one row per period, so call this once for each edge type to report
+    /// on, with different names.
+    ///
+    /// # Errors
+    /// If the file already exists and `overwrite` is set to false, raises an error and info message.
+    /// If the file cannot be created, returns [`IxaError`]
+    fn add_network_periodic_report<T: EdgeType + 'static>(
+        &mut self,
+        short_name: &str,
+        period: f64,
+    ) -> Result<(), IxaError>;
+
+    /// Write the edges of type `T` to a CSV file at `path`, e.g., to
+    /// analyze the network with another tool, with columns `source`,
+    /// `target` and `weight`. If the edge data is a struct, each of its
+    /// fields is written in a column named after it; other edge data is
+    /// written in a column named `inner`, and `()` adds no columns.
+    ///
+    /// Each edge is a row, so edges added with [`Context::add_edge_bidi()`]
+    /// are two rows. The rows are in order of the source person. The file
+    /// always has a header row, even if there are no edges.
+    ///
+    /// # Errors
+    /// Returns [`IxaError`] if the file can't be written.
+    fn export_network_csv<T: EdgeType + 'static>(&self, path: &Path) -> Result<(), IxaError>
+    where
+        T::Value: Serialize;
+
+    /// Add edges of type `T` from a CSV file at `path`, e.g., a contact
+    /// network from an existing dataset. The file must have a header row.
+    /// `mapper` is called with each of the other rows and returns the
+    /// edge for it, as the person it's from, the neighbor, the weight and
+    /// the edge data; each edge is added with [`Context::add_edge()`].
+    ///
+    /// A bad row doesn't stop the loading: the edges from the other rows
+    /// are still added.
+    ///
+    /// # Errors
+    /// Returns [`IxaError::MultipleErrors`] with the error for each row
+    /// that couldn't be read, that `mapper` returned an error for, or
+    /// whose edge couldn't be added, in order. Returns [`IxaError`] if the
+    /// file can't be opened.
+    fn load_network_from_csv<T: EdgeType + 'static>(
+        &mut self,
+        path: &Path,
+        mapper: impl Fn(&StringRecord) -> Result<(PersonId, PersonId, f32, T::Value), IxaError>,
+    ) -> Result<(), IxaError>;
+}
+
+// Public API.
+impl ContextNetworkExt for Context {
+    fn add_edge<T: EdgeType + 'static>(
+        &mut self,
+        person: PersonId,
+        neighbor: PersonId,
+        weight: f32,
+        inner: T::Value,
+    ) -> Result<(), IxaError> {
+        for id in [person, neighbor] {
+            if !self.person_exists(id) {
+                return Err(IxaError::PersonNotFound(id));
+            }
+        }
+        self.get_data_container_mut(NetworkPlugin)
+            .add_edge::<T>(person, neighbor, weight, inner)?;
+        track_edge_type::<T>(self);
+        self.emit_event(EdgeAddedEvent::<T> {
+            edge: Edge {
+                person,
+                neighbor,
+                weight,
+                inner,
+            },
+        });
+        Ok(())
+    }
+
+    fn add_edge_bidi<T: EdgeType + 'static>(
+        &mut self,
+        person1: PersonId,
+        person2: PersonId,
+        weight: f32,
+        inner: T::Value,
+    ) -> Result<(), IxaError> {
+        self.add_edge::<T>(person1, person2, weight, inner)?;
+        if T::UNDIRECTED {
+            return Ok(());
+        }
+        self.add_edge::<T>(person2, person1, weight, inner)
+    }
+
+    fn remove_edge<T: EdgeType + 'static>(
+        &mut self,
+        person: PersonId,
+        neighbor: PersonId,
+    ) -> Result<(), IxaError> {
+        let data_container = self.get_data_container(NetworkPlugin);
+        // Generate an error if not initialized.
+        if data_container.is_none() {
+            return Err(IxaError::IxaError(String::from("Network not initialized")));
+        }
+        let data_container = self.get_data_container_mut(NetworkPlugin);
+        let edge = data_container.remove_edge::<T>(person, neighbor)?;
+        self.emit_event(EdgeRemovedEvent::<T> { edge });
+        Ok(())
+    }
+
+    fn get_edge<T: EdgeType + 'static>(
+        &self,
+        person: PersonId,
+        neighbor: PersonId,
+    ) -> Option<&Edge<T::Value>> {
+        let data_container = self.get_data_container(NetworkPlugin);
+
+        match data_container {
+            None => None,
+            Some(data_container) => data_container.get_edge::<T>(person, neighbor),
+        }
+    }
+
+    fn get_edges<T: EdgeType + 'static>(&self, person: PersonId) -> Vec<Edge<T::Value>> {
+        let data_container = self.get_data_container(NetworkPlugin);
+
+        match data_container {
+            None => Vec::new(),
+            Some(data_container) => data_container.get_edges::<T>(person),
+        }
+    }
+
+    fn get_matching_edges<T: EdgeType + 'static>(
+        &self,
+        person: PersonId,
+        filter: impl Fn(&Context, &Edge<T::Value>) -> bool + 'static,
+    ) -> Vec<Edge<T::Value>> {
+        let Some(data_container) = self.get_data_container(NetworkPlugin) else {
+            return Vec::new();
+        };
+        data_container
+            .edges_ref::<T>(person)
+            .iter()
+            .filter(|edge| filter(self, edge))
+            .copied()
+            .collect()
+    }
+
+    fn get_incoming_edges<T: EdgeType + 'static>(&self, person: PersonId) -> Vec<Edge<T::Value>> {
+        match self.get_data_container(NetworkPlugin) {
+            None => Vec::new(),
+            Some(data_container) => data_container.incoming_edges::<T>(person),
+        }
+    }
+
+    fn enable_reverse_edges<T: EdgeType + 'static>(&mut self) {
+        self.get_data_container_mut(NetworkPlugin)
+            .enable_reverse_edges::<T>();
+    }
+
+    fn find_people_by_degree<T: EdgeType + 'static>(&self, degree: usize) -> Vec<PersonId> {
+        let data_container = self.get_data_container(NetworkPlugin);
+
+        match data_container {
+            None => Vec::new(),
+            Some(data_container) => data_container.find_people_by_degree::<T>(degree),
+        }
+    }
+
+    fn count_outgoing_edges<T: EdgeType + 'static>(&self, person: PersonId) -> usize {
+        self.get_data_container(NetworkPlugin)
+            .map_or(0, |data_container| data_container.degree::<T>(person))
+    }
+
+    fn count_edges_total<T: EdgeType + 'static>(&self) -> usize {
+        self.get_data_container(NetworkPlugin)
+            .map_or(0, NetworkData::count_edges_total::<T>)
+    }
+
+    fn bfs_from<T: EdgeType + 'static>(
+        &mut self,
+        start: PersonId,
+        max_depth: Option<usize>,
+        visitor: impl FnMut(&mut Context, PersonId) -> bool,
+    ) {
+        traverse::<T>(self, start, max_depth, false, visitor);
+    }
+
+    fn dfs_from<T: EdgeType + 'static>(
+        &mut self,
+        start: PersonId,
+        max_depth: Option<usize>,
+        visitor: impl FnMut(&mut Context, PersonId) -> bool,
+    ) {
+        traverse::<T>(self, start, max_depth, true, visitor);
+    }
+
+    fn shortest_path<T: EdgeType + 'static>(
+        &self,
+        from: PersonId,
+        to: PersonId,
+    ) -> Option<(f32, Vec<PersonId>)> {
+        let data_container = self.get_data_container(NetworkPlugin);
+        shortest_path::shortest_path::<T>(data_container, from, to, true)
+    }
+
+    fn shortest_path_cost_only<T: EdgeType + 'static>(
+        &self,
+        from: PersonId,
+        to: PersonId,
+    ) -> Option<f32> {
+        let data_container = self.get_data_container(NetworkPlugin);
+        shortest_path::shortest_path::<T>(data_container, from, to, false)
+            .map(|(distance, _)| distance)
+    }
+
+    fn select_random_edge<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        person_id: PersonId,
+    ) -> Result<Edge<T::Value>, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        let edges = self
+            .get_data_container(NetworkPlugin)
+            .map_or(Cow::Borrowed(&[][..]), |data_container| {
+                data_container.edges_ref::<T>(person_id)
+            });
+        if edges.is_empty() {
+            return Err(IxaError::IxaError(String::from(
+                "Can't sample from empty list",
+            )));
+        }
+
+        let index = self.with_scratch(|scratch| {
+            let weights = scratch.collect(edges.iter().map(|x| x.weight));
+            self.sample_weighted(rng_id, &weights)
+        });
+        Ok(edges[index])
+    }
+
+    fn random_walk<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        start: PersonId,
+        steps: usize,
+    ) -> Vec<PersonId>
+    where
+        R::RngType: Rng,
+    {
+        random_walk::random_walk::<T, R>(self, rng_id, start, steps, false)
+    }
+
+    fn random_walk_with_revisits<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        start: PersonId,
+        steps: usize,
+    ) -> Vec<PersonId>
+    where
+        R::RngType: Rng,
+    {
+        random_walk::random_walk::<T, R>(self, rng_id, start, steps, true)
+    }
+
+    fn generate_erdos_renyi<T: EdgeType + 'static, R: RngId + 'static>(
+        &mut self,
+        rng_id: R,
+        p: f64,
+    ) -> Result<(), IxaError>
+    where
+        R::RngType: Rng,
+    {
+        generators::erdos_renyi::<T, R>(self, rng_id, p)
+    }
+
+    fn generate_barabasi_albert<T: EdgeType + 'static, R: RngId + 'static>(
+        &mut self,
+        rng_id: R,
+        m: usize,
+    ) -> Result<(), IxaError>
+    where
+        R::RngType: Rng,
+    {
+        generators::barabasi_albert::<T, R>(self, rng_id, m)
+    }
+
+    fn local_clustering_coefficient<T: EdgeType + 'static>(&self, person: PersonId) -> f64 {
+        metrics::local_clustering_coefficient::<T>(self, person)
+    }
+
+    fn global_clustering_coefficient<T: EdgeType + 'static>(&self) -> f64 {
+        metrics::global_clustering_coefficient::<T>(self)
+    }
+
+    fn sample_mean_path_length<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        n_pairs: usize,
+    ) -> Result<PathLengthEstimate, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        metrics::sample_mean_path_length::<T, R>(self, rng_id, n_pairs)
+    }
+
+    fn degree_assortativity<T: EdgeType + 'static>(&self) -> f64 {
+        metrics::degree_assortativity::<T>(self)
+    }
+
+    fn find_connected_components<T: EdgeType + 'static>(&self) -> Vec<Vec<PersonId>> {
+        metrics::find_connected_components::<T>(self)
+    }
+
+    fn count_connected_components<T: EdgeType + 'static>(&self) -> usize {
+        metrics::count_connected_components::<T>(self)
+    }
+
+    fn closeness_centrality<T: EdgeType + 'static>(&self, person: PersonId) -> f64 {
+        centrality::closeness_centrality::<T>(self, person)
+    }
+
+    fn betweenness_centrality<T: EdgeType + 'static>(&self) -> HashMap<PersonId, f64> {
+        centrality::betweenness_centrality::<T>(self)
+    }
+
+    fn approximate_betweenness_centrality<T: EdgeType + 'static, R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        n_sources: usize,
+    ) -> Result<HashMap<PersonId, f64>, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        centrality::approximate_betweenness_centrality::<T, R>(self, rng_id, n_sources)
+    }
+
+    fn add_network_periodic_report<T: EdgeType + 'static>(
+        &mut self,
+        short_name: &str,
+        period: f64,
+    ) -> Result<(), IxaError> {
+        trace!("Adding network periodic report {short_name}");
+        self.add_report::<NetworkStatsReport<T>>(short_name)?;
+        self.add_periodic_plan_with_phase(
+            period,
+            |context| {
+                context.send_report(network_stats::<T>(context));
+            },
+            ExecutionPhase::Last,
+        );
+        Ok(())
+    }
+
+    fn export_network_csv<T: EdgeType + 'static>(&self, path: &Path) -> Result<(), IxaError>
+    where
+        T::Value: Serialize,
+    {
+        export::export_network_csv::<T>(self, path)
+    }
+
+    fn load_network_from_csv<T: EdgeType + 'static>(
+        &mut self,
+        path: &Path,
+        mapper: impl Fn(&StringRecord) -> Result<(PersonId, PersonId, f32, T::Value), IxaError>,
+    ) -> Result<(), IxaError> {
+        import::load_network_from_csv::<T>(self, path, mapper)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+// Tests for the inner core.
+mod test_inner {
+    use super::{Edge, NetworkData};
+    use crate::error::IxaError;
+    use crate::people::PersonId;
+
+    define_edge_type!(EdgeType1, ());
+    define_edge_type!(EdgeType2, ());
+    define_edge_type!(EdgeType3, bool);
+
+    #[test]
+    fn add_edge() {
+        let mut nd = NetworkData::new();
+
+        nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), 0.01, ())
+            .unwrap();
+        let edge = nd.get_edge::<EdgeType1>(PersonId(1), PersonId(2)).unwrap();
+        assert_eq!(edge.weight, 0.01);
+    }
+
+    #[test]
+    fn add_edge_with_inner() {
+        let mut nd = NetworkData::new();
+
+        nd.add_edge::<EdgeType3>(PersonId(1), PersonId(2), 0.01, true)
+            .unwrap();
+        let edge = nd.get_edge::<EdgeType3>(PersonId(1), PersonId(2)).unwrap();
+        assert_eq!(edge.weight, 0.01);
+        assert!(edge.inner);
+    }
+
+    #[test]
+    fn add_two_edges() {
+        let mut nd = NetworkData::new();
+
+        nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), 0.01, ())
+            .unwrap();
+        nd.add_edge::<EdgeType1>(PersonId(1), PersonId(3), 0.02, ())
+            .unwrap();
+        let edge = nd.get_edge::<EdgeType1>(PersonId(1), PersonId(2)).unwrap();
+        assert_eq!(edge.weight, 0.01);
+        let edge = nd.get_edge::<EdgeType1>(PersonId(1), PersonId(3)).unwrap();
+        assert_eq!(edge.weight, 0.02);
+
+        let edges = nd.get_edges::<EdgeType1>(PersonId(1));
+        assert_eq!(
+            edges,
+            vec![
+                Edge {
+                    person: PersonId(1),
+                    neighbor: PersonId(2),
+                    weight: 0.01,
+                    inner: ()
+                },
+                Edge {
+                    person: PersonId(1),
+                    neighbor: PersonId(3),
+                    weight: 0.02,
+                    inner: ()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn add_two_edge_types() {
+        let mut nd = NetworkData::new();
+
+        nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), 0.01, ())
+            .unwrap();
+        nd.add_edge::<EdgeType2>(PersonId(1), PersonId(2), 0.02, ())
+            .unwrap();
+        let edge = nd.get_edge::<EdgeType1>(PersonId(1), PersonId(2)).unwrap();
+        assert_eq!(edge.weight, 0.01);
+        let edge = nd.get_edge::<EdgeType2>(PersonId(1), PersonId(2)).unwrap();
+        assert_eq!(edge.weight, 0.02);
+
+        let edges = nd.get_edges::<EdgeType1>(PersonId(1));
+        assert_eq!(
+            edges,
+            vec![Edge {
+                person: PersonId(1),
+                neighbor: PersonId(2),
+                weight: 0.01,
+                inner: ()
+            }]
+        );
+    }
+
+    #[test]
+    fn add_edge_twice_fails() {
+        let mut nd = NetworkData::new();
+
+        nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), 0.01, ())
+            .unwrap();
+        let edge = nd.get_edge::<EdgeType1>(PersonId(1), PersonId(2)).unwrap();
+        assert_eq!(edge.weight, 0.01);
+
+        assert!(matches!(
+            nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), 0.02, ()),
+            Err(IxaError::IxaError(_))
+        ));
+    }
+
+    #[test]
+    fn add_remove_add_edge() {
+        let mut nd = NetworkData::new();
+
+        nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), 0.01, ())
+            .unwrap();
+        let edge = nd.get_edge::<EdgeType1>(PersonId(1), PersonId(2)).unwrap();
+        assert_eq!(edge.weight, 0.01);
+
+        nd.remove_edge::<EdgeType1>(PersonId(1), PersonId(2))
+            .unwrap();
+        let edge = nd.get_edge::<EdgeType1>(PersonId(1), PersonId(2));
+        assert!(edge.is_none());
+
+        nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), 0.02, ())
+            .unwrap();
+        let edge = nd.get_edge::<EdgeType1>(PersonId(1), PersonId(2)).unwrap();
+        assert_eq!(edge.weight, 0.02);
+    }
+
+    #[test]
+    fn remove_nonexistent_edge() {
+        let mut nd = NetworkData::new();
+        assert!(matches!(
+            nd.remove_edge::<EdgeType1>(PersonId(1), PersonId(2)),
+            Err(IxaError::IxaError(_))
+        ));
+    }
+
+    #[test]
+    fn add_edge_to_self() {
+        let mut nd = NetworkData::new();
+
+        let result = nd.add_edge::<EdgeType1>(PersonId(1), PersonId(1), 0.01, ());
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+    }
+
+    #[test]
+    fn add_edge_bogus_weight() {
+        let mut nd = NetworkData::new();
+
+        let result = nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), -1.0, ());
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+
+        let result = nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), f32::NAN, ());
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+
+        let result = nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), f32::INFINITY, ());
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+    }
+
+    #[test]
+    fn find_people_by_degree() {
+        let mut nd = NetworkData::new();
+
+        nd.add_edge::<EdgeType1>(PersonId(1), PersonId(2), 0.0, ())
+            .unwrap();
+        nd.add_edge::<EdgeType1>(PersonId(1), PersonId(3), 0.0, ())
+            .unwrap();
+        nd.add_edge::<EdgeType1>(PersonId(2), PersonId(3), 0.0, ())
+            .unwrap();
+        nd.add_edge::<EdgeType1>(PersonId(3), PersonId(2), 0.0, ())
+            .unwrap();
+
+        let matches = nd.find_people_by_degree::<EdgeType1>(2);
+        assert_eq!(matches, vec![PersonId(1)]);
+        let matches = nd.find_people_by_degree::<EdgeType1>(1);
+        assert_eq!(matches, vec![PersonId(2), PersonId(3)]);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+// Tests for the API.
+mod test_api {
+    use crate::context::Context;
+    use crate::define_rng;
+    use crate::error::IxaError;
+    use crate::network::{ContextNetworkExt, Edge, EdgeAddedEvent, EdgeRemovedEvent};
+    use crate::people::{define_person_property, ContextPeopleExt, PersonId};
+    use crate::random::ContextRandomExt;
+    use crate::report::ContextReportExt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    define_edge_type!(EdgeType1, u32);
+    define_edge_type!(EdgeType2, ());
+    define_edge_type!(Household, u32, undirected);
+    define_person_property!(Age, u8);
+
+    fn setup() -> (Context, PersonId, PersonId) {
+        let mut context = Context::new();
+        let person1 = context.add_person((Age, 1)).unwrap();
+        let person2 = context.add_person((Age, 2)).unwrap();
+
+        (context, person1, person2)
+    }
+
+    #[test]
+    fn add_edge() {
+        let (mut context, person1, person2) = setup();
+
+        context
+            .add_edge::<EdgeType1>(person1, person2, 0.01, 1)
+            .unwrap();
+        assert_eq!(
+            context
+                .get_edge::<EdgeType1>(person1, person2)
+                .unwrap()
+                .weight,
+            0.01
+        );
+        assert_eq!(
+            context.get_edges::<EdgeType1>(person1),
+            vec![Edge {
+                person: person1,
+                neighbor: person2,
+                weight: 0.01,
+                inner: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn remove_edge() {
+        let (mut context, person1, person2) = setup();
+        // Check that we get an error if nothing has been added.
+
+        assert!(matches!(
+            context.remove_edge::<EdgeType1>(person1, person2),
+            Err(IxaError::IxaError(_))
+        ));
+        context
+            .add_edge::<EdgeType1>(person1, person2, 0.01, 1)
+            .unwrap();
+        context.remove_edge::<EdgeType1>(person1, person2).unwrap();
+        assert!(context.get_edge::<EdgeType1>(person1, person2).is_none());
+        assert_eq!(context.get_edges::<EdgeType1>(person1).len(), 0);
+    }
+
+    #[test]
+    fn count_edges() {
+        let (mut context, person1, person2) = setup();
+        let person3 = context.add_person((Age, 3)).unwrap();
+        assert_eq!(context.count_edges_total::<EdgeType1>(), 0);
+        assert_eq!(context.count_outgoing_edges::<EdgeType1>(person1), 0);
+
+        context
+            .add_edge::<EdgeType1>(person1, person2, 1.0, 1)
+            .unwrap();
+        context
+            .add_edge::<EdgeType1>(person1, person3, 1.0, 1)
+            .unwrap();
+        context
+            .add_edge_bidi::<EdgeType1>(person2, person3, 1.0, 1)
+            .unwrap();
+        context
+            .add_edge::<EdgeType2>(person3, person1, 1.0, ())
+            .unwrap();
+        // Edges that couldn't be added aren't counted.
+        assert!(context
+            .add_edge::<EdgeType1>(person1, person2, 1.0, 1)
+            .is_err());
+        assert_eq!(context.count_edges_total::<EdgeType1>(), 4);
+        assert_eq!(context.count_edges_total::<EdgeType2>(), 1);
+        assert_eq!(context.count_outgoing_edges::<EdgeType1>(person1), 2);
+        assert_eq!(context.count_outgoing_edges::<EdgeType1>(person2), 1);
+
+        context.remove_edge::<EdgeType1>(person1, person2).unwrap();
+        assert!(context.remove_edge::<EdgeType1>(person1, person2).is_err());
+        assert_eq!(context.count_edges_total::<EdgeType1>(), 3);
+        assert_eq!(context.count_outgoing_edges::<EdgeType1>(person1), 1);
+
+        // Removing a person removes their edges in both directions.
+        context.remove_person(person3).unwrap();
+        context.execute();
+        assert_eq!(context.count_edges_total::<EdgeType1>(), 0);
+        assert_eq!(context.count_edges_total::<EdgeType2>(), 0);
+    }
+
+    #[test]
+    fn count_edges_with_reverse_index() {
+        let (mut context, person1, person2) = setup();
+        let person3 = context.add_person((Age, 3)).unwrap();
+        context.enable_reverse_edges::<EdgeType1>();
+        context
+            .add_edge_bidi::<EdgeType1>(person1, person2, 1.0, 1)
+            .unwrap();
+        context
+            .add_edge::<EdgeType1>(person3, person2, 1.0, 1)
+            .unwrap();
+        assert_eq!(context.count_edges_total::<EdgeType1>(), 3);
+        context.remove_person(person2).unwrap();
+        context.execute();
+        assert_eq!(context.count_edges_total::<EdgeType1>(), 0);
+        assert_eq!(context.count_outgoing_edges::<EdgeType1>(person3), 0);
+    }
+
+    #[test]
+    fn add_edge_bidi() {
+        let (mut context, person1, person2) = setup();
+
+        context
+            .add_edge_bidi::<EdgeType1>(person1, person2, 0.01, 1)
+            .unwrap();
+        assert_eq!(
+            context
+                .get_edge::<EdgeType1>(person1, person2)
+                .unwrap()
+                .weight,
+            0.01
+        );
+        assert_eq!(
+            context
+                .get_edge::<EdgeType1>(person2, person1)
+                .unwrap()
+                .weight,
+            0.01
+        );
+    }
+
+    #[test]
+    fn add_edge_different_weights() {
+        let (mut context, person1, person2) = setup();
+
+        context
+            .add_edge::<EdgeType1>(person1, person2, 0.01, 1)
+            .unwrap();
+        context
+            .add_edge::<EdgeType1>(person2, person1, 0.02, 1)
+            .unwrap();
+        assert_eq!(
+            context
+                .get_edge::<EdgeType1>(person1, person2)
+                .unwrap()
+                .weight,
+            0.01
+        );
+        assert_eq!(
+            context
+                .get_edge::<EdgeType1>(person2, person1)
+                .unwrap()
+                .weight,
+            0.02
+        );
+    }
+
+    #[test]
+    fn get_matching_edges_weight() {
+        let (mut context, person1, person2) = setup();
+        let person3 = context.add_person((Age, 3)).unwrap();
+
+        context
+            .add_edge::<EdgeType1>(person1, person2, 0.01, 1)
+            .unwrap();
+        context
+            .add_edge::<EdgeType1>(person1, person3, 0.03, 1)
+            .unwrap();
+        let edges =
+            context.get_matching_edges::<EdgeType1>(person1, |_context, edge| edge.weight > 0.01);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].person, person1);
+        assert_eq!(edges[0].neighbor, person3);
+    }
+
+    #[test]
+    fn get_matching_edges_inner() {
+        let (mut context, person1, person2) = setup();
+        let person3 = context.add_person((Age, 3)).unwrap();
+
+        context
+            .add_edge::<EdgeType1>(person1, person2, 0.01, 1)
+            .unwrap();
+        context
+            .add_edge::<EdgeType1>(person1, person3, 0.03, 3)
+            .unwrap();
+        let edges =
+            context.get_matching_edges::<EdgeType1>(person1, |_context, edge| edge.inner == 3);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].person, person1);
+        assert_eq!(edges[0].neighbor, person3);
+    }
+
+    #[test]
+    fn get_matching_edges_person_property() {
+        let (mut context, person1, person2) = setup();
+        let person3 = context.add_person((Age, 3)).unwrap();
+
+        context
+            .add_edge::<EdgeType1>(person1, person2, 0.01, 1)
+            .unwrap();
+        context
+            .add_edge::<EdgeType1>(person1, person3, 0.03, 3)
+            .unwrap();
+        let edges = context.get_matching_edges::<EdgeType1>(person1, |context, edge| {
+            context.match_person(edge.neighbor, (Age, 3))
+        });
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].person, person1);
+        assert_eq!(edges[0].neighbor, person3);
+    }
+
+    #[test]
+    fn select_random_edge() {
+        define_rng!(NetworkTestRng);
+
+        let (mut context, person1, person2) = setup();
+        let person3 = context.add_person((Age, 3)).unwrap();
+        context.init_random(42);
+
+        context
+            .add_edge::<EdgeType1>(person1, person2, 0.01, 1)
+            .unwrap();
+        context
+            .add_edge::<EdgeType1>(person1, person3, 10_000_000.0, 3)
+            .unwrap();
+
+        let edge = context
+            .select_random_edge::<EdgeType1, _>(NetworkTestRng, person1)
+            .unwrap();
+        assert_eq!(edge.person, person1);
+        assert_eq!(edge.neighbor, person3);
+    }
+
+    #[test]
+    fn edge_events() {
+        let (mut context, person1, person2) = setup();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let added_log = Rc::clone(&log);
+        context.subscribe_to_event(move |_, event: EdgeAddedEvent<EdgeType1>| {
+            added_log.borrow_mut().push(("added", event.edge));
+        });
+        let removed_log = Rc::clone(&log);
+        context.subscribe_to_event(move |_, event: EdgeRemovedEvent<EdgeType1>| {
+            removed_log.borrow_mut().push(("removed", event.edge));
+        });
+
+        context
+            .add_edge_bidi::<EdgeType1>(person1, person2, 0.5, 7)
+            .unwrap();
+        context.remove_edge::<EdgeType1>(person2, person1).unwrap();
+        // Failed changes don't emit events
+        assert!(context
+            .add_edge::<EdgeType1>(person1, person2, 0.5, 7)
+            .is_err());
+        assert!(context.remove_edge::<EdgeType1>(person2, person1).is_err());
+        context.execute();
+
+        let edge = |person, neighbor| Edge {
+            person,
+            neighbor,
+            weight: 0.5,
+            inner: 7,
+        };
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                ("added", edge(person1, person2)),
+                ("added", edge(person2, person1)),
+                ("removed", edge(person2, person1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_person_removes_edges() {
+        let (mut context, person1, person2) = setup();
+        let person3 = context.add_person((Age, 3)).unwrap();
+        context
+            .add_edge_bidi::<EdgeType1>(person1, person2, 1.0, 1)
+            .unwrap();
+        context
+            .add_edge_bidi::<EdgeType1>(person2, person3, 1.0, 2)
+            .unwrap();
+        context
+            .add_edge::<EdgeType2>(person3, person2, 1.0, ())
+            .unwrap();
+        context
+            .add_edge::<EdgeType2>(person3, person1, 1.0, ())
+            .unwrap();
+        let removed = Rc::new(RefCell::new(Vec::new()));
+        let removed_clone = Rc::clone(&removed);
+        context.subscribe_to_event(move |_, event: EdgeRemovedEvent<EdgeType1>| {
+            removed_clone
+                .borrow_mut()
+                .push((event.edge.person, event.edge.neighbor));
+        });
+
+        context.remove_person(person2).unwrap();
+        context.execute();
+
+        assert!(context.get_edges::<EdgeType1>(person2).is_empty());
+        assert!(context.get_edges::<EdgeType1>(person1).is_empty());
+        assert!(context.get_edges::<EdgeType1>(person3).is_empty());
+        assert_eq!(
+            context
+                .get_edges::<EdgeType2>(person3)
+                .iter()
+                .map(|edge| edge.neighbor)
+                .collect::<Vec<_>>(),
+            vec![person1]
+        );
+        let mut removed = removed.borrow().clone();
+        removed.sort_by_key(|(person, neighbor)| (person.0, neighbor.0));
+        assert_eq!(
+            removed,
+            vec![
+                (person1, person2),
+                (person2, person1),
+                (person2, person3),
+                (person3, person2),
+            ]
+        );
+    }
+
+    #[test]
+    fn add_edge_to_removed_person() {
+        let (mut context, person1, person2) = setup();
+        context.remove_person(person2).unwrap();
+        assert!(matches!(
+            context.add_edge::<EdgeType1>(person1, person2, 1.0, 1),
+            Err(IxaError::PersonNotFound(id)) if id == person2
+        ));
+        assert!(matches!(
+            context.add_edge::<EdgeType1>(person2, person1, 1.0, 1),
+            Err(IxaError::PersonNotFound(id)) if id == person2
+        ));
+    }
+
+    // Returns (person, neighbor, inner) for each edge to `person`.
+    fn incoming(context: &Context, person: PersonId) -> Vec<(PersonId, PersonId, u32)> {
+        context
+            .get_incoming_edges::<EdgeType1>(person)
+            .iter()
+            .map(|edge| (edge.person, edge.neighbor, edge.inner))
+            .collect()
+    }
+
+    fn check_incoming_edges(reverse_edges: bool) {
+        let (mut context, person1, person2) = setup();
+        let person3 = context.add_person((Age, 3)).unwrap();
+        let person4 = context.add_person((Age, 4)).unwrap();
+        assert!(incoming(&context, person1).is_empty());
+
+        context
+            .add_edge::<EdgeType1>(person3, person1, 1.0, 31)
+            .unwrap();
+        if reverse_edges {
+            // Existing edges are indexed too.
+            context.enable_reverse_edges::<EdgeType1>();
+        }
+        context
+            .add_edge::<EdgeType1>(person2, person1, 1.0, 21)
+            .unwrap();
+        context
+            .add_edge::<EdgeType1>(person4, person1, 1.0, 41)
+            .unwrap();
+        context
+            .add_edge::<EdgeType1>(person1, person2, 1.0, 12)
+            .unwrap();
+        // Edges of other types aren't included.
+        context
+            .add_edge::<EdgeType2>(person1, person3, 1.0, ())
+            .unwrap();
+        assert_eq!(
+            incoming(&context, person1),
+            vec![
+                (person2, person1, 21),
+                (person3, person1, 31),
+                (person4, person1, 41)
+            ]
+        );
+        assert_eq!(incoming(&context, person2), vec![(person1, person2, 12)]);
+        assert!(incoming(&context, person3).is_empty());
+
+        context.remove_edge::<EdgeType1>(person3, person1).unwrap();
+        assert_eq!(
+            incoming(&context, person1),
+            vec![(person2, person1, 21), (person4, person1, 41)]
+        );
+
+        context.remove_person(person2).unwrap();
+        context.execute();
+        assert_eq!(incoming(&context, person1), vec![(person4, person1, 41)]);
+        assert!(incoming(&context, person2).is_empty());
+        assert!(context.get_edges::<EdgeType1>(person1).is_empty());
+
+        context
+            .add_edge::<EdgeType1>(person3, person1, 1.0, 31)
+            .unwrap();
+        assert_eq!(
+            incoming(&context, person1),
+            vec![(person3, person1, 31), (person4, person1, 41)]
+        );
+    }
+
+    #[test]
+    fn get_incoming_edges() {
+        check_incoming_edges(false);
+    }
+
+    #[test]
+    fn get_incoming_edges_with_reverse_edges() {
+        check_incoming_edges(true);
+    }
+
+    // Returns a context with five people and edges 0->1, 0->2, 1->3, 2->3
+    // and 3->4.
+    fn setup_traversal() -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        let people: Vec<_> = (0..5)
+            .map(|age| context.add_person((Age, age)).unwrap())
+            .collect();
+        for (from, to) in [(0, 1), (0, 2), (1, 3), (2, 3), (3, 4)] {
+            context
+                .add_edge::<EdgeType1>(people[from], people[to], 1.0, 0)
+                .unwrap();
+        }
+        (context, people)
+    }
+
+    // Returns the people visited, in order, stopping after `stop` if set.
+    fn traversal(
+        context: &mut Context,
+        depth_first: bool,
+        start: PersonId,
+        max_depth: Option<usize>,
+        stop: Option<PersonId>,
+    ) -> Vec<PersonId> {
+        let mut visited = Vec::new();
+        let visitor = |_: &mut Context, person| {
+            visited.push(person);
+            Some(person) != stop
+        };
+        if depth_first {
+            context.dfs_from::<EdgeType1>(start, max_depth, visitor);
+        } else {
+            context.bfs_from::<EdgeType1>(start, max_depth, visitor);
+        }
+        visited
+    }
+
+    #[test]
+    fn bfs_from() {
+        let (mut context, p) = setup_traversal();
+        assert_eq!(
+            traversal(&mut context, false, p[0], None, None),
+            vec![p[0], p[1], p[2], p[3], p[4]]
+        );
+        assert_eq!(
+            traversal(&mut context, false, p[0], Some(1), None),
+            vec![p[0], p[1], p[2]]
+        );
+        assert_eq!(
+            traversal(&mut context, false, p[0], Some(0), None),
+            vec![p[0]]
+        );
+        assert_eq!(
+            traversal(&mut context, false, p[0], None, Some(p[2])),
+            vec![p[0], p[1], p[2]]
+        );
+        // Edges are directed
+        assert_eq!(
+            traversal(&mut context, false, p[3], None, None),
+            vec![p[3], p[4]]
+        );
+    }
+
+    #[test]
+    fn dfs_from() {
+        let (mut context, p) = setup_traversal();
+        assert_eq!(
+            traversal(&mut context, true, p[0], None, None),
+            vec![p[0], p[1], p[3], p[4], p[2]]
+        );
+        assert_eq!(
+            traversal(&mut context, true, p[0], Some(2), None),
+            vec![p[0], p[1], p[3], p[2]]
+        );
+        assert_eq!(
+            traversal(&mut context, true, p[0], None, Some(p[3])),
+            vec![p[0], p[1], p[3]]
+        );
+    }
+
+    #[test]
+    fn traverse_without_edges() {
+        let (mut context, p) = setup_traversal();
+        for depth_first in [false, true] {
+            assert_eq!(
+                traversal(&mut context, depth_first, p[4], None, None),
+                vec![p[4]]
+            );
+        }
+        // Nor is there a network at all
+        let mut context = Context::new();
+        let person = context.add_person((Age, 1)).unwrap();
+        assert_eq!(
+            traversal(&mut context, false, person, None, None),
+            vec![person]
+        );
+    }
+
+    #[test]
+    fn bfs_visitor_changes_network() {
+        let (mut context, p) = setup_traversal();
+        let mut visited = Vec::new();
+        context.bfs_from::<EdgeType1>(p[0], None, |context, person| {
+            visited.push(person);
+            if person == p[1] {
+                // Cut off the edge from 3 before it's visited
+                context.remove_edge::<EdgeType1>(p[3], p[4]).unwrap();
+            }
+            true
+        });
+        assert_eq!(visited, vec![p[0], p[1], p[2], p[3]]);
+    }
+
+    #[test]
+    fn get_incoming_edges_no_network() {
+        let (mut context, person1, _) = setup();
+        assert!(incoming(&context, person1).is_empty());
+        context.enable_reverse_edges::<EdgeType1>();
+        context.enable_reverse_edges::<EdgeType1>();
+        assert!(incoming(&context, person1).is_empty());
+    }
+
+    #[test]
+    fn add_network_periodic_report() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        {
+            let mut context = Context::new();
+            context
+                .report_options()
+                .file_prefix("test_".to_string())
+                .directory(path.clone());
+            let person1 = context.add_person((Age, 1)).unwrap();
+            let person2 = context.add_person((Age, 2)).unwrap();
+            let person3 = context.add_person((Age, 3)).unwrap();
+            let person4 = context.add_person((Age, 4)).unwrap();
+            context
+                .add_network_periodic_report::<EdgeType1>("network", 1.0)
+                .unwrap();
+
+            // There are no edges until t=0.5.
+            context.add_plan(0.5, move |context| {
+                context
+                    .add_edge_bidi::<EdgeType1>(person1, person2, 1.0, 1)
+                    .unwrap();
+                context
+                    .add_edge::<EdgeType1>(person1, person3, 1.0, 1)
+                    .unwrap();
+                context
+                    .add_edge::<EdgeType1>(person1, person4, 1.0, 1)
+                    .unwrap();
+                // Edges of other types aren't counted.
+                context
+                    .add_edge::<EdgeType2>(person3, person4, 1.0, ())
+                    .unwrap();
+            });
+            context.add_plan(1.5, move |context| {
+                context.remove_person(person2).unwrap();
+            });
+            context.execute();
+        }
+
+        let mut reader = csv::Reader::from_path(path.join("test_network.csv")).unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec![
+                "t",
+                "edge_type",
+                "edges",
+                "mean_degree",
+                "min_degree",
+                "q25_degree",
+                "median_degree",
+                "q75_degree",
+                "max_degree"
+            ]
+        );
+        let rows: Vec<Vec<String>> = reader
+            .records()
+            .map(|record| record.unwrap().iter().map(String::from).collect())
+            .collect();
+        assert_eq!(rows.len(), 3);
+        let expected = [
+            (0.0, "0", 0.0, ["0", "0", "0", "0", "0"]),
+            (1.0, "4", 1.0, ["0", "0", "0", "1", "3"]),
+            (2.0, "2", 2.0 / 3.0, ["0", "0", "0", "2", "2"]),
+        ];
+        for (row, (t, edges, mean_degree, degrees)) in rows.iter().zip(expected) {
+            assert_eq!(row[0].parse::<f64>().unwrap(), t);
+            assert_eq!(row[1], "EdgeType1");
+            assert_eq!(row[2], edges);
+            assert_eq!(row[3].parse::<f64>().unwrap(), mean_degree);
+            assert_eq!(row[4..], degrees);
+        }
+    }
+
+    #[test]
+    fn network_periodic_report_empty_population() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        {
+            let mut context = Context::new();
+            context
+                .report_options()
+                .file_prefix("test_".to_string())
+                .directory(path.clone());
+            context
+                .add_network_periodic_report::<EdgeType2>("empty_network", 1.0)
+                .unwrap();
+            context.execute();
+        }
+
+        let mut reader = csv::Reader::from_path(path.join("test_empty_network.csv")).unwrap();
+        let rows: Vec<Vec<String>> = reader
+            .records()
+            .map(|record| record.unwrap().iter().map(String::from).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![vec![
+                "0.0",
+                "EdgeType2",
+                "0",
+                "0.0",
+                "0",
+                "0",
+                "0",
+                "0",
+                "0"
+            ]]
+        );
+    }
+
+    #[test]
+    fn undirected_edges() {
+        let mut context = Context::new();
+        let people: Vec<PersonId> = (0..3).map(|_| context.add_person(()).unwrap()).collect();
+        context
+            .add_edge::<Household>(people[1], people[0], 0.5, 7)
+            .unwrap();
+        context
+            .add_edge_bidi::<Household>(people[1], people[2], 2.0, 8)
+            .unwrap();
+        let result = context.add_edge::<Household>(people[0], people[1], 1.0, 9);
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+
+        // Each edge is stored once, with the lower id first.
+        assert_eq!(context.count_edges_total::<Household>(), 2);
+        let edge = context.get_edge::<Household>(people[0], people[1]).unwrap();
+        assert_eq!(
+            *edge,
+            Edge {
+                person: people[0],
+                neighbor: people[1],
+                weight: 0.5,
+                inner: 7
+            }
+        );
+        assert_eq!(
+            context.get_edge::<Household>(people[1], people[0]),
+            Some(edge)
+        );
+
+        // Both people see it as an edge of theirs.
+        let edges = context.get_edges::<Household>(people[1]);
+        assert_eq!(edges.len(), 2);
+        assert!(edges.iter().all(|edge| edge.person == people[1]));
+        assert_eq!(
+            edges.iter().map(|edge| edge.neighbor).collect::<Vec<_>>(),
+            [people[0], people[2]]
+        );
+        assert_eq!(context.count_outgoing_edges::<Household>(people[0]), 1);
+        assert_eq!(context.count_outgoing_edges::<Household>(people[1]), 2);
+        assert_eq!(
+            context.find_people_by_degree::<Household>(1),
+            [people[0], people[2]]
+        );
+        let incoming = context.get_incoming_edges::<Household>(people[0]);
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].person, people[1]);
+        assert_eq!(incoming[0].neighbor, people[0]);
+        let matching =
+            context.get_matching_edges::<Household>(people[2], |_, edge| edge.inner == 8);
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].neighbor, people[1]);
+
+        // Removing it from either end removes it.
+        context
+            .remove_edge::<Household>(people[2], people[1])
+            .unwrap();
+        assert!(context
+            .remove_edge::<Household>(people[1], people[2])
+            .is_err());
+        assert_eq!(context.count_edges_total::<Household>(), 1);
+        assert!(context.get_edges::<Household>(people[2]).is_empty());
+        assert_eq!(context.get_edges::<Household>(people[0])[0].weight, 0.5);
+
+        context.remove_person(people[0]).unwrap();
+        context.execute();
+        assert_eq!(context.count_edges_total::<Household>(), 0);
+        assert!(context.get_edges::<Household>(people[1]).is_empty());
+    }
+
+    define_rng!(UndirectedRng);
+
+    #[test]
+    fn undirected_edges_stay_symmetric() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let people: Vec<PersonId> = (0..30).map(|_| context.add_person(()).unwrap()).collect();
+        // The weights of the edges that should exist, by unordered pair
+        let mut expected = std::collections::HashMap::new();
+        for step in 0..3000 {
+            let a = people[context.sample_range(UndirectedRng, 0..people.len())];
+            let b = people[context.sample_range(UndirectedRng, 0..people.len())];
+            if a == b || !context.person_exists(a) || !context.person_exists(b) {
+                continue;
+            }
+            let key = (a.0.min(b.0), a.0.max(b.0));
+            match context.sample_range(UndirectedRng, 0..10) {
+                0..=5 => {
+                    let weight = f32::from(context.sample_range(UndirectedRng, 0..100_u8));
+                    let added = context.add_edge::<Household>(a, b, weight, step).is_ok();
+                    assert_eq!(added, expected.insert(key, weight).is_none());
+                    if !added {
+                        expected.insert(key, context.get_edge::<Household>(a, b).unwrap().weight);
+                    }
+                }
+                6..=8 => {
+                    let removed = context.remove_edge::<Household>(b, a).is_ok();
+                    assert_eq!(removed, expected.remove(&key).is_some());
+                }
+                _ => {
+                    context.remove_person(a).unwrap();
+                    context.execute();
+                    expected.retain(|&(low, high), _| low != a.0 && high != a.0);
+                }
+            }
+
+            assert_eq!(context.count_edges_total::<Household>(), expected.len());
+            for &x in &people {
+                for &y in &people {
+                    let forward = context.get_edge::<Household>(x, y).map(|edge| edge.weight);
+                    let backward = context.get_edge::<Household>(y, x).map(|edge| edge.weight);
+                    assert_eq!(forward, backward);
+                    assert_eq!(
+                        forward,
+                        expected.get(&(x.0.min(y.0), x.0.max(y.0))).copied()
+                    );
+                }
+                let degree = expected
+                    .keys()
+                    .filter(|&&(low, high)| low == x.0 || high == x.0)
+                    .count();
+                assert_eq!(context.count_outgoing_edges::<Household>(x), degree);
+                for edge in context.get_edges::<Household>(x) {
+                    assert_eq!(edge.person, x);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Random walks over a network, e.g., to follow a possible chain of
+//! transmission from a case. Each step picks an edge from the current
+//! person with [`select_random_edge()`](super::ContextNetworkExt::select_random_edge),
+//! so edges are chosen in proportion to their weights.
+use super::{ContextNetworkExt, EdgeType, NetworkPlugin};
+use crate::{Context, PersonId, RngId};
+use rand::Rng;
+use std::collections::HashSet;
+
+pub(super) fn random_walk<T: EdgeType + 'static, R: RngId + 'static>(
+    context: &Context,
+    rng_id: R,
+    start: PersonId,
+    steps: usize,
+    revisit: bool,
+) -> Vec<PersonId>
+where
+    R::RngType: Rng,
+{
+    let mut walk = vec![start];
+    let mut visited = HashSet::from([start]);
+    let Some(data) = context.get_data_container(NetworkPlugin) else {
+        return walk;
+    };
+    let allowed =
+        |visited: &HashSet<PersonId>, neighbor: &PersonId| revisit || !visited.contains(neighbor);
+    for _ in 0..steps {
+        let current = *walk.last().unwrap();
+        // Edges with no weight are never chosen, so stop if they're all
+        // that's left.
+        let can_move = data
+            .edges_ref::<T>(current)
+            .iter()
+            .any(|edge| edge.weight > 0.0 && allowed(&visited, &edge.neighbor));
+        if !can_move {
+            break;
+        }
+        // Choosing again when the edge goes back to someone already
+        // visited is the same as choosing among the other edges in
+        // proportion to their weights.
+        let next = loop {
+            let edge = context.select_random_edge::<T, R>(rng_id, current).unwrap();
+            if allowed(&visited, &edge.neighbor) {
+                break edge.neighbor;
+            }
+        };
+        visited.insert(next);
+        walk.push(next);
+    }
+    walk
+}
+
+#[cfg(test)]
+mod test {
+    use crate::network::{ContextNetworkExt, EdgeType};
+    use crate::{define_rng, Context, ContextPeopleExt, ContextRandomExt, PersonId};
+
+    define_rng!(WalkRng);
+
+    struct Contact;
+    impl EdgeType for Contact {
+        type Value = ();
+    }
+
+    fn setup(n: usize, edges: &[(usize, usize, f32)]) -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        context.init_random(42);
+        let people: Vec<PersonId> = (0..n).map(|_| context.add_person(()).unwrap()).collect();
+        for &(a, b, weight) in edges {
+            context
+                .add_edge::<Contact>(people[a], people[b], weight, ())
+                .unwrap();
+        }
+        (context, people)
+    }
+
+    #[test]
+    fn walk_stops_at_dead_end() {
+        let (context, people) = setup(4, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0)]);
+        let walk = context.random_walk::<Contact, _>(WalkRng, people[0], 2);
+        assert_eq!(walk, people[..3]);
+        let walk = context.random_walk::<Contact, _>(WalkRng, people[0], 10);
+        assert_eq!(walk, people);
+        let walk = context.random_walk::<Contact, _>(WalkRng, people[3], 10);
+        assert_eq!(walk, [people[3]]);
+        let walk = context.random_walk::<Contact, _>(WalkRng, people[1], 0);
+        assert_eq!(walk, [people[1]]);
+    }
+
+    #[test]
+    fn walk_without_network() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let person = context.add_person(()).unwrap();
+        assert_eq!(
+            context.random_walk::<Contact, _>(WalkRng, person, 5),
+            [person]
+        );
+    }
+
+    #[test]
+    fn revisiting_is_optional() {
+        // Two people linked both ways
+        let (context, people) = setup(2, &[(0, 1, 1.0), (1, 0, 1.0)]);
+        let walk = context.random_walk::<Contact, _>(WalkRng, people[0], 4);
+        assert_eq!(walk, people);
+        let walk = context.random_walk_with_revisits::<Contact, _>(WalkRng, people[0], 4);
+        assert_eq!(
+            walk,
+            [people[0], people[1], people[0], people[1], people[0]]
+        );
+    }
+
+    #[test]
+    fn walk_skips_visited_and_weightless_edges() {
+        // 1 can go back to 0, to 2 over an edge with no weight, or to 3.
+        let (context, people) = setup(4, &[(0, 1, 1.0), (1, 0, 100.0), (1, 2, 0.0), (1, 3, 1.0)]);
+        for _ in 0..20 {
+            let walk = context.random_walk::<Contact, _>(WalkRng, people[0], 5);
+            assert_eq!(walk, [people[0], people[1], people[3]]);
+        }
+    }
+
+    #[test]
+    fn steps_follow_edge_weights() {
+        // From 0, the edge to 1 is three times as likely as the edge to 2.
+        let (context, people) = setup(3, &[(0, 1, 3.0), (0, 2, 1.0)]);
+        let to_one = (0..4000)
+            .filter(|_| context.random_walk::<Contact, _>(WalkRng, people[0], 1)[1] == people[1])
+            .count();
+        assert!((2800..3200).contains(&to_one));
+    }
+}
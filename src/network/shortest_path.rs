@@ -0,0 +1,209 @@
+//! Weighted shortest paths with Dijkstra's algorithm, using edge weights as
+//! distances. Weights are never negative because
+//! [`add_edge()`](crate::ContextNetworkExt::add_edge) rejects them, which is
+//! what Dijkstra's algorithm requires.
+use super::{EdgeType, NetworkData};
+use crate::people::PersonId;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+// An entry in the queue of people to visit. The ordering is reversed so
+// that `BinaryHeap`, a max-heap, pops the closest person first, with ties
+// broken by `PersonId` so the path found doesn't depend on hashing.
+struct Pending {
+    distance: f32,
+    person: PersonId,
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then_with(|| other.person.0.cmp(&self.person.0))
+    }
+}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+// Returns the distance from `from` to `to` over edges of type T, and the
+// path if `record_path` is set, or `None` if `to` can't be reached.
+pub(super) fn shortest_path<T: EdgeType + 'static>(
+    data: Option<&NetworkData>,
+    from: PersonId,
+    to: PersonId,
+    record_path: bool,
+) -> Option<(f32, Vec<PersonId>)> {
+    let mut distances = HashMap::from([(from, 0.0)]);
+    let mut predecessors = HashMap::new();
+    let mut pending = BinaryHeap::from([Pending {
+        distance: 0.0,
+        person: from,
+    }]);
+
+    while let Some(Pending { distance, person }) = pending.pop() {
+        if person == to {
+            let mut path = Vec::new();
+            if record_path {
+                path.push(to);
+                let mut current = to;
+                while let Some(&previous) = predecessors.get(&current) {
+                    path.push(previous);
+                    current = previous;
+                }
+                path.reverse();
+            }
+            return Some((distance, path));
+        }
+        // Skip entries for people who were reached by a shorter path after
+        // they were queued.
+        if distance > distances[&person] {
+            continue;
+        }
+        let Some(data) = data else {
+            continue;
+        };
+        for edge in data.edges_ref::<T>(person).iter() {
+            let candidate = distance + edge.weight;
+            if distances
+                .get(&edge.neighbor)
+                .is_none_or(|&known| candidate < known)
+            {
+                distances.insert(edge.neighbor, candidate);
+                if record_path {
+                    predecessors.insert(edge.neighbor, person);
+                }
+                pending.push(Pending {
+                    distance: candidate,
+                    person: edge.neighbor,
+                });
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use crate::network::{ContextNetworkExt, EdgeType};
+    use crate::{Context, ContextPeopleExt, PersonId};
+
+    struct Road;
+    impl EdgeType for Road {
+        type Value = ();
+    }
+
+    struct Rail;
+    impl EdgeType for Rail {
+        type Value = ();
+    }
+
+    // 0 -> 1 -> 3 costs 1 + 5, 0 -> 2 -> 3 costs 2 + 2, and 0 -> 4 is free.
+    // 5 can't be reached.
+    fn setup() -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        let people: Vec<PersonId> = (0..6).map(|_| context.add_person(()).unwrap()).collect();
+        for (from, to, weight) in [
+            (0, 1, 1.0),
+            (1, 3, 5.0),
+            (0, 2, 2.0),
+            (2, 3, 2.0),
+            (0, 4, 0.0),
+        ] {
+            context
+                .add_edge::<Road>(people[from], people[to], weight, ())
+                .unwrap();
+        }
+        context
+            .add_edge::<Road>(people[5], people[0], 1.0, ())
+            .unwrap();
+        (context, people)
+    }
+
+    #[test]
+    fn shortest_path() {
+        let (context, people) = setup();
+        assert_eq!(
+            context.shortest_path::<Road>(people[0], people[3]),
+            Some((4.0, vec![people[0], people[2], people[3]]))
+        );
+        assert_eq!(
+            context.shortest_path::<Road>(people[5], people[3]),
+            Some((5.0, vec![people[5], people[0], people[2], people[3]]))
+        );
+        assert_eq!(
+            context.shortest_path_cost_only::<Road>(people[0], people[3]),
+            Some(4.0)
+        );
+    }
+
+    #[test]
+    fn zero_weight_edges() {
+        let (context, people) = setup();
+        assert_eq!(
+            context.shortest_path::<Road>(people[0], people[4]),
+            Some((0.0, vec![people[0], people[4]]))
+        );
+    }
+
+    #[test]
+    fn same_person() {
+        let (context, people) = setup();
+        assert_eq!(
+            context.shortest_path::<Road>(people[3], people[3]),
+            Some((0.0, vec![people[3]]))
+        );
+        assert_eq!(
+            context.shortest_path_cost_only::<Road>(people[3], people[3]),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        let (context, people) = setup();
+        // Edges are directed.
+        assert_eq!(context.shortest_path::<Road>(people[3], people[0]), None);
+        assert_eq!(context.shortest_path::<Road>(people[0], people[5]), None);
+        assert_eq!(
+            context.shortest_path_cost_only::<Road>(people[0], people[5]),
+            None
+        );
+        // Only edges of the given type are followed.
+        assert_eq!(context.shortest_path::<Rail>(people[0], people[3]), None);
+        assert_eq!(
+            Context::new().shortest_path::<Road>(people[0], people[1]),
+            None
+        );
+    }
+
+    #[test]
+    fn shorter_path_found_later() {
+        // The direct edge is found first but the path with more edges is
+        // shorter.
+        let mut context = Context::new();
+        let people: Vec<PersonId> = (0..4).map(|_| context.add_person(()).unwrap()).collect();
+        for (from, to, weight) in [(0, 3, 10.0), (0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0)] {
+            context
+                .add_edge::<Road>(people[from], people[to], weight, ())
+                .unwrap();
+        }
+        assert_eq!(
+            context.shortest_path::<Road>(people[0], people[3]),
+            Some((3.0, people.clone()))
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! Read-only access to a `Context` for modules that only observe the
+//! simulation.
+//!
+//! Reporting and analysis modules shouldn't change the state of the
+//! simulation, but a handler that gets a `&mut Context` can do anything.
+//! A [`ReadOnlyContext`] is a view of a `Context` that only has the
+//! methods that read it: the current time and execution statistics,
+//! global properties and modes, people's properties, queries and
+//! tabulations, edges of networks, and sending reports. Setting
+//! properties, adding people or edges and scheduling plans aren't
+//! available, so an observer that tries to fails to compile:
+//!
+//! ```compile_fail
+//! use ixa::{define_person_property, Context, ContextPeopleExt, PersonCreatedEvent};
+//!
+//! define_person_property!(Age, u8);
+//!
+//! let mut context = Context::new();
+//! context.subscribe_observer(|context, event: &PersonCreatedEvent| {
+//!     context.set_person_property(event.person_id, Age, 0);
+//! });
+//! ```
+//!
+//! Observers can't sample random numbers either. Drawing from a stream
+//! would change what the rest of the simulation draws from it, so the
+//! results would depend on whether the observer is there.
+//!
+//! Get a view with [`Context::as_read_only()`], or have one passed to a
+//! handler with [`Context::subscribe_observer()`] or to a plan with
+//! [`Context::add_observer_plan()`]:
+//!
+//! ```
+//! use ixa::{define_person_property, Context, ContextPeopleExt, PersonCreatedEvent};
+//!
+//! define_person_property!(Age, u8);
+//!
+//! let mut context = Context::new();
+//! context.subscribe_observer(|context, event: &PersonCreatedEvent| {
+//!     let age = context.get_person_property(event.person_id, Age);
+//!     println!("{age} year old added at {}", context.get_current_time());
+//! });
+//! context.add_observer_plan(10.0, |context| {
+//!     println!("{} adults", context.query_people_count(((Age, 18),)));
+//! });
+//! ```
+//!
+//! The view borrows the `Context`, so it doesn't copy anything. Data
+//! plugins are available with [`ReadOnlyContext::get_data_container()`];
+//! a plugin that uses interior mutability can still be changed through
+//! it, so such plugins should only expose methods that read.
+use crate::context::{Context, DataPlugin, ExecutionPhase};
+use crate::error::IxaError;
+use crate::execution_stats::ExecutionStatistics;
+use crate::global_properties::{ContextGlobalPropertiesExt, GlobalProperty};
+use crate::modes::{ContextModeExt, SimulationMode};
+use crate::network::{ContextNetworkExt, Edge, EdgeType};
+use crate::people::{
+    ContextPeopleExt, NumericValue, PersonId, PersonProperty, PropertySummary, Query,
+};
+use crate::report::{ContextReportExt, Report};
+use crate::tabulator::{TabulationKey, Tabulator};
+use std::collections::HashMap;
+use std::ops::RangeBounds;
+
+/// A read-only view of a [`Context`]. See the [module documentation](self).
+#[derive(Clone, Copy)]
+pub struct ReadOnlyContext<'a> {
+    context: &'a Context,
+}
+
+impl<'a> ReadOnlyContext<'a> {
+    pub(crate) fn new(context: &'a Context) -> Self {
+        ReadOnlyContext { context }
+    }
+
+    /// See [`Context::get_current_time()`].
+    #[must_use]
+    pub fn get_current_time(&self) -> f64 {
+        self.context.get_current_time()
+    }
+
+    /// See [`Context::get_current_phase()`].
+    #[must_use]
+    pub fn get_current_phase(&self) -> Option<ExecutionPhase> {
+        self.context.get_current_phase()
+    }
+
+    /// See [`Context::execution_statistics()`].
+    #[must_use]
+    pub fn execution_statistics(&self) -> ExecutionStatistics {
+        self.context.execution_statistics()
+    }
+
+    /// See [`Context::get_data_container()`].
+    #[must_use]
+    pub fn get_data_container<T: DataPlugin>(
+        &self,
+        data_plugin: T,
+    ) -> Option<&'a T::DataContainer> {
+        self.context.get_data_container(data_plugin)
+    }
+
+    /// See [`Context::get_global_property_value()`].
+    #[must_use]
+    pub fn get_global_property_value<T: GlobalProperty + 'static>(
+        &self,
+        property: T,
+    ) -> Option<&'a T::Value> {
+        self.context.get_global_property_value(property)
+    }
+
+    /// See [`Context::mode()`].
+    #[must_use]
+    pub fn mode<M: SimulationMode>(&self, mode: M) -> M::Value {
+        self.context.mode(mode)
+    }
+
+    /// See [`Context::get_current_population()`].
+    #[must_use]
+    pub fn get_current_population(&self) -> usize {
+        self.context.get_current_population()
+    }
+
+    /// See [`Context::person_exists()`].
+    #[must_use]
+    pub fn person_exists(&self, person_id: PersonId) -> bool {
+        self.context.person_exists(person_id)
+    }
+
+    /// See [`Context::get_person_property()`].
+    ///
+    /// # Panics
+    /// Panics if the person has been removed.
+    #[must_use]
+    pub fn get_person_property<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        property: T,
+    ) -> T::Value {
+        self.context.get_person_property(person_id, property)
+    }
+
+    /// See [`Context::try_get_person_property()`].
+    ///
+    /// # Errors
+    /// Returns [`IxaError::PersonNotFound`] if the person doesn't exist or
+    /// has been removed.
+    pub fn try_get_person_property<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        property: T,
+    ) -> Result<T::Value, IxaError> {
+        self.context.try_get_person_property(person_id, property)
+    }
+
+    /// See [`Context::query_people()`].
+    #[must_use]
+    pub fn query_people<T: Query>(&self, q: T) -> Vec<PersonId> {
+        self.context.query_people(q)
+    }
+
+    /// See [`Context::query_people_count()`].
+    #[must_use]
+    pub fn query_people_count<T: Query>(&self, q: T) -> usize {
+        self.context.query_people_count(q)
+    }
+
+    /// See [`Context::query_people_range()`].
+    #[must_use]
+    pub fn query_people_range<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+        range: impl RangeBounds<T::Value>,
+    ) -> Vec<PersonId>
+    where
+        T::Value: PartialOrd,
+    {
+        self.context.query_people_range(property, range)
+    }
+
+    /// See [`Context::query_people_any_value()`].
+    #[must_use]
+    pub fn query_people_any_value<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+        values: &[T::Value],
+    ) -> Vec<PersonId> {
+        self.context.query_people_any_value(property, values)
+    }
+
+    /// See [`Context::match_person()`].
+    #[must_use]
+    pub fn match_person<T: Query>(&self, person_id: PersonId, q: T) -> bool {
+        self.context.match_person(person_id, q)
+    }
+
+    /// See [`Context::tabulate_person_properties()`].
+    pub fn tabulate_person_properties<T: Tabulator, F>(&self, tabulator: &T, print_fn: F)
+    where
+        F: Fn(&ReadOnlyContext, &[String], usize),
+    {
+        self.context
+            .tabulate_person_properties(tabulator, |context, values, count| {
+                print_fn(&ReadOnlyContext::new(context), values, count);
+            });
+    }
+
+    /// See [`Context::tabulate_person_count()`].
+    #[must_use]
+    pub fn tabulate_person_count<T: TabulationKey>(&self) -> HashMap<T::Values, usize>
+    where
+        T::Values: Eq,
+    {
+        self.context.tabulate_person_count::<T>()
+    }
+
+    /// See [`Context::distinct_property_values()`].
+    #[must_use]
+    pub fn distinct_property_values<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+    ) -> Vec<(T::Value, usize)> {
+        self.context.distinct_property_values(property)
+    }
+
+    /// See [`Context::property_summary()`].
+    ///
+    /// # Panics
+    /// Panics if a property has no value and no initializer for some person.
+    #[must_use]
+    pub fn property_summary<T, Q>(&self, property: T, query: Q) -> PropertySummary
+    where
+        T: PersonProperty + 'static,
+        T::Value: NumericValue,
+        Q: Query,
+    {
+        self.context.property_summary(property, query)
+    }
+
+    /// See [`Context::property_summary_by()`].
+    ///
+    /// # Panics
+    /// Panics if a property has no value and no initializer for some person.
+    #[must_use]
+    pub fn property_summary_by<T, G, Q>(
+        &self,
+        property: T,
+        group: G,
+        query: Q,
+    ) -> Vec<(G::Value, PropertySummary)>
+    where
+        T: PersonProperty + 'static,
+        T::Value: NumericValue,
+        G: PersonProperty + 'static,
+        Q: Query,
+    {
+        self.context.property_summary_by(property, group, query)
+    }
+
+    /// See [`Context::get_edges()`].
+    #[must_use]
+    pub fn get_edges<T: EdgeType + 'static>(&self, person: PersonId) -> Vec<Edge<T::Value>> {
+        self.context.get_edges::<T>(person)
+    }
+
+    /// See [`Context::get_matching_edges()`].
+    #[must_use]
+    pub fn get_matching_edges<T: EdgeType + 'static>(
+        &self,
+        person: PersonId,
+        filter: impl Fn(&ReadOnlyContext, &Edge<T::Value>) -> bool + 'static,
+    ) -> Vec<Edge<T::Value>> {
+        self.context
+            .get_matching_edges::<T>(person, move |context, edge| {
+                filter(&ReadOnlyContext::new(context), edge)
+            })
+    }
+
+    /// See [`Context::count_outgoing_edges()`].
+    #[must_use]
+    pub fn count_outgoing_edges<T: EdgeType + 'static>(&self, person: PersonId) -> usize {
+        self.context.count_outgoing_edges::<T>(person)
+    }
+
+    /// See [`Context::count_edges_total()`].
+    #[must_use]
+    pub fn count_edges_total<T: EdgeType + 'static>(&self) -> usize {
+        self.context.count_edges_total::<T>()
+    }
+
+    /// See [`Context::send_report()`].
+    pub fn send_report<T: Report>(&self, report: T) {
+        self.context.send_report(report);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::global_properties::ContextGlobalPropertiesExt;
+    use crate::network::ContextNetworkExt;
+    use crate::people::ContextPeopleExt;
+    use crate::report::{ContextReportExt, Report};
+    use crate::{
+        create_report_trait, define_edge_type, define_global_property, define_person_property,
+        Context, PersonCreatedEvent, PersonPropertyChangeEvent,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    define_person_property!(Age, u8);
+    define_global_property!(AdultAge, u8);
+    define_edge_type!(Friends, ());
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct AdultCount {
+        time: f64,
+        adults: usize,
+    }
+    create_report_trait!(AdultCount);
+
+    #[test]
+    fn observer_queries_and_sends_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut context = Context::new();
+        context.report_options().directory(dir.path().to_path_buf());
+        context.add_report::<AdultCount>("adults").unwrap();
+        context.set_global_property_value(AdultAge, 18).unwrap();
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let seen_clone = Rc::clone(&seen);
+        context.subscribe_observer(move |context, event: &PersonCreatedEvent| {
+            let age = context.get_person_property(event.person_id, Age);
+            seen_clone
+                .borrow_mut()
+                .push((context.get_current_time(), age));
+        });
+        for time in [1.0, 2.0] {
+            context.add_observer_plan(time, |context| {
+                let adult_age = *context.get_global_property_value(AdultAge).unwrap();
+                context.send_report(AdultCount {
+                    time: context.get_current_time(),
+                    adults: context.query_people_range(Age, adult_age..).len(),
+                });
+            });
+        }
+        context.add_plan(0.5, |context| {
+            context.add_person((Age, 30)).unwrap();
+            context.add_person((Age, 10)).unwrap();
+        });
+        context.add_plan(1.5, |context| {
+            let child = context.query_people(((Age, 10),))[0];
+            context.set_person_property(child, Age, 20);
+        });
+        context.execute();
+
+        assert_eq!(*seen.borrow(), [(0.5, 30), (0.5, 10)]);
+        drop(context);
+        let mut reader = csv::Reader::from_path(dir.path().join("adults.csv")).unwrap();
+        let rows: Vec<AdultCount> = reader.deserialize().map(Result::unwrap).collect();
+        assert_eq!(
+            rows,
+            [
+                AdultCount {
+                    time: 1.0,
+                    adults: 1
+                },
+                AdultCount {
+                    time: 2.0,
+                    adults: 2
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn observer_handler_can_keep_state() {
+        let mut context = Context::new();
+        let changes = Rc::new(RefCell::new(0));
+        let changes_clone = Rc::clone(&changes);
+        let mut count = 0;
+        context.subscribe_observer(move |context, event: &PersonPropertyChangeEvent<Age>| {
+            assert!(context.person_exists(event.person_id));
+            assert_eq!(event.current, event.previous + 1);
+            count += 1;
+            *changes_clone.borrow_mut() = count;
+        });
+        let person = context.add_person((Age, 1)).unwrap();
+        context.set_person_property(person, Age, 2);
+        context.set_person_property(person, Age, 3);
+        context.execute();
+        assert_eq!(*changes.borrow(), 2);
+    }
+
+    #[test]
+    fn read_only_view_matches_context() {
+        let mut context = Context::new();
+        let person1 = context.add_person((Age, 30)).unwrap();
+        let person2 = context.add_person((Age, 40)).unwrap();
+        context
+            .add_edge::<Friends>(person1, person2, 1.0, ())
+            .unwrap();
+
+        let view = context.as_read_only();
+        assert_eq!(view.get_current_population(), 2);
+        assert!(view.person_exists(person2));
+        assert!(view.match_person(person1, ((Age, 30),)));
+        assert_eq!(view.query_people_any_value(Age, &[30, 40]).len(), 2);
+        assert_eq!(view.count_edges_total::<Friends>(), 1);
+        assert_eq!(view.count_outgoing_edges::<Friends>(person1), 1);
+        let older = view.get_matching_edges::<Friends>(person1, |context, edge| {
+            context.get_person_property(edge.neighbor, Age) > 35
+        });
+        assert_eq!(older.len(), 1);
+        assert_eq!(view.property_summary(Age, ()).count, 2);
+    }
+}
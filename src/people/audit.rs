@@ -0,0 +1,386 @@
+use crate::context::ExecutionPhase;
+use crate::{Context, PersonId};
+use std::fmt;
+use std::panic::Location;
+use std::rc::Rc;
+
+#[cfg(debug_assertions)]
+use crate::people::PeoplePlugin;
+#[cfg(debug_assertions)]
+use crate::{debug, PersonProperty};
+#[cfg(debug_assertions)]
+use std::any::TypeId;
+
+type TrapCallback = dyn Fn(&Context, &PropertyAccess);
+
+/// Whether a [`PropertyAccess`] read or wrote the property
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// A read or write of a person property reported by an audit (see
+/// [`ContextPeopleExt::audit_property_reads()`](crate::ContextPeopleExt::audit_property_reads))
+#[derive(Clone, Debug)]
+pub struct PropertyAccess {
+    pub kind: AccessKind,
+    /// The name of the property
+    pub property: &'static str,
+    pub person_id: PersonId,
+    /// The value that was read, or the new value for writes, formatted
+    /// with `Debug`
+    pub value: String,
+    /// The simulation time of the access
+    pub time: f64,
+    /// The phase of the plan that was running (see
+    /// [`Context::get_current_phase()`])
+    pub phase: Option<ExecutionPhase>,
+    /// Where [`get_person_property()`](crate::ContextPeopleExt::get_person_property)
+    /// or [`set_person_property()`](crate::ContextPeopleExt::set_person_property)
+    /// was called. Reads made by Ixa on
+    /// the caller's behalf, e.g., by queries, point inside Ixa.
+    pub location: &'static Location<'static>,
+}
+
+impl fmt::Display for PropertyAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Read => "read",
+            AccessKind::Write => "write",
+        };
+        write!(
+            f,
+            "{kind} of {} for {:?} ({}) at t={}",
+            self.property, self.person_id, self.value, self.time
+        )?;
+        match self.phase {
+            Some(phase) => write!(f, " in {phase:?} phase")?,
+            None => write!(f, " outside the event loop")?,
+        }
+        write!(f, ", from {}", self.location)
+    }
+}
+
+/// What to do when an audited property is accessed
+#[derive(Clone)]
+pub enum AuditAction {
+    /// Log each access at debug level
+    Log,
+    /// Call the trap's callback for the accesses that match its filters
+    Trap(AuditTrap),
+}
+
+/// A callback for accesses to an audited property, optionally limited to
+/// a window of time or to one person.
+///
+/// The callback can't change the simulation. To stop in a debugger when the
+/// property is accessed, set a breakpoint in the callback.
+#[derive(Clone)]
+#[cfg_attr(not(debug_assertions), allow(dead_code))]
+pub struct AuditTrap {
+    start: f64,
+    end: f64,
+    person_id: Option<PersonId>,
+    callback: Rc<TrapCallback>,
+}
+
+impl AuditTrap {
+    /// A trap that calls `callback` for every access
+    pub fn new(callback: impl Fn(&Context, &PropertyAccess) + 'static) -> Self {
+        AuditTrap {
+            start: f64::NEG_INFINITY,
+            end: f64::INFINITY,
+            person_id: None,
+            callback: Rc::new(callback),
+        }
+    }
+
+    /// Only call the callback for accesses from time `start` to `end`,
+    /// inclusive
+    #[must_use]
+    pub fn between(mut self, start: f64, end: f64) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    /// Only call the callback for accesses to the property of `person_id`
+    #[must_use]
+    pub fn for_person(mut self, person_id: PersonId) -> Self {
+        self.person_id = Some(person_id);
+        self
+    }
+
+    #[cfg(debug_assertions)]
+    fn matches(&self, access: &PropertyAccess) -> bool {
+        (self.start..=self.end).contains(&access.time)
+            && self
+                .person_id
+                .is_none_or(|person_id| person_id == access.person_id)
+    }
+}
+
+// The audits of one property
+#[cfg(debug_assertions)]
+#[derive(Default)]
+pub(super) struct PropertyAudit {
+    reads: Option<AuditAction>,
+    writes: Option<AuditAction>,
+}
+
+#[cfg(debug_assertions)]
+pub(super) fn set_audit<T: PersonProperty + 'static>(
+    context: &mut Context,
+    kind: AccessKind,
+    action: AuditAction,
+) {
+    let audit = context
+        .get_data_container_mut(PeoplePlugin)
+        .audits
+        .entry(TypeId::of::<T>())
+        .or_default();
+    match kind {
+        AccessKind::Read => audit.reads = Some(action),
+        AccessKind::Write => audit.writes = Some(action),
+    }
+}
+
+#[cfg(debug_assertions)]
+pub(super) fn remove_audit<T: PersonProperty + 'static>(context: &mut Context) {
+    context
+        .get_data_container_mut(PeoplePlugin)
+        .audits
+        .remove(&TypeId::of::<T>());
+}
+
+// Reports an access to property T if it is audited.
+#[cfg(debug_assertions)]
+pub(super) fn audit_access<T: PersonProperty + 'static>(
+    context: &Context,
+    person_id: PersonId,
+    kind: AccessKind,
+    value: &T::Value,
+    location: &'static Location<'static>,
+) {
+    let Some(data_container) = context.get_data_container(PeoplePlugin) else {
+        return;
+    };
+    if data_container.audits.is_empty() {
+        return;
+    }
+    let Some(action) = data_container
+        .audits
+        .get(&TypeId::of::<T>())
+        .and_then(|audit| match kind {
+            AccessKind::Read => audit.reads.as_ref(),
+            AccessKind::Write => audit.writes.as_ref(),
+        })
+    else {
+        return;
+    };
+
+    let access = PropertyAccess {
+        kind,
+        property: T::name(),
+        person_id,
+        value: format!("{value:?}"),
+        time: context.get_current_time(),
+        phase: context.get_current_phase(),
+        location,
+    };
+    match action {
+        AuditAction::Log => debug!("Audit: {access}"),
+        AuditAction::Trap(trap) => {
+            if trap.matches(&access) {
+                (trap.callback)(context, &access);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::{AuditAction, AuditTrap, PropertyAccess};
+    use crate::{define_person_property, Context, ContextPeopleExt, PersonId};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    define_person_property!(Age, u8);
+
+    // Returns a context with people of ages 10, 20 and 30, and the
+    // accesses reported to a trap with `filter` applied.
+    fn setup_trap(
+        filter: impl FnOnce(AuditTrap) -> AuditTrap,
+    ) -> (Context, Rc<RefCell<Vec<PropertyAccess>>>) {
+        let mut context = Context::new();
+        for age in [10, 20, 30] {
+            context.add_person((Age, age)).unwrap();
+        }
+        let accesses = Rc::new(RefCell::new(Vec::new()));
+        let accesses_clone = Rc::clone(&accesses);
+        let trap = filter(AuditTrap::new(move |_, access| {
+            accesses_clone.borrow_mut().push(access.clone());
+        }));
+        context.audit_property_reads(Age, AuditAction::Trap(trap));
+        (context, accesses)
+    }
+
+    // Reads everyone's age at times 1, 2 and 3.
+    fn read_ages(context: &mut Context) {
+        for t in 1..=3 {
+            context.add_plan(f64::from(t), |context| {
+                for id in 0..3 {
+                    context.get_person_property(PersonId(id), Age);
+                }
+            });
+        }
+        context.execute();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn trap_reports_reads() {
+        use super::AccessKind;
+        use crate::ExecutionPhase;
+
+        let (mut context, accesses) = setup_trap(|trap| trap);
+        let line = line!() + 1;
+        let age = context.get_person_property(PersonId(1), Age);
+        assert_eq!(age, 20);
+        context.add_plan_with_phase(
+            2.0,
+            |context| {
+                context.get_person_property(PersonId(2), Age);
+            },
+            ExecutionPhase::Last,
+        );
+        context.execute();
+
+        let accesses = accesses.borrow();
+        assert_eq!(accesses.len(), 2);
+        assert_eq!(accesses[0].kind, AccessKind::Read);
+        assert_eq!(accesses[0].property, "Age");
+        assert_eq!(accesses[0].person_id, PersonId(1));
+        assert_eq!(accesses[0].value, "20");
+        assert_eq!(accesses[0].time, 0.0);
+        assert_eq!(accesses[0].phase, None);
+        assert_eq!(accesses[0].location.file(), file!());
+        assert_eq!(accesses[0].location.line(), line);
+        assert_eq!(accesses[1].person_id, PersonId(2));
+        assert_eq!(accesses[1].time, 2.0);
+        assert_eq!(accesses[1].phase, Some(ExecutionPhase::Last));
+        assert_eq!(
+            accesses[0].to_string(),
+            format!(
+                "read of Age for Person 1 (20) at t=0 outside the event loop, from {}",
+                accesses[0].location
+            )
+        );
+        assert_eq!(
+            accesses[1].to_string(),
+            format!(
+                "read of Age for Person 2 (30) at t=2 in Last phase, from {}",
+                accesses[1].location
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn trap_filters() {
+        let (mut context, accesses) =
+            setup_trap(|trap| trap.between(2.0, 3.0).for_person(PersonId(1)));
+        read_ages(&mut context);
+        let accesses = accesses.borrow();
+        assert_eq!(
+            accesses
+                .iter()
+                .map(|access| (access.time, access.person_id))
+                .collect::<Vec<_>>(),
+            vec![(2.0, PersonId(1)), (3.0, PersonId(1))]
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn audit_writes() {
+        use super::AccessKind;
+
+        let (mut context, reads) = setup_trap(|trap| trap);
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        let writes_clone = Rc::clone(&writes);
+        context.audit_property_writes(
+            Age,
+            AuditAction::Trap(AuditTrap::new(move |context, access| {
+                // Writes are reported before the value changes.
+                let current = context.get_person_property(access.person_id, Age);
+                writes_clone
+                    .borrow_mut()
+                    .push((access.kind, access.value.clone(), current));
+            })),
+        );
+        context.set_person_property(PersonId(0), Age, 11);
+        assert_eq!(
+            *writes.borrow(),
+            vec![(AccessKind::Write, "11".to_string(), 10)]
+        );
+        // Only the read by the trap is reported; the read by
+        // set_person_property() itself isn't.
+        assert_eq!(reads.borrow().len(), 1);
+    }
+
+    #[test]
+    fn stop_auditing_property() {
+        let (mut context, accesses) = setup_trap(|trap| trap);
+        context.stop_auditing_property(Age);
+        read_ages(&mut context);
+        assert!(accesses.borrow().is_empty());
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn no_audits_in_release_builds() {
+        let (mut context, accesses) = setup_trap(|trap| trap);
+        read_ages(&mut context);
+        context.set_person_property(PersonId(0), Age, 11);
+        assert!(accesses.borrow().is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn log_reads() {
+        use crate::{set_log_level, ContextLogExt, LevelFilter};
+
+        let _guard = crate::log::TEST_MUTEX.lock().expect("Mutex poisoned");
+        set_log_level(LevelFilter::Trace);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.log");
+        {
+            let mut context = Context::new();
+            context.set_log_file(path.clone()).unwrap();
+            context.add_person((Age, 10)).unwrap();
+            context.audit_property_reads(Age, AuditAction::Log);
+            for t in 1..=3 {
+                context.add_plan(f64::from(t), |context| {
+                    context.get_person_property(PersonId(0), Age);
+                });
+            }
+            context.execute();
+        }
+        let log = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = log.lines().filter(|line| line.contains("Audit:")).collect();
+        assert_eq!(lines.len(), 3);
+        for (t, line) in (1..=3).zip(lines) {
+            assert!(line.contains(" DEBUG "), "{line}");
+            assert!(
+                line.contains(&format!(
+                    "Audit: read of Age for Person 0 (10) at t={t} in Normal phase, from {}:",
+                    file!()
+                )),
+                "{line}"
+            );
+        }
+    }
+}
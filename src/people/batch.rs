@@ -0,0 +1,397 @@
+use crate::people::context_extension::{ContextPeopleExt, ContextPeopleExtInternal};
+use crate::people::index::IndexValue;
+use crate::people::{staged, PeoplePlugin};
+use crate::state_machine;
+use crate::{Context, PersonId, PersonProperty, PersonPropertyChangeEvent};
+use std::any::TypeId;
+use std::collections::HashMap;
+
+#[cfg(debug_assertions)]
+use crate::people::audit::{self, AccessKind};
+
+// The people in `people` whose values of `T` are about to change, grouped
+// by their current value. Consecutive people usually have the same value,
+// so each run of them only has its value hashed once.
+fn group_by_value<T: PersonProperty>(
+    people: &[PersonId],
+    previous: &[T::Value],
+    value: T::Value,
+    max_indexed: usize,
+) -> Vec<(IndexValue, Vec<PersonId>)> {
+    let mut groups: Vec<(IndexValue, Vec<PersonId>)> = Vec::new();
+    let mut positions: HashMap<IndexValue, usize> = HashMap::new();
+    let mut last: Option<(T::Value, usize)> = None;
+    for (&person_id, &old) in people.iter().zip(previous) {
+        // People who haven't been indexed yet are indexed with their new
+        // value when they are.
+        if old == value || person_id.0 >= max_indexed {
+            continue;
+        }
+        let position = match last {
+            Some((last_value, position)) if last_value == old => position,
+            _ => {
+                let hash = IndexValue::compute(&old);
+                let position = *positions.entry(hash.clone()).or_insert_with(|| {
+                    groups.push((hash, Vec::new()));
+                    groups.len() - 1
+                });
+                last = Some((old, position));
+                position
+            }
+        };
+        groups[position].1.push(person_id);
+    }
+    groups
+}
+
+// Moves the people whose values are changing from their entries in the
+// index of `T` to the entry for `value`, one entry at a time, and likewise
+// their counts.
+fn move_in_index<T: PersonProperty + 'static>(
+    context: &Context,
+    people: &[PersonId],
+    previous: &[T::Value],
+    value: T::Value,
+) {
+    let data_container = context.get_data_container(PeoplePlugin).unwrap();
+    let Some(mut index) = data_container.get_index_ref_mut(TypeId::of::<T>()) else {
+        return;
+    };
+    if index.lookup.is_none() && index.counts.is_none() {
+        return;
+    }
+    let groups = group_by_value::<T>(people, previous, value, index.max_indexed);
+    let new_hash = IndexValue::compute(&value);
+    for (hash, group) in groups {
+        index.move_counts(&hash, &new_hash, || format!("{value:?}"), group.len());
+        let Some(lookup) = index.lookup.as_mut() else {
+            continue;
+        };
+        let Some(entry) = lookup.get_mut(&hash) else {
+            continue;
+        };
+        // When everyone with a value is changing, e.g., everyone who was
+        // unvaccinated, the entry can be moved rather than emptied.
+        let moved = if group.len() == entry.1.len() {
+            lookup.remove(&hash).unwrap().1
+        } else {
+            for person_id in &group {
+                entry.1.swap_remove(person_id);
+            }
+            group.into_iter().collect()
+        };
+        match lookup.get_mut(&new_hash) {
+            Some(entry) if entry.1.len() >= moved.len() => entry.1.extend(moved),
+            Some(entry) => {
+                let existing = std::mem::replace(&mut entry.1, moved);
+                entry.1.extend(existing);
+            }
+            None => {
+                lookup.insert(new_hash.clone(), (format!("{value:?}"), moved));
+            }
+        }
+    }
+}
+
+// Whether `T` has a custom index, which is updated one person at a time
+fn has_custom_index<T: PersonProperty + 'static>(context: &Context) -> bool {
+    context
+        .get_data_container(PeoplePlugin)
+        .unwrap()
+        .get_index_ref(TypeId::of::<T>())
+        .is_some_and(|index| index.custom.is_some())
+}
+
+// The current values of `T` for `people`, initializing any that haven't
+// been yet.
+fn current_values<T: PersonProperty + 'static>(
+    context: &Context,
+    people: &[PersonId],
+) -> Vec<T::Value> {
+    let data_container = context.get_data_container(PeoplePlugin).unwrap();
+    let uninitialized: Vec<PersonId> = {
+        let values = data_container.get_property_values::<T>();
+        people
+            .iter()
+            .filter(|person_id| values.get(person_id.0).copied().flatten().is_none())
+            .copied()
+            .collect()
+    };
+    for person_id in uninitialized {
+        context.get_person_property_unaudited(person_id, T::get_instance());
+    }
+    let values = data_container.get_property_values::<T>();
+    people
+        .iter()
+        .map(|person_id| values[person_id.0].unwrap())
+        .collect()
+}
+
+#[cfg_attr(debug_assertions, track_caller)]
+pub(super) fn update_people<T: PersonProperty + 'static>(
+    context: &mut Context,
+    people: &[PersonId],
+    value: T::Value,
+) {
+    assert!(!T::is_derived(), "Cannot set a derived property");
+    state_machine::check_direct_set::<T>(context);
+    if people.is_empty() {
+        return;
+    }
+    context.register_property::<T>();
+    for &person_id in people {
+        assert!(
+            context.person_exists(person_id),
+            "Can't set {} of {person_id:?}, who has been removed",
+            T::name()
+        );
+        #[cfg(debug_assertions)]
+        audit::audit_access::<T>(
+            context,
+            person_id,
+            AccessKind::Write,
+            &value,
+            std::panic::Location::caller(),
+        );
+    }
+    if staged::has_staged_changes::<T>(context) {
+        for &person_id in people {
+            staged::property_set::<T>(context, person_id);
+        }
+    }
+    // Everyone gets the same value, so it only needs to be checked once.
+    context
+        .get_data_container_mut(PeoplePlugin)
+        .check_finite::<T>(people[0], &value, cfg!(debug_assertions));
+
+    let previous = current_values::<T>(context, people);
+
+    // Derived properties that depend on `T` record their values before the
+    // change, as in `set_person_property()`.
+    let dependencies = context
+        .get_data_container(PeoplePlugin)
+        .unwrap()
+        .dependency_map
+        .borrow_mut()
+        .get_mut(&TypeId::of::<T>())
+        .map(std::mem::take);
+    let mut dependency_event_callbacks = Vec::new();
+    if let Some(mut dependencies) = dependencies {
+        for &person_id in people {
+            for dependency in &mut dependencies {
+                dependency.dependency_changed(context, person_id, &mut dependency_event_callbacks);
+            }
+        }
+        let data_container = context.get_data_container(PeoplePlugin).unwrap();
+        data_container
+            .dependency_map
+            .borrow_mut()
+            .insert(TypeId::of::<T>(), dependencies);
+    }
+
+    let custom_index = has_custom_index::<T>(context);
+    if custom_index {
+        for (&person_id, &old) in people.iter().zip(&previous) {
+            if old != value {
+                context.remove_from_index_maybe(person_id, T::get_instance());
+            }
+        }
+    } else {
+        move_in_index::<T>(context, people, &previous, value);
+    }
+
+    {
+        let data_container = context.get_data_container(PeoplePlugin).unwrap();
+        let mut values = data_container.get_property_values::<T>();
+        for person_id in people {
+            values[person_id.0] = Some(value);
+        }
+    }
+
+    let notify_changes = context
+        .get_data_container(PeoplePlugin)
+        .unwrap()
+        .get_index_ref(TypeId::of::<T>())
+        .is_some_and(|index| index.notify_changes);
+    for (&person_id, &old) in people.iter().zip(&previous) {
+        if old != value {
+            if custom_index {
+                context.add_to_index_maybe(person_id, T::get_instance());
+            }
+            if notify_changes {
+                context.emit_index_change::<T>(person_id, Some(old), Some(value));
+            }
+        }
+        context.emit_event(PersonPropertyChangeEvent::<T> {
+            person_id,
+            current: value,
+            previous: old,
+        });
+    }
+
+    for callback in dependency_event_callbacks {
+        callback(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::people::{IndexChange, PersonPropertyChangeEvent};
+    use crate::{
+        define_derived_property, define_person_property, define_person_property_with_default,
+    };
+    use crate::{Context, ContextPeopleExt, PersonId};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    define_person_property!(BatchAge, u8);
+    define_person_property_with_default!(Vaccinated, bool, false);
+    define_person_property!(Doses, u8, |_context, person_id: PersonId| {
+        u8::try_from(person_id.0 % 2).unwrap()
+    });
+    define_derived_property!(
+        Protected,
+        bool,
+        [Vaccinated, BatchAge],
+        |vaccinated, age| { vaccinated && age >= 5 }
+    );
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    // A context with `n` people, with an index on `Vaccinated` and handlers
+    // that log every event about it and `Protected`.
+    fn setup(n: u8) -> (Context, Log) {
+        let mut context = Context::new();
+        for age in 0..n {
+            context.add_person((BatchAge, age)).unwrap();
+        }
+        context.index_property(Vaccinated);
+        context.index_property(Protected);
+        let log = Log::default();
+        let log_clone = Rc::clone(&log);
+        context.subscribe_to_event(
+            move |context, event: PersonPropertyChangeEvent<Vaccinated>| {
+                // Handlers run after everyone has been updated.
+                let unvaccinated = context.query_people_count((Vaccinated, false));
+                log_clone.borrow_mut().push(format!(
+                    "{:?} {} -> {} ({unvaccinated} unvaccinated)",
+                    event.person_id, event.previous, event.current
+                ));
+            },
+        );
+        let log_clone = Rc::clone(&log);
+        context.subscribe_to_event(move |_, event: PersonPropertyChangeEvent<Protected>| {
+            log_clone.borrow_mut().push(format!(
+                "protected {:?} {} -> {}",
+                event.person_id, event.previous, event.current
+            ));
+        });
+        let log_clone = Rc::clone(&log);
+        context.subscribe_to_index_changes(move |_, change: IndexChange<Vaccinated>| {
+            log_clone.borrow_mut().push(format!(
+                "index {:?} {:?} -> {:?}",
+                change.person_id, change.previous, change.current
+            ));
+        });
+        (context, log)
+    }
+
+    fn sorted(mut people: Vec<PersonId>) -> Vec<PersonId> {
+        people.sort_by_key(|person_id| person_id.0);
+        people
+    }
+
+    #[test]
+    fn same_as_setting_one_at_a_time() {
+        let (mut batched, batched_log) = setup(10);
+        let (mut looped, looped_log) = setup(10);
+        let people: Vec<PersonId> = [1, 6, 3, 8].map(PersonId).to_vec();
+
+        batched.update_people_by_id(&people, Vaccinated, true);
+        for &person_id in &people {
+            looped.set_person_property(person_id, Vaccinated, true);
+        }
+        batched.execute();
+        looped.execute();
+
+        for context in [&batched, &looped] {
+            assert_eq!(
+                sorted(context.query_people((Vaccinated, true))),
+                sorted(people.clone())
+            );
+            assert_eq!(
+                sorted(context.query_people((Protected, true))),
+                [PersonId(6), PersonId(8)]
+            );
+            assert_eq!(context.query_people_count((Protected, false)), 8);
+        }
+        let mut batched_log = batched_log.borrow().clone();
+        let mut looped_log = looped_log.borrow().clone();
+        batched_log.sort();
+        looped_log.sort();
+        assert_eq!(batched_log, looped_log);
+        assert!(batched_log.contains(&String::from("Person 1 false -> true (6 unvaccinated)")));
+    }
+
+    #[test]
+    fn update_people_by_query() {
+        let (mut context, log) = setup(10);
+        assert_eq!(context.update_people((BatchAge, 20), Vaccinated, true), 0);
+        context.update_people_by_id(&[PersonId(1)], Vaccinated, true);
+        context.execute();
+        log.borrow_mut().clear();
+
+        // Person 1 is already vaccinated, so only has a change event.
+        assert_eq!(
+            context.update_people(crate::people::InRange(BatchAge, ..3), Vaccinated, true),
+            3
+        );
+        context.execute();
+        assert_eq!(context.query_people_count((Vaccinated, true)), 3);
+        let log = log.borrow();
+        // A change event each for `Vaccinated` and `Protected`, and an index
+        // change for the two people whose value changed.
+        assert_eq!(log.len(), 3 + 3 + 2);
+        assert!(!log.contains(&String::from("index Person 1 Some(true) -> Some(true)")));
+    }
+
+    #[test]
+    fn initializes_values_first() {
+        let mut context = Context::new();
+        let people: Vec<PersonId> = (0..4).map(|_| context.add_person(()).unwrap()).collect();
+        context.index_property(Doses);
+        let previous = Rc::new(RefCell::new(Vec::new()));
+        let previous_clone = Rc::clone(&previous);
+        context.subscribe_to_event(move |_, event: PersonPropertyChangeEvent<Doses>| {
+            previous_clone.borrow_mut().push(event.previous);
+        });
+
+        context.update_people_by_id(&people, Doses, 2);
+        context.execute();
+        assert_eq!(*previous.borrow(), [0, 1, 0, 1]);
+        assert_eq!(context.query_people_count((Doses, 2)), 4);
+        assert_eq!(context.query_people_count((Doses, 1)), 0);
+    }
+
+    #[test]
+    fn people_added_after_indexing() {
+        let (mut context, _) = setup(4);
+        context.query_people_count((Vaccinated, false));
+        let late = context.add_person((BatchAge, 40)).unwrap();
+
+        context.update_people_by_id(&[PersonId(0), late], Vaccinated, true);
+        assert_eq!(
+            sorted(context.query_people((Vaccinated, true))),
+            [PersonId(0), late]
+        );
+        assert_eq!(context.query_people_count((Vaccinated, false)), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't set Vaccinated of Person 1, who has been removed")]
+    fn removed_person_panics() {
+        let (mut context, _) = setup(3);
+        context.remove_person(PersonId(1)).unwrap();
+        context.update_people_by_id(&[PersonId(0), PersonId(1)], Vaccinated, true);
+    }
+}
@@ -0,0 +1,80 @@
+//! Saving people and their property values in checkpoints; see
+//! [`crate::checkpoint`].
+use crate::people::data::PeopleData;
+use crate::people::PeoplePlugin;
+use crate::{Context, ContextPeopleExt, IxaError, PersonId, PersonProperty};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::any::TypeId;
+
+pub(crate) fn plugin_type_id() -> TypeId {
+    TypeId::of::<PeoplePlugin>()
+}
+
+/// The people who have been created and removed, as saved in a checkpoint
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SavedPeople {
+    people_created: usize,
+    removed_people: Vec<PersonId>,
+}
+
+/// Saves the people who have been created and removed, or returns `None`
+/// if no one has been added.
+pub(crate) fn save_people(context: &Context) -> Option<SavedPeople> {
+    let data_container = context.get_data_container(PeoplePlugin)?;
+    let mut removed_people: Vec<PersonId> = data_container.removed_people.iter().copied().collect();
+    removed_people.sort_unstable_by_key(|person_id| person_id.0);
+    Some(SavedPeople {
+        people_created: data_container.people_created,
+        removed_people,
+    })
+}
+
+/// Restores the people saved with `save_people()`. No creation events are
+/// emitted.
+pub(crate) fn restore_people(context: &mut Context, saved: SavedPeople) {
+    let data_container = context.get_data_container_mut(PeoplePlugin);
+    data_container.people_created = saved.people_created;
+    data_container.removed_people = saved.removed_people.into_iter().collect();
+}
+
+/// Returns the type ids and names of the properties with stored values
+pub(crate) fn stored_property_types(context: &Context) -> Vec<(TypeId, &'static str)> {
+    context
+        .get_data_container(PeoplePlugin)
+        .map(PeopleData::stored_property_types)
+        .unwrap_or_default()
+}
+
+pub(crate) fn save_property<T: PersonProperty + 'static>(
+    context: &Context,
+) -> Result<Option<Vec<u8>>, IxaError>
+where
+    T::Value: Serialize,
+{
+    match context.get_data_container(PeoplePlugin) {
+        Some(data_container) => data_container.serialize_property_values::<T>(),
+        None => Ok(None),
+    }
+}
+
+/// Restores the values saved with `save_property()`. The people must
+/// have been restored first. Indexes of the property pick up the values
+/// when they're next used.
+pub(crate) fn restore_property<T: PersonProperty + 'static>(
+    context: &mut Context,
+    data: &[u8],
+) -> Result<(), IxaError>
+where
+    T::Value: DeserializeOwned,
+{
+    let data_container = context.get_data_container(PeoplePlugin).ok_or_else(|| {
+        IxaError::IxaError(format!(
+            "Checkpoint has values of {} but no people",
+            T::name()
+        ))
+    })?;
+    data_container.deserialize_property_values::<T>(data)?;
+    context.register_property::<T>();
+    Ok(())
+}
@@ -1,14 +1,50 @@
-use crate::people::index::{Index, IndexValue};
-use crate::people::query::Query;
+use crate::people::audit::AuditAction;
+#[cfg(debug_assertions)]
+use crate::people::audit::{self, AccessKind};
+use crate::people::batch;
+use crate::people::custom_index::{self, PropertyIndexImpl};
+use crate::people::data::PeopleData;
+use crate::people::index::{Index, IndexLookup, IndexValue, IndexedPeople};
+use crate::people::lazy::{self, LazyColumn};
+use crate::people::loader::load_people_from_csv;
+use crate::people::property::is_non_finite;
+use crate::people::query::{AnyOf, FallbackQueries, InRange, Query, ValueFilter};
+use crate::people::snapshot::{
+    diff_snapshot, take_snapshot, PropertyDiff, PropertySnapshot, SnapshotSpec,
+};
+use crate::people::staged::{self, StagedChangePolicy};
+use crate::people::summary::{summarize, summarize_by, NumericValue, PropertySummary};
+use crate::people::targets::{apply_targets, select_target_people, TargetSpec};
+use crate::people::template::{add_people_from_template, PersonTemplate};
 use crate::people::{index, InitializationList, PeoplePlugin, PersonPropertyHolder};
+use crate::scratch::Scratch;
+use crate::state_machine;
 use crate::{
-    Context, ContextRandomExt, IxaError, PersonCreatedEvent, PersonId, PersonProperty,
-    PersonPropertyChangeEvent, RngId, Tabulator,
+    Context, ContextRandomExt, IndexChange, IxaError, PersonCreatedEvent, PersonId, PersonProperty,
+    PersonPropertyChangeEvent, PersonRemovedEvent, RngId, TabulationKey, Tabulator,
 };
+use log::trace;
+#[cfg(not(debug_assertions))]
+use log::warn;
 use rand::Rng;
+use serde::de::DeserializeOwned;
 use std::any::TypeId;
 use std::cell::Ref;
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
+use std::ops::RangeBounds;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Statistics about a call to [`Context::backfill_property()`]
+#[derive(Debug, Clone, Copy)]
+pub struct BackfillStats {
+    /// The number of people whose value was computed
+    pub people_computed: usize,
+    /// How long the backfill took
+    pub elapsed: Duration,
+}
 
 /// A trait extension for [`Context`] that exposes the people
 /// functionality.
@@ -24,20 +60,53 @@ pub trait ContextPeopleExt {
     /// `let person = context.add_person((Age, 42)).unwrap();`
     ///
     /// # Errors
-    /// Will return [`IxaError`] if a required initializer is not provided.
+    /// Returns [`IxaError::MissingRequiredProperties`] listing every
+    /// required property that isn't provided, or
+    /// [`IxaError::DuplicateProperty`] if a property is given twice.
     fn add_person<T: InitializationList>(&mut self, props: T) -> Result<PersonId, IxaError>;
 
+    /// Remove a person from the simulation. Their property values are
+    /// deleted, they are removed from every index, and a
+    /// [`PersonRemovedEvent`] is emitted. Their `PersonId` is not reused.
+    ///
+    /// Plans that captured the `PersonId` are still executed, so they
+    /// should check [`Context::person_exists()`] before using it.
+    ///
+    /// # Errors
+    /// Returns [`IxaError::PersonNotFound`] if the person doesn't exist or
+    /// was already removed.
+    fn remove_person(&mut self, person_id: PersonId) -> Result<(), IxaError>;
+
+    /// Returns whether `person_id` refers to a person who has been added
+    /// and not removed.
+    fn person_exists(&self, person_id: PersonId) -> bool;
+
     /// Given a `PersonId` returns the value of a defined person property,
     /// initializing it if it hasn't been set yet. If no initializer is
     /// provided, and the property is not set this will panic, as long
     /// as the property has been set or subscribed to at least once before.
     /// Otherwise, Ixa doesn't know about the property.
+    ///
+    /// # Panics
+    /// Panics if the person has been removed.
     fn get_person_property<T: PersonProperty + 'static>(
         &self,
         person_id: PersonId,
         _property: T,
     ) -> T::Value;
 
+    /// Like [`Context::get_person_property()`], but returns an error
+    /// instead of panicking if the person doesn't exist.
+    ///
+    /// # Errors
+    /// Returns [`IxaError::PersonNotFound`] if the person doesn't exist or
+    /// has been removed.
+    fn try_get_person_property<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        property: T,
+    ) -> Result<T::Value, IxaError>;
+
     #[doc(hidden)]
     fn register_property<T: PersonProperty + 'static>(&self);
 
@@ -50,6 +119,132 @@ pub trait ContextPeopleExt {
         value: T::Value,
     );
 
+    /// Set property `T` to `value` for everyone who matches `query`,
+    /// returning how many people that was, e.g., to vaccinate a whole age
+    /// group at once. See [`Context::update_people_by_id()`].
+    ///
+    /// # Panics
+    /// Panics if `T` is a derived property.
+    fn update_people<T: PersonProperty + 'static, Q: Query>(
+        &mut self,
+        query: Q,
+        property: T,
+        value: T::Value,
+    ) -> usize;
+
+    /// Set property `T` to `value` for each of `people`. This has the same
+    /// effect as calling [`Context::set_person_property()`] for each of
+    /// them, with a change event for each person, but is much faster for
+    /// many people: the index of `T` is updated an entry at a time rather
+    /// than a person at a time, and the change events are emitted after
+    /// all the values have been set, so handlers see everyone's new value.
+    ///
+    /// # Panics
+    /// Panics if `T` is a derived property or any of the people has been
+    /// removed.
+    fn update_people_by_id<T: PersonProperty + 'static>(
+        &mut self,
+        people: &[PersonId],
+        property: T,
+        value: T::Value,
+    );
+
+    /// Set property `T` of `person_id` to `value` at `time`, as if by
+    /// [`Context::set_person_property()`] in a plan in the first phase at
+    /// that time, e.g., to have someone recover at the end of their
+    /// infectious period. This
+    /// is much cheaper than a plan per person, and the people with staged
+    /// changes can be found with a
+    /// [`HasStagedChange`](crate::people::HasStagedChange) query.
+    ///
+    /// A person has at most one staged change to each property, so this
+    /// replaces any change already staged for them. The change is
+    /// cancelled if the property is set directly before `time` or the
+    /// person is removed; use
+    /// [`Context::stage_property_change_with_policy()`] to keep it instead.
+    ///
+    /// # Panics
+    /// Panics if `T` is a derived property, the person doesn't exist, or
+    /// `time` is in the past, infinite, or NaN.
+    fn stage_property_change<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        property: T,
+        value: T::Value,
+        time: f64,
+    );
+
+    /// Like [`Context::stage_property_change()`], but with `policy` saying
+    /// what happens if the property is set directly before `time`.
+    ///
+    /// # Panics
+    /// Panics if `T` is a derived property, the person doesn't exist, or
+    /// `time` is in the past, infinite, or NaN.
+    fn stage_property_change_with_policy<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        property: T,
+        value: T::Value,
+        time: f64,
+        policy: StagedChangePolicy,
+    );
+
+    /// Cancel the change to property `T` staged for `person_id`, returning
+    /// its time and value, or `None` if there wasn't one.
+    fn cancel_staged_change<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        property: T,
+    ) -> Option<(f64, T::Value)>;
+
+    /// The time and value of the change to property `T` staged for
+    /// `person_id`, or `None` if there isn't one.
+    fn get_staged_change<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        property: T,
+    ) -> Option<(f64, T::Value)>;
+
+    /// Report each read of property `T` with
+    /// [`Context::get_person_property()`] as described by `action`,
+    /// replacing any previous audit of reads of `T`. Use this to find out
+    /// which code reads a property and when, e.g., to track down results
+    /// that depend on the order of plans.
+    ///
+    /// Audits are only available in debug builds. In release builds this
+    /// logs a warning and does nothing, and reading properties doesn't
+    /// check for audits at all.
+    fn audit_property_reads<T: PersonProperty + 'static>(
+        &mut self,
+        property: T,
+        action: AuditAction,
+    );
+
+    /// Like [`Context::audit_property_reads()`], but reports each change
+    /// with [`Context::set_person_property()`], with the new value. Writes
+    /// are reported before the value changes.
+    fn audit_property_writes<T: PersonProperty + 'static>(
+        &mut self,
+        property: T,
+        action: AuditAction,
+    );
+
+    /// Stop auditing reads and writes of property `T`.
+    fn stop_auditing_property<T: PersonProperty + 'static>(&mut self, property: T);
+
+    /// Allow property `T` to be set to NaN or infinite values. By default,
+    /// non-finite floating point values are rejected in debug builds and
+    /// reported as warnings in release builds.
+    fn allow_non_finite<T: PersonProperty + 'static>(&mut self, property: T);
+
+    /// In release builds, check every `interval`th property set for
+    /// non-finite values and log a warning when one is found. An interval
+    /// of 0 disables the check. Debug builds check every value and panic.
+    /// The default is [`DEFAULT_NON_FINITE_CHECK_INTERVAL`].
+    ///
+    /// [`DEFAULT_NON_FINITE_CHECK_INTERVAL`]: crate::people::DEFAULT_NON_FINITE_CHECK_INTERVAL
+    fn set_non_finite_check_interval(&mut self, interval: usize);
+
     /// Create an index for property `T`.
     ///
     /// If an index is available [`Context::query_people()`] will use it, so this is
@@ -59,6 +254,67 @@ pub trait ContextPeopleExt {
     /// that one is created.
     fn index_property<T: PersonProperty + 'static>(&mut self, property: T);
 
+    /// Keep a count of the people with each value of property `T`, without
+    /// the lists of people that [`Context::index_property()`] keeps. This
+    /// makes [`Context::query_people_count()`] with just `T`, and
+    /// periodic reports and [`Context::tabulate_person_properties()`] of
+    /// `T` alone, take time in proportion to the number of values rather
+    /// than the population. Call it before adding reports of `T`, since
+    /// they otherwise create the full index.
+    ///
+    /// If `T` has a custom index, this has no effect.
+    fn index_property_counts<T: PersonProperty + 'static>(&mut self, property: T);
+
+    /// Index property `T` with `index` rather than the built-in index, so
+    /// that a model can use a data structure suited to the property. See
+    /// [`PropertyIndexImpl`] for how queries use it. Calling
+    /// [`Context::index_property()`] afterwards has no effect.
+    ///
+    /// Custom indexes aren't used by [`Context::tabulate_person_count()`],
+    /// which looks at everyone instead.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if `T` is already indexed.
+    fn install_custom_index<T: PersonProperty + 'static>(
+        &mut self,
+        property: T,
+        index: Box<dyn PropertyIndexImpl<T>>,
+    ) -> Result<(), IxaError>;
+
+    /// Call `handler` with an [`IndexChange`] whenever a person is added
+    /// to, moves within, or is removed from the index of property `T`,
+    /// which is created if it doesn't exist. This lets a data plugin keep
+    /// a cache of the people with each value up to date incrementally.
+    ///
+    /// When it's subscribed, `handler` is first sent a change with no
+    /// previous value for everyone who already exists, in order of
+    /// [`PersonId`]. From then on, people are indexed on `T` as soon as
+    /// they're created rather than at the next query, so that `handler`
+    /// hears about them right away.
+    ///
+    /// Changes are emitted just before the [`PersonCreatedEvent`],
+    /// [`PersonPropertyChangeEvent`], or [`PersonRemovedEvent`] for the
+    /// same change, so `handler` runs before the handlers of those events.
+    fn subscribe_to_index_changes<T: PersonProperty + 'static>(
+        &mut self,
+        handler: impl Fn(&mut Context, IndexChange<T>) + 'static,
+    );
+
+    /// Compute property `T` for everyone who currently exists, so that the
+    /// work happens at a time of the modeler's choosing rather than inside
+    /// the first query that needs it.
+    ///
+    /// Properties with an initializer have their values computed and
+    /// stored for anyone who doesn't have one yet. Derived properties
+    /// aren't stored, but computing them initializes the properties they
+    /// depend on. In both cases, an index on `T` is brought up to date.
+    /// People created afterwards are handled lazily as usual.
+    ///
+    /// # Panics
+    /// Panics if `T` or one of its dependencies is a required property
+    /// that someone doesn't have a value for.
+    fn backfill_property<T: PersonProperty + 'static>(&mut self, property: T) -> BackfillStats;
+
     /// Query for all people matching a given set of criteria.
     ///
     /// [`Context::query_people()`] takes any type that implements [Query],
@@ -66,8 +322,74 @@ pub trait ContextPeopleExt {
     /// to use the automatic syntax that implements [Query] for
     /// a tuple of pairs of (property, value), like so:
     /// `context.query_people(((Age, 30), (Gender, Female)))`.
+    ///
+    /// To pick one matching person at random, use
+    /// [`Context::sample_person()`], which avoids collecting everyone.
     fn query_people<T: Query>(&self, q: T) -> Vec<PersonId>;
 
+    /// Query for all people whose value of `property` is in `range`, e.g.,
+    /// `context.query_people_range(Age, 18..=65)`. Any kind of range can be
+    /// used, including unbounded ones like `65..`, and the property can
+    /// have any value type that can be compared, such as integers or
+    /// `OrderedFloat`. This is the same as
+    /// `context.query_people(InRange(property, range))`; see
+    /// [`InRange`](crate::people::InRange) to combine it with other criteria.
+    ///
+    /// If the property is indexed (see [`Context::index_property()`]) this
+    /// checks each distinct value in the index; otherwise it scans the
+    /// population.
+    fn query_people_range<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+        range: impl RangeBounds<T::Value>,
+    ) -> Vec<PersonId>
+    where
+        T::Value: PartialOrd;
+
+    /// Query for all people whose value of `property` is any one of
+    /// `values`, e.g.,
+    /// `context.query_people_any_value(InfectionStatus, &[Infected, Recovered])`.
+    /// This is the same as `context.query_people(AnyOf(property, values.to_vec()))`;
+    /// see [`AnyOf`](crate::people::AnyOf) to combine it with other criteria.
+    ///
+    /// If the property is indexed this combines the index entries for
+    /// each value; otherwise it scans the population.
+    fn query_people_any_value<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+        values: &[T::Value],
+    ) -> Vec<PersonId>;
+
+    /// Query for the `k` people matching `query` with the highest values
+    /// of `property`, e.g., the 10 people with the highest viral load with
+    /// `context.query_top_k_people(ViralLoad, (Infected, true), 10)`. They
+    /// are returned from highest to lowest, with ties in order of person
+    /// id. If fewer than `k` people match, all of them are returned.
+    ///
+    /// This selects the top `k` with [`slice::select_nth_unstable_by()`]
+    /// rather than sorting everyone who matches, so it takes O(n) time on
+    /// average plus O(k log k) to sort the result.
+    fn query_top_k_people<T: PersonProperty + 'static, Q: Query>(
+        &self,
+        property: T,
+        query: Q,
+        k: usize,
+    ) -> Vec<PersonId>
+    where
+        T::Value: Ord;
+
+    /// Query for the `k` people matching `query` with the lowest values of
+    /// `property`, from lowest to highest, like
+    /// [`Context::query_top_k_people()`].
+    fn query_bottom_k_people<T: PersonProperty + 'static, Q: Query>(
+        &self,
+        property: T,
+        query: Q,
+        k: usize,
+    ) -> Vec<PersonId>
+    where
+        T::Value: Ord;
+
     /// Get the count of all people matching a given set of criteria.
     ///
     /// [`Context::query_people_count()`] takes any type that implements [Query],
@@ -89,25 +411,354 @@ pub trait ContextPeopleExt {
     where
         F: Fn(&Context, &[String], usize);
 
-    /// Randomly sample a person from the population of people who match the query.
+    /// Count the people with each combination of values of the properties
+    /// in `T`, which is a tuple of properties such as `(Age, RiskCategory)`.
+    /// Combinations that no one has are not included.
+    ///
+    /// If all of the properties are indexed (see
+    /// [`Context::index_property()`]) the counts come from the indexes;
+    /// otherwise this scans the population once. Derived properties can be
+    /// used too.
+    fn tabulate_person_count<T: TabulationKey>(&self) -> HashMap<T::Values, usize>
+    where
+        T::Values: Eq;
+
+    /// Randomly sample a person from the population of people who match the
+    /// query, or return `None` if no one matches.
     ///
     /// The syntax here is the same as with [`Context::query_people()`].
+    /// This doesn't collect the matching people: an empty query or a query
+    /// on a single indexed property takes constant time, and anything else
+    /// is a single pass over the candidates using reservoir sampling. If
+    /// any of the properties in the query are indexed and no one has the
+    /// value, nobody is looked at, so there's no need to count the
+    /// matches first.
+    ///
+    /// This used to return an error when no one matched. Callers that
+    /// treated that as "no one to sample" can use the `None` instead, and
+    /// `sample_person(rng_id, query).unwrap()` becomes
+    /// `sample_person(rng_id, query).unwrap().unwrap()`.
     ///
     /// # Errors
-    /// Returns `IxaError` if population is 0.
+    /// Returns `IxaError` if the population is 0.
     fn sample_person<R: RngId + 'static, T: Query>(
         &self,
         rng_id: R,
         query: T,
+    ) -> Result<Option<PersonId>, IxaError>
+    where
+        R::RngType: Rng;
+
+    /// Randomly sample a person who matches `query`, or if no one does,
+    /// the first of `fallbacks` that someone matches. This is for
+    /// progressively relaxed criteria, e.g., someone in the same household,
+    /// then the same county, then anyone. `fallbacks` is a slice or array
+    /// of queries of the same type, or a tuple of queries of different
+    /// types.
+    ///
+    /// Returns the person and which query they were sampled from: 0 for
+    /// `query` and `i + 1` for `fallbacks[i]`, or `None` if no one matches
+    /// any of them.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if the population is 0.
+    fn sample_person_or<R: RngId + 'static, T: Query, F: FallbackQueries + ?Sized>(
+        &self,
+        rng_id: R,
+        query: T,
+        fallbacks: &F,
+    ) -> Result<Option<(PersonId, usize)>, IxaError>
+    where
+        R::RngType: Rng;
+
+    /// Randomly sample a person who matches `query` with probability
+    /// proportional to `weight_fn(context, person)`, e.g., the size of
+    /// their household. This is a single pass over the matching people
+    /// using weighted reservoir sampling, so the weights are never
+    /// collected, and people with a weight of 0 are never picked.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if the population is 0, no one matches, all the
+    /// weights are 0, or a weight is negative, infinite or NaN.
+    fn sample_person_weighted<R: RngId + 'static, T: Query, F>(
+        &self,
+        rng_id: R,
+        query: T,
+        weight_fn: F,
     ) -> Result<PersonId, IxaError>
+    where
+        R::RngType: Rng,
+        F: Fn(&Context, PersonId) -> f64;
+
+    /// Randomly sample `k` distinct people from the people who match the
+    /// query, or all of them, in no particular order, if fewer than `k`
+    /// match. Like [`Context::sample_person()`], this doesn't collect the
+    /// matching people unless it needs to: an empty query or a query on a
+    /// single indexed property takes O(k) time, and anything else is a
+    /// single pass over the candidates using reservoir sampling.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if population is 0.
+    fn sample_people<R: RngId + 'static, T: Query>(
+        &self,
+        rng_id: R,
+        query: T,
+        k: usize,
+    ) -> Result<Vec<PersonId>, IxaError>
+    where
+        R::RngType: Rng;
+
+    /// Set property values so that the population exactly matches a list of
+    /// aggregate targets. For each [`TargetSpec`], in order, the required
+    /// number of people in each stratum are sampled without replacement and
+    /// assigned the target's value. People chosen by an earlier target for
+    /// the same property are not eligible for later targets for that property.
+    ///
+    /// All of the targets are checked before any property is set, so if this
+    /// returns an error no people have been modified, and nothing has been
+    /// drawn from `rng_id`.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if a stratum doesn't have enough eligible people
+    /// for a target or a target fraction is not between 0 and 1.
+    fn initialize_to_targets<R: RngId + 'static>(
+        &mut self,
+        rng_id: R,
+        targets: &[TargetSpec],
+    ) -> Result<(), IxaError>
+    where
+        R::RngType: Rng + Clone;
+
+    /// List the distinct values of `property` that people currently have,
+    /// with the number of people who have each one. Values are sorted by
+    /// decreasing count, and values with the same count are in a fixed order
+    /// based on their hash, so the result is reproducible.
+    ///
+    /// If the property is indexed (see [`Context::index_property()`]) the
+    /// counts come from the index; otherwise this scans the population.
+    fn distinct_property_values<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+    ) -> Vec<(T::Value, usize)>;
+
+    /// Randomly sample a value of `property`, weighted by the number of
+    /// people who have each value.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if population is 0.
+    fn sample_property_value<R: RngId + 'static, T: PersonProperty + 'static>(
+        &self,
+        rng_id: R,
+        property: T,
+    ) -> Result<T::Value, IxaError>
     where
         R::RngType: Rng;
+
+    /// Add `count` people whose initial property values come from
+    /// `template`. Distributions in the template are drawn from separately
+    /// for each person using a stream named after `rng_id` (see
+    /// [`PersonTemplate::distribution()`]). The new people have consecutive
+    /// ids and are returned in order.
+    ///
+    /// # Errors
+    /// Returns [`IxaError::MissingRequiredProperties`], without adding
+    /// anyone, if the template doesn't set every required property. The
+    /// error lists all of the missing properties.
+    ///
+    /// # Panics
+    /// Panics if the template has a distribution and `init_random()` hasn't
+    /// been called.
+    fn add_people_from_template<R: RngId + 'static>(
+        &mut self,
+        rng_id: R,
+        template: &PersonTemplate,
+        count: usize,
+    ) -> Result<Vec<PersonId>, IxaError>;
+
+    /// Add a person for each row of the CSV file at `path`. Each row is
+    /// deserialized into an `R`, matching columns to fields by the names in
+    /// the header, and `to_person` turns it into the person's initial
+    /// property values. Optional columns can be `Option` fields or have
+    /// `#[serde(default)]`. The file is read one row at a time, so large
+    /// files aren't held in memory, and a [`PersonCreatedEvent`] is emitted
+    /// for each person as with [`Context::add_person()`].
+    ///
+    /// Returns the number of people added.
+    ///
+    /// ```
+    /// # use ixa::{define_person_property, Context, ContextPeopleExt};
+    /// # use serde::Deserialize;
+    /// define_person_property!(Age, u8);
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Row {
+    ///     age: u8,
+    /// }
+    ///
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// # let path = dir.path().join("people.csv");
+    /// # std::fs::write(&path, "age\n10\n70\n").unwrap();
+    /// let mut context = Context::new();
+    /// let count = context
+    ///     .load_people_from_csv(&path, |_context, row: Row| (Age, row.age))
+    ///     .unwrap();
+    /// assert_eq!(count, 2);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns `IxaError` if the file can't be read, a row can't be parsed
+    /// ([`IxaError::InvalidRow`]) or a person can't be added. The error is
+    /// wrapped in an [`IxaError::Context`] naming the file and where this
+    /// was called from, and errors adding a person in another naming the
+    /// line. The people from the rows before that one have already been
+    /// added.
+    fn load_people_from_csv<R, T, F>(
+        &mut self,
+        path: &Path,
+        to_person: F,
+    ) -> Result<usize, IxaError>
+    where
+        R: DeserializeOwned,
+        T: InitializationList,
+        F: FnMut(&Context, R) -> T;
+
+    /// Like [`Context::load_people_from_csv()`], but the properties in
+    /// `lazy_columns` aren't read until they're first used, so that the
+    /// columns a model doesn't need aren't parsed or stored. The first
+    /// time a lazy property's value is needed, whether it's read, set,
+    /// indexed or queried, its whole column is read in a single pass over
+    /// the file and it becomes an ordinary property. The time this takes
+    /// is logged.
+    ///
+    /// The lazy properties count as set for the people from the file, so
+    /// they can be required. They can't be read until the file has been
+    /// loaded.
+    ///
+    /// ```
+    /// # use ixa::{define_person_property, Context, ContextPeopleExt};
+    /// # use ixa::people::LazyColumn;
+    /// # use serde::Deserialize;
+    /// define_person_property!(Age, u8);
+    /// define_person_property!(Income, u32);
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Row {
+    ///     age: u8,
+    /// }
+    ///
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// # let path = dir.path().join("people.csv");
+    /// # std::fs::write(&path, "age,income\n10,0\n70,31000\n").unwrap();
+    /// let mut context = Context::new();
+    /// context
+    ///     .load_people_from_csv_lazy(
+    ///         &path,
+    ///         |_context, row: Row| (Age, row.age),
+    ///         vec![LazyColumn::new(Income, "income")],
+    ///     )
+    ///     .unwrap();
+    /// // This reads the income column.
+    /// assert_eq!(context.query_people_count((Income, 31000)), 1);
+    /// ```
+    ///
+    /// # Errors
+    /// Returns `IxaError` as [`Context::load_people_from_csv()`] does, or
+    /// if the file has no column for one of `lazy_columns`.
+    ///
+    /// # Panics
+    /// Reading a lazy column panics if the file has changed since it was
+    /// loaded, or a value can't be parsed. Use
+    /// [`Context::materialize_lazy_property()`] to get an error instead.
+    fn load_people_from_csv_lazy<R, T, F>(
+        &mut self,
+        path: &Path,
+        to_person: F,
+        lazy_columns: Vec<LazyColumn>,
+    ) -> Result<usize, IxaError>
+    where
+        R: DeserializeOwned,
+        T: InitializationList,
+        F: FnMut(&Context, R) -> T;
+
+    /// Read the column of `property` now if it was loaded with
+    /// [`Context::load_people_from_csv_lazy()`] and hasn't been read yet.
+    /// Returns whether it was read.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if the file has changed since it was loaded, a
+    /// value can't be parsed ([`IxaError::InvalidRow`]), or people are
+    /// still being loaded from it. The column is then still unread.
+    fn materialize_lazy_property<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+    ) -> Result<bool, IxaError>;
+
+    /// Record the current values of the properties in `specs` for every
+    /// person, so that they can be compared with later values using
+    /// [`Context::diff_against()`]. Only the listed properties are stored,
+    /// either as copies of the values or as hashes (see [`SnapshotSpec`]).
+    ///
+    /// # Panics
+    /// Panics if a property has no value and no initializer for some person.
+    fn snapshot_properties(&self, specs: &[SnapshotSpec]) -> PropertySnapshot;
+
+    /// Compare the current property values with a snapshot taken earlier in
+    /// this simulation, listing the people whose values changed and the
+    /// people who were created since.
+    ///
+    /// # Panics
+    /// Panics if a property has no value and no initializer for some person.
+    fn diff_against(&self, snapshot: &PropertySnapshot) -> PropertyDiff;
+
+    /// Summarize the values of the numeric property `property` among the
+    /// people who match `query`, e.g.,
+    /// `context.property_summary(ViralLoad, ((InfectionStatus, Infectious),))`.
+    /// The syntax of `query` is the same as with [`Context::query_people()`].
+    /// Quantiles of more than [`EXACT_SUMMARY_LIMIT`](crate::people::EXACT_SUMMARY_LIMIT)
+    /// values are estimated.
+    ///
+    /// The values of `property` must be [`NumericValue`]s:
+    ///
+    /// ```compile_fail
+    /// use ixa::{define_person_property, Context, ContextPeopleExt};
+    ///
+    /// define_person_property!(Name, &'static str);
+    ///
+    /// let context = Context::new();
+    /// context.property_summary(Name, ());
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if a property has no value and no initializer for some person.
+    fn property_summary<T, Q>(&self, property: T, query: Q) -> PropertySummary
+    where
+        T: PersonProperty + 'static,
+        T::Value: NumericValue,
+        Q: Query;
+
+    /// Like [`Context::property_summary()`], but with a separate summary for
+    /// each value of `group` among the people who match `query`, sorted by
+    /// the debug representation of the value.
+    ///
+    /// # Panics
+    /// Panics if a property has no value and no initializer for some person.
+    fn property_summary_by<T, G, Q>(
+        &self,
+        property: T,
+        group: G,
+        query: Q,
+    ) -> Vec<(G::Value, PropertySummary)>
+    where
+        T: PersonProperty + 'static,
+        T::Value: NumericValue,
+        G: PersonProperty + 'static,
+        Q: Query;
 }
 
 impl ContextPeopleExt for Context {
     fn get_current_population(&self) -> usize {
         self.get_data_container(PeoplePlugin)
-            .map_or(0, |data_container| data_container.current_population)
+            .map_or(0, PeopleData::population)
     }
 
     fn add_person<T: InitializationList>(&mut self, props: T) -> Result<PersonId, IxaError> {
@@ -127,120 +778,223 @@ impl ContextPeopleExt for Context {
         let data_container = self.get_data_container_mut(PeoplePlugin);
         data_container.is_initializing = false;
 
+        self.index_new_people_for_subscribers();
         self.emit_event(PersonCreatedEvent { person_id });
         Ok(person_id)
     }
 
+    fn remove_person(&mut self, person_id: PersonId) -> Result<(), IxaError> {
+        if !self.person_exists(person_id) {
+            return Err(IxaError::PersonNotFound(person_id));
+        }
+
+        // Remove the person from the indexes while their values can still
+        // be computed.
+        let mut removals = Vec::new();
+        {
+            let data_container = self.get_data_container(PeoplePlugin).unwrap();
+            let mut indexes = data_container.property_indexes.borrow_mut();
+            for index in indexes.values_mut() {
+                if index.is_indexed() && person_id.0 < index.max_indexed {
+                    index.remove_person(self, person_id);
+                    if index.notify_changes {
+                        removals.push((index.name, index.emit_membership_change));
+                    }
+                }
+            }
+        }
+        removals.sort_unstable_by_key(|(name, _)| *name);
+        for (_, emit_membership_change) in removals {
+            emit_membership_change(self, person_id, false);
+        }
+
+        staged::person_removed(self, person_id);
+        self.get_data_container_mut(PeoplePlugin)
+            .remove_person(person_id);
+        self.emit_event(PersonRemovedEvent { person_id });
+        Ok(())
+    }
+
+    fn person_exists(&self, person_id: PersonId) -> bool {
+        self.get_data_container(PeoplePlugin)
+            .is_some_and(|data_container| data_container.person_exists(person_id))
+    }
+
+    #[cfg_attr(debug_assertions, track_caller)]
     fn get_person_property<T: PersonProperty + 'static>(
         &self,
         person_id: PersonId,
         property: T,
     ) -> T::Value {
-        let data_container = self.get_data_container(PeoplePlugin)
-            .expect("PeoplePlugin is not initialized; make sure you add a person before accessing properties");
-        self.register_property::<T>();
+        let value = self.get_person_property_unaudited(person_id, property);
+        #[cfg(debug_assertions)]
+        audit::audit_access::<T>(
+            self,
+            person_id,
+            AccessKind::Read,
+            &value,
+            std::panic::Location::caller(),
+        );
+        value
+    }
 
-        if T::is_derived() {
-            return T::compute(self, person_id);
+    fn try_get_person_property<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        property: T,
+    ) -> Result<T::Value, IxaError> {
+        if !self.person_exists(person_id) {
+            return Err(IxaError::PersonNotFound(person_id));
         }
+        Ok(self.get_person_property(person_id, property))
+    }
 
-        // Attempt to retrieve the existing value
-        if let Some(value) = *data_container.get_person_property_ref(person_id, property) {
-            return value;
+    #[allow(clippy::single_match_else)]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn set_person_property<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        property: T,
+        value: T::Value,
+    ) {
+        #[cfg(debug_assertions)]
+        if self
+            .get_data_container(PeoplePlugin)
+            .is_some_and(|data_container| !data_container.is_initializing)
+        {
+            audit::audit_access::<T>(
+                self,
+                person_id,
+                AccessKind::Write,
+                &value,
+                std::panic::Location::caller(),
+            );
         }
+        state_machine::check_direct_set::<T>(self);
+        staged::property_set::<T>(self, person_id);
+        self.set_person_property_unaudited(person_id, property, value);
+    }
 
-        // Initialize the property. This does not fire a change event
-        let initialized_value = T::compute(self, person_id);
-        data_container.set_person_property(person_id, property, initialized_value);
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn update_people<T: PersonProperty + 'static, Q: Query>(
+        &mut self,
+        query: Q,
+        property: T,
+        value: T::Value,
+    ) -> usize {
+        let people = self.query_people(query);
+        self.update_people_by_id(&people, property, value);
+        people.len()
+    }
 
-        initialized_value
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn update_people_by_id<T: PersonProperty + 'static>(
+        &mut self,
+        people: &[PersonId],
+        _property: T,
+        value: T::Value,
+    ) {
+        batch::update_people::<T>(self, people, value);
     }
 
-    #[allow(clippy::single_match_else)]
-    fn set_person_property<T: PersonProperty + 'static>(
+    fn stage_property_change<T: PersonProperty + 'static>(
         &mut self,
         person_id: PersonId,
         property: T,
         value: T::Value,
+        time: f64,
     ) {
-        self.register_property::<T>();
+        self.stage_property_change_with_policy(
+            person_id,
+            property,
+            value,
+            time,
+            StagedChangePolicy::default(),
+        );
+    }
 
-        assert!(!T::is_derived(), "Cannot set a derived property");
+    fn stage_property_change_with_policy<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        _property: T,
+        value: T::Value,
+        time: f64,
+        policy: StagedChangePolicy,
+    ) {
+        state_machine::check_direct_set::<T>(self);
+        staged::stage_property_change::<T>(self, person_id, value, time, policy);
+    }
 
-        // This function can be called in two separate modes:
-        //
-        // 1. As a regular API function, in which case we want to
-        //    emit an event and notify dependencies.
-        // 2. Internally as part of initialization during add_person()
-        //    in which case no events are emitted.
-        //
-        // Which mode it is is determined by the data_container.is_initializing
-        // property, which is set by add_person. This is complicated but
-        // necessary because the initialization functions are called by
-        // a per-PersonProperty closure generated by a macro and so are
-        // outside of the crate, but we don't want to expose a public
-        // initialize_person_property() function.
-        //
-        // Temporarily remove dependency properties since we need mutable references
-        // to self during callback execution
-        let initializing = self
-            .get_data_container(PeoplePlugin)
-            .unwrap()
-            .is_initializing;
+    fn cancel_staged_change<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        _property: T,
+    ) -> Option<(f64, T::Value)> {
+        staged::cancel_staged_change::<T>(self, person_id)
+    }
 
-        let (previous_value, deps_temp) = if initializing {
-            (None, None)
-        } else {
-            let previous_value = self.get_person_property(person_id, property);
-            if previous_value != value {
-                self.remove_from_index_maybe(person_id, property);
-            }
+    fn get_staged_change<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        _property: T,
+    ) -> Option<(f64, T::Value)> {
+        staged::get_staged_change::<T>(self, person_id)
+    }
 
-            (
-                Some(previous_value),
-                self.get_data_container(PeoplePlugin)
-                    .unwrap()
-                    .dependency_map
-                    .borrow_mut()
-                    .get_mut(&TypeId::of::<T>())
-                    .map(std::mem::take),
-            )
-        };
+    #[cfg(debug_assertions)]
+    fn audit_property_reads<T: PersonProperty + 'static>(
+        &mut self,
+        _property: T,
+        action: AuditAction,
+    ) {
+        audit::set_audit::<T>(self, AccessKind::Read, action);
+    }
 
-        let mut dependency_event_callbacks = Vec::new();
-        if let Some(mut deps) = deps_temp {
-            // If there are dependencies, set up a bunch of callbacks with the
-            // current value
-            for dep in &mut deps {
-                dep.dependency_changed(self, person_id, &mut dependency_event_callbacks);
-            }
+    #[cfg(debug_assertions)]
+    fn audit_property_writes<T: PersonProperty + 'static>(
+        &mut self,
+        _property: T,
+        action: AuditAction,
+    ) {
+        audit::set_audit::<T>(self, AccessKind::Write, action);
+    }
 
-            // Put the dependency list back in
-            let data_container = self.get_data_container(PeoplePlugin).unwrap();
-            let mut dependencies = data_container.dependency_map.borrow_mut();
-            dependencies.insert(TypeId::of::<T>(), deps);
-        }
+    #[cfg(debug_assertions)]
+    fn stop_auditing_property<T: PersonProperty + 'static>(&mut self, _property: T) {
+        audit::remove_audit::<T>(self);
+    }
 
-        // Update the main property and send a change event
-        let data_container = self.get_data_container(PeoplePlugin).unwrap();
-        data_container.set_person_property(person_id, property, value);
+    #[cfg(not(debug_assertions))]
+    fn audit_property_reads<T: PersonProperty + 'static>(
+        &mut self,
+        _property: T,
+        _action: AuditAction,
+    ) {
+        warn!("Ignoring audit of {}, which needs a debug build", T::name());
+    }
 
-        if !initializing {
-            if previous_value.unwrap() != value {
-                self.add_to_index_maybe(person_id, property);
-            }
+    #[cfg(not(debug_assertions))]
+    fn audit_property_writes<T: PersonProperty + 'static>(
+        &mut self,
+        _property: T,
+        _action: AuditAction,
+    ) {
+        warn!("Ignoring audit of {}, which needs a debug build", T::name());
+    }
 
-            let change_event: PersonPropertyChangeEvent<T> = PersonPropertyChangeEvent {
-                person_id,
-                current: value,
-                previous: previous_value.unwrap(), // This muse be Some() of !initializing
-            };
-            self.emit_event(change_event);
-        }
+    #[cfg(not(debug_assertions))]
+    fn stop_auditing_property<T: PersonProperty + 'static>(&mut self, _property: T) {}
 
-        for callback in dependency_event_callbacks {
-            callback(self);
-        }
+    fn allow_non_finite<T: PersonProperty + 'static>(&mut self, _property: T) {
+        self.get_data_container_mut(PeoplePlugin)
+            .non_finite_allowed
+            .insert(TypeId::of::<T>());
+    }
+
+    fn set_non_finite_check_interval(&mut self, interval: usize) {
+        let data_container = self.get_data_container_mut(PeoplePlugin);
+        data_container.non_finite_check_interval = interval;
+        data_container.non_finite_set_count = 0;
     }
 
     fn index_property<T: PersonProperty + 'static>(&mut self, _property: T) {
@@ -255,9 +1009,133 @@ impl ContextPeopleExt for Context {
         let mut index = data_container
             .get_index_ref_mut_by_prop(T::get_instance())
             .unwrap();
-        if index.lookup.is_none() {
+        if index.lookup.is_none() && index.custom.is_none() {
             index.lookup = Some(HashMap::new());
+            // Everyone is indexed again, so they're counted again.
+            if index.counts.is_some() {
+                index.counts = Some(HashMap::new());
+                index.max_indexed = 0;
+            }
+        }
+    }
+
+    fn index_property_counts<T: PersonProperty + 'static>(&mut self, _property: T) {
+        // Ensure that the data container exists
+        let _ = self.get_data_container_mut(PeoplePlugin);
+        self.register_property::<T>();
+
+        let data_container = self.get_data_container(PeoplePlugin).unwrap();
+        let mut index = data_container
+            .get_index_ref_mut_by_prop(T::get_instance())
+            .unwrap();
+        if index.counts.is_some() || index.custom.is_some() {
+            return;
+        }
+        // The people who are already indexed can be counted from the index.
+        let counts = index.lookup.as_ref().map_or_else(HashMap::new, |lookup| {
+            lookup
+                .iter()
+                .map(|(hash, (display, people))| (hash.clone(), (display.clone(), people.len())))
+                .collect()
+        });
+        index.counts = Some(counts);
+    }
+
+    fn install_custom_index<T: PersonProperty + 'static>(
+        &mut self,
+        _property: T,
+        custom_index: Box<dyn PropertyIndexImpl<T>>,
+    ) -> Result<(), IxaError> {
+        let _ = self.get_data_container_mut(PeoplePlugin);
+        self.register_property::<T>();
+
+        let data_container = self.get_data_container(PeoplePlugin).unwrap();
+        let mut index = data_container
+            .get_index_ref_mut_by_prop(T::get_instance())
+            .unwrap();
+        if index.is_indexed() {
+            return Err(IxaError::IxaError(format!(
+                "{} is already indexed",
+                T::name()
+            )));
+        }
+        index.custom = Some(custom_index::erase(custom_index));
+        Ok(())
+    }
+
+    fn subscribe_to_index_changes<T: PersonProperty + 'static>(
+        &mut self,
+        handler: impl Fn(&mut Context, IndexChange<T>) + 'static,
+    ) {
+        self.index_property(T::get_instance());
+        let handler = Rc::new(handler);
+        let handler_clone = Rc::clone(&handler);
+        self.subscribe_to_event(move |context, change: IndexChange<T>| {
+            handler_clone(context, change);
+        });
+
+        let data_container = self.get_data_container(PeoplePlugin).unwrap();
+        {
+            let mut index = data_container
+                .get_index_ref_mut_by_prop(T::get_instance())
+                .unwrap();
+            index.notify_changes = true;
+            index.index_unindexed_people(self);
+        }
+        let existing: Vec<(PersonId, T::Value)> = data_container
+            .people_iterator()
+            .map(|person_id| {
+                (
+                    person_id,
+                    self.get_person_property_unaudited(person_id, T::get_instance()),
+                )
+            })
+            .collect();
+        self.queue_callback(move |context| {
+            for (person_id, value) in existing {
+                handler(
+                    context,
+                    IndexChange {
+                        person_id,
+                        previous: None,
+                        current: Some(value),
+                    },
+                );
+            }
+        });
+    }
+
+    fn backfill_property<T: PersonProperty + 'static>(&mut self, property: T) -> BackfillStats {
+        let start = Instant::now();
+        let mut people_computed = 0;
+        if let Some(data_container) = self.get_data_container(PeoplePlugin) {
+            self.register_property::<T>();
+            for person_id in data_container.people_iterator() {
+                if T::is_derived()
+                    || data_container
+                        .get_person_property_ref(person_id, property)
+                        .is_none()
+                {
+                    self.get_person_property(person_id, property);
+                    people_computed += 1;
+                }
+            }
+            data_container
+                .get_index_ref_mut(TypeId::of::<T>())
+                .unwrap()
+                .index_unindexed_people(self);
         }
+        let stats = BackfillStats {
+            people_computed,
+            elapsed: start.elapsed(),
+        };
+        trace!(
+            "backfilled {} for {} people in {:?}",
+            T::name(),
+            stats.people_computed,
+            stats.elapsed
+        );
+        stats
     }
 
     fn query_people<T: Query>(&self, q: T) -> Vec<PersonId> {
@@ -273,10 +1151,54 @@ impl ContextPeopleExt for Context {
                 result.push(person);
             },
             q.get_query(),
+            q.get_value_filters(),
         );
         result
     }
 
+    fn query_people_any_value<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+        values: &[T::Value],
+    ) -> Vec<PersonId> {
+        self.query_people(AnyOf(property, values.to_vec()))
+    }
+
+    fn query_people_range<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+        range: impl RangeBounds<T::Value>,
+    ) -> Vec<PersonId>
+    where
+        T::Value: PartialOrd,
+    {
+        self.query_people(InRange(property, range))
+    }
+
+    fn query_top_k_people<T: PersonProperty + 'static, Q: Query>(
+        &self,
+        property: T,
+        query: Q,
+        k: usize,
+    ) -> Vec<PersonId>
+    where
+        T::Value: Ord,
+    {
+        select_k_people(self, property, query, k, |a, b| b.cmp(a))
+    }
+
+    fn query_bottom_k_people<T: PersonProperty + 'static, Q: Query>(
+        &self,
+        property: T,
+        query: Q,
+        k: usize,
+    ) -> Vec<PersonId>
+    where
+        T::Value: Ord,
+    {
+        select_k_people(self, property, query, k, Ord::cmp)
+    }
+
     fn query_people_count<T: Query>(&self, q: T) -> usize {
         // Special case the situation where nobody exists.
         if self.get_data_container(PeoplePlugin).is_none() {
@@ -284,12 +1206,31 @@ impl ContextPeopleExt for Context {
         }
 
         T::setup(self);
+        let property_hashes = q.get_query();
+        let value_filters = q.get_value_filters();
+        // A query for one value of a property with counts is answered by
+        // the count.
+        if let ([(t, hash)], true) = (property_hashes.as_slice(), value_filters.is_empty()) {
+            let data_container = self.get_data_container(PeoplePlugin).unwrap();
+            let mut index = data_container.get_index_ref_mut(*t).unwrap();
+            if index.counts.is_some() {
+                index.index_unindexed_people(self);
+                return index
+                    .counts
+                    .as_ref()
+                    .unwrap()
+                    .get(hash)
+                    .map_or(0, |(_, count)| *count);
+            }
+        }
+
         let mut count: usize = 0;
         self.query_people_internal(
             |_person| {
                 count += 1;
             },
-            q.get_query(),
+            property_hashes,
+            value_filters,
         );
         count
     }
@@ -307,6 +1248,11 @@ impl ContextPeopleExt for Context {
                 return false;
             }
         }
+        for (_, filter) in &q.get_value_filters() {
+            if !filter(self, person_id) {
+                return false;
+            }
+        }
         true
     }
 
@@ -321,6 +1267,13 @@ impl ContextPeopleExt for Context {
             return;
         }
         let instance = T::get_instance();
+        // Register the dependencies now, so that they don't need to be
+        // registered while computing the value, e.g., during indexing.
+        if T::is_derived() {
+            for dependency in T::dependencies() {
+                dependency.register(self);
+            }
+        }
         let dependencies = instance.non_derived_dependencies();
         for dependency in dependencies {
             let mut dependency_map = data_container.dependency_map.borrow_mut();
@@ -345,6 +1298,21 @@ impl ContextPeopleExt for Context {
     {
         let type_ids = tabulator.get_typelist();
 
+        // A single property with counts can be tabulated from them.
+        if let [t] = type_ids.as_slice() {
+            let data_container = self.get_data_container(PeoplePlugin)
+                .expect("PeoplePlugin is not initialized; make sure you add a person before accessing properties");
+            if let Some(mut index) = data_container.get_index_ref_mut(*t) {
+                if index.counts.is_some() {
+                    index.index_unindexed_people(self);
+                    for (display, count) in index.counts.as_ref().unwrap().values() {
+                        print_fn(self, std::slice::from_ref(display), *count);
+                    }
+                    return;
+                }
+            }
+        }
+
         // First, update indexes
         {
             let data_container = self.get_data_container(PeoplePlugin)
@@ -363,26 +1331,159 @@ impl ContextPeopleExt for Context {
             .property_indexes
             .borrow();
 
-        let indices = type_ids
-            .iter()
-            .filter_map(|t| index_container.get(t))
-            .collect::<Vec<&Index>>();
+        self.with_scratch(|scratch| {
+            let indices = scratch.collect(type_ids.iter().filter_map(|t| index_container.get(t)));
+            index::process_indices(
+                self,
+                indices.as_slice(),
+                &mut Vec::new(),
+                &IndexedPeople::new(),
+                &print_fn,
+            );
+        });
+    }
 
-        index::process_indices(
-            self,
-            indices.as_slice(),
-            &mut Vec::new(),
-            &HashSet::new(),
-            &print_fn,
-        );
+    fn tabulate_person_count<T: TabulationKey>(&self) -> HashMap<T::Values, usize>
+    where
+        T::Values: Eq,
+    {
+        let mut counts = HashMap::new();
+        let Some(data_container) = self.get_data_container(PeoplePlugin) else {
+            return counts;
+        };
+        T::register(self);
+        let type_ids = T::get_typelist();
+        for t in &type_ids {
+            data_container
+                .get_index_ref_mut(*t)
+                .unwrap()
+                .index_unindexed_people(self);
+        }
+
+        // Find one person with each combination of values, and the number
+        // of people who have it. The values are looked up afterward, because
+        // computing them may need to register other properties.
+        let mut groups = Vec::new();
+        {
+            let index_container = data_container.property_indexes.borrow();
+            let indices = type_ids
+                .iter()
+                .map(|t| index_container.get(t).unwrap())
+                .collect::<Vec<&Index>>();
+            if indices.iter().all(|index| index.lookup.is_some()) {
+                index::for_each_index_group(&indices, None, &mut |people| {
+                    let first = people.iter().min_by_key(|person_id| person_id.0).unwrap();
+                    groups.push((*first, people.len()));
+                });
+            }
+        }
+
+        if groups.is_empty() {
+            for person_id in data_container.people_iterator() {
+                *counts.entry(T::get_values(self, person_id)).or_insert(0) += 1;
+            }
+        } else {
+            for (person_id, count) in groups {
+                *counts.entry(T::get_values(self, person_id)).or_insert(0) += count;
+            }
+        }
+        counts
     }
 
-    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
     fn sample_person<R: RngId + 'static, T: Query>(
         &self,
         rng_id: R,
         query: T,
+    ) -> Result<Option<PersonId>, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        sample_matching_person(self, rng_id, &query)
+    }
+
+    fn sample_person_or<R: RngId + 'static, T: Query, F: FallbackQueries + ?Sized>(
+        &self,
+        rng_id: R,
+        query: T,
+        fallbacks: &F,
+    ) -> Result<Option<(PersonId, usize)>, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        if let Some(person_id) = sample_matching_person(self, rng_id, &query)? {
+            return Ok(Some((person_id, 0)));
+        }
+        Ok(fallbacks
+            .sample_first(self, rng_id)?
+            .map(|(person_id, position)| (person_id, position + 1)))
+    }
+
+    fn sample_person_weighted<R: RngId + 'static, T: Query, F>(
+        &self,
+        rng_id: R,
+        query: T,
+        weight_fn: F,
     ) -> Result<PersonId, IxaError>
+    where
+        R::RngType: Rng,
+        F: Fn(&Context, PersonId) -> f64,
+    {
+        if self.get_current_population() == 0 {
+            return Err(IxaError::IxaError(String::from("Empty population")));
+        }
+        T::setup(self);
+
+        // This is "A-Res" from Efraimidis and Spirakis, Weighted random
+        // sampling with a reservoir, https://doi.org/10.1016/j.ipl.2005.11.003
+        // Each person gets the key u^(1/weight) for u uniform in [0, 1), and
+        // the person with the largest key is selected. The keys are
+        // compared as logarithms so that small weights don't underflow.
+        let mut selected: Option<(PersonId, f64)> = None;
+        let mut matched = false;
+        let mut invalid: Option<(PersonId, f64)> = None;
+        self.query_people_internal(
+            |person_id| {
+                matched = true;
+                if invalid.is_some() {
+                    return;
+                }
+                let weight = weight_fn(self, person_id);
+                if !weight.is_finite() || weight < 0.0 {
+                    invalid = Some((person_id, weight));
+                    return;
+                }
+                if weight <= 0.0 {
+                    return;
+                }
+                let key = f64::ln(self.sample_range(rng_id, 0.0..1.0)) / weight;
+                if selected.is_none_or(|(_, best)| key > best) {
+                    selected = Some((person_id, key));
+                }
+            },
+            query.get_query(),
+            query.get_value_filters(),
+        );
+
+        if let Some((person_id, weight)) = invalid {
+            return Err(IxaError::IxaError(format!(
+                "Invalid weight {weight} for {person_id:?}"
+            )));
+        }
+        if !matched {
+            return Err(IxaError::IxaError(String::from("No one matches the query")));
+        }
+        selected
+            .map(|(person_id, _)| person_id)
+            .ok_or_else(|| IxaError::IxaError(String::from("All weights are 0")))
+    }
+
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    fn sample_people<R: RngId + 'static, T: Query>(
+        &self,
+        rng_id: R,
+        query: T,
+        k: usize,
+    ) -> Result<Vec<PersonId>, IxaError>
     where
         R::RngType: Rng,
     {
@@ -390,57 +1491,438 @@ impl ContextPeopleExt for Context {
             return Err(IxaError::IxaError(String::from("Empty population")));
         }
 
-        // Special case the empty query because we can do it in O(1).
-        if query.get_query().is_empty() {
-            let result = self.sample_range(rng_id, 0..self.get_current_population());
-            return Ok(PersonId(result));
+        // As with sample_person(), the empty query and a single indexed
+        // property can pick from a list directly.
+        let data_container = self.get_data_container(PeoplePlugin).unwrap();
+        let property_hashes = query.get_query();
+        let value_filters = query.get_value_filters();
+        if property_hashes.is_empty()
+            && value_filters.is_empty()
+            && data_container.removed_people.is_empty()
+        {
+            let population = self.get_current_population();
+            return Ok(sample_indices(self, rng_id, population, k)
+                .into_iter()
+                .map(PersonId)
+                .collect());
         }
 
         T::setup(self);
 
-        // This function implements "Algorithm L" from KIM-HUNG LI
-        // Reservoir-Sampling Algorithms of Time Complexity O(n(1 + log(N/n)))
-        // https://dl.acm.org/doi/pdf/10.1145/198429.198435
-        // Temporary variables.
-        let mut selected: Option<PersonId> = None;
-        let mut w: f64 = self.sample_range(rng_id, 0.0..1.0);
+        if let ([(t, hash)], true) = (property_hashes.as_slice(), value_filters.is_empty()) {
+            let mut index = data_container.get_index_ref_mut(*t).unwrap();
+            if index.lookup.is_some() {
+                index.index_unindexed_people(self);
+                let Some((_, people)) = index.lookup.as_ref().unwrap().get(hash) else {
+                    return Ok(Vec::new());
+                };
+                return Ok(sample_indices(self, rng_id, people.len(), k)
+                    .into_iter()
+                    .map(|i| people[i])
+                    .collect());
+            }
+        }
+
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+
+        // This is "Algorithm L" as in sample_person(), but keeping `k`
+        // people rather than one. `next` is the count at which the next
+        // person replaces one in the reservoir.
+        #[allow(clippy::cast_precision_loss)]
+        let k_f64 = k as f64;
+        let mut selected: Vec<PersonId> = Vec::with_capacity(k);
+        let mut w: f64 = (f64::ln(self.sample_range(rng_id, 0.0..1.0)) / k_f64).exp();
         let mut ctr: usize = 0;
-        let mut i: usize = 1;
+        let mut next: usize = 0;
+        let skip = |w: f64| {
+            ((f64::ln(self.sample_range(rng_id, 0.0..1.0)) / f64::ln(1.0 - w)).floor() as usize)
+                .saturating_add(1)
+        };
 
         self.query_people_internal(
             |person| {
                 ctr += 1;
-                if i == ctr {
-                    selected = Some(person);
-                    i += (f64::ln(self.sample_range(rng_id, 0.0..1.0)) / f64::ln(1.0 - w)).floor()
-                        as usize
-                        + 1;
-                    w *= self.sample_range(rng_id, 0.0..1.0);
+                if ctr <= k {
+                    selected.push(person);
+                    if ctr == k {
+                        next = k.saturating_add(skip(w));
+                    }
+                } else if ctr == next {
+                    selected[self.sample_range(rng_id, 0..k)] = person;
+                    w *= (f64::ln(self.sample_range(rng_id, 0.0..1.0)) / k_f64).exp();
+                    next = next.saturating_add(skip(w));
                 }
             },
-            query.get_query(),
+            property_hashes,
+            value_filters,
         );
-
-        selected.ok_or(IxaError::IxaError(String::from("No matching people")))
+        Ok(selected)
     }
-}
 
-pub trait ContextPeopleExtInternal {
-    fn register_indexer<T: PersonProperty + 'static>(&self);
-    fn add_to_index_maybe<T: PersonProperty + 'static>(&mut self, person_id: PersonId, property: T);
-    fn remove_from_index_maybe<T: PersonProperty + 'static>(
+    fn initialize_to_targets<R: RngId + 'static>(
         &mut self,
-        person_id: PersonId,
-        property: T,
+        rng_id: R,
+        targets: &[TargetSpec],
+    ) -> Result<(), IxaError>
+    where
+        R::RngType: Rng + Clone,
+    {
+        // People are chosen with a copy of the generator, which replaces
+        // it only if every target can be met.
+        let mut rng = self.sample(rng_id, |rng| rng.clone());
+        let selections = select_target_people(self, &mut rng, targets)?;
+        self.sample(rng_id, |stream| *stream = rng);
+        apply_targets(self, targets, selections);
+        Ok(())
+    }
+
+    fn distinct_property_values<T: PersonProperty + 'static>(
+        &self,
+        property: T,
+    ) -> Vec<(T::Value, usize)> {
+        self.register_property::<T>();
+        let counts = {
+            let data_container = self.get_data_container(PeoplePlugin).unwrap();
+            let mut index = data_container.get_index_ref_mut(TypeId::of::<T>()).unwrap();
+            index.index_unindexed_people(self);
+            index.value_counts(self)
+        };
+        counts
+            .into_iter()
+            .map(|(_, count, person_id)| (self.get_person_property(person_id, property), count))
+            .collect()
+    }
+
+    fn sample_property_value<R: RngId + 'static, T: PersonProperty + 'static>(
+        &self,
+        rng_id: R,
+        property: T,
+    ) -> Result<T::Value, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        // Every person is equally likely, so each value is chosen in
+        // proportion to the number of people who have it.
+        let person_id = self
+            .sample_person(rng_id, ())?
+            .ok_or_else(|| IxaError::IxaError(String::from("Empty population")))?;
+        Ok(self.get_person_property(person_id, property))
+    }
+
+    fn add_people_from_template<R: RngId + 'static>(
+        &mut self,
+        _rng_id: R,
+        template: &PersonTemplate,
+        count: usize,
+    ) -> Result<Vec<PersonId>, IxaError> {
+        add_people_from_template::<R>(self, template, count)
+    }
+
+    #[track_caller]
+    fn load_people_from_csv<R, T, F>(
+        &mut self,
+        path: &Path,
+        to_person: F,
+    ) -> Result<usize, IxaError>
+    where
+        R: DeserializeOwned,
+        T: InitializationList,
+        F: FnMut(&Context, R) -> T,
+    {
+        let location = std::panic::Location::caller();
+        load_people_from_csv(self, path, to_person).map_err(|error| IxaError::Context {
+            context: format!("Loading people from {}", path.display()),
+            location: location.to_string(),
+            source: Box::new(error),
+        })
+    }
+
+    #[track_caller]
+    fn load_people_from_csv_lazy<R, T, F>(
+        &mut self,
+        path: &Path,
+        to_person: F,
+        lazy_columns: Vec<LazyColumn>,
+    ) -> Result<usize, IxaError>
+    where
+        R: DeserializeOwned,
+        T: InitializationList,
+        F: FnMut(&Context, R) -> T,
+    {
+        let location = std::panic::Location::caller();
+        lazy::load_people_from_csv_lazy(self, path, to_person, lazy_columns).map_err(|error| {
+            IxaError::Context {
+                context: format!("Loading people from {}", path.display()),
+                location: location.to_string(),
+                source: Box::new(error),
+            }
+        })
+    }
+
+    fn materialize_lazy_property<T: PersonProperty + 'static>(
+        &self,
+        _property: T,
+    ) -> Result<bool, IxaError> {
+        lazy::materialize_pending(self, TypeId::of::<T>())
+    }
+
+    fn snapshot_properties(&self, specs: &[SnapshotSpec]) -> PropertySnapshot {
+        take_snapshot(self, specs)
+    }
+
+    fn diff_against(&self, snapshot: &PropertySnapshot) -> PropertyDiff {
+        diff_snapshot(self, snapshot)
+    }
+
+    fn property_summary<T, Q>(&self, property: T, query: Q) -> PropertySummary
+    where
+        T: PersonProperty + 'static,
+        T::Value: NumericValue,
+        Q: Query,
+    {
+        summarize(self, property, query)
+    }
+
+    fn property_summary_by<T, G, Q>(
+        &self,
+        property: T,
+        group: G,
+        query: Q,
+    ) -> Vec<(G::Value, PropertySummary)>
+    where
+        T: PersonProperty + 'static,
+        T::Value: NumericValue,
+        G: PersonProperty + 'static,
+        Q: Query,
+    {
+        summarize_by(self, property, group, query)
+    }
+}
+
+// The first `k` people matching `query` when they're ordered by their
+// values of `property` with `order`, and then by id.
+fn select_k_people<T: PersonProperty + 'static, Q: Query>(
+    context: &Context,
+    property: T,
+    query: Q,
+    k: usize,
+    order: impl Fn(&T::Value, &T::Value) -> Ordering,
+) -> Vec<PersonId> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut people: Vec<(T::Value, PersonId)> = context
+        .query_people(query)
+        .into_iter()
+        .map(|person_id| (context.get_person_property(person_id, property), person_id))
+        .collect();
+    let compare = |a: &(T::Value, PersonId), b: &(T::Value, PersonId)| {
+        order(&a.0, &b.0).then(a.1 .0.cmp(&b.1 .0))
+    };
+    if people.len() > k {
+        people.select_nth_unstable_by(k - 1, compare);
+        people.truncate(k);
+    }
+    people.sort_unstable_by(compare);
+    people.into_iter().map(|(_, person_id)| person_id).collect()
+}
+
+pub trait ContextPeopleExtInternal {
+    // Like get_person_property() and set_person_property(), but not
+    // reported to audits, for reads and writes that Ixa makes for its own
+    // bookkeeping.
+    fn get_person_property_unaudited<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        property: T,
+    ) -> T::Value;
+    fn set_person_property_unaudited<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        property: T,
+        value: T::Value,
+    );
+    fn register_indexer<T: PersonProperty + 'static>(&self);
+    fn add_to_index_maybe<T: PersonProperty + 'static>(&mut self, person_id: PersonId, property: T);
+    fn remove_from_index_maybe<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        property: T,
+    );
+    // Emits an `IndexChange` if anyone has subscribed to changes to the
+    // index of `T`
+    fn emit_index_change<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        previous: Option<T::Value>,
+        current: Option<T::Value>,
     );
+    // Indexes the people added since the indexes with subscribers were
+    // last updated and emits an `IndexChange` for each of them
+    fn index_new_people_for_subscribers(&mut self);
     fn query_people_internal(
         &self,
         accumulator: impl FnMut(PersonId),
         property_hashes: Vec<(TypeId, IndexValue)>,
+        value_filters: Vec<(TypeId, ValueFilter<'_>)>,
     );
 }
 
 impl ContextPeopleExtInternal for Context {
+    fn get_person_property_unaudited<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        property: T,
+    ) -> T::Value {
+        let data_container = self.get_data_container(PeoplePlugin)
+            .expect("PeoplePlugin is not initialized; make sure you add a person before accessing properties");
+        assert!(
+            data_container.removed_people.is_empty()
+                || !data_container.removed_people.contains(&person_id),
+            "Can't get {} of {person_id:?}, who has been removed",
+            T::name()
+        );
+        self.register_property::<T>();
+
+        if T::is_derived() {
+            let value = T::compute(self, person_id);
+            if cfg!(debug_assertions)
+                && !data_container
+                    .non_finite_allowed
+                    .contains(&TypeId::of::<T>())
+            {
+                assert!(
+                    !is_non_finite(&value),
+                    "Non-finite value {value:?} for derived property {} of {person_id:?}",
+                    T::name()
+                );
+            }
+            return value;
+        }
+
+        // Attempt to retrieve the existing value
+        if let Some(value) = *data_container.get_person_property_ref(person_id, property) {
+            return value;
+        }
+
+        // Read the property's column if it was loaded lazily.
+        if lazy::materialize_pending_or_panic::<T>(self) {
+            if let Some(value) = *data_container.get_person_property_ref(person_id, property) {
+                return value;
+            }
+        }
+
+        // Initialize the property. This does not fire a change event
+        let initialized_value = T::compute(self, person_id);
+        data_container.set_person_property(person_id, property, initialized_value);
+
+        initialized_value
+    }
+
+    #[allow(clippy::single_match_else)]
+    fn set_person_property_unaudited<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        property: T,
+        value: T::Value,
+    ) {
+        self.register_property::<T>();
+
+        assert!(!T::is_derived(), "Cannot set a derived property");
+        assert!(
+            !self
+                .get_data_container(PeoplePlugin)
+                .unwrap()
+                .removed_people
+                .contains(&person_id),
+            "Can't set {} of {person_id:?}, who has been removed",
+            T::name()
+        );
+        self.get_data_container_mut(PeoplePlugin).check_finite::<T>(
+            person_id,
+            &value,
+            cfg!(debug_assertions),
+        );
+
+        // This function can be called in two separate modes:
+        //
+        // 1. As a regular API function, in which case we want to
+        //    emit an event and notify dependencies.
+        // 2. Internally as part of initialization during add_person()
+        //    in which case no events are emitted.
+        //
+        // Which mode it is is determined by the data_container.is_initializing
+        // property, which is set by add_person. This is complicated but
+        // necessary because the initialization functions are called by
+        // a per-PersonProperty closure generated by a macro and so are
+        // outside of the crate, but we don't want to expose a public
+        // initialize_person_property() function.
+        //
+        // Temporarily remove dependency properties since we need mutable references
+        // to self during callback execution
+        let initializing = self
+            .get_data_container(PeoplePlugin)
+            .unwrap()
+            .is_initializing;
+
+        let (previous_value, deps_temp) = if initializing {
+            (None, None)
+        } else {
+            let previous_value = self.get_person_property_unaudited(person_id, property);
+            if previous_value != value {
+                self.remove_from_index_maybe(person_id, property);
+            }
+
+            (
+                Some(previous_value),
+                self.get_data_container(PeoplePlugin)
+                    .unwrap()
+                    .dependency_map
+                    .borrow_mut()
+                    .get_mut(&TypeId::of::<T>())
+                    .map(std::mem::take),
+            )
+        };
+
+        let mut dependency_event_callbacks = Vec::new();
+        if let Some(mut deps) = deps_temp {
+            // If there are dependencies, set up a bunch of callbacks with the
+            // current value
+            for dep in &mut deps {
+                dep.dependency_changed(self, person_id, &mut dependency_event_callbacks);
+            }
+
+            // Put the dependency list back in
+            let data_container = self.get_data_container(PeoplePlugin).unwrap();
+            let mut dependencies = data_container.dependency_map.borrow_mut();
+            dependencies.insert(TypeId::of::<T>(), deps);
+        }
+
+        // Update the main property and send a change event
+        let data_container = self.get_data_container(PeoplePlugin).unwrap();
+        data_container.set_person_property(person_id, property, value);
+
+        if !initializing {
+            if previous_value.unwrap() != value {
+                self.add_to_index_maybe(person_id, property);
+                self.emit_index_change::<T>(person_id, previous_value, Some(value));
+            }
+
+            let change_event: PersonPropertyChangeEvent<T> = PersonPropertyChangeEvent {
+                person_id,
+                current: value,
+                previous: previous_value.unwrap(), // This muse be Some() of !initializing
+            };
+            self.emit_event(change_event);
+        }
+
+        for callback in dependency_event_callbacks {
+            callback(self);
+        }
+    }
+
     fn register_indexer<T: PersonProperty + 'static>(&self) {
         {
             let data_container = self.get_data_container(PeoplePlugin).unwrap();
@@ -468,7 +1950,7 @@ impl ContextPeopleExtInternal for Context {
             .unwrap()
             .get_index_ref_mut_by_prop(property)
         {
-            if index.lookup.is_some() {
+            if index.is_indexed() {
                 index.add_person(self, person_id);
             }
         }
@@ -484,104 +1966,391 @@ impl ContextPeopleExtInternal for Context {
             .unwrap()
             .get_index_ref_mut_by_prop(property)
         {
-            if index.lookup.is_some() {
+            if index.is_indexed() {
                 index.remove_person(self, person_id);
             }
         }
     }
 
-    fn query_people_internal(
-        &self,
-        mut accumulator: impl FnMut(PersonId),
-        property_hashes: Vec<(TypeId, IndexValue)>,
+    fn emit_index_change<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        previous: Option<T::Value>,
+        current: Option<T::Value>,
     ) {
-        let mut indexes = Vec::<Ref<HashSet<PersonId>>>::new();
-        let mut unindexed = Vec::<(TypeId, IndexValue)>::new();
-        let data_container = self.get_data_container(PeoplePlugin)
-            .expect("PeoplePlugin is not initialized; make sure you add a person before accessing properties");
-
-        // 1. Walk through each property and update the indexes.
-        for (t, _) in &property_hashes {
-            let mut index = data_container.get_index_ref_mut(*t).unwrap();
-            index.index_unindexed_people(self);
+        let notify_changes = self
+            .get_data_container(PeoplePlugin)
+            .unwrap()
+            .get_index_ref(TypeId::of::<T>())
+            .is_some_and(|index| index.notify_changes);
+        if notify_changes {
+            self.emit_event(IndexChange::<T> {
+                person_id,
+                previous,
+                current,
+            });
         }
+    }
 
-        // 2. Collect the index entry corresponding to the value.
-        for (t, hash) in property_hashes {
-            let index = data_container.get_index_ref(t).unwrap();
-            if let Ok(lookup) = Ref::filter_map(index, |x| x.lookup.as_ref()) {
-                if let Ok(matching_people) =
-                    Ref::filter_map(lookup, |x| x.get(&hash).map(|entry| &entry.1))
-                {
-                    indexes.push(matching_people);
-                } else {
-                    // This is empty and so the intersection will
-                    // also be empty.
-                    return;
+    fn index_new_people_for_subscribers(&mut self) {
+        let mut additions = Vec::new();
+        {
+            let data_container = self.get_data_container(PeoplePlugin).unwrap();
+            let mut indexes = data_container.property_indexes.borrow_mut();
+            for index in indexes.values_mut().filter(|index| index.notify_changes) {
+                let first_unindexed = index.max_indexed;
+                index.index_unindexed_people(self);
+                for id in first_unindexed..index.max_indexed {
+                    if data_container.person_exists(PersonId(id)) {
+                        additions.push((index.name, index.emit_membership_change, PersonId(id)));
+                    }
                 }
-            } else {
-                // No index, so we'll get to this after.
-                unindexed.push((t, hash));
             }
         }
+        // Sort so that the order of the events doesn't depend on hashing.
+        additions.sort_by_key(|(name, _, _)| *name);
+        for (_, emit_membership_change, person_id) in additions {
+            emit_membership_change(self, person_id, true);
+        }
+    }
 
-        // 3. Create an iterator over people, based one either:
-        //    (1) the smallest index if there is one.
-        //    (2) the overall population if there are no indices.
+    fn query_people_internal(
+        &self,
+        accumulator: impl FnMut(PersonId),
+        property_hashes: Vec<(TypeId, IndexValue)>,
+        value_filters: Vec<(TypeId, ValueFilter<'_>)>,
+    ) {
+        self.with_scratch(|scratch| {
+            query_people_with_scratch(self, scratch, accumulator, property_hashes, value_filters);
+        });
+    }
+}
 
-        let holder: Ref<HashSet<PersonId>>;
-        let to_check: Box<dyn Iterator<Item = PersonId>> = if indexes.is_empty() {
-            Box::new(data_container.people_iterator())
+// Returns `min(k, n)` distinct indices in `0..n` chosen uniformly at
+// random, using Floyd's algorithm so that it takes O(k) time.
+fn sample_indices<R: RngId + 'static>(
+    context: &Context,
+    rng_id: R,
+    n: usize,
+    k: usize,
+) -> Vec<usize>
+where
+    R::RngType: Rng,
+{
+    if k >= n {
+        return (0..n).collect();
+    }
+    let mut selected = Vec::with_capacity(k);
+    let mut seen = HashSet::with_capacity(k);
+    for j in n - k..n {
+        let candidate = context.sample_range(rng_id, 0..=j);
+        let choice = if seen.contains(&candidate) {
+            j
         } else {
-            indexes.sort_by_key(|x| x.len());
-
-            holder = indexes.remove(0);
-            Box::new(holder.iter().copied())
+            candidate
         };
+        seen.insert(choice);
+        selected.push(choice);
+    }
+    selected
+}
 
-        // 4. Walk over the iterator and add people to the result
-        // iff:
-        //    (1) they exist in all the indexes
-        //    (2) they match the unindexed properties
-        'outer: for person in to_check {
-            // (1) check all the indexes
-            for index in &indexes {
-                if !index.contains(&person) {
-                    continue 'outer;
-                }
-            }
+// Samples a person who matches `query`, for `sample_person()` and the
+// queries of `sample_person_or()`.
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+pub(super) fn sample_matching_person<R: RngId + 'static, T: Query + ?Sized>(
+    context: &Context,
+    rng_id: R,
+    query: &T,
+) -> Result<Option<PersonId>, IxaError>
+where
+    R::RngType: Rng,
+{
+    if context.get_current_population() == 0 {
+        return Err(IxaError::IxaError(String::from("Empty population")));
+    }
 
-            // (2) check the unindexed properties
-            for (t, hash) in &unindexed {
-                let index = data_container.get_index_ref(*t).unwrap();
-                if *hash != (*index.indexer)(self, person) {
-                    continue 'outer;
-                }
+    // Special case the empty query because we can do it in O(1) if
+    // no one has been removed.
+    let no_one_removed = context
+        .get_data_container(PeoplePlugin)
+        .unwrap()
+        .removed_people
+        .is_empty();
+    let property_hashes = query.get_query();
+    let value_filters = query.get_value_filters();
+    if property_hashes.is_empty() && value_filters.is_empty() && no_one_removed {
+        let result = context.sample_range(rng_id, 0..context.get_current_population());
+        return Ok(Some(PersonId(result)));
+    }
+
+    T::setup(context);
+
+    // A single indexed property can also be done in O(1) by picking
+    // someone from its index entry.
+    if let ([(t, hash)], true) = (property_hashes.as_slice(), value_filters.is_empty()) {
+        let data_container = context.get_data_container(PeoplePlugin).unwrap();
+        let mut index = data_container.get_index_ref_mut(*t).unwrap();
+        if index.lookup.is_some() {
+            index.index_unindexed_people(context);
+            return Ok(index
+                .lookup
+                .as_ref()
+                .unwrap()
+                .get(hash)
+                .map(|(_, people)| people[context.sample_range(rng_id, 0..people.len())]));
+        }
+    }
+
+    // This function implements "Algorithm L" from KIM-HUNG LI
+    // Reservoir-Sampling Algorithms of Time Complexity O(n(1 + log(N/n)))
+    // https://dl.acm.org/doi/pdf/10.1145/198429.198435
+    // Temporary variables.
+    let mut selected: Option<PersonId> = None;
+    let mut w: f64 = context.sample_range(rng_id, 0.0..1.0);
+    let mut ctr: usize = 0;
+    let mut i: usize = 1;
+
+    context.query_people_internal(
+        |person| {
+            ctr += 1;
+            if i == ctr {
+                selected = Some(person);
+                i += (f64::ln(context.sample_range(rng_id, 0.0..1.0)) / f64::ln(1.0 - w)).floor()
+                    as usize
+                    + 1;
+                w *= context.sample_range(rng_id, 0.0..1.0);
             }
+        },
+        property_hashes,
+        value_filters,
+    );
+
+    Ok(selected)
+}
 
-            // This matches.
-            accumulator(person);
+fn update_indexes<'a>(context: &Context, types: impl Iterator<Item = &'a TypeId>) {
+    let data_container = context.get_data_container(PeoplePlugin).unwrap();
+    for t in types {
+        // Filters that aren't on a property don't have an index.
+        if let Some(mut index) = data_container.get_index_ref_mut(*t) {
+            index.index_unindexed_people(context);
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::people::{PeoplePlugin, PersonPropertyHolder};
-    use crate::random::{define_rng, ContextRandomExt};
-    use crate::{
-        define_derived_property, define_global_property, define_person_property,
-        define_person_property_with_default, Context, ContextGlobalPropertiesExt, ContextPeopleExt,
-        IxaError, PersonId, PersonPropertyChangeEvent,
-    };
-    use std::any::TypeId;
-    use std::cell::RefCell;
-    use std::rc::Rc;
+// The keys of the entries in `lookup` whose values pass `filter`.
+// Everyone in an entry has the same value, so only one of them needs to
+// be checked.
+fn passing_keys(
+    context: &Context,
+    lookup: &IndexLookup,
+    filter: &ValueFilter<'_>,
+) -> Vec<IndexValue> {
+    lookup
+        .iter()
+        .filter(|(_, (_, people))| {
+            people
+                .iter()
+                .next()
+                .is_some_and(|person| filter(context, *person))
+        })
+        .map(|(key, _)| key.clone())
+        .collect()
+}
 
-    define_person_property!(Age, u8);
-    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-    pub enum AgeGroupValue {
-        Child,
+// The people with a value from the custom index that should drive a
+// query, if one has fewer people than the narrowest built-in index or
+// filter entries, or there are none of those and it can't count.
+fn custom_source_people(
+    data_container: &PeopleData,
+    indexes: &[Ref<IndexedPeople>],
+    filter_indexes: &[(Ref<IndexLookup>, Vec<IndexValue>)],
+    custom_sources: &mut [(Option<usize>, TypeId, IndexValue)],
+) -> Option<Vec<PersonId>> {
+    let narrowest = indexes.iter().map(|people| people.len()).min().or_else(|| {
+        filter_indexes
+            .iter()
+            .map(|(lookup, keys)| keys.iter().map(|key| lookup[key].1.len()).sum())
+            .min()
+    });
+    custom_sources.sort_by_key(|(count, _, _)| count.unwrap_or(usize::MAX));
+    let (_, t, hash) = custom_sources
+        .first()
+        .filter(|(count, _, _)| match (count, narrowest) {
+            (_, None) => true,
+            (Some(count), Some(narrowest)) => *count < narrowest,
+            (None, Some(_)) => false,
+        })?;
+    let index = data_container.get_index_ref(*t).unwrap();
+    index.custom.as_ref().unwrap().get_people(hash)
+}
+
+fn query_people_with_scratch(
+    context: &Context,
+    scratch: &Scratch<'_>,
+    mut accumulator: impl FnMut(PersonId),
+    property_hashes: Vec<(TypeId, IndexValue)>,
+    value_filters: Vec<(TypeId, ValueFilter<'_>)>,
+) {
+    let mut indexes = scratch.vec::<Ref<IndexedPeople>>();
+    let mut unindexed = scratch.vec::<(TypeId, IndexValue)>();
+    // For each indexed value filter, the index and the keys of the
+    // entries whose values pass the filter.
+    let mut filter_indexes: Vec<(Ref<IndexLookup>, Vec<IndexValue>)> = Vec::new();
+    let mut unindexed_filters = Vec::new();
+    // For each property with a custom index that can list the people with
+    // a value, the number of them if the index can count them.
+    let mut custom_sources = scratch.vec::<(Option<usize>, TypeId, IndexValue)>();
+    let data_container = context.get_data_container(PeoplePlugin).expect(
+        "PeoplePlugin is not initialized; make sure you add a person before accessing properties",
+    );
+
+    // 1. Walk through each property and update the indexes.
+    let types = property_hashes.iter().map(|(t, _)| t);
+    update_indexes(context, types.chain(value_filters.iter().map(|(t, _)| t)));
+
+    // 2. Collect the index entry corresponding to the value.
+    for (t, hash) in property_hashes {
+        let index = data_container.get_index_ref(t).unwrap();
+        if let Some(custom) = &index.custom {
+            // People are still checked against the value, since the index
+            // might not be used to choose them.
+            let count = custom.get_count(&hash);
+            if count == Some(0) {
+                return;
+            }
+            if custom.supports_set_lookup() {
+                custom_sources.push((count, t, hash.clone()));
+            }
+            unindexed.push((t, hash));
+        } else if let Ok(lookup) = Ref::filter_map(index, |x| x.lookup.as_ref()) {
+            if let Ok(matching_people) =
+                Ref::filter_map(lookup, |x| x.get(&hash).map(|entry| &entry.1))
+            {
+                indexes.push(matching_people);
+            } else {
+                // This is empty and so the intersection will
+                // also be empty.
+                return;
+            }
+        } else {
+            // No index, so we'll get to this after.
+            unindexed.push((t, hash));
+        }
+    }
+
+    // Likewise collect the entries that pass each value filter. If no
+    // entries pass, nobody matches.
+    for (t, filter) in value_filters {
+        let Some(index) = data_container.get_index_ref(t) else {
+            unindexed_filters.push(filter);
+            continue;
+        };
+        if let Ok(lookup) = Ref::filter_map(index, |x| x.lookup.as_ref()) {
+            let keys = passing_keys(context, &lookup, &filter);
+            if keys.is_empty() {
+                return;
+            }
+            filter_indexes.push((lookup, keys));
+        } else {
+            unindexed_filters.push(filter);
+        }
+    }
+
+    // 3. Create an iterator over people, based one either:
+    //    (1) the smallest index if there is one.
+    //    (2) the smallest set of entries passing a value filter.
+    //    (3) the overall population if there are no indices.
+    //    A custom index is used instead if it has fewer people than the
+    //    chosen index or entries, or there are none and it can't count.
+
+    let holder: Ref<IndexedPeople>;
+    let filter_holder: (Ref<IndexLookup>, Vec<IndexValue>);
+    let custom_holder: Vec<PersonId>;
+    let to_check: Box<dyn Iterator<Item = PersonId>> = if let Some(people) = custom_source_people(
+        data_container,
+        &indexes,
+        &filter_indexes,
+        &mut custom_sources,
+    ) {
+        custom_holder = people;
+        Box::new(custom_holder.iter().copied())
+    } else if !indexes.is_empty() {
+        indexes.sort_by_key(|x| x.len());
+
+        holder = indexes.remove(0);
+        Box::new(holder.iter().copied())
+    } else if !filter_indexes.is_empty() {
+        filter_indexes.sort_by_key(|(lookup, keys)| {
+            keys.iter().map(|key| lookup[key].1.len()).sum::<usize>()
+        });
+
+        filter_holder = filter_indexes.remove(0);
+        let (lookup, keys) = &filter_holder;
+        Box::new(keys.iter().flat_map(|key| lookup[key].1.iter().copied()))
+    } else {
+        Box::new(data_container.people_iterator())
+    };
+
+    // 4. Walk over the iterator and add people to the result
+    // iff:
+    //    (1) they exist in all the indexes
+    //    (2) they exist in a passing entry of each indexed value filter
+    //    (3) they match the unindexed properties and value filters
+    'outer: for person in to_check {
+        // (1) check all the indexes
+        for index in &indexes {
+            if !index.contains(&person) {
+                continue 'outer;
+            }
+        }
+
+        // (2) check the entries passing the value filters
+        for (lookup, keys) in &filter_indexes {
+            if !keys.iter().any(|key| lookup[key].1.contains(&person)) {
+                continue 'outer;
+            }
+        }
+
+        // (3) check the unindexed properties and value filters
+        for (t, hash) in &unindexed {
+            let index = data_container.get_index_ref(*t).unwrap();
+            if *hash != (*index.indexer)(context, person) {
+                continue 'outer;
+            }
+        }
+        for filter in &unindexed_filters {
+            if !filter(context, person) {
+                continue 'outer;
+            }
+        }
+
+        // This matches.
+        accumulator(person);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::people::{PeoplePlugin, PersonPropertyHolder};
+    use crate::random::{define_rng, ContextRandomExt};
+    use crate::{
+        define_derived_property, define_global_property, define_person_property,
+        define_person_property_with_default, Context, ContextGlobalPropertiesExt, ContextPeopleExt,
+        IxaError, PersonId, PersonPropertyChangeEvent, PersonRemovedEvent,
+    };
+    use ordered_float::OrderedFloat;
+    use rand::Rng;
+    use std::any::TypeId;
+    use std::cell::RefCell;
+    use std::collections::{HashMap, HashSet};
+    use std::rc::Rc;
+
+    define_person_property!(Age, u8);
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub enum AgeGroupValue {
+        Child,
         Adult,
     }
     define_global_property!(ThresholdP, u8);
@@ -675,303 +2444,1280 @@ mod tests {
     }
 
     #[test]
-    fn add_person_with_initialize() {
-        let mut context = Context::new();
-
-        let person_id = context
-            .add_person(((Age, 42), (RiskCategory, RiskCategoryValue::Low)))
-            .unwrap();
-        assert_eq!(context.get_person_property(person_id, Age), 42);
+    fn add_person_with_initialize() {
+        let mut context = Context::new();
+
+        let person_id = context
+            .add_person(((Age, 42), (RiskCategory, RiskCategoryValue::Low)))
+            .unwrap();
+        assert_eq!(context.get_person_property(person_id, Age), 42);
+        assert_eq!(
+            context.get_person_property(person_id, RiskCategory),
+            RiskCategoryValue::Low
+        );
+    }
+
+    #[test]
+    fn add_person_with_initialize_missing() {
+        let mut context = Context::new();
+
+        context.add_person((Age, 10)).unwrap();
+        // Fails because we don't provide a value for Age
+        assert!(matches!(
+            context.add_person(()),
+            Err(IxaError::MissingRequiredProperties { entity: "Person", properties })
+                if properties == vec!["Age"]
+        ));
+    }
+
+    #[test]
+    fn add_person_lists_all_missing() {
+        let mut context = Context::new();
+
+        context
+            .add_person(((Age, 10), (RiskCategory, RiskCategoryValue::High)))
+            .unwrap();
+        assert!(matches!(
+            context.add_person((IsRunner, true)),
+            Err(IxaError::MissingRequiredProperties { entity: "Person", properties })
+                if properties == vec!["Age", "RiskCategory"]
+        ));
+        assert_eq!(context.get_current_population(), 1);
+    }
+
+    #[test]
+    fn add_person_duplicate_property() {
+        let mut context = Context::new();
+
+        assert!(matches!(
+            context.add_person(((Age, 10), (IsRunner, true), (Age, 11))),
+            Err(IxaError::DuplicateProperty {
+                entity: "Person",
+                property: "Age"
+            })
+        ));
+        assert_eq!(context.get_current_population(), 0);
+    }
+
+    #[test]
+    fn add_person_with_initialize_missing_first() {
+        let mut context = Context::new();
+
+        // Succeeds because context doesn't know about any properties
+        // yet.
+        context.add_person(()).unwrap();
+    }
+
+    #[test]
+    fn add_person_with_initialize_missing_with_default() {
+        let mut context = Context::new();
+
+        context.add_person((IsRunner, true)).unwrap();
+        // Succeeds because |IsRunner| has a default.
+        context.add_person(()).unwrap();
+    }
+
+    #[test]
+    fn person_debug_display() {
+        let mut context = Context::new();
+
+        let person_id = context.add_person(()).unwrap();
+        assert_eq!(format!("{person_id}"), "0");
+        assert_eq!(format!("{person_id:?}"), "Person 0");
+    }
+
+    #[test]
+    fn add_person_initializers() {
+        let mut context = Context::new();
+        let person_id = context.add_person(()).unwrap();
+
+        assert_eq!(context.get_person_property(person_id, RunningShoes), 0);
+        assert!(!context.get_person_property(person_id, IsRunner));
+    }
+
+    #[test]
+    fn property_initialization_is_lazy() {
+        let mut context = Context::new();
+        let person = context.add_person((IsRunner, true)).unwrap();
+        let people_data = context.get_data_container_mut(PeoplePlugin);
+
+        // Verify we haven't initialized the property yet
+        let has_value = *people_data.get_person_property_ref(person, RunningShoes);
+        assert!(has_value.is_none());
+
+        // This should initialize it
+        let value = context.get_person_property(person, RunningShoes);
+        assert_eq!(value, 4);
+    }
+
+    #[test]
+    fn initialize_without_initializer_succeeds() {
+        let mut context = Context::new();
+        context
+            .add_person((RiskCategory, RiskCategoryValue::High))
+            .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Property not initialized when person created")]
+    fn set_without_initializer_panics() {
+        let mut context = Context::new();
+        let person_id = context.add_person(()).unwrap();
+        context.set_person_property(person_id, RiskCategory, RiskCategoryValue::High);
+    }
+
+    #[test]
+    #[should_panic(expected = "Property not initialized when person created")]
+    fn get_without_initializer_panics() {
+        let mut context = Context::new();
+        let person_id = context.add_person(()).unwrap();
+        context.get_person_property(person_id, RiskCategory);
+    }
+
+    #[test]
+    fn get_person_property_returns_correct_value() {
+        let mut context = Context::new();
+        let person = context.add_person((Age, 10)).unwrap();
+        assert_eq!(
+            context.get_person_property(person, AgeGroup),
+            AgeGroupValue::Child
+        );
+    }
+
+    #[test]
+    fn get_person_property_changes_correctly() {
+        let mut context = Context::new();
+        let person = context.add_person((Age, 17)).unwrap();
+        assert_eq!(
+            context.get_person_property(person, AgeGroup),
+            AgeGroupValue::Child
+        );
+        context.set_person_property(person, Age, 18);
+        assert_eq!(
+            context.get_person_property(person, AgeGroup),
+            AgeGroupValue::Adult
+        );
+    }
+
+    #[test]
+    fn get_derived_property_multiple_deps() {
+        let mut context = Context::new();
+        let person = context.add_person(((Age, 17), (IsRunner, true))).unwrap();
+        let flag = Rc::new(RefCell::new(false));
+        let flag_clone = flag.clone();
+        context.subscribe_to_event(
+            move |_context, event: PersonPropertyChangeEvent<AdultRunner>| {
+                assert_eq!(event.person_id.0, 0);
+                assert!(!event.previous);
+                assert!(event.current);
+                *flag_clone.borrow_mut() = true;
+            },
+        );
+        context.set_person_property(person, Age, 18);
+        context.execute();
+        assert!(*flag.borrow());
+    }
+
+    #[test]
+    fn register_derived_only_once() {
+        let mut context = Context::new();
+        let person = context.add_person(((Age, 17), (IsRunner, true))).unwrap();
+
+        let flag = Rc::new(RefCell::new(0));
+        let flag_clone = flag.clone();
+        context.subscribe_to_event(
+            move |_context, _event: PersonPropertyChangeEvent<AdultRunner>| {
+                *flag_clone.borrow_mut() += 1;
+            },
+        );
+        context.subscribe_to_event(
+            move |_context, _event: PersonPropertyChangeEvent<AdultRunner>| {
+                // Make sure that we don't register multiple times
+            },
+        );
+        context.set_person_property(person, Age, 18);
+        context.execute();
+        assert_eq!(*flag.borrow(), 1);
+    }
+
+    #[test]
+    fn test_resolve_dependencies() {
+        let mut actual = SeniorRunner.non_derived_dependencies();
+        let mut expected = vec![TypeId::of::<Age>(), TypeId::of::<IsRunner>()];
+        actual.sort();
+        expected.sort();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn get_derived_property_dependent_on_another_derived() {
+        let mut context = Context::new();
+        let person = context.add_person(((Age, 88), (IsRunner, false))).unwrap();
+        let flag = Rc::new(RefCell::new(0));
+        let flag_clone = flag.clone();
+        assert!(!context.get_person_property(person, SeniorRunner));
+        context.subscribe_to_event(
+            move |_context, event: PersonPropertyChangeEvent<SeniorRunner>| {
+                assert_eq!(event.person_id.0, 0);
+                assert!(!event.previous);
+                assert!(event.current);
+                *flag_clone.borrow_mut() += 1;
+            },
+        );
+        context.set_person_property(person, IsRunner, true);
+        context.execute();
+        assert_eq!(*flag.borrow(), 1);
+    }
+
+    #[test]
+    fn get_derived_property_diamond_dependencies() {
+        let mut context = Context::new();
+        let person = context.add_person(((Age, 17), (IsSwimmer, true))).unwrap();
+
+        let flag = Rc::new(RefCell::new(0));
+        let flag_clone = flag.clone();
+        assert!(!context.get_person_property(person, AdultAthlete));
+        context.subscribe_to_event(
+            move |_context, event: PersonPropertyChangeEvent<AdultAthlete>| {
+                assert_eq!(event.person_id.0, 0);
+                assert!(!event.previous);
+                assert!(event.current);
+                *flag_clone.borrow_mut() += 1;
+            },
+        );
+        context.set_person_property(person, Age, 18);
+        context.execute();
+        assert_eq!(*flag.borrow(), 1);
+    }
+
+    #[test]
+    fn get_derived_property_with_globals() {
+        let mut context = Context::new();
+        context.set_global_property_value(ThresholdP, 18).unwrap();
+        let child = context.add_person((Age, 17)).unwrap();
+        let adult = context.add_person((Age, 19)).unwrap();
+        assert!(!context.get_person_property(child, IsEligible));
+        assert!(context.get_person_property(adult, IsEligible));
+    }
+
+    #[test]
+    fn text_match_person() {
+        let mut context = Context::new();
+        let person = context
+            .add_person(((Age, 42), (RiskCategory, RiskCategoryValue::High)))
+            .unwrap();
+        assert!(context.match_person(person, ((Age, 42), (RiskCategory, RiskCategoryValue::High))));
+        assert!(!context.match_person(person, ((Age, 43), (RiskCategory, RiskCategoryValue::High))));
+        assert!(!context.match_person(person, ((Age, 42), (RiskCategory, RiskCategoryValue::Low))));
+    }
+
+    #[test]
+    fn test_sample_person_simple() {
+        define_rng!(SampleRng1);
+        let mut context = Context::new();
+        context.init_random(42);
+        assert!(matches!(
+            context.sample_person(SampleRng1, ()),
+            Err(IxaError::IxaError(_))
+        ));
+        let person = context.add_person(()).unwrap();
+        assert_eq!(context.sample_person(SampleRng1, ()).unwrap(), Some(person));
+    }
+
+    #[test]
+    fn test_sample_matching_person() {
+        define_rng!(SampleRng2);
+
+        let mut context = Context::new();
+        context.init_random(42);
+
+        // Test an empty query.
+        assert!(matches!(
+            context.sample_person(SampleRng2, ()),
+            Err(IxaError::IxaError(_))
+        ));
+        let person1 = context.add_person((Age, 10)).unwrap();
+        let person2 = context.add_person((Age, 10)).unwrap();
+        let person3 = context.add_person((Age, 10)).unwrap();
+        let person4 = context.add_person((Age, 30)).unwrap();
+
+        // Test a non-matching query.
+        assert_eq!(context.sample_person(SampleRng2, (Age, 50)).unwrap(), None);
+
+        // See that the simple query always returns person3
+        for _ in 0..10 {
+            assert_eq!(
+                context.sample_person(SampleRng2, (Age, 30)).unwrap(),
+                Some(person4)
+            );
+        }
+
+        let mut count_p1: usize = 0;
+        let mut count_p2: usize = 0;
+        let mut count_p3: usize = 0;
+        for _ in 0..30000 {
+            let p = context
+                .sample_person(SampleRng2, (Age, 10))
+                .unwrap()
+                .unwrap();
+            if p == person1 {
+                count_p1 += 1;
+            } else if p == person2 {
+                count_p2 += 1;
+            } else if p == person3 {
+                count_p3 += 1;
+            } else {
+                panic!("Unexpected person");
+            }
+        }
+
+        // The chance of any of these being more unbalanced than this is ~10^{-4}
+        assert!(count_p1 >= 8700);
+        assert!(count_p2 >= 8700);
+        assert!(count_p3 >= 8700);
+    }
+
+    #[test]
+    fn sample_matching_person_indexed() {
+        define_rng!(IndexedSampleRng);
+        let mut context = Context::new();
+        context.init_random(42);
+        context.index_property(Age);
+        let people: Vec<PersonId> = (0..4)
+            .map(|_| context.add_person((Age, 10)).unwrap())
+            .collect();
+        context.add_person((Age, 30)).unwrap();
+        assert_eq!(
+            context.sample_person(IndexedSampleRng, (Age, 50)).unwrap(),
+            None
+        );
+
+        // Removed people and people who changed value aren't picked
+        context.remove_person(people[0]).unwrap();
+        context.set_person_property(people[1], Age, 30);
+        let mut counts = HashMap::new();
+        for _ in 0..30000 {
+            let person = context
+                .sample_person(IndexedSampleRng, (Age, 10))
+                .unwrap()
+                .unwrap();
+            *counts.entry(person).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 2);
+        assert!(counts[&people[2]] >= 14000);
+        assert!(counts[&people[3]] >= 14000);
+
+        // People added after the index was built can be picked
+        let person = context.add_person((Age, 70)).unwrap();
+        assert_eq!(
+            context.sample_person(IndexedSampleRng, (Age, 70)).unwrap(),
+            Some(person)
+        );
+    }
+
+    #[test]
+    fn sample_person_weighted_distribution() {
+        define_rng!(WeightedRng);
+        let mut context = Context::new();
+        context.init_random(42);
+        // Ages 0 to 4, weighted by age, and someone who doesn't match.
+        let people: Vec<PersonId> = (0..5)
+            .map(|age| {
+                context
+                    .add_person(((Age, age), (RiskCategory, RiskCategoryValue::High)))
+                    .unwrap()
+            })
+            .collect();
+        context
+            .add_person(((Age, 100), (RiskCategory, RiskCategoryValue::Low)))
+            .unwrap();
+
+        let trials = 20000;
+        let mut counts = HashMap::new();
+        for _ in 0..trials {
+            let person = context
+                .sample_person_weighted(
+                    WeightedRng,
+                    (RiskCategory, RiskCategoryValue::High),
+                    |context, person_id| f64::from(context.get_person_property(person_id, Age)),
+                )
+                .unwrap();
+            *counts.entry(person).or_insert(0) += 1;
+        }
+        assert!(!counts.contains_key(&people[0]));
+        assert_eq!(counts.len(), 4);
+
+        // Chi-squared with 3 degrees of freedom, p = 0.001
+        let chi_squared: f64 = (1u8..5)
+            .map(|age| {
+                let expected = f64::from(trials) * f64::from(age) / 10.0;
+                let observed = f64::from(counts[&people[usize::from(age)]]);
+                (observed - expected).powi(2) / expected
+            })
+            .sum();
+        assert!(chi_squared < 16.27, "chi-squared is {chi_squared}");
+    }
+
+    #[test]
+    fn sample_person_weighted_errors() {
+        define_rng!(WeightedErrorRng);
+        let mut context = Context::new();
+        context.init_random(42);
+        assert!(context
+            .sample_person_weighted(WeightedErrorRng, (), |_, _| 1.0)
+            .is_err());
+        let person = context.add_person((Age, 0)).unwrap();
+        context.add_person((Age, 1)).unwrap();
+        assert!(context
+            .sample_person_weighted(WeightedErrorRng, (Age, 5), |_, _| 1.0)
+            .is_err());
+        assert!(context
+            .sample_person_weighted(WeightedErrorRng, (), |_, _| 0.0)
+            .is_err());
+        assert!(context
+            .sample_person_weighted(WeightedErrorRng, (), |_, _| -1.0)
+            .is_err());
+        assert!(context
+            .sample_person_weighted(WeightedErrorRng, (), |_, _| f64::NAN)
+            .is_err());
+        assert_eq!(
+            context
+                .sample_person_weighted(WeightedErrorRng, (), |_, person_id| {
+                    if person_id == person {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                })
+                .unwrap(),
+            person
+        );
+    }
+
+    #[test]
+    fn sample_person_weighted_is_reproducible() {
+        define_rng!(ReproducibleWeightedRng);
+        let sample = |seed| {
+            let mut context = Context::new();
+            context.init_random(seed);
+            for age in 0..50 {
+                context.add_person((Age, age)).unwrap();
+            }
+            (0..10)
+                .map(|_| {
+                    context
+                        .sample_person_weighted(
+                            ReproducibleWeightedRng,
+                            (),
+                            |context, person_id| {
+                                f64::from(context.get_person_property(person_id, Age)) + 1.0
+                            },
+                        )
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sample(7), sample(7));
+        assert_ne!(sample(7), sample(8));
+    }
+
+    #[test]
+    fn sample_person_or_falls_back() {
+        define_rng!(FallbackRng);
+        let mut context = Context::new();
+        context.init_random(42);
+        assert!(matches!(
+            context.sample_person_or(FallbackRng, (Age, 10), &[(Age, 20)]),
+            Err(IxaError::IxaError(_))
+        ));
+        let child = context
+            .add_person(((Age, 10), (RiskCategory, RiskCategoryValue::High)))
+            .unwrap();
+        let adult = context
+            .add_person(((Age, 40), (RiskCategory, RiskCategoryValue::Low)))
+            .unwrap();
+
+        // The primary query matches.
+        assert_eq!(
+            context
+                .sample_person_or(FallbackRng, (Age, 10), &[(Age, 40)])
+                .unwrap(),
+            Some((child, 0))
+        );
+        // The second fallback is the first one that matches.
+        assert_eq!(
+            context
+                .sample_person_or(FallbackRng, (Age, 50), &[(Age, 60), (Age, 40)])
+                .unwrap(),
+            Some((adult, 2))
+        );
+        // Fallbacks of different types
+        assert_eq!(
+            context
+                .sample_person_or(
+                    FallbackRng,
+                    ((Age, 40), (RiskCategory, RiskCategoryValue::High)),
+                    &((Age, 50), (RiskCategory, RiskCategoryValue::High), ()),
+                )
+                .unwrap(),
+            Some((child, 2))
+        );
+        let fallbacks: Vec<(Age, u8)> = vec![(Age, 60), (Age, 70)];
+        assert_eq!(
+            context
+                .sample_person_or(FallbackRng, (Age, 50), fallbacks.as_slice())
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn sample_person_no_match_without_iterating() {
+        define_rng!(NoMatchRng);
+        let mut context = Context::new();
+        context.init_random(42);
+        context.index_property(Age);
+        for age in 0..100 {
+            context.add_person((Age, age)).unwrap();
+        }
+        assert_eq!(context.query_people_count((Age, 150)), 0);
+
+        // Senior isn't indexed, so each person looked at would compute it,
+        // but no one has the indexed age, so nobody is looked at.
+        let before = senior_computations();
+        assert_eq!(
+            context
+                .sample_person(NoMatchRng, ((Age, 150), (Senior, true)))
+                .unwrap(),
+            None
+        );
+        assert_eq!(senior_computations(), before);
+
+        // The same when the last person with a value changes.
+        let person = context
+            .sample_person(NoMatchRng, (Age, 99))
+            .unwrap()
+            .unwrap();
+        context.set_person_property(person, Age, 98);
+        let before = senior_computations();
+        assert_eq!(
+            context
+                .sample_person(NoMatchRng, ((Age, 99), (Senior, true)))
+                .unwrap(),
+            None
+        );
+        assert_eq!(senior_computations(), before);
+    }
+
+    fn sample_people_setup(indexed: bool) -> Context {
+        let mut context = Context::new();
+        context.init_random(42);
+        if indexed {
+            context.index_property(Age);
+        }
+        for i in 0..30 {
+            context
+                .add_person((Age, if i % 3 == 0 { 10 } else { 20 }))
+                .unwrap();
+        }
+        context
+    }
+
+    fn check_sample_people(indexed: bool) {
+        define_rng!(SamplePeopleRng);
+        let context = sample_people_setup(indexed);
+
+        let sample = context
+            .sample_people(SamplePeopleRng, (Age, 10), 4)
+            .unwrap();
+        assert_eq!(sample.len(), 4);
+        assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 4);
+        assert!(sample
+            .iter()
+            .all(|person| context.get_person_property(*person, Age) == 10));
+
+        // Asking for more than match returns all of them
+        let mut all = context
+            .sample_people(SamplePeopleRng, (Age, 10), 20)
+            .unwrap();
+        all.sort_by_key(|person| person.0);
+        let mut expected = context.query_people((Age, 10));
+        expected.sort_by_key(|person| person.0);
+        assert_eq!(all, expected);
+        assert!(context
+            .sample_people(SamplePeopleRng, (Age, 10), 0)
+            .unwrap()
+            .is_empty());
+        assert!(context
+            .sample_people(SamplePeopleRng, (Age, 30), 3)
+            .unwrap()
+            .is_empty());
+
+        // Each of the 10 matching people is picked 3/10 of the time
+        let mut counts: HashMap<PersonId, usize> = HashMap::new();
+        for _ in 0..30000 {
+            for person in context
+                .sample_people(SamplePeopleRng, (Age, 10), 3)
+                .unwrap()
+            {
+                *counts.entry(person).or_insert(0) += 1;
+            }
+        }
+        assert_eq!(counts.len(), 10);
+        for count in counts.values() {
+            assert!((8400..9600).contains(count), "{count}");
+        }
+    }
+
+    #[test]
+    fn sample_people() {
+        check_sample_people(false);
+    }
+
+    #[test]
+    fn sample_people_indexed() {
+        check_sample_people(true);
+    }
+
+    #[test]
+    fn sample_people_whole_population() {
+        define_rng!(SampleAllRng);
+        let mut context = Context::new();
+        context.init_random(42);
+        assert!(context.sample_people(SampleAllRng, (), 3).is_err());
+        for _ in 0..10 {
+            context.add_person((Age, 1)).unwrap();
+        }
+        let sample = context.sample_people(SampleAllRng, (), 3).unwrap();
+        assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 3);
+        assert_eq!(
+            context.sample_people(SampleAllRng, (), 30).unwrap().len(),
+            10
+        );
+
+        // Removed people aren't picked
+        for person in &sample {
+            context.remove_person(*person).unwrap();
+        }
+        let rest = context.sample_people(SampleAllRng, (), 30).unwrap();
+        assert_eq!(rest.len(), 7);
+        assert!(rest.iter().all(|person| !sample.contains(person)));
+    }
+
+    #[test]
+    fn sample_people_is_deterministic() {
+        define_rng!(DeterministicSampleRng);
+        let draw = |indexed| {
+            let context = sample_people_setup(indexed);
+            context
+                .sample_people(DeterministicSampleRng, (Age, 20), 5)
+                .unwrap()
+        };
+        assert_eq!(draw(false), draw(false));
+        assert_eq!(draw(true), draw(true));
+    }
+
+    #[test]
+    fn query_top_and_bottom_k_people() {
+        let mut context = Context::new();
+        let ages = [30, 10, 50, 30, 70, 10, 50];
+        let people: Vec<PersonId> = ages
+            .iter()
+            .map(|&age| context.add_person((Age, age)).unwrap())
+            .collect();
+
+        // Ties are broken by person id
+        assert_eq!(
+            context.query_top_k_people(Age, (), 4),
+            vec![people[4], people[2], people[6], people[0]]
+        );
+        assert_eq!(
+            context.query_bottom_k_people(Age, (), 3),
+            vec![people[1], people[5], people[0]]
+        );
+        // Only people matching the query are considered
+        assert_eq!(
+            context.query_top_k_people(Age, (Age, 30), 5),
+            vec![people[0], people[3]]
+        );
+        assert!(context.query_top_k_people(Age, (), 0).is_empty());
+        assert!(context.query_bottom_k_people(Age, (Age, 20), 2).is_empty());
+
+        // Asking for more than match returns everyone, in order
+        let everyone = context.query_bottom_k_people(Age, (), 100);
+        let mut expected = people.clone();
+        expected.sort_by_key(|person| (ages[person.0], person.0));
+        assert_eq!(everyone, expected);
+    }
+
+    fn distinct_values_setup(context: &mut Context) {
+        for age in [30, 10, 10, 50, 30, 10] {
+            context.add_person((Age, age)).unwrap();
+        }
+    }
+
+    #[test]
+    fn distinct_property_values() {
+        let mut context = Context::new();
+        distinct_values_setup(&mut context);
+        let values = context.distinct_property_values(Age);
+        assert_eq!(values, vec![(10, 3), (30, 2), (50, 1)]);
+
+        // Derived properties work too. Ties are ordered by hash.
+        assert_eq!(
+            context.distinct_property_values(AgeGroup),
+            vec![(AgeGroupValue::Child, 3), (AgeGroupValue::Adult, 3)]
+        );
+    }
+
+    #[test]
+    fn distinct_property_values_indexed_matches_unindexed() {
+        let mut unindexed = Context::new();
+        distinct_values_setup(&mut unindexed);
+
+        let mut indexed = Context::new();
+        indexed.index_property(Age);
+        indexed.index_property(AgeGroup);
+        distinct_values_setup(&mut indexed);
+
+        // People added after the index was built are picked up lazily
+        for context in [&mut unindexed, &mut indexed] {
+            context.add_person((Age, 50)).unwrap();
+            context.add_person((Age, 70)).unwrap();
+        }
+        assert_eq!(
+            indexed.distinct_property_values(Age),
+            unindexed.distinct_property_values(Age)
+        );
+        assert_eq!(
+            indexed.distinct_property_values(AgeGroup),
+            unindexed.distinct_property_values(AgeGroup)
+        );
+        assert_eq!(
+            indexed.distinct_property_values(Age),
+            vec![(10, 3), (30, 2), (50, 2), (70, 1)]
+        );
+    }
+
+    #[test]
+    fn distinct_property_values_after_changes() {
+        let mut context = Context::new();
+        context.index_property(RiskCategory);
+        let people: Vec<PersonId> = (0..4)
+            .map(|_| {
+                context
+                    .add_person((RiskCategory, RiskCategoryValue::Low))
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(
+            context.distinct_property_values(RiskCategory),
+            vec![(RiskCategoryValue::Low, 4)]
+        );
+
+        context.set_person_property(people[0], RiskCategory, RiskCategoryValue::High);
+        context.set_person_property(people[1], RiskCategory, RiskCategoryValue::High);
+        context.set_person_property(people[2], RiskCategory, RiskCategoryValue::High);
+        assert_eq!(
+            context.distinct_property_values(RiskCategory),
+            vec![(RiskCategoryValue::High, 3), (RiskCategoryValue::Low, 1)]
+        );
+
+        // Values nobody has any more are left out
+        context.set_person_property(people[3], RiskCategory, RiskCategoryValue::High);
+        assert_eq!(
+            context.distinct_property_values(RiskCategory),
+            vec![(RiskCategoryValue::High, 4)]
+        );
+    }
+
+    #[test]
+    fn sample_property_value_weighted_by_count() {
+        define_rng!(ValueRng);
+        let mut context = Context::new();
+        context.init_random(42);
+        assert!(matches!(
+            context.sample_property_value(ValueRng, Age),
+            Err(IxaError::IxaError(_))
+        ));
+        distinct_values_setup(&mut context);
+
+        let mut counts = HashMap::<u8, usize>::new();
+        for _ in 0..60000 {
+            *counts
+                .entry(context.sample_property_value(ValueRng, Age).unwrap())
+                .or_default() += 1;
+        }
+        // Expected counts are 30000, 20000 and 10000 with standard deviations
+        // below 125, so these bounds are more than 4 standard deviations away.
+        assert!(counts[&10].abs_diff(30000) < 500);
+        assert!(counts[&30].abs_diff(20000) < 500);
+        assert!(counts[&50].abs_diff(10000) < 500);
+        assert_eq!(counts.len(), 3);
+    }
+
+    define_person_property!(Weight, OrderedFloat<f64>);
+    define_derived_property!(InverseWeight, OrderedFloat<f64>, [Weight], |weight| {
+        OrderedFloat(1.0) / weight
+    });
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Non-finite value NaN for property Weight of Person 0")]
+    fn set_non_finite_property_panics() {
+        let mut context = Context::new();
+        let person = context.add_person((Weight, OrderedFloat(70.0))).unwrap();
+        context.set_person_property(person, Weight, OrderedFloat(f64::NAN));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Non-finite value inf for property Weight of Person 0")]
+    fn add_person_non_finite_property_panics() {
+        let mut context = Context::new();
+        let _ = context.add_person((Weight, OrderedFloat(f64::INFINITY)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(
+        expected = "Non-finite value inf for derived property InverseWeight of Person 0"
+    )]
+    fn derived_non_finite_property_panics() {
+        let mut context = Context::new();
+        let person = context.add_person((Weight, OrderedFloat(0.0))).unwrap();
+        let _ = context.get_person_property(person, InverseWeight);
+    }
+
+    #[test]
+    fn allow_non_finite_property() {
+        let mut context = Context::new();
+        context.allow_non_finite(Weight);
+        context.allow_non_finite(InverseWeight);
+        let person = context.add_person((Weight, OrderedFloat(0.0))).unwrap();
+        assert_eq!(
+            context.get_person_property(person, InverseWeight),
+            OrderedFloat(f64::INFINITY)
+        );
+        context.set_person_property(person, Weight, OrderedFloat(f64::NEG_INFINITY));
+        assert_eq!(
+            context.get_person_property(person, Weight),
+            OrderedFloat(f64::NEG_INFINITY)
+        );
+    }
+
+    #[test]
+    fn non_finite_check_is_sampled_when_not_strict() {
+        let mut context = Context::new();
+        context.set_non_finite_check_interval(2);
+        let nan = OrderedFloat(f64::NAN);
+        let data_container = context.get_data_container_mut(PeoplePlugin);
+        // Only the 2nd and 4th values are checked.
+        data_container.check_finite::<Weight>(PersonId(0), &nan, false);
+        assert_eq!(data_container.non_finite_warning_count, 0);
+        data_container.check_finite::<Weight>(PersonId(0), &nan, false);
+        assert_eq!(data_container.non_finite_warning_count, 1);
+        data_container.check_finite::<Weight>(PersonId(0), &OrderedFloat(1.0), false);
+        data_container.check_finite::<Weight>(PersonId(0), &OrderedFloat(1.0), false);
+        assert_eq!(data_container.non_finite_warning_count, 1);
+
+        context.set_non_finite_check_interval(0);
+        let data_container = context.get_data_container_mut(PeoplePlugin);
+        data_container.check_finite::<Weight>(PersonId(0), &nan, false);
+        data_container.check_finite::<Weight>(PersonId(0), &nan, false);
+        assert_eq!(data_container.non_finite_warning_count, 1);
+    }
+
+    #[test]
+    fn non_finite_values_are_recognized() {
+        use crate::people::property::is_non_finite;
+
+        assert!(is_non_finite(&f64::NAN));
+        assert!(is_non_finite(&f32::INFINITY));
+        assert!(is_non_finite(&OrderedFloat(f64::NEG_INFINITY)));
+        assert!(is_non_finite(&Some(OrderedFloat(f32::NAN))));
+        assert!(!is_non_finite(&OrderedFloat(1.0)));
+        assert!(!is_non_finite(&None::<OrderedFloat<f64>>));
+        assert!(!is_non_finite(&42u8));
+    }
+
+    define_rng!(PeopleRng);
+    define_person_property!(
+        InitialAge,
+        u8,
+        init_distribution = |rng| rng.gen_range(0..100)
+    );
+
+    fn initial_ages(seed: u64, eager: bool, reverse: bool) -> Vec<u8> {
+        let mut context = Context::new();
+        context.init_random(seed);
+        let mut people = Vec::new();
+        let mut eager_ages = Vec::new();
+        for _ in 0..20 {
+            let person = context.add_person(()).unwrap();
+            if eager {
+                eager_ages.push(context.get_person_property(person, InitialAge));
+            }
+            people.push(person);
+        }
+        if eager {
+            return eager_ages;
+        }
+        if reverse {
+            people.reverse();
+        }
+        let mut ages: Vec<(usize, u8)> = people
+            .into_iter()
+            .map(|person| (person.0, context.get_person_property(person, InitialAge)))
+            .collect();
+        ages.sort_unstable();
+        ages.into_iter().map(|(_, age)| age).collect()
+    }
+
+    #[test]
+    fn init_distribution_is_independent_of_access_order() {
         assert_eq!(
-            context.get_person_property(person_id, RiskCategory),
-            RiskCategoryValue::Low
+            initial_ages(42, false, false),
+            initial_ages(42, false, true)
+        );
+        assert_eq!(
+            initial_ages(42, true, false),
+            initial_ages(42, false, false)
         );
     }
 
     #[test]
-    fn add_person_with_initialize_missing() {
-        let mut context = Context::new();
-
-        context.add_person((Age, 10)).unwrap();
-        // Fails because we don't provide a value for Age
-        assert!(matches!(context.add_person(()), Err(IxaError::IxaError(_))));
+    fn init_distribution_is_deterministic_per_seed() {
+        let ages = initial_ages(42, false, false);
+        assert_eq!(ages, initial_ages(42, false, false));
+        assert_ne!(ages, initial_ages(43, false, false));
+        // People don't all get the same value.
+        assert!(ages.iter().any(|age| *age != ages[0]));
     }
 
     #[test]
-    fn add_person_with_initialize_missing_first() {
+    fn init_distribution_uses_provided_value() {
         let mut context = Context::new();
-
-        // Succeeds because context doesn't know about any properties
-        // yet.
-        context.add_person(()).unwrap();
+        context.init_random(42);
+        let person = context.add_person((InitialAge, 150)).unwrap();
+        assert_eq!(context.get_person_property(person, InitialAge), 150);
     }
 
     #[test]
-    fn add_person_with_initialize_missing_with_default() {
+    fn init_distribution_does_not_use_model_rngs() {
         let mut context = Context::new();
+        context.init_random(42);
+        let expected = context.sample_range(PeopleRng, 0..u64::MAX);
 
-        context.add_person((IsRunner, true)).unwrap();
-        // Succeeds because |IsRunner| has a default.
-        context.add_person(()).unwrap();
+        let mut context = Context::new();
+        context.init_random(42);
+        let person = context.add_person(()).unwrap();
+        let _ = context.get_person_property(person, InitialAge);
+        assert_eq!(context.sample_range(PeopleRng, 0..u64::MAX), expected);
     }
 
-    #[test]
-    fn person_debug_display() {
+    fn removal_setup(context: &mut Context) -> Vec<PersonId> {
+        [10, 30, 10, 50, 30]
+            .into_iter()
+            .map(|age| {
+                context
+                    .add_person(((Age, age), (RiskCategory, RiskCategoryValue::Low)))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn remove_person_queries(indexed: bool) {
         let mut context = Context::new();
+        if indexed {
+            context.index_property(Age);
+            context.index_property(AgeGroup);
+        }
+        let people = removal_setup(&mut context);
+        assert_eq!(context.query_people_count((Age, 10)), 2);
+
+        context.remove_person(people[0]).unwrap();
+        assert_eq!(context.get_current_population(), 4);
+        assert!(!context.person_exists(people[0]));
+        assert!(context.person_exists(people[1]));
+        assert_eq!(context.query_people((Age, 10)), vec![people[2]]);
+        assert_eq!(
+            context.query_people_count((AgeGroup, AgeGroupValue::Child)),
+            1
+        );
+        assert_eq!(context.query_people(()).len(), 4);
+        assert_eq!(context.query_people_range(Age, ..20), vec![people[2]]);
+        assert_eq!(
+            context.distinct_property_values(Age),
+            vec![(30, 2), (10, 1), (50, 1)]
+        );
 
-        let person_id = context.add_person(()).unwrap();
-        assert_eq!(format!("{person_id}"), "0");
-        assert_eq!(format!("{person_id:?}"), "Person 0");
+        // Ids aren't reused
+        let person = context
+            .add_person(((Age, 10), (RiskCategory, RiskCategoryValue::High)))
+            .unwrap();
+        assert_ne!(person, people[0]);
+        assert_eq!(context.query_people_count((Age, 10)), 2);
+        assert_eq!(context.get_current_population(), 5);
     }
 
     #[test]
-    fn add_person_initializers() {
-        let mut context = Context::new();
-        let person_id = context.add_person(()).unwrap();
+    fn remove_person_unindexed() {
+        remove_person_queries(false);
+    }
 
-        assert_eq!(context.get_person_property(person_id, RunningShoes), 0);
-        assert!(!context.get_person_property(person_id, IsRunner));
+    #[test]
+    fn remove_person_indexed() {
+        remove_person_queries(true);
     }
 
     #[test]
-    fn property_initialization_is_lazy() {
+    fn remove_person_index_created_later() {
         let mut context = Context::new();
-        let person = context.add_person((IsRunner, true)).unwrap();
-        let people_data = context.get_data_container_mut(PeoplePlugin);
-
-        // Verify we haven't initialized the property yet
-        let has_value = *people_data.get_person_property_ref(person, RunningShoes);
-        assert!(has_value.is_none());
+        let people = removal_setup(&mut context);
+        context.remove_person(people[3]).unwrap();
+        context.index_property(Age);
+        assert!(context.query_people((Age, 50)).is_empty());
+    }
 
-        // This should initialize it
-        let value = context.get_person_property(person, RunningShoes);
-        assert_eq!(value, 4);
+    fn assert_counts_match_query(context: &Context) {
+        for age in [10, 30, 50, 70] {
+            let expected = context
+                .query_people(())
+                .into_iter()
+                .filter(|person| context.get_person_property(*person, Age) == age)
+                .count();
+            assert_eq!(context.query_people_count((Age, age)), expected);
+        }
+        for group in [AgeGroupValue::Child, AgeGroupValue::Adult] {
+            let expected = context
+                .query_people(())
+                .into_iter()
+                .filter(|person| context.get_person_property(*person, AgeGroup) == group)
+                .count();
+            assert_eq!(context.query_people_count((AgeGroup, group)), expected);
+        }
     }
 
     #[test]
-    fn initialize_without_initializer_succeeds() {
+    fn property_counts_follow_changes() {
         let mut context = Context::new();
+        context.index_property_counts(Age);
+        context.index_property_counts(AgeGroup);
+        let people = removal_setup(&mut context);
+        assert_counts_match_query(&context);
+
+        context.set_person_property(people[0], Age, 70);
+        assert_counts_match_query(&context);
+        context.update_people((Age, 30), Age, 10);
+        assert_counts_match_query(&context);
+        context.remove_person(people[2]).unwrap();
+        assert_counts_match_query(&context);
         context
-            .add_person((RiskCategory, RiskCategoryValue::High))
+            .add_person(((Age, 50), (RiskCategory, RiskCategoryValue::Low)))
             .unwrap();
+        assert_counts_match_query(&context);
+        assert_eq!(context.query_people_count((Age, 30)), 0);
+
+        // Only the counts are kept
+        let data_container = context.get_data_container(PeoplePlugin).unwrap();
+        let index = data_container.get_index_ref(TypeId::of::<Age>()).unwrap();
+        assert!(index.lookup.is_none());
     }
 
     #[test]
-    #[should_panic(expected = "Property not initialized when person created")]
-    fn set_without_initializer_panics() {
+    fn property_counts_with_index() {
+        // Counts added to an existing index
         let mut context = Context::new();
-        let person_id = context.add_person(()).unwrap();
-        context.set_person_property(person_id, RiskCategory, RiskCategoryValue::High);
+        let people = removal_setup(&mut context);
+        context.index_property(Age);
+        assert_eq!(context.query_people_count((Age, 10)), 2);
+        context.index_property_counts(Age);
+        context.set_person_property(people[0], Age, 50);
+        assert_counts_match_query(&context);
+        assert_eq!(context.query_people((Age, 50)).len(), 2);
+
+        // An index added to existing counts
+        let mut context = Context::new();
+        context.index_property_counts(Age);
+        let people = removal_setup(&mut context);
+        assert_eq!(context.query_people_count((Age, 10)), 2);
+        context.index_property(Age);
+        context.remove_person(people[1]).unwrap();
+        assert_counts_match_query(&context);
+        assert_eq!(context.query_people((Age, 30)), vec![people[4]]);
     }
 
     #[test]
-    #[should_panic(expected = "Property not initialized when person created")]
-    fn get_without_initializer_panics() {
+    fn remove_person_errors() {
         let mut context = Context::new();
-        let person_id = context.add_person(()).unwrap();
-        context.get_person_property(person_id, RiskCategory);
+        assert!(matches!(
+            context.remove_person(PersonId(0)),
+            Err(IxaError::PersonNotFound(PersonId(0)))
+        ));
+        let people = removal_setup(&mut context);
+        context.remove_person(people[1]).unwrap();
+        assert!(matches!(
+            context.remove_person(people[1]),
+            Err(IxaError::PersonNotFound(_))
+        ));
+        assert!(matches!(
+            context.try_get_person_property(people[1], Age),
+            Err(IxaError::PersonNotFound(person)) if person == people[1]
+        ));
+        assert_eq!(context.try_get_person_property(people[2], Age).unwrap(), 10);
     }
 
     #[test]
-    fn get_person_property_returns_correct_value() {
+    #[should_panic(expected = "who has been removed")]
+    fn get_property_of_removed_person() {
         let mut context = Context::new();
-        let person = context.add_person((Age, 10)).unwrap();
-        assert_eq!(
-            context.get_person_property(person, AgeGroup),
-            AgeGroupValue::Child
-        );
+        let people = removal_setup(&mut context);
+        context.remove_person(people[0]).unwrap();
+        context.get_person_property(people[0], IsRunner);
     }
 
     #[test]
-    fn get_person_property_changes_correctly() {
+    #[should_panic(expected = "who has been removed")]
+    fn set_property_of_removed_person() {
         let mut context = Context::new();
-        let person = context.add_person((Age, 17)).unwrap();
-        assert_eq!(
-            context.get_person_property(person, AgeGroup),
-            AgeGroupValue::Child
-        );
-        context.set_person_property(person, Age, 18);
-        assert_eq!(
-            context.get_person_property(person, AgeGroup),
-            AgeGroupValue::Adult
-        );
+        let people = removal_setup(&mut context);
+        context.remove_person(people[0]).unwrap();
+        context.set_person_property(people[0], Age, 20);
     }
 
     #[test]
-    fn get_derived_property_multiple_deps() {
+    fn remove_person_event_and_plans() {
         let mut context = Context::new();
-        let person = context.add_person(((Age, 17), (IsRunner, true))).unwrap();
-        let flag = Rc::new(RefCell::new(false));
-        let flag_clone = flag.clone();
-        context.subscribe_to_event(
-            move |_context, event: PersonPropertyChangeEvent<AdultRunner>| {
-                assert_eq!(event.person_id.0, 0);
-                assert!(!event.previous);
-                assert!(event.current);
-                *flag_clone.borrow_mut() = true;
-            },
-        );
-        context.set_person_property(person, Age, 18);
+        let people = removal_setup(&mut context);
+        let removed = Rc::new(RefCell::new(Vec::new()));
+        let removed_clone = Rc::clone(&removed);
+        context.subscribe_to_event(move |context, event: PersonRemovedEvent| {
+            assert!(!context.person_exists(event.person_id));
+            removed_clone.borrow_mut().push(event.person_id);
+        });
+
+        let person = people[4];
+        context.add_plan(1.0, move |context| {
+            context.remove_person(person).unwrap();
+        });
+        // A plan that captured the removed person has to check for them
+        let checked = Rc::new(RefCell::new(None));
+        let checked_clone = Rc::clone(&checked);
+        context.add_plan(2.0, move |context| {
+            *checked_clone.borrow_mut() = Some(context.person_exists(person));
+        });
         context.execute();
-        assert!(*flag.borrow());
+
+        assert_eq!(*removed.borrow(), vec![person]);
+        assert_eq!(*checked.borrow(), Some(false));
     }
 
     #[test]
-    fn register_derived_only_once() {
+    fn sample_person_skips_removed() {
+        define_rng!(RemovalRng);
         let mut context = Context::new();
-        let person = context.add_person(((Age, 17), (IsRunner, true))).unwrap();
+        context.init_random(42);
+        let people = removal_setup(&mut context);
+        for person in &people[1..] {
+            context.remove_person(*person).unwrap();
+        }
+        for _ in 0..20 {
+            assert_eq!(
+                context.sample_person(RemovalRng, ()).unwrap(),
+                Some(people[0])
+            );
+        }
+        context.remove_person(people[0]).unwrap();
+        assert!(context.sample_person(RemovalRng, ()).is_err());
+    }
 
-        let flag = Rc::new(RefCell::new(0));
-        let flag_clone = flag.clone();
-        context.subscribe_to_event(
-            move |_context, _event: PersonPropertyChangeEvent<AdultRunner>| {
-                *flag_clone.borrow_mut() += 1;
-            },
-        );
-        context.subscribe_to_event(
-            move |_context, _event: PersonPropertyChangeEvent<AdultRunner>| {
-                // Make sure that we don't register multiple times
-            },
-        );
-        context.set_person_property(person, Age, 18);
-        context.execute();
-        assert_eq!(*flag.borrow(), 1);
+    thread_local! {
+        static SENIOR_COMPUTATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
     }
 
-    #[test]
-    fn test_resolve_dependencies() {
-        let mut actual = SeniorRunner.non_derived_dependencies();
-        let mut expected = vec![TypeId::of::<Age>(), TypeId::of::<IsRunner>()];
-        actual.sort();
-        expected.sort();
-        assert_eq!(actual, expected);
+    define_derived_property!(Senior, bool, [Age], |age| {
+        SENIOR_COMPUTATIONS.with(|count| count.set(count.get() + 1));
+        age >= 65
+    });
+
+    fn senior_computations() -> usize {
+        SENIOR_COMPUTATIONS.with(std::cell::Cell::get)
     }
 
-    #[test]
-    fn get_derived_property_dependent_on_another_derived() {
-        let mut context = Context::new();
-        let person = context.add_person(((Age, 88), (IsRunner, false))).unwrap();
-        let flag = Rc::new(RefCell::new(0));
-        let flag_clone = flag.clone();
-        assert!(!context.get_person_property(person, SeniorRunner));
-        context.subscribe_to_event(
-            move |_context, event: PersonPropertyChangeEvent<SeniorRunner>| {
-                assert_eq!(event.person_id.0, 0);
-                assert!(!event.previous);
-                assert!(event.current);
-                *flag_clone.borrow_mut() += 1;
-            },
-        );
-        context.set_person_property(person, IsRunner, true);
-        context.execute();
-        assert_eq!(*flag.borrow(), 1);
+    fn backfill_setup(context: &mut Context) {
+        for age in 0..100 {
+            context.add_person((Age, age)).unwrap();
+        }
     }
 
     #[test]
-    fn get_derived_property_diamond_dependencies() {
+    fn backfill_property_index_before() {
         let mut context = Context::new();
-        let person = context.add_person(((Age, 17), (IsSwimmer, true))).unwrap();
-
-        let flag = Rc::new(RefCell::new(0));
-        let flag_clone = flag.clone();
-        assert!(!context.get_person_property(person, AdultAthlete));
-        context.subscribe_to_event(
-            move |_context, event: PersonPropertyChangeEvent<AdultAthlete>| {
-                assert_eq!(event.person_id.0, 0);
-                assert!(!event.previous);
-                assert!(event.current);
-                *flag_clone.borrow_mut() += 1;
-            },
-        );
-        context.set_person_property(person, Age, 18);
-        context.execute();
-        assert_eq!(*flag.borrow(), 1);
+        backfill_setup(&mut context);
+        context.index_property(Senior);
+        let before = senior_computations();
+        let stats = context.backfill_property(Senior);
+        assert_eq!(stats.people_computed, 100);
+        assert!(senior_computations() - before >= 100);
+
+        // Queries use the index and don't compute anything
+        let before = senior_computations();
+        assert_eq!(context.query_people((Senior, true)).len(), 35);
+        assert_eq!(context.query_people_count((Senior, false)), 65);
+        assert_eq!(senior_computations(), before);
+
+        // People created afterwards are handled lazily
+        context.add_person((Age, 70)).unwrap();
+        assert_eq!(context.query_people((Senior, true)).len(), 36);
     }
 
     #[test]
-    fn get_derived_property_with_globals() {
+    fn backfill_property_index_after() {
         let mut context = Context::new();
-        context.set_global_property_value(ThresholdP, 18).unwrap();
-        let child = context.add_person((Age, 17)).unwrap();
-        let adult = context.add_person((Age, 19)).unwrap();
-        assert!(!context.get_person_property(child, IsEligible));
-        assert!(context.get_person_property(adult, IsEligible));
+        backfill_setup(&mut context);
+        context.backfill_property(Senior);
+        context.index_property(Senior);
+        assert_eq!(context.query_people((Senior, true)).len(), 35);
+
+        // The index is now up to date
+        let before = senior_computations();
+        assert_eq!(context.query_people((Senior, false)).len(), 65);
+        assert_eq!(senior_computations(), before);
     }
 
+    define_person_property!(Height, u8, |_context, person: PersonId| {
+        u8::try_from(person.0 % 50).unwrap()
+    });
+
     #[test]
-    fn text_match_person() {
+    fn backfill_property_stores_values() {
         let mut context = Context::new();
-        let person = context
-            .add_person(((Age, 42), (RiskCategory, RiskCategoryValue::High)))
-            .unwrap();
-        assert!(context.match_person(person, ((Age, 42), (RiskCategory, RiskCategoryValue::High))));
-        assert!(!context.match_person(person, ((Age, 43), (RiskCategory, RiskCategoryValue::High))));
-        assert!(!context.match_person(person, ((Age, 42), (RiskCategory, RiskCategoryValue::Low))));
+        backfill_setup(&mut context);
+        context.get_person_property(PersonId(0), Height);
+        context.set_person_property(PersonId(1), Height, 200);
+
+        // People who already have a value are left alone
+        let stats = context.backfill_property(Height);
+        assert_eq!(stats.people_computed, 98);
+        assert_eq!(context.backfill_property(Height).people_computed, 0);
+        assert_eq!(context.get_person_property(PersonId(1), Height), 200);
+        assert_eq!(context.get_person_property(PersonId(60), Height), 10);
     }
 
     #[test]
-    fn test_sample_person_simple() {
-        define_rng!(SampleRng1);
+    fn backfill_property_empty_population() {
         let mut context = Context::new();
-        context.init_random(42);
-        assert!(matches!(
-            context.sample_person(SampleRng1, ()),
-            Err(IxaError::IxaError(_))
-        ));
-        let person = context.add_person(()).unwrap();
-        assert_eq!(context.sample_person(SampleRng1, ()).unwrap(), person);
+        assert_eq!(context.backfill_property(Senior).people_computed, 0);
     }
 
     #[test]
-    fn test_sample_matching_person() {
-        define_rng!(SampleRng2);
-
+    #[should_panic(
+        expected = "Property not initialized when person created: RiskCategory has no value for Person 0"
+    )]
+    fn backfill_property_required() {
         let mut context = Context::new();
-        context.init_random(42);
-
-        // Test an empty query.
-        assert!(matches!(
-            context.sample_person(SampleRng2, ()),
-            Err(IxaError::IxaError(_))
-        ));
-        let person1 = context.add_person((Age, 10)).unwrap();
-        let person2 = context.add_person((Age, 10)).unwrap();
-        let person3 = context.add_person((Age, 10)).unwrap();
-        let person4 = context.add_person((Age, 30)).unwrap();
-
-        // Test a non-matching query.
-        assert!(matches!(
-            context.sample_person(SampleRng2, (Age, 50)),
-            Err(IxaError::IxaError(_))
-        ));
-
-        // See that the simple query always returns person3
-        for _ in 0..10 {
-            assert_eq!(
-                context.sample_person(SampleRng2, (Age, 30)).unwrap(),
-                person4
-            );
-        }
-
-        let mut count_p1: usize = 0;
-        let mut count_p2: usize = 0;
-        let mut count_p3: usize = 0;
-        for _ in 0..30000 {
-            let p = context.sample_person(SampleRng2, (Age, 10)).unwrap();
-            if p == person1 {
-                count_p1 += 1;
-            } else if p == person2 {
-                count_p2 += 1;
-            } else if p == person3 {
-                count_p3 += 1;
-            } else {
-                panic!("Unexpected person");
-            }
-        }
-
-        // The chance of any of these being more unbalanced than this is ~10^{-4}
-        assert!(count_p1 >= 8700);
-        assert!(count_p2 >= 8700);
-        assert!(count_p3 >= 8700);
+        context.add_person(()).unwrap();
+        context.backfill_property(RiskCategory);
     }
 }
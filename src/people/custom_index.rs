@@ -0,0 +1,254 @@
+use crate::people::context_extension::ContextPeopleExtInternal;
+use crate::people::index::{IndexValue, IndexedPeople};
+use crate::{Context, PersonId, PersonProperty};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// An index of the people with each value of property `T`, for replacing
+/// the built-in index with a different data structure, e.g., bitsets for
+/// a property with few values. Install it with
+/// [`Context::install_custom_index()`](crate::ContextPeopleExt::install_custom_index).
+///
+/// Ixa keeps the index up to date: [`PropertyIndexImpl::add_person()`] is
+/// called once for each person with their value, before the index is
+/// next used, and when their value changes it's called with the new value
+/// after [`PropertyIndexImpl::remove_person()`] is called with the old
+/// one. People are removed when they're removed from the population.
+///
+/// Queries use the index according to what it supports. If it supports
+/// counts, a query for a value no one has returns without looking at
+/// anyone, and the count is used to decide whether to iterate over the
+/// people with the value. If it supports set lookups, a query can iterate
+/// over the people with the value instead of the whole population. Either
+/// way, people are checked against the rest of the query as usual.
+///
+/// [`FullIndex`] and [`ValueCountIndex`] implement this trait the way
+/// [`Context::index_property()`](crate::ContextPeopleExt::index_property)
+/// and
+/// [`Context::index_property_counts()`](crate::ContextPeopleExt::index_property_counts)
+/// index a property.
+pub trait PropertyIndexImpl<T: PersonProperty> {
+    /// Add `person_id`, who has `value`, to the index.
+    fn add_person(&mut self, person_id: PersonId, value: &T::Value);
+
+    /// Remove `person_id`, who had `value` when they were added, from the
+    /// index.
+    fn remove_person(&mut self, person_id: PersonId, value: &T::Value);
+
+    /// Whether [`PropertyIndexImpl::get_people()`] returns the people with
+    /// a value. Queries only plan to use set lookups if this is `true`.
+    fn supports_set_lookup(&self) -> bool {
+        false
+    }
+
+    /// The people with `value`, in any order, or `None` if the index can't
+    /// list them, in which case queries look at everyone instead.
+    fn get_people(&self, _value: &T::Value) -> Option<Vec<PersonId>> {
+        None
+    }
+
+    /// Whether [`PropertyIndexImpl::get_count()`] returns the number of
+    /// people with a value.
+    fn supports_count(&self) -> bool {
+        false
+    }
+
+    /// The number of people with `value`, or `None` if the index can't
+    /// count them.
+    fn get_count(&self, _value: &T::Value) -> Option<usize> {
+        None
+    }
+}
+
+/// A [`PropertyIndexImpl`] that keeps the people with each value of `T`, so
+/// it supports both set lookups and counts
+pub struct FullIndex<T: PersonProperty> {
+    people: HashMap<IndexValue, IndexedPeople>,
+    property: PhantomData<T>,
+}
+
+impl<T: PersonProperty> FullIndex<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        FullIndex {
+            people: HashMap::new(),
+            property: PhantomData,
+        }
+    }
+}
+
+impl<T: PersonProperty> Default for FullIndex<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: PersonProperty> PropertyIndexImpl<T> for FullIndex<T> {
+    fn add_person(&mut self, person_id: PersonId, value: &T::Value) {
+        self.people
+            .entry(IndexValue::compute(value))
+            .or_default()
+            .insert(person_id);
+    }
+
+    fn remove_person(&mut self, person_id: PersonId, value: &T::Value) {
+        let hash = IndexValue::compute(value);
+        if let Some(people) = self.people.get_mut(&hash) {
+            people.swap_remove(&person_id);
+            if people.is_empty() {
+                self.people.remove(&hash);
+            }
+        }
+    }
+
+    fn supports_set_lookup(&self) -> bool {
+        true
+    }
+
+    fn get_people(&self, value: &T::Value) -> Option<Vec<PersonId>> {
+        Some(
+            self.people
+                .get(&IndexValue::compute(value))
+                .map(|people| people.iter().copied().collect())
+                .unwrap_or_default(),
+        )
+    }
+
+    fn supports_count(&self) -> bool {
+        true
+    }
+
+    fn get_count(&self, value: &T::Value) -> Option<usize> {
+        Some(
+            self.people
+                .get(&IndexValue::compute(value))
+                .map_or(0, IndexedPeople::len),
+        )
+    }
+}
+
+/// A [`PropertyIndexImpl`] that only keeps the number of people with each
+/// value of `T`, so it supports counts but not set lookups
+pub struct ValueCountIndex<T: PersonProperty> {
+    counts: HashMap<IndexValue, usize>,
+    property: PhantomData<T>,
+}
+
+impl<T: PersonProperty> ValueCountIndex<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        ValueCountIndex {
+            counts: HashMap::new(),
+            property: PhantomData,
+        }
+    }
+}
+
+impl<T: PersonProperty> Default for ValueCountIndex<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: PersonProperty> PropertyIndexImpl<T> for ValueCountIndex<T> {
+    fn add_person(&mut self, _person_id: PersonId, value: &T::Value) {
+        *self.counts.entry(IndexValue::compute(value)).or_insert(0) += 1;
+    }
+
+    fn remove_person(&mut self, _person_id: PersonId, value: &T::Value) {
+        let hash = IndexValue::compute(value);
+        if let Some(count) = self.counts.get_mut(&hash) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&hash);
+            }
+        }
+    }
+
+    fn supports_count(&self) -> bool {
+        true
+    }
+
+    fn get_count(&self, value: &T::Value) -> Option<usize> {
+        Some(
+            self.counts
+                .get(&IndexValue::compute(value))
+                .copied()
+                .unwrap_or(0),
+        )
+    }
+}
+
+// A custom index with the property type erased, so that it can be stored
+// in an `Index`.
+pub(super) trait ErasedIndex {
+    fn add_person(&mut self, context: &Context, person_id: PersonId);
+
+    fn remove_person(&mut self, context: &Context, person_id: PersonId);
+
+    fn supports_set_lookup(&self) -> bool;
+
+    // The people with the value whose hash is `hash`, or `None` if the
+    // index can't list them
+    fn get_people(&self, hash: &IndexValue) -> Option<Vec<PersonId>>;
+
+    // The number of people with the value whose hash is `hash`, or `None`
+    // if the index can't count them
+    fn get_count(&self, hash: &IndexValue) -> Option<usize>;
+}
+
+struct CustomIndex<T: PersonProperty> {
+    index: Box<dyn PropertyIndexImpl<T>>,
+    // Queries only have the hashes of values, so keep the value of each
+    // hash that has been added. A hash that isn't here is a value no one
+    // has had.
+    values: HashMap<IndexValue, T::Value>,
+}
+
+impl<T: PersonProperty + 'static> ErasedIndex for CustomIndex<T> {
+    fn add_person(&mut self, context: &Context, person_id: PersonId) {
+        let value = context.get_person_property_unaudited(person_id, T::get_instance());
+        self.values
+            .entry(IndexValue::compute(&value))
+            .or_insert(value);
+        self.index.add_person(person_id, &value);
+    }
+
+    fn remove_person(&mut self, context: &Context, person_id: PersonId) {
+        let value = context.get_person_property_unaudited(person_id, T::get_instance());
+        self.index.remove_person(person_id, &value);
+    }
+
+    fn supports_set_lookup(&self) -> bool {
+        self.index.supports_set_lookup()
+    }
+
+    fn get_people(&self, hash: &IndexValue) -> Option<Vec<PersonId>> {
+        if !self.index.supports_set_lookup() {
+            return None;
+        }
+        match self.values.get(hash) {
+            Some(value) => self.index.get_people(value),
+            None => Some(Vec::new()),
+        }
+    }
+
+    fn get_count(&self, hash: &IndexValue) -> Option<usize> {
+        if !self.index.supports_count() {
+            return None;
+        }
+        match self.values.get(hash) {
+            Some(value) => self.index.get_count(value),
+            None => Some(0),
+        }
+    }
+}
+
+pub(super) fn erase<T: PersonProperty + 'static>(
+    index: Box<dyn PropertyIndexImpl<T>>,
+) -> Box<dyn ErasedIndex> {
+    Box::new(CustomIndex {
+        index,
+        values: HashMap::new(),
+    })
+}
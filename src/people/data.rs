@@ -1,7 +1,13 @@
+#[cfg(debug_assertions)]
+use crate::people::audit::PropertyAudit;
 use crate::people::context_extension::{ContextPeopleExt, ContextPeopleExtInternal};
 use crate::people::index::Index;
+use crate::people::lazy::PendingColumn;
+use crate::people::property::is_non_finite;
 use crate::people::InitializationList;
-use crate::{Context, IxaError, PersonId, PersonProperty, PersonPropertyChangeEvent};
+use crate::{warn, Context, IxaError, PersonId, PersonProperty, PersonPropertyChangeEvent};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::any::{Any, TypeId};
 use std::cell::{Ref, RefCell, RefMut};
 use std::collections::{HashMap, HashSet};
@@ -12,27 +18,52 @@ type ContextCallback = dyn FnOnce(&mut Context);
 // from 0 to population - 1. Person properties are associated with a person
 // via their id.
 pub(super) struct StoredPeopleProperties {
+    name: &'static str,
     is_required: bool,
     values: Box<dyn Any>,
+    // Clears the value for the person with the given index
+    clear: fn(&mut dyn Any, usize),
 }
 
 impl StoredPeopleProperties {
     fn new<T: PersonProperty + 'static>() -> Self {
         StoredPeopleProperties {
+            name: T::name(),
             is_required: T::is_required(),
             values: Box::<Vec<Option<T::Value>>>::default(),
+            clear: clear_value::<T>,
         }
     }
 }
 
+fn clear_value<T: PersonProperty + 'static>(values: &mut dyn Any, index: usize) {
+    let values: &mut Vec<Option<T::Value>> = values
+        .downcast_mut()
+        .expect("Type mismatch in properties_map");
+    if let Some(value) = values.get_mut(index) {
+        *value = None;
+    }
+}
+
 pub(super) struct PeopleData {
     pub(super) is_initializing: bool,
-    pub(super) current_population: usize,
+    // The number of people that have been added, including people who
+    // have since been removed. This is also the next `PersonId`.
+    pub(super) people_created: usize,
+    pub(super) removed_people: HashSet<PersonId>,
     pub(super) properties_map: RefCell<HashMap<TypeId, StoredPeopleProperties>>,
     pub(super) registered_derived_properties: RefCell<HashSet<TypeId>>,
     pub(super) dependency_map: RefCell<HashMap<TypeId, Vec<Box<dyn PersonPropertyHolder>>>>,
     pub(super) property_indexes: RefCell<HashMap<TypeId, Index>>,
     pub(super) people_types: RefCell<HashMap<String, TypeId>>,
+    // The columns loaded lazily from files that haven't been read yet
+    pub(super) lazy_columns: RefCell<HashMap<TypeId, PendingColumn>>,
+    pub(super) non_finite_allowed: HashSet<TypeId>,
+    pub(super) non_finite_check_interval: usize,
+    pub(super) non_finite_set_count: usize,
+    pub(super) non_finite_warning_count: usize,
+    #[cfg(debug_assertions)]
+    pub(super) audits: HashMap<TypeId, PropertyAudit>,
 }
 
 // The purpose of this trait is to enable storing a Vec of different
@@ -63,6 +94,8 @@ pub trait PersonPropertyHolder {
     fn non_derived_dependencies(&self) -> Vec<TypeId>;
     fn collect_non_derived_dependencies(&self, result: &mut HashSet<TypeId>);
     fn property_type_id(&self) -> TypeId;
+    // Registers the property with the context; see `register_property()`.
+    fn register(&self, context: &Context);
 }
 
 impl<T> PersonPropertyHolder for T
@@ -75,19 +108,22 @@ where
         person: PersonId,
         callback_vec: &mut Vec<Box<ContextCallback>>,
     ) {
-        let previous = context.get_person_property(person, T::get_instance());
+        let previous = context.get_person_property_unaudited(person, T::get_instance());
         context.remove_from_index_maybe(person, T::get_instance());
 
         // Captures the current value of the person property and defers the actual event
         // emission to when we have access to the new value.
         callback_vec.push(Box::new(move |ctx| {
-            let current = ctx.get_person_property(person, T::get_instance());
+            let current = ctx.get_person_property_unaudited(person, T::get_instance());
             let change_event: PersonPropertyChangeEvent<T> = PersonPropertyChangeEvent {
                 person_id: person,
                 current,
                 previous,
             };
             ctx.add_to_index_maybe(person, T::get_instance());
+            if previous != current {
+                ctx.emit_index_change::<T>(person, Some(previous), Some(current));
+            }
             ctx.emit_event(change_event);
         }));
     }
@@ -104,6 +140,10 @@ where
         TypeId::of::<T>()
     }
 
+    fn register(&self, context: &Context) {
+        context.register_property::<T>();
+    }
+
     /// Returns of dependencies, where any derived dependencies
     /// are recursively expanded to their non-derived dependencies.
     /// If the property is not derived, the Vec will be empty.
@@ -131,11 +171,29 @@ impl PeopleData {
     /// Adds a person and returns a `PersonId` that can be used to reference them.
     /// This will increment the current population by 1.
     pub(super) fn add_person(&mut self) -> PersonId {
-        let id = self.current_population;
-        self.current_population += 1;
+        let id = self.people_created;
+        self.people_created += 1;
         PersonId(id)
     }
 
+    /// The number of people who have been added and not removed.
+    pub(super) fn population(&self) -> usize {
+        self.people_created - self.removed_people.len()
+    }
+
+    pub(super) fn person_exists(&self, person_id: PersonId) -> bool {
+        person_id.0 < self.people_created && !self.removed_people.contains(&person_id)
+    }
+
+    /// Deletes the stored property values of a person and marks them as
+    /// removed. They must already have been removed from the indexes.
+    pub(super) fn remove_person(&mut self, person_id: PersonId) {
+        for property in self.properties_map.get_mut().values_mut() {
+            (property.clear)(property.values.as_mut(), person_id.0);
+        }
+        self.removed_people.insert(person_id);
+    }
+
     /// Retrieves a specific property of a person by their `PersonId`.
     ///
     /// Returns `RefMut<Option<T::Value>>`: `Some(value)` if the property exists for the given person,
@@ -145,17 +203,9 @@ impl PeopleData {
         &self,
         person: PersonId,
         _property: T,
-    ) -> RefMut<Option<T::Value>> {
-        let properties_map = self.properties_map.borrow_mut();
+    ) -> RefMut<'_, Option<T::Value>> {
         let index = person.0;
-        RefMut::map(properties_map, |properties_map| {
-            let properties = properties_map
-                .entry(TypeId::of::<T>())
-                .or_insert_with(|| StoredPeopleProperties::new::<T>());
-            let values: &mut Vec<Option<T::Value>> = properties
-                .values
-                .downcast_mut()
-                .expect("Type mismatch in properties_map");
+        RefMut::map(self.get_property_values::<T>(), |values| {
             if index >= values.len() {
                 values.resize(index + 1, None);
             }
@@ -163,6 +213,22 @@ impl PeopleData {
         })
     }
 
+    /// Retrieves the stored values of a property, indexed by `PersonId`.
+    /// This can be shorter than the population, and values that haven't
+    /// been initialized are `None`.
+    pub(super) fn get_property_values<T: PersonProperty + 'static>(
+        &self,
+    ) -> RefMut<'_, Vec<Option<T::Value>>> {
+        RefMut::map(self.properties_map.borrow_mut(), |properties_map| {
+            properties_map
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| StoredPeopleProperties::new::<T>())
+                .values
+                .downcast_mut()
+                .expect("Type mismatch in properties_map")
+        })
+    }
+
     /// Sets the value of a property for a person
     #[allow(clippy::needless_pass_by_value)]
     pub(super) fn set_person_property<T: PersonProperty + 'static>(
@@ -175,7 +241,44 @@ impl PeopleData {
         *property_ref = Some(value);
     }
 
-    pub(super) fn get_index_ref_mut(&self, t: TypeId) -> Option<RefMut<Index>> {
+    /// Checks a new value for a property before it is set. If `strict`,
+    /// panics on a non-finite value; otherwise only every Nth value is
+    /// checked and a warning is logged.
+    pub(super) fn check_finite<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        value: &T::Value,
+        strict: bool,
+    ) {
+        if self.non_finite_allowed.contains(&TypeId::of::<T>()) {
+            return;
+        }
+        if strict {
+            assert!(
+                !is_non_finite(value),
+                "Non-finite value {value:?} for property {} of {person_id:?}",
+                T::name()
+            );
+            return;
+        }
+        if self.non_finite_check_interval == 0 {
+            return;
+        }
+        self.non_finite_set_count += 1;
+        if self
+            .non_finite_set_count
+            .is_multiple_of(self.non_finite_check_interval)
+            && is_non_finite(value)
+        {
+            warn!(
+                "Non-finite value {value:?} for property {} of {person_id:?}",
+                T::name()
+            );
+            self.non_finite_warning_count += 1;
+        }
+    }
+
+    pub(super) fn get_index_ref_mut(&self, t: TypeId) -> Option<RefMut<'_, Index>> {
         let index_map = self.property_indexes.borrow_mut();
         if index_map.contains_key(&t) {
             Some(RefMut::map(index_map, |map| map.get_mut(&t).unwrap()))
@@ -184,7 +287,7 @@ impl PeopleData {
         }
     }
 
-    pub(super) fn get_index_ref(&self, t: TypeId) -> Option<Ref<Index>> {
+    pub(super) fn get_index_ref(&self, t: TypeId) -> Option<Ref<'_, Index>> {
         let index_map = self.property_indexes.borrow();
         if index_map.contains_key(&t) {
             Some(Ref::map(index_map, |map| map.get(&t).unwrap()))
@@ -196,18 +299,18 @@ impl PeopleData {
     pub(super) fn get_index_ref_mut_by_prop<T: PersonProperty + 'static>(
         &self,
         _property: T,
-    ) -> Option<RefMut<Index>> {
+    ) -> Option<RefMut<'_, Index>> {
         let type_id = TypeId::of::<T>();
         self.get_index_ref_mut(type_id)
     }
 
-    // Convenience function to iterate over the current population.
-    // Note that this doesn't hold a reference to PeopleData, so if
-    // you change the population while using it, it won't notice.
-    pub(super) fn people_iterator(&self) -> PeopleIterator {
+    // Convenience function to iterate over the current population, in
+    // order of `PersonId`, skipping people who have been removed.
+    pub(super) fn people_iterator(&self) -> PeopleIterator<'_> {
         PeopleIterator {
-            population: self.current_population,
+            population: self.people_created,
             person_id: 0,
+            removed: &self.removed_people,
         }
     }
 
@@ -215,33 +318,106 @@ impl PeopleData {
         &self,
         initialization: &T,
     ) -> Result<(), IxaError> {
-        let properties_map = self.properties_map.borrow();
-        for (t, property) in properties_map.iter() {
-            if property.is_required && !initialization.has_property(*t) {
-                return Err(IxaError::IxaError(String::from("Missing initial value")));
-            }
+        if let Some(property) = initialization.duplicate_property() {
+            return Err(IxaError::DuplicateProperty {
+                entity: "Person",
+                property,
+            });
         }
+        let missing = self.missing_required_properties(initialization);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(IxaError::MissingRequiredProperties {
+                entity: "Person",
+                properties: missing,
+            })
+        }
+    }
+
+    /// Returns the names of the required properties that `initialization`
+    /// doesn't set, in alphabetical order.
+    pub(super) fn missing_required_properties<T: InitializationList>(
+        &self,
+        initialization: &T,
+    ) -> Vec<&'static str> {
+        let properties_map = self.properties_map.borrow();
+        let mut missing: Vec<&'static str> = properties_map
+            .iter()
+            .filter(|(t, property)| property.is_required && !initialization.has_property(**t))
+            .map(|(_, property)| property.name)
+            .collect();
+        missing.sort_unstable();
+        missing
+    }
 
+    /// Returns the type ids and names of the properties that have stored
+    /// values, i.e., that aren't derived and have been set or initialized.
+    pub(super) fn stored_property_types(&self) -> Vec<(TypeId, &'static str)> {
+        self.properties_map
+            .borrow()
+            .iter()
+            .map(|(t, property)| (*t, property.name))
+            .collect()
+    }
+
+    /// Serializes the stored values of a property, including `None` for
+    /// people whose value hasn't been initialized, or returns `None` if
+    /// nothing has been stored.
+    pub(super) fn serialize_property_values<T: PersonProperty + 'static>(
+        &self,
+    ) -> Result<Option<Vec<u8>>, IxaError>
+    where
+        T::Value: Serialize,
+    {
+        let properties_map = self.properties_map.borrow();
+        let Some(properties) = properties_map.get(&TypeId::of::<T>()) else {
+            return Ok(None);
+        };
+        let values: &Vec<Option<T::Value>> = properties
+            .values
+            .downcast_ref()
+            .expect("Type mismatch in properties_map");
+        Ok(Some(bincode::serialize(values)?))
+    }
+
+    /// Replaces the stored values of a property with values serialized by
+    /// `serialize_property_values()`, without emitting change events or
+    /// updating indexes.
+    pub(super) fn deserialize_property_values<T: PersonProperty + 'static>(
+        &self,
+        data: &[u8],
+    ) -> Result<(), IxaError>
+    where
+        T::Value: DeserializeOwned,
+    {
+        let values: Vec<Option<T::Value>> = bincode::deserialize(data)?;
+        let mut properties_map = self.properties_map.borrow_mut();
+        let properties = properties_map
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| StoredPeopleProperties::new::<T>());
+        properties.values = Box::new(values);
         Ok(())
     }
 }
 
-pub(super) struct PeopleIterator {
+pub(super) struct PeopleIterator<'a> {
     population: usize,
     person_id: usize,
+    removed: &'a HashSet<PersonId>,
 }
 
-impl Iterator for PeopleIterator {
+impl Iterator for PeopleIterator<'_> {
     type Item = PersonId;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let ret = if self.person_id < self.population {
-            Some(PersonId(self.person_id))
-        } else {
-            None
-        };
-        self.person_id += 1;
-
-        ret
+        while self.person_id < self.population {
+            let person_id = PersonId(self.person_id);
+            self.person_id += 1;
+            if self.removed.is_empty() || !self.removed.contains(&person_id) {
+                return Some(person_id);
+            }
+        }
+        None
     }
 }
@@ -10,6 +10,16 @@ pub struct PersonCreatedEvent {
     pub person_id: PersonId,
 }
 
+/// Emitted when a person is removed with
+/// [`Context::remove_person()`](crate::Context::remove_person). Their property
+/// values have already been deleted when this is handled.
+#[derive(Clone, Copy, IxaEvent)]
+#[allow(clippy::manual_non_exhaustive)]
+pub struct PersonRemovedEvent {
+    /// The [`PersonId`] of the removed person.
+    pub person_id: PersonId,
+}
+
 /// Emitted when a person property is updated
 /// These should not be emitted outside this module
 #[derive(Copy, Clone)]
@@ -31,14 +41,42 @@ impl<T: PersonProperty + 'static> IxaEvent for PersonPropertyChangeEvent<T> {
     }
 }
 
+/// Emitted when a person is added to, moves within, or is removed from the
+/// index of property `T`, for properties subscribed to with
+/// [`Context::subscribe_to_index_changes()`](crate::ContextPeopleExt::subscribe_to_index_changes).
+///
+/// Each change is emitted just before the [`PersonCreatedEvent`],
+/// [`PersonPropertyChangeEvent`], or [`PersonRemovedEvent`] for the same
+/// change, so its handlers run first. Setting a property to the value it
+/// already has doesn't change the index, so nothing is emitted.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[allow(clippy::manual_non_exhaustive)]
+pub struct IndexChange<T: PersonProperty> {
+    /// The [`PersonId`] whose entry changed
+    pub person_id: PersonId,
+    /// The value the person was indexed under, or `None` if they're new to
+    /// the index
+    pub previous: Option<T::Value>,
+    /// The value the person is now indexed under, or `None` if they were
+    /// removed
+    pub current: Option<T::Value>,
+}
+
+impl<T: PersonProperty + 'static> IxaEvent for IndexChange<T> {}
+
 #[cfg(test)]
+#[allow(clippy::float_cmp)]
 mod tests {
+    use crate::people::PersonTemplate;
     use crate::{
-        define_derived_property, define_global_property, define_person_property,
-        define_person_property_with_default, Context, ContextPeopleExt, PersonCreatedEvent,
-        PersonId, PersonPropertyChangeEvent,
+        define_derived_property, define_person_property, define_person_property_with_default,
+        define_rng, Context, ContextPeopleExt, ContextRandomExt, IndexChange, PersonCreatedEvent,
+        PersonId, PersonProperty, PersonPropertyChangeEvent, PersonRemovedEvent,
     };
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
     use std::cell::RefCell;
+    use std::collections::{HashMap, HashSet};
     use std::rc::Rc;
 
     define_person_property!(Age, u8);
@@ -47,10 +85,6 @@ mod tests {
         Child,
         Adult,
     }
-    define_global_property!(Threshold, u8);
-    define_derived_property!(IsEligible, bool, [Age], [Threshold], |age, threshold| {
-        age >= threshold
-    });
 
     define_derived_property!(AgeGroup, AgeGroupValue, [Age], |age| {
         if age < 18 {
@@ -67,6 +101,8 @@ mod tests {
     }
     define_person_property!(RiskCategory, RiskCategoryValue);
     define_person_property_with_default!(IsRunner, bool, false);
+    define_person_property!(County, u8);
+    define_rng!(IndexChangeRng);
     define_person_property!(RunningShoes, u8, |context: &Context, person: PersonId| {
         let is_runner = context.get_person_property(person, IsRunner);
         if is_runner {
@@ -122,6 +158,38 @@ mod tests {
         assert!(*flag.borrow());
     }
 
+    #[test]
+    fn count_infections_in_window() {
+        let mut context = Context::new();
+        let people: Vec<PersonId> = (0..5)
+            .map(|_| {
+                context
+                    .add_person((RiskCategory, RiskCategoryValue::Low))
+                    .unwrap()
+            })
+            .collect();
+        // Each person becomes high risk on a scripted day
+        for (person_id, day) in people.into_iter().zip([5.0, 10.0, 12.0, 20.0, 25.0]) {
+            context.add_plan(day, move |context| {
+                context.set_person_property(person_id, RiskCategory, RiskCategoryValue::High);
+            });
+        }
+
+        assert_eq!(
+            context.count_events::<PersonPropertyChangeEvent<RiskCategory>>(9.0),
+            1
+        );
+        assert_eq!(
+            context.count_events::<PersonPropertyChangeEvent<RiskCategory>>(20.0),
+            3
+        );
+        assert_eq!(context.get_current_time(), 20.0);
+        assert_eq!(
+            context.count_events::<PersonPropertyChangeEvent<RiskCategory>>(30.0),
+            1
+        );
+    }
+
     #[test]
     fn observe_person_property_change_with_set() {
         let mut context = Context::new();
@@ -160,4 +228,201 @@ mod tests {
         context.execute();
         assert!(*flag.borrow());
     }
+
+    type ChangeLog<V> = Rc<RefCell<Vec<(PersonId, Option<V>, Option<V>)>>>;
+
+    fn log_index_changes<T: PersonProperty + 'static>(
+        context: &mut Context,
+    ) -> ChangeLog<T::Value> {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let log_clone = Rc::clone(&log);
+        context.subscribe_to_index_changes(move |_context, change: IndexChange<T>| {
+            log_clone
+                .borrow_mut()
+                .push((change.person_id, change.previous, change.current));
+        });
+        log
+    }
+
+    #[test]
+    fn index_changes_on_set() {
+        let mut context = Context::new();
+        let log = log_index_changes::<County>(&mut context);
+        let person = context.add_person((County, 1)).unwrap();
+        context.execute();
+        assert_eq!(*log.borrow(), vec![(person, None, Some(1))]);
+
+        log.borrow_mut().clear();
+        context.set_person_property(person, County, 2);
+        // Setting the same value doesn't move the person.
+        context.set_person_property(person, County, 2);
+        context.execute();
+        assert_eq!(*log.borrow(), vec![(person, Some(1), Some(2))]);
+        assert_eq!(context.query_people((County, 2)), vec![person]);
+    }
+
+    #[test]
+    fn index_change_runs_before_property_change() {
+        let mut context = Context::new();
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let order_clone = Rc::clone(&order);
+        context.subscribe_to_event(move |_context, _: PersonPropertyChangeEvent<County>| {
+            order_clone.borrow_mut().push("property");
+        });
+        let order_clone = Rc::clone(&order);
+        context.subscribe_to_index_changes(move |_context, _: IndexChange<County>| {
+            order_clone.borrow_mut().push("index");
+        });
+        let person = context.add_person((County, 1)).unwrap();
+        context.execute();
+        order.borrow_mut().clear();
+
+        context.set_person_property(person, County, 2);
+        context.execute();
+        assert_eq!(*order.borrow(), vec!["index", "property"]);
+    }
+
+    #[test]
+    fn index_changes_for_existing_people() {
+        let mut context = Context::new();
+        let people: Vec<PersonId> = (0..3)
+            .map(|i| context.add_person((County, i)).unwrap())
+            .collect();
+        context.index_property(County);
+        assert_eq!(context.query_people_count((County, 0)), 1);
+        // These people haven't been indexed yet.
+        let later = context.add_person((County, 0)).unwrap();
+        context.remove_person(people[1]).unwrap();
+
+        let log = log_index_changes::<County>(&mut context);
+        context.execute();
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                (people[0], None, Some(0)),
+                (people[2], None, Some(2)),
+                (later, None, Some(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn index_changes_on_creation_from_template() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let log = log_index_changes::<County>(&mut context);
+        let created = Rc::new(RefCell::new(0));
+        let created_clone = Rc::clone(&created);
+        let log_clone = Rc::clone(&log);
+        context.subscribe_to_event(move |_context, event: PersonCreatedEvent| {
+            // The index change for each person is handled first.
+            assert!(log_clone
+                .borrow()
+                .iter()
+                .any(|(person_id, _, _)| *person_id == event.person_id));
+            *created_clone.borrow_mut() += 1;
+        });
+
+        let template = PersonTemplate::new().value(County, 3);
+        let people = context
+            .add_people_from_template(IndexChangeRng, &template, 3)
+            .unwrap();
+        context.execute();
+        assert_eq!(*created.borrow(), 3);
+        assert_eq!(
+            *log.borrow(),
+            people
+                .iter()
+                .map(|person_id| (*person_id, None, Some(3)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn index_changes_on_removal() {
+        let mut context = Context::new();
+        let person = context.add_person((County, 4)).unwrap();
+        let log = log_index_changes::<County>(&mut context);
+        let log_clone = Rc::clone(&log);
+        context.subscribe_to_event(move |_context, event: PersonRemovedEvent| {
+            assert_eq!(
+                log_clone.borrow().last(),
+                Some(&(event.person_id, Some(4), None))
+            );
+        });
+        context.execute();
+        log.borrow_mut().clear();
+
+        context.remove_person(person).unwrap();
+        context.execute();
+        assert_eq!(*log.borrow(), vec![(person, Some(4), None)]);
+    }
+
+    #[test]
+    fn index_changes_for_derived_property() {
+        let mut context = Context::new();
+        let person = context.add_person((Age, 17)).unwrap();
+        let log = log_index_changes::<AgeGroup>(&mut context);
+        context.execute();
+        log.borrow_mut().clear();
+
+        context.set_person_property(person, Age, 18);
+        // Still an adult, so the index doesn't change.
+        context.set_person_property(person, Age, 19);
+        context.execute();
+        assert_eq!(
+            *log.borrow(),
+            vec![(
+                person,
+                Some(AgeGroupValue::Child),
+                Some(AgeGroupValue::Adult)
+            )]
+        );
+    }
+
+    #[test]
+    fn incremental_cache_matches_recomputation() {
+        // A cache of the people in each county, kept up to date from index
+        // changes alone.
+        type CountyCache = Rc<RefCell<HashMap<u8, HashSet<PersonId>>>>;
+
+        let mut context = Context::new();
+        let mut people: Vec<PersonId> = (0..20)
+            .map(|i| context.add_person((County, i % 4)).unwrap())
+            .collect();
+        let cache: CountyCache = Rc::new(RefCell::new(HashMap::new()));
+        let cache_clone = Rc::clone(&cache);
+        context.subscribe_to_index_changes(move |_context, change: IndexChange<County>| {
+            let mut cache = cache_clone.borrow_mut();
+            if let Some(previous) = change.previous {
+                cache.get_mut(&previous).unwrap().remove(&change.person_id);
+            }
+            if let Some(current) = change.current {
+                cache.entry(current).or_default().insert(change.person_id);
+            }
+        });
+
+        let mut rng = StdRng::seed_from_u64(8);
+        for _ in 0..500 {
+            match rng.gen_range(0..4) {
+                0 => people.push(context.add_person((County, rng.gen_range(0..6))).unwrap()),
+                1 if people.len() > 1 => {
+                    let person = people.swap_remove(rng.gen_range(0..people.len()));
+                    context.remove_person(person).unwrap();
+                }
+                _ => {
+                    let person = people[rng.gen_range(0..people.len())];
+                    context.set_person_property(person, County, rng.gen_range(0..6));
+                }
+            }
+            context.execute();
+        }
+
+        for county in 0..6 {
+            let expected: HashSet<PersonId> =
+                context.query_people((County, county)).into_iter().collect();
+            let cached = cache.borrow().get(&county).cloned().unwrap_or_default();
+            assert_eq!(cached, expected, "county {county}");
+        }
+    }
 }
@@ -2,6 +2,10 @@ use crate::people::PeoplePlugin;
 use crate::Context;
 use crate::IxaError;
 use crate::PersonId;
+use fxhash::FxHasher64;
+use std::any::TypeId;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
 pub(crate) trait ContextPeopleExtCrate {
     fn get_person_property_by_name(
@@ -9,9 +13,48 @@ pub(crate) trait ContextPeopleExtCrate {
         name: &str,
         person_id: PersonId,
     ) -> Result<String, IxaError>;
+
+    // The distinct values of a property as display strings, with counts,
+    // in the order of `Context::distinct_property_values()`.
+    fn get_distinct_property_values_by_name(
+        &self,
+        name: &str,
+    ) -> Result<Vec<(String, usize)>, IxaError>;
+
+    // A hash of every person's value of each registered property, sorted
+    // by property name.
+    fn get_property_digests(&self) -> Vec<(String, u64)>;
+
+    // The names of the registered properties, sorted.
+    fn get_property_names(&self) -> Vec<String>;
+
+    // Calls `callback` with the person and the previous and current display
+    // values whenever the named property changes.
+    fn subscribe_to_property_changes_by_name(
+        &mut self,
+        name: &str,
+        callback: impl Fn(&mut Context, PersonId, &str, &str) + 'static,
+    ) -> Result<(), IxaError>;
+
+    // Whether the properties of a person being added are being set.
+    fn is_initializing_person(&self) -> bool;
+
+    // Whether `property` has counts from `Context::index_property_counts()`.
+    fn has_property_counts(&self, property: TypeId) -> bool;
 }
 
 impl ContextPeopleExtCrate for Context {
+    fn is_initializing_person(&self) -> bool {
+        self.get_data_container(PeoplePlugin)
+            .is_some_and(|data_container| data_container.is_initializing)
+    }
+
+    fn has_property_counts(&self, property: TypeId) -> bool {
+        self.get_data_container(PeoplePlugin)
+            .and_then(|data_container| data_container.get_index_ref(property))
+            .is_some_and(|index| index.counts.is_some())
+    }
+
     fn get_person_property_by_name(
         &self,
         name: &str,
@@ -22,21 +65,114 @@ impl ContextPeopleExtCrate for Context {
             .people_types
             .borrow()
             .get(name)
-            .ok_or(IxaError::IxaError(format!("No property '{name}'")))?;
+            .ok_or_else(|| IxaError::PropertyNotFound {
+                name: name.to_string(),
+            })?;
 
         let index = data_container.get_index_ref(type_id).unwrap(); // This should exist
         Ok((index.get_display)(self, person_id))
     }
+
+    fn get_distinct_property_values_by_name(
+        &self,
+        name: &str,
+    ) -> Result<Vec<(String, usize)>, IxaError> {
+        let data_container = self.get_data_container(PeoplePlugin).unwrap();
+        let type_id = *data_container
+            .people_types
+            .borrow()
+            .get(name)
+            .ok_or_else(|| IxaError::PropertyNotFound {
+                name: name.to_string(),
+            })?;
+
+        let mut index = data_container.get_index_ref_mut(type_id).unwrap(); // This should exist
+        index.index_unindexed_people(self);
+        Ok(index
+            .value_counts(self)
+            .into_iter()
+            .map(|(_, count, person_id)| ((index.get_display)(self, person_id), count))
+            .collect())
+    }
+
+    fn get_property_digests(&self) -> Vec<(String, u64)> {
+        let Some(data_container) = self.get_data_container(PeoplePlugin) else {
+            return Vec::new();
+        };
+        let mut properties: Vec<(String, TypeId)> = data_container
+            .people_types
+            .borrow()
+            .iter()
+            .map(|(name, type_id)| (name.clone(), *type_id))
+            .collect();
+        properties.sort_by(|a, b| a.0.cmp(&b.0));
+
+        properties
+            .into_iter()
+            .map(|(name, type_id)| {
+                let index = data_container.get_index_ref(type_id).unwrap();
+                let mut hasher = FxHasher64::default();
+                for person_id in data_container.people_iterator() {
+                    (index.indexer)(self, person_id).hash(&mut hasher);
+                }
+                (name, hasher.finish())
+            })
+            .collect()
+    }
+
+    fn get_property_names(&self) -> Vec<String> {
+        let Some(data_container) = self.get_data_container(PeoplePlugin) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = data_container
+            .people_types
+            .borrow()
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn subscribe_to_property_changes_by_name(
+        &mut self,
+        name: &str,
+        callback: impl Fn(&mut Context, PersonId, &str, &str) + 'static,
+    ) -> Result<(), IxaError> {
+        let subscribe_to_changes = {
+            let data_container = self.get_data_container(PeoplePlugin).ok_or_else(|| {
+                IxaError::PropertyNotFound {
+                    name: name.to_string(),
+                }
+            })?;
+            let type_id = *data_container
+                .people_types
+                .borrow()
+                .get(name)
+                .ok_or_else(|| IxaError::PropertyNotFound {
+                    name: name.to_string(),
+                })?;
+            data_container
+                .get_index_ref(type_id)
+                .unwrap() // This should exist
+                .subscribe_to_changes
+        };
+        subscribe_to_changes(self, Rc::new(callback));
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::ContextPeopleExtCrate;
     use crate::people::{define_person_property, ContextPeopleExt};
-    use crate::Context;
     use crate::ContextRandomExt;
+    use crate::{define_person_property_with_default, Context, IxaError, PersonId};
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
     define_person_property!(Age, u8);
+    define_person_property_with_default!(IsRunner, bool, false);
 
     #[test]
     fn get_property_string() {
@@ -48,6 +184,52 @@ mod test {
         assert_eq!(age, "10");
     }
 
+    #[test]
+    fn get_property_names() {
+        let mut context = Context::new();
+        assert!(context.get_property_names().is_empty());
+        context.add_person((Age, 10)).unwrap();
+        context.get_person_property(PersonId(0), IsRunner);
+        assert_eq!(context.get_property_names(), vec!["Age", "IsRunner"]);
+    }
+
+    #[test]
+    fn subscribe_to_property_changes_by_name() {
+        let mut context = Context::new();
+        let person = context.add_person((Age, 10)).unwrap();
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let changes_clone = Rc::clone(&changes);
+        context
+            .subscribe_to_property_changes_by_name(
+                "Age",
+                move |context, person_id, previous, current| {
+                    changes_clone.borrow_mut().push((
+                        context.get_current_time(),
+                        person_id,
+                        previous.to_string(),
+                        current.to_string(),
+                    ));
+                },
+            )
+            .unwrap();
+        context.add_plan(1.0, move |context| {
+            context.set_person_property(person, Age, 11);
+        });
+        context.execute();
+        assert_eq!(
+            *changes.borrow(),
+            vec![(1.0, person, "10".to_string(), "11".to_string())]
+        );
+    }
+
+    #[test]
+    fn subscribe_to_unknown_property_changes_by_name() {
+        let mut context = Context::new();
+        context.add_person((Age, 10)).unwrap();
+        let result = context.subscribe_to_property_changes_by_name("Unknown", |_, _, _, _| {});
+        assert!(matches!(result, Err(IxaError::PropertyNotFound { name }) if name == "Unknown"));
+    }
+
     #[test]
     fn get_unknown_property_string() {
         let mut context = Context::new();
@@ -1,8 +1,13 @@
-use crate::{Context, ContextPeopleExt, PersonId, PersonProperty};
-use std::collections::{HashMap, HashSet};
+use crate::people::context_extension::ContextPeopleExtInternal;
+use crate::people::custom_index::ErasedIndex;
+use crate::people::{IndexChange, PeoplePlugin, PersonPropertyChangeEvent};
+use crate::{Context, PersonId, PersonProperty};
+use indexmap::IndexSet;
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
-#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 // The lookup key for entries in the index. This is a serialized
 // version of the value. If that serialization fits in 128 bits, we
 // store it in Fixed to avoid the allocation of the Vec. Otherwise it
@@ -51,6 +56,22 @@ impl Hasher for IndexValueHasher {
 
 type PersonCallback<T> = dyn Fn(&Context, PersonId) -> T;
 
+// Called with the person and the previous and current display values when
+// a property changes.
+pub(super) type PropertyChangeCallback = dyn Fn(&mut Context, PersonId, &str, &str);
+
+// The people in an index entry. This is an `IndexSet` rather than a
+// `HashSet` so that a random member can be picked in constant time.
+pub(super) type IndexedPeople = IndexSet<PersonId>;
+
+// The index entries, keyed by value, with the display value and the people
+// who have it.
+pub(super) type IndexLookup = HashMap<IndexValue, (String, IndexedPeople)>;
+
+// The number of people with each value, keyed by value, with the display
+// value.
+pub(super) type IndexCounts = HashMap<IndexValue, (String, usize)>;
+
 // An index for a single property.
 pub struct Index {
     // Primarily for debugging purposes
@@ -58,11 +79,27 @@ pub struct Index {
     pub(super) name: &'static str,
     // The hash of the property value maps to a list of PersonIds
     // or None if we're not indexing
-    pub(super) lookup: Option<HashMap<IndexValue, (String, HashSet<PersonId>)>>,
+    pub(super) lookup: Option<IndexLookup>,
+    // An index installed with `Context::install_custom_index()`, which is
+    // used instead of `lookup`
+    pub(super) custom: Option<Box<dyn ErasedIndex>>,
+    // The number of people with each value, for a property indexed with
+    // `Context::index_property_counts()`. This is much smaller than
+    // `lookup`, but can only be used to count people.
+    pub(super) counts: Option<IndexCounts>,
     // A callback that calculates the IndexValue of a person's current property value
     pub(super) indexer: Box<PersonCallback<IndexValue>>,
     // A callback that calculates the display value of a person's current property value
     pub(super) get_display: Box<PersonCallback<String>>,
+    // Subscribes a callback to changes of the property, so that code that
+    // only knows the property's name can watch it
+    pub(super) subscribe_to_changes: fn(&mut Context, Rc<PropertyChangeCallback>),
+    // Whether anyone has subscribed to changes to the index. If so, people
+    // are indexed as soon as they're added rather than at the next query.
+    pub(super) notify_changes: bool,
+    // Emits an `IndexChange` for a person who was added to (`true`) or
+    // removed from (`false`) the index, with their current value
+    pub(super) emit_membership_change: fn(&mut Context, PersonId, bool),
     // The largest person ID that has been indexed. Used so that we
     // can lazily index when a person is added.
     pub(super) max_indexed: usize,
@@ -73,50 +110,156 @@ impl Index {
         Self {
             name: std::any::type_name::<T>(),
             lookup: None,
+            custom: None,
+            counts: None,
             indexer: Box::new(move |context: &Context, person_id: PersonId| {
-                let value = context.get_person_property(person_id, property);
+                let value = context.get_person_property_unaudited(person_id, property);
                 IndexValue::compute(&value)
             }),
             get_display: Box::new(move |context: &Context, person_id: PersonId| {
-                let value = context.get_person_property(person_id, property);
+                let value = context.get_person_property_unaudited(person_id, property);
                 format!("{value:?}")
             }),
+            subscribe_to_changes: subscribe_to_changes::<T>,
+            notify_changes: false,
+            emit_membership_change: emit_membership_change::<T>,
             max_indexed: 0,
         }
     }
 
+    // Whether the property is indexed, with the built-in index, a custom
+    // one, or just counts
+    pub(super) fn is_indexed(&self) -> bool {
+        self.lookup.is_some() || self.custom.is_some() || self.counts.is_some()
+    }
+
     pub(super) fn add_person(&mut self, context: &Context, person_id: PersonId) {
+        if let Some(custom) = &mut self.custom {
+            // People who haven't been indexed yet are added when they are.
+            if person_id.0 < self.max_indexed {
+                custom.add_person(context, person_id);
+            }
+            return;
+        }
         let hash = (self.indexer)(context, person_id);
-        self.lookup
-            .as_mut()
-            .unwrap()
-            .entry(hash)
-            .or_insert_with(|| ((self.get_display)(context, person_id), HashSet::new()))
-            .1
-            .insert(person_id);
+        if let Some(counts) = &mut self.counts {
+            // People who haven't been indexed yet are counted when they are.
+            if person_id.0 < self.max_indexed {
+                counts
+                    .entry(hash.clone())
+                    .or_insert_with(|| ((self.get_display)(context, person_id), 0))
+                    .1 += 1;
+            }
+        }
+        if let Some(lookup) = &mut self.lookup {
+            lookup
+                .entry(hash)
+                .or_insert_with(|| ((self.get_display)(context, person_id), IndexedPeople::new()))
+                .1
+                .insert(person_id);
+        }
     }
 
     pub(super) fn remove_person(&mut self, context: &Context, person_id: PersonId) {
+        if let Some(custom) = &mut self.custom {
+            if person_id.0 < self.max_indexed {
+                custom.remove_person(context, person_id);
+            }
+            return;
+        }
         let hash = (self.indexer)(context, person_id);
-        if let Some(entry) = self.lookup.as_mut().unwrap().get_mut(&hash) {
-            entry.1.remove(&person_id);
-            // Clean up the entry if there are no people
-            if entry.0.is_empty() {
-                self.lookup.as_mut().unwrap().remove(&hash);
+        if let Some(counts) = &mut self.counts {
+            if person_id.0 < self.max_indexed {
+                let entry = counts.get_mut(&hash).unwrap();
+                entry.1 -= 1;
+                if entry.1 == 0 {
+                    counts.remove(&hash);
+                }
+            }
+        }
+        if let Some(lookup) = &mut self.lookup {
+            if let Some(entry) = lookup.get_mut(&hash) {
+                entry.1.swap_remove(&person_id);
+                // Clean up the entry if there are no people
+                if entry.1.is_empty() {
+                    lookup.remove(&hash);
+                }
             }
         }
     }
 
+    // Move `moved` people from the count of the value whose hash is
+    // `previous` to that of `current`, which is displayed as `display`.
+    pub(super) fn move_counts(
+        &mut self,
+        previous: &IndexValue,
+        current: &IndexValue,
+        display: impl FnOnce() -> String,
+        moved: usize,
+    ) {
+        let Some(counts) = &mut self.counts else {
+            return;
+        };
+        let entry = counts.get_mut(previous).unwrap();
+        entry.1 -= moved;
+        if entry.1 == 0 {
+            counts.remove(previous);
+        }
+        counts
+            .entry(current.clone())
+            .or_insert_with(|| (display(), 0))
+            .1 += moved;
+    }
+
     pub(super) fn index_unindexed_people(&mut self, context: &Context) {
-        if self.lookup.is_none() {
+        if !self.is_indexed() {
             return;
         }
-        let current_pop = context.get_current_population();
-        for id in self.max_indexed..current_pop {
+        let data_container = context.get_data_container(PeoplePlugin).unwrap();
+        for id in self.max_indexed..data_container.people_created {
             let person_id = PersonId(id);
-            self.add_person(context, person_id);
+            // The person counts as indexed while they're being added.
+            self.max_indexed = id + 1;
+            if !data_container.removed_people.contains(&person_id) {
+                match &mut self.custom {
+                    Some(custom) => custom.add_person(context, person_id),
+                    None => self.add_person(context, person_id),
+                }
+            }
         }
-        self.max_indexed = current_pop;
+        self.max_indexed = data_container.people_created;
+    }
+
+    // The distinct values of the property, each with the number of people
+    // who have it and the lowest id among them, sorted by decreasing count
+    // and then by `IndexValue`. If the property is indexed this reads the
+    // lookup table, so call `index_unindexed_people()` first; otherwise
+    // it scans the whole population.
+    pub(super) fn value_counts(&self, context: &Context) -> Vec<(IndexValue, usize, PersonId)> {
+        let mut counts: Vec<(IndexValue, usize, PersonId)> = if let Some(lookup) = &self.lookup {
+            lookup
+                .iter()
+                .filter_map(|(hash, (_, people))| {
+                    let first = people.iter().min_by_key(|person_id| person_id.0)?;
+                    Some((hash.clone(), people.len(), *first))
+                })
+                .collect()
+        } else {
+            let mut counts = HashMap::<IndexValue, (usize, PersonId)>::new();
+            let data_container = context.get_data_container(PeoplePlugin).unwrap();
+            for person_id in data_container.people_iterator() {
+                counts
+                    .entry((self.indexer)(context, person_id))
+                    .or_insert((0, person_id))
+                    .0 += 1;
+            }
+            counts
+                .into_iter()
+                .map(|(hash, (count, first))| (hash, count, first))
+                .collect()
+        };
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
     }
 }
 
@@ -124,7 +267,7 @@ pub fn process_indices(
     context: &Context,
     remaining_indices: &[&Index],
     property_names: &mut Vec<String>,
-    current_matches: &HashSet<PersonId>,
+    current_matches: &IndexedPeople,
     print_fn: &dyn Fn(&Context, &[String], usize),
 ) {
     if remaining_indices.is_empty() {
@@ -155,6 +298,66 @@ pub fn process_indices(
     }
 }
 
+// Calls `group_fn` with the people who have each combination of values of
+// the properties in `remaining_indices`, all of which must be indexed.
+// Combinations that no one has are skipped.
+pub(super) fn for_each_index_group(
+    remaining_indices: &[&Index],
+    current_matches: Option<&IndexedPeople>,
+    group_fn: &mut dyn FnMut(&IndexedPeople),
+) {
+    let Some((next_index, rest_indices)) = remaining_indices.split_first() else {
+        if let Some(matches) = current_matches {
+            if !matches.is_empty() {
+                group_fn(matches);
+            }
+        }
+        return;
+    };
+
+    for (_, people) in next_index.lookup.as_ref().unwrap().values() {
+        match current_matches {
+            None => for_each_index_group(rest_indices, Some(people), group_fn),
+            Some(matches) => {
+                let intersection = matches.intersection(people).copied().collect();
+                for_each_index_group(rest_indices, Some(&intersection), group_fn);
+            }
+        }
+    }
+}
+
+fn subscribe_to_changes<T: PersonProperty + 'static>(
+    context: &mut Context,
+    callback: Rc<PropertyChangeCallback>,
+) {
+    context.subscribe_to_event(move |context, event: PersonPropertyChangeEvent<T>| {
+        callback(
+            context,
+            event.person_id,
+            &format!("{:?}", event.previous),
+            &format!("{:?}", event.current),
+        );
+    });
+}
+
+fn emit_membership_change<T: PersonProperty + 'static>(
+    context: &mut Context,
+    person_id: PersonId,
+    added: bool,
+) {
+    let value = context.get_person_property_unaudited(person_id, T::get_instance());
+    let (previous, current) = if added {
+        (None, Some(value))
+    } else {
+        (Some(value), None)
+    };
+    context.emit_event(IndexChange::<T> {
+        person_id,
+        previous,
+        current,
+    });
+}
+
 #[cfg(test)]
 mod test {
     // Tests in `src/people/query.rs` also exercise indexing code.
@@ -0,0 +1,451 @@
+use crate::people::loader::{add_people_from_rows, row_error};
+use crate::people::{InitializationList, PeoplePlugin};
+use crate::{info, Context, IxaError, PersonId, PersonProperty};
+use csv::StringRecord;
+use serde::de::DeserializeOwned;
+use std::any::TypeId;
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Instant, SystemTime};
+
+// Reads the values of a lazy column from `source`, returning how many
+// people were given values.
+type Materialize = fn(&Context, &LazySource, &str) -> Result<usize, IxaError>;
+
+/// A column of a CSV file of people whose values aren't read until its
+/// property is first used; see [`Context::load_people_from_csv_lazy()`].
+pub struct LazyColumn {
+    column: String,
+    property: TypeId,
+    name: &'static str,
+    materialize: Materialize,
+}
+
+impl LazyColumn {
+    /// Read property `T` from the column named `column`, parsing each
+    /// field as a `T::Value`.
+    ///
+    /// # Panics
+    /// Panics if `T` is a derived property.
+    #[must_use]
+    pub fn new<T: PersonProperty + 'static>(_property: T, column: &str) -> Self
+    where
+        T::Value: DeserializeOwned,
+    {
+        assert!(
+            !T::is_derived(),
+            "Cannot load the derived property {} from a file",
+            T::name()
+        );
+        LazyColumn {
+            column: column.to_string(),
+            property: TypeId::of::<T>(),
+            name: T::name(),
+            materialize: materialize::<T>,
+        }
+    }
+}
+
+// A file that lazy columns are read from, with the people added from its
+// rows, in order. The size and modification time are recorded when it's
+// loaded so that a changed file isn't read.
+pub(super) struct LazySource {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+    people: RefCell<Vec<PersonId>>,
+    loading: Cell<bool>,
+}
+
+impl LazySource {
+    fn check_unchanged(&self) -> Result<(), IxaError> {
+        let metadata = std::fs::metadata(&self.path)?;
+        if metadata.len() != self.len || metadata.modified().ok() != self.modified {
+            return Err(self.changed());
+        }
+        Ok(())
+    }
+
+    fn changed(&self) -> IxaError {
+        IxaError::IxaError(format!(
+            "{} has changed since people were loaded from it",
+            self.path.display()
+        ))
+    }
+}
+
+// A lazy column whose values haven't been read yet
+pub(super) struct PendingColumn {
+    name: &'static str,
+    column: String,
+    source: Rc<LazySource>,
+    materialize: Materialize,
+}
+
+// The initial values of a person loaded from a file, which also count as
+// setting the lazy properties so that they aren't reported missing.
+struct WithLazyColumns<T> {
+    properties: T,
+    lazy: Rc<[TypeId]>,
+}
+
+impl<T: InitializationList> InitializationList for WithLazyColumns<T> {
+    fn has_property(&self, t: TypeId) -> bool {
+        self.properties.has_property(t) || self.lazy.contains(&t)
+    }
+    fn set_properties(&self, context: &mut Context, person_id: PersonId) {
+        self.properties.set_properties(context, person_id);
+    }
+    fn duplicate_property(&self) -> Option<&'static str> {
+        self.properties.duplicate_property()
+    }
+}
+
+pub(super) fn load_people_from_csv_lazy<R, T, F>(
+    context: &mut Context,
+    path: &Path,
+    mut to_person: F,
+    lazy_columns: Vec<LazyColumn>,
+) -> Result<usize, IxaError>
+where
+    R: DeserializeOwned,
+    T: InitializationList,
+    F: FnMut(&Context, R) -> T,
+{
+    let metadata = std::fs::metadata(path)?;
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    if let Some(missing) = lazy_columns
+        .iter()
+        .find(|lazy| !headers.iter().any(|header| header == lazy.column))
+    {
+        return Err(IxaError::IxaError(format!(
+            "No column '{}' for {}",
+            missing.column, missing.name
+        )));
+    }
+    // A property can only be pending from one file, so finish reading it
+    // from any earlier one.
+    for lazy in &lazy_columns {
+        materialize_pending(context, lazy.property)?;
+    }
+
+    let source = Rc::new(LazySource {
+        path: path.to_path_buf(),
+        len: metadata.len(),
+        modified: metadata.modified().ok(),
+        people: RefCell::new(Vec::new()),
+        loading: Cell::new(true),
+    });
+    let lazy: Rc<[TypeId]> = lazy_columns.iter().map(|lazy| lazy.property).collect();
+    {
+        let data_container = context.get_data_container_mut(PeoplePlugin);
+        let pending = data_container.lazy_columns.get_mut();
+        for lazy in lazy_columns {
+            pending.insert(
+                lazy.property,
+                PendingColumn {
+                    name: lazy.name,
+                    column: lazy.column,
+                    source: Rc::clone(&source),
+                    materialize: lazy.materialize,
+                },
+            );
+        }
+    }
+
+    let result = add_people_from_rows(
+        context,
+        &mut reader,
+        &headers,
+        |context, row| WithLazyColumns {
+            properties: to_person(context, row),
+            lazy: Rc::clone(&lazy),
+        },
+        |person_id| source.people.borrow_mut().push(person_id),
+    );
+    // The people from the rows before an error were still added, so their
+    // lazy values can be read.
+    source.loading.set(false);
+    result
+}
+
+// Reads the values of `property` if it's a lazy column that hasn't been
+// read yet, returning whether it was. If this fails the column stays
+// pending.
+pub(super) fn materialize_pending(context: &Context, property: TypeId) -> Result<bool, IxaError> {
+    let Some(data_container) = context.get_data_container(PeoplePlugin) else {
+        return Ok(false);
+    };
+    // The column is taken out while it's read so it's only read once.
+    let pending = {
+        let mut lazy_columns = data_container.lazy_columns.borrow_mut();
+        let Some(pending) = lazy_columns.get(&property) else {
+            return Ok(false);
+        };
+        if pending.source.loading.get() {
+            return Err(IxaError::IxaError(format!(
+                "{} can't be read while people are being loaded from {}",
+                pending.name,
+                pending.source.path.display()
+            )));
+        }
+        lazy_columns.remove(&property).unwrap()
+    };
+
+    let start = Instant::now();
+    let result = pending
+        .source
+        .check_unchanged()
+        .and_then(|()| (pending.materialize)(context, &pending.source, &pending.column));
+    match result {
+        Ok(count) => {
+            info!(
+                "Loaded {} for {count} people from column '{}' of {} in {:?}",
+                pending.name,
+                pending.column,
+                pending.source.path.display(),
+                start.elapsed()
+            );
+            Ok(true)
+        }
+        Err(error) => {
+            data_container
+                .lazy_columns
+                .borrow_mut()
+                .insert(property, pending);
+            Err(error)
+        }
+    }
+}
+
+// Like `materialize_pending()`, but panics if the column can't be read,
+// for when a value is needed.
+pub(super) fn materialize_pending_or_panic<T: PersonProperty + 'static>(context: &Context) -> bool {
+    match materialize_pending(context, TypeId::of::<T>()) {
+        Ok(materialized) => materialized,
+        Err(error) => panic!("Can't load {} lazily: {}", T::name(), error.display_chain()),
+    }
+}
+
+// Reads the whole of `column` in one pass, and then sets the values of
+// `T` for the people who don't have one yet. Nothing is set unless every
+// row can be parsed.
+fn materialize<T: PersonProperty + 'static>(
+    context: &Context,
+    source: &LazySource,
+    column: &str,
+) -> Result<usize, IxaError>
+where
+    T::Value: DeserializeOwned,
+{
+    let mut reader = csv::Reader::from_path(&source.path)?;
+    let index = reader
+        .headers()?
+        .iter()
+        .position(|header| header == column)
+        .ok_or_else(|| source.changed())?;
+    let column_header = StringRecord::from(vec![column]);
+    let people = source.people.borrow();
+
+    let mut parsed = Vec::with_capacity(people.len());
+    let mut record = StringRecord::new();
+    let mut field = StringRecord::new();
+    for _ in 0..people.len() {
+        if !reader.read_record(&mut record)? {
+            return Err(source.changed());
+        }
+        let line = record.position().map_or(0, csv::Position::line);
+        field.clear();
+        field.push_field(record.get(index).unwrap_or_default());
+        let value: T::Value = field
+            .deserialize(None)
+            .map_err(|error| row_error(line, &column_header, &error))?;
+        parsed.push(value);
+    }
+
+    let data_container = context.get_data_container(PeoplePlugin).unwrap();
+    let mut values = data_container.get_property_values::<T>();
+    let mut count = 0;
+    for (&person_id, value) in people.iter().zip(parsed) {
+        if data_container.removed_people.contains(&person_id) {
+            continue;
+        }
+        if values.len() <= person_id.0 {
+            values.resize(person_id.0 + 1, None);
+        }
+        // A value that was already set, e.g., when the person was added,
+        // is kept.
+        if values[person_id.0].is_none() {
+            values[person_id.0] = Some(value);
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::people::LazyColumn;
+    use crate::{define_person_property, Context, ContextPeopleExt, IxaError, PersonId};
+    use serde::Deserialize;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    define_person_property!(LazyAge, u8);
+    define_person_property!(LazyCounty, u32);
+    define_person_property!(LazyIncome, Option<u32>);
+
+    const PEOPLE: &str = "age,county,income\n10,17031,\n20,6037,31000\n30,17031,52000\n";
+
+    #[derive(Deserialize)]
+    struct AgeRecord {
+        age: u8,
+    }
+
+    #[derive(Deserialize)]
+    struct Record {
+        age: u8,
+        county: u32,
+        income: Option<u32>,
+    }
+
+    fn write_csv(contents: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    fn load_lazy(context: &mut Context, file: &NamedTempFile) {
+        let count = context
+            .load_people_from_csv_lazy(
+                file.path(),
+                |_, record: AgeRecord| (LazyAge, record.age),
+                vec![
+                    LazyColumn::new(LazyCounty, "county"),
+                    LazyColumn::new(LazyIncome, "income"),
+                ],
+            )
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+
+    fn values(context: &Context) -> Vec<(u8, u32, Option<u32>)> {
+        context
+            .query_people(())
+            .into_iter()
+            .map(|person_id| {
+                (
+                    context.get_person_property(person_id, LazyAge),
+                    context.get_person_property(person_id, LazyCounty),
+                    context.get_person_property(person_id, LazyIncome),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn materialized_values_match_eager_loading() {
+        let file = write_csv(PEOPLE);
+        let mut eager = Context::new();
+        eager
+            .load_people_from_csv(file.path(), |_, record: Record| {
+                (
+                    (LazyAge, record.age),
+                    (LazyCounty, record.county),
+                    (LazyIncome, record.income),
+                )
+            })
+            .unwrap();
+        let mut lazy = Context::new();
+        load_lazy(&mut lazy, &file);
+
+        assert_eq!(values(&lazy), values(&eager));
+        assert_eq!(
+            values(&lazy),
+            [
+                (10, 17031, None),
+                (20, 6037, Some(31000)),
+                (30, 17031, Some(52000))
+            ]
+        );
+    }
+
+    #[test]
+    fn column_is_read_once() {
+        let mut file = write_csv(PEOPLE);
+        let mut context = Context::new();
+        load_lazy(&mut context, &file);
+
+        assert_eq!(context.get_person_property(PersonId(2), LazyCounty), 17031);
+        context.set_person_property(PersonId(0), LazyCounty, 1);
+        // The file isn't read again, so changing it now doesn't matter.
+        file.write_all(b"40,1,1\n").unwrap();
+        for _ in 0..3 {
+            assert!(!context.materialize_lazy_property(LazyCounty).unwrap());
+            assert_eq!(context.get_person_property(PersonId(0), LazyCounty), 1);
+            assert_eq!(context.get_person_property(PersonId(1), LazyCounty), 6037);
+        }
+        // People added later aren't from the file.
+        let person = context
+            .add_person(((LazyAge, 50), (LazyCounty, 2), (LazyIncome, None)))
+            .unwrap();
+        assert_eq!(context.get_person_property(person, LazyCounty), 2);
+    }
+
+    #[test]
+    fn query_materializes_column() {
+        let file = write_csv(PEOPLE);
+        let mut context = Context::new();
+        context.index_property(LazyIncome);
+        load_lazy(&mut context, &file);
+
+        assert_eq!(
+            context.query_people((LazyCounty, 17031)),
+            [PersonId(0), PersonId(2)]
+        );
+        assert_eq!(
+            context.query_people((LazyIncome, Some(31000))),
+            [PersonId(1)]
+        );
+        assert!(!context.materialize_lazy_property(LazyCounty).unwrap());
+        assert!(!context.materialize_lazy_property(LazyIncome).unwrap());
+    }
+
+    #[test]
+    fn changed_file_is_an_error() {
+        let mut file = write_csv(PEOPLE);
+        let mut context = Context::new();
+        load_lazy(&mut context, &file);
+        file.write_all(b"40,1,1\n").unwrap();
+
+        let error = context.materialize_lazy_property(LazyCounty).unwrap_err();
+        assert!(
+            matches!(&error, IxaError::IxaError(message) if message.ends_with("has changed since people were loaded from it")),
+            "{error:?}"
+        );
+        // The column is still unread, so using it is still an error.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            context.get_person_property(PersonId(0), LazyCounty)
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn missing_lazy_column_is_an_error() {
+        let file = write_csv("age\n10\n");
+        let mut context = Context::new();
+        let result = context.load_people_from_csv_lazy(
+            file.path(),
+            |_, record: AgeRecord| (LazyAge, record.age),
+            vec![LazyColumn::new(LazyCounty, "county")],
+        );
+        let error = result.unwrap_err().display_chain();
+        assert!(
+            error.contains("No column 'county' for LazyCounty"),
+            "{error}"
+        );
+        assert_eq!(context.get_current_population(), 0);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::people::InitializationList;
+use crate::{Context, ContextPeopleExt, IxaError, IxaResultExt, PersonId};
+use csv::{ErrorKind, StringRecord};
+use serde::de::DeserializeOwned;
+use std::fs::File;
+use std::path::Path;
+
+// Describes a row that couldn't be deserialized, naming the column when
+// the error is about a particular field.
+pub(super) fn row_error(line: u64, headers: &StringRecord, error: &csv::Error) -> IxaError {
+    let (column, message) = match error.kind() {
+        ErrorKind::Deserialize { err, .. } => {
+            let column = err
+                .field()
+                .and_then(|field| usize::try_from(field).ok())
+                .and_then(|field| headers.get(field));
+            (column.map(String::from), err.kind().to_string())
+        }
+        _ => (None, error.to_string()),
+    };
+    IxaError::InvalidRow {
+        line,
+        column,
+        message,
+    }
+}
+
+pub(super) fn load_people_from_csv<R, T, F>(
+    context: &mut Context,
+    path: &Path,
+    to_person: F,
+) -> Result<usize, IxaError>
+where
+    R: DeserializeOwned,
+    T: InitializationList,
+    F: FnMut(&Context, R) -> T,
+{
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    add_people_from_rows(context, &mut reader, &headers, to_person, |_| {})
+}
+
+// Adds a person for each of the remaining rows of `reader`, calling
+// `added` with each of them in order.
+pub(super) fn add_people_from_rows<R, T, F>(
+    context: &mut Context,
+    reader: &mut csv::Reader<File>,
+    headers: &StringRecord,
+    mut to_person: F,
+    mut added: impl FnMut(PersonId),
+) -> Result<usize, IxaError>
+where
+    R: DeserializeOwned,
+    T: InitializationList,
+    F: FnMut(&Context, R) -> T,
+{
+    // Only one row is held in memory at a time.
+    let mut record = StringRecord::new();
+    let mut count = 0;
+    while reader.read_record(&mut record)? {
+        let line = record.position().map_or(0, csv::Position::line);
+        let row: R = record
+            .deserialize(Some(headers))
+            .map_err(|error| row_error(line, headers, &error))?;
+        let properties = to_person(context, row);
+        let person_id = context
+            .add_person(properties)
+            .with_ctx(|| format!("Initializing person from line {line}"))?;
+        added(person_id);
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{define_person_property, define_person_property_with_default};
+    use crate::{Context, ContextPeopleExt, IxaError, PersonCreatedEvent};
+    use serde::Deserialize;
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+    use tempfile::NamedTempFile;
+
+    define_person_property!(LoadedAge, u8);
+    define_person_property_with_default!(LoadedCounty, u32, 0);
+
+    #[derive(Deserialize)]
+    struct Record {
+        age: u8,
+        #[serde(default)]
+        county: Option<u32>,
+    }
+
+    fn write_csv(contents: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    fn load(context: &mut Context, file: &NamedTempFile) -> Result<usize, IxaError> {
+        context.load_people_from_csv(file.path(), |_, record: Record| {
+            (
+                (LoadedAge, record.age),
+                (LoadedCounty, record.county.unwrap_or(0)),
+            )
+        })
+    }
+
+    #[test]
+    fn loads_every_row() {
+        let file = write_csv("age,county\n10,17031\n20,\n30,6037\n");
+        let mut context = Context::new();
+        let created = Rc::new(RefCell::new(Vec::new()));
+        let created_clone = Rc::clone(&created);
+        context.subscribe_to_event(move |_, event: PersonCreatedEvent| {
+            created_clone.borrow_mut().push(event.person_id);
+        });
+        assert_eq!(load(&mut context, &file).unwrap(), 3);
+        context.execute();
+
+        assert_eq!(created.borrow().len(), 3);
+        let people = context.query_people(());
+        let values: Vec<(u8, u32)> = people
+            .iter()
+            .map(|&person_id| {
+                (
+                    context.get_person_property(person_id, LoadedAge),
+                    context.get_person_property(person_id, LoadedCounty),
+                )
+            })
+            .collect();
+        assert_eq!(values, [(10, 17031), (20, 0), (30, 6037)]);
+    }
+
+    #[test]
+    fn optional_column_can_be_missing() {
+        let file = write_csv("age\n10\n20\n");
+        let mut context = Context::new();
+        assert_eq!(load(&mut context, &file).unwrap(), 2);
+        assert_eq!(context.query_people_count((LoadedCounty, 0)), 2);
+    }
+
+    #[test]
+    fn bad_value_names_line_and_column() {
+        let file = write_csv("age,county\n10,1\n20,2\nold,3\n40,4\n");
+        let mut context = Context::new();
+        match load(&mut context, &file) {
+            Err(IxaError::Context { source, .. }) => match *source {
+                IxaError::InvalidRow { line, column, .. } => {
+                    assert_eq!(line, 4);
+                    assert_eq!(column.as_deref(), Some("age"));
+                }
+                other => panic!("Unexpected error {other:?}"),
+            },
+            other => panic!("Unexpected result {other:?}"),
+        }
+        // The rows before the bad one were loaded.
+        assert_eq!(context.get_current_population(), 2);
+    }
+
+    #[test]
+    fn missing_required_column_is_an_error() {
+        let file = write_csv("county\n1\n");
+        let mut context = Context::new();
+        let error = load(&mut context, &file).unwrap_err().display_chain();
+        assert!(error.contains("caused by: Line 2: "), "{error}");
+        assert!(error.contains("age"), "{error}");
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        let mut context = Context::new();
+        let result = context.load_people_from_csv(
+            std::path::Path::new("no/such/people.csv"),
+            |_, record: Record| (LoadedAge, record.age),
+        );
+        assert!(matches!(
+            result,
+            Err(IxaError::Context { source, .. }) if matches!(*source, IxaError::CsvError(_))
+        ));
+    }
+
+    #[test]
+    fn missing_property_names_file_line_property_and_caller() {
+        #[derive(Deserialize)]
+        struct CountyRecord {
+            county: u32,
+        }
+
+        let file = write_csv("county\n1\n");
+        let mut context = Context::new();
+        context.add_person((LoadedAge, 30)).unwrap();
+        // The age is required, but isn't given.
+        let caller_line = line!() + 1;
+        let result = context.load_people_from_csv(file.path(), |_, record: CountyRecord| {
+            (LoadedCounty, record.county)
+        });
+        let error = result.unwrap_err();
+        let chain = error.display_chain();
+        let lines: Vec<&str> = chain.lines().collect();
+        assert_eq!(lines.len(), 3, "{chain}");
+        assert!(
+            lines[0].starts_with(&format!(
+                "Loading people from {} (at {}:{caller_line}:",
+                file.path().display(),
+                file!()
+            )),
+            "{chain}"
+        );
+        assert!(
+            lines[1].starts_with("  caused by: Initializing person from line 2 (at "),
+            "{chain}"
+        );
+        assert_eq!(
+            lines[2],
+            "  caused by: Person is missing values for required properties: LoadedAge"
+        );
+    }
+}
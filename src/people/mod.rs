@@ -17,6 +17,11 @@
 //! * The property can have an initializer function (provided when
 //!   the property is defined) that is called lazily when the
 //!   property is first accessed.
+//! * The property can have an initial distribution (provided when
+//!   the property is defined with `init_distribution`) that is
+//!   sampled lazily when the property is first accessed, using a
+//!   generator that depends only on the base seed, the property,
+//!   and the person.
 //!
 //! If neither a default or an initializer is provided, then you
 //! must provide an initial value for each person on person
@@ -41,6 +46,15 @@
 //! it may be called multiple times with those inputs, depending
 //! on the program structure.
 //!
+//! # Enum Properties
+//!
+//! Properties whose values are enums with variants that carry data, such
+//! as an infection status that records when the person was infected, can
+//! be defined with [`define_enum_person_property!()`]. Along with the
+//! property itself, this defines a derived property for the variant alone,
+//! so people can be queried, indexed and tabulated by variant regardless
+//! of the data.
+//!
 //! # Change Events
 //!
 //! Whenever a person property `E` has potentially changed, either
@@ -53,38 +67,143 @@
 //! on a lazily initialized event will emit an event for the change from
 //! the initialized value to the new value.
 //!
+//! # Staging Changes
+//!
+//! When a property's future value is known in advance, e.g., the time a
+//! person will recover, [`Context::stage_property_change()`] schedules the
+//! change without a plan per person. Staged changes can be looked up with
+//! [`Context::get_staged_change()`], cancelled with
+//! [`Context::cancel_staged_change()`], and queried with
+//! [`HasStagedChange`]. By default, setting the property directly or
+//! removing the person cancels the change; see [`StagedChangePolicy`].
+//!
+//! # Removing People
+//!
+//! People can be removed from the simulation, e.g., when they die or
+//! emigrate, with [`Context::remove_person()`]. This deletes their property
+//! values, removes them from the indexes, and emits a
+//! [`PersonRemovedEvent`]. Removed people aren't counted in the population
+//! or returned by queries, and their [`PersonId`]s are never reused.
+//! Plans that captured a removed person's id still run, so they should
+//! check [`Context::person_exists()`] first: getting a property of a
+//! removed person panics, and [`Context::try_get_person_property()`]
+//! returns an error.
+//!
 //! # Querying
 //!
 //! Person properties provides an interface to query for people matching
 //! a given set of properties. The basic syntax is to supply a set of
 //! (property, value) pairs, like so `query_people(((Age, 30), (Gender, Female)))`.
 //! Note that these need to be wrapped in an extra set of parentheses
-//! to make them a single tuple to pass to [`Context::query_people()`]. Queries implement
-//! strict equality, so if you want a fancier predicate you need to implement
-//! a derived property that computes it and then query over the derived property.
+//! to make them a single tuple to pass to [`Context::query_people()`]. Pairs
+//! match on strict equality. To match a range of values use [`InRange`],
+//! e.g., `query_people(InRange(Age, 65..))`, and to match any one of several
+//! values use [`AnyOf`]. These can be combined with other criteria by putting
+//! them first in a pair, e.g.,
+//! `query_people((InRange(Age, 65..), ((Gender, Female), (RiskCategory, High))))`.
+//! For anything fancier, implement a derived property that computes it and
+//! then query over the derived property.
 //!
 //! The internals of query are deliberately opaque in that Ixa may or
 //! may not ordinarily choose to create caches or indexes for
 //! queries. However, you force an index to be created for a single
 //! property by using [`Context::index_property()`].
+//!
+//! # Non-finite Values
+//!
+//! NaN and infinite values in floating point properties usually come
+//! from a bug in model code and silently end up in their own groups in
+//! queries and tabulations. In debug builds, setting a floating point
+//! property (or computing a derived property) to a non-finite value
+//! panics with the name of the property and the person. In release
+//! builds, every Nth set is checked and logs a warning instead; see
+//! [`Context::set_non_finite_check_interval()`]. Properties that
+//! legitimately use infinities can opt out with
+//! [`Context::allow_non_finite()`].
+//!
+//! # Initializing to Targets
+//!
+//! When only aggregate information about the starting state is available,
+//! [`Context::initialize_to_targets()`] sets property values for exactly
+//! the number of people required by a list of [`TargetSpec`]s, optionally
+//! stratified by another property.
+//!
+//! To create many similar people at once, describe their shared values and
+//! value distributions with a [`PersonTemplate`] and pass it to
+//! [`Context::add_people_from_template()`].
+//!
+//! # Comparing Snapshots
+//!
+//! To find out who changed between two points in a run, take a
+//! [`PropertySnapshot`] of selected properties with
+//! [`Context::snapshot_properties()`] and later compare it with the current
+//! values using [`Context::diff_against()`]. The resulting [`PropertyDiff`]
+//! can be queried by property or written to a report.
+//!
+//! # Auditing Property Access
+//!
+//! When results depend on the order of plans at the same time, e.g.,
+//! because one module reads a property before another has updated it,
+//! [`Context::audit_property_reads()`] and
+//! [`Context::audit_property_writes()`] show which code accesses a
+//! property and when. Each access can be logged at debug level with the
+//! person, time, execution phase and the location of the call, or passed
+//! to an [`AuditTrap`] callback for a window of time or a single person.
+//! Audits are only available in debug builds, so they cost nothing in
+//! release builds.
+//!
+//! # Summarizing Numeric Properties
+//!
+//! [`Context::property_summary()`] computes the count, mean, standard
+//! deviation, minimum, maximum and quartiles of a numeric property among the
+//! people matching a query, e.g., the viral load of the infectious people,
+//! and [`Context::property_summary_by()`] does the same for each value of
+//! another property. The values are summarized in a single pass with bounded
+//! memory, so quantiles of more than [`EXACT_SUMMARY_LIMIT`] values are
+//! estimates.
 
+mod audit;
+mod batch;
+pub(crate) mod checkpoint;
 mod context_extension;
+mod custom_index;
 mod data;
 mod event;
 pub(crate) mod external_api;
 mod index;
+mod lazy;
+mod loader;
 mod property;
 mod query;
+mod snapshot;
+mod staged;
+mod summary;
+mod targets;
+mod template;
 
 use crate::{context::Context, define_data_plugin};
-pub use context_extension::ContextPeopleExt;
+pub use audit::{AccessKind, AuditAction, AuditTrap, PropertyAccess};
+pub use context_extension::{BackfillStats, ContextPeopleExt};
+pub use custom_index::{FullIndex, PropertyIndexImpl, ValueCountIndex};
 use data::PeopleData;
 pub use data::PersonPropertyHolder;
-pub use event::{PersonCreatedEvent, PersonPropertyChangeEvent};
+pub use event::{IndexChange, PersonCreatedEvent, PersonPropertyChangeEvent, PersonRemovedEvent};
+pub(crate) use index::IndexValue;
+pub use lazy::LazyColumn;
 pub use property::{
-    define_derived_property, define_person_property, define_person_property_with_default,
-    PersonProperty,
+    define_derived_property, define_enum_person_property, define_person_property,
+    define_person_property_with_default, PersonProperty,
 };
+pub(crate) use query::Query;
+pub use query::{AnyOf, HasStagedChange, InRange};
+pub use snapshot::{
+    PropertyChange, PropertyDiff, PropertyDiffReport, PropertySnapshot, SnapshotSpec,
+};
+pub use staged::StagedChangePolicy;
+pub(crate) use summary::summarize_by_name;
+pub use summary::{NumericValue, PropertySummary, EXACT_SUMMARY_LIMIT, SUMMARY_QUANTILES};
+pub use targets::{TargetCount, TargetSpec};
+pub use template::PersonTemplate;
 
 use seq_macro::seq;
 use serde::{Deserialize, Serialize};
@@ -101,15 +220,27 @@ define_data_plugin!(
     PeopleData,
     PeopleData {
         is_initializing: false,
-        current_population: 0,
+        people_created: 0,
+        removed_people: HashSet::new(),
         properties_map: RefCell::new(HashMap::new()),
         registered_derived_properties: RefCell::new(HashSet::new()),
         dependency_map: RefCell::new(HashMap::new()),
         property_indexes: RefCell::new(HashMap::new()),
         people_types: RefCell::new(HashMap::new()),
+        lazy_columns: RefCell::new(HashMap::new()),
+        non_finite_allowed: HashSet::new(),
+        non_finite_check_interval: DEFAULT_NON_FINITE_CHECK_INTERVAL,
+        non_finite_set_count: 0,
+        non_finite_warning_count: 0,
+        #[cfg(debug_assertions)]
+        audits: HashMap::new(),
     }
 );
 
+/// In release builds, the number of property sets between checks for
+/// non-finite values. See [`Context::set_non_finite_check_interval()`].
+pub const DEFAULT_NON_FINITE_CHECK_INTERVAL: usize = 1000;
+
 /// Represents a unique person.
 //  the id refers to that person's index in the range 0 to population
 // - 1 in the PeopleData container.
@@ -134,6 +265,10 @@ impl Debug for PersonId {
 pub trait InitializationList {
     fn has_property(&self, t: TypeId) -> bool;
     fn set_properties(&self, context: &mut Context, person_id: PersonId);
+    /// The name of a property that's set more than once, if any
+    fn duplicate_property(&self) -> Option<&'static str> {
+        None
+    }
 }
 
 // Implement the query version with 0 and 1 parameters
@@ -180,6 +315,13 @@ macro_rules! impl_initialization_list {
                        context.set_person_property(person_id, T~N::get_instance(), self.N.1 );
                     )*
                 }
+
+                fn duplicate_property(&self) -> Option<&'static str> {
+                    let properties = [#( (TypeId::of::<T~N>(), T~N::name()), )*];
+                    properties.iter().enumerate().find_map(|(i, (t, name))| {
+                        properties[..i].iter().any(|(other, _)| other == t).then_some(*name)
+                    })
+                }
             }
         });
     }
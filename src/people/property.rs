@@ -1,5 +1,7 @@
 use crate::people::data::PersonPropertyHolder;
 use crate::{Context, PersonId};
+use ordered_float::OrderedFloat;
+use std::any::Any;
 use std::fmt::Debug;
 use std::hash::Hash;
 
@@ -7,10 +9,10 @@ use std::hash::Hash;
 /// disease status.
 ///
 /// Person properties should defined with the [`define_person_property!()`],
-/// [`define_person_property_with_default!()`] and [`define_derived_property!()`]
-/// macros.
+/// [`define_person_property_with_default!()`], [`define_derived_property!()`]
+/// and [`define_enum_person_property!()`] macros.
 pub trait PersonProperty: Copy {
-    type Value: Copy + Debug + PartialEq + Hash;
+    type Value: Copy + Debug + PartialEq + Hash + 'static;
     #[must_use]
     fn is_derived() -> bool {
         false
@@ -28,14 +30,72 @@ pub trait PersonProperty: Copy {
     fn name() -> &'static str;
 }
 
+/// Returns true if `value` is a NaN or infinite floating point number.
+///
+/// Because `f32` and `f64` don't implement `Hash`, floating point property
+/// values are normally stored as [`OrderedFloat`]s, so this recognizes
+/// `f32`, `f64`, and `OrderedFloat`s of either, as well as `Option`s of any
+/// of these. Values of any other type are always considered finite.
+pub(crate) fn is_non_finite(value: &dyn Any) -> bool {
+    fn check<T: Any + Copy>(value: &dyn Any, is_finite: impl Fn(T) -> bool) -> Option<bool> {
+        if let Some(value) = value.downcast_ref::<T>() {
+            return Some(!is_finite(*value));
+        }
+        if let Some(value) = value.downcast_ref::<Option<T>>() {
+            return Some(value.is_some_and(|value| !is_finite(value)));
+        }
+        None
+    }
+
+    check(value, f64::is_finite)
+        .or_else(|| check(value, f32::is_finite))
+        .or_else(|| check(value, |value: OrderedFloat<f64>| value.is_finite()))
+        .or_else(|| check(value, |value: OrderedFloat<f32>| value.is_finite()))
+        .unwrap_or(false)
+}
+
 /// Defines a person property with the following parameters:
 /// * `$person_property`: A name for the identifier type of the property
 /// * `$value`: The type of the property's value
 /// * `$initialize`: (Optional) A function that takes a `Context` and `PersonId` and
 ///   returns the initial value. If it is not defined, calling `get_person_property`
 ///   on the property without explicitly setting a value first will panic.
+///
+/// Instead of `$initialize`, you can write `init_distribution = |rng| ...`
+/// with a closure that takes a `&mut StdRng` and draws the initial value:
+///
+/// ```
+/// # use ixa::define_person_property;
+/// use rand::distributions::{Distribution, WeightedIndex};
+///
+/// define_person_property!(Age, usize, init_distribution = |rng| {
+///     WeightedIndex::new([1.0, 2.0, 1.0]).unwrap().sample(rng) * 30
+/// });
+/// ```
+///
+/// As with `$initialize`, the value is drawn when the property is first
+/// accessed unless a value was provided in `add_person()`. Each draw uses a
+/// generator seeded from the base seed (see `init_random()`), the property
+/// name, and the person, so a person's value doesn't depend on when or in
+/// what order people are accessed, and drawing it doesn't affect any
+/// `RngId`. A property can't have both a default and an initial
+/// distribution, and derived properties can't have one at all.
 #[macro_export]
 macro_rules! define_person_property {
+    ($person_property:ident, $value:ty, init_distribution = $sampler:expr) => {
+        $crate::define_person_property!(
+            $person_property,
+            $value,
+            |context: &$crate::context::Context, person_id: $crate::people::PersonId| {
+                $crate::random::sample_person_initial_value(
+                    context,
+                    stringify!($person_property),
+                    person_id,
+                    $sampler,
+                )
+            }
+        );
+    };
     ($person_property:ident, $value:ty, $initialize:expr) => {
         #[derive(Debug, Copy, Clone)]
         pub struct $person_property;
@@ -64,7 +124,13 @@ macro_rules! define_person_property {
                 _context: &$crate::context::Context,
                 _person: $crate::people::PersonId,
             ) -> Self::Value {
-                panic!("Property not initialized when person created.");
+                panic!(
+                    "Property not initialized when person created: {} has no value for {:?}. \
+                     Set it when adding the person, or give it a default or initializer \
+                     and call backfill_property()",
+                    stringify!($person_property),
+                    _person
+                );
             }
             fn is_required() -> bool {
                 true
@@ -84,8 +150,18 @@ pub use define_person_property;
 /// * `$person_property`: A name for the identifier type of the property
 /// * `$value`: The type of the property's value
 /// * `$default`: An initial value
+///
+/// A default can't be combined with an initial distribution:
+///
+/// ```compile_fail
+/// # use ixa::define_person_property_with_default;
+/// define_person_property_with_default!(Age, u8, 0, init_distribution = |_rng| 1);
+/// ```
 #[macro_export]
 macro_rules! define_person_property_with_default {
+    ($person_property:ident, $value:ty, $default:expr, init_distribution = $sampler:expr) => {
+        compile_error!("A person property can't have both a default and an init_distribution");
+    };
     ($person_property:ident, $value:ty, $default:expr) => {
         $crate::define_person_property!($person_property, $value, |_context, _person_id| {
             $default
@@ -98,7 +174,12 @@ pub use define_person_property_with_default;
 /// * `$person_property`: A name for the identifier type of the property
 /// * `$value`: The type of the property's value
 /// * `[$($dependency),+]`: A list of person properties the derived property depends on
-/// * `[$($dependency),*]`: A list of global properties the derived property depends on (optional)
+/// * `[$($dependency),*]`: A list of global properties the derived property depends on (optional),
+///   which can't be mutable (see [`define_mutable_global_property!()`](crate::define_mutable_global_property))
+/// * `[$($value_map),+]`: A list of value maps (optional, after the global properties; see
+///   [`define_value_map!()`](crate::define_value_map)), whose values are looked up for the person.
+///   The key properties of the maps are dependencies too. Computing the value panics if a map
+///   isn't set or has no value for the person's key.
 /// * $calculate: A closure that takes the values of each dependency and returns the derived value
 #[macro_export]
 macro_rules! define_derived_property {
@@ -138,6 +219,66 @@ macro_rules! define_derived_property {
                 stringify!($derived_property)
             }
         }
+
+        $(
+            const _: () = assert!(
+                !<$global_dependency as $crate::global_properties::GlobalProperty>::IS_MUTABLE,
+                "Derived properties can't depend on mutable global properties"
+            );
+        )*
+    };
+    (
+        $derived_property:ident,
+        $value:ty,
+        [$($dependency:ident),*],
+        [$($global_dependency:ident),*],
+        [$($value_map:ident),+],
+        |$($param:ident),+| $derive_fn:expr
+    ) => {
+        #[derive(Debug, Copy, Clone)]
+        pub struct $derived_property;
+
+        impl $crate::people::PersonProperty for $derived_property {
+            type Value = $value;
+            fn compute(context: &$crate::context::Context, person_id: $crate::people::PersonId) -> Self::Value {
+                #[allow(unused_imports)]
+                use $crate::global_properties::ContextGlobalPropertiesExt;
+                use $crate::value_map::ContextValueMapExt;
+                #[allow(unused_parens)]
+                let ($($param,)*) = (
+                    $(context.get_person_property(person_id, $dependency),)*
+                    $(
+                        *context.get_global_property_value($global_dependency)
+                            .expect(&format!("Global property {} not initialized", stringify!($global_dependency))),
+                    )*
+                    $(
+                        context.lookup($value_map, person_id)
+                            .unwrap_or_else(|error| panic!("{error}")),
+                    )*
+                );
+                (|$($param),+| $derive_fn)($($param),+)
+            }
+            fn is_derived() -> bool { true }
+            fn dependencies() -> Vec<Box<dyn $crate::people::PersonPropertyHolder>> {
+                vec![
+                    $(Box::new($dependency),)*
+                    $(Box::new(<$value_map as $crate::value_map::ValueMap>::key_property()),)+
+                ]
+            }
+            fn get_instance() -> Self {
+                $derived_property
+            }
+            fn name() -> &'static str {
+                stringify!($derived_property)
+            }
+        }
+
+        $(
+            const _: () = assert!(
+                !<$global_dependency as $crate::global_properties::GlobalProperty>::IS_MUTABLE,
+                "Derived properties can't depend on mutable global properties"
+            );
+        )*
     };
     (
         $derived_property:ident,
@@ -145,7 +286,7 @@ macro_rules! define_derived_property {
         [$($dependency:ident),*],
         |$($param:ident),+| $derive_fn:expr
     ) => {
-        define_derived_property!(
+        $crate::define_derived_property!(
             $derived_property,
             $value,
             [$($dependency),*],
@@ -155,3 +296,129 @@ macro_rules! define_derived_property {
     };
 }
 pub use define_derived_property;
+
+/// Defines a person property whose value is an enum, which may have
+/// variants that carry data, along with a derived property for the
+/// variant alone:
+///
+/// ```
+/// # use ixa::{define_enum_person_property, Context, ContextPeopleExt};
+/// use ordered_float::OrderedFloat;
+///
+/// define_enum_person_property!(
+///     InfectionStatus,
+///     enum InfectionStatusValue {
+///         Susceptible,
+///         Infected { since: OrderedFloat<f64>, strain: u8 },
+///         Recovered,
+///     }
+/// );
+///
+/// let mut context = Context::new();
+/// let person = context
+///     .add_person((InfectionStatus, InfectionStatusValue::Susceptible))
+///     .unwrap();
+/// context.set_person_property(
+///     person,
+///     InfectionStatus,
+///     InfectionStatusValue::Infected { since: OrderedFloat(1.0), strain: 2 },
+/// );
+/// let infected = context.query_people((InfectionStatusKind, InfectionStatusKindValue::Infected));
+/// assert_eq!(infected, vec![person]);
+/// ```
+///
+/// This defines:
+/// * The enum, which derives `Debug`, `Copy`, `Clone`, `PartialEq` and
+///   `Hash`, so the types of the variants' fields must implement those.
+///   Attributes written before `enum`, such as other derives, are applied
+///   to it.
+/// * `$person_property`, a person property whose value is the enum. As with
+///   [`define_person_property!()`], it is required when a person is added
+///   unless an initializer or `init_distribution = ...` is given after the
+///   enum.
+/// * `<$person_property>KindValue`, an enum with the same variants but
+///   without their fields, which implements `Display`, `Eq`, `Serialize`
+///   and `Deserialize`, along with a `kind()` method on the value enum that
+///   returns it.
+/// * `<$person_property>Kind`, a derived property whose value is the
+///   variant. It can be queried, indexed and tabulated like any other
+///   property, so people can be found by variant regardless of the data
+///   they carry.
+///
+/// Like other derived properties, a
+/// [`PersonPropertyChangeEvent`](crate::PersonPropertyChangeEvent) for
+/// `<$person_property>Kind` is emitted whenever the enum property is set,
+/// including when only the fields change and the variant stays the same,
+/// in which case `current` and `previous` are equal.
+#[macro_export]
+macro_rules! define_enum_person_property {
+    (
+        $person_property:ident,
+        $(#[$meta:meta])*
+        enum $value:ident {
+            $(
+                $variant:ident
+                $( { $($field:ident : $field_type:ty),* $(,)? } )?
+                $( ( $($tuple_type:ty),* $(,)? ) )?
+            ),+ $(,)?
+        }
+        $(, $($initialize:tt)+ )?
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Copy, Clone, PartialEq, Hash)]
+        pub enum $value {
+            $(
+                $variant
+                $( { $($field : $field_type),* } )?
+                $( ( $($tuple_type),* ) )?
+            ),+
+        }
+
+        paste::paste! {
+            #[doc = concat!("The variants of [`", stringify!($value), "`], without their data.")]
+            #[derive(
+                Debug,
+                Copy,
+                Clone,
+                PartialEq,
+                Eq,
+                Hash,
+                PartialOrd,
+                Ord,
+                serde::Serialize,
+                serde::Deserialize,
+            )]
+            pub enum [<$person_property KindValue>] {
+                $($variant),+
+            }
+
+            impl std::fmt::Display for [<$person_property KindValue>] {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    match self {
+                        $(Self::$variant => f.write_str(stringify!($variant)),)+
+                    }
+                }
+            }
+
+            impl $value {
+                /// The variant of this value, without its data
+                #[must_use]
+                pub fn kind(&self) -> [<$person_property KindValue>] {
+                    match self {
+                        $(Self::$variant { .. } => [<$person_property KindValue>]::$variant,)+
+                    }
+                }
+            }
+
+            $crate::define_person_property!($person_property, $value $(, $($initialize)+)?);
+
+            $crate::define_derived_property!(
+                [<$person_property Kind>],
+                [<$person_property KindValue>],
+                [$person_property],
+                |value| $value::kind(&value)
+            );
+        }
+    };
+}
+pub use define_enum_person_property;
@@ -1,7 +1,15 @@
+use crate::people::context_extension::sample_matching_person;
 use crate::people::index::IndexValue;
-use crate::{Context, ContextPeopleExt, PersonProperty};
+use crate::people::staged::has_staged_change;
+use crate::{Context, ContextPeopleExt, IxaError, PersonId, PersonProperty, RngId};
+use rand::Rng;
 use seq_macro::seq;
 use std::any::TypeId;
+use std::ops::{RangeBounds, RangeFull};
+
+/// Checks whether a person's value of a property matches a criterion
+/// that isn't a single value.
+pub type ValueFilter<'a> = Box<dyn Fn(&Context, PersonId) -> bool + 'a>;
 
 /// Encapsulates a person query.
 ///
@@ -11,6 +19,15 @@ use std::any::TypeId;
 pub trait Query {
     fn setup(context: &Context);
     fn get_query(&self) -> Vec<(TypeId, IndexValue)>;
+
+    /// Criteria that match a set of values of a property rather than a
+    /// single one, as produced by [`AnyOf`] and [`InRange`]. When the
+    /// property is indexed, each filter is only checked against one person
+    /// from each index entry. Filters keyed by a type that isn't a property
+    /// are checked against each person. Most queries don't have any.
+    fn get_value_filters(&self) -> Vec<(TypeId, ValueFilter<'_>)> {
+        Vec::new()
+    }
 }
 
 impl Query for () {
@@ -32,6 +49,141 @@ impl<T1: PersonProperty + 'static> Query for (T1, T1::Value) {
     }
 }
 
+/// A query matching people whose value of a property is any one of
+/// `values`, e.g.,
+/// `context.query_people(AnyOf(InfectionStatus, vec![Infected, Recovered]))`.
+///
+/// To combine it with other criteria, pair it with a regular query:
+/// `context.query_people((AnyOf(InfectionStatus, vec![Infected, Recovered]), (Age, 30)))`.
+/// An empty list of values matches no one.
+pub struct AnyOf<T: PersonProperty>(pub T, pub Vec<T::Value>);
+
+impl<T: PersonProperty + 'static> Query for AnyOf<T> {
+    fn setup(context: &Context) {
+        context.register_property::<T>();
+    }
+
+    fn get_query(&self) -> Vec<(TypeId, IndexValue)> {
+        vec![]
+    }
+
+    fn get_value_filters(&self) -> Vec<(TypeId, ValueFilter<'_>)> {
+        let hashes: Vec<IndexValue> = self.1.iter().map(IndexValue::compute).collect();
+        let property = self.0;
+        vec![(
+            TypeId::of::<T>(),
+            Box::new(move |context, person_id| {
+                hashes.contains(&IndexValue::compute(
+                    &context.get_person_property(person_id, property),
+                ))
+            }),
+        )]
+    }
+}
+
+/// A query matching people whose value of a property is in a range, e.g.,
+/// `context.query_people(InRange(Age, 65..))`. Any kind of range can be
+/// used, and the property can have any value type that can be compared,
+/// such as integers or `OrderedFloat`.
+///
+/// To combine it with other criteria, pair it with a regular query:
+/// `context.query_people((InRange(Age, 65..), (RiskCategory, RiskCategoryValue::High)))`.
+pub struct InRange<T: PersonProperty, R: RangeBounds<T::Value>>(pub T, pub R);
+
+impl<T: PersonProperty + 'static, R: RangeBounds<T::Value>> Query for InRange<T, R>
+where
+    T::Value: PartialOrd,
+{
+    fn setup(context: &Context) {
+        context.register_property::<T>();
+    }
+
+    fn get_query(&self) -> Vec<(TypeId, IndexValue)> {
+        vec![]
+    }
+
+    fn get_value_filters(&self) -> Vec<(TypeId, ValueFilter<'_>)> {
+        let property = self.0;
+        vec![(
+            TypeId::of::<T>(),
+            Box::new(move |context, person_id| {
+                self.1
+                    .contains(&context.get_person_property(person_id, property))
+            }),
+        )]
+    }
+}
+
+/// A query matching people with a change to a property staged with
+/// [`Context::stage_property_change()`](crate::ContextPeopleExt::stage_property_change)
+/// for a time in a window, e.g., the people who will recover in the next
+/// week:
+/// `context.query_people(HasStagedChange(InfectionStatus, now..now + 7.0))`.
+///
+/// Like [`InRange`], it can be combined with other criteria by pairing it
+/// with a regular query.
+pub struct HasStagedChange<T: PersonProperty, R: RangeBounds<f64>>(pub T, pub R);
+
+impl<T: PersonProperty + 'static, R: RangeBounds<f64>> Query for HasStagedChange<T, R> {
+    fn setup(context: &Context) {
+        context.register_property::<T>();
+    }
+
+    fn get_query(&self) -> Vec<(TypeId, IndexValue)> {
+        vec![]
+    }
+
+    fn get_value_filters(&self) -> Vec<(TypeId, ValueFilter<'_>)> {
+        // This doesn't depend on the value of `T`, so it's keyed by a type
+        // that isn't a property to have it checked against each person.
+        vec![(
+            TypeId::of::<HasStagedChange<T, RangeFull>>(),
+            Box::new(move |context, person_id| has_staged_change::<T>(context, person_id, &self.1)),
+        )]
+    }
+}
+
+// Pairing a filter with another query requires the filter to come first,
+// because a pair of a property and something else is taken to be a
+// (property, value) pair.
+macro_rules! impl_filter_pair {
+    ($filter:ty, [$($param:tt)*], [$($bounds:tt)*]) => {
+        impl<$($param)*, Q: Query> Query for ($filter, Q)
+        where
+            $($bounds)*
+        {
+            fn setup(context: &Context) {
+                <$filter as Query>::setup(context);
+                Q::setup(context);
+            }
+
+            fn get_query(&self) -> Vec<(TypeId, IndexValue)> {
+                let mut query = self.0.get_query();
+                query.extend(self.1.get_query());
+                query
+            }
+
+            fn get_value_filters(&self) -> Vec<(TypeId, ValueFilter<'_>)> {
+                let mut filters = self.0.get_value_filters();
+                filters.extend(self.1.get_value_filters());
+                filters
+            }
+        }
+    };
+}
+
+impl_filter_pair!(AnyOf<T>, [T: PersonProperty + 'static], []);
+impl_filter_pair!(
+    InRange<T, R>,
+    [T: PersonProperty + 'static, R: RangeBounds<T::Value>],
+    [T::Value: PartialOrd]
+);
+impl_filter_pair!(
+    HasStagedChange<T, R>,
+    [T: PersonProperty + 'static, R: RangeBounds<f64>],
+    []
+);
+
 // Implement the versions with 1..20 parameters.
 macro_rules! impl_query {
     ($ct:expr) => {
@@ -68,11 +220,97 @@ seq!(Z in 1..20 {
     impl_query!(Z);
 });
 
+/// The fallback queries of
+/// [`Context::sample_person_or()`](crate::ContextPeopleExt::sample_person_or),
+/// which are tried in order. This is implemented for slices and arrays of
+/// queries of the same type, and tuples of up to 8 queries of different
+/// types. Do not use this trait directly.
+pub trait FallbackQueries {
+    /// Samples a person who matches the first query that anyone matches,
+    /// returning them with the position of that query.
+    ///
+    /// # Errors
+    /// Returns `IxaError` if the population is 0.
+    fn sample_first<R: RngId + 'static>(
+        &self,
+        context: &Context,
+        rng_id: R,
+    ) -> Result<Option<(PersonId, usize)>, IxaError>
+    where
+        R::RngType: Rng;
+}
+
+impl<Q: Query> FallbackQueries for [Q] {
+    fn sample_first<R: RngId + 'static>(
+        &self,
+        context: &Context,
+        rng_id: R,
+    ) -> Result<Option<(PersonId, usize)>, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        for (position, query) in self.iter().enumerate() {
+            if let Some(person_id) = sample_matching_person(context, rng_id, query)? {
+                return Ok(Some((person_id, position)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<Q: Query, const N: usize> FallbackQueries for [Q; N] {
+    fn sample_first<R: RngId + 'static>(
+        &self,
+        context: &Context,
+        rng_id: R,
+    ) -> Result<Option<(PersonId, usize)>, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        self.as_slice().sample_first(context, rng_id)
+    }
+}
+
+macro_rules! impl_fallback_queries {
+    ($ct:expr) => {
+        seq!(N in 0..$ct {
+            impl<#(Q~N: Query,)*> FallbackQueries for (#(Q~N,)*) {
+                fn sample_first<R: RngId + 'static>(
+                    &self,
+                    context: &Context,
+                    rng_id: R,
+                ) -> Result<Option<(PersonId, usize)>, IxaError>
+                where
+                    R::RngType: Rng,
+                {
+                    #(
+                        if let Some(person_id) = sample_matching_person(context, rng_id, &self.N)? {
+                            return Ok(Some((person_id, N)));
+                        }
+                    )*
+                    Ok(None)
+                }
+            }
+        });
+    }
+}
+
+seq!(Z in 1..9 {
+    impl_fallback_queries!(Z);
+});
+
 #[cfg(test)]
 mod tests {
-    use crate::people::PeoplePlugin;
-    use crate::{define_derived_property, define_person_property, Context, ContextPeopleExt};
+    use crate::people::{AnyOf, InRange, PeoplePlugin};
+    use crate::{
+        define_derived_property, define_enum_person_property, define_person_property, Context,
+        ContextPeopleExt, PersonId, PersonPropertyChangeEvent,
+    };
+    use ordered_float::OrderedFloat;
     use std::any::TypeId;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
 
     define_person_property!(Age, u8);
 
@@ -84,6 +322,27 @@ mod tests {
 
     define_person_property!(RiskCategory, RiskCategoryValue);
 
+    define_enum_person_property!(
+        InfectionStatus,
+        #[derive(Eq)]
+        enum InfectionStatusValue {
+            Susceptible,
+            Infected {
+                since: OrderedFloat<f64>,
+                strain: u8,
+            },
+            Recovered(u8),
+        },
+        |_context, _person_id| InfectionStatusValue::Susceptible
+    );
+
+    fn infected(since: f64, strain: u8) -> InfectionStatusValue {
+        InfectionStatusValue::Infected {
+            since: OrderedFloat(since),
+            strain,
+        }
+    }
+
     #[test]
     fn query_people() {
         let mut context = Context::new();
@@ -336,4 +595,357 @@ mod tests {
         assert_eq!(seniors.len(), 2, "Two seniors");
         assert_eq!(not_seniors.len(), 0, "No non-seniors");
     }
+
+    fn check_kind_counts(context: &Context, susceptible: usize, infected: usize, recovered: usize) {
+        for (kind, count) in [
+            (InfectionStatusKindValue::Susceptible, susceptible),
+            (InfectionStatusKindValue::Infected, infected),
+            (InfectionStatusKindValue::Recovered, recovered),
+        ] {
+            assert_eq!(
+                context.query_people_count((InfectionStatusKind, kind)),
+                count,
+                "{kind}"
+            );
+        }
+    }
+
+    fn query_enum_kind(indexed: bool) {
+        let mut context = Context::new();
+        if indexed {
+            context.index_property(InfectionStatusKind);
+        }
+        let people = (0..4)
+            .map(|_| context.add_person(()).unwrap())
+            .collect::<Vec<_>>();
+        check_kind_counts(&context, 4, 0, 0);
+
+        context.set_person_property(people[0], InfectionStatus, infected(1.0, 1));
+        context.set_person_property(people[1], InfectionStatus, infected(2.0, 2));
+        context.set_person_property(
+            people[2],
+            InfectionStatus,
+            InfectionStatusValue::Recovered(1),
+        );
+        check_kind_counts(&context, 1, 2, 1);
+        let mut infected_people =
+            context.query_people((InfectionStatusKind, InfectionStatusKindValue::Infected));
+        infected_people.sort_by_key(|person_id| person_id.0);
+        assert_eq!(infected_people, vec![people[0], people[1]]);
+
+        // Changing only the data keeps the person in the same variant
+        context.set_person_property(people[0], InfectionStatus, infected(3.0, 2));
+        check_kind_counts(&context, 1, 2, 1);
+
+        context.set_person_property(
+            people[1],
+            InfectionStatus,
+            InfectionStatusValue::Recovered(2),
+        );
+        check_kind_counts(&context, 1, 1, 2);
+
+        // Full values can still be queried
+        assert_eq!(
+            context.query_people((InfectionStatus, infected(3.0, 2))),
+            vec![people[0]]
+        );
+        assert!(context
+            .query_people((InfectionStatus, infected(1.0, 1)))
+            .is_empty());
+        assert_eq!(
+            context.query_people_count((InfectionStatus, InfectionStatusValue::Recovered(2))),
+            1
+        );
+    }
+
+    #[test]
+    fn query_enum_kind_unindexed() {
+        query_enum_kind(false);
+    }
+
+    #[test]
+    fn query_enum_kind_indexed() {
+        query_enum_kind(true);
+    }
+
+    #[test]
+    fn enum_kind_change_events() {
+        let mut context = Context::new();
+        let person = context.add_person(()).unwrap();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let events_clone = Rc::clone(&events);
+        context.subscribe_to_event(
+            move |_, event: PersonPropertyChangeEvent<InfectionStatusKind>| {
+                events_clone
+                    .borrow_mut()
+                    .push((event.previous, event.current));
+            },
+        );
+
+        context.set_person_property(person, InfectionStatus, infected(1.0, 1));
+        // A change to only the data still emits an event, with the same
+        // previous and current kinds
+        context.set_person_property(person, InfectionStatus, infected(2.0, 1));
+        context.execute();
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                (
+                    InfectionStatusKindValue::Susceptible,
+                    InfectionStatusKindValue::Infected
+                ),
+                (
+                    InfectionStatusKindValue::Infected,
+                    InfectionStatusKindValue::Infected
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn tabulate_enum_kind() {
+        let mut context = Context::new();
+        for i in 0..6 {
+            let person = context.add_person(()).unwrap();
+            if i % 2 == 0 {
+                context.set_person_property(person, InfectionStatus, infected(f64::from(i), i));
+            }
+        }
+        assert_eq!(
+            context.tabulate_person_count::<(InfectionStatusKind,)>(),
+            HashMap::from([
+                ((InfectionStatusKindValue::Susceptible,), 3),
+                ((InfectionStatusKindValue::Infected,), 3),
+            ])
+        );
+
+        // The kind serializes and displays as the variant name
+        assert_eq!(
+            serde_json::to_string(&InfectionStatusKindValue::Recovered).unwrap(),
+            "\"Recovered\""
+        );
+        assert_eq!(InfectionStatusKindValue::Infected.to_string(), "Infected");
+        assert_eq!(
+            InfectionStatusValue::Recovered(1).kind(),
+            InfectionStatusKindValue::Recovered
+        );
+    }
+
+    define_person_property!(Weight, OrderedFloat<f64>);
+
+    fn query_range_setup(context: &mut Context) {
+        for age in 0..100 {
+            context
+                .add_person(((Age, age), (Weight, OrderedFloat(f64::from(age) / 2.0))))
+                .unwrap();
+        }
+    }
+
+    fn sorted(mut people: Vec<PersonId>) -> Vec<PersonId> {
+        people.sort_by_key(|person_id| person_id.0);
+        people
+    }
+
+    fn check_range_queries(context: &Context) {
+        let adults = context.query_people_range(Age, 18..=65);
+        assert_eq!(adults.len(), 48);
+        assert!(adults
+            .iter()
+            .all(|person| (18..=65).contains(&context.get_person_property(*person, Age))));
+        assert_eq!(context.query_people_range(Age, 65..).len(), 35);
+        assert_eq!(context.query_people_range(Age, ..18).len(), 18);
+        assert_eq!(context.query_people_range(Age, ..).len(), 100);
+
+        // Empty ranges match no one
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 30..=20;
+        assert!(context.query_people_range(Age, empty).is_empty());
+        assert!(context.query_people_range(Age, 30..30).is_empty());
+
+        // A single point is the same as an exact match
+        assert_eq!(
+            sorted(context.query_people_range(Age, 42..=42)),
+            sorted(context.query_people((Age, 42)))
+        );
+    }
+
+    #[test]
+    fn query_people_range() {
+        let mut context = Context::new();
+        query_range_setup(&mut context);
+        check_range_queries(&context);
+    }
+
+    #[test]
+    fn query_people_range_indexed() {
+        let mut context = Context::new();
+        context.index_property(Age);
+        query_range_setup(&mut context);
+        check_range_queries(&context);
+
+        // People added after the index was built are included
+        context
+            .add_person(((Age, 20), (Weight, OrderedFloat(80.0))))
+            .unwrap();
+        assert_eq!(context.query_people_range(Age, 18..=65).len(), 49);
+    }
+
+    #[test]
+    fn query_people_range_float() {
+        let mut context = Context::new();
+        query_range_setup(&mut context);
+        let (low, high) = (OrderedFloat(10.0), OrderedFloat(20.0));
+        assert_eq!(context.query_people_range(Weight, low..high).len(), 20);
+        assert_eq!(context.query_people_range(Weight, low..=high).len(), 21);
+        assert!(context
+            .query_people_range(Weight, OrderedFloat(100.0)..)
+            .is_empty());
+    }
+
+    #[test]
+    fn query_people_range_derived() {
+        define_derived_property!(Decade, u8, [Age], |age| age / 10);
+        let mut context = Context::new();
+        query_range_setup(&mut context);
+        assert_eq!(context.query_people_range(Decade, 2..=3).len(), 20);
+    }
+
+    #[test]
+    fn query_people_range_empty_population() {
+        let context = Context::new();
+        assert!(context.query_people_range(Age, 0..=10).is_empty());
+    }
+
+    fn check_any_value_queries(context: &Context) {
+        let some_ages = context.query_people_any_value(Age, &[10, 20, 30]);
+        assert_eq!(some_ages.len(), 3);
+        assert!(some_ages
+            .iter()
+            .all(|person| [10, 20, 30].contains(&context.get_person_property(*person, Age))));
+
+        // Duplicate and absent values don't change the result
+        assert_eq!(
+            sorted(context.query_people_any_value(Age, &[10, 20, 20, 30, 200])),
+            sorted(some_ages)
+        );
+        assert!(context.query_people_any_value(Age, &[]).is_empty());
+        assert!(context.query_people_any_value(Age, &[200]).is_empty());
+
+        // A single value is the same as an exact match
+        assert_eq!(
+            context.query_people_any_value(Age, &[42]),
+            context.query_people((Age, 42))
+        );
+
+        // The query form combines with other criteria
+        let query = (AnyOf(Age, vec![10, 20, 30]), (Weight, OrderedFloat(10.0)));
+        assert_eq!(context.query_people(query), vec![PersonId(20)]);
+        assert_eq!(
+            context.query_people_count((
+                AnyOf(Age, vec![10, 20, 30, 40]),
+                ((Weight, OrderedFloat(10.0)), (Age, 20))
+            )),
+            1
+        );
+        assert!(context.match_person(PersonId(30), AnyOf(Age, vec![10, 30])));
+        assert!(!context.match_person(PersonId(30), AnyOf(Age, vec![10, 20])));
+        assert!(!context.match_person(
+            PersonId(30),
+            (AnyOf(Age, vec![10, 30]), (Weight, OrderedFloat(10.0)))
+        ));
+    }
+
+    #[test]
+    fn query_people_any_value() {
+        let mut context = Context::new();
+        query_range_setup(&mut context);
+        check_any_value_queries(&context);
+    }
+
+    #[test]
+    fn query_people_any_value_indexed() {
+        let mut context = Context::new();
+        context.index_property(Age);
+        context.index_property(Weight);
+        query_range_setup(&mut context);
+        check_any_value_queries(&context);
+
+        // People added after the index was built are included
+        context
+            .add_person(((Age, 20), (Weight, OrderedFloat(80.0))))
+            .unwrap();
+        assert_eq!(context.query_people_any_value(Age, &[10, 20]).len(), 3);
+    }
+
+    #[test]
+    fn query_people_any_value_multiple() {
+        let mut context = Context::new();
+        context.index_property(RiskCategory);
+        for age in 0..10 {
+            let risk = if age < 5 {
+                RiskCategoryValue::High
+            } else {
+                RiskCategoryValue::Low
+            };
+            context
+                .add_person(((Age, age), (RiskCategory, risk)))
+                .unwrap();
+        }
+        // Two "any of" criteria, one indexed and one not
+        let query = (
+            AnyOf(RiskCategory, vec![RiskCategoryValue::High]),
+            AnyOf(Age, vec![3, 4, 5, 6]),
+        );
+        assert_eq!(
+            sorted(context.query_people(query)),
+            vec![PersonId(3), PersonId(4)]
+        );
+    }
+
+    fn check_in_range_queries(context: &mut Context) {
+        for age in 50..80 {
+            let risk = if age % 2 == 0 {
+                RiskCategoryValue::High
+            } else {
+                RiskCategoryValue::Low
+            };
+            context
+                .add_person(((Age, age), (RiskCategory, risk)))
+                .unwrap();
+        }
+        let query = (InRange(Age, 65..), (RiskCategory, RiskCategoryValue::High));
+        assert_eq!(context.query_people(query).len(), 7);
+        assert_eq!(context.query_people_count(InRange(Age, 65..)), 15);
+        assert!(context.match_person(PersonId(20), InRange(Age, 65..=70)));
+        assert!(!context.match_person(PersonId(20), InRange(Age, ..65)));
+
+        // Changing a value moves people in and out of the range
+        context.set_person_property(PersonId(20), Age, 40);
+        context.set_person_property(PersonId(0), Age, 66);
+        let mut seniors =
+            context.query_people((InRange(Age, 65..), (RiskCategory, RiskCategoryValue::High)));
+        seniors.sort_by_key(|person_id| person_id.0);
+        assert_eq!(seniors.len(), 7);
+        assert_eq!(seniors[0], PersonId(0));
+        assert!(!seniors.contains(&PersonId(20)));
+
+        // Ranges and lists of values combine
+        assert_eq!(
+            sorted(context.query_people((InRange(Age, 60..70), AnyOf(Age, vec![40, 61, 66])))),
+            vec![PersonId(0), PersonId(11), PersonId(16)]
+        );
+    }
+
+    #[test]
+    fn query_people_in_range() {
+        let mut context = Context::new();
+        check_in_range_queries(&mut context);
+    }
+
+    #[test]
+    fn query_people_in_range_indexed() {
+        let mut context = Context::new();
+        context.index_property(Age);
+        check_in_range_queries(&mut context);
+    }
 }
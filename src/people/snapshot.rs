@@ -0,0 +1,449 @@
+use crate::external_api::ids::encode_person_id;
+use crate::people::PeoplePlugin;
+use crate::report::{ContextReportExt, Report};
+use crate::{create_report_trait, Context, ContextPeopleExt, PersonId, PersonProperty};
+use serde::Serialize;
+use std::any::TypeId;
+
+type ColumnCapture = dyn Fn(&Context, usize) -> Box<dyn SnapshotColumn>;
+
+/// A property to record in a [`PropertySnapshot`] with
+/// [`Context::snapshot_properties()`].
+pub struct SnapshotSpec {
+    capture: Box<ColumnCapture>,
+}
+
+impl SnapshotSpec {
+    /// Record a copy of each person's value, so diffs include the previous
+    /// value of people who changed.
+    #[must_use]
+    pub fn values<T: PersonProperty + 'static>(property: T) -> Self {
+        SnapshotSpec {
+            capture: Box::new(move |context, people_created| {
+                let values = (0..people_created)
+                    .map(|id| {
+                        let person_id = PersonId(id);
+                        context
+                            .person_exists(person_id)
+                            .then(|| context.get_person_property(person_id, property))
+                    })
+                    .collect();
+                Box::new(ValueColumn::<T> { values })
+            }),
+        }
+    }
+
+    /// Record only a 64-bit hash of each person's value. This uses less
+    /// memory for large values, but diffs don't include the previous value,
+    /// and a change that happens to produce the same hash is missed.
+    #[must_use]
+    pub fn hashes<T: PersonProperty + 'static>(property: T) -> Self {
+        SnapshotSpec {
+            capture: Box::new(move |context, people_created| {
+                let hashes = (0..people_created)
+                    .map(|id| {
+                        let person_id = PersonId(id);
+                        context.person_exists(person_id).then(|| {
+                            fxhash::hash64(&context.get_person_property(person_id, property))
+                        })
+                    })
+                    .collect();
+                Box::new(HashColumn::<T> { hashes, property })
+            }),
+        }
+    }
+}
+
+// The recorded values of one property, which can be compared with the
+// current values.
+trait SnapshotColumn {
+    fn diff(&self, context: &Context) -> PropertyChanges;
+}
+
+// Values and hashes are `None` for people who had been removed.
+struct ValueColumn<T: PersonProperty> {
+    values: Vec<Option<T::Value>>,
+}
+
+impl<T: PersonProperty + 'static> SnapshotColumn for ValueColumn<T> {
+    fn diff(&self, context: &Context) -> PropertyChanges {
+        let mut changes = Vec::new();
+        for (id, previous) in self.values.iter().enumerate() {
+            let person_id = PersonId(id);
+            let Some(previous) = previous else {
+                continue;
+            };
+            if !context.person_exists(person_id) {
+                continue;
+            }
+            let current = context.get_person_property(person_id, T::get_instance());
+            if current != *previous {
+                changes.push(PropertyChange {
+                    person_id,
+                    previous: Some(format!("{previous:?}")),
+                    current: format!("{current:?}"),
+                });
+            }
+        }
+        PropertyChanges::new::<T>(changes)
+    }
+}
+
+struct HashColumn<T: PersonProperty> {
+    hashes: Vec<Option<u64>>,
+    property: T,
+}
+
+impl<T: PersonProperty + 'static> SnapshotColumn for HashColumn<T> {
+    fn diff(&self, context: &Context) -> PropertyChanges {
+        let mut changes = Vec::new();
+        for (id, previous) in self.hashes.iter().enumerate() {
+            let person_id = PersonId(id);
+            let Some(previous) = previous else {
+                continue;
+            };
+            if !context.person_exists(person_id) {
+                continue;
+            }
+            let current = context.get_person_property(person_id, self.property);
+            if fxhash::hash64(&current) != *previous {
+                changes.push(PropertyChange {
+                    person_id,
+                    previous: None,
+                    current: format!("{current:?}"),
+                });
+            }
+        }
+        PropertyChanges::new::<T>(changes)
+    }
+}
+
+/// The values of selected person properties at one point in a simulation,
+/// created by [`Context::snapshot_properties()`].
+pub struct PropertySnapshot {
+    time: f64,
+    // People with lower ids were created before the snapshot
+    people_created: usize,
+    columns: Vec<Box<dyn SnapshotColumn>>,
+}
+
+impl PropertySnapshot {
+    /// The simulation time when the snapshot was taken
+    #[must_use]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+/// A person whose property value differs from a snapshot
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyChange {
+    pub person_id: PersonId,
+    /// The value when the snapshot was taken, formatted with `Debug`, or
+    /// `None` if the snapshot only recorded hashes
+    pub previous: Option<String>,
+    /// The current value, formatted with `Debug`
+    pub current: String,
+}
+
+struct PropertyChanges {
+    type_id: TypeId,
+    name: &'static str,
+    changes: Vec<PropertyChange>,
+}
+
+impl PropertyChanges {
+    fn new<T: PersonProperty + 'static>(changes: Vec<PropertyChange>) -> Self {
+        PropertyChanges {
+            type_id: TypeId::of::<T>(),
+            name: T::name(),
+            changes,
+        }
+    }
+}
+
+/// The differences between a [`PropertySnapshot`] and the current state,
+/// created by [`Context::diff_against()`].
+pub struct PropertyDiff {
+    snapshot_time: f64,
+    time: f64,
+    created: Vec<PersonId>,
+    properties: Vec<PropertyChanges>,
+}
+
+impl PropertyDiff {
+    /// The people who were added after the snapshot was taken. Their values
+    /// are not compared.
+    #[must_use]
+    pub fn created_people(&self) -> &[PersonId] {
+        &self.created
+    }
+
+    /// The people whose value of `property` changed since the snapshot, in
+    /// order of id, or `None` if the snapshot didn't record `property`
+    #[must_use]
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn changes<T: PersonProperty + 'static>(&self, _property: T) -> Option<&[PropertyChange]> {
+        self.properties
+            .iter()
+            .find(|changes| changes.type_id == TypeId::of::<T>())
+            .map(|changes| changes.changes.as_slice())
+    }
+
+    /// Whether no properties changed and no people were added
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self
+                .properties
+                .iter()
+                .all(|changes| changes.changes.is_empty())
+    }
+
+    /// Write a row to the [`PropertyDiffReport`] for each change and each
+    /// created person. The report must already have been added with
+    /// [`Context::add_report()`].
+    pub fn send_reports(&self, context: &mut Context) {
+        for person_id in &self.created {
+            context.send_report(PropertyDiffReport {
+                snapshot_time: self.snapshot_time,
+                time: self.time,
+                person_id: encode_person_id(*person_id),
+                change: "created",
+                property: "",
+                previous: String::new(),
+                current: String::new(),
+            });
+        }
+        for changes in &self.properties {
+            for change in &changes.changes {
+                context.send_report(PropertyDiffReport {
+                    snapshot_time: self.snapshot_time,
+                    time: self.time,
+                    person_id: encode_person_id(change.person_id),
+                    change: "changed",
+                    property: changes.name,
+                    previous: change.previous.clone().unwrap_or_default(),
+                    current: change.current.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// A row of a [`PropertyDiff`] written by [`PropertyDiff::send_reports()`].
+/// `previous` is empty for people who were created and for properties that
+/// were only hashed. The person is written as a tagged id, e.g., `person:4`
+/// (see [`crate::external_api::ids`]).
+#[derive(Serialize)]
+pub struct PropertyDiffReport {
+    snapshot_time: f64,
+    time: f64,
+    person_id: String,
+    change: &'static str,
+    property: &'static str,
+    previous: String,
+    current: String,
+}
+
+create_report_trait!(PropertyDiffReport);
+
+fn people_created(context: &Context) -> usize {
+    context
+        .get_data_container(PeoplePlugin)
+        .map_or(0, |data_container| data_container.people_created)
+}
+
+pub(super) fn take_snapshot(context: &Context, specs: &[SnapshotSpec]) -> PropertySnapshot {
+    let people_created = people_created(context);
+    PropertySnapshot {
+        time: context.get_current_time(),
+        people_created,
+        columns: specs
+            .iter()
+            .map(|spec| (spec.capture)(context, people_created))
+            .collect(),
+    }
+}
+
+pub(super) fn diff_snapshot(context: &Context, snapshot: &PropertySnapshot) -> PropertyDiff {
+    PropertyDiff {
+        snapshot_time: snapshot.time,
+        time: context.get_current_time(),
+        created: (snapshot.people_created..people_created(context))
+            .map(PersonId)
+            .filter(|person_id| context.person_exists(*person_id))
+            .collect(),
+        properties: snapshot
+            .columns
+            .iter()
+            .map(|column| column.diff(context))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::{PropertyChange, PropertyDiffReport, SnapshotSpec};
+    use crate::{
+        define_person_property, define_person_property_with_default, Context, ContextPeopleExt,
+        ContextReportExt, PersonId,
+    };
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub enum InfectionStatusValue {
+        Susceptible,
+        Infected,
+        Recovered,
+    }
+    define_person_property_with_default!(
+        InfectionStatus,
+        InfectionStatusValue,
+        InfectionStatusValue::Susceptible
+    );
+    define_person_property!(Age, u8);
+
+    fn setup() -> (Context, Vec<PersonId>) {
+        let mut context = Context::new();
+        let people = (0..4)
+            .map(|age| context.add_person((Age, age)).unwrap())
+            .collect();
+        (context, people)
+    }
+
+    #[test]
+    fn diff_after_scripted_changes() {
+        let (mut context, people) = setup();
+        let snapshot = context.snapshot_properties(&[
+            SnapshotSpec::values(InfectionStatus),
+            SnapshotSpec::values(Age),
+        ]);
+
+        context.set_person_property(people[1], InfectionStatus, InfectionStatusValue::Infected);
+        context.set_person_property(people[3], InfectionStatus, InfectionStatusValue::Infected);
+        context.set_person_property(people[3], InfectionStatus, InfectionStatusValue::Recovered);
+        // Changing a value and then changing it back isn't a difference
+        context.set_person_property(people[2], InfectionStatus, InfectionStatusValue::Infected);
+        context.set_person_property(
+            people[2],
+            InfectionStatus,
+            InfectionStatusValue::Susceptible,
+        );
+        let new_person = context.add_person((Age, 30)).unwrap();
+
+        let diff = context.diff_against(&snapshot);
+        assert_eq!(
+            diff.changes(InfectionStatus).unwrap(),
+            &[
+                PropertyChange {
+                    person_id: people[1],
+                    previous: Some("Susceptible".to_string()),
+                    current: "Infected".to_string(),
+                },
+                PropertyChange {
+                    person_id: people[3],
+                    previous: Some("Susceptible".to_string()),
+                    current: "Recovered".to_string(),
+                },
+            ]
+        );
+        assert_eq!(diff.created_people(), &[new_person]);
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn hashed_snapshot_detects_changes() {
+        let (mut context, people) = setup();
+        let snapshot = context.snapshot_properties(&[SnapshotSpec::hashes(Age)]);
+        context.set_person_property(people[0], Age, 10);
+
+        let diff = context.diff_against(&snapshot);
+        assert_eq!(
+            diff.changes(Age).unwrap(),
+            &[PropertyChange {
+                person_id: people[0],
+                previous: None,
+                current: "10".to_string(),
+            }]
+        );
+        assert!(diff.changes(InfectionStatus).is_none());
+    }
+
+    #[test]
+    fn untouched_properties_have_no_changes() {
+        let (mut context, people) = setup();
+        let snapshot = context.snapshot_properties(&[
+            SnapshotSpec::values(Age),
+            SnapshotSpec::hashes(InfectionStatus),
+        ]);
+        context.set_person_property(people[0], InfectionStatus, InfectionStatusValue::Infected);
+
+        let diff = context.diff_against(&snapshot);
+        assert_eq!(diff.changes(Age).unwrap(), &[]);
+        assert_eq!(diff.changes(InfectionStatus).unwrap().len(), 1);
+
+        let unchanged = context.diff_against(&context.snapshot_properties(&[
+            SnapshotSpec::values(Age),
+            SnapshotSpec::hashes(InfectionStatus),
+        ]));
+        assert!(unchanged.is_empty());
+    }
+
+    #[test]
+    fn diff_report() {
+        let dir = tempdir().unwrap();
+        let (mut context, people) = setup();
+        context.report_options().directory(dir.path().to_path_buf());
+        context
+            .add_report::<PropertyDiffReport>("property_diff")
+            .unwrap();
+
+        let snapshot = context.snapshot_properties(&[
+            SnapshotSpec::values(InfectionStatus),
+            SnapshotSpec::hashes(Age),
+        ]);
+        context.add_plan(30.0, move |context| {
+            context.set_person_property(people[2], InfectionStatus, InfectionStatusValue::Infected);
+            context.set_person_property(people[2], Age, 5);
+            context.add_person((Age, 1)).unwrap();
+        });
+        context.run_until(60.0);
+        context.diff_against(&snapshot).send_reports(&mut context);
+        drop(context);
+
+        let contents = fs::read_to_string(dir.path().join("property_diff.csv")).unwrap();
+        assert_eq!(
+            contents,
+            "snapshot_time,time,person_id,change,property,previous,current\n\
+             0.0,60.0,person:4,created,,,\n\
+             0.0,60.0,person:2,changed,InfectionStatus,Susceptible,Infected\n\
+             0.0,60.0,person:2,changed,Age,,5\n"
+        );
+    }
+
+    #[test]
+    fn diff_skips_removed_people() {
+        let (mut context, people) = setup();
+        context.remove_person(people[0]).unwrap();
+        let snapshot = context.snapshot_properties(&[
+            SnapshotSpec::values(Age),
+            SnapshotSpec::hashes(InfectionStatus),
+        ]);
+
+        context.set_person_property(people[1], Age, 10);
+        context.set_person_property(people[2], InfectionStatus, InfectionStatusValue::Infected);
+        context.remove_person(people[1]).unwrap();
+        context.remove_person(people[2]).unwrap();
+        let added = context.add_person((Age, 5)).unwrap();
+        let removed_added = context.add_person((Age, 6)).unwrap();
+        context.remove_person(removed_added).unwrap();
+
+        let diff = context.diff_against(&snapshot);
+        assert_eq!(diff.changes(Age).unwrap(), &[]);
+        assert_eq!(diff.changes(InfectionStatus).unwrap(), &[]);
+        assert_eq!(diff.created_people(), &[added]);
+    }
+}
@@ -0,0 +1,522 @@
+use crate::plan::PlanId;
+use crate::{
+    define_data_plugin, Context, ContextPeopleExt, ExecutionPhase, PersonId, PersonProperty,
+};
+use ordered_float::OrderedFloat;
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeBounds;
+
+/// What happens to a change staged with
+/// [`Context::stage_property_change_with_policy()`](crate::ContextPeopleExt::stage_property_change_with_policy)
+/// if the property is set directly before the change is applied.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StagedChangePolicy {
+    /// Cancel the staged change, e.g., so that a person who is hospitalized
+    /// before they were due to recover doesn't recover anyway. This is the
+    /// default.
+    #[default]
+    CancelOnSet,
+    /// Keep the staged change, which overwrites the value that was set.
+    KeepOnSet,
+}
+
+struct StagedChange<V> {
+    time: OrderedFloat<f64>,
+    value: V,
+    policy: StagedChangePolicy,
+}
+
+// The people with changes staged for a time, and how many of those
+// changes haven't been cancelled or replaced
+#[derive(Default)]
+struct Bucket {
+    live: usize,
+    people: Vec<PersonId>,
+}
+
+// The changes to a property staged for each person, with the people
+// grouped by the time of their change.
+struct Schedule<T: PersonProperty> {
+    changes: HashMap<PersonId, StagedChange<T::Value>>,
+    // People aren't removed from a bucket when their change is cancelled
+    // or replaced, so each one is checked against `changes` when its time
+    // comes. Buckets are removed when they have no live changes left.
+    buckets: BTreeMap<OrderedFloat<f64>, Bucket>,
+    // The plan that applies the changes at the earliest time, if any
+    next_plan: Option<(OrderedFloat<f64>, PlanId)>,
+}
+
+impl<T: PersonProperty + 'static> Schedule<T> {
+    fn new() -> Self {
+        Schedule {
+            changes: HashMap::new(),
+            buckets: BTreeMap::new(),
+            next_plan: None,
+        }
+    }
+
+    // Forget `person_id`'s change, returning it along with the plan to
+    // cancel if it was the last one at the earliest time.
+    fn remove(&mut self, person_id: PersonId) -> Option<(StagedChange<T::Value>, Option<PlanId>)> {
+        let change = self.changes.remove(&person_id)?;
+        let bucket = self.buckets.get_mut(&change.time).unwrap();
+        bucket.live -= 1;
+        let mut plan_id = None;
+        if bucket.live == 0 {
+            self.buckets.remove(&change.time);
+            if self
+                .next_plan
+                .is_some_and(|(next_time, _)| next_time == change.time)
+            {
+                plan_id = self.next_plan.take().map(|(_, plan_id)| plan_id);
+            }
+        }
+        Some((change, plan_id))
+    }
+}
+
+type Rescheduler = fn(&mut Context);
+
+trait ErasedSchedule {
+    // Forget `person_id`'s change, returning the plan to cancel if it was
+    // the last one at the earliest time
+    fn remove_person(&mut self, person_id: PersonId) -> Option<PlanId>;
+
+    // Adds a plan for the earliest time with changes after the last one
+    // is cancelled
+    fn schedule_next(&self) -> Rescheduler;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: PersonProperty + 'static> ErasedSchedule for Schedule<T> {
+    fn remove_person(&mut self, person_id: PersonId) -> Option<PlanId> {
+        self.remove(person_id).and_then(|(_, plan_id)| plan_id)
+    }
+
+    fn schedule_next(&self) -> Rescheduler {
+        schedule_next::<T>
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Default)]
+struct StagedChangesData {
+    schedules: HashMap<TypeId, Box<dyn ErasedSchedule>>,
+}
+
+define_data_plugin!(
+    StagedChangesPlugin,
+    StagedChangesData,
+    StagedChangesData::default()
+);
+
+fn get_schedule<T: PersonProperty + 'static>(context: &Context) -> Option<&Schedule<T>> {
+    context
+        .get_data_container(StagedChangesPlugin)?
+        .schedules
+        .get(&TypeId::of::<T>())
+        .map(|schedule| schedule.as_any().downcast_ref::<Schedule<T>>().unwrap())
+}
+
+fn get_schedule_mut<T: PersonProperty + 'static>(context: &mut Context) -> &mut Schedule<T> {
+    context
+        .get_data_container_mut(StagedChangesPlugin)
+        .schedules
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| Box::new(Schedule::<T>::new()))
+        .as_any_mut()
+        .downcast_mut::<Schedule<T>>()
+        .unwrap()
+}
+
+pub(super) fn stage_property_change<T: PersonProperty + 'static>(
+    context: &mut Context,
+    person_id: PersonId,
+    value: T::Value,
+    time: f64,
+    policy: StagedChangePolicy,
+) {
+    assert!(!T::is_derived(), "Cannot set a derived property");
+    assert!(
+        !time.is_nan() && !time.is_infinite() && time >= context.get_current_time(),
+        "Time is invalid"
+    );
+    assert!(
+        context.person_exists(person_id),
+        "Can't stage a change to {} of {person_id:?}, who doesn't exist",
+        T::name()
+    );
+
+    cancel_staged_change::<T>(context, person_id);
+    let schedule = get_schedule_mut::<T>(context);
+    schedule.changes.insert(
+        person_id,
+        StagedChange {
+            time: OrderedFloat(time),
+            value,
+            policy,
+        },
+    );
+    let bucket = schedule.buckets.entry(OrderedFloat(time)).or_default();
+    bucket.live += 1;
+    bucket.people.push(person_id);
+    if schedule
+        .next_plan
+        .is_some_and(|(next_time, _)| next_time.0 <= time)
+    {
+        return;
+    }
+    if let Some((_, plan_id)) = schedule.next_plan.take() {
+        context.cancel_plan(&plan_id);
+    }
+    schedule_next::<T>(context);
+}
+
+// Adds a plan for the earliest time with staged changes, if there is one.
+// Changes are applied in the first phase, so that they come before other
+// plans at the same time regardless of when they were staged.
+fn schedule_next<T: PersonProperty + 'static>(context: &mut Context) {
+    let Some(&time) = get_schedule_mut::<T>(context).buckets.keys().next() else {
+        return;
+    };
+    let plan_id = context.add_plan_with_phase(
+        time.0,
+        move |context| apply_staged_changes::<T>(context, time),
+        ExecutionPhase::First,
+    );
+    get_schedule_mut::<T>(context).next_plan = Some((time, plan_id));
+}
+
+fn apply_staged_changes<T: PersonProperty + 'static>(
+    context: &mut Context,
+    time: OrderedFloat<f64>,
+) {
+    let schedule = get_schedule_mut::<T>(context);
+    schedule.next_plan = None;
+    let bucket = schedule.buckets.remove(&time).unwrap_or_default();
+    let mut changes = Vec::with_capacity(bucket.live);
+    for person_id in bucket.people {
+        // Skip people whose change was cancelled or moved to another time.
+        if schedule
+            .changes
+            .get(&person_id)
+            .is_some_and(|change| change.time == time)
+        {
+            let change = schedule.changes.remove(&person_id).unwrap();
+            changes.push((person_id, change.value));
+        }
+    }
+    for (person_id, value) in changes {
+        context.set_person_property(person_id, T::get_instance(), value);
+    }
+    schedule_next::<T>(context);
+}
+
+pub(super) fn get_staged_change<T: PersonProperty + 'static>(
+    context: &Context,
+    person_id: PersonId,
+) -> Option<(f64, T::Value)> {
+    let change = get_schedule::<T>(context)?.changes.get(&person_id)?;
+    Some((change.time.0, change.value))
+}
+
+pub(super) fn cancel_staged_change<T: PersonProperty + 'static>(
+    context: &mut Context,
+    person_id: PersonId,
+) -> Option<(f64, T::Value)> {
+    let schedule = get_schedule_mut::<T>(context);
+    let (change, plan_id) = schedule.remove(person_id)?;
+    if let Some(plan_id) = plan_id {
+        context.cancel_plan(&plan_id);
+        schedule_next::<T>(context);
+    }
+    Some((change.time.0, change.value))
+}
+
+pub(crate) fn has_staged_change<T: PersonProperty + 'static>(
+    context: &Context,
+    person_id: PersonId,
+    window: &impl RangeBounds<f64>,
+) -> bool {
+    get_staged_change::<T>(context, person_id).is_some_and(|(time, _)| window.contains(&time))
+}
+
+// Whether any changes to `T` are staged.
+pub(super) fn has_staged_changes<T: PersonProperty + 'static>(context: &Context) -> bool {
+    get_schedule::<T>(context).is_some_and(|schedule| !schedule.changes.is_empty())
+}
+
+// Called before `T` is set directly for `person_id`.
+pub(super) fn property_set<T: PersonProperty + 'static>(
+    context: &mut Context,
+    person_id: PersonId,
+) {
+    let cancel = get_schedule::<T>(context)
+        .and_then(|schedule| schedule.changes.get(&person_id))
+        .is_some_and(|change| change.policy == StagedChangePolicy::CancelOnSet);
+    if cancel {
+        cancel_staged_change::<T>(context, person_id);
+    }
+}
+
+// Called when `person_id` is removed from the population.
+pub(super) fn person_removed(context: &mut Context, person_id: PersonId) {
+    let Some(data_container) = context.get_data_container(StagedChangesPlugin) else {
+        return;
+    };
+    if data_container.schedules.is_empty() {
+        return;
+    }
+    let to_reschedule: Vec<(PlanId, Rescheduler)> = context
+        .get_data_container_mut(StagedChangesPlugin)
+        .schedules
+        .values_mut()
+        .filter_map(|schedule| {
+            let plan_id = schedule.remove_person(person_id)?;
+            Some((plan_id, schedule.schedule_next()))
+        })
+        .collect();
+    for (plan_id, schedule_next) in to_reschedule {
+        context.cancel_plan(&plan_id);
+        schedule_next(context);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::StagedChangePolicy;
+    use crate::people::{HasStagedChange, PersonPropertyChangeEvent};
+    use crate::{define_person_property, define_person_property_with_default};
+    use crate::{Context, ContextPeopleExt, PersonId};
+    use serde::{Deserialize, Serialize};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub enum Status {
+        Infected,
+        Recovered,
+        Hospitalized,
+    }
+
+    define_person_property_with_default!(StagedStatus, Status, Status::Infected);
+    define_person_property!(StagedAge, u8);
+
+    fn add_people(context: &mut Context, count: u8) -> Vec<PersonId> {
+        (0..count)
+            .map(|age| context.add_person((StagedAge, age)).unwrap())
+            .collect()
+    }
+
+    fn record_changes(context: &mut Context) -> Rc<RefCell<Vec<(f64, PersonId, Status)>>> {
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let changes_clone = Rc::clone(&changes);
+        context.subscribe_to_event(
+            move |context, event: PersonPropertyChangeEvent<StagedStatus>| {
+                assert_eq!(event.previous, Status::Infected);
+                changes_clone.borrow_mut().push((
+                    context.get_current_time(),
+                    event.person_id,
+                    event.current,
+                ));
+            },
+        );
+        changes
+    }
+
+    #[test]
+    fn applies_at_time_with_events() {
+        let mut context = Context::new();
+        let people = add_people(&mut context, 3);
+        let changes = record_changes(&mut context);
+        context.stage_property_change(people[0], StagedStatus, Status::Recovered, 5.0);
+        context.stage_property_change(people[1], StagedStatus, Status::Recovered, 2.0);
+        context.stage_property_change(people[2], StagedStatus, Status::Recovered, 5.0);
+        // Only one plan is pending for all of them.
+        assert_eq!(context.get_pending_plan_count(), 1);
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        for time in [1.0, 2.0, 4.0, 5.0] {
+            let seen = Rc::clone(&seen);
+            context.add_plan(time, move |context| {
+                seen.borrow_mut()
+                    .push(context.query_people_count((StagedStatus, Status::Recovered)));
+            });
+        }
+        context.execute();
+
+        assert_eq!(
+            *changes.borrow(),
+            [
+                (2.0, people[1], Status::Recovered),
+                (5.0, people[0], Status::Recovered),
+                (5.0, people[2], Status::Recovered),
+            ]
+        );
+        // The changes are applied before other plans at the same time.
+        assert_eq!(*seen.borrow(), [0, 1, 1, 3]);
+        assert_eq!(context.get_current_time(), 5.0);
+        assert_eq!(context.get_staged_change(people[0], StagedStatus), None);
+    }
+
+    #[test]
+    fn restaging_replaces_change() {
+        let mut context = Context::new();
+        let people = add_people(&mut context, 1);
+        let changes = record_changes(&mut context);
+        context.stage_property_change(people[0], StagedStatus, Status::Recovered, 5.0);
+        context.stage_property_change(people[0], StagedStatus, Status::Hospitalized, 3.0);
+        assert_eq!(
+            context.get_staged_change(people[0], StagedStatus),
+            Some((3.0, Status::Hospitalized))
+        );
+        context.execute();
+
+        assert_eq!(*changes.borrow(), [(3.0, people[0], Status::Hospitalized)]);
+        // Nothing was left to run at the original time.
+        assert_eq!(context.get_current_time(), 3.0);
+    }
+
+    #[test]
+    fn cancel() {
+        let mut context = Context::new();
+        let people = add_people(&mut context, 2);
+        let changes = record_changes(&mut context);
+        context.stage_property_change(people[0], StagedStatus, Status::Recovered, 5.0);
+        context.stage_property_change(people[1], StagedStatus, Status::Recovered, 2.0);
+
+        assert_eq!(
+            context.cancel_staged_change(people[1], StagedStatus),
+            Some((2.0, Status::Recovered))
+        );
+        assert_eq!(context.cancel_staged_change(people[1], StagedStatus), None);
+        context.execute();
+        assert_eq!(*changes.borrow(), [(5.0, people[0], Status::Recovered)]);
+
+        // Cancelling the last change doesn't leave a plan behind.
+        context.stage_property_change(people[1], StagedStatus, Status::Recovered, 10.0);
+        context.cancel_staged_change(people[1], StagedStatus);
+        assert_eq!(context.get_pending_plan_count(), 0);
+        context.execute();
+        assert_eq!(context.get_current_time(), 5.0);
+    }
+
+    #[test]
+    fn direct_set_cancels_by_default() {
+        let mut context = Context::new();
+        let people = add_people(&mut context, 1);
+        context.stage_property_change(people[0], StagedStatus, Status::Recovered, 5.0);
+        let person_id = people[0];
+        context.add_plan(1.0, move |context| {
+            context.set_person_property(person_id, StagedStatus, Status::Hospitalized);
+        });
+        context.execute();
+
+        assert_eq!(
+            context.get_person_property(people[0], StagedStatus),
+            Status::Hospitalized
+        );
+        assert_eq!(context.get_current_time(), 1.0);
+    }
+
+    #[test]
+    fn direct_set_keeps_change_if_asked() {
+        let mut context = Context::new();
+        let people = add_people(&mut context, 1);
+        context.stage_property_change_with_policy(
+            people[0],
+            StagedStatus,
+            Status::Recovered,
+            5.0,
+            StagedChangePolicy::KeepOnSet,
+        );
+        let person_id = people[0];
+        context.add_plan(1.0, move |context| {
+            context.set_person_property(person_id, StagedStatus, Status::Hospitalized);
+        });
+        context.execute();
+
+        assert_eq!(
+            context.get_person_property(people[0], StagedStatus),
+            Status::Recovered
+        );
+        assert_eq!(context.get_current_time(), 5.0);
+    }
+
+    #[test]
+    fn removal_cancels() {
+        let mut context = Context::new();
+        let people = add_people(&mut context, 2);
+        let changes = record_changes(&mut context);
+        context.stage_property_change(people[0], StagedStatus, Status::Recovered, 5.0);
+        context.stage_property_change(people[1], StagedStatus, Status::Recovered, 5.0);
+        context.remove_person(people[0]).unwrap();
+        assert_eq!(context.get_staged_change(people[0], StagedStatus), None);
+        context.execute();
+
+        assert_eq!(*changes.borrow(), [(5.0, people[1], Status::Recovered)]);
+    }
+
+    #[test]
+    fn query_by_window() {
+        let mut context = Context::new();
+        let people = add_people(&mut context, 4);
+        context.index_property(StagedAge);
+        for (i, &person_id) in people.iter().enumerate() {
+            context.stage_property_change(
+                person_id,
+                StagedStatus,
+                Status::Recovered,
+                f64::from(u8::try_from(i).unwrap()) * 3.0,
+            );
+        }
+
+        let sorted = |mut people: Vec<PersonId>| {
+            people.sort_by_key(ToString::to_string);
+            people
+        };
+        assert_eq!(
+            sorted(context.query_people(HasStagedChange(StagedStatus, 0.0..7.0))),
+            people[..3]
+        );
+        assert_eq!(
+            context.query_people_count(HasStagedChange(StagedStatus, 7.0..)),
+            1
+        );
+        // Indexed properties don't change how the filter is checked.
+        assert_eq!(
+            context.query_people((HasStagedChange(StagedStatus, ..), (StagedAge, 2))),
+            [people[2]]
+        );
+        assert!(context.match_person(people[1], HasStagedChange(StagedStatus, 3.0..=3.0)));
+
+        context.cancel_staged_change(people[1], StagedStatus);
+        assert_eq!(
+            context.query_people_count(HasStagedChange(StagedStatus, 0.0..7.0)),
+            2
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Time is invalid")]
+    fn past_time_panics() {
+        let mut context = Context::new();
+        let people = add_people(&mut context, 1);
+        context.add_plan(2.0, move |context| {
+            context.stage_property_change(people[0], StagedStatus, Status::Recovered, 1.0);
+        });
+        context.execute();
+    }
+}
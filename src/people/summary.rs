@@ -0,0 +1,538 @@
+use crate::people::external_api::ContextPeopleExtCrate;
+use crate::people::index::IndexValue;
+use crate::people::query::Query;
+use crate::{Context, ContextPeopleExt, IxaError, PersonProperty};
+use ordered_float::OrderedFloat;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// A numeric person property value, which can be summarized with
+/// [`Context::property_summary()`]. Floating point properties have
+/// [`OrderedFloat`] values.
+pub trait NumericValue: Copy {
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_numeric_value {
+    ($($t:ty),*) => {
+        $(
+            impl NumericValue for $t {
+                fn to_f64(self) -> f64 {
+                    f64::from(self)
+                }
+            }
+        )*
+    };
+}
+impl_numeric_value!(u8, u16, u32, i8, i16, i32, f32, f64);
+
+// These can lose precision for very large values.
+macro_rules! impl_numeric_value_lossy {
+    ($($t:ty),*) => {
+        $(
+            impl NumericValue for $t {
+                #[allow(clippy::cast_precision_loss)]
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+impl_numeric_value_lossy!(u64, i64, usize, isize);
+
+impl<T: NumericValue + ordered_float::FloatCore> NumericValue for OrderedFloat<T> {
+    fn to_f64(self) -> f64 {
+        self.0.to_f64()
+    }
+}
+
+/// The quantiles of the values in a [`PropertySummary`], i.e., the
+/// quartiles.
+pub const SUMMARY_QUANTILES: [f64; 3] = [0.25, 0.5, 0.75];
+
+/// The largest number of values that are summarized exactly. Quantiles of
+/// more values are estimated with the P² algorithm, which doesn't store
+/// the values.
+pub const EXACT_SUMMARY_LIMIT: usize = 1000;
+
+/// Summary statistics of the values of a numeric person property, returned
+/// by [`Context::property_summary()`]. When there are no values, `count`
+/// is 0 and the statistics are NaN.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PropertySummary {
+    /// The number of values
+    pub count: usize,
+    pub mean: f64,
+    /// The sample standard deviation, which is 0 for a single value
+    pub sd: f64,
+    pub min: f64,
+    pub max: f64,
+    /// The quantiles in [`SUMMARY_QUANTILES`]. These are interpolated
+    /// between the closest values when `exact` and otherwise estimated.
+    pub quantiles: [f64; 3],
+    /// Whether the quantiles are exact, which they are for up to
+    /// [`EXACT_SUMMARY_LIMIT`] values
+    pub exact: bool,
+}
+
+impl PropertySummary {
+    #[must_use]
+    pub fn median(&self) -> f64 {
+        self.quantiles[1]
+    }
+
+    /// The interquartile range
+    #[must_use]
+    pub fn iqr(&self) -> f64 {
+        self.quantiles[2] - self.quantiles[0]
+    }
+}
+
+impl Display for PropertySummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "count={} mean={} sd={} min={} q25={} median={} q75={} max={}",
+            self.count,
+            self.mean,
+            self.sd,
+            self.min,
+            self.quantiles[0],
+            self.quantiles[1],
+            self.quantiles[2],
+            self.max
+        )
+    }
+}
+
+// A P² estimate of a quantile, which is kept up to date with five markers
+// rather than the values (Jain and Chlamtac, 1985).
+struct P2Quantile {
+    // Marker heights and positions, and desired positions and their
+    // increments.
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    fn new(p: f64, first: &[f64]) -> Self {
+        let mut heights = [0.0; 5];
+        heights.copy_from_slice(&first[..5]);
+        heights.sort_by(f64::total_cmp);
+        P2Quantile {
+            heights,
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [1.0, 1.0 + 2.0 * p, 1.0 + 4.0 * p, 3.0 + 2.0 * p, 5.0],
+            increments: [0.0, p / 2.0, p, f64::midpoint(1.0, p), 1.0],
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        let heights = &mut self.heights;
+        let positions = &mut self.positions;
+
+        // Find the cell containing the value, extending the extremes if
+        // needed.
+        let cell = if value < heights[0] {
+            heights[0] = value;
+            0
+        } else if value >= heights[4] {
+            heights[4] = value;
+            3
+        } else {
+            (0..4).find(|&i| value < heights[i + 1]).unwrap()
+        };
+        for position in &mut positions[cell + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+
+        // Move the middle markers towards their desired positions, using
+        // a parabolic prediction of the height or, if that isn't between
+        // the neighboring heights, a linear one.
+        for i in 1..4 {
+            let offset = self.desired[i] - positions[i];
+            let (below, above) = (
+                positions[i - 1] - positions[i],
+                positions[i + 1] - positions[i],
+            );
+            if (offset >= 1.0 && above > 1.0) || (offset <= -1.0 && below < -1.0) {
+                let step = offset.signum();
+                let parabolic = heights[i]
+                    + step / (above - below)
+                        * ((step - below) * (heights[i + 1] - heights[i]) / above
+                            + (above - step) * (heights[i] - heights[i - 1]) / -below);
+                heights[i] = if heights[i - 1] < parabolic && parabolic < heights[i + 1] {
+                    parabolic
+                } else {
+                    let neighbor = if step > 0.0 { i + 1 } else { i - 1 };
+                    heights[i]
+                        + step * (heights[neighbor] - heights[i])
+                            / (positions[neighbor] - positions[i])
+                };
+                positions[i] += step;
+            }
+        }
+    }
+
+    fn estimate(&self) -> f64 {
+        self.heights[2]
+    }
+}
+
+// The quantile `p` of `sorted`, interpolating between the closest values.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn exact_quantile(sorted: &[f64], p: f64) -> f64 {
+    let h = (sorted.len() - 1) as f64 * p;
+    let below = h.floor() as usize;
+    match sorted.get(below + 1) {
+        None => sorted[below],
+        Some(above) => sorted[below] + (h - h.floor()) * (above - sorted[below]),
+    }
+}
+
+// Computes a `PropertySummary` in a single pass over the values, using
+// bounded memory.
+pub(crate) struct SummaryAccumulator {
+    count: usize,
+    mean: f64,
+    // The sum of squared differences from the mean (Welford's algorithm)
+    m2: f64,
+    min: f64,
+    max: f64,
+    // The values while there are at most EXACT_SUMMARY_LIMIT of them, and
+    // the quantile estimates after that.
+    values: Vec<f64>,
+    sketches: Option<Vec<P2Quantile>>,
+}
+
+impl SummaryAccumulator {
+    pub(crate) fn new() -> Self {
+        SummaryAccumulator {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            values: Vec::new(),
+            sketches: None,
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+
+        if let Some(sketches) = &mut self.sketches {
+            for sketch in sketches {
+                sketch.add(x);
+            }
+            return;
+        }
+        self.values.push(x);
+        if self.values.len() > EXACT_SUMMARY_LIMIT {
+            let values = std::mem::take(&mut self.values);
+            let mut sketches: Vec<P2Quantile> = SUMMARY_QUANTILES
+                .iter()
+                .map(|&p| P2Quantile::new(p, &values))
+                .collect();
+            for &value in &values[5..] {
+                for sketch in &mut sketches {
+                    sketch.add(value);
+                }
+            }
+            self.sketches = Some(sketches);
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn finish(mut self) -> PropertySummary {
+        if self.count == 0 {
+            return PropertySummary {
+                count: 0,
+                mean: f64::NAN,
+                sd: f64::NAN,
+                min: f64::NAN,
+                max: f64::NAN,
+                quantiles: [f64::NAN; 3],
+                exact: true,
+            };
+        }
+
+        let quantiles = if let Some(sketches) = &self.sketches {
+            [0, 1, 2].map(|i| sketches[i].estimate())
+        } else {
+            self.values.sort_by(f64::total_cmp);
+            SUMMARY_QUANTILES.map(|p| exact_quantile(&self.values, p))
+        };
+        let sd = if self.count > 1 {
+            (self.m2 / (self.count - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        PropertySummary {
+            count: self.count,
+            mean: self.mean,
+            sd,
+            min: self.min,
+            max: self.max,
+            quantiles,
+            exact: self.sketches.is_none(),
+        }
+    }
+}
+
+pub(super) fn summarize<T, Q>(context: &Context, property: T, query: Q) -> PropertySummary
+where
+    T: PersonProperty + 'static,
+    T::Value: NumericValue,
+    Q: Query,
+{
+    let mut accumulator = SummaryAccumulator::new();
+    for person_id in context.query_people(query) {
+        accumulator.add(context.get_person_property(person_id, property).to_f64());
+    }
+    accumulator.finish()
+}
+
+pub(super) fn summarize_by<T, G, Q>(
+    context: &Context,
+    property: T,
+    group: G,
+    query: Q,
+) -> Vec<(G::Value, PropertySummary)>
+where
+    T: PersonProperty + 'static,
+    T::Value: NumericValue,
+    G: PersonProperty + 'static,
+    Q: Query,
+{
+    let mut groups = HashMap::<IndexValue, (G::Value, SummaryAccumulator)>::new();
+    for person_id in context.query_people(query) {
+        let value = context.get_person_property(person_id, group);
+        groups
+            .entry(IndexValue::compute(&value))
+            .or_insert_with(|| (value, SummaryAccumulator::new()))
+            .1
+            .add(context.get_person_property(person_id, property).to_f64());
+    }
+
+    let mut summaries: Vec<(String, G::Value, PropertySummary)> = groups
+        .into_values()
+        .map(|(value, accumulator)| (format!("{value:?}"), value, accumulator.finish()))
+        .collect();
+    summaries.sort_by(|a, b| a.0.cmp(&b.0));
+    summaries
+        .into_iter()
+        .map(|(_, value, summary)| (value, summary))
+        .collect()
+}
+
+// Summarize a property by name for the debugger and the Web API, with
+// the values of the group property, if any, displayed as strings. The
+// values are parsed from their display strings, so this fails for
+// properties that aren't numeric.
+pub(crate) fn summarize_by_name(
+    context: &Context,
+    property: &str,
+    group: Option<&str>,
+) -> Result<Vec<(Option<String>, PropertySummary)>, IxaError> {
+    let mut groups = HashMap::<Option<String>, SummaryAccumulator>::new();
+    for person_id in context.query_people(()) {
+        let display = context.get_person_property_by_name(property, person_id)?;
+        let value: f64 = display.parse().map_err(|_| {
+            IxaError::IxaError(format!(
+                "Property '{property}' is not numeric: found value {display}"
+            ))
+        })?;
+        let label = match group {
+            None => None,
+            Some(group) => Some(context.get_person_property_by_name(group, person_id)?),
+        };
+        groups
+            .entry(label)
+            .or_insert_with(SummaryAccumulator::new)
+            .add(value);
+    }
+
+    let mut summaries: Vec<(Option<String>, PropertySummary)> = groups
+        .into_iter()
+        .map(|(label, accumulator)| (label, accumulator.finish()))
+        .collect();
+    summaries.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(summaries)
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::{exact_quantile, SummaryAccumulator, EXACT_SUMMARY_LIMIT};
+    use crate::people::define_person_property;
+    use crate::{Context, ContextPeopleExt, ContextRandomExt};
+    use ordered_float::OrderedFloat;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    define_person_property!(ViralLoad, OrderedFloat<f64>);
+    define_person_property!(Infectious, bool);
+    define_person_property!(AgeGroup, u8);
+
+    #[test]
+    fn exact_summary() {
+        let mut context = Context::new();
+        for (load, infectious, age_group) in [
+            (1.0, true, 0),
+            (4.0, true, 1),
+            (2.0, true, 0),
+            (3.0, true, 1),
+            (100.0, false, 0),
+        ] {
+            context
+                .add_person((
+                    (ViralLoad, OrderedFloat(load)),
+                    (Infectious, infectious),
+                    (AgeGroup, age_group),
+                ))
+                .unwrap();
+        }
+
+        let summary = context.property_summary(ViralLoad, ((Infectious, true),));
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.mean, 2.5);
+        assert!((summary.sd - (5.0_f64 / 3.0).sqrt()).abs() < 1e-12);
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.max, 4.0);
+        assert_eq!(summary.quantiles, [1.75, 2.5, 3.25]);
+        assert_eq!(summary.median(), 2.5);
+        assert_eq!(summary.iqr(), 1.5);
+        assert!(summary.exact);
+    }
+
+    #[test]
+    fn empty_and_single_summary() {
+        let mut context = Context::new();
+        let summary = context.property_summary(ViralLoad, ());
+        assert_eq!(summary.count, 0);
+        assert!(summary.mean.is_nan());
+
+        context
+            .add_person((
+                (ViralLoad, OrderedFloat(7.0)),
+                (Infectious, true),
+                (AgeGroup, 0),
+            ))
+            .unwrap();
+        let summary = context.property_summary(ViralLoad, ());
+        assert_eq!(summary.count, 1);
+        assert_eq!(summary.sd, 0.0);
+        assert_eq!(summary.quantiles, [7.0; 3]);
+    }
+
+    #[test]
+    fn grouped_summary() {
+        let mut context = Context::new();
+        for (load, age_group) in [(1.0, 2), (10.0, 1), (3.0, 2), (20.0, 1), (5.0, 2)] {
+            context
+                .add_person((
+                    (ViralLoad, OrderedFloat(load)),
+                    (Infectious, true),
+                    (AgeGroup, age_group),
+                ))
+                .unwrap();
+        }
+
+        let summaries = context.property_summary_by(ViralLoad, AgeGroup, ((Infectious, true),));
+        assert_eq!(summaries.len(), 2);
+        let (group, summary) = &summaries[0];
+        assert_eq!(*group, 1);
+        assert_eq!(summary.count, 2);
+        assert_eq!(summary.mean, 15.0);
+        let (group, summary) = &summaries[1];
+        assert_eq!(*group, 2);
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.median(), 3.0);
+
+        assert!(context
+            .property_summary_by(ViralLoad, AgeGroup, ((Infectious, false),))
+            .is_empty());
+    }
+
+    #[test]
+    fn sketch_accuracy() {
+        // Shuffled values from a skewed distribution, so the order doesn't
+        // help the estimates.
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut values: Vec<f64> = (0..100_000)
+            .map(|_| -rng.gen::<f64>().ln() * 10.0)
+            .collect();
+        values.shuffle(&mut rng);
+
+        let mut accumulator = SummaryAccumulator::new();
+        for &value in &values {
+            accumulator.add(value);
+        }
+        let summary = accumulator.finish();
+        assert!(!summary.exact);
+        assert_eq!(summary.count, values.len());
+
+        values.sort_by(f64::total_cmp);
+        for (estimate, p) in summary.quantiles.iter().zip(super::SUMMARY_QUANTILES) {
+            // Compare ranks rather than values: each estimate should be
+            // within half a percentile of the true quantile.
+            let rank = values.partition_point(|value| value < estimate);
+            #[allow(clippy::cast_precision_loss)]
+            let estimated_p = rank as f64 / values.len() as f64;
+            assert!(
+                (estimated_p - p).abs() < 0.005,
+                "quantile {p} estimated as {estimate} at {estimated_p}"
+            );
+            assert!((estimate - exact_quantile(&values, p)).abs() / estimate < 0.05);
+        }
+        let mean = values.iter().sum::<f64>() / 100_000.0;
+        assert!((summary.mean - mean).abs() < 1e-9);
+    }
+
+    #[test]
+    fn switches_to_sketch_above_limit() {
+        let mut accumulator = SummaryAccumulator::new();
+        for value in 0..EXACT_SUMMARY_LIMIT {
+            accumulator.add(f64::from(u32::try_from(value).unwrap()));
+        }
+        let summary = accumulator.finish();
+        assert!(summary.exact);
+        assert_eq!(summary.median(), 499.5);
+
+        let mut context = Context::new();
+        context.init_random(42);
+        for value in 0..=EXACT_SUMMARY_LIMIT {
+            context
+                .add_person((
+                    (
+                        ViralLoad,
+                        OrderedFloat(f64::from(u32::try_from(value).unwrap())),
+                    ),
+                    (Infectious, true),
+                    (AgeGroup, 0),
+                ))
+                .unwrap();
+        }
+        let summary = context.property_summary(ViralLoad, ());
+        assert!(!summary.exact);
+        assert!((summary.median() - 500.0).abs() < 5.0);
+    }
+}
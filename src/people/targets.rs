@@ -0,0 +1,415 @@
+use crate::people::index::IndexValue;
+use crate::{Context, ContextPeopleExt, IxaError, PersonId, PersonProperty};
+use rand::Rng;
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
+
+type PersonAssigner = dyn Fn(&mut Context, PersonId);
+type StratumKey = dyn Fn(&Context, PersonId) -> IndexValue;
+
+/// The number of people a [`TargetSpec`] should assign its value to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TargetCount {
+    /// An exact number of people in each stratum.
+    Count(usize),
+    /// A fraction of the people in each stratum.
+    ///
+    /// Fractions are converted to counts with the largest remainder
+    /// method: the total is `fraction * population` rounded to the
+    /// nearest integer (halves round up), each stratum first gets the
+    /// floor of `fraction * stratum_size`, and the remaining people go
+    /// to the strata with the largest fractional parts, with ties going
+    /// to the stratum whose first member has the lowest id.
+    Fraction(f64),
+}
+
+/// A target for [`Context::initialize_to_targets()`]: set `property` to
+/// `value` for exactly the number of people given by `count`, optionally
+/// within each stratum of another property.
+pub struct TargetSpec {
+    property_type: TypeId,
+    property_name: &'static str,
+    count: TargetCount,
+    assign: Box<PersonAssigner>,
+    stratum: Option<Box<StratumKey>>,
+}
+
+impl TargetSpec {
+    /// Create a target that assigns `value` to `property`.
+    #[must_use]
+    pub fn new<T: PersonProperty + 'static>(
+        property: T,
+        value: T::Value,
+        count: TargetCount,
+    ) -> Self {
+        TargetSpec {
+            property_type: TypeId::of::<T>(),
+            property_name: T::name(),
+            count,
+            assign: Box::new(move |context, person_id| {
+                context.set_person_property(person_id, property, value);
+            }),
+            stratum: None,
+        }
+    }
+
+    /// Apply the count separately within each group of people sharing a
+    /// value of `property`.
+    #[must_use]
+    pub fn stratify_by<S: PersonProperty + 'static>(mut self, property: S) -> Self {
+        self.stratum = Some(Box::new(move |context, person_id| {
+            IndexValue::compute(&context.get_person_property(person_id, property))
+        }));
+        self
+    }
+}
+
+// Group the population into strata in order of the lowest id in each stratum,
+// so that the result doesn't depend on hash ordering.
+fn get_strata(context: &Context, stratum: Option<&StratumKey>) -> Vec<Vec<PersonId>> {
+    let population = context.query_people(()).into_iter();
+    let Some(stratum) = stratum else {
+        return vec![population.collect()];
+    };
+
+    let mut strata: Vec<Vec<PersonId>> = Vec::new();
+    let mut positions = HashMap::new();
+    for person_id in population {
+        let position = *positions
+            .entry(stratum(context, person_id))
+            .or_insert_with(|| {
+                strata.push(Vec::new());
+                strata.len() - 1
+            });
+        strata[position].push(person_id);
+    }
+    strata
+}
+
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn get_stratum_counts(
+    count: TargetCount,
+    strata: &[Vec<PersonId>],
+) -> Result<Vec<usize>, IxaError> {
+    let fraction = match count {
+        TargetCount::Count(count) => return Ok(vec![count; strata.len()]),
+        TargetCount::Fraction(fraction) => fraction,
+    };
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err(IxaError::IxaError(format!(
+            "Target fraction {fraction} is not between 0 and 1"
+        )));
+    }
+
+    // Largest remainder method.
+    let population: usize = strata.iter().map(Vec::len).sum();
+    let total = (fraction * population as f64).round() as usize;
+    let exact: Vec<f64> = strata
+        .iter()
+        .map(|stratum| fraction * stratum.len() as f64)
+        .collect();
+    let mut counts: Vec<usize> = exact.iter().map(|x| x.floor() as usize).collect();
+    let mut order: Vec<usize> = (0..strata.len()).collect();
+    // A stable sort keeps ties in stratum order.
+    order.sort_by(|a, b| {
+        let remainder_a = exact[*a] - exact[*a].floor();
+        let remainder_b = exact[*b] - exact[*b].floor();
+        remainder_b.total_cmp(&remainder_a)
+    });
+    let assigned: usize = counts.iter().sum();
+    for i in order.into_iter().take(total.saturating_sub(assigned)) {
+        counts[i] += 1;
+    }
+    Ok(counts)
+}
+
+fn unsatisfiable(target: &TargetSpec, required: usize, available: usize) -> IxaError {
+    IxaError::IxaError(format!(
+        "Cannot satisfy target for {}: {required} people required but only {available} available",
+        target.property_name,
+    ))
+}
+
+/// Choose the people for every target with `rng`, without modifying any
+/// properties. Returns one list of people per target.
+///
+/// Every target's count in each stratum is checked against the size of the
+/// stratum before anything is drawn from `rng`. Whether targets for the
+/// same property leave enough people for each other can depend on who the
+/// earlier ones chose, so that is checked as people are chosen.
+pub(super) fn select_target_people<R: Rng>(
+    context: &Context,
+    rng: &mut R,
+    targets: &[TargetSpec],
+) -> Result<Vec<Vec<PersonId>>, IxaError> {
+    let mut target_strata = Vec::new();
+    for target in targets {
+        let strata = get_strata(context, target.stratum.as_deref());
+        let counts = get_stratum_counts(target.count, &strata)?;
+        for (stratum, count) in strata.iter().zip(&counts) {
+            if *count > stratum.len() {
+                return Err(unsatisfiable(target, *count, stratum.len()));
+            }
+        }
+        target_strata.push((strata, counts));
+    }
+
+    // People already chosen for each property by an earlier target.
+    let mut claimed: HashMap<TypeId, HashSet<PersonId>> = HashMap::new();
+    let mut selections = Vec::new();
+
+    for (target, (strata, counts)) in targets.iter().zip(target_strata) {
+        let claimed = claimed.entry(target.property_type).or_default();
+        let mut selected = Vec::new();
+
+        for (stratum, count) in strata.iter().zip(counts) {
+            let candidates: Vec<PersonId> = stratum
+                .iter()
+                .filter(|person_id| !claimed.contains(person_id))
+                .copied()
+                .collect();
+            if count > candidates.len() {
+                return Err(unsatisfiable(target, count, candidates.len()));
+            }
+            let chosen = rand::seq::index::sample(rng, candidates.len(), count);
+            for i in chosen {
+                claimed.insert(candidates[i]);
+                selected.push(candidates[i]);
+            }
+        }
+        selections.push(selected);
+    }
+
+    Ok(selections)
+}
+
+pub(super) fn apply_targets(
+    context: &mut Context,
+    targets: &[TargetSpec],
+    selections: Vec<Vec<PersonId>>,
+) {
+    for (target, selected) in targets.iter().zip(selections) {
+        for person_id in selected {
+            (target.assign)(context, person_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TargetCount, TargetSpec};
+    use crate::{
+        define_person_property, define_person_property_with_default, define_rng, Context,
+        ContextPeopleExt, ContextRandomExt, IxaError, PersonId,
+    };
+    use rand::RngCore;
+
+    define_rng!(TargetRng);
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub enum AgeGroupValue {
+        Child,
+        Adult,
+    }
+    define_person_property!(AgeGroup, AgeGroupValue);
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub enum InfectionStatusValue {
+        Susceptible,
+        Infectious,
+        Recovered,
+    }
+    define_person_property_with_default!(
+        InfectionStatus,
+        InfectionStatusValue,
+        InfectionStatusValue::Susceptible
+    );
+    define_person_property_with_default!(Vaccinated, bool, false);
+
+    fn setup(seed: u64, children: usize, adults: usize) -> Context {
+        let mut context = Context::new();
+        context.init_random(seed);
+        for _ in 0..children {
+            context
+                .add_person((AgeGroup, AgeGroupValue::Child))
+                .unwrap();
+        }
+        for _ in 0..adults {
+            context
+                .add_person((AgeGroup, AgeGroupValue::Adult))
+                .unwrap();
+        }
+        context
+    }
+
+    fn count(context: &Context, age_group: AgeGroupValue, status: InfectionStatusValue) -> usize {
+        context.query_people_count(((AgeGroup, age_group), (InfectionStatus, status)))
+    }
+
+    #[test]
+    fn exact_counts_per_stratum() {
+        use AgeGroupValue::{Adult, Child};
+        use InfectionStatusValue::{Infectious, Recovered};
+
+        let mut context = setup(42, 40, 60);
+        context
+            .initialize_to_targets(
+                TargetRng,
+                &[
+                    TargetSpec::new(
+                        InfectionStatus,
+                        InfectionStatusValue::Recovered,
+                        TargetCount::Fraction(0.1),
+                    )
+                    .stratify_by(AgeGroup),
+                    TargetSpec::new(
+                        InfectionStatus,
+                        InfectionStatusValue::Infectious,
+                        TargetCount::Count(3),
+                    ),
+                    TargetSpec::new(Vaccinated, true, TargetCount::Count(5)).stratify_by(AgeGroup),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(count(&context, Child, Recovered), 4);
+        assert_eq!(count(&context, Adult, Recovered), 6);
+        assert_eq!(
+            count(&context, Child, Infectious) + count(&context, Adult, Infectious),
+            3
+        );
+        assert_eq!(
+            context.query_people_count(((AgeGroup, Child), (Vaccinated, true))),
+            5
+        );
+        assert_eq!(
+            context.query_people_count(((AgeGroup, Adult), (Vaccinated, true))),
+            5
+        );
+    }
+
+    #[test]
+    fn unsatisfiable_targets_have_no_side_effects() {
+        let mut context = setup(42, 5, 5);
+        let untouched = setup(42, 5, 5);
+        let result = context.initialize_to_targets(
+            TargetRng,
+            &[
+                TargetSpec::new(
+                    InfectionStatus,
+                    InfectionStatusValue::Recovered,
+                    TargetCount::Count(3),
+                )
+                .stratify_by(AgeGroup),
+                TargetSpec::new(
+                    InfectionStatus,
+                    InfectionStatusValue::Infectious,
+                    TargetCount::Fraction(0.5),
+                )
+                .stratify_by(AgeGroup),
+            ],
+        );
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+        assert_eq!(
+            context.query_people_count((InfectionStatus, InfectionStatusValue::Susceptible)),
+            10
+        );
+
+        // A target larger than its stratum fails before anything is drawn.
+        let result = context.initialize_to_targets(
+            TargetRng,
+            &[TargetSpec::new(Vaccinated, true, TargetCount::Count(6)).stratify_by(AgeGroup)],
+        );
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+        assert_eq!(context.query_people_count((Vaccinated, true)), 0);
+
+        // The failed calls didn't advance the generator.
+        assert_eq!(
+            context.sample(TargetRng, RngCore::next_u64),
+            untouched.sample(TargetRng, RngCore::next_u64)
+        );
+    }
+
+    #[test]
+    fn invalid_fraction() {
+        let mut context = setup(42, 5, 5);
+        let result = context.initialize_to_targets(
+            TargetRng,
+            &[TargetSpec::new(
+                Vaccinated,
+                true,
+                TargetCount::Fraction(1.5),
+            )],
+        );
+        assert!(matches!(result, Err(IxaError::IxaError(_))));
+    }
+
+    #[test]
+    fn largest_remainder_rounding() {
+        // 0.25 * 3 = 0.75 children and 0.25 * 7 = 1.75 adults. The total is
+        // round(2.5) = 3, so after the floors (0 and 1) both strata get one
+        // more person.
+        let mut context = setup(42, 3, 7);
+        context
+            .initialize_to_targets(
+                TargetRng,
+                &[
+                    TargetSpec::new(Vaccinated, true, TargetCount::Fraction(0.25))
+                        .stratify_by(AgeGroup),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            context.query_people_count(((AgeGroup, AgeGroupValue::Child), (Vaccinated, true))),
+            1
+        );
+        assert_eq!(
+            context.query_people_count(((AgeGroup, AgeGroupValue::Adult), (Vaccinated, true))),
+            2
+        );
+
+        // 0.1 * 4 = 0.4 and 0.1 * 6 = 0.6 with a total of 1: the person goes
+        // to the stratum with the larger remainder.
+        let mut context = setup(42, 4, 6);
+        context
+            .initialize_to_targets(
+                TargetRng,
+                &[
+                    TargetSpec::new(Vaccinated, true, TargetCount::Fraction(0.1))
+                        .stratify_by(AgeGroup),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            context.query_people_count(((AgeGroup, AgeGroupValue::Adult), (Vaccinated, true))),
+            1
+        );
+        assert_eq!(context.query_people_count((Vaccinated, true)), 1);
+    }
+
+    #[test]
+    fn deterministic_per_seed() {
+        let selected = |seed| {
+            let mut context = setup(seed, 50, 50);
+            context
+                .initialize_to_targets(
+                    TargetRng,
+                    &[TargetSpec::new(
+                        Vaccinated,
+                        true,
+                        TargetCount::Fraction(0.2),
+                    )],
+                )
+                .unwrap();
+            let mut people: Vec<PersonId> = context.query_people((Vaccinated, true));
+            people.sort_by_key(|person_id| person_id.0);
+            people
+        };
+        assert_eq!(selected(42), selected(42));
+        assert_ne!(selected(42), selected(43));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::people::{InitializationList, PeoplePlugin};
+use crate::random::sample_person_initial_value;
+use crate::{Context, ContextPeopleExt, IxaError, PersonId, PersonProperty, RngId};
+use rand::rngs::StdRng;
+use std::any::TypeId;
+
+type PropertyAssigner = dyn Fn(&mut Context, PersonId, &str);
+
+struct TemplateField {
+    property_type: TypeId,
+    assign: Box<PropertyAssigner>,
+}
+
+/// Initial property values shared by a group of people, for creating many
+/// similar people with [`Context::add_people_from_template()`].
+///
+/// Each property either has a constant value that every person gets or a
+/// distribution that is drawn from separately for each person. Setting the
+/// same property twice replaces the earlier value.
+///
+/// ```
+/// use ixa::people::PersonTemplate;
+/// use ixa::{define_person_property, define_rng, Context, ContextPeopleExt, ContextRandomExt};
+/// use rand::Rng;
+///
+/// define_person_property!(County, u32);
+/// define_person_property!(HouseholdSize, u8);
+/// define_rng!(TemplateRng);
+///
+/// let mut context = Context::new();
+/// context.init_random(42);
+/// let template = PersonTemplate::new()
+///     .value(County, 17031)
+///     .distribution(HouseholdSize, |rng| rng.gen_range(1..=6));
+/// let people = context
+///     .add_people_from_template(TemplateRng, &template, 100)
+///     .unwrap();
+/// assert_eq!(people.len(), 100);
+/// assert_eq!(context.query_people_count((County, 17031)), 100);
+/// ```
+#[derive(Default)]
+pub struct PersonTemplate {
+    fields: Vec<TemplateField>,
+}
+
+impl PersonTemplate {
+    /// Create a template that doesn't set any properties.
+    #[must_use]
+    pub fn new() -> Self {
+        PersonTemplate::default()
+    }
+
+    /// Give every person `value` for `property`.
+    ///
+    /// # Panics
+    /// Panics if `property` is derived.
+    #[must_use]
+    pub fn value<T: PersonProperty + 'static>(self, property: T, value: T::Value) -> Self {
+        self.with_field::<T>(Box::new(move |context, person_id, _| {
+            context.set_person_property(person_id, property, value);
+        }))
+    }
+
+    /// Draw each person's value for `property` with `sampler`. Each draw
+    /// uses a generator seeded from the base seed, the name of the `RngId`
+    /// passed to [`Context::add_people_from_template()`], the property, and
+    /// the person, so the values are reproducible and don't consume draws
+    /// from any `RngId`.
+    ///
+    /// # Panics
+    /// Panics if `property` is derived.
+    #[must_use]
+    pub fn distribution<T: PersonProperty + 'static>(
+        self,
+        property: T,
+        sampler: impl Fn(&mut StdRng) -> T::Value + 'static,
+    ) -> Self {
+        self.with_field::<T>(Box::new(move |context, person_id, stream| {
+            let value = sample_person_initial_value(
+                context,
+                &format!("{stream}.{}", T::name()),
+                person_id,
+                &sampler,
+            );
+            context.set_person_property(person_id, property, value);
+        }))
+    }
+
+    fn with_field<T: PersonProperty + 'static>(mut self, assign: Box<PropertyAssigner>) -> Self {
+        assert!(
+            !T::is_derived(),
+            "Cannot set derived property {} in a template",
+            T::name()
+        );
+        self.fields
+            .retain(|field| field.property_type != TypeId::of::<T>());
+        self.fields.push(TemplateField {
+            property_type: TypeId::of::<T>(),
+            assign,
+        });
+        self
+    }
+}
+
+// A template together with the name of the stream used for its
+// distributions, which can be used to initialize a person.
+#[derive(Clone, Copy)]
+struct TemplateInstance<'a> {
+    template: &'a PersonTemplate,
+    stream: &'static str,
+}
+
+impl InitializationList for TemplateInstance<'_> {
+    fn has_property(&self, t: TypeId) -> bool {
+        self.template
+            .fields
+            .iter()
+            .any(|field| field.property_type == t)
+    }
+
+    fn set_properties(&self, context: &mut Context, person_id: PersonId) {
+        for field in &self.template.fields {
+            (field.assign)(context, person_id, self.stream);
+        }
+    }
+}
+
+pub(super) fn add_people_from_template<R: RngId + 'static>(
+    context: &mut Context,
+    template: &PersonTemplate,
+    count: usize,
+) -> Result<Vec<PersonId>, IxaError> {
+    let instance = TemplateInstance {
+        template,
+        stream: R::get_name(),
+    };
+    context
+        .get_data_container_mut(PeoplePlugin)
+        .check_initialization_list(&instance)?;
+
+    let mut people = Vec::with_capacity(count);
+    for _ in 0..count {
+        people.push(context.add_person(instance)?);
+    }
+    Ok(people)
+}
+
+#[cfg(test)]
+mod test {
+    use super::PersonTemplate;
+    use crate::{
+        define_derived_property, define_person_property, define_person_property_with_default,
+        define_rng, Context, ContextPeopleExt, ContextRandomExt, IxaError, PersonId,
+    };
+    use rand::Rng;
+
+    define_rng!(TemplateRng);
+    define_rng!(OtherTemplateRng);
+
+    define_person_property!(County, u32);
+    define_person_property!(Size, u16);
+    define_person_property_with_default!(Open, bool, true);
+    define_derived_property!(Large, bool, [Size], |size| size > 100);
+
+    fn workplaces() -> PersonTemplate {
+        PersonTemplate::new()
+            .value(County, 17031)
+            .distribution(Size, |rng| rng.gen_range(1..=500))
+    }
+
+    fn sizes(context: &Context, people: &[PersonId]) -> Vec<u16> {
+        people
+            .iter()
+            .map(|person_id| context.get_person_property(*person_id, Size))
+            .collect()
+    }
+
+    #[test]
+    fn constants_and_distributions() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let people = context
+            .add_people_from_template(TemplateRng, &workplaces(), 50)
+            .unwrap();
+
+        assert_eq!(people.len(), 50);
+        // Ids are consecutive
+        for (i, person_id) in people.iter().enumerate() {
+            assert_eq!(person_id.0, i);
+            assert_eq!(context.get_person_property(*person_id, County), 17031);
+            assert!(context.get_person_property(*person_id, Open));
+        }
+        let sizes = sizes(&context, &people);
+        assert!(sizes.iter().all(|size| (1..=500).contains(size)));
+        assert!(sizes.iter().any(|size| *size != sizes[0]));
+    }
+
+    #[test]
+    fn later_value_replaces_earlier() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let template = workplaces().value(Size, 7).value(County, 1);
+        let people = context
+            .add_people_from_template(TemplateRng, &template, 3)
+            .unwrap();
+        assert_eq!(sizes(&context, &people), vec![7, 7, 7]);
+        assert_eq!(context.query_people_count((County, 1)), 3);
+    }
+
+    #[test]
+    fn distributions_are_deterministic() {
+        let draw = |seed, count| {
+            let mut context = Context::new();
+            context.init_random(seed);
+            // Using another generator first doesn't change the draws
+            let _: u32 = context.sample_range(TemplateRng, 0..10);
+            let people = context
+                .add_people_from_template(TemplateRng, &workplaces(), count)
+                .unwrap();
+            sizes(&context, &people)
+        };
+        assert_eq!(draw(42, 20), draw(42, 20));
+        assert_eq!(draw(42, 10), draw(42, 20)[..10]);
+        assert_ne!(draw(42, 20), draw(43, 20));
+
+        // The stream depends on the rng id
+        let mut context = Context::new();
+        context.init_random(42);
+        let people = context
+            .add_people_from_template(OtherTemplateRng, &workplaces(), 20)
+            .unwrap();
+        assert_ne!(sizes(&context, &people), draw(42, 20));
+    }
+
+    #[test]
+    fn missing_properties_reported_once() {
+        let mut context = Context::new();
+        context.init_random(42);
+        // Register the required properties
+        context
+            .add_people_from_template(TemplateRng, &workplaces(), 1)
+            .unwrap();
+
+        let result = context.add_people_from_template(
+            TemplateRng,
+            &PersonTemplate::new().value(Open, false),
+            1000,
+        );
+        match result {
+            Err(IxaError::MissingRequiredProperties { entity, properties }) => {
+                assert_eq!(entity, "Person");
+                assert_eq!(properties, vec!["County", "Size"]);
+            }
+            _ => panic!("Expected an error"),
+        }
+        assert_eq!(context.get_current_population(), 1);
+    }
+
+    #[test]
+    fn queries_see_new_people() {
+        let mut context = Context::new();
+        context.init_random(42);
+        context.index_property(County);
+        context.index_property(Large);
+        context
+            .add_people_from_template(TemplateRng, &workplaces(), 5)
+            .unwrap();
+        let people = context
+            .add_people_from_template(TemplateRng, &workplaces().value(County, 6037), 10)
+            .unwrap();
+
+        let mut matches = context.query_people((County, 6037));
+        matches.sort_by_key(|person_id| person_id.0);
+        assert_eq!(matches, people);
+        assert_eq!(context.query_people_count((County, 17031)), 5);
+        let large = sizes(&context, &people)
+            .iter()
+            .filter(|size| **size > 100)
+            .count();
+        assert_eq!(
+            context.query_people(((County, 6037), (Large, true))).len(),
+            large
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot set derived property Large in a template")]
+    fn derived_property_panics() {
+        let _ = PersonTemplate::new().value(Large, true);
+    }
+}
@@ -3,16 +3,29 @@
 //! Defines a `Queue<T, P>` that is intended to store a queue of items of type
 //! T - sorted by `f64` time and definable priority `P` - called 'plans'.
 //! This queue has methods for adding plans, cancelling plans, and retrieving
-//! the earliest plan in the queue. Adding a plan is *O*(log(*n*)) while
-//! cancellation and retrieval are *O*(1).
+//! the earliest plan in the queue. Adding a plan is *O*(log(*n*)), or *O*(1)
+//! when there is already a pending plan with the same time and priority,
+//! while cancellation and retrieval are *O*(1). The number of pending
+//! plans, the number at a given time, and the time of the next plan can be
+//! found in *O*(1), though counting the plans at a time is *O*(*n*) the
+//! first time.
 //!
 //! This queue is used by `Context` to store future events where some callback
 //! closure `FnOnce(&mut Context)` will be executed at a given point in time.
+//!
+//! Plans can also be added with a key of any hashable type, such as
+//! `(PersonId, &'static str)`, so that code which didn't add a plan can
+//! find and cancel it, e.g., to cancel a person's recovery when they die.
+//! Any number of plans can share a key.
 
 use log::trace;
 use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
     cmp::Ordering,
-    collections::{BinaryHeap, HashMap},
+    collections::{hash_map, BinaryHeap, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    rc::Rc,
 };
 
 /// A priority queue that stores arbitrary data sorted by time
@@ -25,24 +38,48 @@ use std::{
 /// and priority then the plan that is scheduled first (i.e., that has the
 /// lowest id) is placed earlier.
 ///
-/// The time, plan id, and priority are stored in a binary heap of `Entry<P>`
-/// objects. The data payload of the event is stored in a hash map by plan id.
-/// Plan cancellation occurs by removing the corresponding entry from the data
-/// hash map.
-pub struct Queue<T, P: Eq + PartialEq + Ord> {
+/// Plans with the same time and priority are kept in a first-in, first-out
+/// bucket of plan ids, in a hash map keyed by the time and priority. The
+/// binary heap holds one `Entry<P>` per bucket, so only the first plan at a
+/// time and priority costs *O*(log(*n*)) to add, and the rest cost *O*(1)
+/// however they are interleaved with plans at other times. The data payload
+/// of the event is stored in a hash map by plan id. Plan cancellation occurs
+/// by removing the corresponding entry from the data hash map. Cancelled
+/// plans at the front of the earliest bucket are dropped straight away, so
+/// the earliest plan id is always a pending plan.
+#[allow(clippy::struct_field_names)]
+pub struct Queue<T, P: Eq + PartialEq + Ord + Hash + Clone> {
     queue: BinaryHeap<Entry<P>>,
-    data_map: HashMap<u64, T>,
+    // The ids of the plans at each time and priority, keyed by the bits of
+    // the time, in the order they were added. A bucket is in the map exactly
+    // when its entry is in the heap.
+    buckets: HashMap<(u64, P), VecDeque<u64>>,
+    queue_len: usize,
+    data_map: HashMap<u64, (f64, T)>,
+    // The number of pending plans at each time, keyed by the bits of the
+    // time. This is only kept once it's been asked for, so that queues
+    // that don't need it don't pay for it.
+    time_counts: RefCell<Option<HashMap<u64, usize>>>,
     plan_counter: u64,
+    // The pending plans with each key, and the key of each pending plan
+    // that has one
+    keyed_plans: HashMap<AnyKey, Vec<u64>>,
+    plan_keys: HashMap<u64, AnyKey>,
 }
 
-impl<T, P: Eq + PartialEq + Ord> Queue<T, P> {
+impl<T, P: Eq + PartialEq + Ord + Hash + Clone> Queue<T, P> {
     /// Create a new empty `Queue<T>`
     #[must_use]
     pub fn new() -> Queue<T, P> {
         Queue {
             queue: BinaryHeap::new(),
+            buckets: HashMap::new(),
+            queue_len: 0,
             data_map: HashMap::new(),
+            time_counts: RefCell::new(None),
             plan_counter: 0,
+            keyed_plans: HashMap::new(),
+            plan_keys: HashMap::new(),
         }
     }
 
@@ -51,15 +88,24 @@ impl<T, P: Eq + PartialEq + Ord> Queue<T, P> {
     /// Returns a `PlanId` for the newly-added plan that can be used to cancel it
     /// if needed.
     pub fn add_plan(&mut self, time: f64, data: T, priority: P) -> PlanId {
-        trace!("adding plan at {}", time);
+        trace!("adding plan at {time}");
         // Add plan to queue, store data, and increment counter
         let plan_id = self.plan_counter;
-        self.queue.push(Entry {
-            time,
-            plan_id,
-            priority,
-        });
-        self.data_map.insert(plan_id, data);
+        match self.buckets.entry((time_key(time), priority)) {
+            hash_map::Entry::Occupied(mut bucket) => bucket.get_mut().push_back(plan_id),
+            hash_map::Entry::Vacant(bucket) => {
+                self.queue.push(Entry {
+                    time,
+                    priority: bucket.key().1.clone(),
+                });
+                bucket.insert(VecDeque::from([plan_id]));
+            }
+        }
+        self.queue_len += 1;
+        self.data_map.insert(plan_id, (time, data));
+        if let Some(time_counts) = self.time_counts.get_mut() {
+            *time_counts.entry(time_key(time)).or_default() += 1;
+        }
         self.plan_counter += 1;
         PlanId(plan_id)
     }
@@ -71,63 +117,222 @@ impl<T, P: Eq + PartialEq + Ord> Queue<T, P> {
     /// This function panics if you cancel a plan which has already
     /// been cancelled or executed.
     pub fn cancel_plan(&mut self, plan_id: &PlanId) {
-        trace!("cancel plan {:?}", plan_id);
+        assert!(self.try_cancel_plan(plan_id), "Plan does not exist");
+    }
+
+    /// Cancel a plan that has been added to the queue if it is still pending
+    ///
+    /// Returns `true` if the plan was cancelled, or `false` if it has
+    /// already been cancelled or retrieved.
+    pub fn try_cancel_plan(&mut self, plan_id: &PlanId) -> bool {
+        trace!("cancel plan {plan_id:?}");
         // Delete the plan from the map, but leave in the queue
         // It will be skipped when the plan is popped from the queue
-        self.data_map
-            .remove(&plan_id.0)
-            .expect("Plan does not exist");
+        let Some((time, _)) = self.data_map.remove(&plan_id.0) else {
+            return false;
+        };
+        self.remove_time(time);
+        self.remove_plan_key(plan_id.0);
+        self.drop_cancelled();
+        true
+    }
+
+    /// Add a plan to the queue at the specified time with a key that can
+    /// be used to find it with [`Queue::has_plan_with_key()`] or cancel it
+    /// with [`Queue::cancel_plans_with_key()`]
+    ///
+    /// Returns a `PlanId` for the newly-added plan.
+    pub fn add_plan_with_key<K: Hash + Eq + 'static>(
+        &mut self,
+        time: f64,
+        data: T,
+        priority: P,
+        key: K,
+    ) -> PlanId {
+        let plan_id = self.add_plan(time, data, priority);
+        let key = AnyKey(Rc::new(key));
+        self.keyed_plans
+            .entry(key.clone())
+            .or_default()
+            .push(plan_id.0);
+        self.plan_keys.insert(plan_id.0, key);
+        plan_id
+    }
+
+    /// Cancel all pending plans that were added with `key`
+    ///
+    /// Returns the number of plans that were cancelled, which is 0 if there
+    /// aren't any.
+    pub fn cancel_plans_with_key<K: Hash + Eq + Clone + 'static>(&mut self, key: &K) -> usize {
+        let Some(plan_ids) = self.keyed_plans.remove(&AnyKey(Rc::new(key.clone()))) else {
+            return 0;
+        };
+        trace!("cancel {} plans with key", plan_ids.len());
+        for plan_id in &plan_ids {
+            if let Some((time, _)) = self.data_map.remove(plan_id) {
+                self.remove_time(time);
+            }
+            self.plan_keys.remove(plan_id);
+        }
+        self.drop_cancelled();
+        plan_ids.len()
+    }
+
+    /// Returns whether there are any pending plans that were added with
+    /// `key`
+    #[must_use]
+    pub fn has_plan_with_key<K: Hash + Eq + Clone + 'static>(&self, key: &K) -> bool {
+        self.keyed_plans.contains_key(&AnyKey(Rc::new(key.clone())))
+    }
+
+    // Forget the key of a plan that is no longer pending.
+    fn remove_plan_key(&mut self, plan_id: u64) {
+        let Some(key) = self.plan_keys.remove(&plan_id) else {
+            return;
+        };
+        let plan_ids = self.keyed_plans.get_mut(&key).unwrap();
+        plan_ids.retain(|id| *id != plan_id);
+        if plan_ids.is_empty() {
+            self.keyed_plans.remove(&key);
+        }
+    }
+
+    // Forget the time of a plan that is no longer pending.
+    fn remove_time(&mut self, time: f64) {
+        let Some(time_counts) = self.time_counts.get_mut() else {
+            return;
+        };
+        let key = time_key(time);
+        let count = time_counts.get_mut(&key).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            time_counts.remove(&key);
+        }
+    }
+
+    // Drop the cancelled plans at the front of the earliest bucket, and
+    // any buckets that are left empty, so that the earliest plan id is a
+    // pending plan.
+    fn drop_cancelled(&mut self) {
+        // Every plan that's still queued is pending, so there's nothing
+        // to drop.
+        if self.queue_len == self.data_map.len() {
+            return;
+        }
+        while let Some(entry) = self.queue.peek() {
+            let key = (time_key(entry.time), entry.priority.clone());
+            let bucket = self.buckets.get_mut(&key).unwrap();
+            while let Some(plan_id) = bucket.front() {
+                if self.data_map.contains_key(plan_id) {
+                    return;
+                }
+                bucket.pop_front();
+                self.queue_len -= 1;
+            }
+            self.buckets.remove(&key);
+            self.queue.pop();
+        }
     }
 
     #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.queue.is_empty()
+        self.queue_len == 0
     }
 
     /// Retrieve the earliest plan in the queue
     ///
     /// Returns the next plan if it exists or else `None` if the queue is empty
+    #[allow(clippy::missing_panics_doc)]
     pub fn get_next_plan(&mut self) -> Option<Plan<T>> {
         trace!("getting next plan");
-        loop {
-            // Pop from queue until we find a plan with data or queue is empty
-            match self.queue.pop() {
-                Some(entry) => {
-                    // Skip plans that have been cancelled and thus have no data
-                    if let Some(data) = self.data_map.remove(&entry.plan_id) {
-                        return Some(Plan {
-                            time: entry.time,
-                            data,
-                        });
-                    }
-                }
-                None => {
-                    return None;
-                }
-            }
+        // Cancelled plans have already been dropped from the front of the
+        // earliest bucket, so its first plan is pending.
+        let entry = self.queue.peek()?;
+        let key = (time_key(entry.time), entry.priority.clone());
+        let bucket = self.buckets.get_mut(&key).unwrap();
+        let plan_id = bucket.pop_front().unwrap();
+        if bucket.is_empty() {
+            self.buckets.remove(&key);
+            self.queue.pop();
         }
+        self.queue_len -= 1;
+        let (time, data) = self.data_map.remove(&plan_id).unwrap();
+        self.remove_time(time);
+        self.remove_plan_key(plan_id);
+        self.drop_cancelled();
+        Some(Plan { time, data })
+    }
+
+    /// Get the time of the earliest plan in the queue without removing it
+    ///
+    /// Returns the time of the next plan if it exists or else `None` if the
+    /// queue is empty
+    #[must_use]
+    pub fn next_plan_time(&self) -> Option<f64> {
+        self.queue.peek().map(|entry| entry.time)
+    }
+
+    /// Get the priority of the earliest plan in the queue without removing
+    /// it, like [`Queue::next_plan_time()`]
+    pub(crate) fn next_plan_priority(&self) -> Option<&P> {
+        self.queue.peek().map(|entry| &entry.priority)
+    }
+
+    /// The number of plans at exactly `time` that haven't been cancelled or
+    /// retrieved yet
+    ///
+    /// The first call counts the plans at every time, which is *O*(*n*).
+    /// The counts are then kept up to date, so later calls are *O*(1).
+    #[must_use]
+    pub fn count_plans_at_time(&self, time: f64) -> usize {
+        let mut time_counts = self.time_counts.borrow_mut();
+        let time_counts = time_counts.get_or_insert_with(|| {
+            let mut time_counts = HashMap::new();
+            for (time, _) in self.data_map.values() {
+                *time_counts.entry(time_key(*time)).or_default() += 1;
+            }
+            time_counts
+        });
+        time_counts.get(&time_key(time)).copied().unwrap_or(0)
     }
 
     #[doc(hidden)]
     pub(crate) fn remaining_plan_count(&self) -> usize {
-        self.queue.len()
+        self.queue_len
     }
+
+    /// The number of plans that haven't been cancelled or retrieved yet
+    #[must_use]
+    pub fn pending_plan_count(&self) -> usize {
+        self.data_map.len()
+    }
+
+    /// The ids of the plans that haven't been cancelled or retrieved yet
+    pub(crate) fn pending_plan_ids(&self) -> impl Iterator<Item = PlanId> + '_ {
+        self.data_map.keys().map(|plan_id| PlanId(*plan_id))
+    }
+}
+
+// The key of `time` in `time_counts`. Zero is the same whatever its sign.
+fn time_key(time: f64) -> u64 {
+    (time + 0.0).to_bits()
 }
 
-impl<T, P: Eq + PartialEq + Ord> Default for Queue<T, P> {
+impl<T, P: Eq + PartialEq + Ord + Hash + Clone> Default for Queue<T, P> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-/// A time, id, and priority object used to order plans in the `Queue<T>`
+/// The time and priority of a bucket of plans, used to order the buckets in
+/// the `Queue<T>`
 ///
-/// `Entry` objects are sorted in increasing order of time, priority and then
-/// plan id
+/// `Entry` objects are sorted in increasing order of time and then priority.
+/// There is only one bucket with each time and priority, so no two entries
+/// in the heap are equal.
 #[derive(PartialEq, Debug)]
 struct Entry<P: Eq + PartialEq + Ord> {
     time: f64,
-    plan_id: u64,
     priority: P,
 }
 
@@ -139,29 +344,57 @@ impl<P: Eq + PartialEq + Ord> PartialOrd for Entry<P> {
     }
 }
 
-/// Entry objects are ordered in increasing order by time, priority, and then
-/// plan id
+/// Entry objects are ordered in increasing order by time and then priority
 impl<P: Eq + PartialEq + Ord> Ord for Entry<P> {
     fn cmp(&self, other: &Self) -> Ordering {
         let time_ordering = self.time.partial_cmp(&other.time).unwrap().reverse();
         match time_ordering {
-            // Break time ties in order of priority and then plan id
-            Ordering::Equal => {
-                let priority_ordering = self
-                    .priority
-                    .partial_cmp(&other.priority)
-                    .unwrap()
-                    .reverse();
-                match priority_ordering {
-                    Ordering::Equal => self.plan_id.cmp(&other.plan_id).reverse(),
-                    _ => priority_ordering,
-                }
-            }
+            // Break time ties in order of priority
+            Ordering::Equal => self.priority.cmp(&other.priority).reverse(),
             _ => time_ordering,
         }
     }
 }
 
+// A plan key of any type. Keys of different types are never equal.
+#[derive(Clone)]
+struct AnyKey(Rc<dyn DynKey>);
+
+trait DynKey {
+    fn as_any(&self) -> &dyn Any;
+    fn dyn_eq(&self, other: &dyn DynKey) -> bool;
+    fn dyn_hash(&self, state: &mut dyn Hasher);
+}
+
+impl<K: Hash + Eq + 'static> DynKey for K {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dyn_eq(&self, other: &dyn DynKey) -> bool {
+        other.as_any().downcast_ref::<K>() == Some(self)
+    }
+
+    fn dyn_hash(&self, mut state: &mut dyn Hasher) {
+        TypeId::of::<K>().hash(&mut state);
+        self.hash(&mut state);
+    }
+}
+
+impl PartialEq for AnyKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.dyn_eq(&*other.0)
+    }
+}
+
+impl Eq for AnyKey {}
+
+impl Hash for AnyKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.dyn_hash(state);
+    }
+}
+
 /// A unique identifier for a plan added to a `Queue<T>`
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 pub struct PlanId(pub(crate) u64);
@@ -175,7 +408,9 @@ pub struct Plan<T> {
 #[cfg(test)]
 #[allow(clippy::float_cmp)]
 mod tests {
-    use super::Queue;
+    use super::{PlanId, Queue};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
     #[test]
     fn empty_queue() {
@@ -297,6 +532,43 @@ mod tests {
         assert!(plan_queue.get_next_plan().is_none());
     }
 
+    #[test]
+    fn next_plan_time() {
+        let mut plan_queue = Queue::new();
+        assert!(plan_queue.next_plan_time().is_none());
+        let plan_to_cancel = plan_queue.add_plan(1.0, 1, ());
+        plan_queue.add_plan(2.0, 2, ());
+        assert_eq!(plan_queue.next_plan_time(), Some(1.0));
+        plan_queue.cancel_plan(&plan_to_cancel);
+        assert_eq!(plan_queue.next_plan_time(), Some(2.0));
+
+        let next_plan = plan_queue.get_next_plan().unwrap();
+        assert_eq!(next_plan.data, 2);
+        assert!(plan_queue.next_plan_time().is_none());
+    }
+
+    #[test]
+    fn count_plans_at_time() {
+        let mut plan_queue = Queue::new();
+        let first = plan_queue.add_plan(1.0, 1, ());
+        plan_queue.add_plan(1.0, 2, ());
+        plan_queue.add_plan(2.0, 3, ());
+        plan_queue.add_plan(-0.0, 0, ());
+        assert_eq!(plan_queue.count_plans_at_time(1.0), 2);
+        assert_eq!(plan_queue.count_plans_at_time(0.0), 1);
+        assert_eq!(plan_queue.count_plans_at_time(3.0), 0);
+        assert_eq!(plan_queue.pending_plan_count(), 4);
+
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 0);
+        plan_queue.cancel_plan(&first);
+        assert_eq!(plan_queue.count_plans_at_time(1.0), 1);
+        assert_eq!(plan_queue.pending_plan_count(), 2);
+        assert_eq!(plan_queue.next_plan_time(), Some(1.0));
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 2);
+        assert_eq!(plan_queue.count_plans_at_time(1.0), 0);
+        assert_eq!(plan_queue.next_plan_time(), Some(2.0));
+    }
+
     #[test]
     #[should_panic(expected = "Plan does not exist")]
     fn cancel_invalid_plan() {
@@ -308,4 +580,163 @@ mod tests {
         assert!(plan_queue.is_empty());
         plan_queue.cancel_plan(&plan_to_cancel);
     }
+
+    #[test]
+    fn try_cancel_plan() {
+        let mut plan_queue = Queue::new();
+        let first = plan_queue.add_plan(1.0, 1, ());
+        let second = plan_queue.add_plan(2.0, 2, ());
+        assert!(plan_queue.try_cancel_plan(&second));
+        // Already cancelled
+        assert!(!plan_queue.try_cancel_plan(&second));
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 1);
+        // Already retrieved
+        assert!(!plan_queue.try_cancel_plan(&first));
+        assert!(plan_queue.get_next_plan().is_none());
+    }
+
+    #[test]
+    fn keyed_plans() {
+        let mut plan_queue = Queue::new();
+        plan_queue.add_plan_with_key(1.0, 1, (), (7, "recovery"));
+        plan_queue.add_plan_with_key(2.0, 2, (), (7, "recovery"));
+        plan_queue.add_plan_with_key(3.0, 3, (), (8, "recovery"));
+        // A key of a different type with the same hash contents
+        plan_queue.add_plan_with_key(4.0, 4, (), (7u64, "recovery"));
+        let unkeyed = plan_queue.add_plan(5.0, 5, ());
+        assert!(plan_queue.has_plan_with_key(&(7, "recovery")));
+        assert!(!plan_queue.has_plan_with_key(&(7, "waning")));
+
+        // Keys are forgotten when their plans are retrieved
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 1);
+        assert!(plan_queue.has_plan_with_key(&(7, "recovery")));
+        assert_eq!(plan_queue.cancel_plans_with_key(&(7, "recovery")), 1);
+        assert!(!plan_queue.has_plan_with_key(&(7, "recovery")));
+        assert!(plan_queue.has_plan_with_key(&(7u64, "recovery")));
+        assert_eq!(plan_queue.cancel_plans_with_key(&(7, "recovery")), 0);
+
+        // ... or cancelled by id
+        let id = plan_queue.add_plan_with_key(6.0, 6, (), (9, "recovery"));
+        assert!(plan_queue.try_cancel_plan(&id));
+        assert!(!plan_queue.has_plan_with_key(&(9, "recovery")));
+
+        assert!(plan_queue.try_cancel_plan(&unkeyed));
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 3);
+        assert!(!plan_queue.has_plan_with_key(&(8, "recovery")));
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 4);
+        assert!(plan_queue.get_next_plan().is_none());
+        assert!(plan_queue.keyed_plans.is_empty());
+        assert!(plan_queue.plan_keys.is_empty());
+    }
+
+    #[test]
+    fn plans_added_to_draining_bucket_go_last() {
+        let mut plan_queue = Queue::new();
+        plan_queue.add_plan(1.0, 1, 0);
+        plan_queue.add_plan(1.0, 2, 0);
+        plan_queue.add_plan(1.0, 3, 1);
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 1);
+        // Same time and priority as the bucket being drained
+        plan_queue.add_plan(1.0, 4, 0);
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 2);
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 4);
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 3);
+        assert!(plan_queue.is_empty());
+    }
+
+    #[test]
+    fn cancel_plans_within_bucket() {
+        let mut plan_queue = Queue::new();
+        let ids: Vec<PlanId> = (0..5).map(|i| plan_queue.add_plan(1.0, i, ())).collect();
+        plan_queue.cancel_plan(&ids[0]);
+        plan_queue.cancel_plan(&ids[2]);
+        plan_queue.cancel_plan(&ids[4]);
+        assert_eq!(plan_queue.next_plan_time(), Some(1.0));
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 1);
+        assert_eq!(plan_queue.get_next_plan().unwrap().data, 3);
+        assert!(plan_queue.get_next_plan().is_none());
+        assert!(plan_queue.is_empty());
+    }
+
+    #[test]
+    fn interleaved_adds_share_buckets() {
+        let mut plan_queue = Queue::new();
+        for i in 0..6 {
+            // Alternate between two times, and two priorities at the later
+            // time
+            let (time, priority) = match i % 3 {
+                0 => (2.0, 0),
+                1 => (1.0, 0),
+                _ => (2.0, 1),
+            };
+            plan_queue.add_plan(time, i, priority);
+        }
+        // Only the first plan at each time and priority adds an entry.
+        assert_eq!(plan_queue.queue.len(), 3);
+        assert_eq!(plan_queue.buckets.len(), 3);
+        let order: Vec<_> = std::iter::from_fn(|| plan_queue.get_next_plan())
+            .map(|plan| plan.data)
+            .collect();
+        assert_eq!(order, vec![1, 4, 0, 3, 2, 5]);
+        assert!(plan_queue.buckets.is_empty());
+    }
+
+    // Compare the queue against a sorted list of (time, priority, id) under
+    // a random mix of additions, cancellations, and retrievals.
+    #[test]
+    fn matches_reference_ordering() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut plan_queue = Queue::new();
+            let mut reference: Vec<(f64, u8, u64)> = Vec::new();
+            let mut current_time = 0.0;
+
+            for _ in 0..2000 {
+                match rng.gen_range(0..10) {
+                    0..=4 => {
+                        let time = current_time + f64::from(rng.gen_range(0..3u8)) * 0.5;
+                        let priority = rng.gen_range(0..3u8);
+                        let plan_id = plan_queue.add_plan(time, plan_queue.plan_counter, priority);
+                        reference.push((time, priority, plan_id.0));
+                    }
+                    5 if !reference.is_empty() => {
+                        let (_, _, id) = reference.swap_remove(rng.gen_range(0..reference.len()));
+                        plan_queue.cancel_plan(&PlanId(id));
+                    }
+                    _ => {
+                        reference.sort_by(|a, b| {
+                            a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2))
+                        });
+                        let expected = if reference.is_empty() {
+                            None
+                        } else {
+                            Some(reference.remove(0))
+                        };
+                        assert_eq!(plan_queue.next_plan_time(), expected.map(|e| e.0));
+                        assert_eq!(
+                            plan_queue.pending_plan_count(),
+                            reference.len() + usize::from(expected.is_some())
+                        );
+                        assert_eq!(
+                            plan_queue.count_plans_at_time(current_time + 0.5),
+                            reference
+                                .iter()
+                                .filter(|e| e.0 == current_time + 0.5)
+                                .count()
+                                + usize::from(expected.is_some_and(|e| e.0 == current_time + 0.5))
+                        );
+                        let plan = plan_queue.get_next_plan();
+                        assert_eq!(
+                            plan.as_ref().map(|p| (p.time, p.data)),
+                            expected.map(|e| (e.0, e.2))
+                        );
+                        if let Some(plan) = plan {
+                            current_time = plan.time;
+                        }
+                        assert_eq!(plan_queue.queue.len(), plan_queue.buckets.len());
+                    }
+                }
+            }
+        }
+    }
 }
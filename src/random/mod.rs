@@ -0,0 +1,774 @@
+use crate::context::Context;
+use crate::error::IxaError;
+use crate::people::PersonId;
+use log::trace;
+use rand::distributions::uniform::{SampleRange, SampleUniform};
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_distr::{Exp, Gamma, LogNormal, Poisson};
+use serde::{Deserialize, Serialize};
+use std::any::{Any, TypeId};
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+
+pub mod portable;
+pub use portable::PortableRng;
+
+/// Use this to define a unique type which will be used as a key to retrieve
+/// an independent rng instance when calling `.get_rng`.
+#[macro_export]
+macro_rules! define_rng {
+    ($random_id:ident) => {
+        #[derive(Copy, Clone)]
+        struct $random_id;
+
+        impl $crate::random::RngId for $random_id {
+            type RngType = $crate::random::IxaRng;
+
+            fn get_name() -> &'static str {
+                stringify!($random_id)
+            }
+        }
+
+        // This ensures that you can't define two RngIds with the same name
+        paste::paste! {
+            #[doc(hidden)]
+            #[no_mangle]
+            #[allow(non_upper_case_globals)]
+            pub static [<rng_name_duplication_guard_ $random_id>]: () = ();
+        }
+    };
+}
+pub use define_rng;
+
+pub trait RngId: Copy + Clone {
+    type RngType: SeedableRng;
+    fn get_name() -> &'static str;
+}
+
+/// The random number generator used for each `RngId` defined with
+/// [`define_rng!()`]. This is the `ChaCha12` generator that `StdRng` wraps,
+/// so it produces the same numbers from the same seed, but its state can
+/// be saved in a checkpoint (see [`crate::checkpoint`]).
+#[cfg(not(feature = "portable-rng"))]
+pub type IxaRng = rand_chacha::ChaCha12Rng;
+
+/// The random number generator used for each `RngId` defined with
+/// [`define_rng!()`]. The `portable-rng` feature is enabled, so this is
+/// [`PortableRng`] (see [`portable`]).
+#[cfg(feature = "portable-rng")]
+pub type IxaRng = PortableRng;
+
+// This is a wrapper which allows for future support for different types of
+// random number generators (anything that implements SeedableRng is valid).
+struct RngHolder {
+    name: &'static str,
+    rng: Box<dyn Any>,
+}
+
+struct RngData {
+    base_seed: u64,
+    rng_holders: RefCell<HashMap<TypeId, RngHolder>>,
+    // Generators restored from a checkpoint that haven't been used since,
+    // keyed by the name of their RngId
+    restored_rngs: RefCell<HashMap<String, IxaRng>>,
+}
+
+// Registers a data container which stores:
+// * base_seed: A base seed for all rngs
+// * rng_holders: A map of rngs, keyed by their RngId. Note that this is
+//   stored in a RefCell to allow for mutable borrow without requiring a
+//   mutable borrow of the Context itself.
+crate::context::define_data_plugin!(
+    RngPlugin,
+    RngData,
+    RngData {
+        base_seed: 0,
+        rng_holders: RefCell::new(HashMap::new()),
+        restored_rngs: RefCell::new(HashMap::new()),
+    }
+);
+
+pub(crate) fn plugin_type_id() -> TypeId {
+    TypeId::of::<RngPlugin>()
+}
+
+/// The base seed and the state of every generator, as saved in a checkpoint
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SavedRngs {
+    base_seed: u64,
+    rngs: Vec<(String, IxaRng)>,
+}
+
+/// Saves the base seed and the states of the generators, or returns
+/// `None` if `init_random()` hasn't been called.
+pub(crate) fn save_rngs(context: &Context) -> Result<Option<SavedRngs>, IxaError> {
+    let Some(data_container) = context.get_data_container(RngPlugin) else {
+        return Ok(None);
+    };
+    let mut rngs: Vec<(String, IxaRng)> = data_container
+        .restored_rngs
+        .borrow()
+        .iter()
+        .map(|(name, rng)| (name.clone(), rng.clone()))
+        .collect();
+    for holder in data_container.rng_holders.borrow().values() {
+        let rng = holder.rng.downcast_ref::<IxaRng>().ok_or_else(|| {
+            IxaError::IxaError(format!(
+                "RNG {} doesn't use IxaRng, so its state can't be checkpointed",
+                holder.name
+            ))
+        })?;
+        rngs.push((holder.name.to_string(), rng.clone()));
+    }
+    rngs.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(Some(SavedRngs {
+        base_seed: data_container.base_seed,
+        rngs,
+    }))
+}
+
+/// Restores the generators saved with `save_rngs()`. Each one replaces
+/// the generator for the `RngId` with the same name when it's first used.
+pub(crate) fn restore_rngs(context: &mut Context, saved: SavedRngs) {
+    context.init_random(saved.base_seed);
+    let data_container = context.get_data_container_mut(RngPlugin);
+    *data_container.restored_rngs.get_mut() = saved.rngs.into_iter().collect();
+}
+
+// One step of the SplitMix64 generator, used as a mixing function.
+fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Mixes `bytes` into `base_seed` with `SplitMix64`, eight little-endian
+/// bytes at a time, followed by the length of `bytes`. Unlike a `Hash`
+/// implementation, the result is the same on every platform and version
+/// of Rust.
+pub(crate) fn mix_seed(base_seed: u64, bytes: &[u8]) -> u64 {
+    let mut state = splitmix64(base_seed);
+    for chunk in bytes.chunks(8) {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        state = splitmix64(state ^ u64::from_le_bytes(word));
+    }
+    splitmix64(state ^ bytes.len() as u64)
+}
+
+/// The seed of the generator for the `RngId` named `name`. It depends only
+/// on the base seed and the name, so a stream doesn't change when other
+/// `RngId`s are defined, removed, or first used in a different order. The
+/// name is mixed into the base seed, rather than its hash being added to
+/// it, so that no two base seeds give the same stream to two different
+/// `RngId`s.
+fn stream_seed(base_seed: u64, name: &str) -> u64 {
+    mix_seed(base_seed, name.as_bytes())
+}
+
+/// Gets a mutable reference to the random number generator associated with the given
+/// `RngId`. If the Rng has not been used before, one will be created with the base seed
+/// you defined in `init`. Note that this will panic if `init` was not called yet.
+fn get_rng<R: RngId + 'static>(context: &Context) -> RefMut<'_, R::RngType> {
+    let data_container = context
+        .get_data_container(RngPlugin)
+        .expect("You must initialize the random number generator with a base seed");
+
+    let rng_holders = data_container.rng_holders.try_borrow_mut().unwrap();
+    RefMut::map(rng_holders, |holders| {
+        holders
+            .entry(TypeId::of::<R>())
+            // Create a new rng holder if it doesn't exist yet
+            .or_insert_with(|| {
+                trace!(
+                    "creating new RNG (seed={}) for type id {:?}",
+                    data_container.base_seed,
+                    TypeId::of::<R>()
+                );
+                let mut rng: Box<dyn Any> = Box::new(R::RngType::seed_from_u64(stream_seed(
+                    data_container.base_seed,
+                    R::get_name(),
+                )));
+                // Continue from the state saved in a checkpoint, if any
+                if let Some(restored) = rng.downcast_mut::<IxaRng>() {
+                    if let Some(saved) = data_container
+                        .restored_rngs
+                        .borrow_mut()
+                        .remove(R::get_name())
+                    {
+                        *restored = saved;
+                    }
+                }
+                RngHolder {
+                    name: R::get_name(),
+                    rng,
+                }
+            })
+            .rng
+            .downcast_mut::<R::RngType>()
+            .unwrap()
+    })
+}
+
+/// A digest of the states of all of the generators that have been used,
+/// for checking that two runs consumed the same random numbers. Each
+/// generator is cloned and the digest is taken from a draw from the clone,
+/// so the simulation's streams are unaffected.
+pub(crate) fn rng_state_digest(context: &Context) -> u64 {
+    let Some(data_container) = context.get_data_container(RngPlugin) else {
+        return 0;
+    };
+    let mut states: Vec<u64> = data_container
+        .rng_holders
+        .borrow()
+        .values()
+        .filter_map(|holder| holder.rng.downcast_ref::<IxaRng>())
+        .map(|rng| rng.clone().next_u64())
+        .collect();
+    states.sort_unstable();
+    fxhash::hash64(&states)
+}
+
+/// Draws the initial value of a property for a person from a generator
+/// seeded only by the base seed, the property name, and the person, so
+/// that the value doesn't depend on the order in which people are accessed
+/// and doesn't consume draws from any model `RngId`. This is used by
+/// `define_person_property!` with `init_distribution`.
+///
+/// Note that this will panic if `init_random` was not called yet.
+#[doc(hidden)]
+pub fn sample_person_initial_value<T>(
+    context: &Context,
+    property_name: &str,
+    person_id: PersonId,
+    sampler: impl FnOnce(&mut StdRng) -> T,
+) -> T {
+    let data_container = context
+        .get_data_container(RngPlugin)
+        .expect("You must initialize the random number generator with a base seed");
+    let seed = person_initial_value_seed(data_container.base_seed, property_name, person_id);
+    sampler(&mut StdRng::seed_from_u64(seed))
+}
+
+/// The seed of the generator for a person's initial value of the property
+/// named `property_name`. The person's index is mixed in as a `u64`, so
+/// the seed is the same on 32- and 64-bit platforms.
+fn person_initial_value_seed(base_seed: u64, property_name: &str, person_id: PersonId) -> u64 {
+    mix_seed(
+        stream_seed(base_seed, property_name),
+        &(person_id.0 as u64).to_le_bytes(),
+    )
+}
+
+// This is a trait exension on Context
+/// Each `RngId` defined with [`define_rng!()`] has its own stream of random
+/// numbers, seeded from the base seed and the `RngId`'s name alone. So a
+/// stream stays the same when other `RngId`s are added, removed, or used
+/// in a different order, and only changes if the base seed or the name
+/// does. (Streams were seeded differently before this guarantee was made,
+/// so a base seed doesn't give the same draws as in older versions.)
+pub trait ContextRandomExt {
+    fn init_random(&mut self, base_seed: u64);
+
+    /// Gets a random sample from the random number generator associated with the given
+    /// `RngId` by applying the specified sampler function. If the Rng has not been used
+    /// before, one will be created with the base seed you defined in `set_base_random_seed`.
+    /// Note that this will panic if `set_base_random_seed` was not called yet.
+    fn sample<R: RngId + 'static, T>(
+        &self,
+        _rng_type: R,
+        sampler: impl FnOnce(&mut R::RngType) -> T,
+    ) -> T;
+
+    /// Gets a random sample from the specified distribution using a random number generator
+    /// associated with the given `RngId`. If the Rng has not been used before, one will be
+    /// created with the base seed you defined in `set_base_random_seed`.
+    /// Note that this will panic if `set_base_random_seed` was not called yet.
+    fn sample_distr<R: RngId + 'static, T>(
+        &self,
+        _rng_type: R,
+        distribution: impl Distribution<T>,
+    ) -> T
+    where
+        R::RngType: Rng;
+
+    /// Gets a random sample within the range provided by `range`
+    /// using the generator associated with the given `RngId`.
+    /// Note that this will panic if `set_base_random_seed` was not called yet.
+    fn sample_range<R: RngId + 'static, S, T>(&self, rng_type: R, range: S) -> T
+    where
+        R::RngType: Rng,
+        S: SampleRange<T>,
+        T: SampleUniform;
+
+    /// Gets a random boolean value which is true with probability `p`
+    /// using the generator associated with the given `RngId`.
+    /// Note that this will panic if `set_base_random_seed` was not called yet.
+    fn sample_bool<R: RngId + 'static>(&self, rng_id: R, p: f64) -> bool
+    where
+        R::RngType: Rng;
+
+    /// Draws a random entry out of the list provided in `weights`
+    /// with the given weights using the generator associated with the
+    /// given `RngId`.  Note that this will panic if
+    /// `set_base_random_seed` was not called yet.
+    fn sample_weighted<R: RngId + 'static, T>(&self, rng_id: R, weights: &[T]) -> usize
+    where
+        R::RngType: Rng,
+        T: Clone + Default + SampleUniform + for<'a> std::ops::AddAssign<&'a T> + PartialOrd;
+
+    /// Gets a random sample from the exponential distribution with the
+    /// given `rate`, e.g., the time until recovery with a rate of one
+    /// over the mean infectious period, using the generator associated
+    /// with the given `RngId`. With the `portable-rng` feature, this uses
+    /// [`portable::exponential()`], so it gives the same results on every
+    /// platform.
+    ///
+    /// # Errors
+    /// Returns an error if `rate` isn't positive and finite.
+    fn sample_exponential<R: RngId + 'static>(&self, rng_id: R, rate: f64) -> Result<f64, IxaError>
+    where
+        R::RngType: Rng;
+
+    /// Gets a random sample from the gamma distribution with the given
+    /// `shape` and `scale`, so that the mean is `shape * scale`, using the
+    /// generator associated with the given `RngId`.
+    ///
+    /// # Errors
+    /// Returns an error if `shape` or `scale` isn't positive and finite.
+    fn sample_gamma<R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        shape: f64,
+        scale: f64,
+    ) -> Result<f64, IxaError>
+    where
+        R::RngType: Rng;
+
+    /// Gets a random sample from the log-normal distribution whose
+    /// logarithm has mean `mu` and standard deviation `sigma`, using the
+    /// generator associated with the given `RngId`.
+    ///
+    /// # Errors
+    /// Returns an error if `mu` isn't finite, or if `sigma` is negative or
+    /// isn't finite.
+    fn sample_lognormal<R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        mu: f64,
+        sigma: f64,
+    ) -> Result<f64, IxaError>
+    where
+        R::RngType: Rng;
+
+    /// Gets a random count from the Poisson distribution with mean
+    /// `lambda`, using the generator associated with the given `RngId`.
+    ///
+    /// # Errors
+    /// Returns an error if `lambda` isn't positive and finite.
+    fn sample_poisson<R: RngId + 'static>(&self, rng_id: R, lambda: f64) -> Result<u64, IxaError>
+    where
+        R::RngType: Rng;
+}
+
+// Returns an error naming the distribution and parameter if `valid` is false
+fn check_parameter(
+    distribution: &str,
+    parameter: &str,
+    value: f64,
+    valid: bool,
+) -> Result<(), IxaError> {
+    if valid {
+        Ok(())
+    } else {
+        Err(IxaError::IxaError(format!(
+            "Invalid {parameter} {value} for the {distribution} distribution"
+        )))
+    }
+}
+
+fn is_positive(value: f64) -> bool {
+    value > 0.0 && value.is_finite()
+}
+
+impl ContextRandomExt for Context {
+    /// Initializes the `RngPlugin` data container to store rngs as well as a base
+    /// seed. Note that rngs are created lazily when `get_rng` is called.
+    fn init_random(&mut self, base_seed: u64) {
+        trace!("initializing random module");
+        let data_container = self.get_data_container_mut(RngPlugin);
+        data_container.base_seed = base_seed;
+
+        // Clear any existing Rngs to ensure they get re-seeded when `get_rng` is called
+        let mut rng_map = data_container.rng_holders.try_borrow_mut().unwrap();
+        rng_map.clear();
+        data_container.restored_rngs.get_mut().clear();
+    }
+
+    fn sample<R: RngId + 'static, T>(
+        &self,
+        _rng_id: R,
+        sampler: impl FnOnce(&mut R::RngType) -> T,
+    ) -> T {
+        let mut rng = get_rng::<R>(self);
+        sampler(&mut rng)
+    }
+
+    fn sample_distr<R: RngId + 'static, T>(
+        &self,
+        _rng_id: R,
+        distribution: impl Distribution<T>,
+    ) -> T
+    where
+        R::RngType: Rng,
+    {
+        let mut rng = get_rng::<R>(self);
+        distribution.sample::<R::RngType>(&mut rng)
+    }
+
+    fn sample_range<R: RngId + 'static, S, T>(&self, rng_id: R, range: S) -> T
+    where
+        R::RngType: Rng,
+        S: SampleRange<T>,
+        T: SampleUniform,
+    {
+        self.sample(rng_id, |rng| rng.gen_range(range))
+    }
+
+    fn sample_bool<R: RngId + 'static>(&self, rng_id: R, p: f64) -> bool
+    where
+        R::RngType: Rng,
+    {
+        self.sample(rng_id, |rng| rng.gen_bool(p))
+    }
+
+    fn sample_weighted<R: RngId + 'static, T>(&self, _rng_id: R, weights: &[T]) -> usize
+    where
+        R::RngType: Rng,
+        T: Clone + Default + SampleUniform + for<'a> std::ops::AddAssign<&'a T> + PartialOrd,
+    {
+        let index = WeightedIndex::new(weights).unwrap();
+        let mut rng = get_rng::<R>(self);
+        index.sample(&mut *rng)
+    }
+
+    fn sample_exponential<R: RngId + 'static>(&self, rng_id: R, rate: f64) -> Result<f64, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        check_parameter("exponential", "rate", rate, is_positive(rate))?;
+        if cfg!(feature = "portable-rng") {
+            return Ok(self.sample(rng_id, |rng| portable::exponential(rng, rate)));
+        }
+        Ok(self.sample_distr(rng_id, Exp::new(rate).unwrap()))
+    }
+
+    fn sample_gamma<R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        shape: f64,
+        scale: f64,
+    ) -> Result<f64, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        check_parameter("gamma", "shape", shape, is_positive(shape))?;
+        check_parameter("gamma", "scale", scale, is_positive(scale))?;
+        Ok(self.sample_distr(rng_id, Gamma::new(shape, scale).unwrap()))
+    }
+
+    fn sample_lognormal<R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        mu: f64,
+        sigma: f64,
+    ) -> Result<f64, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        check_parameter("log-normal", "mu", mu, mu.is_finite())?;
+        check_parameter(
+            "log-normal",
+            "sigma",
+            sigma,
+            sigma >= 0.0 && sigma.is_finite(),
+        )?;
+        Ok(self.sample_distr(rng_id, LogNormal::new(mu, sigma).unwrap()))
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn sample_poisson<R: RngId + 'static>(&self, rng_id: R, lambda: f64) -> Result<u64, IxaError>
+    where
+        R::RngType: Rng,
+    {
+        check_parameter("Poisson", "lambda", lambda, is_positive(lambda))?;
+        // Poisson samples are whole numbers stored as floats
+        Ok(self.sample_distr(rng_id, Poisson::new(lambda).unwrap()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::define_data_plugin;
+    use crate::error::IxaError;
+    use crate::people::PersonId;
+    use crate::random::{person_initial_value_seed, ContextRandomExt};
+    use rand::RngCore;
+    use rand::{distributions::WeightedIndex, prelude::Distribution};
+
+    define_rng!(FooRng);
+    define_rng!(BarRng);
+
+    #[test]
+    fn get_rng_basic() {
+        let mut context = Context::new();
+        context.init_random(42);
+
+        assert_ne!(
+            context.sample(FooRng, RngCore::next_u64),
+            context.sample(FooRng, RngCore::next_u64)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "You must initialize the random number generator with a base seed")]
+    fn panic_if_not_initialized() {
+        let context = Context::new();
+        context.sample(FooRng, RngCore::next_u64);
+    }
+
+    #[test]
+    fn multiple_rng_types() {
+        let mut context = Context::new();
+        context.init_random(42);
+
+        assert_ne!(
+            context.sample(FooRng, RngCore::next_u64),
+            context.sample(BarRng, RngCore::next_u64)
+        );
+    }
+
+    #[test]
+    fn streams_independent_of_order() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let foo_first: Vec<_> = (0..4)
+            .map(|_| context.sample(FooRng, RngCore::next_u64))
+            .collect();
+        let bar_second: Vec<_> = (0..4)
+            .map(|_| context.sample(BarRng, RngCore::next_u64))
+            .collect();
+
+        let mut context = Context::new();
+        context.init_random(42);
+        let bar_first: Vec<_> = (0..4)
+            .map(|_| context.sample(BarRng, RngCore::next_u64))
+            .collect();
+        let foo_second: Vec<_> = (0..4)
+            .map(|_| context.sample(FooRng, RngCore::next_u64))
+            .collect();
+
+        assert_eq!(foo_first, foo_second);
+        assert_eq!(bar_first, bar_second);
+    }
+
+    #[test]
+    fn streams_differ_across_base_seeds() {
+        // Offsetting the base seed by the difference between the hashes of
+        // the names mustn't give one RngId the stream of the other.
+        let shifted = 42_u64
+            .wrapping_add(fxhash::hash64("FooRng"))
+            .wrapping_sub(fxhash::hash64("BarRng"));
+        let mut context = Context::new();
+        context.init_random(42);
+        let foo = context.sample(FooRng, RngCore::next_u64);
+        context.init_random(shifted);
+        assert_ne!(foo, context.sample(BarRng, RngCore::next_u64));
+    }
+
+    #[test]
+    fn person_initial_value_seeds_are_portable() {
+        // These values are the same on every platform; if they change,
+        // every initial value drawn with `init_distribution` changes too.
+        assert_eq!(
+            person_initial_value_seed(42, "ixa.InitAge", PersonId(7)),
+            3_644_797_252_297_406_480
+        );
+        assert_eq!(
+            person_initial_value_seed(42, "ixa.InitAge", PersonId(8)),
+            4_819_831_166_187_568_283
+        );
+        assert_eq!(
+            person_initial_value_seed(43, "ixa.InitAge", PersonId(7)),
+            188_854_360_120_214_174
+        );
+    }
+
+    #[test]
+    fn reset_seed() {
+        let mut context = Context::new();
+        context.init_random(42);
+
+        let run_0 = context.sample(FooRng, RngCore::next_u64);
+        let run_1 = context.sample(FooRng, RngCore::next_u64);
+
+        // Reset with same seed, ensure we get the same values
+        context.init_random(42);
+        assert_eq!(run_0, context.sample(FooRng, RngCore::next_u64));
+        assert_eq!(run_1, context.sample(FooRng, RngCore::next_u64));
+
+        // Reset with different seed, ensure we get different values
+        context.init_random(88);
+        assert_ne!(run_0, context.sample(FooRng, RngCore::next_u64));
+        assert_ne!(run_1, context.sample(FooRng, RngCore::next_u64));
+    }
+
+    define_data_plugin!(
+        SamplerData,
+        WeightedIndex<f64>,
+        WeightedIndex::new(vec![1.0]).unwrap()
+    );
+
+    #[test]
+    fn sampler_function_closure_capture() {
+        let mut context = Context::new();
+        context.init_random(42);
+        // Initialize weighted sampler
+        *context.get_data_container_mut(SamplerData) = WeightedIndex::new(vec![1.0, 2.0]).unwrap();
+
+        let parameters = context.get_data_container(SamplerData).unwrap();
+        let n_samples = 3000;
+        let mut zero_counter = 0;
+        for _ in 0..n_samples {
+            let sample = context.sample(FooRng, |rng| parameters.sample(rng));
+            if sample == 0 {
+                zero_counter += 1;
+            }
+        }
+        // Within three standard deviations (about 26) of the expected count
+        assert!((zero_counter - 1000_i32).abs() < 78);
+    }
+
+    #[test]
+    fn sample_distribution() {
+        let mut context = Context::new();
+        context.init_random(42);
+
+        // Initialize weighted sampler
+        *context.get_data_container_mut(SamplerData) = WeightedIndex::new(vec![1.0, 2.0]).unwrap();
+
+        let parameters = context.get_data_container(SamplerData).unwrap();
+        let n_samples = 3000;
+        let mut zero_counter = 0;
+        for _ in 0..n_samples {
+            let sample = context.sample_distr(FooRng, parameters);
+            if sample == 0 {
+                zero_counter += 1;
+            }
+        }
+        // Within three standard deviations (about 26) of the expected count
+        assert!((zero_counter - 1000_i32).abs() < 78);
+    }
+
+    #[test]
+    fn sample_range() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let result = context.sample_range(FooRng, 0..10);
+        assert!((0..10).contains(&result));
+    }
+
+    #[test]
+    fn sample_bool() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let _r: bool = context.sample_bool(FooRng, 0.5);
+    }
+
+    #[test]
+    fn sample_weighted() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let r: usize = context.sample_weighted(FooRng, &[0.1, 0.3, 0.4]);
+        assert!(r < 3);
+    }
+
+    fn draw_all(seed: u64) -> (f64, f64, f64, u64) {
+        let mut context = Context::new();
+        context.init_random(seed);
+        (
+            context.sample_exponential(FooRng, 0.5).unwrap(),
+            context.sample_gamma(FooRng, 2.0, 3.0).unwrap(),
+            context.sample_lognormal(BarRng, 0.0, 1.0).unwrap(),
+            context.sample_poisson(BarRng, 4.0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn convenience_samplers_are_deterministic() {
+        assert_eq!(draw_all(42), draw_all(42));
+        assert_ne!(draw_all(42), draw_all(43));
+    }
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn convenience_sampler_means() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let n = 20_000;
+        let mean = |samples: Vec<f64>| samples.iter().sum::<f64>() / f64::from(n);
+
+        let exponential = (0..n)
+            .map(|_| context.sample_exponential(FooRng, 0.5).unwrap())
+            .collect();
+        assert!((mean(exponential) - 2.0).abs() < 0.1);
+        let gamma = (0..n)
+            .map(|_| context.sample_gamma(FooRng, 2.0, 3.0).unwrap())
+            .collect();
+        assert!((mean(gamma) - 6.0).abs() < 0.2);
+        let poisson = (0..n)
+            .map(|_| context.sample_poisson(FooRng, 4.0).unwrap() as f64)
+            .collect();
+        assert!((mean(poisson) - 4.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn convenience_samplers_reject_invalid_parameters() {
+        let mut context = Context::new();
+        context.init_random(42);
+        assert!(context.sample_exponential(FooRng, 0.0).is_err());
+        assert!(context.sample_exponential(FooRng, -1.0).is_err());
+        assert!(context.sample_exponential(FooRng, f64::NAN).is_err());
+        assert!(context.sample_gamma(FooRng, 0.0, 1.0).is_err());
+        assert!(context.sample_gamma(FooRng, 1.0, f64::INFINITY).is_err());
+        assert!(context.sample_lognormal(FooRng, f64::NAN, 1.0).is_err());
+        assert!(context.sample_lognormal(FooRng, 0.0, -1.0).is_err());
+        assert!(context.sample_poisson(FooRng, 0.0).is_err());
+
+        match context.sample_exponential(FooRng, -1.0) {
+            Err(IxaError::IxaError(message)) => {
+                assert_eq!(message, "Invalid rate -1 for the exponential distribution");
+            }
+            _ => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "portable-rng")]
+    #[allow(clippy::float_cmp)]
+    fn portable_rng_feature() {
+        use crate::random::{portable, PortableRng};
+
+        let mut context = Context::new();
+        context.init_random(42);
+        // This only compiles if define_rng! uses PortableRng.
+        let mut copy = context.sample(FooRng, |rng: &mut PortableRng| rng.clone());
+        let sample = context.sample_exponential(FooRng, 2.0).unwrap();
+        assert_eq!(sample, portable::exponential(&mut copy, 2.0));
+    }
+}
@@ -0,0 +1,247 @@
+//! A fully specified random number generator and samplers for results that
+//! are bit-for-bit identical on every platform.
+//!
+//! The numbers a simulation draws depend on more than the seed: on the
+//! generator algorithm, which belongs to a dependency and can change when
+//! it's upgraded, and on how uniform draws are turned into samples from
+//! continuous distributions. `rand_distr` uses the platform's math library
+//! for functions like `ln()` and `exp()`, which can round differently on
+//! Linux and macOS, so the same model can produce slightly different
+//! numbers on each.
+//!
+//! For outputs that must be reproduced exactly, e.g., for a regulatory
+//! submission:
+//! * Enable the `portable-rng` feature, which makes [`PortableRng`] the
+//!   generator of every [`define_rng!()`](crate::define_rng). It is
+//!   implemented here, so it can only change with Ixa itself. Without the
+//!   feature, nothing changes.
+//! * Draw continuous values with the functions in this module, e.g.,
+//!   `context.sample(MyRng, |rng| portable::normal(rng, 0.0, 1.0))`. They
+//!   use a pure-Rust math library, not the platform's. With the feature,
+//!   [`ContextRandomExt::sample_exponential()`](crate::ContextRandomExt::sample_exponential)
+//!   uses [`exponential()`] too.
+//! * Integer ranges, [`ContextRandomExt::sample_bool()`](crate::ContextRandomExt::sample_bool)
+//!   and [`ContextRandomExt::sample_weighted()`](crate::ContextRandomExt::sample_weighted)
+//!   only use exact arithmetic, so they're already portable, but the gamma,
+//!   log-normal and Poisson samplers aren't. Nor is
+//!   [`ContextPeopleExt::sample_person()`](crate::ContextPeopleExt::sample_person)
+//!   with a query that has to look at everyone, which uses logarithms.
+//! * Avoid calling `f64` methods that use the math library, such as `ln()`,
+//!   `exp()`, `powf()` and the trigonometric functions, on values that
+//!   affect the simulation; use the versions in the `libm` crate instead.
+//!   Arithmetic and `sqrt()` give the same results everywhere.
+use rand::{Error, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// The xoshiro256++ generator of Blackman and Vigna
+/// (<https://prng.di.unimi.it/>), seeded from a `u64` with `SplitMix64`.
+///
+/// It has 256 bits of state, a period of 2<sup>256</sup> − 1 and is fast,
+/// but isn't suitable for cryptography. The output of a generator seeded
+/// with [`SeedableRng::seed_from_u64()`] is fixed by the tests in this
+/// module, so it will stay the same in future versions of Ixa.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortableRng {
+    state: [u64; 4],
+}
+
+// Advances `state` and returns the next output of `SplitMix64`.
+fn split_mix_64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl RngCore for PortableRng {
+    #[allow(clippy::cast_possible_truncation)]
+    fn next_u32(&mut self) -> u32 {
+        // The upper bits are the best ones
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s0.wrapping_add(*s3).rotate_left(23).wrapping_add(*s0);
+        let t = *s1 << 17;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+        result
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for PortableRng {
+    type Seed = [u8; 32];
+
+    /// Uses the seed as the state, as four little-endian `u64`s. The state
+    /// can't be all zeros, so an all-zero seed is replaced by the state from
+    /// `seed_from_u64(0)`.
+    fn from_seed(seed: [u8; 32]) -> Self {
+        if seed == [0; 32] {
+            return Self::seed_from_u64(0);
+        }
+        let mut state = [0; 4];
+        for (word, bytes) in state.iter_mut().zip(seed.chunks_exact(8)) {
+            *word = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        PortableRng { state }
+    }
+
+    /// Fills the state with the first four outputs of `SplitMix64` seeded
+    /// with `seed`, as recommended by the authors.
+    fn seed_from_u64(mut seed: u64) -> Self {
+        PortableRng {
+            state: std::array::from_fn(|_| split_mix_64(&mut seed)),
+        }
+    }
+}
+
+/// Returns a uniform sample from [0, 1), with 53 random bits.
+#[allow(clippy::cast_precision_loss)]
+pub fn uniform<R: RngCore + ?Sized>(rng: &mut R) -> f64 {
+    // 2^-53
+    const SCALE: f64 = 1.0 / 9_007_199_254_740_992.0;
+    (rng.next_u64() >> 11) as f64 * SCALE
+}
+
+/// Returns a sample from the exponential distribution with rate `rate`,
+/// which should be positive, by inversion.
+pub fn exponential<R: RngCore + ?Sized>(rng: &mut R, rate: f64) -> f64 {
+    -libm::log1p(-uniform(rng)) / rate
+}
+
+/// Returns a sample from the normal distribution with mean `mean` and
+/// standard deviation `std_dev`, using Marsaglia's polar method.
+pub fn normal<R: RngCore + ?Sized>(rng: &mut R, mean: f64, std_dev: f64) -> f64 {
+    loop {
+        let u = 2.0 * uniform(rng) - 1.0;
+        let v = 2.0 * uniform(rng) - 1.0;
+        let s = u * u + v * v;
+        if s > 0.0 && s < 1.0 {
+            // Only one of the pair of samples is used, so each sample uses
+            // a fixed number of draws per attempt.
+            return mean + std_dev * u * (-2.0 * libm::log(s) / s).sqrt();
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::{exponential, normal, uniform, PortableRng};
+    use rand::{RngCore, SeedableRng};
+
+    // These values must never change; see the module documentation. They
+    // were checked against an independent implementation of xoshiro256++.
+    #[test]
+    fn golden_sequence() {
+        let mut rng = PortableRng::seed_from_u64(42);
+        let draws: Vec<u64> = (0..5).map(|_| rng.next_u64()).collect();
+        assert_eq!(
+            draws,
+            vec![
+                0xd076_4d4f_4476_689f,
+                0x519e_4174_576f_3791,
+                0xfbe0_7cfb_0c24_ed8c,
+                0xb37d_9f60_0cd8_35b8,
+                0xcb23_1c38_7484_6a73,
+            ]
+        );
+    }
+
+    #[test]
+    fn reference_sequence() {
+        // The first outputs of the reference implementation with this state
+        let mut seed = [0; 32];
+        for (i, word) in [1u64, 2, 3, 4].iter().enumerate() {
+            seed[i * 8..(i + 1) * 8].copy_from_slice(&word.to_le_bytes());
+        }
+        let mut rng = PortableRng::from_seed(seed);
+        let draws: Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+        assert_eq!(
+            draws,
+            vec![
+                41_943_041,
+                58_720_359,
+                3_588_806_011_781_223,
+                3_591_011_842_654_386
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_seed() {
+        assert_eq!(
+            PortableRng::from_seed([0; 32]),
+            PortableRng::seed_from_u64(0)
+        );
+        assert_eq!(
+            PortableRng::seed_from_u64(0).next_u64(),
+            0x5317_5d61_490b_23df
+        );
+    }
+
+    #[test]
+    fn fill_bytes() {
+        let mut rng = PortableRng::seed_from_u64(42);
+        let mut bytes = [0; 12];
+        rng.fill_bytes(&mut bytes);
+        let mut expected = PortableRng::seed_from_u64(42);
+        assert_eq!(bytes[..8], expected.next_u64().to_le_bytes());
+        assert_eq!(bytes[8..], expected.next_u64().to_le_bytes()[..4]);
+    }
+
+    #[test]
+    fn golden_samples() {
+        let mut rng = PortableRng::seed_from_u64(42);
+        assert_eq!(uniform(&mut rng), 0.814_305_145_122_909_9);
+        assert_eq!(uniform(&mut rng), 0.318_821_040_061_661_1);
+        // The bits of these are fixed too. They were checked against an
+        // independent implementation of the same transforms.
+        let mut rng = PortableRng::seed_from_u64(42);
+        let samples = [
+            exponential(&mut rng, 2.0),
+            normal(&mut rng, 1.0, 2.0),
+            normal(&mut rng, 1.0, 2.0),
+        ];
+        assert_eq!(
+            samples.map(f64::to_bits),
+            [
+                0x3fea_f03b_8672_f7bf,
+                0x4002_8d12_f7f3_2c18,
+                0x3ff7_7e9f_ec39_836a
+            ]
+        );
+    }
+
+    #[test]
+    fn sample_moments() {
+        let mut rng = PortableRng::seed_from_u64(7);
+        let n = 100_000;
+        let n_f64 = f64::from(n);
+        let exponential_mean = (0..n).map(|_| exponential(&mut rng, 4.0)).sum::<f64>() / n_f64;
+        assert!((exponential_mean - 0.25).abs() < 0.005);
+        let samples: Vec<f64> = (0..n).map(|_| normal(&mut rng, 3.0, 2.0)).collect();
+        let mean = samples.iter().sum::<f64>() / n_f64;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n_f64;
+        assert!((mean - 3.0).abs() < 0.03);
+        assert!((variance - 4.0).abs() < 0.1);
+    }
+}
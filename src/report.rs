@@ -1,25 +1,62 @@
 use crate::context::Context;
 use crate::error::IxaError;
-use crate::people::ContextPeopleExt;
-use crate::Tabulator;
+use crate::people::{ContextPeopleExt, NumericValue, Query};
+use crate::tabulator::{subscribe_streaming_tabulation, StreamingMode, StreamingTabulation};
 use crate::{error, trace};
-use csv::Writer;
-use std::any::TypeId;
-use std::cell::{RefCell, RefMut};
+use crate::{PersonProperty, TabulationKey, Tabulator};
+use csv::{ReaderBuilder, StringRecord, Writer};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell, RefMut};
 use std::collections::HashMap;
 use std::env;
 use std::fs::File;
+use std::io::{self, Write};
+use std::marker::PhantomData;
 use std::path::PathBuf;
 
+/// How report files are compressed
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ReportCompression {
+    /// Plain CSV files, named `<prefix><short_name>.csv`
+    #[default]
+    None,
+    /// Gzip-compressed CSV files, named `<prefix><short_name>.csv.gz`
+    Gzip,
+}
+
+/// How report files are written
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// CSV files with a header, named `<prefix><short_name>.csv`
+    #[default]
+    Csv,
+    /// Newline-delimited JSON, one object per row, named
+    /// `<prefix><short_name>.jsonl`. Fields keep their types, and nested
+    /// structs and sequences are written as JSON objects and arrays.
+    JsonLines,
+    /// A JSON array of objects, one per row, named
+    /// `<prefix><short_name>.json`. The array is closed when the
+    /// simulation is shut down or the context is dropped, so the file is
+    /// only well-formed after that.
+    JsonArray,
+}
+
 // * file_prefix: precedes the report name in the filename. An example of a
 // potential prefix might be scenario or simulation name
 // * directory: location that the CSVs are written to. An example of this might
 // be /data/
 // * overwrite: if true, will overwrite existing files in the same location
+// * compression: how the reports added from now on are compressed
+// * format: how the reports added from now on with `add_report` are written
 pub struct ConfigReportOptions {
     pub file_prefix: String,
     pub output_dir: PathBuf,
     pub overwrite: bool,
+    pub compression: ReportCompression,
+    pub format: ReportFormat,
 }
 
 impl ConfigReportOptions {
@@ -32,26 +69,46 @@ impl ConfigReportOptions {
             file_prefix: String::new(),
             output_dir: env::current_dir().unwrap(),
             overwrite: false,
+            compression: ReportCompression::None,
+            format: ReportFormat::Csv,
         }
     }
     /// Sets the file prefix option (e.g., "report_")
     pub fn file_prefix(&mut self, file_prefix: String) -> &mut ConfigReportOptions {
-        trace!("setting report prefix to {}", file_prefix);
+        trace!("setting report prefix to {file_prefix}");
         self.file_prefix = file_prefix;
         self
     }
     /// Sets the directory where reports will be output
     pub fn directory(&mut self, directory: PathBuf) -> &mut ConfigReportOptions {
-        trace!("setting report directory to {:?}", directory);
+        trace!("setting report directory to {}", directory.display());
         self.output_dir = directory;
         self
     }
     /// Sets whether to overwrite existing reports of the same name if they exist
     pub fn overwrite(&mut self, overwrite: bool) -> &mut ConfigReportOptions {
-        trace!("setting report overwrite {}", overwrite);
+        trace!("setting report overwrite {overwrite}");
         self.overwrite = overwrite;
         self
     }
+    /// Sets how the reports added after this are compressed, so a context
+    /// can have both compressed and uncompressed reports. Compressed reports
+    /// are finished when the simulation is shut down with
+    /// [`Context::shutdown()`], so nothing can be sent to them after that.
+    pub fn compress(&mut self, compression: ReportCompression) -> &mut ConfigReportOptions {
+        trace!("setting report compression {compression:?}");
+        self.compression = compression;
+        self
+    }
+    /// Sets the format of the reports added after this with
+    /// [`ContextReportExt::add_report()`] or
+    /// [`ContextReportExt::add_report_with_columns()`]. Reports with
+    /// columns fixed by ixa, such as periodic reports, are always CSV.
+    pub fn format(&mut self, format: ReportFormat) -> &mut ConfigReportOptions {
+        trace!("setting report format {format:?}");
+        self.format = format;
+        self
+    }
 }
 
 impl Default for ConfigReportOptions {
@@ -60,11 +117,82 @@ impl Default for ConfigReportOptions {
     }
 }
 
+/// The file a report is written to, which is compressed if the report was
+/// added with compression enabled (see [`ConfigReportOptions::compress()`])
+pub struct ReportFile {
+    output: ReportOutput,
+}
+
+enum ReportOutput {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+}
+
+impl ReportFile {
+    fn new(file: File, compression: ReportCompression) -> Self {
+        let output = match compression {
+            ReportCompression::None => ReportOutput::Plain(file),
+            ReportCompression::Gzip => {
+                ReportOutput::Gzip(GzEncoder::new(file, Compression::default()))
+            }
+        };
+        ReportFile { output }
+    }
+
+    // Writes out everything, including the end of a compressed file.
+    fn finish(self) -> io::Result<()> {
+        match self.output {
+            ReportOutput::Plain(mut file) => file.flush(),
+            ReportOutput::Gzip(encoder) => encoder.finish().map(|_| ()),
+        }
+    }
+}
+
+impl Write for ReportFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.output {
+            ReportOutput::Plain(file) => file.write(buf),
+            ReportOutput::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.output {
+            ReportOutput::Plain(file) => file.flush(),
+            ReportOutput::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
 pub trait Report: 'static {
     // Returns report type
     fn type_id(&self) -> TypeId;
     // Serializes the data with the correct writer
-    fn serialize(&self, writer: &mut Writer<File>);
+    fn serialize(&self, writer: &mut Writer<ReportFile>);
+    /// Writes the data as a line of JSON, for reports in the
+    /// [`ReportFormat::JsonLines`] format. [`create_report_trait!`]
+    /// implements this with [`write_json_line()`].
+    ///
+    /// # Panics
+    /// The default implementation panics, because the report type doesn't
+    /// support JSON.
+    fn serialize_json(&self, _file: &mut ReportFile) {
+        panic!(
+            "Report type {} can't be written as JSON",
+            std::any::type_name::<Self>()
+        );
+    }
+}
+
+/// Writes `row` to `file` as one line of JSON
+///
+/// # Panics
+/// If `row` can't be serialized or the file can't be written to.
+pub fn write_json_line<T: Serialize>(file: &mut ReportFile, row: &T) {
+    // Serialized into a buffer first so that the file gets one write per row
+    let mut line = serde_json::to_vec(row).expect("Failed to serialize row");
+    line.push(b'\n');
+    file.write_all(&line).expect("Failed to write row");
 }
 
 /// Use this macro to define a unique report type
@@ -76,48 +204,296 @@ macro_rules! create_report_trait {
                 std::any::TypeId::of::<$name>()
             }
 
-            fn serialize(&self, writer: &mut csv::Writer<std::fs::File>) {
+            fn serialize(&self, writer: &mut csv::Writer<$crate::report::ReportFile>) {
                 writer.serialize(self).unwrap();
             }
+
+            fn serialize_json(&self, file: &mut $crate::report::ReportFile) {
+                $crate::report::write_json_line(file, self);
+            }
         }
     };
 }
 
+// Identifies the report of a property summary in the report data.
+struct PropertySummaryReport<T, G>(PhantomData<(T, G)>);
+
+/// A computed column for a report of type `T` (see
+/// [`ContextReportExt::add_report_with_columns()`]). Make one from a
+/// closure with [`report_column()`].
+pub type ReportColumnFn<T> = Box<dyn Fn(&Context, &T) -> String>;
+
+/// Makes a computed column from a function which receives the context and
+/// the row being sent and returns the column value. The function can
+/// capture values, such as a parameter read when the report is added.
+pub fn report_column<T>(column_fn: impl Fn(&Context, &T) -> String + 'static) -> ReportColumnFn<T> {
+    Box::new(column_fn)
+}
+
+// Where a row of a report is written, depending on its format
+enum RowOutput<'a> {
+    Csv(&'a mut Writer<ReportFile>),
+    JsonLines(&'a mut ReportFile),
+}
+
+// Writes a row of a report that has computed columns. The row is passed
+// as `&dyn Any` and downcast to the concrete report type inside.
+type RowSerializer = dyn Fn(&Context, &dyn Any, RowOutput);
+
 struct ReportData {
-    file_writers: RefCell<HashMap<TypeId, Writer<File>>>,
+    file_writers: RefCell<HashMap<TypeId, Writer<ReportFile>>>,
+    json_files: RefCell<HashMap<TypeId, ReportFile>>,
+    json_array_rows: RefCell<HashMap<TypeId, u64>>,
+    row_serializers: HashMap<TypeId, Box<RowSerializer>>,
+    files: Vec<(TypeId, String, PathBuf)>,
+    rows_written: RefCell<HashMap<TypeId, u64>>,
     config: ConfigReportOptions,
 }
 
 // Registers a data container that stores
-// * file_writers: Maps report type to file writer
+// * file_writers: Maps report type to file writer, for CSV reports
+// * json_files: Maps report type to file, for JSON lines and JSON array reports
+// * json_array_rows: Maps report type to the number of rows written, for JSON
+//   array reports, which need a separator between rows and closing at the end
+// * row_serializers: Maps report type to a serializer which appends
+//   computed columns, for reports added with `add_report_with_columns`
+// * files: The type, short name and path of each report, in the order they were added
+// * rows_written: The number of rows written to each report
 // * config: Contains all the customizable filename options that the user supplies
 crate::context::define_data_plugin!(
     ReportPlugin,
     ReportData,
     ReportData {
         file_writers: RefCell::new(HashMap::new()),
+        json_files: RefCell::new(HashMap::new()),
+        json_array_rows: RefCell::new(HashMap::new()),
+        row_serializers: HashMap::new(),
+        files: Vec::new(),
+        rows_written: RefCell::new(HashMap::new()),
         config: ConfigReportOptions::new(),
     }
 );
 
+impl ReportData {
+    // Removes the JSON file of a report, if it has one, closing the array
+    // of a JSON array report.
+    fn remove_json_file(&self, type_id: TypeId) -> Option<ReportFile> {
+        let mut file = self.json_files.borrow_mut().remove(&type_id)?;
+        if self.json_array_rows.borrow_mut().remove(&type_id).is_some() {
+            if let Err(e) = file.write_all(b"]\n") {
+                error!("Failed to close a JSON array report: {e}");
+            }
+        }
+        Some(file)
+    }
+}
+
+impl Drop for ReportData {
+    fn drop(&mut self) {
+        let arrays: Vec<TypeId> = self.json_array_rows.borrow().keys().copied().collect();
+        for type_id in arrays {
+            self.remove_json_file(type_id);
+        }
+    }
+}
+
+// The open report files belong to the current run, so they aren't saved in checkpoints
+pub(crate) fn plugin_type_id() -> TypeId {
+    TypeId::of::<ReportPlugin>()
+}
+
+// Finishes the compressed reports and flushes the others, so that the files
+// are complete once the simulation has been shut down, even if the context
+// isn't dropped. Compressed reports can't be written to after this.
+pub(crate) fn finish_reports(context: &Context) {
+    let Some(data_container) = context.get_data_container(ReportPlugin) else {
+        return;
+    };
+    let mut writers = data_container.file_writers.borrow_mut();
+    let compressed: Vec<TypeId> = writers
+        .iter()
+        .filter(|(_, writer)| matches!(writer.get_ref().output, ReportOutput::Gzip(_)))
+        .map(|(type_id, _)| *type_id)
+        .collect();
+    for type_id in compressed {
+        let writer = writers.remove(&type_id).unwrap();
+        if let Err(e) = writer
+            .into_inner()
+            .map_err(csv::IntoInnerError::into_error)
+            .and_then(ReportFile::finish)
+        {
+            error!("Failed to finish a compressed report: {e}");
+        }
+    }
+    for writer in writers.values_mut() {
+        if let Err(e) = writer.flush() {
+            error!("Failed to flush a report: {e}");
+        }
+    }
+    // JSON arrays are closed, so they can't be written to after this either.
+    let finished: Vec<TypeId> = data_container
+        .json_files
+        .borrow()
+        .iter()
+        .filter(|(type_id, file)| {
+            matches!(file.output, ReportOutput::Gzip(_))
+                || data_container
+                    .json_array_rows
+                    .borrow()
+                    .contains_key(type_id)
+        })
+        .map(|(type_id, _)| *type_id)
+        .collect();
+    for type_id in finished {
+        if let Err(e) = data_container.remove_json_file(type_id).unwrap().finish() {
+            error!("Failed to finish a report: {e}");
+        }
+    }
+    let mut json_files = data_container.json_files.borrow_mut();
+    for file in json_files.values_mut() {
+        if let Err(e) = file.flush() {
+            error!("Failed to flush a report: {e}");
+        }
+    }
+}
+
+// Serializes `row` into a header and a record. We go through an in-memory
+// CSV writer because the csv crate can't serialize a struct together with
+// additional fields.
+fn serialize_to_record<T: Serialize>(row: &T) -> (StringRecord, StringRecord) {
+    let mut buffer = Writer::from_writer(Vec::new());
+    buffer.serialize(row).expect("Failed to serialize row");
+    let data = buffer.into_inner().expect("Failed to serialize row");
+    let mut reader = ReaderBuilder::new().from_reader(data.as_slice());
+    let header = reader.headers().expect("Failed to read header").clone();
+    let record = reader
+        .records()
+        .next()
+        .expect("No record serialized")
+        .expect("Failed to read record");
+    (header, record)
+}
+
 impl Context {
     // Builds the filename. Called by `add_report`, `short_name` refers to the
     // report type. The three main components are `prefix`, `directory`, and
     // `short_name`.
-    fn generate_filename(&mut self, short_name: &str) -> PathBuf {
+    fn generate_filename(&mut self, short_name: &str, format: ReportFormat) -> PathBuf {
         let data_container = self.get_data_container_mut(ReportPlugin);
         let prefix = &data_container.config.file_prefix;
         let directory = &data_container.config.output_dir;
         let short_name = short_name.to_string();
         let basename = format!("{prefix}{short_name}");
-        directory.join(basename).with_extension("csv")
+        let extension = match (format, data_container.config.compression) {
+            (ReportFormat::Csv, ReportCompression::None) => "csv",
+            (ReportFormat::Csv, ReportCompression::Gzip) => "csv.gz",
+            (ReportFormat::JsonLines, ReportCompression::None) => "jsonl",
+            (ReportFormat::JsonLines, ReportCompression::Gzip) => "jsonl.gz",
+            (ReportFormat::JsonArray, ReportCompression::None) => "json",
+            (ReportFormat::JsonArray, ReportCompression::Gzip) => "json.gz",
+        };
+        directory.join(basename).with_extension(extension)
+    }
+
+    // Creates the file for a report, which is written in `format`.
+    fn add_report_file(
+        &mut self,
+        type_id: TypeId,
+        short_name: &str,
+        format: ReportFormat,
+    ) -> Result<(), IxaError> {
+        let path = self.generate_filename(short_name, format);
+
+        let data_container = self.get_data_container_mut(ReportPlugin);
+
+        let file_creation_result = File::create_new(&path);
+        let created_file = match file_creation_result {
+            Ok(file) => file,
+            Err(e) => match e.kind() {
+                std::io::ErrorKind::AlreadyExists => {
+                    if data_container.config.overwrite {
+                        File::create(&path)?
+                    } else {
+                        error!("File already exists: {}. Please set `overwrite` to true in the file configuration and rerun.", path.display());
+                        return Err(IxaError::IoError(e));
+                    }
+                }
+                _ => {
+                    return Err(IxaError::IoError(e));
+                }
+            },
+        };
+        let mut file = ReportFile::new(created_file, data_container.config.compression);
+        data_container.remove_json_file(type_id);
+        match format {
+            ReportFormat::Csv => {
+                data_container
+                    .file_writers
+                    .borrow_mut()
+                    .insert(type_id, Writer::from_writer(file));
+            }
+            ReportFormat::JsonLines => {
+                data_container.file_writers.borrow_mut().remove(&type_id);
+                data_container.json_files.borrow_mut().insert(type_id, file);
+            }
+            ReportFormat::JsonArray => {
+                file.write_all(b"[\n")?;
+                data_container.file_writers.borrow_mut().remove(&type_id);
+                data_container.json_files.borrow_mut().insert(type_id, file);
+                data_container
+                    .json_array_rows
+                    .borrow_mut()
+                    .insert(type_id, 0);
+            }
+        }
+        data_container
+            .files
+            .retain(|(existing, _, _)| *existing != type_id);
+        data_container
+            .files
+            .push((type_id, short_name.to_string(), path));
+        Ok(())
+    }
+
+    pub(crate) fn count_report_row(&self, type_id: TypeId) {
+        if let Some(data_container) = self.get_data_container(ReportPlugin) {
+            *data_container
+                .rows_written
+                .borrow_mut()
+                .entry(type_id)
+                .or_insert(0) += 1;
+        }
+    }
+
+    /// Returns the name, path and number of rows written of each report
+    /// that has been added, or `None` if there are no reports.
+    pub(crate) fn report_statistics(&self) -> Option<Vec<(String, PathBuf, u64)>> {
+        let data_container = self.get_data_container(ReportPlugin)?;
+        if data_container.files.is_empty() {
+            return None;
+        }
+        let rows_written = data_container.rows_written.borrow();
+        Some(
+            data_container
+                .files
+                .iter()
+                .map(|(type_id, name, path)| {
+                    (
+                        name.clone(),
+                        path.clone(),
+                        rows_written.get(type_id).copied().unwrap_or(0),
+                    )
+                })
+                .collect(),
+        )
     }
 }
 
 pub trait ContextReportExt {
     /// Add a report file keyed by a `TypeId`.
     /// The `short_name` is used for file naming to distinguish what data each
-    /// output file points to.
+    /// output file points to. The file is always CSV, whatever the format
+    /// in [`ConfigReportOptions`], since the caller writes the records.
     /// # Errors
     /// If the file already exists and `overwrite` is set to false, raises an error and info message.
     /// If the file cannot be created, raises an error.
@@ -125,12 +501,84 @@ pub trait ContextReportExt {
 
     /// Call `add_report` with each report type, passing the name of the report type.
     /// The `short_name` is used for file naming to distinguish what data each
-    /// output file points to.
+    /// output file points to. The report is written in the format set with
+    /// [`ConfigReportOptions::format()`].
     /// # Errors
     /// If the file already exists and `overwrite` is set to false, raises an error and info message.
     /// If the file cannot be created, raises an error.
     fn add_report<T: Report + 'static>(&mut self, short_name: &str) -> Result<(), IxaError>;
 
+    /// Add a report like [`ContextReportExt::add_report`] that is written
+    /// as JSON lines ([`ReportFormat::JsonLines`]) to
+    /// `<prefix><short_name>.jsonl`, whatever the format in
+    /// [`ConfigReportOptions`]. The rows must serialize as JSON, which is
+    /// the case for report types defined with [`create_report_trait!`].
+    /// # Errors
+    /// If the file already exists and `overwrite` is set to false, raises an error and info message.
+    /// If the file cannot be created, raises an error.
+    fn add_report_jsonl<T: Report + 'static>(&mut self, short_name: &str) -> Result<(), IxaError>;
+
+    /// Add a report like [`ContextReportExt::add_report_jsonl`] that is
+    /// written as a JSON array ([`ReportFormat::JsonArray`]) to
+    /// `<prefix><short_name>.json`. The array is closed when the simulation
+    /// is shut down or the context is dropped.
+    /// # Errors
+    /// If the file already exists and `overwrite` is set to false, raises an error and info message.
+    /// If the file cannot be created, raises an error.
+    fn add_report_json_array<T: Report + 'static>(
+        &mut self,
+        short_name: &str,
+    ) -> Result<(), IxaError>;
+
+    /// Add a report like [`ContextReportExt::add_report`] with additional
+    /// columns that are computed when each row is sent. Each entry in
+    /// `columns` is a column name and a function, made with
+    /// [`report_column()`], which receives the context and the row being
+    /// sent, and returns the value of the column. The computed columns are
+    /// appended after the fields of `T`, in order.
+    ///
+    /// The functions only get shared access to the context, so they can read
+    /// people and global properties but not modify the simulation:
+    ///
+    /// ```compile_fail
+    /// use ixa::report::report_column;
+    /// use ixa::{create_report_trait, Context, ContextReportExt, Report};
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Incidence {
+    ///     t: f64,
+    /// }
+    /// create_report_trait!(Incidence);
+    ///
+    /// let mut context = Context::new();
+    /// context
+    ///     .add_report_with_columns::<Incidence>(
+    ///         "incidence",
+    ///         vec![(
+    ///             "shutdown",
+    ///             report_column(|context: &Context, _row: &Incidence| {
+    ///                 context.shutdown();
+    ///                 String::new()
+    ///             }),
+    ///         )],
+    ///     )
+    ///     .unwrap();
+    /// ```
+    ///
+    /// Note that every row is serialized through an intermediate record and
+    /// every column function is called once per row, so this is somewhat
+    /// slower than [`ContextReportExt::add_report`] for very large reports.
+    ///
+    /// # Errors
+    /// If the file already exists and `overwrite` is set to false, raises an error and info message.
+    /// If the file cannot be created, raises an error.
+    fn add_report_with_columns<T: Report + Serialize + 'static>(
+        &mut self,
+        short_name: &str,
+        columns: Vec<(&str, ReportColumnFn<T>)>,
+    ) -> Result<(), IxaError>;
+
     /// Adds a periodic report at the end of period `period` which summarizes the
     /// number of people in each combination of properties in `tabulator`.
     /// # Errors
@@ -142,51 +590,140 @@ pub trait ContextReportExt {
         period: f64,
         tabulator: T,
     ) -> Result<(), IxaError>;
-    fn get_writer(&self, type_id: TypeId) -> RefMut<Writer<File>>;
+
+    /// Adds a periodic report like [`Context::add_periodic_report()`] for
+    /// groupings with too many combinations of values to write in full,
+    /// e.g., `(CensusTract, AgeGroup)`. `T` is a tuple of properties (see
+    /// [`TabulationKey`]).
+    ///
+    /// Rather than being recomputed each period, the counts are kept up to
+    /// date as people are added, removed and changed, and only some groups
+    /// are written, as chosen by `mode`. In debug builds, the counts are
+    /// checked against a full recomputation each period, and a mismatch
+    /// panics.
+    /// # Errors
+    /// If the file already exists and `overwrite` is set to false, raises an error and info message.
+    /// If the file cannot be created, returns [`IxaError`]
+    fn add_streaming_periodic_report<T: TabulationKey>(
+        &mut self,
+        short_name: &str,
+        period: f64,
+        mode: StreamingMode,
+    ) -> Result<(), IxaError>;
+
+    /// Adds a periodic report at the end of period `period` which summarizes
+    /// the numeric property `property` among the people who match `query`,
+    /// with one row for each value of `group` (see
+    /// [`Context::property_summary_by()`]). The columns are `t`, the name of
+    /// `group`, `count`, `mean`, `sd`, `min`, `q25`, `median`, `q75` and
+    /// `max`. Values of `group` that no one matching `query` has are not
+    /// included.
+    /// # Errors
+    /// If the file already exists and `overwrite` is set to false, raises an error and info message.
+    /// If the file cannot be created, returns [`IxaError`]
+    fn add_property_summary_report<T, G, Q>(
+        &mut self,
+        short_name: &str,
+        period: f64,
+        property: T,
+        group: G,
+        query: Q,
+    ) -> Result<(), IxaError>
+    where
+        T: PersonProperty + 'static,
+        T::Value: NumericValue,
+        G: PersonProperty + 'static,
+        Q: Query + Clone + 'static;
+    fn get_writer(&self, type_id: TypeId) -> RefMut<'_, Writer<ReportFile>>;
     fn send_report<T: Report>(&self, report: T);
     fn report_options(&mut self) -> &mut ConfigReportOptions;
 }
 
 impl ContextReportExt for Context {
     fn add_report_by_type_id(&mut self, type_id: TypeId, short_name: &str) -> Result<(), IxaError> {
-        trace!("adding report {} by type_id {:?}", short_name, type_id);
-        let path = self.generate_filename(short_name);
-
-        let data_container = self.get_data_container_mut(ReportPlugin);
-
-        let file_creation_result = File::create_new(&path);
-        let created_file = match file_creation_result {
-            Ok(file) => file,
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::AlreadyExists => {
-                    if data_container.config.overwrite {
-                        File::create(&path)?
-                    } else {
-                        error!("File already exists: {}. Please set `overwrite` to true in the file configuration and rerun.", path.display());
-                        return Err(IxaError::IoError(e));
+        trace!("adding report {short_name} by type_id {type_id:?}");
+        self.add_report_file(type_id, short_name, ReportFormat::Csv)
+    }
+    fn add_report<T: Report + 'static>(&mut self, short_name: &str) -> Result<(), IxaError> {
+        trace!("Adding report {short_name}");
+        let format = self.report_options().format;
+        self.add_report_file(TypeId::of::<T>(), short_name, format)
+    }
+    fn add_report_jsonl<T: Report + 'static>(&mut self, short_name: &str) -> Result<(), IxaError> {
+        trace!("Adding JSON lines report {short_name}");
+        self.add_report_file(TypeId::of::<T>(), short_name, ReportFormat::JsonLines)
+    }
+    fn add_report_json_array<T: Report + 'static>(
+        &mut self,
+        short_name: &str,
+    ) -> Result<(), IxaError> {
+        trace!("Adding JSON array report {short_name}");
+        self.add_report_file(TypeId::of::<T>(), short_name, ReportFormat::JsonArray)
+    }
+    fn add_report_with_columns<T: Report + Serialize + 'static>(
+        &mut self,
+        short_name: &str,
+        columns: Vec<(&str, ReportColumnFn<T>)>,
+    ) -> Result<(), IxaError> {
+        trace!(
+            "Adding report {} with {} columns",
+            short_name,
+            columns.len()
+        );
+        self.add_report::<T>(short_name)?;
+
+        let (names, column_fns): (Vec<String>, Vec<ReportColumnFn<T>>) = columns
+            .into_iter()
+            .map(|(name, column_fn)| (name.to_string(), column_fn))
+            .unzip();
+        // Like csv's own serialization, the header is written with the first row.
+        let header_written = Cell::new(false);
+        let serializer = move |context: &Context, row: &dyn Any, output: RowOutput| {
+            let row = row
+                .downcast_ref::<T>()
+                .expect("Report type does not match serializer");
+            let writer = match output {
+                RowOutput::Csv(writer) => writer,
+                RowOutput::JsonLines(file) => {
+                    // Computed columns are added to the object as strings
+                    let Ok(serde_json::Value::Object(mut object)) = serde_json::to_value(row)
+                    else {
+                        panic!("Report rows with computed columns must serialize as maps");
+                    };
+                    for (name, column_fn) in names.iter().zip(&column_fns) {
+                        object.insert(name.clone(), column_fn(context, row).into());
                     }
+                    write_json_line(file, &object);
+                    return;
                 }
-                _ => {
-                    return Err(IxaError::IoError(e));
-                }
-            },
+            };
+            let (mut header, mut record) = serialize_to_record(row);
+            if !header_written.get() {
+                header.extend(&names);
+                writer
+                    .write_record(&header)
+                    .expect("Failed to write header");
+                header_written.set(true);
+            }
+            for column_fn in &column_fns {
+                record.push_field(&column_fn(context, row));
+            }
+            writer.write_record(&record).expect("Failed to write row");
         };
-        let writer = Writer::from_writer(created_file);
-        let mut file_writer = data_container.file_writers.borrow_mut();
-        file_writer.insert(type_id, writer);
+
+        let data_container = self.get_data_container_mut(ReportPlugin);
+        data_container
+            .row_serializers
+            .insert(TypeId::of::<T>(), Box::new(serializer));
         Ok(())
     }
-    fn add_report<T: Report + 'static>(&mut self, short_name: &str) -> Result<(), IxaError> {
-        trace!("Adding report {}", short_name);
-        self.add_report_by_type_id(TypeId::of::<T>(), short_name)
-    }
     fn add_periodic_report<T: Tabulator + Clone + 'static>(
         &mut self,
         short_name: &str,
         period: f64,
         tabulator: T,
     ) -> Result<(), IxaError> {
-        trace!("Adding periodic report {}", short_name);
+        trace!("Adding periodic report {short_name}");
 
         self.add_report_by_type_id(TypeId::of::<T>(), short_name)?;
 
@@ -214,6 +751,8 @@ impl ContextReportExt for Context {
                     row.push(count.to_string());
 
                     writer.write_record(&row).expect("Failed to write row");
+                    drop(writer);
+                    context.count_report_row(TypeId::of::<T>());
                 });
             },
             crate::context::ExecutionPhase::Last,
@@ -222,7 +761,125 @@ impl ContextReportExt for Context {
         Ok(())
     }
 
-    fn get_writer(&self, type_id: TypeId) -> RefMut<Writer<File>> {
+    fn add_streaming_periodic_report<T: TabulationKey>(
+        &mut self,
+        short_name: &str,
+        period: f64,
+        mode: StreamingMode,
+    ) -> Result<(), IxaError> {
+        trace!("Adding streaming periodic report {short_name}");
+
+        let type_id = TypeId::of::<StreamingTabulation<T>>();
+        self.add_report_by_type_id(type_id, short_name)?;
+
+        {
+            // Write the header
+            let mut writer = self.get_writer(type_id);
+            let mut header = vec!["t".to_string()];
+            header.extend(T::get_columns());
+            header.push("count".to_string());
+            writer
+                .write_record(&header)
+                .expect("Failed to write header");
+        }
+
+        let tabulation = subscribe_streaming_tabulation::<T>(self, mode);
+
+        self.add_periodic_plan_with_phase(
+            period,
+            move |context: &mut Context| {
+                let time = context.get_current_time().to_string();
+                let mut rows = 0;
+                {
+                    let mut writer = context.get_writer(type_id);
+                    tabulation.borrow_mut().emit(context, |labels, count| {
+                        let mut row = vec![time.clone()];
+                        row.extend(labels.iter().cloned());
+                        row.push(count.to_string());
+                        writer.write_record(&row).expect("Failed to write row");
+                        rows += 1;
+                    });
+                }
+                for _ in 0..rows {
+                    context.count_report_row(type_id);
+                }
+            },
+            crate::context::ExecutionPhase::Last,
+        );
+
+        Ok(())
+    }
+
+    fn add_property_summary_report<T, G, Q>(
+        &mut self,
+        short_name: &str,
+        period: f64,
+        property: T,
+        group: G,
+        query: Q,
+    ) -> Result<(), IxaError>
+    where
+        T: PersonProperty + 'static,
+        T::Value: NumericValue,
+        G: PersonProperty + 'static,
+        Q: Query + Clone + 'static,
+    {
+        trace!("Adding property summary report {short_name}");
+
+        let type_id = TypeId::of::<PropertySummaryReport<T, G>>();
+        self.add_report_by_type_id(type_id, short_name)?;
+
+        {
+            // Write the header
+            let mut writer = self.get_writer(type_id);
+            let header = [
+                "t",
+                G::name(),
+                "count",
+                "mean",
+                "sd",
+                "min",
+                "q25",
+                "median",
+                "q75",
+                "max",
+            ];
+            writer.write_record(header).expect("Failed to write header");
+        }
+
+        self.add_periodic_plan_with_phase(
+            period,
+            move |context: &mut Context| {
+                let summaries = context.property_summary_by(property, group, query.clone());
+                {
+                    let mut writer = context.get_writer(type_id);
+                    for (value, summary) in &summaries {
+                        let row = [
+                            context.get_current_time().to_string(),
+                            format!("{value:?}"),
+                            summary.count.to_string(),
+                            summary.mean.to_string(),
+                            summary.sd.to_string(),
+                            summary.min.to_string(),
+                            summary.quantiles[0].to_string(),
+                            summary.quantiles[1].to_string(),
+                            summary.quantiles[2].to_string(),
+                            summary.max.to_string(),
+                        ];
+                        writer.write_record(&row).expect("Failed to write row");
+                    }
+                }
+                for _ in &summaries {
+                    context.count_report_row(type_id);
+                }
+            },
+            crate::context::ExecutionPhase::Last,
+        );
+
+        Ok(())
+    }
+
+    fn get_writer(&self, type_id: TypeId) -> RefMut<'_, Writer<ReportFile>> {
         // No data container will exist if no reports have been added
         let data_container = self
             .get_data_container(ReportPlugin)
@@ -237,8 +894,37 @@ impl ContextReportExt for Context {
 
     /// Write a new row to the appropriate report file
     fn send_report<T: Report>(&self, report: T) {
+        self.count_report_row(report.type_id());
+        let data_container = self.get_data_container(ReportPlugin);
+        let row_serializer = data_container
+            .and_then(|data_container| data_container.row_serializers.get(&report.type_id()));
+        if let Some(data_container) = data_container {
+            let mut json_files = data_container.json_files.borrow_mut();
+            if let Some(file) = json_files.get_mut(&report.type_id()) {
+                if let Some(rows) = data_container
+                    .json_array_rows
+                    .borrow_mut()
+                    .get_mut(&report.type_id())
+                {
+                    if *rows > 0 {
+                        file.write_all(b",").expect("Failed to write row");
+                    }
+                    *rows += 1;
+                }
+                match row_serializer {
+                    Some(row_serializer) => {
+                        row_serializer(self, &report, RowOutput::JsonLines(file));
+                    }
+                    None => report.serialize_json(file),
+                }
+                return;
+            }
+        }
         let writer = &mut self.get_writer(report.type_id());
-        report.serialize(writer);
+        match row_serializer {
+            Some(row_serializer) => row_serializer(self, &report, RowOutput::Csv(writer)),
+            None => report.serialize(writer),
+        }
     }
 
     /// Returns a `ConfigReportOptions` object which has setter methods for report configuration
@@ -322,6 +1008,285 @@ mod test {
         }
     }
 
+    #[derive(Serialize, Deserialize)]
+    struct PlainReport {
+        is_runner: bool,
+    }
+
+    create_report_trait!(PlainReport);
+
+    fn read_gzip_report(file_path: &std::path::Path) -> Vec<(u32, String)> {
+        let file = File::open(file_path).unwrap();
+        let mut reader = csv::Reader::from_reader(flate2::read::GzDecoder::new(file));
+        reader
+            .deserialize()
+            .map(|result| {
+                let record: SampleReport = result.unwrap();
+                (record.id, record.value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn add_compressed_report() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        {
+            let mut context = Context::new();
+            context
+                .report_options()
+                .file_prefix("prefix_".to_string())
+                .directory(path.clone())
+                .compress(ReportCompression::Gzip);
+            context.add_report::<SampleReport>("sample_report").unwrap();
+            for id in 0..3 {
+                context.send_report(SampleReport {
+                    id,
+                    value: format!("Value {id}"),
+                });
+            }
+        }
+
+        assert!(!path.join("prefix_sample_report.csv").exists());
+        assert_eq!(
+            read_gzip_report(&path.join("prefix_sample_report.csv.gz")),
+            (0..3)
+                .map(|id| (id, format!("Value {id}")))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn compression_is_per_report() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        {
+            let mut context = Context::new();
+            context
+                .report_options()
+                .directory(path.clone())
+                .compress(ReportCompression::Gzip);
+            context.add_report::<SampleReport>("compressed").unwrap();
+            context.report_options().compress(ReportCompression::None);
+            context.add_report::<PlainReport>("plain").unwrap();
+            context.send_report(SampleReport {
+                id: 1,
+                value: "Test Value".to_string(),
+            });
+            context.send_report(PlainReport { is_runner: true });
+        }
+
+        assert_eq!(
+            read_gzip_report(&path.join("compressed.csv.gz")),
+            vec![(1, "Test Value".to_string())]
+        );
+        let mut reader = csv::Reader::from_path(path.join("plain.csv")).unwrap();
+        let records: Vec<PlainReport> = reader.deserialize().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].is_runner);
+    }
+
+    #[test]
+    fn compressed_report_finished_on_shutdown() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        let mut context = Context::new();
+        context
+            .report_options()
+            .directory(path.clone())
+            .compress(ReportCompression::Gzip);
+        context.add_report::<SampleReport>("sample_report").unwrap();
+        context.add_plan(1.0, |context| {
+            context.send_report(SampleReport {
+                id: 1,
+                value: "Test Value".to_string(),
+            });
+            context.shutdown();
+        });
+        context.execute();
+
+        // The file is complete while the context is still alive.
+        assert_eq!(
+            read_gzip_report(&path.join("sample_report.csv.gz")),
+            vec![(1, "Test Value".to_string())]
+        );
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Parameters {
+        r0: f64,
+        ages: Vec<u8>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct NestedReport {
+        id: u32,
+        parameters: Parameters,
+    }
+
+    create_report_trait!(NestedReport);
+
+    fn read_json_lines<T: serde::de::DeserializeOwned>(reader: impl std::io::Read) -> Vec<T> {
+        use std::io::BufRead;
+        std::io::BufReader::new(reader)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn json_lines_report_round_trips() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        let rows: Vec<NestedReport> = (0..3)
+            .map(|id| NestedReport {
+                id,
+                parameters: Parameters {
+                    r0: 1.5 + f64::from(id),
+                    ages: vec![0, 18, 65],
+                },
+            })
+            .collect();
+        {
+            let mut context = Context::new();
+            context
+                .report_options()
+                .directory(path.clone())
+                .format(ReportFormat::JsonLines);
+            context.add_report::<NestedReport>("nested").unwrap();
+            for row in &rows {
+                context.send_report(NestedReport {
+                    id: row.id,
+                    parameters: Parameters {
+                        r0: row.parameters.r0,
+                        ages: row.parameters.ages.clone(),
+                    },
+                });
+            }
+            // Periodic reports are still CSV
+            context
+                .add_periodic_report("periodic", 1.0, (IsRunner,))
+                .unwrap();
+            context.add_person(()).unwrap();
+            context.execute();
+        }
+
+        assert!(!path.join("nested.csv").exists());
+        let file = File::open(path.join("nested.jsonl")).unwrap();
+        assert_eq!(read_json_lines::<NestedReport>(file), rows);
+        let mut reader = csv::Reader::from_path(path.join("periodic.csv")).unwrap();
+        assert_eq!(reader.headers().unwrap(), vec!["t", "IsRunner", "count"]);
+    }
+
+    #[test]
+    fn json_lines_report_compressed_with_columns() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        {
+            let mut context = Context::new();
+            context
+                .report_options()
+                .directory(path.clone())
+                .format(ReportFormat::JsonLines)
+                .compress(ReportCompression::Gzip);
+            context
+                .add_report_with_columns::<SampleReport>(
+                    "sample",
+                    vec![(
+                        "time",
+                        report_column(|context: &Context, _row: &SampleReport| {
+                            context.get_current_time().to_string()
+                        }),
+                    )],
+                )
+                .unwrap();
+            context.send_report(SampleReport {
+                id: 1,
+                value: "Test Value".to_string(),
+            });
+            context.shutdown();
+            context.execute();
+        }
+
+        let file = File::open(path.join("sample.jsonl.gz")).unwrap();
+        let rows: Vec<serde_json::Value> = read_json_lines(flate2::read::GzDecoder::new(file));
+        assert_eq!(
+            rows,
+            vec![serde_json::json!({"id": 1, "value": "Test Value", "time": "0"})]
+        );
+    }
+
+    #[test]
+    fn add_report_jsonl_ignores_format_option() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        let mut context = Context::new();
+        context.report_options().directory(path.clone());
+        context.add_report_jsonl::<SampleReport>("sample").unwrap();
+        context.send_report(SampleReport {
+            id: 1,
+            value: "Test Value".to_string(),
+        });
+        context.shutdown();
+        context.execute();
+
+        // Readable before the context is dropped
+        let file = File::open(path.join("sample.jsonl")).unwrap();
+        let rows: Vec<serde_json::Value> = read_json_lines(file);
+        assert_eq!(
+            rows,
+            vec![serde_json::json!({"id": 1, "value": "Test Value"})]
+        );
+    }
+
+    #[test]
+    fn json_array_report() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        let rows: Vec<NestedReport> = (0..3)
+            .map(|id| NestedReport {
+                id,
+                parameters: Parameters {
+                    r0: 2.0,
+                    ages: vec![u8::try_from(id).unwrap(); 2],
+                },
+            })
+            .collect();
+        let read_rows = |name: &str| -> Vec<NestedReport> {
+            serde_json::from_reader(File::open(path.join(name)).unwrap()).unwrap()
+        };
+
+        // Closed at shutdown
+        let mut context = Context::new();
+        context.report_options().directory(path.clone());
+        context
+            .add_report_json_array::<NestedReport>("shutdown")
+            .unwrap();
+        for row in &rows {
+            context.send_report(NestedReport {
+                id: row.id,
+                parameters: Parameters {
+                    r0: row.parameters.r0,
+                    ages: row.parameters.ages.clone(),
+                },
+            });
+        }
+        context.shutdown();
+        context.execute();
+        assert_eq!(read_rows("shutdown.json"), rows);
+
+        // Closed when the context is dropped, even with no rows
+        {
+            let mut context = Context::new();
+            context.report_options().directory(path.clone());
+            context
+                .add_report_json_array::<NestedReport>("empty")
+                .unwrap();
+            context.execute();
+        }
+        assert_eq!(read_rows("empty.json"), vec![]);
+    }
+
     struct PathBufWithDrop {
         file: PathBuf,
     }
@@ -584,4 +1549,264 @@ mod test {
 
         assert_eq!(actual, expected, "CSV file should contain the correct data");
     }
+
+    #[test]
+    fn add_periodic_report_from_counts() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        {
+            let mut context = Context::new();
+            let config = context.report_options();
+            config
+                .file_prefix("test_".to_string())
+                .directory(path.clone());
+            context.index_property_counts(IsRunner);
+            context
+                .add_periodic_report("periodic", 1.0, (IsRunner,))
+                .unwrap();
+            let person = context.add_person(()).unwrap();
+            context.add_person(()).unwrap();
+            context.add_person(()).unwrap();
+
+            context.add_plan(1.0, move |context: &mut Context| {
+                context.set_person_property(person, IsRunner, true);
+            });
+            context.add_plan(2.0, move |context: &mut Context| {
+                context.remove_person(person).unwrap();
+            });
+
+            context.execute();
+        }
+
+        let mut reader = csv::Reader::from_path(path.join("test_periodic.csv")).unwrap();
+        let mut actual: Vec<Vec<String>> = reader
+            .records()
+            .map(|result| result.unwrap().iter().map(String::from).collect())
+            .collect();
+        actual.sort();
+        assert_eq!(
+            actual,
+            vec![
+                vec!["0", "false", "3"],
+                vec!["1", "false", "2"],
+                vec!["1", "true", "1"],
+                vec!["2", "false", "2"],
+            ]
+        );
+    }
+
+    #[test]
+    fn add_streaming_periodic_report() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        {
+            let mut context = Context::new();
+            context
+                .report_options()
+                .file_prefix("test_".to_string())
+                .directory(path.clone());
+            let person = context.add_person(()).unwrap();
+            context.add_person(()).unwrap();
+            context
+                .add_streaming_periodic_report::<(IsRunner,)>(
+                    "streaming",
+                    1.0,
+                    StreamingMode::Delta,
+                )
+                .unwrap();
+            context.add_plan(1.5, move |context: &mut Context| {
+                context.set_person_property(person, IsRunner, true);
+            });
+            // Nothing changes in the third period
+            context.add_plan(3.0, |_| {});
+            context.execute();
+            assert_eq!(
+                context.execution_statistics().reports.unwrap().files[0].rows,
+                3
+            );
+        }
+
+        let mut reader = csv::Reader::from_path(path.join("test_streaming.csv")).unwrap();
+        assert_eq!(reader.headers().unwrap(), vec!["t", "IsRunner", "count"]);
+        let actual: Vec<Vec<String>> = reader
+            .records()
+            .map(|result| result.unwrap().iter().map(String::from).collect())
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                vec!["0", "false", "2"],
+                vec!["2", "false", "1"],
+                vec!["2", "true", "1"],
+            ]
+        );
+    }
+
+    #[test]
+    fn add_property_summary_report() {
+        use crate::define_person_property;
+        define_person_property!(Weight, u32);
+
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        {
+            let mut context = Context::new();
+            context
+                .report_options()
+                .file_prefix("test_".to_string())
+                .directory(path.clone());
+            let person = context.add_person((Weight, 10)).unwrap();
+            context.add_person((Weight, 20)).unwrap();
+            context.add_person((Weight, 30)).unwrap();
+            context
+                .add_property_summary_report("summary", 1.0, Weight, IsRunner, ())
+                .unwrap();
+            context.add_plan(0.5, move |context: &mut Context| {
+                context.set_person_property(person, IsRunner, true);
+            });
+            context.execute();
+        }
+
+        let mut reader = csv::Reader::from_path(path.join("test_summary.csv")).unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec!["t", "IsRunner", "count", "mean", "sd", "min", "q25", "median", "q75", "max"]
+        );
+        let actual: Vec<Vec<String>> = reader
+            .records()
+            .map(|result| result.unwrap().iter().map(String::from).collect())
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                vec!["0", "false", "3", "20", "10", "10", "15", "20", "25", "30"],
+                vec![
+                    "1",
+                    "false",
+                    "2",
+                    "25",
+                    "7.0710678118654755",
+                    "20",
+                    "22.5",
+                    "25",
+                    "27.5",
+                    "30"
+                ],
+                vec!["1", "true", "1", "10", "0", "10", "10", "10", "10", "10"],
+            ]
+        );
+    }
+
+    #[test]
+    fn add_report_with_columns() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        {
+            let mut context = Context::new();
+            context.report_options().directory(path.clone());
+            // Columns can capture values
+            let label = String::from("run");
+            context
+                .add_report_with_columns::<SampleReport>(
+                    "computed",
+                    vec![
+                        (
+                            "t",
+                            report_column(|context, _row| context.get_current_time().to_string()),
+                        ),
+                        (
+                            "is_runner",
+                            report_column(|context, row: &SampleReport| {
+                                let person = context.query_people((IsRunner, true));
+                                (person.len() == 1 && row.id == 1).to_string()
+                            }),
+                        ),
+                        (
+                            "label",
+                            report_column(move |_context, row: &SampleReport| {
+                                format!("{label}-{}", row.id)
+                            }),
+                        ),
+                    ],
+                )
+                .unwrap();
+            let person = context.add_person(()).unwrap();
+            context.add_plan(1.0, move |context| {
+                context.set_person_property(person, IsRunner, true);
+                context.send_report(SampleReport {
+                    id: 1,
+                    value: "Test Value".to_string(),
+                });
+            });
+            context.add_plan(2.5, |context| {
+                context.send_report(SampleReport {
+                    id: 2,
+                    value: "Another Value".to_string(),
+                });
+            });
+            context.execute();
+        }
+
+        let mut reader = csv::Reader::from_path(path.join("computed.csv")).unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec!["id", "value", "t", "is_runner", "label"]
+        );
+        let actual: Vec<Vec<String>> = reader
+            .records()
+            .map(|result| result.unwrap().iter().map(String::from).collect())
+            .collect();
+        let expected = vec![
+            vec!["1", "Test Value", "1", "true", "run-1"],
+            vec!["2", "Another Value", "2.5", "false", "run-2"],
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn add_report_with_no_columns() {
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        {
+            let mut context = Context::new();
+            context.report_options().directory(path.clone());
+            context
+                .add_report_with_columns::<SampleReport>("no_columns", vec![])
+                .unwrap();
+            context.send_report(SampleReport {
+                id: 1,
+                value: "Value,1".to_string(),
+            });
+        }
+
+        let mut reader = csv::Reader::from_path(path.join("no_columns.csv")).unwrap();
+        assert_eq!(reader.headers().unwrap(), vec!["id", "value"]);
+        let record: SampleReport = reader.deserialize().next().unwrap().unwrap();
+        assert_eq!(record.id, 1);
+        assert_eq!(record.value, "Value,1");
+    }
+
+    #[derive(Serialize)]
+    struct FloatReport {
+        value: f64,
+    }
+
+    create_report_trait!(FloatReport);
+
+    #[test]
+    fn send_report_with_non_finite_values() {
+        let mut context = Context::new();
+        let temp_dir = tempdir().unwrap();
+        let path = PathBuf::from(&temp_dir.path());
+        context.report_options().directory(path.clone());
+        context.add_report::<FloatReport>("float_report").unwrap();
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 0.5] {
+            context.send_report(FloatReport { value });
+        }
+        // Dropping the context flushes the report
+        drop(context);
+
+        let contents = std::fs::read_to_string(path.join("float_report.csv")).unwrap();
+        assert_eq!(contents, "value\nNaN\ninf\n-inf\n0.5\n");
+    }
 }
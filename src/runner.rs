@@ -1,16 +1,25 @@
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 
 use crate::error::IxaError;
+pub use crate::experiment::{run_experiment, run_experiment_with_options, ExperimentOptions};
 use crate::global_properties::ContextGlobalPropertiesExt;
-use crate::random::ContextRandomExt;
+use crate::log::{default_log_file_path, ContextLogExt};
+use crate::modes::set_mode_from_arg;
+use crate::random::{mix_seed, ContextRandomExt};
 use crate::report::ContextReportExt;
-use crate::{context::Context, debugger::ContextDebugExt, web_api::ContextWebApiExt};
+use crate::sweep::{Sweep, SweepRun, MANIFEST_FILE};
+use crate::web_api::{ContextWebApiExt, DEFAULT_KEEP_API_ALIVE};
+use crate::{context::Context, debugger::ContextDebugExt};
 use crate::{info, set_log_level, LevelFilter};
 
-use clap::{Args, Command, FromArgMatches as _};
+use clap::{ArgMatches, Args, Command, FromArgMatches as _};
 
 /// Default cli arguments for ixa runner
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct BaseArgs {
     /// Random seed
     #[arg(short, long, default_value = "0")]
@@ -36,13 +45,49 @@ pub struct BaseArgs {
     #[arg(short, long)]
     pub log_level: Option<LevelFilter>,
 
+    /// Optional path for the log file. When logging is enabled and an output directory or
+    /// prefix is given, defaults to `<output>/<prefix>run.log`
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
     /// Set a breakpoint at a given time and start the debugger. Defaults to t=0.0
     #[arg(short, long)]
     pub debugger: Option<Option<f64>>,
 
+    /// Start the debugger once the simulation finishes, to query, tabulate
+    /// and export its final state. Implied by --debugger
+    #[arg(long, conflicts_with = "web")]
+    pub explore: bool,
+
     /// Enable the Web API at a given time. Defaults to t=0.0
     #[arg(short, long)]
     pub web: Option<Option<u16>>,
+
+    /// How many seconds the Web API keeps serving read-only commands after
+    /// the simulation finishes, unless a client finalizes it first.
+    /// Defaults to 10
+    #[arg(long, value_name = "SECS")]
+    pub keep_api_alive: Option<u64>,
+
+    /// Let Web API requests give person ids as bare integers, e.g., `12`,
+    /// as well as tagged ids like `person:12`
+    #[arg(long)]
+    pub web_bare_person_ids: bool,
+
+    /// Set a simulation mode, e.g., `--mode MaskPolicy=Schools`. May be given
+    /// more than once, and overrides modes set in the config file
+    #[arg(long = "mode", value_name = "MODE=VALUE")]
+    pub modes: Vec<String>,
+
+    /// Print the execution statistics to stderr as a line of JSON every
+    /// SECS seconds while the simulation runs
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub stats_interval: Option<u64>,
+
+    /// Run every combination of the values in the `sweep` of the config
+    /// file, one after another. See [`crate::sweep`]
+    #[arg(long, requires = "config")]
+    pub sweep: bool,
 }
 
 impl BaseArgs {
@@ -54,8 +99,15 @@ impl BaseArgs {
             file_prefix: None,
             force_overwrite: false,
             log_level: None,
+            log_file: None,
             debugger: None,
+            explore: false,
             web: None,
+            keep_api_alive: None,
+            web_bare_person_ids: false,
+            modes: Vec::new(),
+            stats_interval: None,
+            sweep: false,
         }
     }
 }
@@ -69,18 +121,82 @@ impl Default for BaseArgs {
 #[derive(Args)]
 pub struct PlaceholderCustom {}
 
+/// Arguments of the `run-experiment` command
+#[derive(Args, Debug)]
+struct ExperimentArgs {
+    /// Path of the YAML manifest describing the experiment
+    manifest: PathBuf,
+
+    /// Rerun runs that already finished
+    #[arg(long)]
+    force: bool,
+
+    /// The number of runs to run at once. Defaults to the number of CPUs
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+}
+
+const RUN_EXPERIMENT: &str = "run-experiment";
+
 fn create_ixa_cli() -> Command {
     let cli = Command::new("ixa");
-    BaseArgs::augment_args(cli)
+    BaseArgs::augment_args(cli).subcommand(ExperimentArgs::augment_args(
+        Command::new(RUN_EXPERIMENT).about("Run the experiment described by a manifest"),
+    ))
+}
+
+// Runs the experiment of `run-experiment` and exits, with a nonzero status
+// if any run failed. `setup_fn` is called with the arguments for each run.
+fn run_experiment_command<F>(
+    matches: &ArgMatches,
+    setup_fn: F,
+) -> Result<Context, Box<dyn std::error::Error>>
+where
+    F: Fn(&mut Context, BaseArgs) -> Result<(), IxaError> + Sync,
+{
+    let args = ExperimentArgs::from_arg_matches(matches)?;
+    let mut options = ExperimentOptions {
+        force: args.force,
+        ..Default::default()
+    };
+    if let Some(threads) = args.threads {
+        options.threads = usize::try_from(threads)?;
+    }
+    let summary = run_experiment_with_options(
+        &args.manifest,
+        |context, run| {
+            let base_args = BaseArgs {
+                random_seed: run.seed,
+                output_dir: Some(run.output_dir.clone()),
+                force_overwrite: true,
+                modes: run.modes.clone(),
+                ..Default::default()
+            };
+            setup_fn(context, base_args)
+        },
+        &options,
+    )?;
+    let failed = summary.failed();
+    println!(
+        "Finished {} runs, {failed} failed",
+        summary.runs.len() - failed
+    );
+    std::process::exit(i32::from(failed > 0));
 }
 
 /// Runs a simulation with custom cli arguments.
 ///
 /// This function allows you to define custom arguments and a setup function
 ///
+/// With `run-experiment <manifest>`, runs the experiment described by the
+/// manifest instead, as with [`run_experiment()`], calling `setup_fn` for
+/// each run with its seed, output directory and modes and the custom
+/// arguments, and exits with a nonzero status if any run failed. Other
+/// base arguments don't apply to the runs.
+///
 /// # Parameters
 /// - `setup_fn`: A function that takes a mutable reference to a `Context`, a `BaseArgs` struct,
-///    a Option<A> where A is the custom cli arguments struct
+///   a Option<A> where A is the custom cli arguments struct
 ///
 /// # Errors
 /// Returns an error if argument parsing or the setup function fails
@@ -88,21 +204,44 @@ fn create_ixa_cli() -> Command {
 pub fn run_with_custom_args<A, F>(setup_fn: F) -> Result<Context, Box<dyn std::error::Error>>
 where
     A: Args,
-    F: Fn(&mut Context, BaseArgs, Option<A>) -> Result<(), IxaError>,
+    F: Fn(&mut Context, BaseArgs, Option<A>) -> Result<(), IxaError> + Sync,
 {
     let mut cli = create_ixa_cli();
     cli = A::augment_args(cli);
     let matches = cli.get_matches();
 
+    if let Some(experiment_matches) = matches.subcommand_matches(RUN_EXPERIMENT) {
+        return run_experiment_command(experiment_matches, |context, base_args| {
+            let custom_args =
+                A::from_arg_matches(&matches).map_err(|error| IxaError::from(error.to_string()))?;
+            setup_fn(context, base_args, Some(custom_args))
+        });
+    }
+
     let base_args_matches = BaseArgs::from_arg_matches(&matches)?;
+    if base_args_matches.sweep {
+        return run_sweep(&base_args_matches, |args, run| {
+            let custom_matches = A::from_arg_matches(&matches)?;
+            run_with_args_internal(args, Some(custom_matches), Some(run), &setup_fn)
+        });
+    }
     let custom_matches = A::from_arg_matches(&matches)?;
-    run_with_args_internal(base_args_matches, Some(custom_matches), setup_fn)
+    run_with_args_internal(base_args_matches, Some(custom_matches), None, setup_fn)
 }
 
 /// Runs a simulation with default cli arguments
 ///
 /// This function parses command line arguments allows you to define a setup function
 ///
+/// With `run-experiment <manifest>`, runs the experiment described by the
+/// manifest instead; see [`run_with_custom_args()`].
+///
+/// With `--sweep`, runs every combination of the values in the `sweep` of
+/// the config file, one after another, calling `setup_fn` for each with
+/// the combination's seed and file prefix; see [`crate::sweep`]. The other
+/// base arguments apply to every run, and the context of the last run is
+/// returned.
+///
 /// # Parameters
 /// - `setup_fn`: A function that takes a mutable reference to a `Context` and `BaseArgs` struct
 ///
@@ -111,18 +250,64 @@ where
 #[allow(clippy::missing_errors_doc)]
 pub fn run_with_args<F>(setup_fn: F) -> Result<Context, Box<dyn std::error::Error>>
 where
-    F: Fn(&mut Context, BaseArgs, Option<PlaceholderCustom>) -> Result<(), IxaError>,
+    F: Fn(&mut Context, BaseArgs, Option<PlaceholderCustom>) -> Result<(), IxaError> + Sync,
 {
     let cli = create_ixa_cli();
     let matches = cli.get_matches();
 
+    if let Some(experiment_matches) = matches.subcommand_matches(RUN_EXPERIMENT) {
+        return run_experiment_command(experiment_matches, |context, base_args| {
+            setup_fn(context, base_args, None)
+        });
+    }
+
     let base_args_matches = BaseArgs::from_arg_matches(&matches)?;
-    run_with_args_internal(base_args_matches, None, setup_fn)
+    if base_args_matches.sweep {
+        return run_sweep(&base_args_matches, |args, run| {
+            run_with_args_internal(args, None, Some(run), &setup_fn)
+        });
+    }
+    run_with_args_internal(base_args_matches, None, None, setup_fn)
+}
+
+// Runs each combination of the sweep in the config file of `args` with
+// `run_one`, after writing the sweep manifest, and returns the context of
+// the last run.
+fn run_sweep<F>(args: &BaseArgs, run_one: F) -> Result<Context, Box<dyn std::error::Error>>
+where
+    F: Fn(BaseArgs, &SweepRun) -> Result<Context, Box<dyn std::error::Error>>,
+{
+    let config_path = args.config.clone().ok_or("--sweep requires --config")?;
+    let Some(sweep) = Sweep::from_path(&config_path)? else {
+        return Err(format!("{} doesn't define a sweep", config_path.display()).into());
+    };
+    let prefix = args.file_prefix.clone().unwrap_or_default();
+    let output_dir = args.output_dir.clone().unwrap_or_default();
+    sweep.write_manifest(
+        &output_dir.join(format!("{prefix}{MANIFEST_FILE}")),
+        args.random_seed,
+        args.force_overwrite,
+    )?;
+
+    let runs = sweep.runs(args.random_seed);
+    let mut context = None;
+    for run in &runs {
+        println!("Running sweep {} of {}", run.index + 1, runs.len());
+        let run_args = BaseArgs {
+            random_seed: run.seed,
+            file_prefix: Some(format!("{prefix}sweep{}_", run.index)),
+            sweep: false,
+            ..args.clone()
+        };
+        context = Some(run_one(run_args, run)?);
+    }
+    Ok(context.expect("A sweep has at least one run"))
 }
 
 fn run_with_args_internal<A, F>(
     args: BaseArgs,
     custom_args: Option<A>,
+    sweep_run: Option<&SweepRun>,
     setup_fn: F,
 ) -> Result<Context, Box<dyn std::error::Error>>
 where
@@ -134,8 +319,20 @@ where
     // Optionally set global properties from a file
     if args.config.is_some() {
         let config_path = args.config.clone().unwrap();
-        println!("Loading global properties from: {config_path:?}");
+        println!("Loading global properties from: {}", config_path.display());
         context.load_global_properties(&config_path)?;
+        if sweep_run.is_none() && Sweep::from_path(&config_path)?.is_some() {
+            return Err(format!(
+                "{} defines a sweep; run it with --sweep",
+                config_path.display()
+            )
+            .into());
+        }
+    }
+
+    // Modes given on the command line override the config file
+    for mode in &args.modes {
+        set_mode_from_arg(&mut context, mode)?;
     }
 
     // Configure report options
@@ -149,6 +346,13 @@ where
     if args.force_overwrite {
         report_config.overwrite(true);
     }
+    let log_file = args.log_file.clone().or_else(|| {
+        (args.log_level.is_some() && (args.output_dir.is_some() || args.file_prefix.is_some()))
+            .then(|| default_log_file_path(&mut context))
+    });
+    if let Some(path) = log_file {
+        context.set_log_file(path)?;
+    }
     if let Some(level) = args.log_level {
         set_log_level(level);
         info!("Logging enabled at level {level}");
@@ -156,7 +360,12 @@ where
         info!("Logging disabled.");
     }
 
-    context.init_random(args.random_seed);
+    // The sweep's values override the config file, and its seed is the
+    // seed of the run
+    match sweep_run {
+        Some(run) => run.apply(&mut context)?,
+        None => context.init_random(args.random_seed),
+    }
 
     // If a breakpoint is provided, stop at that time
     if let Some(t) = args.debugger {
@@ -172,21 +381,148 @@ where
         let port = t.unwrap_or(33334);
         let url = context.setup_web_api(port).unwrap();
         println!("Web API active on {url}");
+        context.accept_bare_person_ids(args.web_bare_person_ids);
         context.schedule_web_api(0.0);
     }
 
+    if let Some(seconds) = args.stats_interval {
+        context.print_execution_statistics_every(Duration::from_secs(seconds));
+    }
+
+    let explore = args.explore || args.debugger.is_some();
+    let keep_api_alive = args.web.map(|_| {
+        args.keep_api_alive
+            .map_or(DEFAULT_KEEP_API_ALIVE, Duration::from_secs)
+    });
+
     // Run the provided Fn
-    setup_fn(&mut context, args, custom_args)?;
+    if let Err(error) = setup_fn(&mut context, args, custom_args) {
+        eprintln!("Error setting up the simulation: {}", error.display_chain());
+        return Err(error.into());
+    }
 
     // Execute the context
     context.execute();
+
+    // Let the debugger or the Web API look at the final state
+    if explore {
+        context.explore();
+    }
+    if let Some(keep_alive) = keep_api_alive {
+        context.finish_web_api(keep_alive);
+    }
     Ok(context)
 }
 
+/// Options for [`run_scenarios()`]
+pub struct ScenarioOptions {
+    /// The number of scenarios to run at once, each on its own thread
+    pub threads: usize,
+    /// The directory that reports are written to. Each scenario's report
+    /// files are prefixed with `scenario<index>_`
+    pub output_dir: PathBuf,
+    /// The seed that the random seed of each scenario is derived from
+    pub base_seed: u64,
+    /// Overwrite existing report files?
+    pub force_overwrite: bool,
+}
+
+impl Default for ScenarioOptions {
+    fn default() -> Self {
+        ScenarioOptions {
+            threads: thread::available_parallelism().map_or(1, usize::from),
+            output_dir: std::env::current_dir().unwrap_or_default(),
+            base_seed: 0,
+            force_overwrite: false,
+        }
+    }
+}
+
+/// The random seed of scenario `index` in [`run_scenarios()`], which
+/// depends only on the base seed and the index, and is the same on every
+/// platform.
+#[must_use]
+pub fn scenario_seed(base_seed: u64, index: usize) -> u64 {
+    mix_seed(base_seed, &(index as u64).to_le_bytes())
+}
+
+/// Runs a simulation for each of `scenarios`, e.g., a set of parameter
+/// values for calibration, on up to `options.threads` threads at once.
+///
+/// Each scenario gets its own `Context`, in which the reports are
+/// configured to be written to `options.output_dir` with the file prefix
+/// `scenario<index>_`, where `index` is the index of the scenario in
+/// `scenarios`, and the random seed is set to
+/// [`scenario_seed(options.base_seed, index)`](scenario_seed()), so that a
+/// rerun reproduces each scenario regardless of the number of threads.
+/// `setup_fn` is then called with the context and the scenario, and the
+/// context is executed.
+///
+/// Returns the result of each scenario, in the order of `scenarios`: an
+/// error if adding the scenario's reports or `setup_fn` failed.
+///
+/// # Panics
+/// Panics if a scenario panics.
+pub fn run_scenarios<P, F>(
+    scenarios: &[P],
+    setup_fn: F,
+    options: &ScenarioOptions,
+) -> Vec<Result<(), IxaError>>
+where
+    P: Sync,
+    F: Fn(&mut Context, &P) -> Result<(), IxaError> + Sync,
+{
+    let run_scenario = |index: usize| -> Result<(), IxaError> {
+        let mut context = Context::new();
+        context
+            .report_options()
+            .directory(options.output_dir.clone())
+            .file_prefix(format!("scenario{index}_"))
+            .overwrite(options.force_overwrite);
+        context.init_random(scenario_seed(options.base_seed, index));
+        setup_fn(&mut context, &scenarios[index])?;
+        context.execute();
+        Ok(())
+    };
+    run_in_parallel(scenarios.len(), options.threads, run_scenario)
+}
+
+// Calls `run` with each index in `0..count` on up to `threads` threads at
+// once and returns the results in order of the index.
+pub(crate) fn run_in_parallel<R, F>(count: usize, threads: usize, run: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(usize) -> R + Sync,
+{
+    let next_index = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.clamp(1, count.max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::Relaxed);
+                        if index >= count {
+                            return results;
+                        }
+                        results.push((index, run(index)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Scenario panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{define_global_property, define_rng};
+    use crate::{define_global_property, define_rng, define_simulation_mode, ContextModeExt};
     use serde::{Deserialize, Serialize};
 
     #[derive(Args, Debug)]
@@ -213,6 +549,56 @@ mod tests {
             .stdout("42\n");
     }
 
+    #[test]
+    fn test_cli_web_api_exits_after_keep_alive() {
+        use std::io::{BufRead, BufReader};
+        use std::process::{Command, Stdio};
+        use std::time::Instant;
+
+        // Note this target is defined in the bin section of Cargo.toml
+        // and the entry point is in tests/bin/runner_test_web_api
+        let mut child = Command::new(assert_cmd::cargo::cargo_bin("runner_test_web_api"))
+            .args(["--web", "33342", "--keep-api-alive", "1"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let url = stdout
+            .lines()
+            .map(Result::unwrap)
+            .find_map(|line| line.strip_prefix("Web API active on ").map(String::from))
+            .unwrap();
+
+        let client = reqwest::blocking::Client::new();
+        let send = |cmd: &str| {
+            client
+                .post(format!("{url}cmd/{cmd}"))
+                .json(&serde_json::json!({}))
+                .send()
+                .unwrap()
+        };
+        assert!(send("continue").status().is_success());
+
+        // The final state can still be read after the simulation finishes.
+        let status: serde_json::Value = send("status").json().unwrap();
+        assert_eq!(status["running"], serde_json::json!(false));
+        assert_eq!(status["population"], serde_json::json!(2));
+
+        // The process exits by itself once the keep alive time has passed.
+        let start = Instant::now();
+        let exit_status = loop {
+            if let Some(exit_status) = child.try_wait().unwrap() {
+                break exit_status;
+            }
+            if start.elapsed() > Duration::from_secs(10) {
+                child.kill().unwrap();
+                panic!("Runner didn't exit after the keep alive time");
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        assert!(exit_status.success());
+    }
+
     #[test]
     fn test_run_with_args() {
         let result = run_with_args(|_, _, _| Ok(()));
@@ -230,7 +616,7 @@ mod tests {
         let mut compare_ctx = Context::new();
         compare_ctx.init_random(42);
         define_rng!(TestRng);
-        let result = run_with_args_internal(test_args, None, |ctx, _, _: Option<()>| {
+        let result = run_with_args_internal(test_args, None, None, |ctx, _, _: Option<()>| {
             assert_eq!(
                 ctx.sample_range(TestRng, 0..100),
                 compare_ctx.sample_range(TestRng, 0..100)
@@ -252,7 +638,7 @@ mod tests {
             config: Some(PathBuf::from("tests/data/global_properties_runner.json")),
             ..Default::default()
         };
-        let result = run_with_args_internal(test_args, None, |ctx, _, _: Option<()>| {
+        let result = run_with_args_internal(test_args, None, None, |ctx, _, _: Option<()>| {
             let p3 = ctx.get_global_property_value(RunnerProperty).unwrap();
             assert_eq!(p3.field_int, 0);
             Ok(())
@@ -268,7 +654,7 @@ mod tests {
             force_overwrite: true,
             ..Default::default()
         };
-        let result = run_with_args_internal(test_args, None, |ctx, _, _: Option<()>| {
+        let result = run_with_args_internal(test_args, None, None, |ctx, _, _: Option<()>| {
             let opts = &ctx.report_options();
             assert_eq!(opts.output_dir, PathBuf::from("data"));
             assert_eq!(opts.file_prefix, "test".to_string());
@@ -282,7 +668,7 @@ mod tests {
     fn test_run_with_custom() {
         let test_args = BaseArgs::new();
         let custom = CustomArgs { a: 42 };
-        let result = run_with_args_internal(test_args, Some(custom), |_, _, c| {
+        let result = run_with_args_internal(test_args, Some(custom), None, |_, _, c| {
             assert_eq!(c.unwrap().a, 42);
             Ok(())
         });
@@ -293,7 +679,261 @@ mod tests {
     fn test_run_with_logging_enabled() {
         let mut test_args = BaseArgs::new();
         test_args.log_level = Some(LevelFilter::Info);
-        let result = run_with_args_internal(test_args, None, |_, _, _: Option<()>| Ok(()));
+        let result = run_with_args_internal(test_args, None, None, |_, _, _: Option<()>| Ok(()));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_run_with_log_file_in_output_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let test_args = BaseArgs {
+            output_dir: Some(dir.path().to_path_buf()),
+            file_prefix: Some("scenario_".to_string()),
+            log_level: Some(LevelFilter::Info),
+            ..Default::default()
+        };
+        let result = run_with_args_internal(test_args, None, None, |_, _, _: Option<()>| Ok(()));
+        assert!(result.is_ok());
+        assert!(dir.path().join("scenario_run.log").exists());
+    }
+
+    #[test]
+    fn test_run_with_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("model.log");
+        let test_args = BaseArgs {
+            output_dir: Some(dir.path().to_path_buf()),
+            log_file: Some(path.clone()),
+            ..Default::default()
+        };
+        let result = run_with_args_internal(test_args, None, None, |_, _, _: Option<()>| Ok(()));
         assert!(result.is_ok());
+        assert!(path.exists());
+        assert!(!dir.path().join("run.log").exists());
+    }
+
+    #[test]
+    fn test_stats_interval_arg() {
+        let command = BaseArgs::augment_args(Command::new("test"));
+        let matches = command
+            .clone()
+            .try_get_matches_from(["test", "--stats-interval", "5"])
+            .unwrap();
+        let test_args = BaseArgs::from_arg_matches(&matches).unwrap();
+        assert_eq!(test_args.stats_interval, Some(5));
+        assert!(command
+            .try_get_matches_from(["test", "--stats-interval", "0"])
+            .is_err());
+        let result = run_with_args_internal(test_args, None, None, |_, _, _: Option<()>| Ok(()));
+        assert!(result.is_ok());
+    }
+
+    define_simulation_mode!(RunnerMode { First, Second });
+
+    #[test]
+    fn test_run_with_mode() {
+        let command = BaseArgs::augment_args(Command::new("test"));
+        let matches = command
+            .try_get_matches_from(["test", "--mode", "RunnerMode=Second"])
+            .unwrap();
+        let test_args = BaseArgs::from_arg_matches(&matches).unwrap();
+        assert_eq!(test_args.modes, vec!["RunnerMode=Second".to_string()]);
+        let result = run_with_args_internal(test_args, None, None, |ctx, _, _: Option<()>| {
+            assert_eq!(ctx.mode(RunnerMode), RunnerModeValue::Second);
+            Ok(())
+        });
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_run_with_invalid_mode() {
+        let test_args = BaseArgs {
+            modes: vec!["RunnerMode=Third".to_string()],
+            ..Default::default()
+        };
+        let Err(error) =
+            run_with_args_internal(test_args, None, None, |_, _, _: Option<()>| Ok(()))
+        else {
+            panic!("Expected an error for an unknown mode value");
+        };
+        let error = error.to_string();
+        assert!(error.contains("First, Second"), "{error}");
+    }
+
+    #[test]
+    fn test_run_scenarios() {
+        use crate::people::ContextPeopleExt;
+        use crate::random::ContextRandomExt;
+        use crate::report::Report;
+        use crate::{create_report_trait, define_rng};
+
+        define_rng!(ScenarioRng);
+
+        #[derive(Serialize, Deserialize)]
+        struct Draw {
+            population: usize,
+            draw: u64,
+        }
+        create_report_trait!(Draw);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let run = |threads: usize| {
+            let options = ScenarioOptions {
+                threads,
+                output_dir: temp_dir.path().to_path_buf(),
+                base_seed: 42,
+                force_overwrite: true,
+            };
+            let results = run_scenarios(
+                &[1, 2, 3, 0],
+                |context, population| {
+                    if *population == 0 {
+                        return Err(IxaError::IxaError("Empty population".to_string()));
+                    }
+                    context.add_report::<Draw>("draws")?;
+                    for _ in 0..*population {
+                        context.add_person(())?;
+                    }
+                    context.add_plan(1.0, |context| {
+                        context.send_report(Draw {
+                            population: context.get_current_population(),
+                            draw: context.sample_range(ScenarioRng, 0..u64::MAX),
+                        });
+                    });
+                    Ok(())
+                },
+                &options,
+            );
+            assert!(results[..3].iter().all(Result::is_ok));
+            assert!(results[3].is_err());
+
+            (0..3)
+                .map(|index| {
+                    let path = temp_dir.path().join(format!("scenario{index}_draws.csv"));
+                    let mut reader = csv::Reader::from_path(path).unwrap();
+                    let row: Vec<String> = reader
+                        .records()
+                        .next()
+                        .unwrap()
+                        .unwrap()
+                        .iter()
+                        .map(String::from)
+                        .collect();
+                    row
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let rows = run(2);
+        assert_eq!(rows[0][0], "1");
+        assert_eq!(rows[1][0], "2");
+        assert_eq!(rows[2][0], "3");
+        // Each scenario has its own seed.
+        assert_ne!(rows[0][1], rows[1][1]);
+        // Reruns reproduce each scenario, regardless of the number of threads.
+        assert_eq!(run(1), rows);
+        assert_eq!(scenario_seed(42, 1), scenario_seed(42, 1));
+        assert_ne!(scenario_seed(42, 1), scenario_seed(43, 1));
+        // The seeds don't depend on the width of usize or the byte order.
+        assert_eq!(scenario_seed(42, 1), 821_699_392_136_364_088);
+    }
+
+    define_global_property!(RunnerSweepR0, f64);
+
+    #[test]
+    fn test_run_sweep() {
+        use crate::create_report_trait;
+        use crate::report::Report;
+        use std::collections::HashSet;
+
+        define_rng!(SweepRng);
+
+        #[derive(Serialize, Deserialize)]
+        struct Incidence {
+            r0: f64,
+            draw: u64,
+        }
+        create_report_trait!(Incidence);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = temp_dir.path().join("sweep.json");
+        std::fs::write(
+            &config,
+            r#"{
+                "ixa.RunnerSweepR0": 1.0,
+                "sweep": {
+                    "ixa.RunnerSweepR0": [1.5, 2.0, 2.5],
+                    "seed": [1, 2, 3, 4, 5]
+                }
+            }"#,
+        )
+        .unwrap();
+        let args = BaseArgs {
+            config: Some(config.clone()),
+            output_dir: Some(temp_dir.path().to_path_buf()),
+            sweep: true,
+            ..Default::default()
+        };
+        let setup = |context: &mut Context, _: BaseArgs, _: Option<()>| {
+            context.add_report::<Incidence>("incidence")?;
+            context.add_plan(1.0, |context| {
+                context.send_report(Incidence {
+                    r0: *context.get_global_property_value(RunnerSweepR0).unwrap(),
+                    draw: context.sample_range(SweepRng, 0..u64::MAX),
+                });
+            });
+            Ok(())
+        };
+        run_sweep(&args, |args, run| {
+            run_with_args_internal(args, None, Some(run), setup)
+        })
+        .unwrap();
+
+        let mut manifest = csv::Reader::from_path(temp_dir.path().join(MANIFEST_FILE)).unwrap();
+        let manifest: Vec<Vec<String>> = manifest
+            .records()
+            .map(|record| record.unwrap().iter().map(String::from).collect())
+            .collect();
+        assert_eq!(manifest.len(), 15);
+        let mut draws = HashSet::new();
+        for row in &manifest {
+            let path = temp_dir
+                .path()
+                .join(format!("sweep{}_incidence.csv", row[0]));
+            let mut reader = csv::Reader::from_path(path).unwrap();
+            let record = reader.records().next().unwrap().unwrap();
+            // The report of each run matches its row of the manifest
+            assert_eq!(&record[0], row[2].as_str());
+            draws.insert((record[0].to_string(), record[1].to_string()));
+        }
+        assert_eq!(manifest[0][1..], ["1", "1.5"]);
+        assert_eq!(manifest[14][1..], ["5", "2.5"]);
+        // Runs with the same seed make the same draws
+        assert_eq!(draws.len(), 15);
+        assert_eq!(
+            draws
+                .iter()
+                .map(|(_, draw)| draw)
+                .collect::<HashSet<_>>()
+                .len(),
+            5
+        );
+
+        // A sweep isn't run without --sweep, and the manifest isn't
+        // overwritten without --force-overwrite
+        let run_without_sweep = run_with_args_internal(
+            BaseArgs {
+                sweep: false,
+                ..args.clone()
+            },
+            None,
+            None,
+            setup,
+        );
+        assert!(run_without_sweep.is_err());
+        let rerun = run_sweep(&args, |args, run| {
+            run_with_args_internal(args, None, Some(run), setup)
+        });
+        assert!(rerun.is_err());
     }
 }
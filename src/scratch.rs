@@ -0,0 +1,145 @@
+//! Scratch memory for short-lived allocations.
+//!
+//! Code that runs many times during a simulation, such as plans that query
+//! people or look at their contacts, often needs temporary collections that
+//! are thrown away as soon as the plan is done. Allocating these on the heap
+//! each time is expensive, so [`Context::with_scratch()`] provides a bump
+//! arena instead:
+//!
+//! ```
+//! use ixa::{Context, ContextPeopleExt, PersonId};
+//!
+//! let mut context = Context::new();
+//! for _ in 0..10 {
+//!     context.add_person(()).unwrap();
+//! }
+//! let total = context.with_scratch(|scratch| {
+//!     let mut people = scratch.vec::<PersonId>();
+//!     people.extend(context.query_people(()));
+//!     people.len()
+//! });
+//! assert_eq!(total, 10);
+//! ```
+//!
+//! Collections allocated from a [`Scratch`] borrow it, so they can't be
+//! returned from the closure or kept until a later plan. When the closure
+//! returns, the arena is reset: its memory is kept for the next call to
+//! `with_scratch()` rather than being freed. Calls may be nested, e.g., from
+//! a plan that queries people while it is using scratch memory itself; each
+//! active call gets its own arena.
+use bumpalo::Bump;
+use std::cell::RefCell;
+
+/// A vector whose memory is allocated from a [`Scratch`] arena.
+pub type ScratchVec<'a, T> = bumpalo::collections::Vec<'a, T>;
+
+/// An arena for temporary allocations, passed to the closure given to
+/// [`Context::with_scratch()`].
+pub struct Scratch<'a> {
+    bump: &'a Bump,
+}
+
+impl<'a> Scratch<'a> {
+    /// Create an empty vector that allocates from this arena.
+    #[must_use]
+    pub fn vec<T>(&self) -> ScratchVec<'a, T> {
+        ScratchVec::new_in(self.bump)
+    }
+
+    /// Create an empty vector with room for `capacity` elements that
+    /// allocates from this arena.
+    #[must_use]
+    pub fn vec_with_capacity<T>(&self, capacity: usize) -> ScratchVec<'a, T> {
+        ScratchVec::with_capacity_in(capacity, self.bump)
+    }
+
+    /// Collect an iterator into a vector that allocates from this arena.
+    pub fn collect<T>(&self, iter: impl IntoIterator<Item = T>) -> ScratchVec<'a, T> {
+        ScratchVec::from_iter_in(iter, self.bump)
+    }
+
+    /// The number of bytes of memory held by this arena, including memory
+    /// that was kept from earlier calls to `with_scratch()`.
+    #[must_use]
+    pub fn capacity_bytes(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+}
+
+// The arenas that aren't in use by an active call to `with_scratch()`.
+#[derive(Default)]
+pub(crate) struct ScratchArenas {
+    free: RefCell<Vec<Bump>>,
+}
+
+impl ScratchArenas {
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&Scratch<'_>) -> R) -> R {
+        // Take an arena out of the pool for the duration of the call, so a
+        // nested call gets a different one.
+        let mut bump = self.free.borrow_mut().pop().unwrap_or_default();
+        let result = f(&Scratch { bump: &bump });
+        bump.reset();
+        self.free.borrow_mut().push(bump);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Context, PersonId};
+
+    #[test]
+    fn memory_is_reused() {
+        let context = Context::new();
+        let first = context.with_scratch(|scratch| {
+            let mut v = scratch.vec::<u64>();
+            v.extend(0..10_000);
+            assert_eq!(v.iter().sum::<u64>(), 49_995_000);
+            scratch.capacity_bytes()
+        });
+        assert!(first >= 80_000);
+
+        // The memory from the first call is kept rather than freed, and the
+        // arena is empty again
+        context.with_scratch(|scratch| {
+            assert_eq!(scratch.capacity_bytes(), first);
+            let v = scratch.vec_with_capacity::<u64>(10_000);
+            assert!(v.is_empty());
+            assert_eq!(scratch.capacity_bytes(), first);
+        });
+    }
+
+    #[test]
+    fn nested_calls_use_separate_arenas() {
+        let context = Context::new();
+        context.with_scratch(|outer| {
+            let people = outer.collect((0..5).map(PersonId));
+            let inner_sum = context.with_scratch(|inner| {
+                let mut doubled = inner.vec();
+                doubled.extend(people.iter().map(|p| p.0 * 2));
+                doubled.iter().sum::<usize>()
+            });
+            assert_eq!(inner_sum, 20);
+            // The inner reset didn't touch the outer arena
+            assert_eq!(
+                people.iter().copied().collect::<Vec<_>>(),
+                (0..5).map(PersonId).collect::<Vec<_>>()
+            );
+        });
+    }
+
+    #[test]
+    fn scratch_in_plans() {
+        let mut context = Context::new();
+        for t in 0..3 {
+            context.add_plan(f64::from(t), move |context| {
+                let count = context.with_scratch(|scratch| {
+                    let v = scratch.collect(0..=t);
+                    v.len()
+                });
+                assert_eq!(count, usize::try_from(t).unwrap() + 1);
+            });
+        }
+        context.execute();
+    }
+}
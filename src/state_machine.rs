@@ -0,0 +1,821 @@
+//! State machines for person properties, e.g., for behavioral states such
+//! as commuting, isolating and hospitalized that people move between
+//! according to rules.
+//!
+//! A [`StateMachine`] declares the transitions between the values of a
+//! property, each with a [`Trigger`] and optionally a guard that has to
+//! pass for the transition to happen, and actions to run when people enter
+//! and leave states. Once it's added with
+//! [`ContextStateMachineExt::add_state_machine()`], the property can only
+//! change through its transitions, and each transition emits a
+//! [`TransitionEvent`].
+//!
+//! ```
+//! use ixa::state_machine::{ContextStateMachineExt, StateMachine, Trigger};
+//! use ixa::{define_person_property, define_person_property_with_default, Context, ContextPeopleExt};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+//! pub enum BehaviorState {
+//!     Commuting,
+//!     Isolating,
+//!     Hospitalized,
+//! }
+//! define_person_property_with_default!(Behavior, BehaviorState, BehaviorState::Commuting);
+//! define_person_property!(Severe, bool);
+//!
+//! let mut context = Context::new();
+//! let machine = StateMachine::on(Behavior)
+//!     .transition(BehaviorState::Commuting, BehaviorState::Isolating, Trigger::Fired("symptoms"))
+//!     .transition(BehaviorState::Isolating, BehaviorState::Commuting, Trigger::Elapsed(7.0))
+//!     .guarded_transition(
+//!         BehaviorState::Isolating,
+//!         BehaviorState::Hospitalized,
+//!         Trigger::Fired("admit"),
+//!         |context, person_id| context.get_person_property(person_id, Severe),
+//!     );
+//! context.add_state_machine(machine).unwrap();
+//!
+//! let person = context.add_person((Severe, false)).unwrap();
+//! assert!(context.fire_transition(person, Behavior, Trigger::Fired("symptoms")));
+//! // Not severe, so this is an invalid transition, which is logged.
+//! assert!(!context.fire_transition(person, Behavior, Trigger::Fired("admit")));
+//! context.execute();
+//! assert_eq!(context.get_person_property(person, Behavior), BehaviorState::Commuting);
+//! ```
+//!
+//! People are in the state given by their value of the property when
+//! they're added, or when the machine is added for people who already
+//! exist, without running entry actions.
+use crate::context::{Context, IxaEvent};
+use crate::error::IxaError;
+use crate::observer::ReadOnlyContext;
+use crate::people::external_api::ContextPeopleExtCrate;
+use crate::people::{
+    ContextPeopleExt, PersonCreatedEvent, PersonId, PersonProperty, PersonRemovedEvent,
+};
+use crate::{define_data_plugin, warn};
+use std::any::{type_name, Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// What makes a transition happen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Trigger {
+    /// A call to [`ContextStateMachineExt::fire_transition()`] with this
+    /// trigger, e.g., `Trigger::Fired("symptoms")`
+    Fired(&'static str),
+    /// Having been in the state for this long
+    Elapsed(f64),
+    /// An event of a type the machine listens to (see
+    /// [`StateMachine::listen()`]), named by [`Trigger::event()`]
+    Event(&'static str),
+}
+
+impl Trigger {
+    /// The trigger for events of type `E`
+    #[must_use]
+    pub fn event<E: IxaEvent>() -> Self {
+        Trigger::Event(type_name::<E>())
+    }
+}
+
+/// What to do when a transition is triggered but can't happen, because
+/// there's no transition from the person's state with the trigger or the
+/// guards of all the ones there are fail
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InvalidTransitionPolicy {
+    /// Log a warning and leave the person in their state
+    #[default]
+    Warn,
+    /// Panic, e.g., in tests of a model's rules
+    Panic,
+}
+
+/// Emitted when a person makes a transition in the state machine for `T`,
+/// after the exit and entry actions have run
+#[derive(Copy, Clone, Debug)]
+pub struct TransitionEvent<T: PersonProperty> {
+    pub person_id: PersonId,
+    pub from: T::Value,
+    pub to: T::Value,
+    pub trigger: Trigger,
+}
+
+impl<T: PersonProperty + 'static> IxaEvent for TransitionEvent<T> {}
+
+type Guard = dyn Fn(&ReadOnlyContext, PersonId) -> bool;
+type Action = dyn Fn(&mut Context, PersonId);
+type Listener = dyn FnOnce(&mut Context);
+
+struct Transition<V> {
+    from: V,
+    to: V,
+    trigger: Trigger,
+    guard: Option<Box<Guard>>,
+}
+
+/// The states and transitions of a person property `T`, built with
+/// [`StateMachine::on()`] and added with
+/// [`ContextStateMachineExt::add_state_machine()`]
+pub struct StateMachine<T: PersonProperty> {
+    property: T,
+    name: String,
+    transitions: Vec<Transition<T::Value>>,
+    entry_actions: Vec<(T::Value, Box<Action>)>,
+    exit_actions: Vec<(T::Value, Box<Action>)>,
+    // Subscriptions to the events the machine listens to, made when it's
+    // added. They're taken out of the machine then.
+    listeners: Vec<(&'static str, Box<Listener>)>,
+    policy: InvalidTransitionPolicy,
+}
+
+impl<T: PersonProperty + 'static> StateMachine<T> {
+    /// Start a state machine for `property`, named after the property
+    #[must_use]
+    pub fn on(property: T) -> Self {
+        StateMachine {
+            property,
+            name: T::name().to_string(),
+            transitions: Vec::new(),
+            entry_actions: Vec::new(),
+            exit_actions: Vec::new(),
+            listeners: Vec::new(),
+            policy: InvalidTransitionPolicy::default(),
+        }
+    }
+
+    /// Set the name used in warnings and errors
+    #[must_use]
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Add a transition from `from` to `to` when `trigger` happens
+    #[must_use]
+    pub fn transition(mut self, from: T::Value, to: T::Value, trigger: Trigger) -> Self {
+        self.transitions.push(Transition {
+            from,
+            to,
+            trigger,
+            guard: None,
+        });
+        self
+    }
+
+    /// Add a transition from `from` to `to` when `trigger` happens and
+    /// `guard` returns true for the person. If there are several
+    /// transitions from a state with the same trigger, the first one whose
+    /// guard passes is made.
+    #[must_use]
+    pub fn guarded_transition(
+        mut self,
+        from: T::Value,
+        to: T::Value,
+        trigger: Trigger,
+        guard: impl Fn(&ReadOnlyContext, PersonId) -> bool + 'static,
+    ) -> Self {
+        self.transitions.push(Transition {
+            from,
+            to,
+            trigger,
+            guard: Some(Box::new(guard)),
+        });
+        self
+    }
+
+    /// Run `action` when a person enters `state` through a transition
+    #[must_use]
+    pub fn on_entry(
+        mut self,
+        state: T::Value,
+        action: impl Fn(&mut Context, PersonId) + 'static,
+    ) -> Self {
+        self.entry_actions.push((state, Box::new(action)));
+        self
+    }
+
+    /// Run `action` when a person leaves `state` through a transition,
+    /// before they enter the next one
+    #[must_use]
+    pub fn on_exit(
+        mut self,
+        state: T::Value,
+        action: impl Fn(&mut Context, PersonId) + 'static,
+    ) -> Self {
+        self.exit_actions.push((state, Box::new(action)));
+        self
+    }
+
+    /// Trigger [`Trigger::event::<E>()`] for the person `person` returns for
+    /// each event of type `E`, if any. Events for people with no
+    /// transition from their state on the event are ignored, rather than
+    /// being invalid transitions.
+    #[must_use]
+    pub fn listen<E: IxaEvent + Clone + 'static>(
+        mut self,
+        person: impl Fn(&E) -> Option<PersonId> + 'static,
+    ) -> Self {
+        self.listeners.push((
+            type_name::<E>(),
+            Box::new(move |context: &mut Context| {
+                context.subscribe_to_event(move |context, event: E| {
+                    if let Some(person_id) = person(&event) {
+                        trigger_transition::<T>(context, person_id, Trigger::event::<E>());
+                    }
+                });
+            }),
+        ));
+        self
+    }
+
+    /// Set what happens when a transition can't be made
+    #[must_use]
+    pub fn invalid_transition_policy(mut self, policy: InvalidTransitionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+// The state of a machine that isn't specific to its property
+struct MachineState {
+    name: String,
+    entered: HashMap<PersonId, f64>,
+    invalid_transitions: usize,
+}
+
+#[derive(Default)]
+struct StateMachineData {
+    // The `Rc<StateMachine<T>>` for each property `T`
+    machines: HashMap<TypeId, Box<dyn Any>>,
+    states: HashMap<TypeId, MachineState>,
+    // Set while a transition changes a property, so that the change isn't
+    // rejected
+    transitioning: bool,
+}
+
+define_data_plugin!(
+    StateMachinePlugin,
+    StateMachineData,
+    StateMachineData::default()
+);
+
+fn get_machine<T: PersonProperty + 'static>(context: &Context) -> Option<Rc<StateMachine<T>>> {
+    let machine = context
+        .get_data_container(StateMachinePlugin)?
+        .machines
+        .get(&TypeId::of::<T>())?;
+    Some(Rc::clone(
+        machine.downcast_ref::<Rc<StateMachine<T>>>().unwrap(),
+    ))
+}
+
+fn get_state_mut<T: PersonProperty + 'static>(context: &mut Context) -> &mut MachineState {
+    context
+        .get_data_container_mut(StateMachinePlugin)
+        .states
+        .get_mut(&TypeId::of::<T>())
+        .unwrap()
+}
+
+/// Panics if `T` has a state machine and isn't being changed by one of its
+/// transitions. Called before a property is set.
+pub(crate) fn check_direct_set<T: PersonProperty + 'static>(context: &Context) {
+    let Some(data_container) = context.get_data_container(StateMachinePlugin) else {
+        return;
+    };
+    if data_container.transitioning || context.is_initializing_person() {
+        return;
+    }
+    if let Some(state) = data_container.states.get(&TypeId::of::<T>()) {
+        panic!(
+            "{} is managed by the state machine '{}' and can only be changed by its transitions",
+            T::name(),
+            state.name
+        );
+    }
+}
+
+// Puts `person_id` in `state`, scheduling the transitions out of it that
+// happen after time in the state.
+fn enter_state<T: PersonProperty + 'static>(
+    context: &mut Context,
+    machine: &StateMachine<T>,
+    person_id: PersonId,
+    state: T::Value,
+) {
+    let now = context.get_current_time();
+    get_state_mut::<T>(context).entered.insert(person_id, now);
+    let mut durations = Vec::new();
+    for transition in &machine.transitions {
+        if let Trigger::Elapsed(duration) = transition.trigger {
+            if transition.from == state && !durations.contains(&duration) {
+                durations.push(duration);
+            }
+        }
+    }
+    for duration in durations {
+        context.add_plan_keyed(
+            now + duration,
+            (TypeId::of::<T>(), person_id),
+            move |context| {
+                trigger_transition::<T>(context, person_id, Trigger::Elapsed(duration));
+            },
+        );
+    }
+}
+
+fn invalid_transition<T: PersonProperty + 'static>(
+    context: &mut Context,
+    machine: &StateMachine<T>,
+    message: &str,
+) {
+    get_state_mut::<T>(context).invalid_transitions += 1;
+    let message = format!(
+        "Invalid transition in state machine '{}': {message}",
+        machine.name
+    );
+    match machine.policy {
+        InvalidTransitionPolicy::Warn => warn!("{message}"),
+        InvalidTransitionPolicy::Panic => panic!("{message}"),
+    }
+}
+
+// Makes the first transition from `person_id`'s state with `trigger` whose
+// guard passes, returning whether there was one.
+fn trigger_transition<T: PersonProperty + 'static>(
+    context: &mut Context,
+    person_id: PersonId,
+    trigger: Trigger,
+) -> bool {
+    let machine = get_machine::<T>(context).unwrap_or_else(|| {
+        panic!("No state machine for {}", T::name());
+    });
+    if !context.person_exists(person_id) {
+        return false;
+    }
+    let from = context.get_person_property(person_id, machine.property);
+    let mut candidates = machine
+        .transitions
+        .iter()
+        .filter(|transition| transition.from == from && transition.trigger == trigger)
+        .peekable();
+    if candidates.peek().is_none() {
+        if !matches!(trigger, Trigger::Event(_)) {
+            invalid_transition(
+                context,
+                &machine,
+                &format!("no transition from {from:?} on {trigger:?} for {person_id}"),
+            );
+        }
+        return false;
+    }
+    let read_only = context.as_read_only();
+    let Some(transition) = candidates.find(|transition| {
+        transition
+            .guard
+            .as_ref()
+            .is_none_or(|guard| guard(&read_only, person_id))
+    }) else {
+        invalid_transition(
+            context,
+            &machine,
+            &format!(
+                "guards rejected the transitions from {from:?} on {trigger:?} for {person_id}"
+            ),
+        );
+        return false;
+    };
+    let to = transition.to;
+
+    for (state, action) in &machine.exit_actions {
+        if *state == from {
+            action(context, person_id);
+        }
+    }
+    context.cancel_plans_matching(&(TypeId::of::<T>(), person_id));
+    context
+        .get_data_container_mut(StateMachinePlugin)
+        .transitioning = true;
+    context.set_person_property(person_id, machine.property, to);
+    context
+        .get_data_container_mut(StateMachinePlugin)
+        .transitioning = false;
+    enter_state(context, &machine, person_id, to);
+    for (state, action) in &machine.entry_actions {
+        if *state == to {
+            action(context, person_id);
+        }
+    }
+    context.emit_event(TransitionEvent::<T> {
+        person_id,
+        from,
+        to,
+        trigger,
+    });
+    true
+}
+
+pub trait ContextStateMachineExt {
+    /// Add a state machine for its property, which can then only be
+    /// changed by the machine's transitions: setting it any other way
+    /// panics with the name of the machine.
+    ///
+    /// # Errors
+    /// Returns [`IxaError`] if the property already has a state machine or
+    /// is derived, a transition has a [`Trigger::Elapsed`] time that isn't
+    /// positive and finite, or a transition is triggered by an event the
+    /// machine doesn't listen to.
+    fn add_state_machine<T: PersonProperty + 'static>(
+        &mut self,
+        machine: StateMachine<T>,
+    ) -> Result<(), IxaError>;
+
+    /// Trigger a transition of `person_id` in the state machine for
+    /// `property`, usually with a [`Trigger::Fired`]. Returns whether a
+    /// transition was made; if not, the invalid transition is handled by
+    /// the machine's [`InvalidTransitionPolicy`].
+    ///
+    /// # Panics
+    /// If `property` has no state machine.
+    fn fire_transition<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        property: T,
+        trigger: Trigger,
+    ) -> bool;
+
+    /// The time `person_id` entered their current state in the state
+    /// machine for `property`, or `None` if there's no machine or person
+    fn time_entered_state<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        property: T,
+    ) -> Option<f64>;
+
+    /// The number of transitions that were triggered but couldn't be made
+    /// in the state machine for `property`
+    fn count_invalid_transitions<T: PersonProperty + 'static>(&self, property: T) -> usize;
+}
+
+impl ContextStateMachineExt for Context {
+    fn add_state_machine<T: PersonProperty + 'static>(
+        &mut self,
+        mut machine: StateMachine<T>,
+    ) -> Result<(), IxaError> {
+        if T::is_derived() {
+            return Err(IxaError::IxaError(format!(
+                "Can't add a state machine for the derived property {}",
+                T::name()
+            )));
+        }
+        if get_machine::<T>(self).is_some() {
+            return Err(IxaError::IxaError(format!(
+                "{} already has a state machine",
+                T::name()
+            )));
+        }
+        let listened: HashSet<&'static str> =
+            machine.listeners.iter().map(|(name, _)| *name).collect();
+        for transition in &machine.transitions {
+            match transition.trigger {
+                Trigger::Elapsed(duration) if !(duration.is_finite() && duration > 0.0) => {
+                    return Err(IxaError::IxaError(format!(
+                        "State machine '{}' has an elapsed time {duration} that isn't positive",
+                        machine.name
+                    )));
+                }
+                Trigger::Event(name) if !listened.contains(name) => {
+                    return Err(IxaError::IxaError(format!(
+                        "State machine '{}' doesn't listen to {name}",
+                        machine.name
+                    )));
+                }
+                _ => {}
+            }
+        }
+
+        for (_, listener) in machine.listeners.drain(..) {
+            listener(self);
+        }
+        let machine = Rc::new(machine);
+        let data_container = self.get_data_container_mut(StateMachinePlugin);
+        data_container.states.insert(
+            TypeId::of::<T>(),
+            MachineState {
+                name: machine.name.clone(),
+                entered: HashMap::new(),
+                invalid_transitions: 0,
+            },
+        );
+        data_container
+            .machines
+            .insert(TypeId::of::<T>(), Box::new(Rc::clone(&machine)));
+
+        for person_id in self.query_people(()) {
+            let state = self.get_person_property(person_id, machine.property);
+            enter_state(self, &machine, person_id, state);
+        }
+        self.subscribe_to_event(move |context, event: PersonCreatedEvent| {
+            // The person may have made a transition before this was handled.
+            if get_state_mut::<T>(context)
+                .entered
+                .contains_key(&event.person_id)
+            {
+                return;
+            }
+            let state = context.get_person_property(event.person_id, machine.property);
+            enter_state(context, &machine, event.person_id, state);
+        });
+        self.subscribe_to_event(|context, event: PersonRemovedEvent| {
+            context.cancel_plans_matching(&(TypeId::of::<T>(), event.person_id));
+            get_state_mut::<T>(context).entered.remove(&event.person_id);
+        });
+        Ok(())
+    }
+
+    fn fire_transition<T: PersonProperty + 'static>(
+        &mut self,
+        person_id: PersonId,
+        _property: T,
+        trigger: Trigger,
+    ) -> bool {
+        trigger_transition::<T>(self, person_id, trigger)
+    }
+
+    fn time_entered_state<T: PersonProperty + 'static>(
+        &self,
+        person_id: PersonId,
+        _property: T,
+    ) -> Option<f64> {
+        self.get_data_container(StateMachinePlugin)?
+            .states
+            .get(&TypeId::of::<T>())?
+            .entered
+            .get(&person_id)
+            .copied()
+    }
+
+    fn count_invalid_transitions<T: PersonProperty + 'static>(&self, _property: T) -> usize {
+        self.get_data_container(StateMachinePlugin)
+            .and_then(|data_container| data_container.states.get(&TypeId::of::<T>()))
+            .map_or(0, |state| state.invalid_transitions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        ContextStateMachineExt, InvalidTransitionPolicy, StateMachine, TransitionEvent, Trigger,
+    };
+    use crate::{
+        define_person_property, define_person_property_with_default, Context, ContextPeopleExt,
+        IxaError, PersonId, PersonPropertyChangeEvent,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    pub enum BehaviorState {
+        Commuting,
+        Isolating,
+        Hospitalized,
+        Discharged,
+    }
+    use BehaviorState::{Commuting, Discharged, Hospitalized, Isolating};
+
+    define_person_property_with_default!(Behavior, BehaviorState, Commuting);
+    define_person_property_with_default!(Severe, bool, false);
+    define_person_property!(Age, u8);
+
+    fn machine() -> StateMachine<Behavior> {
+        StateMachine::on(Behavior)
+            .name("behavior")
+            .transition(Commuting, Isolating, Trigger::Fired("symptoms"))
+            .transition(Isolating, Commuting, Trigger::Elapsed(7.0))
+            .guarded_transition(
+                Isolating,
+                Hospitalized,
+                Trigger::Fired("admit"),
+                |context, person_id| context.get_person_property(person_id, Severe),
+            )
+            .guarded_transition(
+                Isolating,
+                Hospitalized,
+                Trigger::event::<PersonPropertyChangeEvent<Severe>>(),
+                |context, person_id| context.get_person_property(person_id, Severe),
+            )
+            .listen(|event: &PersonPropertyChangeEvent<Severe>| Some(event.person_id))
+            .transition(Hospitalized, Discharged, Trigger::Elapsed(10.0))
+    }
+
+    #[test]
+    fn guards_choose_the_transition() {
+        let mut context = Context::new();
+        let machine = StateMachine::on(Behavior)
+            .transition(Commuting, Isolating, Trigger::Fired("symptoms"))
+            .guarded_transition(
+                Commuting,
+                Hospitalized,
+                Trigger::Fired("sick"),
+                |context, person_id| context.get_person_property(person_id, Age) >= 65,
+            )
+            .guarded_transition(
+                Commuting,
+                Isolating,
+                Trigger::Fired("sick"),
+                |context, person_id| context.get_person_property(person_id, Age) >= 18,
+            );
+        context.add_state_machine(machine).unwrap();
+        let old = context.add_person((Age, 70)).unwrap();
+        let adult = context.add_person((Age, 30)).unwrap();
+        let child = context.add_person((Age, 5)).unwrap();
+
+        assert!(context.fire_transition(old, Behavior, Trigger::Fired("sick")));
+        assert!(context.fire_transition(adult, Behavior, Trigger::Fired("sick")));
+        assert!(!context.fire_transition(child, Behavior, Trigger::Fired("sick")));
+        assert_eq!(context.get_person_property(old, Behavior), Hospitalized);
+        assert_eq!(context.get_person_property(adult, Behavior), Isolating);
+        assert_eq!(context.get_person_property(child, Behavior), Commuting);
+        assert_eq!(context.count_invalid_transitions(Behavior), 1);
+
+        // No transition from Isolating on "symptoms"
+        assert!(!context.fire_transition(adult, Behavior, Trigger::Fired("symptoms")));
+        assert_eq!(context.count_invalid_transitions(Behavior), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid transition in state machine 'behavior'")]
+    fn invalid_transition_policy_panics() {
+        let mut context = Context::new();
+        context
+            .add_state_machine(machine().invalid_transition_policy(InvalidTransitionPolicy::Panic))
+            .unwrap();
+        let person = context.add_person(()).unwrap();
+        context.fire_transition(person, Behavior, Trigger::Fired("admit"));
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn elapsed_time_triggers() {
+        let mut context = Context::new();
+        context.add_state_machine(machine()).unwrap();
+        let recovers = context.add_person(()).unwrap();
+        let admitted = context.add_person((Severe, true)).unwrap();
+        context.add_plan(1.0, move |context| {
+            context.fire_transition(recovers, Behavior, Trigger::Fired("symptoms"));
+            context.fire_transition(admitted, Behavior, Trigger::Fired("symptoms"));
+        });
+        // Leaving Isolating early cancels the return to Commuting.
+        context.add_plan(3.0, move |context| {
+            context.fire_transition(admitted, Behavior, Trigger::Fired("admit"));
+        });
+        context.add_plan(9.0, move |context| {
+            assert_eq!(context.get_person_property(recovers, Behavior), Commuting);
+            assert_eq!(context.time_entered_state(recovers, Behavior), Some(8.0));
+            assert_eq!(
+                context.get_person_property(admitted, Behavior),
+                Hospitalized
+            );
+            assert_eq!(context.time_entered_state(admitted, Behavior), Some(3.0));
+        });
+        context.execute();
+        assert_eq!(context.get_person_property(admitted, Behavior), Discharged);
+        assert_eq!(context.time_entered_state(admitted, Behavior), Some(13.0));
+        assert_eq!(context.get_current_time(), 13.0);
+        assert_eq!(context.count_invalid_transitions(Behavior), 0);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn existing_people_enter_when_machine_is_added() {
+        let mut context = Context::new();
+        let person = context.add_person((Behavior, Hospitalized)).unwrap();
+        context.add_plan(2.0, |context| {
+            context.add_state_machine(machine()).unwrap();
+        });
+        context.execute();
+        assert_eq!(context.get_person_property(person, Behavior), Discharged);
+        assert_eq!(context.get_current_time(), 12.0);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Behavior is managed by the state machine 'behavior' and can only be changed by its transitions"
+    )]
+    fn direct_set_is_rejected() {
+        let mut context = Context::new();
+        context.add_state_machine(machine()).unwrap();
+        // Setting the initial value is fine.
+        let person = context.add_person((Behavior, Isolating)).unwrap();
+        context.set_person_property(person, Behavior, Commuting);
+    }
+
+    #[test]
+    #[should_panic(expected = "managed by the state machine 'behavior'")]
+    fn direct_update_is_rejected() {
+        let mut context = Context::new();
+        context.add_state_machine(machine()).unwrap();
+        let person = context.add_person(()).unwrap();
+        context.update_people_by_id(&[person], Behavior, Isolating);
+    }
+
+    #[test]
+    fn invalid_machines() {
+        let mut context = Context::new();
+        let elapsed =
+            StateMachine::on(Behavior).transition(Commuting, Isolating, Trigger::Elapsed(0.0));
+        assert!(matches!(
+            context.add_state_machine(elapsed),
+            Err(IxaError::IxaError(_))
+        ));
+        let unheard = StateMachine::on(Behavior).transition(
+            Commuting,
+            Isolating,
+            Trigger::event::<PersonPropertyChangeEvent<Severe>>(),
+        );
+        assert!(matches!(
+            context.add_state_machine(unheard),
+            Err(IxaError::IxaError(_))
+        ));
+        context.add_state_machine(machine()).unwrap();
+        assert!(matches!(
+            context.add_state_machine(machine()),
+            Err(IxaError::IxaError(_))
+        ));
+    }
+
+    type Record = (f64, PersonId, BehaviorState, BehaviorState, Trigger);
+
+    #[test]
+    fn transition_events_for_scenario() {
+        let mut context = Context::new();
+        let actions = Rc::new(RefCell::new(Vec::new()));
+        let exits = Rc::clone(&actions);
+        let entries = Rc::clone(&actions);
+        let machine = machine()
+            .on_exit(Isolating, move |context, person_id| {
+                exits.borrow_mut().push(format!(
+                    "exit Isolating {person_id} at {}",
+                    context.get_current_time()
+                ));
+            })
+            .on_entry(Hospitalized, move |context, person_id| {
+                entries.borrow_mut().push(format!(
+                    "enter Hospitalized {person_id} at {}",
+                    context.get_current_time()
+                ));
+            });
+        context.add_state_machine(machine).unwrap();
+        let events: Rc<RefCell<Vec<Record>>> = Rc::new(RefCell::new(Vec::new()));
+        let events_clone = Rc::clone(&events);
+        context.subscribe_to_event(move |context, event: TransitionEvent<Behavior>| {
+            events_clone.borrow_mut().push((
+                context.get_current_time(),
+                event.person_id,
+                event.from,
+                event.to,
+                event.trigger,
+            ));
+        });
+
+        let a = context.add_person(()).unwrap();
+        let b = context.add_person(()).unwrap();
+        context.add_plan(1.0, move |context| {
+            context.fire_transition(a, Behavior, Trigger::Fired("symptoms"));
+            context.fire_transition(b, Behavior, Trigger::Fired("symptoms"));
+        });
+        // Becoming severe while isolating leads to hospital. Becoming
+        // severe while commuting doesn't do anything.
+        context.add_plan(2.0, move |context| {
+            context.set_person_property(b, Severe, true);
+        });
+        context.add_plan(9.0, move |context| {
+            context.set_person_property(a, Severe, true);
+        });
+        context.execute();
+
+        let severe = Trigger::event::<PersonPropertyChangeEvent<Severe>>();
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                (1.0, a, Commuting, Isolating, Trigger::Fired("symptoms")),
+                (1.0, b, Commuting, Isolating, Trigger::Fired("symptoms")),
+                (2.0, b, Isolating, Hospitalized, severe),
+                (8.0, a, Isolating, Commuting, Trigger::Elapsed(7.0)),
+                (12.0, b, Hospitalized, Discharged, Trigger::Elapsed(10.0)),
+            ]
+        );
+        assert_eq!(
+            *actions.borrow(),
+            vec![
+                format!("exit Isolating {b} at 2"),
+                format!("enter Hospitalized {b} at 2"),
+                format!("exit Isolating {a} at 8"),
+            ]
+        );
+        assert_eq!(context.count_invalid_transitions(Behavior), 0);
+    }
+}
@@ -0,0 +1,305 @@
+//! Parameter sweeps described in a global properties config file, for
+//! sensitivity analyses with the runner's `--sweep` option.
+//!
+//! The `sweep` key of the config file lists values for some of the global
+//! properties, and optionally for the random `seed`:
+//!
+//! ```json
+//! {
+//!   "ixa.Population": 10000,
+//!   "ixa.R0": 2.0,
+//!   "sweep": {
+//!     "ixa.R0": [1.5, 2.0, 2.5],
+//!     "seed": [1, 2, 3, 4, 5]
+//!   }
+//! }
+//! ```
+//!
+//! The sweep runs the simulation once for each combination of the values,
+//! i.e., the Cartesian product, 15 runs in this example. Every run loads
+//! the rest of the config file and then sets the swept values, so the
+//! sweep overrides `ixa.R0` above. Runs that don't sweep `seed` use the
+//! `--random-seed` given on the command line.
+//!
+//! Combinations are numbered from 0 with the parameters sorted by name and
+//! the last one varying fastest. Run `i` writes its reports with the file
+//! prefix `sweep<i>_` (after any `--prefix`), and [`MANIFEST_FILE`] in the
+//! output directory maps each index to its parameter values. Every value is
+//! checked before anything runs, and errors name the parameter.
+use crate::context::Context;
+use crate::error::IxaError;
+use crate::global_properties::{parse_global_property_value, replace_global_property_value};
+use crate::random::ContextRandomExt;
+use std::fs;
+use std::path::Path;
+
+/// The key of the config file that describes the sweep
+pub const SWEEP_KEY: &str = "sweep";
+
+/// The sweep parameter that sets the random seed
+pub const SEED_PARAMETER: &str = "seed";
+
+/// The name of the file, written to the output directory, that maps each
+/// sweep index to its parameter values
+pub const MANIFEST_FILE: &str = "sweep_manifest.csv";
+
+/// The values to sweep over, by parameter
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    parameters: Vec<(String, Vec<serde_json::Value>)>,
+}
+
+/// One combination of the values of a [`Sweep`]
+#[derive(Debug, Clone)]
+pub struct SweepRun {
+    pub index: usize,
+    pub seed: u64,
+    /// The global property values of the run, by name
+    pub parameters: Vec<(String, serde_json::Value)>,
+}
+
+impl Sweep {
+    /// Read the sweep of the config file at `path`, if it has one.
+    ///
+    /// # Errors
+    /// Returns [`IxaError`] if the file can't be read or parsed, or the
+    /// sweep isn't valid; see [`Sweep::from_json()`].
+    pub fn from_path(path: &Path) -> Result<Option<Sweep>, IxaError> {
+        let mut config: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&fs::read_to_string(path)?)?;
+        config.remove(SWEEP_KEY).map(Sweep::from_json).transpose()
+    }
+
+    /// Parse and validate a sweep: an object mapping each parameter to a
+    /// non-empty list of values.
+    ///
+    /// # Errors
+    /// Returns [`IxaError::MultipleErrors`] with every invalid parameter and
+    /// value found.
+    pub fn from_json(value: serde_json::Value) -> Result<Sweep, IxaError> {
+        let serde_json::Value::Object(object) = value else {
+            return Err(IxaError::from(
+                "The sweep must be an object mapping parameters to lists of values",
+            ));
+        };
+        let mut errors = Vec::new();
+        let mut parameters = Vec::new();
+        for (name, values) in object {
+            let values = match values {
+                serde_json::Value::Array(values) if !values.is_empty() => values,
+                _ => {
+                    errors.push(IxaError::from(format!(
+                        "Sweep parameter {name} must be a non-empty list of values"
+                    )));
+                    continue;
+                }
+            };
+            for value in &values {
+                let result = if name == SEED_PARAMETER {
+                    value
+                        .as_u64()
+                        .map(|_| ())
+                        .ok_or_else(|| IxaError::from("expected a non-negative integer"))
+                } else {
+                    parse_global_property_value(&name, value.clone()).map(|_| ())
+                };
+                if let Err(error) = result {
+                    errors.push(IxaError::from(format!(
+                        "Invalid value {value} for sweep parameter {name}: {error}"
+                    )));
+                }
+            }
+            parameters.push((name, values));
+        }
+        if !errors.is_empty() {
+            return Err(IxaError::MultipleErrors(errors));
+        }
+        parameters.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(Sweep { parameters })
+    }
+
+    /// The number of combinations
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.parameters
+            .iter()
+            .map(|(_, values)| values.len())
+            .product()
+    }
+
+    /// Whether the sweep has no combinations, which is never the case for
+    /// a valid sweep
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every combination, in order of the index. Runs that don't sweep the
+    /// seed use `default_seed`.
+    #[must_use]
+    pub fn runs(&self, default_seed: u64) -> Vec<SweepRun> {
+        (0..self.len())
+            .map(|index| {
+                let mut run = SweepRun {
+                    index,
+                    seed: default_seed,
+                    parameters: Vec::new(),
+                };
+                let mut rest = index;
+                for (name, values) in self.parameters.iter().rev() {
+                    let value = values[rest % values.len()].clone();
+                    rest /= values.len();
+                    if name == SEED_PARAMETER {
+                        run.seed = value.as_u64().unwrap_or(default_seed);
+                    } else {
+                        run.parameters.push((name.clone(), value));
+                    }
+                }
+                run.parameters.reverse();
+                run
+            })
+            .collect()
+    }
+
+    /// Write the CSV file that maps each index to its seed and parameter
+    /// values.
+    ///
+    /// # Errors
+    /// Returns [`IxaError`] if the file exists and `overwrite` is false, or
+    /// the file can't be written.
+    pub fn write_manifest(
+        &self,
+        path: &Path,
+        default_seed: u64,
+        overwrite: bool,
+    ) -> Result<(), IxaError> {
+        if path.exists() && !overwrite {
+            return Err(IxaError::from(format!(
+                "File already exists: {}. Rerun with --force-overwrite",
+                path.display()
+            )));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = csv::Writer::from_path(path)?;
+        let mut header = vec!["sweep_index", SEED_PARAMETER];
+        header.extend(
+            self.parameters
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .filter(|name| *name != SEED_PARAMETER),
+        );
+        writer.write_record(header)?;
+        for run in self.runs(default_seed) {
+            let mut record = vec![run.index.to_string(), run.seed.to_string()];
+            record.extend(run.parameters.iter().map(|(_, value)| match value {
+                serde_json::Value::String(string) => string.clone(),
+                value => value.to_string(),
+            }));
+            writer.write_record(record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl SweepRun {
+    /// Set the run's global property values and random seed.
+    ///
+    /// # Errors
+    /// Returns [`IxaError`] if a value isn't valid for its property.
+    pub fn apply(&self, context: &mut Context) -> Result<(), IxaError> {
+        for (name, value) in &self.parameters {
+            let parsed = parse_global_property_value(name, value.clone())?;
+            replace_global_property_value(context, parsed);
+        }
+        context.init_random(self.seed);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::define_global_property;
+    use serde_json::json;
+
+    define_global_property!(SweepRate, f64);
+    define_global_property!(SweepPolicy, String);
+
+    fn errors(value: serde_json::Value) -> Vec<String> {
+        match Sweep::from_json(value) {
+            Err(IxaError::MultipleErrors(errors)) => {
+                errors.iter().map(ToString::to_string).collect()
+            }
+            other => panic!("Expected validation errors, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn expands_cartesian_product() {
+        let sweep = Sweep::from_json(json!({
+            "seed": [7, 8],
+            "ixa.SweepRate": [0.5, 1.5, 2.5],
+            "ixa.SweepPolicy": ["open", "closed"],
+        }))
+        .unwrap();
+        assert_eq!(sweep.len(), 12);
+        let runs = sweep.runs(0);
+        assert_eq!(runs.len(), 12);
+        assert_eq!(
+            runs[0].parameters,
+            vec![
+                ("ixa.SweepPolicy".to_string(), json!("open")),
+                ("ixa.SweepRate".to_string(), json!(0.5)),
+            ]
+        );
+        assert_eq!(runs[0].seed, 7);
+        assert_eq!(runs[1].seed, 8);
+        assert_eq!(runs[2].parameters[1].1, json!(1.5));
+        assert_eq!(runs[6].parameters[0].1, json!("closed"));
+        assert_eq!(runs[11].seed, 8);
+        assert_eq!(runs[11].parameters[1].1, json!(2.5));
+    }
+
+    #[test]
+    fn seed_defaults_when_not_swept() {
+        let sweep = Sweep::from_json(json!({"ixa.SweepRate": [1.0, 2.0]})).unwrap();
+        assert!(sweep.runs(42).iter().all(|run| run.seed == 42));
+    }
+
+    #[test]
+    fn validation_errors_name_the_parameter() {
+        let errors = errors(json!({
+            "ixa.SweepRate": [1.0, "fast"],
+            "ixa.SweepPolicy": "open",
+            "ixa.SweepMissing": [1],
+            "seed": [-1],
+        }));
+        assert_eq!(errors.len(), 4);
+        assert!(errors[0].contains("Invalid value 1 for sweep parameter ixa.SweepMissing"));
+        assert!(errors[1].contains("Sweep parameter ixa.SweepPolicy must be a non-empty list"));
+        assert!(errors[2].contains("Invalid value \"fast\" for sweep parameter ixa.SweepRate"));
+        assert!(errors[3].contains("Invalid value -1 for sweep parameter seed"));
+        assert!(Sweep::from_json(json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn writes_manifest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(MANIFEST_FILE);
+        let sweep = Sweep::from_json(json!({
+            "ixa.SweepRate": [0.5, 1.5],
+            "ixa.SweepPolicy": ["open"],
+        }))
+        .unwrap();
+        sweep.write_manifest(&path, 3, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "sweep_index,seed,ixa.SweepPolicy,ixa.SweepRate\n0,3,open,0.5\n1,3,open,1.5\n"
+        );
+        assert!(sweep.write_manifest(&path, 3, false).is_err());
+        sweep.write_manifest(&path, 3, true).unwrap();
+    }
+}
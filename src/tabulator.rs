@@ -1,7 +1,17 @@
-use crate::people::PersonProperty;
-use crate::{Context, ContextPeopleExt};
+use crate::error::IxaError;
+use crate::people::external_api::ContextPeopleExtCrate;
+use crate::people::{
+    IndexValue, PersonCreatedEvent, PersonProperty, PersonPropertyChangeEvent, PersonRemovedEvent,
+};
+use crate::{Context, ContextPeopleExt, PersonId};
 use seq_macro::seq;
 use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::rc::Rc;
 
 pub trait Tabulator {
     fn setup(&self, context: &mut Context);
@@ -11,7 +21,10 @@ pub trait Tabulator {
 
 impl<T: PersonProperty + 'static> Tabulator for (T,) {
     fn setup(&self, context: &mut Context) {
-        context.index_property(T::get_instance());
+        // A single property with counts is tabulated from them.
+        if !context.has_property_counts(TypeId::of::<T>()) {
+            context.index_property(T::get_instance());
+        }
     }
     fn get_typelist(&self) -> Vec<TypeId> {
         vec![std::any::TypeId::of::<T>()]
@@ -63,25 +76,309 @@ seq!(Z in 2..20 {
     impl_tabulator!(Z);
 });
 
+#[doc(hidden)]
+pub type PersonUpdateHandler = Rc<dyn Fn(&mut Context, PersonId)>;
+
+/// A tuple of person properties to group people by, for use with
+/// [`Context::tabulate_person_count()`]. This is implemented for tuples of
+/// up to 12 properties, e.g., `(Age,)` or `(Age, RiskCategory)`.
+pub trait TabulationKey: 'static {
+    /// The values of the properties, in the same order
+    type Values: Copy + Debug + PartialEq + Hash;
+    #[doc(hidden)]
+    fn register(context: &Context);
+    #[doc(hidden)]
+    fn get_typelist() -> Vec<TypeId>;
+    #[doc(hidden)]
+    fn get_values(context: &Context, person_id: PersonId) -> Self::Values;
+    #[doc(hidden)]
+    fn get_columns() -> Vec<String>;
+    #[doc(hidden)]
+    fn get_labels(values: &Self::Values) -> Vec<String>;
+    #[doc(hidden)]
+    fn subscribe_to_changes(context: &mut Context, handler: PersonUpdateHandler);
+}
+
+macro_rules! impl_tabulation_key {
+    ($ct:expr) => {
+        seq!(N in 0..$ct {
+            impl<
+                #(
+                    T~N : PersonProperty + 'static,
+                )*
+            > TabulationKey for (
+                #(
+                    T~N,
+                )*
+            )
+            {
+                type Values = (
+                    #(
+                        T~N::Value,
+                    )*
+                );
+
+                fn register(context: &Context) {
+                    #(
+                        context.register_property::<T~N>();
+                    )*
+                }
+
+                fn get_typelist() -> Vec<TypeId> {
+                    vec![
+                    #(
+                        std::any::TypeId::of::<T~N>(),
+                    )*
+                    ]
+                }
+
+                fn get_values(context: &Context, person_id: PersonId) -> Self::Values {
+                    (
+                        #(
+                            context.get_person_property(person_id, T~N::get_instance()),
+                        )*
+                    )
+                }
+
+                fn get_columns() -> Vec<String> {
+                    vec![
+                    #(
+                        String::from(T~N::name()),
+                    )*
+                    ]
+                }
+
+                fn get_labels(values: &Self::Values) -> Vec<String> {
+                    vec![
+                    #(
+                        format!("{:?}", values.N),
+                    )*
+                    ]
+                }
+
+                fn subscribe_to_changes(
+                    context: &mut Context,
+                    handler: PersonUpdateHandler,
+                ) {
+                    #(
+                        let handler_clone = Rc::clone(&handler);
+                        context.subscribe_to_event(
+                            move |context, event: PersonPropertyChangeEvent<T~N>| {
+                                handler_clone(context, event.person_id);
+                            },
+                        );
+                    )*
+                }
+            }
+        });
+    }
+}
+
+seq!(Z in 1..13 {
+    impl_tabulation_key!(Z);
+});
+
+/// Which groups a streaming periodic report writes each period; see
+/// [`ContextReportExt::add_streaming_periodic_report()`].
+///
+/// [`ContextReportExt::add_streaming_periodic_report()`]: crate::ContextReportExt::add_streaming_periodic_report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamingMode {
+    /// The groups whose count changed since the last period, including
+    /// groups whose count dropped to 0. The first period writes every
+    /// group that anyone is in.
+    Delta,
+    /// The `k` groups with the most people, with ties broken by the
+    /// values' labels.
+    TopK(usize),
+}
+
+const NO_GROUP: u32 = u32::MAX;
+
+// Counts of people in each group of `T` that are kept up to date as people
+// are added, removed and changed, rather than being recomputed. Groups are
+// numbered in the order they are first seen, and their labels are only
+// stored once.
+pub(crate) struct StreamingTabulation<T: TabulationKey> {
+    mode: StreamingMode,
+    groups: HashMap<IndexValue, u32>,
+    labels: Vec<Vec<String>>,
+    counts: Vec<usize>,
+    // The counts as of the last emission, for delta mode
+    emitted: Vec<usize>,
+    // The group each person is counted in, indexed by person id
+    person_groups: Vec<u32>,
+    _key: PhantomData<T>,
+}
+
+impl<T: TabulationKey> StreamingTabulation<T> {
+    fn new(mode: StreamingMode) -> Self {
+        StreamingTabulation {
+            mode,
+            groups: HashMap::new(),
+            labels: Vec::new(),
+            counts: Vec::new(),
+            emitted: Vec::new(),
+            person_groups: Vec::new(),
+            _key: PhantomData,
+        }
+    }
+
+    fn group_of(&mut self, context: &Context, person_id: PersonId) -> u32 {
+        let values = T::get_values(context, person_id);
+        let key = IndexValue::compute(&values);
+        if let Some(group) = self.groups.get(&key) {
+            return *group;
+        }
+        let group = u32::try_from(self.labels.len()).expect("Too many groups");
+        self.groups.insert(key, group);
+        self.labels.push(T::get_labels(&values));
+        self.counts.push(0);
+        self.emitted.push(0);
+        group
+    }
+
+    // Move `person_id` to the group for their current values. This is
+    // called for every event that may have changed the person's group, so
+    // it doesn't matter in what order the events are handled.
+    fn update_person(&mut self, context: &Context, person_id: PersonId) {
+        let new_group = if context.person_exists(person_id) {
+            self.group_of(context, person_id)
+        } else {
+            NO_GROUP
+        };
+        if person_id.0 >= self.person_groups.len() {
+            self.person_groups.resize(person_id.0 + 1, NO_GROUP);
+        }
+        let old_group = std::mem::replace(&mut self.person_groups[person_id.0], new_group);
+        if old_group != new_group {
+            if old_group != NO_GROUP {
+                self.counts[old_group as usize] -= 1;
+            }
+            if new_group != NO_GROUP {
+                self.counts[new_group as usize] += 1;
+            }
+        }
+    }
+
+    // Recompute the counts from scratch and compare them to the
+    // incremental ones.
+    fn check_consistency(&self, context: &Context) -> Result<(), IxaError> {
+        let mut expected = HashMap::<IndexValue, (Vec<String>, usize)>::new();
+        for person_id in context.query_people(()) {
+            let values = T::get_values(context, person_id);
+            expected
+                .entry(IndexValue::compute(&values))
+                .or_insert_with(|| (T::get_labels(&values), 0))
+                .1 += 1;
+        }
+        for (key, group) in &self.groups {
+            let expected_count = expected.remove(key).map_or(0, |(_, count)| count);
+            let count = self.counts[*group as usize];
+            if count != expected_count {
+                return Err(IxaError::from(format!(
+                    "Streaming tabulation has drifted: {:?} has count {count} but should have {expected_count}",
+                    self.labels[*group as usize]
+                )));
+            }
+        }
+        if let Some((labels, count)) = expected.into_values().next() {
+            return Err(IxaError::from(format!(
+                "Streaming tabulation has drifted: {labels:?} is missing but should have count {count}"
+            )));
+        }
+        Ok(())
+    }
+
+    // Call `write_row` with the labels and count of each group to write
+    // for this period. In debug builds, the counts are checked against a
+    // full recomputation first.
+    pub(crate) fn emit(&mut self, context: &Context, mut write_row: impl FnMut(&[String], usize)) {
+        if cfg!(debug_assertions) {
+            if let Err(error) = self.check_consistency(context) {
+                panic!("{error}");
+            }
+        }
+        match self.mode {
+            StreamingMode::Delta => {
+                for (group, count) in self.counts.iter().enumerate() {
+                    if *count != self.emitted[group] {
+                        write_row(&self.labels[group], *count);
+                    }
+                }
+            }
+            StreamingMode::TopK(k) => {
+                let order = |a: &usize, b: &usize| {
+                    self.counts[*b]
+                        .cmp(&self.counts[*a])
+                        .then_with(|| self.labels[*a].cmp(&self.labels[*b]))
+                };
+                let mut top = (0..self.counts.len())
+                    .filter(|group| self.counts[*group] > 0)
+                    .collect::<Vec<_>>();
+                if top.len() > k {
+                    top.select_nth_unstable_by(k, order);
+                    top.truncate(k);
+                }
+                top.sort_unstable_by(order);
+                for group in top {
+                    write_row(&self.labels[group], self.counts[group]);
+                }
+            }
+        }
+        self.emitted.clone_from(&self.counts);
+    }
+}
+
+// Create a streaming tabulation of the people in `context` that is kept up
+// to date by subscribing to the events that can change people's groups.
+pub(crate) fn subscribe_streaming_tabulation<T: TabulationKey>(
+    context: &mut Context,
+    mode: StreamingMode,
+) -> Rc<RefCell<StreamingTabulation<T>>> {
+    T::register(context);
+    let tabulation = Rc::new(RefCell::new(StreamingTabulation::<T>::new(mode)));
+    for person_id in context.query_people(()) {
+        tabulation.borrow_mut().update_person(context, person_id);
+    }
+
+    let tabulation_clone = Rc::clone(&tabulation);
+    let update: PersonUpdateHandler = Rc::new(move |context, person_id| {
+        tabulation_clone
+            .borrow_mut()
+            .update_person(context, person_id);
+    });
+    let update_clone = Rc::clone(&update);
+    context.subscribe_to_event(move |context, event: PersonCreatedEvent| {
+        update_clone(context, event.person_id);
+    });
+    let update_clone = Rc::clone(&update);
+    context.subscribe_to_event(move |context, event: PersonRemovedEvent| {
+        update_clone(context, event.person_id);
+    });
+    T::subscribe_to_changes(context, update);
+    tabulation
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Tabulator;
+    use super::{subscribe_streaming_tabulation, StreamingMode, StreamingTabulation, Tabulator};
     use crate::{
         define_derived_property, define_person_property, define_person_property_with_default,
-        Context, ContextPeopleExt,
+        Context, ContextPeopleExt, PersonId, TabulationKey,
     };
+    use ordered_float::OrderedFloat;
     use std::any::TypeId;
     use std::cell::RefCell;
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
+    use std::rc::Rc;
 
     define_person_property!(Age, u8);
     type RiskCategoryValue = u8;
     define_person_property!(RiskCategory, RiskCategoryValue);
     define_person_property_with_default!(IsRunner, bool, false);
     define_person_property_with_default!(IsSwimmer, bool, false);
-    define_derived_property!(AdultSwimmer, bool, [IsSwimmer, Age], |is_swimmer, age| {
-        is_swimmer && age >= 18
-    });
+    define_derived_property!(IsAdult, bool, [Age], |age| age >= 18);
 
     #[test]
     fn test_tabulator() {
@@ -155,4 +452,319 @@ mod tests {
             &expected,
         );
     }
+
+    #[test]
+    fn tabulate_from_counts() {
+        let tabulator = (Age,);
+        let mut expected = HashSet::new();
+        expected.insert((vec!["10".to_string()], 1));
+        expected.insert((vec!["20".to_string()], 2));
+        expected.insert((vec!["30".to_string()], 1));
+        tabulate_properties_test_setup(
+            &tabulator,
+            |context| {
+                context.index_property_counts(Age);
+                let bob = context.add_person((Age, 10)).unwrap();
+                context.add_person((Age, 20)).unwrap();
+                context.add_person((Age, 30)).unwrap();
+                context.set_person_property(bob, Age, 20);
+                context.add_person((Age, 10)).unwrap();
+            },
+            &expected,
+        );
+    }
+
+    #[test]
+    fn tabulate_non_finite_values() {
+        define_person_property!(Weight, OrderedFloat<f64>);
+
+        let tabulator = (Weight,);
+        let expected = ["NaN", "inf", "-inf", "1.5"]
+            .into_iter()
+            .map(|value| (vec![value.to_string()], 1))
+            .collect();
+        tabulate_properties_test_setup(
+            &tabulator,
+            |context| {
+                context.allow_non_finite(Weight);
+                for weight in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1.5] {
+                    context.add_person((Weight, OrderedFloat(weight))).unwrap();
+                }
+            },
+            &expected,
+        );
+    }
+
+    fn tabulate_person_count_setup(context: &mut Context) {
+        for i in 0..60u8 {
+            let person = context
+                .add_person(((Age, i % 30), (RiskCategory, i % 3)))
+                .unwrap();
+            if i % 4 == 0 {
+                context.set_person_property(person, IsRunner, true);
+            }
+        }
+    }
+
+    fn nested_query_counts(context: &Context) -> HashMap<(bool, u8, bool), usize> {
+        let mut expected = HashMap::new();
+        for is_adult in [false, true] {
+            for risk in 0..3 {
+                for is_runner in [false, true] {
+                    let count = context.query_people_count((
+                        (IsAdult, is_adult),
+                        (RiskCategory, risk),
+                        (IsRunner, is_runner),
+                    ));
+                    if count > 0 {
+                        expected.insert((is_adult, risk, is_runner), count);
+                    }
+                }
+            }
+        }
+        expected
+    }
+
+    #[test]
+    fn tabulate_person_count_matches_queries() {
+        let mut context = Context::new();
+        tabulate_person_count_setup(&mut context);
+        let expected = nested_query_counts(&context);
+        assert_eq!(expected.values().sum::<usize>(), 60);
+        assert_eq!(
+            context.tabulate_person_count::<(IsAdult, RiskCategory, IsRunner)>(),
+            expected
+        );
+
+        let by_risk = context.tabulate_person_count::<(RiskCategory,)>();
+        assert_eq!(by_risk, HashMap::from([((0,), 20), ((1,), 20), ((2,), 20)]));
+    }
+
+    #[test]
+    fn tabulate_person_count_indexed() {
+        let mut context = Context::new();
+        tabulate_person_count_setup(&mut context);
+        let unindexed = context.tabulate_person_count::<(IsAdult, RiskCategory, IsRunner)>();
+
+        context.index_property(IsAdult);
+        context.index_property(RiskCategory);
+        context.index_property(IsRunner);
+        let indexed = context.tabulate_person_count::<(IsAdult, RiskCategory, IsRunner)>();
+        assert_eq!(indexed, unindexed);
+
+        // The indexes are kept up to date as people are added and changed
+        let person = context.add_person(((Age, 40), (RiskCategory, 2))).unwrap();
+        context.set_person_property(person, IsRunner, true);
+        let counts = context.tabulate_person_count::<(IsAdult, RiskCategory, IsRunner)>();
+        assert_eq!(counts, nested_query_counts(&context));
+        assert_eq!(
+            counts[&(true, 2, true)],
+            unindexed.get(&(true, 2, true)).copied().unwrap_or(0) + 1
+        );
+    }
+
+    #[test]
+    fn tabulate_person_count_empty() {
+        let context = Context::new();
+        assert!(context.tabulate_person_count::<(Age,)>().is_empty());
+    }
+
+    type StreamingKey = (IsAdult, RiskCategory, IsRunner);
+
+    fn recomputed_counts(context: &Context) -> HashMap<Vec<String>, usize> {
+        context
+            .tabulate_person_count::<StreamingKey>()
+            .into_iter()
+            .map(|(values, count)| {
+                (
+                    vec![
+                        values.0.to_string(),
+                        values.1.to_string(),
+                        values.2.to_string(),
+                    ],
+                    count,
+                )
+            })
+            .collect()
+    }
+
+    fn streaming_counts<T: TabulationKey>(
+        tabulation: &StreamingTabulation<T>,
+    ) -> HashMap<Vec<String>, usize> {
+        tabulation
+            .labels
+            .iter()
+            .zip(&tabulation.counts)
+            .filter(|(_, count)| **count > 0)
+            .map(|(labels, count)| (labels.clone(), *count))
+            .collect()
+    }
+
+    fn emitted_rows<T: TabulationKey>(
+        context: &Context,
+        tabulation: &mut StreamingTabulation<T>,
+    ) -> Vec<(Vec<String>, usize)> {
+        let mut rows = Vec::new();
+        tabulation.emit(context, |labels, count| rows.push((labels.to_vec(), count)));
+        rows
+    }
+
+    #[test]
+    fn streaming_counts_match_recomputation() {
+        let mut context = Context::new();
+        tabulate_person_count_setup(&mut context);
+        let tabulation =
+            subscribe_streaming_tabulation::<StreamingKey>(&mut context, StreamingMode::Delta);
+        assert_eq!(
+            streaming_counts(&tabulation.borrow()),
+            recomputed_counts(&context)
+        );
+
+        let people = context.query_people(());
+        for t in 0..20u8 {
+            let person = people[usize::from(t) * 2];
+            let other = people[usize::from(t) * 2 + 1];
+            context.add_plan(f64::from(t), move |context| {
+                // Several changes to the same person in one plan, so the
+                // events are handled after all of them have been made
+                context.set_person_property(person, Age, 10 + t * 3);
+                context.set_person_property(person, RiskCategory, t % 3);
+                context.set_person_property(person, IsRunner, t % 2 == 0);
+                context.add_person(((Age, t), (RiskCategory, 1))).unwrap();
+                if t % 5 == 0 {
+                    context.remove_person(other).unwrap();
+                }
+            });
+            let tabulation = Rc::clone(&tabulation);
+            context.add_plan_with_phase(
+                f64::from(t),
+                move |context| {
+                    let tabulation = tabulation.borrow();
+                    assert_eq!(streaming_counts(&tabulation), recomputed_counts(context));
+                    tabulation.check_consistency(context).unwrap();
+                },
+                crate::context::ExecutionPhase::Last,
+            );
+        }
+        context.execute();
+        assert_eq!(
+            streaming_counts(&tabulation.borrow())
+                .values()
+                .sum::<usize>(),
+            76
+        );
+    }
+
+    #[test]
+    fn streaming_delta_emission() {
+        let mut context = Context::new();
+        let adult = context.add_person(((Age, 30), (RiskCategory, 0))).unwrap();
+        context.add_person(((Age, 30), (RiskCategory, 0))).unwrap();
+        context.add_person(((Age, 5), (RiskCategory, 1))).unwrap();
+        let tabulation = subscribe_streaming_tabulation::<(IsAdult, RiskCategory)>(
+            &mut context,
+            StreamingMode::Delta,
+        );
+        let labels = |is_adult: bool, risk: u8| vec![is_adult.to_string(), risk.to_string()];
+
+        // Everything is written the first time
+        let mut rows = emitted_rows(&context, &mut tabulation.borrow_mut());
+        rows.sort();
+        assert_eq!(rows, vec![(labels(false, 1), 1), (labels(true, 0), 2)]);
+        // Nothing changed
+        assert!(emitted_rows(&context, &mut tabulation.borrow_mut()).is_empty());
+
+        // A change and back again within one period isn't written
+        context.set_person_property(adult, RiskCategory, 2);
+        context.set_person_property(adult, RiskCategory, 0);
+        context.execute();
+        assert!(emitted_rows(&context, &mut tabulation.borrow_mut()).is_empty());
+
+        context.set_person_property(adult, Age, 10);
+        context.set_person_property(adult, RiskCategory, 2);
+        context.remove_person(PersonId(2)).unwrap();
+        context.execute();
+        let mut rows = emitted_rows(&context, &mut tabulation.borrow_mut());
+        rows.sort();
+        // The group that is now empty is written with a count of 0
+        assert_eq!(
+            rows,
+            vec![
+                (labels(false, 1), 0),
+                (labels(false, 2), 1),
+                (labels(true, 0), 1)
+            ]
+        );
+
+        context.remove_person(adult).unwrap();
+        context.execute();
+        assert_eq!(
+            emitted_rows(&context, &mut tabulation.borrow_mut()),
+            vec![(labels(false, 2), 0)]
+        );
+    }
+
+    #[test]
+    fn streaming_top_k() {
+        let mut context = Context::new();
+        // RiskCategory 0 has 4 people, 1 and 3 have 3 each and 2 has 2
+        for risk in [0, 0, 0, 0, 3, 3, 3, 1, 1, 1, 2, 2] {
+            context
+                .add_person(((Age, 40), (RiskCategory, risk)))
+                .unwrap();
+        }
+        let tabulation =
+            subscribe_streaming_tabulation::<(RiskCategory,)>(&mut context, StreamingMode::TopK(2));
+        let expected = vec![(vec!["0".to_string()], 4), (vec!["1".to_string()], 3)];
+        // The tie between 1 and 3 is broken by the label, every time
+        for _ in 0..3 {
+            assert_eq!(
+                emitted_rows(&context, &mut tabulation.borrow_mut()),
+                expected
+            );
+        }
+
+        let none =
+            subscribe_streaming_tabulation::<(RiskCategory,)>(&mut context, StreamingMode::TopK(0));
+        assert!(emitted_rows(&context, &mut none.borrow_mut()).is_empty());
+        let all = subscribe_streaming_tabulation::<(RiskCategory,)>(
+            &mut context,
+            StreamingMode::TopK(10),
+        );
+        assert_eq!(emitted_rows(&context, &mut all.borrow_mut()).len(), 4);
+    }
+
+    #[test]
+    fn streaming_consistency_check_detects_drift() {
+        let mut context = Context::new();
+        tabulate_person_count_setup(&mut context);
+        let tabulation =
+            subscribe_streaming_tabulation::<StreamingKey>(&mut context, StreamingMode::Delta);
+        tabulation.borrow().check_consistency(&context).unwrap();
+
+        tabulation.borrow_mut().counts[0] += 1;
+        let error = tabulation
+            .borrow()
+            .check_consistency(&context)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("drifted"), "{error}");
+
+        tabulation.borrow_mut().counts[0] -= 1;
+        tabulation.borrow_mut().groups.clear();
+        // Groups that are missing entirely are caught too
+        assert!(tabulation.borrow().check_consistency(&context).is_err());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Streaming tabulation has drifted")]
+    fn streaming_emit_panics_on_drift() {
+        let mut context = Context::new();
+        tabulate_person_count_setup(&mut context);
+        let tabulation =
+            subscribe_streaming_tabulation::<StreamingKey>(&mut context, StreamingMode::Delta);
+        tabulation.borrow_mut().counts[1] -= 1;
+        emitted_rows(&context, &mut tabulation.borrow_mut());
+    }
 }
@@ -0,0 +1,277 @@
+//! Checks that a model's results depend only on its random seed.
+//!
+//! [`assert_deterministic()`] builds and runs a simulation twice with the
+//! same seed and panics if the outcomes differ, which catches models that
+//! depend on things like `HashMap` iteration order or unseeded random
+//! numbers. [`assert_seed_sensitivity()`] runs it with two different seeds
+//! and panics if the outcomes are the same, which catches models that
+//! ignore the seed they are given.
+//!
+//! The outcome of a run is summarized by a set of digests:
+//! * The final simulation time and population
+//! * A hash of every person's value of each registered person property
+//! * A hash of the state of each random number generator that was used
+//! * A hash of the contents of each report file
+//!
+//! Each run writes its reports to a fresh temporary directory, so the setup
+//! function should add reports without changing the report directory.
+//!
+//! ```
+//! use ixa::testing::{assert_deterministic, DeterminismConfig};
+//! use ixa::{define_rng, Context, ContextRandomExt};
+//!
+//! define_rng!(ModelRng);
+//!
+//! fn setup(context: &mut Context) {
+//!     let delay = context.sample_range(ModelRng, 0.0..1.0);
+//!     context.add_plan(delay, |_| {});
+//! }
+//!
+//! assert_deterministic(setup, &DeterminismConfig::default());
+//! ```
+use crate::people::external_api::ContextPeopleExtCrate;
+use crate::random::rng_state_digest;
+use crate::{Context, ContextPeopleExt, ContextRandomExt, ContextReportExt};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How to run the simulation for the checks in this module
+#[derive(Clone, Debug)]
+pub struct DeterminismConfig {
+    /// The base random seed
+    pub seed: u64,
+    /// The seed to compare against in [`assert_seed_sensitivity()`]
+    pub alternate_seed: u64,
+    /// Stop at this time (see [`Context::run_until()`]) instead of running
+    /// until there are no plans left
+    pub end_time: Option<f64>,
+}
+
+impl Default for DeterminismConfig {
+    fn default() -> Self {
+        DeterminismConfig {
+            seed: 42,
+            alternate_seed: 43,
+            end_time: None,
+        }
+    }
+}
+
+/// Assert that two runs of the simulation built by `setup` with the same
+/// seed have the same outcome.
+///
+/// # Panics
+/// Panics with a list of the digests that differ if the runs diverge.
+pub fn assert_deterministic(setup: impl Fn(&mut Context), config: &DeterminismConfig) {
+    let first = run_digests(&setup, config, config.seed);
+    let second = run_digests(&setup, config, config.seed);
+    let differences = describe_differences(&first, &second);
+    assert!(
+        differences.is_empty(),
+        "Runs with seed {} diverged:\n{differences}",
+        config.seed
+    );
+}
+
+/// Assert that runs of the simulation built by `setup` with
+/// `config.seed` and `config.alternate_seed` have different outcomes.
+///
+/// # Panics
+/// Panics if every digest is the same for both seeds.
+pub fn assert_seed_sensitivity(setup: impl Fn(&mut Context), config: &DeterminismConfig) {
+    let first = run_digests(&setup, config, config.seed);
+    let second = run_digests(&setup, config, config.alternate_seed);
+    assert!(
+        first != second,
+        "Runs with seeds {} and {} had the same outcome; is the model using the seed?",
+        config.seed,
+        config.alternate_seed
+    );
+}
+
+// Build and run a simulation and return its digests, keyed by name.
+fn run_digests(
+    setup: &impl Fn(&mut Context),
+    config: &DeterminismConfig,
+    seed: u64,
+) -> BTreeMap<String, String> {
+    static RUN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let report_dir: PathBuf = std::env::temp_dir().join(format!(
+        "ixa-determinism-{}-{}",
+        std::process::id(),
+        RUN_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    fs::create_dir_all(&report_dir).expect("Could not create report directory");
+
+    let mut context = Context::new();
+    context.init_random(seed);
+    context
+        .report_options()
+        .directory(report_dir.clone())
+        .overwrite(true);
+    setup(&mut context);
+    match config.end_time {
+        Some(end_time) => context.run_until(end_time),
+        None => context.execute(),
+    }
+
+    let mut digests = BTreeMap::new();
+    digests.insert(
+        "final time".to_string(),
+        context.get_current_time().to_string(),
+    );
+    digests.insert(
+        "population".to_string(),
+        context.get_current_population().to_string(),
+    );
+    for (name, digest) in context.get_property_digests() {
+        digests.insert(format!("property {name}"), format!("{digest:016x}"));
+    }
+    digests.insert(
+        "random number generators".to_string(),
+        format!("{:016x}", rng_state_digest(&context)),
+    );
+
+    // Dropping the context flushes the reports.
+    drop(context);
+    let mut files: Vec<PathBuf> = fs::read_dir(&report_dir)
+        .expect("Could not read report directory")
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+    for file in files {
+        let contents = fs::read(&file).expect("Could not read report");
+        let name = file.file_name().unwrap().to_string_lossy();
+        digests.insert(
+            format!("report {name}"),
+            format!("{:016x}", fxhash::hash64(&contents)),
+        );
+    }
+    let _ = fs::remove_dir_all(&report_dir);
+    digests
+}
+
+fn describe_differences(
+    first: &BTreeMap<String, String>,
+    second: &BTreeMap<String, String>,
+) -> String {
+    let mut differences = String::new();
+    for name in first
+        .keys()
+        .chain(second.keys().filter(|k| !first.contains_key(*k)))
+    {
+        let a = first.get(name).map_or("missing", String::as_str);
+        let b = second.get(name).map_or("missing", String::as_str);
+        if a != b {
+            let _ = writeln!(differences, "  {name}: {a} != {b}");
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod test {
+    use super::{assert_deterministic, assert_seed_sensitivity, DeterminismConfig};
+    use crate::{
+        create_report_trait, define_person_property_with_default, define_rng, Context,
+        ContextPeopleExt, ContextRandomExt, ContextReportExt, PersonId, Report,
+    };
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use serde::Serialize;
+    use std::collections::HashMap;
+
+    define_rng!(DeterminismRng);
+    define_person_property_with_default!(Infected, bool, false);
+    define_person_property_with_default!(Order, usize, 0);
+
+    #[derive(Serialize)]
+    struct InfectionReport {
+        time: f64,
+        person_id: usize,
+    }
+    create_report_trait!(InfectionReport);
+
+    // Infects random people at random times, reporting each infection.
+    fn model(context: &mut Context) {
+        context.add_report::<InfectionReport>("infections").unwrap();
+        for _ in 0..20 {
+            context.add_person(()).unwrap();
+        }
+        for _ in 0..5 {
+            let time = context.sample_range(DeterminismRng, 0.0..10.0);
+            context.add_plan(time, |context| {
+                let person_id = context.sample_person(DeterminismRng, ()).unwrap().unwrap();
+                context.set_person_property(person_id, Infected, true);
+                context.send_report(InfectionReport {
+                    time: context.get_current_time(),
+                    person_id: person_id.0,
+                });
+            });
+        }
+    }
+
+    #[test]
+    fn correct_model_passes() {
+        let config = DeterminismConfig::default();
+        assert_deterministic(model, &config);
+        assert_seed_sensitivity(model, &config);
+    }
+
+    #[test]
+    fn stops_at_end_time() {
+        let config = DeterminismConfig {
+            end_time: Some(5.0),
+            ..DeterminismConfig::default()
+        };
+        assert_deterministic(
+            |context| {
+                context.add_plan(1.0, |context| {
+                    // A model that would otherwise never finish
+                    fn reschedule(context: &mut Context) {
+                        let next = context.get_current_time() + 1.0;
+                        context.add_plan(next, reschedule);
+                    }
+                    reschedule(context);
+                });
+            },
+            &config,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "property Order")]
+    fn catches_hash_map_iteration_order() {
+        assert_deterministic(
+            |context| {
+                let people: HashMap<usize, PersonId> = (0..20)
+                    .map(|i| (i, context.add_person(()).unwrap()))
+                    .collect();
+                // The iteration order differs between runs
+                for (order, person_id) in people.values().enumerate() {
+                    context.set_person_property(*person_id, Order, order);
+                }
+            },
+            &DeterminismConfig::default(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "is the model using the seed?")]
+    fn catches_ignored_seed() {
+        assert_seed_sensitivity(
+            |context| {
+                // Uses a fixed seed instead of the context's generators
+                let mut rng = StdRng::seed_from_u64(8);
+                for _ in 0..20 {
+                    let infected = rng.gen_bool(0.5);
+                    context.add_person((Infected, infected)).unwrap();
+                }
+            },
+            &DeterminismConfig::default(),
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Pooled-hazard transmission within a group of people who share a setting,
+//! e.g., a household or a classroom, with per-person modifiers.
+//!
+//! Each member of a setting is classified as infectious, susceptible, or
+//! neither, and has a relative infectiousness and a relative susceptibility
+//! given by `OrderedFloat<f64>` person properties (which can be derived from age,
+//! vaccination status, etc.). Over an interval `dt`, a susceptible member
+//! `j` of a setting with `n` members experiences the hazard
+//!
+//! ```text
+//! h_j = beta * s_j * dt * (sum of r_i over infectious members i) / (n - 1)^alpha
+//! ```
+//!
+//! and is infected with probability `1 - exp(-h_j)`. `alpha` scales
+//! transmission with the size of the setting: with `alpha = 0` each
+//! infectious member contributes the same hazard regardless of the size
+//! (density-dependent), and with `alpha = 1` the hazard is spread over the
+//! other members (frequency-dependent).
+//!
+//! The probabilities are computed in one pass over the membership, reading
+//! each member's properties once. People who are in several settings during
+//! the same interval have independent hazards in each; use
+//! [`ContextTransmissionExt::combined_infection_probabilities()`], which
+//! sums them, rather than combining per-setting probabilities by hand.
+use crate::{Context, ContextPeopleExt, ContextRandomExt, PersonId, PersonProperty, RngId};
+use ordered_float::OrderedFloat;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// The role a person plays in transmission within a setting
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransmissionRole {
+    Infectious,
+    Susceptible,
+    /// Neither infectious nor susceptible, e.g., recovered. These people
+    /// count towards the size of the setting.
+    Neither,
+}
+
+/// The parameters of the pooled hazard within a setting.
+///
+/// `S` and `I` are the person properties giving the relative
+/// susceptibility and relative infectiousness of each person.
+#[derive(Copy, Clone, Debug)]
+pub struct HazardParams<S, I> {
+    /// The transmission rate per unit time between a pair of people with
+    /// relative susceptibility and infectiousness of 1
+    pub beta: f64,
+    /// The exponent of the size scaling, usually between 0 and 1
+    pub alpha: f64,
+    pub susceptibility: S,
+    pub infectiousness: I,
+    /// Classifies each member of the setting
+    pub role: fn(&Context, PersonId) -> TransmissionRole,
+}
+
+fn setting_hazards<S, I>(
+    context: &Context,
+    members: &[PersonId],
+    dt: f64,
+    params: &HazardParams<S, I>,
+) -> Vec<(PersonId, f64)>
+where
+    S: PersonProperty<Value = OrderedFloat<f64>> + 'static,
+    I: PersonProperty<Value = OrderedFloat<f64>> + 'static,
+{
+    let mut total_infectiousness = 0.0;
+    let mut susceptibles = Vec::new();
+    for &person_id in members {
+        match (params.role)(context, person_id) {
+            TransmissionRole::Infectious => {
+                total_infectiousness += context
+                    .get_person_property(person_id, params.infectiousness)
+                    .0;
+            }
+            TransmissionRole::Susceptible => {
+                let susceptibility = context
+                    .get_person_property(person_id, params.susceptibility)
+                    .0;
+                if susceptibility > 0.0 {
+                    susceptibles.push((person_id, susceptibility));
+                }
+            }
+            TransmissionRole::Neither => {}
+        }
+    }
+
+    if total_infectiousness <= 0.0 || susceptibles.is_empty() {
+        return Vec::new();
+    }
+
+    // There is at least one infectious and one susceptible member, so
+    // there are at least two members.
+    #[allow(clippy::cast_precision_loss)]
+    let others = (members.len() - 1) as f64;
+    let pooled = params.beta * dt * total_infectiousness / others.powf(params.alpha);
+    for (_, hazard) in &mut susceptibles {
+        *hazard *= pooled;
+    }
+    susceptibles
+}
+
+// `1 - exp(-h)` without losing precision when `h` is tiny.
+fn probability_from_hazard(hazard: f64) -> f64 {
+    -(-hazard).exp_m1()
+}
+
+pub trait ContextTransmissionExt {
+    /// Returns the probability that each susceptible member of the setting
+    /// made up of `members` is infected over an interval of length `dt`.
+    ///
+    /// Susceptible members with a probability of zero, e.g., because there
+    /// are no infectious members, are omitted.
+    fn setting_infection_probabilities<S, I>(
+        &self,
+        members: &[PersonId],
+        dt: f64,
+        params: &HazardParams<S, I>,
+    ) -> impl Iterator<Item = (PersonId, f64)>
+    where
+        S: PersonProperty<Value = OrderedFloat<f64>> + 'static,
+        I: PersonProperty<Value = OrderedFloat<f64>> + 'static;
+
+    /// Returns the probability that each susceptible person in any of
+    /// `settings` is infected over an interval of length `dt`, summing the
+    /// hazards of people who are members of more than one setting. People
+    /// are returned in the order they first appear.
+    fn combined_infection_probabilities<S, I>(
+        &self,
+        settings: &[&[PersonId]],
+        dt: f64,
+        params: &HazardParams<S, I>,
+    ) -> impl Iterator<Item = (PersonId, f64)>
+    where
+        S: PersonProperty<Value = OrderedFloat<f64>> + 'static,
+        I: PersonProperty<Value = OrderedFloat<f64>> + 'static;
+
+    /// Draws which people are infected given their probabilities of
+    /// infection, using the random number generator `rng_id`.
+    fn sample_infections<R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        probabilities: impl IntoIterator<Item = (PersonId, f64)>,
+    ) -> Vec<PersonId>
+    where
+        R::RngType: Rng;
+}
+
+impl ContextTransmissionExt for Context {
+    fn setting_infection_probabilities<S, I>(
+        &self,
+        members: &[PersonId],
+        dt: f64,
+        params: &HazardParams<S, I>,
+    ) -> impl Iterator<Item = (PersonId, f64)>
+    where
+        S: PersonProperty<Value = OrderedFloat<f64>> + 'static,
+        I: PersonProperty<Value = OrderedFloat<f64>> + 'static,
+    {
+        setting_hazards(self, members, dt, params)
+            .into_iter()
+            .map(|(person_id, hazard)| (person_id, probability_from_hazard(hazard)))
+    }
+
+    fn combined_infection_probabilities<S, I>(
+        &self,
+        settings: &[&[PersonId]],
+        dt: f64,
+        params: &HazardParams<S, I>,
+    ) -> impl Iterator<Item = (PersonId, f64)>
+    where
+        S: PersonProperty<Value = OrderedFloat<f64>> + 'static,
+        I: PersonProperty<Value = OrderedFloat<f64>> + 'static,
+    {
+        let mut hazards: Vec<(PersonId, f64)> = Vec::new();
+        let mut positions: HashMap<PersonId, usize> = HashMap::new();
+        for members in settings {
+            for (person_id, hazard) in setting_hazards(self, members, dt, params) {
+                if let Some(&position) = positions.get(&person_id) {
+                    hazards[position].1 += hazard;
+                } else {
+                    positions.insert(person_id, hazards.len());
+                    hazards.push((person_id, hazard));
+                }
+            }
+        }
+        hazards
+            .into_iter()
+            .map(|(person_id, hazard)| (person_id, probability_from_hazard(hazard)))
+    }
+
+    fn sample_infections<R: RngId + 'static>(
+        &self,
+        rng_id: R,
+        probabilities: impl IntoIterator<Item = (PersonId, f64)>,
+    ) -> Vec<PersonId>
+    where
+        R::RngType: Rng,
+    {
+        probabilities
+            .into_iter()
+            .filter(|&(_, probability)| self.sample_bool(rng_id, probability))
+            .map(|(person_id, _)| person_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ContextTransmissionExt, HazardParams, TransmissionRole};
+    use crate::{
+        define_person_property, define_person_property_with_default, define_rng, Context,
+        ContextPeopleExt, ContextRandomExt, PersonId,
+    };
+    use ordered_float::OrderedFloat;
+
+    define_rng!(TransmissionRng);
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize)]
+    pub enum StatusValue {
+        S,
+        I,
+        R,
+    }
+    define_person_property_with_default!(Status, StatusValue, StatusValue::S);
+    define_person_property!(RelativeSusceptibility, OrderedFloat<f64>);
+    define_person_property!(RelativeInfectiousness, OrderedFloat<f64>);
+
+    fn role(context: &Context, person_id: PersonId) -> TransmissionRole {
+        match context.get_person_property(person_id, Status) {
+            StatusValue::S => TransmissionRole::Susceptible,
+            StatusValue::I => TransmissionRole::Infectious,
+            StatusValue::R => TransmissionRole::Neither,
+        }
+    }
+
+    fn params(
+        beta: f64,
+        alpha: f64,
+    ) -> HazardParams<RelativeSusceptibility, RelativeInfectiousness> {
+        HazardParams {
+            beta,
+            alpha,
+            susceptibility: RelativeSusceptibility,
+            infectiousness: RelativeInfectiousness,
+            role,
+        }
+    }
+
+    fn add(context: &mut Context, status: StatusValue, s: f64, r: f64) -> PersonId {
+        context
+            .add_person((
+                (Status, status),
+                (RelativeSusceptibility, OrderedFloat(s)),
+                (RelativeInfectiousness, OrderedFloat(r)),
+            ))
+            .unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn probabilities_by_alpha() {
+        let mut context = Context::new();
+        let i1 = add(&mut context, StatusValue::I, 1.0, 1.0);
+        let i2 = add(&mut context, StatusValue::I, 1.0, 0.5);
+        let s1 = add(&mut context, StatusValue::S, 1.0, 1.0);
+        let s2 = add(&mut context, StatusValue::S, 0.5, 1.0);
+        let r = add(&mut context, StatusValue::R, 1.0, 1.0);
+        let members = [i1, i2, s1, s2, r];
+
+        // Total infectiousness is 1.5 and there are 4 other members.
+        let alpha_zero: Vec<_> = context
+            .setting_infection_probabilities(&members, 2.0, &params(0.1, 0.0))
+            .collect();
+        assert_eq!(alpha_zero.len(), 2);
+        assert_eq!(alpha_zero[0].0, s1);
+        assert_close(alpha_zero[0].1, 1.0 - (-0.3_f64).exp());
+        assert_eq!(alpha_zero[1].0, s2);
+        assert_close(alpha_zero[1].1, 1.0 - (-0.15_f64).exp());
+
+        let alpha_one: Vec<_> = context
+            .setting_infection_probabilities(&members, 2.0, &params(0.1, 1.0))
+            .collect();
+        assert_close(alpha_one[0].1, 1.0 - (-0.075_f64).exp());
+        assert_close(alpha_one[1].1, 1.0 - (-0.0375_f64).exp());
+    }
+
+    #[test]
+    fn no_infectious_members() {
+        let mut context = Context::new();
+        let members = [
+            add(&mut context, StatusValue::S, 1.0, 1.0),
+            add(&mut context, StatusValue::R, 1.0, 1.0),
+            add(&mut context, StatusValue::I, 1.0, 0.0),
+        ];
+        assert_eq!(
+            context
+                .setting_infection_probabilities(&members, 1.0, &params(1.0, 0.0))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn tiny_hazards_are_not_rounded_to_zero() {
+        let mut context = Context::new();
+        let members = [
+            add(&mut context, StatusValue::I, 1.0, 1.0),
+            add(&mut context, StatusValue::S, 1.0, 1.0),
+        ];
+        let probabilities: Vec<_> = context
+            .setting_infection_probabilities(&members, 1.0, &params(1e-20, 0.0))
+            .collect();
+        assert_close(probabilities[0].1, 1e-20);
+        assert!(probabilities[0].1 > 0.0);
+    }
+
+    #[test]
+    fn combined_sums_hazards_across_settings() {
+        let mut context = Context::new();
+        let person = add(&mut context, StatusValue::S, 1.0, 1.0);
+        let household = [person, add(&mut context, StatusValue::I, 1.0, 1.0)];
+        let school = [
+            person,
+            add(&mut context, StatusValue::S, 1.0, 1.0),
+            add(&mut context, StatusValue::I, 1.0, 2.0),
+        ];
+
+        let combined: Vec<_> = context
+            .combined_infection_probabilities(&[&household, &school], 1.0, &params(0.1, 1.0))
+            .collect();
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0].0, person);
+        // 0.1 * 1 / 1 from the household plus 0.1 * 2 / 2 from the school
+        assert_close(combined[0].1, 1.0 - (-0.2_f64).exp());
+        assert_eq!(combined[1].0, school[1]);
+        assert_close(combined[1].1, 1.0 - (-0.1_f64).exp());
+    }
+
+    #[test]
+    fn sample_infections_realizes_probabilities() {
+        let mut context = Context::new();
+        context.init_random(42);
+        let people: Vec<_> = (0..3)
+            .map(|_| add(&mut context, StatusValue::S, 1.0, 1.0))
+            .collect();
+        let infected = context.sample_infections(
+            TransmissionRng,
+            [(people[0], 1.0), (people[1], 0.0), (people[2], 1.0)],
+        );
+        assert_eq!(infected, vec![people[0], people[2]]);
+    }
+}
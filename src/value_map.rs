@@ -0,0 +1,686 @@
+//! Lookup tables from the value of a person property to a value shared by
+//! everyone with that value ("value maps"), such as a contact rate by age
+//! or a healthcare capacity tier by county.
+//!
+//! A value map is defined with [`define_value_map!()`], given a table, and
+//! read with [`Context::lookup()`] for a person or
+//! [`Context::lookup_value()`] for a key:
+//!
+//! ```
+//! use ixa::{define_person_property, define_value_map, Context, ContextPeopleExt};
+//! use ixa::ContextValueMapExt;
+//!
+//! define_person_property!(Age, u8);
+//! define_value_map!(ContactRateByAge, Age => f64);
+//!
+//! let mut context = Context::new();
+//! context.set_value_map(ContactRateByAge, [(5, 12.0), (35, 8.0)]);
+//! let person = context.add_person((Age, 35)).unwrap();
+//! assert_eq!(context.lookup(ContactRateByAge, person).unwrap(), 8.0);
+//! assert_eq!(context.lookup_value(ContactRateByAge, 5).unwrap(), 12.0);
+//! ```
+//!
+//! Nothing is stored for each person: a lookup reads the person's value of
+//! the key property and finds it in the table. The table can be a list of
+//! entries, a CSV file with a key and a value column, a global property,
+//! or a function that is called once for each key that is looked up.
+//!
+//! Looking up a key that isn't in the table is an error that names the
+//! key. Instead, the map can have a default value, or, for keys that are
+//! ordered, use the value of the nearest key below, e.g., the lower bound
+//! of an age band:
+//!
+//! ```
+//! # use ixa::{define_person_property, define_value_map};
+//! # define_person_property!(Age, u8);
+//! define_value_map!(RiskByAge, Age => f64, default = 1.0);
+//! define_value_map!(ContactRateByAgeBand, Age => f64, nearest_below);
+//! ```
+//!
+//! Derived properties can use value maps (see
+//! [`define_derived_property!()`](crate::define_derived_property)), which
+//! is how they feed the susceptibility and infectiousness of the
+//! [`transmission`](crate::transmission) helpers.
+//!
+//! Setting the table of a map that already has one replaces it and emits a
+//! [`ValueMapChangedEvent`]. A map set from a global property with
+//! [`Context::set_value_map_from_global_property()`] is rebuilt when a
+//! reload of live overrides (see [`crate::live_overrides`]) changes the
+//! property, once the [`GlobalPropertiesReloadedEvent`] is handled. As
+//! with global properties, derived properties that use a map aren't
+//! reindexed when it changes.
+use crate::context::{Context, IxaEvent};
+use crate::define_data_plugin;
+use crate::error::IxaError;
+use crate::global_properties::{ContextGlobalPropertiesExt, GlobalProperty};
+use crate::live_overrides::GlobalPropertiesReloadedEvent;
+use crate::people::{ContextPeopleExt, PersonId, PersonProperty};
+use serde::de::DeserializeOwned;
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::path::Path;
+
+/// A lookup table from the value of a person property. Do not implement
+/// this directly, but define value maps with [`define_value_map!()`].
+pub trait ValueMap: Copy + 'static {
+    /// The person property whose value is looked up
+    type Key: PersonProperty<Value = Self::KeyValue> + 'static;
+    /// The type of the keys, which is the value of the key property
+    type KeyValue: Copy + Debug + Eq + Hash + 'static;
+    /// The type of the values in the table
+    type Value: Clone + PartialEq + 'static;
+
+    fn new() -> Self;
+    fn key_property() -> Self::Key;
+    fn name() -> &'static str;
+
+    #[doc(hidden)]
+    // The value for a key that isn't in the table, or `None` if it's an
+    // error to look it up
+    fn missing_key(
+        _entries: &HashMap<Self::KeyValue, Self::Value>,
+        _key: Self::KeyValue,
+    ) -> Option<Self::Value> {
+        None
+    }
+}
+
+/// Defines a value map with the following parameters:
+/// * `$value_map`: Name for the identifier type of the value map
+/// * `$key`: The person property whose value is looked up
+/// * `$value`: The type of the values
+/// * `default = $default` or `nearest_below` (optional): The value for a
+///   key that isn't in the table, either `$default` or the value of the
+///   largest key below it, for keys that are `Ord`. Without either,
+///   looking up the key is an error.
+#[macro_export]
+macro_rules! define_value_map {
+    ($value_map:ident, $key:ident => $value:ty) => {
+        $crate::define_value_map!(@define $value_map, $key, $value, |_entries, _key| None);
+    };
+    ($value_map:ident, $key:ident => $value:ty, default = $default:expr) => {
+        $crate::define_value_map!(@define $value_map, $key, $value, |_entries, _key| {
+            Some($default)
+        });
+    };
+    ($value_map:ident, $key:ident => $value:ty, nearest_below) => {
+        $crate::define_value_map!(@define $value_map, $key, $value, |entries, key| {
+            $crate::value_map::nearest_below(entries, key)
+        });
+    };
+    (@define $value_map:ident, $key:ident, $value:ty, $missing_key:expr) => {
+        #[derive(Debug, Copy, Clone)]
+        pub struct $value_map;
+
+        impl $crate::value_map::ValueMap for $value_map {
+            type Key = $key;
+            type KeyValue = <$key as $crate::people::PersonProperty>::Value;
+            type Value = $value;
+
+            fn new() -> Self {
+                $value_map
+            }
+
+            fn key_property() -> $key {
+                <$key as $crate::people::PersonProperty>::get_instance()
+            }
+
+            fn name() -> &'static str {
+                stringify!($value_map)
+            }
+
+            fn missing_key(
+                entries: &std::collections::HashMap<Self::KeyValue, $value>,
+                key: Self::KeyValue,
+            ) -> Option<$value> {
+                ($missing_key)(entries, key)
+            }
+        }
+    };
+}
+pub use define_value_map;
+
+#[doc(hidden)]
+#[allow(clippy::implicit_hasher)]
+/// The value of the largest key that is less than `key`
+pub fn nearest_below<K: Ord + Copy, V: Clone>(entries: &HashMap<K, V>, key: K) -> Option<V> {
+    entries
+        .iter()
+        .filter(|(entry_key, _)| **entry_key < key)
+        .max_by_key(|(entry_key, _)| **entry_key)
+        .map(|(_, value)| value.clone())
+}
+
+/// Emitted when the table of a value map is replaced, but not when it is
+/// first set
+#[derive(Clone, Copy)]
+pub struct ValueMapChangedEvent<M: ValueMap> {
+    pub value_map: M,
+}
+
+impl<M: ValueMap> IxaEvent for ValueMapChangedEvent<M> {}
+
+type ComputeFn<M> = dyn Fn(<M as ValueMap>::KeyValue) -> <M as ValueMap>::Value;
+
+struct Table<M: ValueMap> {
+    entries: HashMap<M::KeyValue, M::Value>,
+    compute: Option<Box<ComputeFn<M>>>,
+    // The values of the keys that aren't entries, once they've been
+    // computed or found with the missing key policy
+    resolved: RefCell<HashMap<M::KeyValue, M::Value>>,
+}
+
+impl<M: ValueMap> Table<M> {
+    fn new(entries: HashMap<M::KeyValue, M::Value>, compute: Option<Box<ComputeFn<M>>>) -> Self {
+        Table {
+            entries,
+            compute,
+            resolved: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn lookup(&self, key: M::KeyValue) -> Result<M::Value, IxaError> {
+        if let Some(value) = self.entries.get(&key) {
+            return Ok(value.clone());
+        }
+        if let Some(value) = self.resolved.borrow().get(&key) {
+            return Ok(value.clone());
+        }
+        let value = match &self.compute {
+            Some(compute) => compute(key),
+            None => M::missing_key(&self.entries, key).ok_or_else(|| {
+                IxaError::IxaError(format!(
+                    "Value map {} has no value for {} {key:?}",
+                    M::name(),
+                    <M::Key as PersonProperty>::name()
+                ))
+            })?,
+        };
+        self.resolved.borrow_mut().insert(key, value.clone());
+        Ok(value)
+    }
+}
+
+#[derive(Default)]
+struct ValueMapData {
+    tables: HashMap<TypeId, Box<dyn Any>>,
+    // The maps whose tables are rebuilt when their global property is
+    // reloaded
+    from_global_property: HashSet<TypeId>,
+    // The maps with a handler for reloads, which stays subscribed if the
+    // map is later set from elsewhere
+    reload_handlers: HashSet<TypeId>,
+}
+
+define_data_plugin!(ValueMapPlugin, ValueMapData, ValueMapData::default());
+
+// Value maps are set up by the model, like its event handlers, so they
+// aren't saved in checkpoints
+pub(crate) fn plugin_type_id() -> TypeId {
+    TypeId::of::<ValueMapPlugin>()
+}
+
+fn get_table<M: ValueMap>(context: &Context) -> Option<&Table<M>> {
+    context
+        .get_data_container(ValueMapPlugin)?
+        .tables
+        .get(&TypeId::of::<M>())
+        .map(|table| table.downcast_ref::<Table<M>>().unwrap())
+}
+
+fn replace_table<M: ValueMap>(context: &mut Context, table: Table<M>, from_global_property: bool) {
+    let data_container = context.get_data_container_mut(ValueMapPlugin);
+    if from_global_property {
+        data_container
+            .from_global_property
+            .insert(TypeId::of::<M>());
+    } else {
+        data_container
+            .from_global_property
+            .remove(&TypeId::of::<M>());
+    }
+    let previous = data_container
+        .tables
+        .insert(TypeId::of::<M>(), Box::new(table));
+    if previous.is_some() {
+        context.emit_event(ValueMapChangedEvent {
+            value_map: M::new(),
+        });
+    }
+}
+
+fn global_property_entries<M, G>(
+    context: &Context,
+) -> Result<HashMap<M::KeyValue, M::Value>, IxaError>
+where
+    M: ValueMap,
+    G: GlobalProperty + 'static,
+    G::Value: Clone + IntoIterator<Item = (M::KeyValue, M::Value)>,
+{
+    let value = context.get_global_property_value(G::new()).ok_or_else(|| {
+        IxaError::IxaError(format!(
+            "The global property of value map {} is not set",
+            M::name()
+        ))
+    })?;
+    Ok(value.clone().into_iter().collect())
+}
+
+pub trait ContextValueMapExt {
+    /// Set the table of a value map to `entries`.
+    fn set_value_map<M: ValueMap>(
+        &mut self,
+        value_map: M,
+        entries: impl IntoIterator<Item = (M::KeyValue, M::Value)>,
+    );
+
+    /// Set a value map to call `compute` the first time each key is looked
+    /// up, and keep the value it returns.
+    fn set_value_map_fn<M: ValueMap>(
+        &mut self,
+        value_map: M,
+        compute: impl Fn(M::KeyValue) -> M::Value + 'static,
+    );
+
+    /// Set the table of a value map from a CSV file with a header and two
+    /// columns, the key and the value.
+    ///
+    /// # Errors
+    /// Will return an `IxaError` if the file can't be read or a row can't
+    /// be parsed, without changing the map.
+    fn load_value_map_from_csv<M: ValueMap>(
+        &mut self,
+        value_map: M,
+        path: &Path,
+    ) -> Result<(), IxaError>
+    where
+        M::KeyValue: DeserializeOwned,
+        M::Value: DeserializeOwned;
+
+    /// Set the table of a value map from the value of a global property,
+    /// such as a `HashMap` from the key to the value. The table is rebuilt
+    /// when a reload of live overrides changes the property.
+    ///
+    /// # Errors
+    /// Will return an `IxaError` if the global property isn't set.
+    fn set_value_map_from_global_property<M, G>(
+        &mut self,
+        value_map: M,
+        property: G,
+    ) -> Result<(), IxaError>
+    where
+        M: ValueMap,
+        G: GlobalProperty + 'static,
+        G::Value: Clone + IntoIterator<Item = (M::KeyValue, M::Value)>;
+
+    /// Look up the value for a person's value of the key property.
+    ///
+    /// # Errors
+    /// Will return an `IxaError` if the map isn't set or has no value for
+    /// the key.
+    fn lookup<M: ValueMap>(&self, value_map: M, person_id: PersonId) -> Result<M::Value, IxaError>;
+
+    /// Look up the value for a key.
+    ///
+    /// # Errors
+    /// Will return an `IxaError` if the map isn't set or has no value for
+    /// the key.
+    fn lookup_value<M: ValueMap>(
+        &self,
+        value_map: M,
+        key: M::KeyValue,
+    ) -> Result<M::Value, IxaError>;
+}
+
+impl ContextValueMapExt for Context {
+    fn set_value_map<M: ValueMap>(
+        &mut self,
+        _value_map: M,
+        entries: impl IntoIterator<Item = (M::KeyValue, M::Value)>,
+    ) {
+        replace_table(
+            self,
+            Table::<M>::new(entries.into_iter().collect(), None),
+            false,
+        );
+    }
+
+    fn set_value_map_fn<M: ValueMap>(
+        &mut self,
+        _value_map: M,
+        compute: impl Fn(M::KeyValue) -> M::Value + 'static,
+    ) {
+        replace_table(
+            self,
+            Table::<M>::new(HashMap::new(), Some(Box::new(compute))),
+            false,
+        );
+    }
+
+    fn load_value_map_from_csv<M: ValueMap>(
+        &mut self,
+        value_map: M,
+        path: &Path,
+    ) -> Result<(), IxaError>
+    where
+        M::KeyValue: DeserializeOwned,
+        M::Value: DeserializeOwned,
+    {
+        let mut reader = csv::Reader::from_path(path)?;
+        let entries = reader
+            .deserialize::<(M::KeyValue, M::Value)>()
+            .collect::<Result<Vec<_>, _>>()?;
+        self.set_value_map(value_map, entries);
+        Ok(())
+    }
+
+    fn set_value_map_from_global_property<M, G>(
+        &mut self,
+        _value_map: M,
+        _property: G,
+    ) -> Result<(), IxaError>
+    where
+        M: ValueMap,
+        G: GlobalProperty + 'static,
+        G::Value: Clone + IntoIterator<Item = (M::KeyValue, M::Value)>,
+    {
+        let entries = global_property_entries::<M, G>(self)?;
+        replace_table(self, Table::<M>::new(entries, None), true);
+        if self
+            .get_data_container_mut(ValueMapPlugin)
+            .reload_handlers
+            .insert(TypeId::of::<M>())
+        {
+            self.subscribe_to_event(|context, _event: GlobalPropertiesReloadedEvent| {
+                // The map may have been set from elsewhere since.
+                let from_global_property = context
+                    .get_data_container(ValueMapPlugin)
+                    .is_some_and(|data| data.from_global_property.contains(&TypeId::of::<M>()));
+                if !from_global_property {
+                    return;
+                }
+                let entries = global_property_entries::<M, G>(context)
+                    .expect("Global property was set with the value map");
+                if get_table::<M>(context).is_some_and(|table| table.entries == entries) {
+                    return;
+                }
+                replace_table(context, Table::<M>::new(entries, None), true);
+            });
+        }
+        Ok(())
+    }
+
+    fn lookup<M: ValueMap>(&self, value_map: M, person_id: PersonId) -> Result<M::Value, IxaError> {
+        let key = self.get_person_property(person_id, M::key_property());
+        self.lookup_value(value_map, key)
+    }
+
+    fn lookup_value<M: ValueMap>(
+        &self,
+        _value_map: M,
+        key: M::KeyValue,
+    ) -> Result<M::Value, IxaError> {
+        get_table::<M>(self)
+            .ok_or_else(|| IxaError::IxaError(format!("Value map {} is not set", M::name())))?
+            .lookup(key)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod test {
+    use super::{ContextValueMapExt, ValueMapChangedEvent};
+    use crate::live_overrides::{ContextLiveOverridesExt, LiveOverridePolicy};
+    use crate::transmission::{ContextTransmissionExt, HazardParams, TransmissionRole};
+    use crate::{
+        define_derived_property, define_mutable_global_property, define_person_property,
+        define_person_property_with_default, Context, ContextGlobalPropertiesExt, ContextPeopleExt,
+        ContextReportExt, PersonId,
+    };
+    use ordered_float::OrderedFloat;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::rc::Rc;
+    use tempfile::tempdir;
+
+    define_person_property!(Age, u8);
+    define_person_property_with_default!(Infected, bool, false);
+
+    define_value_map!(ContactRateByAge, Age => f64);
+    define_value_map!(RiskByAge, Age => f64, default = 1.0);
+    define_value_map!(ContactRateByAgeBand, Age => f64, nearest_below);
+
+    define_mutable_global_property!(ContactRates, HashMap<u8, f64>);
+
+    #[test]
+    fn lookups_by_person_and_key() {
+        let mut context = Context::new();
+        let person = context.add_person((Age, 35)).unwrap();
+        let error = context.lookup(ContactRateByAge, person).unwrap_err();
+        assert_eq!(error.to_string(), "Value map ContactRateByAge is not set");
+
+        context.set_value_map(ContactRateByAge, [(5, 12.0), (35, 8.0)]);
+        assert_eq!(context.lookup(ContactRateByAge, person).unwrap(), 8.0);
+        assert_eq!(context.lookup_value(ContactRateByAge, 5).unwrap(), 12.0);
+        let error = context.lookup_value(ContactRateByAge, 36).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Value map ContactRateByAge has no value for Age 36"
+        );
+    }
+
+    #[test]
+    fn missing_key_policies() {
+        let mut context = Context::new();
+        context.set_value_map(RiskByAge, [(65, 3.0)]);
+        assert_eq!(context.lookup_value(RiskByAge, 65).unwrap(), 3.0);
+        assert_eq!(context.lookup_value(RiskByAge, 64).unwrap(), 1.0);
+
+        context.set_value_map(ContactRateByAgeBand, [(0, 12.0), (18, 8.0), (65, 4.0)]);
+        assert_eq!(context.lookup_value(ContactRateByAgeBand, 0).unwrap(), 12.0);
+        assert_eq!(
+            context.lookup_value(ContactRateByAgeBand, 17).unwrap(),
+            12.0
+        );
+        assert_eq!(context.lookup_value(ContactRateByAgeBand, 18).unwrap(), 8.0);
+        assert_eq!(context.lookup_value(ContactRateByAgeBand, 90).unwrap(), 4.0);
+
+        context.set_value_map(ContactRateByAgeBand, [(18, 8.0)]);
+        let error = context.lookup_value(ContactRateByAgeBand, 17).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Value map ContactRateByAgeBand has no value for Age 17"
+        );
+    }
+
+    #[test]
+    fn computed_values_are_kept() {
+        let mut context = Context::new();
+        let calls = Rc::new(Cell::new(0));
+        let calls_clone = Rc::clone(&calls);
+        context.set_value_map_fn(ContactRateByAge, move |age| {
+            calls_clone.set(calls_clone.get() + 1);
+            f64::from(age) / 10.0
+        });
+        for age in [10, 20, 10, 10] {
+            context.add_person((Age, age)).unwrap();
+        }
+        let rates: Vec<f64> = context
+            .query_people(())
+            .into_iter()
+            .map(|person| context.lookup(ContactRateByAge, person).unwrap())
+            .collect();
+        assert_eq!(rates, vec![1.0, 2.0, 1.0, 1.0]);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn load_from_csv() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("rates.csv");
+        fs::write(&path, "age,rate\n5,12.0\n35,8.5\n").unwrap();
+        let mut context = Context::new();
+        context
+            .load_value_map_from_csv(ContactRateByAge, &path)
+            .unwrap();
+        assert_eq!(context.lookup_value(ContactRateByAge, 35).unwrap(), 8.5);
+
+        // A bad row leaves the map as it was.
+        fs::write(&path, "age,rate\n5,12.0\n300,8.5\n").unwrap();
+        assert!(context
+            .load_value_map_from_csv(ContactRateByAge, &path)
+            .is_err());
+        assert_eq!(context.lookup_value(ContactRateByAge, 35).unwrap(), 8.5);
+    }
+
+    #[test]
+    fn reload_from_global_property() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("overrides.json");
+        let mut context = Context::new();
+        context.report_options().directory(dir.path().to_path_buf());
+        assert!(context
+            .set_value_map_from_global_property(ContactRateByAge, ContactRates)
+            .is_err());
+        context
+            .set_global_property_value(ContactRates, HashMap::from([(5, 12.0)]))
+            .unwrap();
+        context
+            .set_value_map_from_global_property(ContactRateByAge, ContactRates)
+            .unwrap();
+        context
+            .enable_live_overrides(
+                &path,
+                LiveOverridePolicy {
+                    check_interval: 1.0,
+                },
+            )
+            .unwrap();
+        let changes = Rc::new(Cell::new(0));
+        let changes_clone = Rc::clone(&changes);
+        context.subscribe_to_event(move |_, _event: ValueMapChangedEvent<ContactRateByAge>| {
+            changes_clone.set(changes_clone.get() + 1);
+        });
+
+        // The second reload doesn't change the rates, and the third comes
+        // after the map has been set from elsewhere.
+        for (time, rate) in [(1.0, 10.0), (2.0, 10.0), (4.0, 9.0)] {
+            let path = path.clone();
+            context.add_plan(time, move |context| {
+                let contents = format!(r#"{{"ixa.ContactRates": {{"5": {rate:.1}}}}}"#);
+                fs::write(&path, contents).unwrap();
+                context.reload_overrides().unwrap();
+            });
+        }
+        context.add_plan(3.0, |context| {
+            context.set_value_map(ContactRateByAge, [(5, 1.0)]);
+        });
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        for time in [0.5, 1.5, 3.5, 4.5] {
+            let seen = Rc::clone(&seen);
+            context.add_plan(time, move |context| {
+                seen.borrow_mut()
+                    .push(context.lookup_value(ContactRateByAge, 5).unwrap());
+            });
+        }
+        context.execute();
+
+        assert_eq!(*seen.borrow(), vec![12.0, 10.0, 1.0, 1.0]);
+        // The reload at 1.0 and setting the map at 3.0
+        assert_eq!(changes.get(), 2);
+    }
+
+    define_value_map!(SusceptibilityByAge, Age => OrderedFloat<f64>, nearest_below);
+    define_value_map!(InfectiousnessByAge, Age => OrderedFloat<f64>, default = OrderedFloat(1.0));
+    define_derived_property!(
+        Susceptibility,
+        OrderedFloat<f64>,
+        [Infected],
+        [],
+        [SusceptibilityByAge],
+        |infected, susceptibility| {
+            if infected {
+                OrderedFloat(0.0)
+            } else {
+                susceptibility
+            }
+        }
+    );
+    define_derived_property!(
+        Infectiousness,
+        OrderedFloat<f64>,
+        [],
+        [],
+        [InfectiousnessByAge],
+        |infectiousness| infectiousness
+    );
+
+    fn role(context: &Context, person_id: PersonId) -> TransmissionRole {
+        if context.get_person_property(person_id, Infected) {
+            TransmissionRole::Infectious
+        } else {
+            TransmissionRole::Susceptible
+        }
+    }
+
+    #[test]
+    fn derived_property_uses_value_map() {
+        let mut context = Context::new();
+        context.set_value_map(
+            SusceptibilityByAge,
+            [
+                (0, OrderedFloat(0.5)),
+                (18, OrderedFloat(1.0)),
+                (65, OrderedFloat(2.0)),
+            ],
+        );
+        context.set_value_map(InfectiousnessByAge, []);
+        let child = context.add_person((Age, 10)).unwrap();
+        let adult = context.add_person((Age, 40)).unwrap();
+        let infected = context.add_person(((Age, 70), (Infected, true))).unwrap();
+        assert_eq!(
+            context.get_person_property(child, Susceptibility),
+            OrderedFloat(0.5)
+        );
+        assert_eq!(
+            context.get_person_property(infected, Susceptibility),
+            OrderedFloat(0.0)
+        );
+        context.index_property(Susceptibility);
+        assert_eq!(
+            context.query_people((Susceptibility, OrderedFloat(1.0))),
+            vec![adult]
+        );
+
+        // Changing the key property changes the derived value.
+        context.set_person_property(child, Age, 20);
+        let mut people = context.query_people((Susceptibility, OrderedFloat(1.0)));
+        people.sort_by_key(|person| person.0);
+        assert_eq!(people, vec![child, adult]);
+
+        // The derived properties feed the transmission helpers.
+        context.set_person_property(adult, Age, 70);
+        let params = HazardParams {
+            beta: 1.0,
+            alpha: 0.0,
+            susceptibility: Susceptibility,
+            infectiousness: Infectiousness,
+            role,
+        };
+        let mut probabilities: Vec<(PersonId, f64)> = context
+            .setting_infection_probabilities(&[child, adult, infected], 1.0, &params)
+            .collect();
+        probabilities.sort_by_key(|(person, _)| person.0);
+        assert_eq!(
+            probabilities,
+            vec![
+                (child, 1.0 - (-1.0_f64).exp()),
+                (adult, 1.0 - (-2.0_f64).exp()),
+            ]
+        );
+    }
+}
@@ -1,17 +1,36 @@
 use crate::context::{run_with_plugin, Context};
 use crate::define_data_plugin;
 use crate::error::IxaError;
-use crate::external_api::{global_properties, next, people, population, run_ext_api, EmptyArgs};
+use crate::execution_stats::ExecutionStatistics;
+use crate::external_api::{
+    global_properties, ids, next, people, population, reload, run_ext_api, status, summary,
+    tabulate, values, EmptyArgs, POST_RUN_COMMANDS,
+};
 use axum::extract::{Json, Path, State};
-use axum::{http::StatusCode, routing::post, Router};
+use axum::{
+    http::StatusCode,
+    routing::{get, post},
+    Router,
+};
 use rand::RngCore;
 use serde_json::json;
 use std::collections::HashMap;
-use std::thread;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::sync::oneshot;
+use tokio::sync::watch;
 use tower_http::services::ServeDir;
 
+/// The default time for which the Web API keeps serving read-only commands
+/// after the simulation finishes; see [`ContextWebApiExt::finish_web_api()`].
+pub const DEFAULT_KEEP_API_ALIVE: Duration = Duration::from_secs(10);
+
+/// How often the execution statistics served at `/stats` are updated
+/// while the simulation runs.
+pub const STATS_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
 type ApiHandler = dyn Fn(&mut Context, serde_json::Value) -> Result<serde_json::Value, IxaError>;
 
 fn register_api_handler<
@@ -36,10 +55,40 @@ fn register_api_handler<
 struct ApiData {
     receiver: mpsc::UnboundedReceiver<ApiRequest>,
     handlers: HashMap<String, Box<ApiHandler>>,
+    shutdown: WebApiShutdownHandle,
+    server: Option<JoinHandle<()>>,
+    // The statistics served at `/stats`
+    statistics: Arc<Mutex<ExecutionStatistics>>,
 }
 
 define_data_plugin!(ApiPlugin, Option<ApiData>, None);
 
+// The API server's state belongs to the current run, so it isn't saved in checkpoints
+pub(crate) fn plugin_type_id() -> std::any::TypeId {
+    std::any::TypeId::of::<ApiPlugin>()
+}
+
+/// A handle for shutting down the Web API server, e.g., from another
+/// thread. Once shut down, the server stops accepting connections,
+/// completes the requests that are in flight, and exits.
+#[derive(Clone)]
+pub struct WebApiShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl WebApiShutdownHandle {
+    /// Ask the server to shut down.
+    pub fn shutdown(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Whether the server has been asked to shut down.
+    #[must_use]
+    pub fn is_shutdown(&self) -> bool {
+        *self.sender.borrow()
+    }
+}
+
 // Input to the API handler.
 struct ApiRequest {
     cmd: String,
@@ -57,6 +106,7 @@ struct ApiResponse {
 #[derive(Clone)]
 struct ApiEndpointServer {
     sender: mpsc::UnboundedSender<ApiRequest>,
+    statistics: Arc<Mutex<ExecutionStatistics>>,
 }
 
 async fn process_cmd(
@@ -77,15 +127,20 @@ async fn process_cmd(
     }
 }
 
+// The statistics are answered by the server itself rather than the
+// simulation, so they're available while it's running.
+async fn get_stats(State(state): State<ApiEndpointServer>) -> Json<ExecutionStatistics> {
+    Json(state.statistics.lock().unwrap().clone())
+}
+
 #[tokio::main]
 async fn serve(
-    sender: mpsc::UnboundedSender<ApiRequest>,
+    state: ApiEndpointServer,
     port: u16,
     prefix: &str,
     ready: oneshot::Sender<Result<String, IxaError>>,
+    mut shutdown: watch::Receiver<bool>,
 ) {
-    let state = ApiEndpointServer { sender };
-
     // run our app with Axum, listening on `port`
     let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{port}")).await;
     if listener.is_err() {
@@ -98,6 +153,7 @@ async fn serve(
     // build our application with a route
     let app = Router::new()
         .route(&format!("/{prefix}/cmd/{{command}}"), post(process_cmd))
+        .route(&format!("/{prefix}/stats"), get(get_stats))
         .nest_service(&format!("/{prefix}/static/"), ServeDir::new("static"))
         .with_state(state);
 
@@ -105,12 +161,50 @@ async fn serve(
     ready
         .send(Ok(format!("http://127.0.0.1:{port}/{prefix}/")))
         .unwrap();
-    axum::serve(listener.unwrap(), app).await.unwrap();
+    // Once shut down, axum stops accepting connections and waits for
+    // the ones that are open to finish.
+    axum::serve(listener.unwrap(), app)
+        .with_graceful_shutdown(async move {
+            let _ = shutdown.wait_for(|shutdown| *shutdown).await;
+        })
+        .await
+        .unwrap();
+}
+
+// Run a command with its registered handler.
+fn run_command(
+    context: &mut Context,
+    api: &ApiData,
+    cmd: &str,
+    arguments: serde_json::Value,
+) -> ApiResponse {
+    let Some(handler) = api.handlers.get(cmd) else {
+        return ApiResponse {
+            code: StatusCode::NOT_FOUND,
+            response: json!({
+                "error" : format!("No command {cmd}")
+            }),
+        };
+    };
+
+    match handler(context, arguments) {
+        Err(err) => ApiResponse {
+            code: StatusCode::BAD_REQUEST,
+            response: json!({
+                "error" : err.to_string()
+            }),
+        },
+        Ok(response) => ApiResponse {
+            code: StatusCode::OK,
+            response,
+        },
+    }
 }
 
 /// Starts the Web API, pausing execution until instructed
 /// to continue.
 fn handle_web_api(context: &mut Context, api: &mut ApiData) {
+    *api.statistics.lock().unwrap() = context.execution_statistics();
     while let Some(req) = api.receiver.blocking_recv() {
         if req.cmd == "continue" {
             let _ = req.rx.send(ApiResponse {
@@ -120,48 +214,95 @@ fn handle_web_api(context: &mut Context, api: &mut ApiData) {
             break;
         }
 
-        let handler = api.handlers.get(&req.cmd);
-        if handler.is_none() {
+        if req.cmd == "finalize" {
             let _ = req.rx.send(ApiResponse {
-                code: StatusCode::NOT_FOUND,
+                code: StatusCode::CONFLICT,
                 response: json!({
-                    "error" : format!("No command {}", req.cmd)
+                    "error" : "The simulation is still running"
                 }),
             });
             continue;
         }
 
-        let handler = handler.unwrap();
-        match handler(context, req.arguments.clone()) {
-            Err(err) => {
-                let _ = req.rx.send(ApiResponse {
-                    code: StatusCode::BAD_REQUEST,
-                    response: json!({
-                        "error" : err.to_string()
-                    }),
-                });
-                continue;
-            }
-            Ok(response) => {
-                let _ = req.rx.send(ApiResponse {
-                    code: StatusCode::OK,
-                    response,
-                });
-            }
-        };
+        let response = run_command(context, api, &req.cmd, req.arguments.clone());
+        let succeeded = response.code == StatusCode::OK;
+        let _ = req.rx.send(response);
 
         // Special case the functions which require exiting
         // the loop.
-        if req.cmd == "next" {
+        if succeeded && req.cmd == "next" {
             // This was already type checked in the handler so .unwrap() cannot fail.
             let next::Args::Next { next_time } = serde_json::from_value(req.arguments).unwrap();
             context.schedule_web_api(next_time);
             return;
         }
-        if req.cmd == "continue" {
-            return;
+    }
+}
+
+// Answer a request once the simulation has finished, when only the
+// post-run commands are available. Returns true if the request was
+// to finalize the Web API.
+fn handle_finished_request(context: &mut Context, api: &ApiData, req: ApiRequest) -> bool {
+    if req.cmd == "finalize" {
+        let _ = req.rx.send(ApiResponse {
+            code: StatusCode::OK,
+            response: json!({}),
+        });
+        return true;
+    }
+
+    let response = if POST_RUN_COMMANDS.contains(&req.cmd.as_str()) {
+        run_command(context, api, &req.cmd, req.arguments)
+    } else {
+        ApiResponse {
+            code: StatusCode::CONFLICT,
+            response: json!({
+                "error" : format!("The simulation has finished, so {} is not available", req.cmd)
+            }),
+        }
+    };
+    let _ = req.rx.send(response);
+    false
+}
+
+/// Serves read-only requests until `keep_alive` has passed, the Web API
+/// is finalized, or it is shut down with its handle, and then shuts the
+/// server down.
+fn finish_web_api(context: &mut Context, api: &mut ApiData, keep_alive: Duration) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    let deadline = tokio::time::Instant::now() + keep_alive;
+    *api.statistics.lock().unwrap() = context.execution_statistics();
+    let mut shutdown = api.shutdown.sender.subscribe();
+
+    loop {
+        let receiver = &mut api.receiver;
+        let req = runtime.block_on(async {
+            tokio::select! {
+                req = receiver.recv() => req,
+                () = tokio::time::sleep_until(deadline) => None,
+                _ = shutdown.wait_for(|shutdown| *shutdown) => None,
+            }
+        });
+        let Some(req) = req else {
+            break;
+        };
+        if handle_finished_request(context, api, req) {
+            break;
         }
     }
+
+    // Stop accepting connections, and answer the requests that are
+    // still in flight until the server is done with them.
+    api.shutdown.shutdown();
+    while let Some(req) = api.receiver.blocking_recv() {
+        handle_finished_request(context, api, req);
+    }
+    if let Some(server) = api.server.take() {
+        let _ = server.join();
+    }
 }
 
 pub trait ContextWebApiExt {
@@ -174,6 +315,27 @@ pub trait ContextWebApiExt {
     /// Schedule the simulation to pause at time t and listen for
     /// requests from the Web API.
     fn schedule_web_api(&mut self, t: f64);
+
+    /// Get a handle for shutting down the Web API server, or `None` if
+    /// the Web API hasn't been set up.
+    fn web_api_shutdown_handle(&self) -> Option<WebApiShutdownHandle>;
+
+    /// Wind down the Web API once the simulation has finished. Control
+    /// commands (`next`, `continue` and `reload`) are rejected, but the
+    /// post-run commands, the same ones as the debugger's
+    /// [`explore()`](crate::ContextDebugExt::explore) mode except `export`,
+    /// keep being served for `keep_alive` so that
+    /// dashboards can fetch the final state, or until a client sends
+    /// `finalize` or the server is shut down with its handle. The server
+    /// then shuts down after completing the requests in flight, and this
+    /// returns once it has exited. Does nothing if the Web API hasn't
+    /// been set up.
+    fn finish_web_api(&mut self, keep_alive: Duration);
+
+    /// Accept person ids in requests as bare integers, e.g., `12`, as well
+    /// as tagged ids like `person:12` (see [`crate::external_api::ids`]),
+    /// for clients written before ids were tagged.
+    fn accept_bare_person_ids(&mut self, accept: bool);
 }
 
 impl ContextWebApiExt for Context {
@@ -196,12 +358,25 @@ impl ContextWebApiExt for Context {
             .to_string();
 
         let (ready_tx, ready_rx) = oneshot::channel::<Result<String, IxaError>>();
-        thread::spawn(move || serve(api_to_ctx_send, port, &secret, ready_tx));
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let statistics = Arc::new(Mutex::new(self.execution_statistics()));
+        let state = ApiEndpointServer {
+            sender: api_to_ctx_send,
+            statistics: Arc::clone(&statistics),
+        };
+        let server = thread::spawn(move || {
+            serve(state, port, &secret, ready_tx, shutdown_rx);
+        });
         let url = ready_rx.blocking_recv().unwrap()?;
 
         let mut api_data = ApiData {
             receiver: api_to_ctx_recv,
             handlers: HashMap::new(),
+            shutdown: WebApiShutdownHandle {
+                sender: Arc::new(shutdown_tx),
+            },
+            server: Some(server),
+            statistics: Arc::clone(&statistics),
         };
 
         register_api_handler::<global_properties::Api, global_properties::Args>(
@@ -211,8 +386,16 @@ impl ContextWebApiExt for Context {
         register_api_handler::<population::Api, EmptyArgs>(&mut api_data, "population");
         register_api_handler::<next::Api, next::Args>(&mut api_data, "next");
         register_api_handler::<people::Api, people::Args>(&mut api_data, "people");
+        register_api_handler::<reload::Api, EmptyArgs>(&mut api_data, "reload");
+        register_api_handler::<values::Api, values::Args>(&mut api_data, "values");
+        register_api_handler::<status::Api, EmptyArgs>(&mut api_data, "status");
+        register_api_handler::<summary::Api, summary::Args>(&mut api_data, "summary");
+        register_api_handler::<tabulate::Api, tabulate::Args>(&mut api_data, "tabulate");
         // Record the data container.
-        *data_container = Some(api_data);
+        *self.get_data_container_mut(ApiPlugin) = Some(api_data);
+        self.publish_execution_statistics_every(STATS_UPDATE_INTERVAL, move |current| {
+            *statistics.lock().unwrap() = current.clone();
+        });
 
         Ok(url)
     }
@@ -224,6 +407,25 @@ impl ContextWebApiExt for Context {
             });
         });
     }
+
+    fn web_api_shutdown_handle(&self) -> Option<WebApiShutdownHandle> {
+        self.get_data_container(ApiPlugin)?
+            .as_ref()
+            .map(|api| api.shutdown.clone())
+    }
+
+    fn accept_bare_person_ids(&mut self, accept: bool) {
+        ids::set_accept_bare_person_ids(self, accept);
+    }
+
+    fn finish_web_api(&mut self, keep_alive: Duration) {
+        if self.web_api_shutdown_handle().is_none() {
+            return;
+        }
+        run_with_plugin::<ApiPlugin>(self, |context, data_container| {
+            finish_web_api(context, data_container.as_mut().unwrap(), keep_alive);
+        });
+    }
 }
 
 #[cfg(test)]
@@ -236,6 +438,7 @@ mod tests {
     use serde::Serialize;
     use serde_json::json;
     use std::thread;
+    use std::time::Duration;
 
     define_global_property!(WebApiTestGlobal, String);
     define_person_property!(Age, u8);
@@ -296,6 +499,7 @@ mod tests {
     // object to isolate the test cases.
 
     #[test]
+    #[allow(clippy::too_many_lines)]
     fn web_api_test() {
         #[derive(Serialize)]
         struct PopulationResponse {
@@ -378,7 +582,7 @@ mod tests {
             &json!({
                 "People" : {
                     "Get" : {
-                        "person_id": 0,
+                        "person_id": "person:0",
                         "property" : "Age"
                     }
                 }
@@ -391,6 +595,19 @@ mod tests {
             ]}
             )
         );
+        // Bare ids aren't accepted unless enabled
+        let res = send_request_text(
+            &url,
+            "people",
+            json!({"People": {"Get": {"person_id": 0, "property": "Age"}}}).to_string(),
+        );
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let error: serde_json::Value = res.json().unwrap();
+        assert!(error["error"]
+            .as_str()
+            .unwrap()
+            .contains("expected the form 'person:<number>'"));
+
         // Valid JSON but wrong type.
         let res = send_request_text(
             &url,
@@ -403,9 +620,138 @@ mod tests {
         let res = send_request_text(&url, "next", String::from("{]"));
         assert_eq!(res.status(), StatusCode::BAD_REQUEST);
 
+        // The status is collected while the simulation is paused in a plan.
+        let res = send_request(&url, "status", &json!({}));
+        assert_eq!(res["schema_version"], json!(1));
+        assert_eq!(res["population"], json!(2));
+        assert_eq!(res["running"], json!(true));
+
+        // Reload fails because live overrides aren't enabled.
+        let res = send_request_text(&url, "reload", String::from("{}"));
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
         // Test continue and make sure that the context
         // exits.
         send_continue(&url);
         let _ = ctx_thread.join();
     }
+
+    #[test]
+    fn web_api_stats_while_running() {
+        let mut context = Context::new();
+        context.add_person((Age, 1)).unwrap();
+        let url = context.setup_web_api(33343).unwrap();
+        let stats = reqwest::blocking::get(format!("{url}stats"))
+            .unwrap()
+            .json::<serde_json::Value>()
+            .unwrap();
+        assert_eq!(stats["running"], json!(false));
+        assert_eq!(stats["population"], json!(1));
+
+        context.add_plan(1.0, |_| thread::sleep(Duration::from_millis(1100)));
+        // The server answers without the simulation pausing, with the
+        // statistics from after the first plan.
+        context.add_plan(2.0, move |_| {
+            let stats = reqwest::blocking::get(format!("{url}stats"))
+                .unwrap()
+                .json::<serde_json::Value>()
+                .unwrap();
+            assert_eq!(stats["running"], json!(true));
+            assert_eq!(stats["plans_executed"], json!(1));
+            assert_eq!(stats["plans_pending"], json!(1));
+            assert_eq!(stats["simulation_time"], json!(1.0));
+        });
+        context.execute();
+        assert_eq!(context.execution_statistics().plans_executed, 2);
+        context.web_api_shutdown_handle().unwrap().shutdown();
+        context.finish_web_api(Duration::ZERO);
+    }
+
+    #[test]
+    fn web_api_read_only_after_run() {
+        let (tx, rx) = std::sync::mpsc::channel::<String>();
+        let ctx_thread = thread::spawn(move || {
+            let mut context = Context::new();
+            let url = context.setup_web_api(33340).unwrap();
+            context.add_person((Age, 1)).unwrap();
+            let _ = tx.send(url);
+            context.execute();
+            context.finish_web_api(Duration::from_secs(30));
+        });
+
+        let url = rx.recv().unwrap();
+        let res = send_request(&url, "population", &json!({}));
+        assert_eq!(res, json!({"population": 1}));
+        let res = send_request(&url, "status", &json!({}));
+        assert_eq!(res["running"], json!(false));
+        let res = send_request(
+            &url,
+            "tabulate",
+            &json!({"Tabulate": {"properties": ["Age"]}}),
+        );
+        assert_eq!(res, json!({"rows": [[["1"], 1]]}));
+        let res = send_request(
+            &url,
+            "people",
+            &json!({"People": {"Query": {"properties": [["Age", "1"]]}}}),
+        );
+        assert_eq!(res, json!({"People": ["person:0"]}));
+
+        // The simulation can't be controlled any more.
+        let res = send_request_text(
+            &url,
+            "next",
+            String::from("{\"Next\": {\"next_time\" : 1.0}}"),
+        );
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let res = send_request_text(&url, "continue", String::from("{}"));
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        // Finalizing ends the window well before the keep alive time.
+        let res = send_request(&url, "finalize", &json!({}));
+        assert_eq!(res, json!({}));
+        ctx_thread.join().unwrap();
+
+        // The server has exited.
+        let client = reqwest::blocking::Client::new();
+        assert!(client
+            .post(format!("{url}cmd/population"))
+            .json(&json!({}))
+            .send()
+            .is_err());
+    }
+
+    #[test]
+    fn web_api_completes_requests_in_flight() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let (go_tx, go_rx) = std::sync::mpsc::channel::<()>();
+        let ctx_thread = thread::spawn(move || {
+            let mut context = Context::new();
+            let url = context.setup_web_api(33341).unwrap();
+            let handle = context.web_api_shutdown_handle().unwrap();
+            context.add_person((Age, 1)).unwrap();
+            context.add_person((Age, 2)).unwrap();
+            let _ = tx.send((url, handle));
+            // Keep the simulation busy while the request comes in.
+            context.add_plan(1.0, move |_| {
+                go_rx.recv().unwrap();
+            });
+            context.execute();
+            context.finish_web_api(Duration::from_secs(30));
+        });
+
+        let (url, handle) = rx.recv().unwrap();
+        let request_url = url.clone();
+        let request = thread::spawn(move || send_request(&request_url, "population", &json!({})));
+        thread::sleep(Duration::from_millis(200));
+
+        // Shut down while the request is waiting for the simulation.
+        assert!(!handle.is_shutdown());
+        handle.shutdown();
+        assert!(handle.is_shutdown());
+        go_tx.send(()).unwrap();
+
+        assert_eq!(request.join().unwrap(), json!({"population": 2}));
+        ctx_thread.join().unwrap();
+    }
 }
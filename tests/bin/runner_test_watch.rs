@@ -0,0 +1,20 @@
+use ixa::runner::run_with_args;
+use ixa::{define_person_property_with_default, ContextPeopleExt};
+
+define_person_property_with_default!(Recovered, bool, false);
+
+fn main() {
+    run_with_args(|context, _args, _| {
+        let people: Vec<_> = (0..3)
+            .map(|_| context.add_person((Recovered, false)).unwrap())
+            .collect();
+        for (t, person) in [(1.0, people[2]), (2.0, people[1])] {
+            context.add_plan(t, move |context| {
+                context.set_person_property(person, Recovered, true);
+            });
+        }
+
+        Ok(())
+    })
+    .unwrap();
+}
@@ -0,0 +1,387 @@
+//! Checks that queries give the same answers when a property has a custom
+//! index as when it doesn't, and that the query planner only calls the
+//! parts of a custom index it says it supports.
+use ixa::people::{FullIndex, InRange, PropertyIndexImpl, ValueCountIndex};
+use ixa::random::ContextRandomExt;
+use ixa::{
+    define_derived_property, define_person_property, define_rng, Context, ContextPeopleExt,
+    PersonId,
+};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+define_rng!(IndexTestRng);
+
+define_person_property!(County, u8);
+define_person_property!(Age, u8);
+define_derived_property!(Adult, bool, [Age], |age| age >= 18);
+
+const COUNTIES: u8 = 4;
+
+// The people in each county, indexed by county.
+#[derive(Default)]
+struct CountyIndex {
+    people: Vec<HashSet<PersonId>>,
+    lookups: Rc<Cell<usize>>,
+    counts: Rc<Cell<usize>>,
+}
+
+impl CountyIndex {
+    fn new() -> Self {
+        CountyIndex {
+            people: (0..COUNTIES).map(|_| HashSet::new()).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl PropertyIndexImpl<County> for CountyIndex {
+    fn add_person(&mut self, person_id: PersonId, value: &u8) {
+        assert!(self.people[usize::from(*value)].insert(person_id));
+    }
+
+    fn remove_person(&mut self, person_id: PersonId, value: &u8) {
+        assert!(self.people[usize::from(*value)].remove(&person_id));
+    }
+
+    fn supports_set_lookup(&self) -> bool {
+        true
+    }
+
+    fn get_people(&self, value: &u8) -> Option<Vec<PersonId>> {
+        self.lookups.set(self.lookups.get() + 1);
+        Some(self.people[usize::from(*value)].iter().copied().collect())
+    }
+
+    fn supports_count(&self) -> bool {
+        true
+    }
+
+    fn get_count(&self, value: &u8) -> Option<usize> {
+        self.counts.set(self.counts.get() + 1);
+        Some(self.people[usize::from(*value)].len())
+    }
+}
+
+// Keeps count of each county but can't list the people in them.
+struct CountOnlyIndex {
+    inner: CountyIndex,
+}
+
+impl PropertyIndexImpl<County> for CountOnlyIndex {
+    fn add_person(&mut self, person_id: PersonId, value: &u8) {
+        self.inner.add_person(person_id, value);
+    }
+
+    fn remove_person(&mut self, person_id: PersonId, value: &u8) {
+        self.inner.remove_person(person_id, value);
+    }
+
+    fn supports_count(&self) -> bool {
+        true
+    }
+
+    fn get_count(&self, value: &u8) -> Option<usize> {
+        self.inner.get_count(value)
+    }
+}
+
+// Says it supports lookups and counts but can't provide them, so queries
+// check everyone.
+struct UnavailableIndex;
+
+impl PropertyIndexImpl<County> for UnavailableIndex {
+    fn add_person(&mut self, _person_id: PersonId, _value: &u8) {}
+
+    fn remove_person(&mut self, _person_id: PersonId, _value: &u8) {}
+
+    fn supports_set_lookup(&self) -> bool {
+        true
+    }
+
+    fn supports_count(&self) -> bool {
+        true
+    }
+}
+
+// Supports neither lookups nor counts, so queries check everyone.
+struct OpaqueIndex {
+    added: Rc<Cell<usize>>,
+}
+
+impl PropertyIndexImpl<County> for OpaqueIndex {
+    fn add_person(&mut self, _person_id: PersonId, _value: &u8) {
+        self.added.set(self.added.get() + 1);
+    }
+
+    fn remove_person(&mut self, _person_id: PersonId, _value: &u8) {
+        self.added.set(self.added.get() - 1);
+    }
+}
+
+// Ignores everyone, for checking that an indexed property can't be given a
+// custom index.
+struct AgeIndex;
+
+impl PropertyIndexImpl<Age> for AgeIndex {
+    fn add_person(&mut self, _person_id: PersonId, _value: &u8) {}
+
+    fn remove_person(&mut self, _person_id: PersonId, _value: &u8) {}
+}
+
+fn add_people(context: &mut Context, count: u8) {
+    for i in 0..count {
+        context
+            .add_person(((County, i % COUNTIES), (Age, i)))
+            .unwrap();
+    }
+}
+
+fn as_set(people: Vec<PersonId>) -> HashSet<PersonId> {
+    people.into_iter().collect()
+}
+
+// Runs `check` against a context with a custom county index and one
+// without, after the same changes to both.
+fn assert_same_results<R: PartialEq + Debug>(check: impl Fn(&Context) -> R) {
+    assert_same_results_with(|| Box::new(CountyIndex::new()), check);
+}
+
+// Like `assert_same_results()`, with the custom index made by `make_index`.
+fn assert_same_results_with<R: PartialEq + Debug>(
+    make_index: impl Fn() -> Box<dyn PropertyIndexImpl<County>>,
+    check: impl Fn(&Context) -> R,
+) {
+    let mut plain = Context::new();
+    let mut custom = Context::new();
+    plain.init_random(42);
+    custom.init_random(42);
+    custom.install_custom_index(County, make_index()).unwrap();
+    plain.index_property(Age);
+    custom.index_property(Age);
+
+    for context in [&mut plain, &mut custom] {
+        add_people(context, 40);
+    }
+    assert_eq!(check(&plain), check(&custom));
+
+    // Move some people to another county, then remove some.
+    for context in [&mut plain, &mut custom] {
+        for person_id in context.query_people((County, 1)) {
+            if context.get_person_property(person_id, Age) % 8 == 1 {
+                context.set_person_property(person_id, County, 3);
+            }
+        }
+        for person_id in context.query_people((Age, 6)) {
+            context.remove_person(person_id).unwrap();
+        }
+        context.execute();
+        add_people(context, 10);
+    }
+    assert_eq!(check(&plain), check(&custom));
+}
+
+#[test]
+fn single_property() {
+    assert_same_results(|context| {
+        (0..COUNTIES)
+            .map(|county| as_set(context.query_people((County, county))))
+            .collect::<Vec<_>>()
+    });
+}
+
+#[test]
+fn with_builtin_index() {
+    assert_same_results(|context| {
+        vec![
+            as_set(context.query_people(((County, 2), (Age, 6)))),
+            as_set(context.query_people(((County, 2), (Age, 7)))),
+            as_set(context.query_people(((County, 3), (Adult, true)))),
+        ]
+    });
+}
+
+#[test]
+fn value_filters() {
+    assert_same_results(|context| {
+        vec![
+            as_set(context.query_people_any_value(County, &[0, 3])),
+            as_set(context.query_people((InRange(Age, 10..30), (County, 1)))),
+        ]
+    });
+}
+
+#[test]
+fn counts_and_sampling() {
+    assert_same_results(|context| {
+        for county in 0..COUNTIES {
+            let sampled = context
+                .sample_person(IndexTestRng, (County, county))
+                .unwrap()
+                .unwrap();
+            assert_eq!(context.get_person_property(sampled, County), county);
+        }
+        let counts = (0..COUNTIES)
+            .map(|county| context.query_people_count((County, county)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts.iter().sum::<usize>(),
+            context.get_current_population()
+        );
+        counts
+    });
+}
+
+// The results of all the kinds of queries above
+fn all_queries(context: &Context) -> Vec<HashSet<PersonId>> {
+    let mut results: Vec<HashSet<PersonId>> = (0..COUNTIES)
+        .map(|county| as_set(context.query_people((County, county))))
+        .collect();
+    results.extend([
+        as_set(context.query_people(((County, 2), (Age, 6)))),
+        as_set(context.query_people(((County, 3), (Adult, true)))),
+        as_set(context.query_people_any_value(County, &[0, 3])),
+        as_set(context.query_people((InRange(Age, 10..30), (County, 1)))),
+    ]);
+    for county in 0..COUNTIES {
+        let count = context.query_people_count((County, county));
+        assert_eq!(count, results[usize::from(county)].len());
+        let sampled = context
+            .sample_person(IndexTestRng, (County, county))
+            .unwrap()
+            .unwrap();
+        assert_eq!(context.get_person_property(sampled, County), county);
+    }
+    results
+}
+
+#[test]
+fn full_index_implementation() {
+    assert_same_results_with(|| Box::new(FullIndex::<County>::new()), all_queries);
+}
+
+#[test]
+fn value_count_index_implementation() {
+    assert_same_results_with(|| Box::new(ValueCountIndex::<County>::new()), all_queries);
+}
+
+#[test]
+fn unavailable_lookups_fall_back_to_scanning() {
+    assert_same_results_with(|| Box::new(UnavailableIndex), all_queries);
+}
+
+#[test]
+fn absent_value_returns_early() {
+    let mut context = Context::new();
+    context.init_random(42);
+    let index = CountyIndex::new();
+    let lookups = Rc::clone(&index.lookups);
+    context
+        .install_custom_index(County, Box::new(index))
+        .unwrap();
+    context.add_person(((County, 0), (Age, 1))).unwrap();
+
+    assert!(context.query_people((County, 2)).is_empty());
+    assert_eq!(context.query_people_count(((County, 2), (Age, 1))), 0);
+    assert_eq!(
+        context.sample_person(IndexTestRng, (County, 2)).unwrap(),
+        None
+    );
+    assert_eq!(lookups.get(), 0);
+}
+
+#[test]
+fn smaller_builtin_index_drives_query() {
+    let mut context = Context::new();
+    let index = CountyIndex::new();
+    let lookups = Rc::clone(&index.lookups);
+    let counts = Rc::clone(&index.counts);
+    context
+        .install_custom_index(County, Box::new(index))
+        .unwrap();
+    context.index_property(Age);
+    add_people(&mut context, 40);
+
+    // Only one person is 5, so the built-in age index is narrower.
+    assert_eq!(context.query_people(((County, 1), (Age, 5))).len(), 1);
+    assert_eq!(lookups.get(), 0);
+    assert!(counts.get() > 0);
+
+    // Without another index the custom one is used.
+    assert_eq!(context.query_people((County, 1)).len(), 10);
+    assert_eq!(lookups.get(), 1);
+}
+
+#[test]
+fn count_only_index() {
+    let mut context = Context::new();
+    context
+        .install_custom_index(
+            County,
+            Box::new(CountOnlyIndex {
+                inner: CountyIndex::new(),
+            }),
+        )
+        .unwrap();
+    add_people(&mut context, 40);
+
+    assert_eq!(context.query_people_count((County, 1)), 10);
+    assert_eq!(context.query_people(((County, 2), (Age, 6))).len(), 1);
+    assert!(context.query_people(((County, 2), (Age, 7))).is_empty());
+}
+
+#[test]
+fn opaque_index() {
+    let mut context = Context::new();
+    let added = Rc::new(Cell::new(0));
+    context
+        .install_custom_index(
+            County,
+            Box::new(OpaqueIndex {
+                added: Rc::clone(&added),
+            }),
+        )
+        .unwrap();
+    add_people(&mut context, 40);
+
+    assert_eq!(context.query_people_count((County, 1)), 10);
+    assert_eq!(added.get(), 40);
+    let person_id = context.query_people((Age, 3))[0];
+    context.remove_person(person_id).unwrap();
+    context.execute();
+    assert_eq!(added.get(), 39);
+    assert_eq!(context.query_people_count((County, 3)), 9);
+}
+
+#[test]
+fn install_twice_is_an_error() {
+    let mut context = Context::new();
+    context
+        .install_custom_index(County, Box::new(CountyIndex::new()))
+        .unwrap();
+    assert!(context
+        .install_custom_index(County, Box::new(CountyIndex::new()))
+        .is_err());
+
+    context.index_property(Age);
+    assert!(context
+        .install_custom_index(Age, Box::new(AgeIndex))
+        .is_err());
+}
+
+#[test]
+fn index_property_after_install_keeps_custom_index() {
+    let mut context = Context::new();
+    let index = CountyIndex::new();
+    let lookups = Rc::clone(&index.lookups);
+    context
+        .install_custom_index(County, Box::new(index))
+        .unwrap();
+    context.index_property(County);
+    add_people(&mut context, 8);
+
+    assert_eq!(context.query_people((County, 1)).len(), 2);
+    assert_eq!(lookups.get(), 1);
+}
@@ -0,0 +1,115 @@
+//! Golden-output tests for the examples.
+//!
+//! Each example that writes reports has a `golden` directory holding a
+//! small deterministic configuration (if it takes one) and the reports it
+//! is expected to produce. The example includes this module in its tests
+//! and calls [`check_goldens()`] with a function that runs the model with
+//! its reports written to a temporary directory:
+//!
+//! ```ignore
+//! #[cfg(test)]
+//! #[path = "../../tests/golden/mod.rs"]
+//! mod golden;
+//!
+//! #[test]
+//! fn golden_output() {
+//!     golden::check_goldens(&golden::golden_dir(file!()), |output_dir| {
+//!         let mut context = initialize(output_dir).unwrap();
+//!         context.execute();
+//!     });
+//! }
+//! ```
+//!
+//! The examples are run with `cargo test --examples`. When a change to
+//! the output is intended, regenerate the goldens with
+//! `UPDATE_GOLDENS=1 cargo test --examples` and commit the new files.
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Set this environment variable to `1` to overwrite the goldens with the
+/// current output instead of comparing against them.
+pub const UPDATE_GOLDENS: &str = "UPDATE_GOLDENS";
+
+/// Returns the `golden` directory next to the example's source file; pass
+/// it `file!()`.
+pub fn golden_dir(source_file: &str) -> PathBuf {
+    Path::new(source_file).parent().unwrap().join("golden")
+}
+
+fn report_files(dir: &Path) -> BTreeSet<String> {
+    fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("Can't read {}: {e}", dir.display()))
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".csv"))
+        .collect()
+}
+
+// Describes the first difference between two files, or returns `None` if
+// they're the same.
+fn first_difference(expected: &str, actual: &str) -> Option<String> {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    for (i, (e, a)) in expected_lines.iter().zip(&actual_lines).enumerate() {
+        if e != a {
+            return Some(format!(
+                "line {} differs\n  expected: {e}\n  actual:   {a}",
+                i + 1
+            ));
+        }
+    }
+    if expected_lines.len() == actual_lines.len() {
+        None
+    } else {
+        Some(format!(
+            "expected {} lines but got {}",
+            expected_lines.len(),
+            actual_lines.len()
+        ))
+    }
+}
+
+/// Runs `run` with a temporary output directory and compares the reports
+/// it writes there with the `.csv` files in `golden_dir`.
+///
+/// # Panics
+/// Panics with a description of each difference if the reports don't
+/// match the goldens.
+pub fn check_goldens(golden_dir: &Path, run: impl FnOnce(&Path)) {
+    let output_dir = tempfile::tempdir().unwrap();
+    run(output_dir.path());
+    let actual_files = report_files(output_dir.path());
+
+    if std::env::var(UPDATE_GOLDENS).is_ok_and(|value| value == "1") {
+        fs::create_dir_all(golden_dir).unwrap();
+        for name in report_files(golden_dir).difference(&actual_files) {
+            fs::remove_file(golden_dir.join(name)).unwrap();
+        }
+        for name in &actual_files {
+            fs::copy(output_dir.path().join(name), golden_dir.join(name)).unwrap();
+        }
+        return;
+    }
+
+    let expected_files = report_files(golden_dir);
+    let mut problems = Vec::new();
+    for name in expected_files.difference(&actual_files) {
+        problems.push(format!("{name}: expected but not written"));
+    }
+    for name in actual_files.difference(&expected_files) {
+        problems.push(format!("{name}: written but has no golden"));
+    }
+    for name in expected_files.intersection(&actual_files) {
+        let expected = fs::read_to_string(golden_dir.join(name)).unwrap();
+        let actual = fs::read_to_string(output_dir.path().join(name)).unwrap();
+        if let Some(difference) = first_difference(&expected, &actual) {
+            problems.push(format!("{name}: {difference}"));
+        }
+    }
+    assert!(
+        problems.is_empty(),
+        "Output doesn't match the goldens in {}:\n{}\nIf the change is intended, rerun with {UPDATE_GOLDENS}=1",
+        golden_dir.display(),
+        problems.join("\n")
+    );
+}
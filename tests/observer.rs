@@ -0,0 +1,8 @@
+//! Checks that observers can't change the simulation: each of the programs
+//! in `tests/ui/observer` tries to through a `ReadOnlyContext` and must
+//! fail to compile.
+#[test]
+fn observers_cannot_mutate() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/observer/*.rs");
+}
@@ -0,0 +1,119 @@
+//! Checks that a small model using the portable generator and samplers in
+//! `ixa::random::portable` produces exactly the same results as when the
+//! golden digest below was recorded. CI runs this on each platform, so a
+//! failure on one of them means something in the model's path isn't
+//! portable. If a change to Ixa is meant to change the results, update the
+//! digest and note it in the release notes.
+use ixa::random::{portable, ContextRandomExt, PortableRng, RngId};
+use ixa::{define_person_property_with_default, Context, ContextPeopleExt, PersonId};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// This is what `define_rng!` defines when the `portable-rng` feature is
+// enabled, written out so the test doesn't need the feature.
+#[derive(Copy, Clone)]
+struct ModelRng;
+
+impl RngId for ModelRng {
+    type RngType = PortableRng;
+
+    fn get_name() -> &'static str {
+        "ModelRng"
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InfectionStatusValue {
+    Susceptible,
+    Infected,
+    Recovered,
+}
+
+define_person_property_with_default!(
+    InfectionStatus,
+    InfectionStatusValue,
+    InfectionStatusValue::Susceptible
+);
+
+const POPULATION: usize = 200;
+const CONTACT_RATE: f64 = 1.5;
+const MEAN_INFECTIOUS_PERIOD: f64 = 3.0;
+
+// The 64-bit FNV-1a hash, which is fully specified.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// The people in the model and the time each infected person recovers.
+struct Model {
+    people: Vec<PersonId>,
+    recoveries: Vec<(f64, usize)>,
+}
+
+fn infect(context: &mut Context, model: &Rc<RefCell<Model>>, index: usize) {
+    let person = model.borrow().people[index];
+    context.set_person_property(person, InfectionStatus, InfectionStatusValue::Infected);
+    // The infectious period is log-normally distributed, using the
+    // portable normal sampler and libm rather than f64::ln() and exp().
+    let log_period = context.sample(ModelRng, |rng| {
+        portable::normal(rng, libm::log(MEAN_INFECTIOUS_PERIOD), 0.5)
+    });
+    let end = context.get_current_time() + libm::exp(log_period);
+    model.borrow_mut().recoveries.push((end, index));
+
+    context.add_plan(end, move |context| {
+        context.set_person_property(person, InfectionStatus, InfectionStatusValue::Recovered);
+    });
+    schedule_contact(context, Rc::clone(model), end);
+}
+
+fn schedule_contact(context: &mut Context, model: Rc<RefCell<Model>>, end: f64) {
+    let time = context.get_current_time()
+        + context.sample(ModelRng, |rng| portable::exponential(rng, CONTACT_RATE));
+    if time >= end {
+        return;
+    }
+    context.add_plan(time, move |context| {
+        let index = context.sample_range(ModelRng, 0..POPULATION);
+        let contact = model.borrow().people[index];
+        if context.get_person_property(contact, InfectionStatus)
+            == InfectionStatusValue::Susceptible
+            && context.sample_bool(ModelRng, 0.4)
+        {
+            infect(context, &model, index);
+        }
+        schedule_contact(context, model, end);
+    });
+}
+
+#[test]
+fn golden_model_digest() {
+    let mut context = Context::new();
+    context.init_random(2025);
+    let people = (0..POPULATION)
+        .map(|_| context.add_person(()).unwrap())
+        .collect();
+    let model = Rc::new(RefCell::new(Model {
+        people,
+        recoveries: Vec::new(),
+    }));
+    let first = context.sample_range(ModelRng, 0..POPULATION);
+    infect(&mut context, &model, first);
+    context.execute();
+
+    let recoveries = &model.borrow().recoveries;
+    assert_eq!(
+        context.query_people_count((InfectionStatus, InfectionStatusValue::Recovered)),
+        recoveries.len()
+    );
+    let digest = recoveries
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, (time, index)| {
+            let hash = fnv1a(hash, &time.to_bits().to_le_bytes());
+            fnv1a(hash, &(*index as u64).to_le_bytes())
+        });
+    assert_eq!((recoveries.len(), digest), (153, 7_879_017_081_904_618_209));
+}
@@ -0,0 +1,7 @@
+use ixa::{Context, ContextPeopleExt};
+
+fn main() {
+    let context = Context::new();
+    let view = context.as_read_only();
+    view.add_person(()).unwrap();
+}
@@ -0,0 +1,8 @@
+use ixa::Context;
+
+fn main() {
+    let mut context = Context::new();
+    context.add_observer_plan(1.0, |context| {
+        context.add_plan(2.0, |_| {});
+    });
+}
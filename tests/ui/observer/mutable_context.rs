@@ -0,0 +1,8 @@
+use ixa::{Context, ContextPeopleExt, PersonCreatedEvent};
+
+fn main() {
+    let mut context = Context::new();
+    context.subscribe_observer(|context: &mut Context, _: &PersonCreatedEvent| {
+        context.add_person(()).unwrap();
+    });
+}
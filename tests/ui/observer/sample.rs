@@ -0,0 +1,11 @@
+use ixa::{define_rng, Context, ContextRandomExt};
+
+define_rng!(ObserverRng);
+
+fn main() {
+    let mut context = Context::new();
+    context.init_random(42);
+    context.add_observer_plan(1.0, |context| {
+        let _: bool = context.sample_bool(ObserverRng, 0.5);
+    });
+}
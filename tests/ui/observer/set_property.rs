@@ -0,0 +1,10 @@
+use ixa::{define_person_property, Context, ContextPeopleExt, PersonCreatedEvent};
+
+define_person_property!(Age, u8);
+
+fn main() {
+    let mut context = Context::new();
+    context.subscribe_observer(|context, event: &PersonCreatedEvent| {
+        context.set_person_property(event.person_id, Age, 0);
+    });
+}